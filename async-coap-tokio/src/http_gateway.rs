@@ -0,0 +1,371 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! A [Hyper][]-based cross-protocol proxy between CoAP and HTTP, as described in
+//! [IETF-RFC8075].
+//!
+//! * [`CoapToHttpGateway`] handles inbound CoAP proxy requests by forwarding them to an
+//!   HTTP server.
+//! * [`HttpToCoapGateway`] handles inbound HTTP requests by forwarding them to a CoAP
+//!   server using a [`LocalEndpoint`].
+//!
+//! The message translation itself is done by [`async_coap::http_gateway`].
+//!
+//! This module is only available when the `http-gateway` feature is enabled.
+//!
+//! [Hyper]: https://hyper.rs/
+//! [IETF-RFC8075]: https://tools.ietf.org/html/rfc8075
+
+use async_coap::http_gateway::*;
+use async_coap::message::MessageWrite;
+use async_coap::prelude::*;
+use async_coap::send_desc::SendDesc;
+use async_coap::{Error, InboundContext, LocalEndpoint, RespondableInboundContext};
+use futures::prelude::*;
+use hyper::client::HttpConnector;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Client, Request, Response, Server, StatusCode};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::ops::Bound;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::runtime::Handle;
+
+/// The default amount of time to wait for the HTTP server to respond.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Reads the entire body of `response` into memory.
+async fn collect_response(response: Response<Body>) -> Result<Response<Vec<u8>>, hyper::Error> {
+    let (parts, body) = response.into_parts();
+    let body = hyper::body::to_bytes(body).await?;
+    Ok(Response::from_parts(parts, body.to_vec()))
+}
+
+/// Forwards inbound CoAP proxy requests to HTTP servers.
+///
+/// Since HTTP servers may take a while to respond, [`CoapToHttpGateway::handle`] doesn't
+/// wait for them: it acknowledges each proxied request with an empty acknowledgement and
+/// performs the HTTP request on the given runtime, sending the translated HTTP response
+/// to the client as a separate response once it arrives. This keeps the receive loop
+/// free to handle other requests in the meantime.
+///
+/// ```no_run
+/// use async_coap::prelude::*;
+/// use async_coap::datagram::DatagramLocalEndpoint;
+/// use async_coap_tokio::http_gateway::CoapToHttpGateway;
+/// use async_coap_tokio::TokioAsyncUdpSocket;
+/// use futures::prelude::*;
+/// use std::sync::Arc;
+/// use tokio::runtime::Handle;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let socket = TokioAsyncUdpSocket::bind("[::]:5683").expect("UDP bind failed");
+///     let local_endpoint = Arc::new(DatagramLocalEndpoint::new(socket));
+///     let gateway = Arc::new(CoapToHttpGateway::new(
+///         local_endpoint.clone(),
+///         Handle::current(),
+///     ));
+///
+///     local_endpoint
+///         .receive_loop_arc(move |context| gateway.handle(context))
+///         .map(|err| panic!("Receive loop terminated: {}", err))
+///         .await;
+/// }
+/// ```
+#[derive(Debug)]
+pub struct CoapToHttpGateway<LE> {
+    local_endpoint: Arc<LE>,
+    runtime: Handle,
+    client: Client<HttpConnector>,
+    timeout: Duration,
+}
+
+impl<LE> CoapToHttpGateway<LE>
+where
+    LE: LocalEndpoint + Send + Sync + 'static,
+{
+    /// Creates a new `CoapToHttpGateway` which performs HTTP requests on `runtime` and
+    /// sends the separate responses to them using `local_endpoint`.
+    pub fn new(local_endpoint: Arc<LE>, runtime: Handle) -> CoapToHttpGateway<LE> {
+        CoapToHttpGateway {
+            local_endpoint,
+            runtime,
+            client: Client::new(),
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Sets the amount of time to wait for the HTTP server to respond before responding
+    /// to the CoAP request with [`MsgCode::ServerErrorGatewayTimeout`].
+    pub fn with_timeout(mut self, timeout: Duration) -> CoapToHttpGateway<LE> {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Handles an inbound CoAP request by forwarding it to the HTTP server identified by
+    /// its `Proxy-Uri` or `Proxy-Scheme` option, sending the translated HTTP response as
+    /// a separate response.
+    ///
+    /// Requests which cannot be proxied (including requests without any proxy options)
+    /// are answered immediately with an appropriate error code, typically
+    /// [`MsgCode::ServerErrorProxyingNotSupported`]. Since only confirmable requests
+    /// can be acknowledged ahead of their response, non-confirmable requests are
+    /// answered with [`MsgCode::ServerErrorServiceUnavailable`]. Messages which aren't
+    /// requests are ignored.
+    ///
+    /// This method is intended to be used as (or called from) the handler passed to
+    /// [`LocalEndpoint::receive`].
+    pub fn handle(&self, context: &LE::RespondableInboundContext) -> Result<(), Error> {
+        let msg = context.message();
+        let method = msg.msg_code();
+
+        if !method.is_method() {
+            return Ok(());
+        }
+
+        let request = match http_request_from_coap(msg) {
+            Ok(request) => request.map(Body::from),
+            Err(code) => return context.respond_error(code, ""),
+        };
+
+        if !msg.msg_type().is_con() {
+            return context.respond_error(MsgCode::ServerErrorServiceUnavailable, "");
+        }
+
+        context.acknowledge()?;
+
+        // The response to the first copy of this request is already on its way.
+        if context.is_dupe() {
+            return Ok(());
+        }
+
+        let local_endpoint = self.local_endpoint.clone();
+        let remote_addr = context.remote_socket_addr();
+        let msg_token = msg.msg_token();
+        let response = self.perform(request);
+
+        self.runtime.spawn(async move {
+            let send_desc = SeparateResponse {
+                msg_token,
+                method,
+                response: response.await,
+            };
+
+            // There is nobody left to report a failure to at this point.
+            let _ = local_endpoint.send(remote_addr, send_desc).await;
+        });
+
+        Ok(())
+    }
+
+    /// Returns a future which performs the given HTTP request, translating failures into
+    /// the message codes to respond with.
+    fn perform(
+        &self,
+        request: Request<Body>,
+    ) -> impl Future<Output = Result<Response<Vec<u8>>, MsgCode>> {
+        let client = self.client.clone();
+        let timeout = self.timeout;
+
+        async move {
+            let response = tokio::time::timeout(timeout, async move {
+                collect_response(client.request(request).await?).await
+            });
+
+            match response.await {
+                Ok(Ok(response)) => Ok(response),
+                Ok(Err(_)) => Err(MsgCode::ServerErrorBadGateway),
+                Err(_elapsed) => Err(MsgCode::ServerErrorGatewayTimeout),
+            }
+        }
+    }
+}
+
+/// Send descriptor for the confirmable separate response to a proxied request.
+struct SeparateResponse {
+    msg_token: MsgToken,
+    method: MsgCode,
+    response: Result<Response<Vec<u8>>, MsgCode>,
+}
+
+impl<IC: InboundContext> SendDesc<IC> for SeparateResponse {
+    fn write_options(
+        &self,
+        _msg: &mut dyn OptionInsert,
+        _socket_addr: &IC::SocketAddr,
+        _start: Bound<OptionNumber>,
+        _end: Bound<OptionNumber>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn write_payload(
+        &self,
+        msg: &mut dyn MessageWrite,
+        _socket_addr: &IC::SocketAddr,
+    ) -> Result<(), Error> {
+        msg.set_msg_type(MsgType::Con);
+        msg.set_msg_token(self.msg_token);
+
+        match &self.response {
+            Ok(response) => write_coap_response(self.method, response, msg),
+            Err(code) => {
+                msg.set_msg_code(*code);
+                Ok(())
+            }
+        }
+    }
+
+    fn handler(&mut self, context: Result<&IC, Error>) -> Result<ResponseStatus<()>, Error> {
+        if context?.message().msg_type().is_res() {
+            Err(Error::Reset)
+        } else {
+            Ok(ResponseStatus::Done(()))
+        }
+    }
+}
+
+/// Forwards inbound HTTP requests to CoAP servers.
+///
+/// The CoAP URI is extracted from the path of the HTTP request using the default URI
+/// mapping template from [IETF-RFC8075 Section 5.3], so a request for
+/// `http://gateway/hc/coap://example.com/a` is forwarded to `coap://example.com/a`.
+/// The prefix can be changed using [`HttpToCoapGateway::with_prefix`].
+///
+/// ```no_run
+/// use async_coap::prelude::*;
+/// use async_coap::datagram::DatagramLocalEndpoint;
+/// use async_coap_tokio::http_gateway::HttpToCoapGateway;
+/// use async_coap_tokio::TokioAsyncUdpSocket;
+/// use futures::prelude::*;
+/// use std::sync::Arc;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let socket = TokioAsyncUdpSocket::bind("[::]:0").expect("UDP bind failed");
+///     let local_endpoint = Arc::new(DatagramLocalEndpoint::new(socket));
+///
+///     tokio::spawn(
+///         local_endpoint
+///             .clone()
+///             .receive_loop_arc(null_receiver!())
+///             .map(|err| panic!("Receive loop terminated: {}", err)),
+///     );
+///
+///     HttpToCoapGateway::new(local_endpoint)
+///         .serve(([0, 0, 0, 0], 8080).into())
+///         .await
+///         .expect("HTTP server failed");
+/// }
+/// ```
+///
+/// [IETF-RFC8075 Section 5.3]: https://tools.ietf.org/html/rfc8075#section-5.3
+#[derive(Debug)]
+pub struct HttpToCoapGateway<LE> {
+    local_endpoint: Arc<LE>,
+    prefix: String,
+}
+
+impl<LE> Clone for HttpToCoapGateway<LE> {
+    fn clone(&self) -> Self {
+        HttpToCoapGateway {
+            local_endpoint: self.local_endpoint.clone(),
+            prefix: self.prefix.clone(),
+        }
+    }
+}
+
+impl<LE> HttpToCoapGateway<LE>
+where
+    LE: LocalEndpoint + Send + Sync + 'static,
+    LE::RemoteEndpoint: Send + Sync,
+{
+    /// Creates a new `HttpToCoapGateway` which sends CoAP requests using `local_endpoint`.
+    pub fn new(local_endpoint: Arc<LE>) -> HttpToCoapGateway<LE> {
+        HttpToCoapGateway {
+            local_endpoint,
+            prefix: DEFAULT_HC_PREFIX.to_string(),
+        }
+    }
+
+    /// Changes the path prefix that HTTP requests must start with.
+    /// The default is [`DEFAULT_HC_PREFIX`].
+    pub fn with_prefix<S: Into<String>>(mut self, prefix: S) -> HttpToCoapGateway<LE> {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Forwards a single HTTP request to the CoAP server it identifies, returning the
+    /// translated CoAP response.
+    pub async fn forward(&self, request: Request<Body>) -> Response<Body> {
+        self.forward_collected(request).await.map(Body::from)
+    }
+
+    async fn forward_collected(&self, request: Request<Body>) -> Response<Vec<u8>> {
+        let status_response = |status| {
+            let mut response = Response::new(Vec::new());
+            *response.status_mut() = status;
+            response
+        };
+
+        let (parts, body) = request.into_parts();
+        let body = match hyper::body::to_bytes(body).await {
+            Ok(body) => body.to_vec(),
+            Err(_) => return status_response(StatusCode::BAD_REQUEST),
+        };
+        let request = Request::from_parts(parts, body);
+
+        let uri = match coap_uri_from_http_target(request.uri(), &self.prefix) {
+            Some(uri) => uri,
+            None => return status_response(StatusCode::NOT_FOUND),
+        };
+
+        let send_desc = match HttpRequestDesc::from_http_request(&request) {
+            Ok(send_desc) => send_desc,
+            Err(status) => return status_response(status),
+        };
+
+        let (base, path) = uri.split();
+
+        let remote_endpoint = match self.local_endpoint.remote_endpoint_from_uri(base) {
+            Ok(remote_endpoint) => remote_endpoint,
+            Err(_) => return bad_gateway_response(),
+        };
+
+        match remote_endpoint.send_to(path, send_desc).await {
+            Ok(msg) => http_response_from_coap(&msg),
+            Err(Error::ResponseTimeout) => status_response(StatusCode::GATEWAY_TIMEOUT),
+            Err(_) => bad_gateway_response(),
+        }
+    }
+
+    /// Serves HTTP requests on `addr` until an error occurs, forwarding each of them
+    /// using [`HttpToCoapGateway::forward`].
+    pub async fn serve(self, addr: SocketAddr) -> Result<(), hyper::Error> {
+        let make_service = make_service_fn(move |_| {
+            let gateway = self.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let gateway = gateway.clone();
+                    async move { Ok::<_, Infallible>(gateway.forward(request).await) }
+                }))
+            }
+        });
+
+        Server::bind(&addr).serve(make_service).await
+    }
+}
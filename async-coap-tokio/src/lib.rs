@@ -52,7 +52,7 @@
 //!
 //!     // Create a future that sends a request to a specific path
 //!     // on the remote endpoint, collecting any blocks in the response
-//!     // and returning `Ok(OwnedImmutableMessage)` upon success.
+//!     // and returning `Ok(Response)` upon success.
 //!     let future = remote_endpoint.send_to(
 //!         rel_ref!("large"),
 //!         CoapRequest::get() // This is a CoAP GET request
@@ -74,3 +74,6 @@
 
 mod tokio_async_udp_socket;
 pub use tokio_async_udp_socket::TokioAsyncUdpSocket;
+
+#[cfg(feature = "http-gateway")]
+pub mod http_gateway;
@@ -13,16 +13,20 @@
 // limitations under the License.
 //
 
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use async_coap::datagram::UnreachableReports;
 use async_coap::datagram::{
     AsyncDatagramSocket, AsyncRecvFrom, AsyncSendTo, DatagramSocketTypes, MulticastSocket,
 };
+use async_coap::TrafficClass;
 use futures::task::Context;
 use futures::{ready, task::Poll};
-use mio::net::UdpSocket;
 use std::net::{SocketAddr, SocketAddrV4, SocketAddrV6, ToSocketAddrs};
 use std::ops::Deref;
 use std::pin::Pin;
-use tokio::io::PollEvented;
+use std::sync::Mutex;
+use tokio::io::ReadBuf;
+use tokio::net::UdpSocket;
 
 /// An asynchronous [`AsyncDatagramSocket`] wrapper around [`std::net::UdpSocket`] that
 /// uses [Tokio][] for the event loop.
@@ -30,12 +34,32 @@ use tokio::io::PollEvented;
 /// This type differs from [`AllowUdpSocket`] in that it provides a real asynchronous,
 /// event-driven interface instead of faking one.
 ///
-/// In order to use this type, you must be using [Tokio][] for your event loop.
+/// In order to use this type, you must be using [Tokio][] for your event loop, and instances
+/// must be created from within the context of a Tokio runtime.
+///
+/// Datagrams sent with [`AsyncSendTo::poll_send_to_with_traffic_class`] are marked on Linux,
+/// Android, macOS, iOS, and FreeBSD. Elsewhere, they are sent unmarked.
+///
+/// On Linux and Android, destinations which the operating system reports as unreachable
+/// are returned by [`AsyncDatagramSocket::take_unreachable`], so that transactions with
+/// them fail right away. Elsewhere, such transactions time out.
 ///
 /// [`AllowUdpSocket`]: async-coap::datagram::AllowUdpSocket
 /// [Tokio]: https://tokio.rs/
 #[derive(Debug)]
-pub struct TokioAsyncUdpSocket(PollEvented<UdpSocket>);
+pub struct TokioAsyncUdpSocket {
+    socket: UdpSocket,
+
+    // A handle to the same socket that bypasses Tokio's readiness tracking.
+    // See `poll_send_to` for why we need this.
+    sender: std::net::UdpSocket,
+
+    // The marking which is currently set on the socket.
+    traffic_class: Mutex<TrafficClass>,
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    unreachable: UnreachableReports,
+}
 
 impl TokioAsyncUdpSocket {
     /// Analog of [`std::net::UdpSocket::bind`] for [`TokioAsyncUdpSocket`].
@@ -58,27 +82,113 @@ impl TokioAsyncUdpSocket {
     }
 
     /// Upgrades a [`std::net::UdpSocket`] by wrapping it in a [`TokioAsyncUdpSocket`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of the context of a Tokio runtime.
     pub fn from_std(udp_socket: std::net::UdpSocket) -> TokioAsyncUdpSocket {
         udp_socket.set_nonblocking(true).unwrap();
-        Self::from_mio(mio::net::UdpSocket::from_socket(udp_socket).expect("Unbound socket"))
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            if let Ok(local_addr) = udp_socket.local_addr() {
+                let _ = UnreachableReports::enable(&udp_socket, local_addr);
+            }
+        }
+
+        TokioAsyncUdpSocket {
+            sender: udp_socket.try_clone().expect("Unable to clone UDP socket"),
+            socket: UdpSocket::from_std(udp_socket).expect("Async UDP socket"),
+            traffic_class: Mutex::new(TrafficClass::DEFAULT),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            unreachable: UnreachableReports::new(),
+        }
     }
 
-    /// Wraps a [`mio::net::UdpSocket`] instance with a [`TokioAsyncUdpSocket`].
-    pub(crate) fn from_mio(udp_socket: UdpSocket) -> TokioAsyncUdpSocket {
-        TokioAsyncUdpSocket(PollEvented::new(udp_socket).expect("Async UDP socket"))
+    /// Marks the datagrams subsequently sent from this socket with `traffic_class`.
+    ///
+    /// On IPv6 sockets, `IP_TOS` is also set on a best-effort basis, since it is what
+    /// applies to datagrams sent to IPv4-mapped addresses.
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd"
+    ))]
+    fn set_traffic_class(&self, traffic_class: TrafficClass) -> std::io::Result<()> {
+        let value = u32::from(traffic_class.octet());
+
+        match self.socket.local_addr()? {
+            SocketAddr::V4(_) => self.socket.set_tos_v4(value),
+            SocketAddr::V6(_) => {
+                let _ = self.socket.set_tos_v4(value);
+                self.set_tclass_v6(value)
+            }
+        }
+    }
+
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "freebsd"
+    ))]
+    fn set_tclass_v6(&self, value: u32) -> std::io::Result<()> {
+        self.socket.set_tclass_v6(value)
+    }
+
+    /// Tokio doesn't support `IPV6_TCLASS` on iOS, so it is set directly.
+    #[cfg(target_os = "ios")]
+    fn set_tclass_v6(&self, value: u32) -> std::io::Result<()> {
+        use std::os::unix::io::AsRawFd;
+
+        let value = value as libc::c_int;
+
+        // SAFETY: `value` outlives the call, and its size is passed along with it.
+        let ret = unsafe {
+            libc::setsockopt(
+                self.socket.as_raw_fd(),
+                libc::IPPROTO_IPV6,
+                libc::IPV6_TCLASS,
+                &value as *const libc::c_int as *const libc::c_void,
+                std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+
+        if ret == 0 {
+            Ok(())
+        } else {
+            Err(std::io::Error::last_os_error())
+        }
     }
-}
 
-impl Unpin for TokioAsyncUdpSocket {}
+    /// Marking isn't supported on this platform, so datagrams are always sent unmarked.
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd"
+    )))]
+    fn set_traffic_class(&self, _traffic_class: TrafficClass) -> std::io::Result<()> {
+        Ok(())
+    }
+}
 
-impl AsyncDatagramSocket for TokioAsyncUdpSocket {}
+impl AsyncDatagramSocket for TokioAsyncUdpSocket {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn take_unreachable(&self) -> Option<Self::SocketAddr> {
+        self.unreachable.take()
+    }
+}
 
 impl DatagramSocketTypes for TokioAsyncUdpSocket {
     type SocketAddr = std::net::SocketAddr;
     type Error = std::io::Error;
 
     fn local_addr(&self) -> Result<Self::SocketAddr, Self::Error> {
-        self.0.get_ref().local_addr()
+        self.socket.local_addr()
     }
 
     fn lookup_host(
@@ -129,17 +239,37 @@ impl AsyncSendTo for TokioAsyncUdpSocket {
     where
         B: async_coap::ToSocketAddrs<SocketAddr = Self::SocketAddr, Error = Self::Error>,
     {
-        // We are ignoring the return value of `poll_write_ready` here because
-        // it will pretty much always lie to us the first time it is called.
-        // Instead, since we know that the underlying socket is configured to
-        // be non-blocking, we trust it instead.
-        let _ = self.0.poll_write_ready(cx);
+        self.poll_send_to_with_traffic_class(cx, buf, addr, TrafficClass::DEFAULT)
+    }
 
+    fn poll_send_to_with_traffic_class<B>(
+        self: Pin<&Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+        addr: B,
+        traffic_class: TrafficClass,
+    ) -> Poll<Result<usize, Self::Error>>
+    where
+        B: async_coap::ToSocketAddrs<SocketAddr = Self::SocketAddr, Error = Self::Error>,
+    {
         if let Some(addr) = addr.to_socket_addrs()?.next() {
-            match self.0.get_ref().send_to(buf, &addr) {
+            // The lock is held while sending, so that the marking can't be changed by
+            // another send in the meantime.
+            let mut current = self.traffic_class.lock().expect("Lock failed");
+
+            if *current != traffic_class {
+                self.set_traffic_class(traffic_class)?;
+                *current = traffic_class;
+            }
+
+            // Tokio won't consider the socket to be writable until the reactor has
+            // had a chance to run, so the first call to `UdpSocket::poll_send_to` pretty
+            // much always returns `Poll::Pending`. Since we know that the underlying socket
+            // is non-blocking, we try sending right away instead and only fall back to
+            // waiting on the reactor if that would block.
+            match self.sender.send_to(buf, addr) {
                 Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                    self.0.clear_write_ready(cx)?;
-                    Poll::Pending
+                    self.socket.poll_send_to(cx, buf, addr)
                 }
                 x => Poll::Ready(x),
             }
@@ -158,18 +288,18 @@ impl AsyncRecvFrom for TokioAsyncUdpSocket {
         cx: &mut Context<'_>,
         buf: &mut [u8],
     ) -> Poll<Result<(usize, Self::SocketAddr, Option<Self::SocketAddr>), Self::Error>> {
-        ready!(self.0.poll_read_ready(cx, mio::Ready::readable()))?;
-
-        match self.0.get_ref().recv_from(buf) {
-            Ok((size, from)) => Poll::Ready(Ok((size, from, None))),
-            Err(e) => match e.kind() {
-                std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut => {
-                    self.0.clear_read_ready(cx, mio::Ready::readable())?;
-                    Poll::Pending
-                }
-                _ => Poll::Ready(Err(e)),
-            },
-        }
+        let mut read_buf = ReadBuf::new(buf);
+        let from = match ready!(self.socket.poll_recv_from(cx, &mut read_buf)) {
+            Ok(from) => from,
+            Err(e) => {
+                #[cfg(any(target_os = "linux", target_os = "android"))]
+                self.unreachable.read_error_queue(&self.socket);
+
+                return Poll::Ready(Err(e));
+            }
+        };
+
+        Poll::Ready(Ok((read_buf.filled().len(), from, None)))
     }
 }
 
@@ -177,7 +307,7 @@ impl Deref for TokioAsyncUdpSocket {
     type Target = UdpSocket;
 
     fn deref(&self) -> &Self::Target {
-        self.0.get_ref()
+        &self.socket
     }
 }
 
@@ -194,7 +324,7 @@ impl MulticastSocket for TokioAsyncUdpSocket {
             IpAddr::V4(addr) => {
                 let local_addr = local_sockaddr.ip();
                 if let IpAddr::V4(local_addr) = local_addr {
-                    self.join_multicast_v4(&addr, &local_addr)
+                    self.join_multicast_v4(addr, local_addr)
                 } else if let SocketAddr::V6(local_sockaddr) = local_sockaddr {
                     self.join_multicast_v6(&addr.to_ipv6_mapped(), local_sockaddr.scope_id())
                 } else {
@@ -224,7 +354,7 @@ impl MulticastSocket for TokioAsyncUdpSocket {
             IpAddr::V4(addr) => {
                 let local_addr = local_sockaddr.ip();
                 if let IpAddr::V4(local_addr) = local_addr {
-                    self.leave_multicast_v4(&addr, &local_addr)
+                    self.leave_multicast_v4(addr, local_addr)
                 } else if let SocketAddr::V6(local_sockaddr) = local_sockaddr {
                     self.leave_multicast_v6(&addr.to_ipv6_mapped(), local_sockaddr.scope_id())
                 } else {
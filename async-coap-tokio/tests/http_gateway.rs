@@ -0,0 +1,148 @@
+#![cfg(feature = "http-gateway")]
+
+use async_coap::datagram::DatagramLocalEndpoint;
+use async_coap::prelude::*;
+use async_coap::RespondableInboundContext;
+use async_coap_tokio::http_gateway::*;
+use async_coap_tokio::TokioAsyncUdpSocket;
+use futures::prelude::*;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{header, Body, Request, Response, Server, StatusCode};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::runtime::Handle;
+use tokio::spawn;
+
+fn bind_local() -> (TokioAsyncUdpSocket, SocketAddr) {
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = socket.local_addr().unwrap();
+    (TokioAsyncUdpSocket::from_std(socket), addr)
+}
+
+#[tokio::test]
+async fn http_to_coap() {
+    let (server_socket, server_addr) = bind_local();
+    let server = Arc::new(DatagramLocalEndpoint::new(server_socket));
+
+    spawn(
+        server
+            .clone()
+            .receive_loop_arc(|context| {
+                context.respond(|msg_out| {
+                    msg_out.set_msg_code(MsgCode::SuccessContent);
+                    msg_out
+                        .insert_option(option::CONTENT_FORMAT, ContentFormat::TEXT_PLAIN_UTF8)?;
+                    msg_out.append_payload_string("hello")
+                })
+            })
+            .map(|err| panic!("Receive loop terminated: {}", err)),
+    );
+
+    let (client_socket, _) = bind_local();
+    let client = Arc::new(DatagramLocalEndpoint::new(client_socket));
+
+    spawn(
+        client
+            .clone()
+            .receive_loop_arc(null_receiver!())
+            .map(|err| panic!("Receive loop terminated: {}", err)),
+    );
+
+    let gateway = HttpToCoapGateway::new(client);
+
+    let request = Request::get(format!("/hc/coap://{}/test", server_addr))
+        .body(Body::empty())
+        .unwrap();
+    let response = gateway.forward(request).await;
+
+    assert_eq!(StatusCode::OK, response.status());
+    assert_eq!(
+        "text/plain;charset=utf-8",
+        response.headers()[header::CONTENT_TYPE]
+    );
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    assert_eq!(&b"hello"[..], &body[..]);
+
+    let request = Request::get("/other/path").body(Body::empty()).unwrap();
+    assert_eq!(
+        StatusCode::NOT_FOUND,
+        gateway.forward(request).await.status()
+    );
+}
+
+#[tokio::test]
+async fn coap_to_http() {
+    // The HTTP server shares the runtime with the gateway, which only works because the
+    // gateway doesn't block its receive loop while it waits for the HTTP response.
+    let make_service = make_service_fn(|_| async {
+        Ok::<_, Infallible>(service_fn(|request: Request<Body>| async move {
+            Ok::<_, Infallible>(
+                Response::builder()
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(format!("\"{}\"", request.uri().path())))
+                    .unwrap(),
+            )
+        }))
+    });
+    let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+    let http_addr = server.local_addr();
+    spawn(server.map(|ret| ret.unwrap()));
+
+    let (gateway_socket, gateway_addr) = bind_local();
+    let gateway_endpoint = Arc::new(DatagramLocalEndpoint::new(gateway_socket));
+    let gateway = Arc::new(CoapToHttpGateway::new(
+        gateway_endpoint.clone(),
+        Handle::current(),
+    ));
+
+    spawn(
+        gateway_endpoint
+            .clone()
+            .receive_loop_arc(move |context| gateway.handle(context))
+            .map(|err| panic!("Receive loop terminated: {}", err)),
+    );
+
+    let (client_socket, _) = bind_local();
+    let client = Arc::new(DatagramLocalEndpoint::new(client_socket));
+
+    spawn(
+        client
+            .clone()
+            .receive_loop_arc(null_receiver!())
+            .map(|err| panic!("Receive loop terminated: {}", err)),
+    );
+
+    let proxy_uri = format!("http://{}/a/b", http_addr);
+    let response = client
+        .send(
+            gateway_addr,
+            CoapRequest::get()
+                .add_option(option::PROXY_URI, proxy_uri.as_str())
+                .emit_any_response(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(MsgCode::SuccessContent, response.msg_code());
+    assert_eq!(
+        Some(ContentFormat::APPLICATION_JSON),
+        response.content_format()
+    );
+    assert_eq!(Some("\"/a/b\""), response.payload_as_str());
+
+    let response = client
+        .send(
+            gateway_addr,
+            CoapRequest::get()
+                .add_option(option::PROXY_URI, "ftp://example.com/")
+                .emit_any_response(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        MsgCode::ServerErrorProxyingNotSupported,
+        response.msg_code()
+    );
+}
@@ -0,0 +1,90 @@
+use async_coap::datagram::{DatagramLocalEndpoint, NotifyPolicy, Observers};
+use async_coap::message::MessageRead;
+use async_coap::prelude::*;
+use async_coap::RespondableInboundContext;
+use async_coap_tokio::TokioAsyncUdpSocket;
+use futures::prelude::*;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::spawn;
+
+fn bind_local() -> (TokioAsyncUdpSocket, SocketAddr) {
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = socket.local_addr().unwrap();
+    (TokioAsyncUdpSocket::from_std(socket), addr)
+}
+
+#[tokio::test]
+async fn observe_notifications() {
+    let (server_socket, server_addr) = bind_local();
+    let server = Arc::new(DatagramLocalEndpoint::new(server_socket));
+
+    // Every other notification is confirmable.
+    let observers = Arc::new(Observers::with_policy(NotifyPolicy {
+        con_every: 2,
+        con_interval: Duration::from_secs(60 * 60),
+    }));
+    let observers_clone = observers.clone();
+
+    spawn(
+        server
+            .clone()
+            .receive_loop_arc(move |context| {
+                let seq = observers_clone.handle_request(context);
+                context.respond(|msg_out| {
+                    msg_out.set_msg_code(MsgCode::SuccessContent);
+                    if let Some(seq) = seq {
+                        msg_out.insert_option(option::OBSERVE, seq)?;
+                    }
+                    msg_out.append_payload_string("0")
+                })
+            })
+            .map(|err| panic!("Receive loop terminated: {}", err)),
+    );
+
+    let (client_socket, _) = bind_local();
+    let client = Arc::new(DatagramLocalEndpoint::new(client_socket));
+
+    spawn(
+        client
+            .clone()
+            .receive_loop_arc(null_receiver!())
+            .map(|err| panic!("Receive loop terminated: {}", err)),
+    );
+
+    let notify = |value: &'static str| {
+        observers.notify(&server, move |msg_out| {
+            msg_out.set_msg_code(MsgCode::SuccessContent);
+            msg_out.append_payload_string(value)
+        })
+    };
+
+    let mut stream = client.send_as_stream(
+        server_addr,
+        CoapRequest::observe().emit_successful_response(),
+    );
+
+    let msg = stream.next().await.unwrap().unwrap();
+    assert_eq!(Some("0"), msg.payload_as_str());
+    assert_eq!(1, observers.len());
+
+    // Non-confirmable.
+    assert_eq!(1, notify("1").await);
+    let msg = stream.next().await.unwrap().unwrap();
+    assert_eq!(Some("1"), msg.payload_as_str());
+    assert_eq!(Some(Ok(1)), msg.options().find_next_of(option::OBSERVE));
+
+    // Confirmable, acknowledged by the client.
+    assert_eq!(1, notify("2").await);
+    let msg = stream.next().await.unwrap().unwrap();
+    assert_eq!(Some("2"), msg.payload_as_str());
+    assert_eq!(1, observers.len());
+
+    // Once the client loses interest, it rejects the next confirmable
+    // notification, which removes it from the set of observers.
+    drop(stream);
+    assert_eq!(1, notify("3").await);
+    assert_eq!(0, notify("4").await);
+    assert!(observers.is_empty());
+}
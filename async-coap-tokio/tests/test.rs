@@ -30,7 +30,7 @@ async fn test_tokio() {
 
     // Create a future that sends a request to a specific path
     // on the remote endpoint, collecting any blocks in the response
-    // and returning `Ok(OwnedImmutableMessage)` upon success.
+    // and returning `Ok(Response)` upon success.
     let future = remote_endpoint.send_to(
         rel_ref!("large"),
         CoapRequest::get() // This is a CoAP GET request
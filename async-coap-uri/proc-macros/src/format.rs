@@ -0,0 +1,456 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Implementation of the checked URI format macros.
+//!
+//! The format string is split into literal pieces and placeholders at compile time.
+//! The literal pieces (the "skeleton") are verified with every placeholder replaced by
+//! a benign stand-in value, and each placeholder is assigned an escaping strategy
+//! based on the URI component it appears in.
+
+use super::*;
+use proc_macro2::{Ident, Span, TokenStream, TokenTree};
+use syn::parse::{ParseStream, Parser};
+use syn::{Expr, Token};
+
+/// How an interpolated argument is escaped at runtime. The names match the
+/// variants of `async_coap_uri::macros::FormatEscape`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Escape {
+    Full,
+    Authority,
+    Segment,
+    Query,
+    Fragment,
+}
+
+impl Escape {
+    fn ident(self) -> Ident {
+        let name = match self {
+            Escape::Full => "Full",
+            Escape::Authority => "Authority",
+            Escape::Segment => "Segment",
+            Escape::Query => "Query",
+            Escape::Fragment => "Fragment",
+        };
+        Ident::new(name, Span::call_site())
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum ArgRef {
+    Index(usize),
+    Name(String),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Piece {
+    Literal(String),
+    Placeholder {
+        arg: ArgRef,
+        spec: String,
+        offset: usize,
+    },
+}
+
+struct Input {
+    krate: TokenStream,
+    kind: UriKind,
+    fmt: LitStr,
+    args: Vec<Expr>,
+    named_args: Vec<(Ident, Expr)>,
+}
+
+fn parse_input(input: ParseStream<'_>) -> syn::Result<Input> {
+    let mut krate = TokenStream::new();
+    while !input.peek(Token![,]) {
+        krate.extend(std::iter::once(input.parse::<TokenTree>()?));
+    }
+    input.parse::<Token![,]>()?;
+
+    let kind_ident: Ident = input.parse()?;
+    let kind = match kind_ident.to_string().as_str() {
+        "Uri" => UriKind::Uri,
+        "UriRef" => UriKind::UriRef,
+        "RelRef" => UriKind::RelRef,
+        _ => return Err(syn::Error::new(kind_ident.span(), "Unknown URI kind")),
+    };
+    input.parse::<Token![,]>()?;
+
+    let fmt: LitStr = input.parse()?;
+    let mut args = Vec::new();
+    let mut named_args: Vec<(Ident, Expr)> = Vec::new();
+
+    while !input.is_empty() {
+        input.parse::<Token![,]>()?;
+        if input.is_empty() {
+            break;
+        }
+
+        if input.peek(syn::Ident) && input.peek2(Token![=]) && !input.peek2(Token![==]) {
+            let name: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            let expr: Expr = input.parse()?;
+            if named_args.iter().any(|(x, _)| x == &name) {
+                return Err(syn::Error::new(name.span(), "Duplicate argument name"));
+            }
+            named_args.push((name, expr));
+        } else if !named_args.is_empty() {
+            return Err(input.error("Positional arguments cannot follow named arguments"));
+        } else {
+            args.push(input.parse()?);
+        }
+    }
+
+    Ok(Input {
+        krate,
+        kind,
+        fmt,
+        args,
+        named_args,
+    })
+}
+
+/// Splits the given format string into pieces, computing the offset of each
+/// placeholder in the skeleton. Every placeholder occupies exactly one byte
+/// of the skeleton.
+fn parse_format_string(fmt: &str) -> Result<Vec<Piece>, String> {
+    let mut pieces = Vec::new();
+    let mut literal = String::new();
+    let mut offset = 0;
+    let mut next_index = 0;
+    let mut chars = fmt.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                literal.push('}');
+            }
+            '}' => return Err("Unmatched `}` in format string".to_string()),
+            '{' => {
+                let mut inner = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => inner.push(c),
+                        None => return Err("Unmatched `{` in format string".to_string()),
+                    }
+                }
+
+                let (arg, spec) = match inner.find(':') {
+                    Some(i) => (&inner[..i], &inner[i + 1..]),
+                    None => (inner.as_str(), ""),
+                };
+
+                if spec.contains('$') || spec.contains('*') {
+                    return Err("Width and precision arguments are not supported".to_string());
+                }
+
+                let arg = if arg.is_empty() {
+                    next_index += 1;
+                    ArgRef::Index(next_index - 1)
+                } else if let Ok(i) = arg.parse::<usize>() {
+                    ArgRef::Index(i)
+                } else if syn::parse_str::<Ident>(arg).is_ok() {
+                    ArgRef::Name(arg.to_string())
+                } else {
+                    return Err(format!("Invalid argument `{}` in format string", arg));
+                };
+
+                offset += literal.len();
+                if !literal.is_empty() {
+                    pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+                }
+
+                pieces.push(Piece::Placeholder {
+                    arg,
+                    spec: spec.to_string(),
+                    offset,
+                });
+                offset += 1;
+            }
+            c => literal.push(c),
+        }
+    }
+
+    if !literal.is_empty() {
+        pieces.push(Piece::Literal(literal));
+    }
+
+    Ok(pieces)
+}
+
+/// Determines how a placeholder at `offset` in `skeleton` should be escaped.
+/// Also returns true if the placeholder is in the position of the port.
+fn classify(kind: UriKind, skeleton: &str, offset: usize) -> Result<(Escape, bool), String> {
+    let captures = RFC3986_APPENDIX_B
+        .captures(skeleton)
+        .ok_or_else(|| "The structure of the URI is not recognized.".to_string())?;
+
+    let contains = |i: usize| {
+        captures
+            .get(i)
+            .map(|x| x.start() <= offset && offset < x.end())
+            .unwrap_or(false)
+    };
+
+    if kind != UriKind::RelRef {
+        if contains(2) {
+            return Err("Placeholders are not allowed in the scheme".to_string());
+        }
+
+        if let Some(authority) = captures.get(4) {
+            if contains(4) {
+                let authority_str = authority.as_str();
+                let host_start = authority_str.rfind('@').map(|i| i + 1).unwrap_or(0);
+                let host_end = if authority_str[host_start..].starts_with('[') {
+                    authority_str[host_start..]
+                        .find(']')
+                        .map(|i| host_start + i + 1)
+                        .unwrap_or_else(|| authority_str.len())
+                } else {
+                    authority_str[host_start..]
+                        .find(':')
+                        .map(|i| host_start + i)
+                        .unwrap_or_else(|| authority_str.len())
+                };
+
+                return if offset >= authority.start() + host_end {
+                    Ok((Escape::Full, true))
+                } else {
+                    Ok((Escape::Authority, false))
+                };
+            }
+        }
+    }
+
+    if contains(9) {
+        Ok((Escape::Fragment, false))
+    } else if contains(7) {
+        Ok((Escape::Query, false))
+    } else {
+        let path = captures.get(5).unwrap();
+        let has_scheme_or_authority =
+            kind != UriKind::RelRef && (captures.get(2).is_some() || captures.get(4).is_some());
+
+        if !has_scheme_or_authority && !skeleton[path.start()..offset].contains('/') {
+            // Colons must be escaped in the first path segment of a relative reference.
+            Ok((Escape::Full, false))
+        } else {
+            Ok((Escape::Segment, false))
+        }
+    }
+}
+
+pub(crate) fn expand(input: TokenStream) -> syn::Result<TokenStream> {
+    let Input {
+        krate,
+        kind,
+        fmt,
+        args,
+        named_args,
+    } = parse_input.parse2(input)?;
+
+    let span = fmt.span();
+    let error = |msg: String| syn::Error::new(span, msg);
+    let pieces = parse_format_string(&fmt.value()).map_err(error)?;
+
+    let mut skeleton = String::new();
+    for piece in pieces.iter() {
+        match piece {
+            Piece::Literal(x) => skeleton.push_str(x),
+            Piece::Placeholder { .. } => skeleton.push('x'),
+        }
+    }
+
+    let mut escapes = Vec::new();
+    let mut validation_skeleton = skeleton.clone().into_bytes();
+    for piece in pieces.iter() {
+        if let Piece::Placeholder { offset, .. } = piece {
+            let (escape, is_port) = classify(kind, &skeleton, *offset).map_err(error)?;
+            if is_port {
+                validation_skeleton[*offset] = b'0';
+            }
+            escapes.push(escape);
+        }
+    }
+
+    // Unwrap safety: We only replaced single ASCII characters.
+    let validation_skeleton = String::from_utf8(validation_skeleton).unwrap();
+    kind.check(&validation_skeleton)
+        .map_err(|msg| error(format!("{} (placeholders were replaced with `x`)", msg)))?;
+
+    let arg_idents: Vec<Ident> = (0..args.len())
+        .map(|i| Ident::new(&format!("__arg{}", i), Span::call_site()))
+        .collect();
+    let named_idents: Vec<Ident> = named_args
+        .iter()
+        .map(|(name, _)| Ident::new(&format!("__arg_{}", name), Span::call_site()))
+        .collect();
+    let mut used = vec![false; args.len() + named_args.len()];
+
+    let mut stmts = Vec::new();
+    let mut escapes = escapes.into_iter();
+    for piece in pieces.iter() {
+        match piece {
+            Piece::Literal(x) => stmts.push(quote!(__uri.push_str(#x);)),
+            Piece::Placeholder { arg, spec, .. } => {
+                let ident = match arg {
+                    ArgRef::Index(i) if *i < args.len() => {
+                        used[*i] = true;
+                        arg_idents[*i].clone()
+                    }
+                    ArgRef::Index(i) => {
+                        return Err(error(format!(
+                            "Invalid reference to positional argument {}",
+                            i
+                        )));
+                    }
+                    ArgRef::Name(name) => match named_args.iter().position(|(x, _)| x == name) {
+                        Some(i) => {
+                            used[args.len() + i] = true;
+                            named_idents[i].clone()
+                        }
+                        // Implicitly captured from the surrounding scope.
+                        None => Ident::new(name, span),
+                    },
+                };
+
+                let spec = if spec.is_empty() {
+                    "{}".to_string()
+                } else {
+                    format!("{{:{}}}", spec)
+                };
+                let escape = escapes.next().unwrap().ident();
+
+                stmts.push(quote! {
+                    #krate::macros::push_escaped(
+                        &mut __uri,
+                        #krate::macros::FormatEscape::#escape,
+                        ::core::format_args!(#spec, #ident),
+                    );
+                });
+            }
+        }
+    }
+
+    if let Some(i) = used.iter().position(|x| !x) {
+        let err_span = match args.get(i) {
+            Some(expr) => syn::spanned::Spanned::span(expr),
+            None => named_args[i - args.len()].0.span(),
+        };
+        return Err(syn::Error::new(err_span, "Argument never used"));
+    }
+
+    let buf_ty = Ident::new(
+        match kind {
+            UriKind::Uri => "UriBuf",
+            UriKind::UriRef => "UriRefBuf",
+            UriKind::RelRef => "RelRefBuf",
+        },
+        Span::call_site(),
+    );
+    let exprs = args.iter().chain(named_args.iter().map(|(_, expr)| expr));
+    let idents = arg_idents.iter().chain(named_idents.iter());
+
+    Ok(quote! {
+        match (#(&#exprs,)*) {
+            (#(#idents,)*) => {
+                let mut __uri = ::std::string::String::new();
+                #(#stmts)*
+                #krate::#buf_ty::from_string(__uri)
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn escapes(kind: UriKind, fmt: &str) -> Result<Vec<Escape>, String> {
+        let pieces = parse_format_string(fmt)?;
+        let mut skeleton = String::new();
+        for piece in pieces.iter() {
+            match piece {
+                Piece::Literal(x) => skeleton.push_str(x),
+                Piece::Placeholder { .. } => skeleton.push('x'),
+            }
+        }
+        pieces
+            .iter()
+            .filter_map(|piece| match piece {
+                Piece::Placeholder { offset, .. } => Some(*offset),
+                _ => None,
+            })
+            .map(|offset| classify(kind, &skeleton, offset).map(|x| x.0))
+            .collect()
+    }
+
+    #[test]
+    fn format_string() {
+        assert_eq!(
+            parse_format_string("a{}b{1:x}{{c}}{name}"),
+            Ok(vec![
+                Piece::Literal("a".to_string()),
+                Piece::Placeholder {
+                    arg: ArgRef::Index(0),
+                    spec: "".to_string(),
+                    offset: 1,
+                },
+                Piece::Literal("b".to_string()),
+                Piece::Placeholder {
+                    arg: ArgRef::Index(1),
+                    spec: "x".to_string(),
+                    offset: 3,
+                },
+                Piece::Literal("{c}".to_string()),
+                Piece::Placeholder {
+                    arg: ArgRef::Name("name".to_string()),
+                    spec: "".to_string(),
+                    offset: 7,
+                },
+            ])
+        );
+        assert!(parse_format_string("a{").is_err());
+        assert!(parse_format_string("a}").is_err());
+        assert!(parse_format_string("{:1$}").is_err());
+        assert!(parse_format_string("{a-b}").is_err());
+    }
+
+    #[test]
+    fn placeholder_components() {
+        assert_eq!(
+            escapes(UriKind::Uri, "coap://{}:{}/a/{}?q={}#{}"),
+            Ok(vec![
+                Escape::Authority,
+                Escape::Full,
+                Escape::Segment,
+                Escape::Query,
+                Escape::Fragment,
+            ])
+        );
+        assert_eq!(
+            escapes(UriKind::RelRef, "{}/{}?{}"),
+            Ok(vec![Escape::Full, Escape::Segment, Escape::Query])
+        );
+        assert!(escapes(UriKind::Uri, "{}://example.com/").is_err());
+    }
+}
@@ -25,14 +25,15 @@ extern crate proc_macro;
 extern crate lazy_static;
 
 use crate::proc_macro::TokenStream;
-use proc_macro_hack::proc_macro_hack;
 use quote::quote;
 use regex::Regex;
-use syn;
+use syn::LitStr;
 
 mod unescape_uri;
 use unescape_uri::UnescapeUri;
 
+mod format;
+
 lazy_static! {
     // Splits full URI string into "scheme", "heir-part", "query", and "fragment"
     //      scheme    = $2
@@ -70,6 +71,7 @@ enum Error {
     EncodingError,
     MalformedStructure,
     MalformedScheme,
+    MalformedHost,
     Degenerate,
 }
 
@@ -79,6 +81,7 @@ impl std::fmt::Debug for Error {
             Error::EncodingError => f.write_str("Encoding Error"),
             Error::MalformedStructure => f.write_str("The structure of the URI is not recognized."),
             Error::MalformedScheme => f.write_str("The scheme of the URI is malformed."),
+            Error::MalformedHost => f.write_str("The host of the URI is malformed."),
             Error::Degenerate => {
                 f.write_str("This relative reference could be confused with a URI.")
             }
@@ -105,7 +108,61 @@ fn assert_uri_str(uri_str: &str) -> Result<(), Error> {
             .ok_or(Error::MalformedScheme)?;
     }
 
-    Ok(())
+    check_authority(captures.get(4).map(|x| x.as_str()))
+}
+
+fn check_authority(authority: Option<&str>) -> Result<(), Error> {
+    let authority = match authority {
+        Some(x) if !x.is_empty() => x,
+        _ => return Ok(()),
+    };
+
+    if !authority.contains(&['[', ']'][..]) {
+        return Ok(());
+    }
+
+    let host = URI_AUTHORITY
+        .captures(authority)
+        .and_then(|x| x.get(3))
+        .ok_or(Error::MalformedHost)?
+        .as_str();
+
+    if !host.starts_with('[') {
+        return Err(Error::MalformedHost);
+    }
+
+    // URI_AUTHORITY guarantees that a bracketed host also ends with a bracket.
+    let literal = &host[1..host.len() - 1];
+
+    if literal.starts_with('v') || literal.starts_with('V') {
+        // IPvFuture  = "v" 1*HEXDIG "." 1*( unreserved / sub-delims / ":" )
+        let mut parts = literal[1..].splitn(2, '.');
+        let version = parts.next().unwrap_or("");
+        let address = parts.next().unwrap_or("");
+
+        if !version.is_empty()
+            && version.chars().all(|c| c.is_ascii_hexdigit())
+            && !address.is_empty()
+            && address
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-._~!$&'()*+,;=:".contains(c))
+        {
+            return Ok(());
+        }
+    } else {
+        // Zone identifiers are defined by RFC6874.
+        let (addr, zone) = match literal.find("%25") {
+            Some(i) => (&literal[..i], Some(&literal[i + 3..])),
+            None => (literal, None),
+        };
+
+        if zone.map(|x| !x.is_empty()).unwrap_or(true) && addr.parse::<std::net::Ipv6Addr>().is_ok()
+        {
+            return Ok(());
+        }
+    }
+
+    Err(Error::MalformedHost)
 }
 
 fn assert_rel_ref_str(uri_str: &str) -> Result<(), Error> {
@@ -120,70 +177,86 @@ fn assert_rel_ref_str(uri_str: &str) -> Result<(), Error> {
 }
 
 fn assert_uri_ref_str(uri_str: &str) -> Result<(), Error> {
-    // Not sure what additional checks to do in this case.
-    RFC3986_APPENDIX_B
+    let captures = RFC3986_APPENDIX_B
         .captures(uri_str)
         .ok_or(Error::MalformedStructure)?;
 
-    Ok(())
+    check_authority(captures.get(4).map(|x| x.as_str()))
 }
 
-fn string_literal_from_token_stream(input: TokenStream) -> String {
-    use syn::LitStr;
-
-    if let Some(nom) = syn::parse::<LitStr>(input.clone()).ok() {
-        return nom.value();
-    }
-
-    panic!("Expected string literal, got {:?}", input);
+/// The kind of URI-reference that a literal is being checked against.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum UriKind {
+    Uri,
+    UriRef,
+    RelRef,
 }
 
-#[proc_macro_hack]
-pub fn assert_uri_literal(input: TokenStream) -> TokenStream {
-    let uri_str = string_literal_from_token_stream(input);
-
-    if let Some(err_pos) = UnescapeUri::new(&uri_str).first_error() {
-        panic!("Malformed percent encoding at index {}", err_pos);
+impl UriKind {
+    fn name(self) -> &'static str {
+        match self {
+            UriKind::Uri => "uri",
+            UriKind::UriRef => "uri_ref",
+            UriKind::RelRef => "rel_ref",
+        }
     }
 
-    if let Err(err) = assert_uri_str(&uri_str) {
-        panic!("Malformed uri literal; {:?}", err);
-    }
+    /// Checks `uri_str` for correctness, returning a description of the problem if
+    /// it is not well-formed.
+    pub(crate) fn check(self, uri_str: &str) -> Result<(), String> {
+        if let Some(err_pos) = UnescapeUri::new(uri_str).first_error() {
+            return Err(format!("Malformed percent encoding at index {}", err_pos));
+        }
 
-    let gen = quote! { () };
-    gen.into()
+        match self {
+            UriKind::Uri => assert_uri_str(uri_str),
+            UriKind::UriRef => assert_uri_ref_str(uri_str),
+            UriKind::RelRef => assert_rel_ref_str(uri_str),
+        }
+        .map_err(|err| format!("Malformed {} literal; {:?}", self.name(), err))
+    }
 }
 
-#[proc_macro_hack]
-pub fn assert_rel_ref_literal(input: TokenStream) -> TokenStream {
-    let uri_str = string_literal_from_token_stream(input);
+fn assert_literal(input: TokenStream, kind: UriKind) -> TokenStream {
+    let lit = match syn::parse::<LitStr>(input) {
+        Ok(lit) => lit,
+        Err(err) => return err.to_compile_error().into(),
+    };
 
-    if let Some(err_pos) = UnescapeUri::new(&uri_str).first_error() {
-        panic!("Malformed percent encoding at index {}", err_pos);
+    match kind.check(&lit.value()) {
+        Ok(()) => quote!(()).into(),
+        Err(msg) => syn::Error::new(lit.span(), msg).to_compile_error().into(),
     }
+}
 
-    if let Err(err) = assert_rel_ref_str(&uri_str) {
-        panic!("Malformed rel_ref literal; {:?}", err);
-    }
+/// Verifies that the given string literal is a well-formed URI.
+#[proc_macro]
+pub fn assert_uri_literal(input: TokenStream) -> TokenStream {
+    assert_literal(input, UriKind::Uri)
+}
 
-    let gen = quote! { () };
-    gen.into()
+/// Verifies that the given string literal is a well-formed relative reference.
+#[proc_macro]
+pub fn assert_rel_ref_literal(input: TokenStream) -> TokenStream {
+    assert_literal(input, UriKind::RelRef)
 }
 
-#[proc_macro_hack]
+/// Verifies that the given string literal is a well-formed URI-reference.
+#[proc_macro]
 pub fn assert_uri_ref_literal(input: TokenStream) -> TokenStream {
-    let uri_str = string_literal_from_token_stream(input);
-
-    if let Some(err_pos) = UnescapeUri::new(&uri_str).first_error() {
-        panic!("Malformed percent encoding at index {}", err_pos);
-    }
+    assert_literal(input, UriKind::UriRef)
+}
 
-    if let Err(err) = assert_uri_ref_str(&uri_str) {
-        panic!("Malformed uri_ref literal; {:?}", err);
+/// Implementation of the checked URI format macros.
+///
+/// Expects the path to the `async-coap-uri` crate, the name of the resulting
+/// type (`Uri`, `UriRef`, or `RelRef`), a format string literal, and the format arguments.
+#[proc_macro]
+pub fn uri_format_checked(input: TokenStream) -> TokenStream {
+    match format::expand(input.into()) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
     }
-
-    let gen = quote! { () };
-    gen.into()
 }
 
 #[cfg(test)]
@@ -262,4 +335,21 @@ mod test {
         assert_eq!(check_uri_ref_str("a/ /c"), Err(Error::EncodingError));
         assert_eq!(check_uri_ref_str("a/\n/c"), Err(Error::EncodingError));
     }
+
+    #[test]
+    fn test_host() {
+        assert_eq!(check_uri_str("coap://[::1]/"), Ok(()));
+        assert_eq!(check_uri_str("coap://[::1]:1234/"), Ok(()));
+        assert_eq!(check_uri_str("coap://[v1.a+b]/"), Ok(()));
+        assert_eq!(check_uri_str("coap://[::1::2]/"), Err(Error::MalformedHost));
+        assert_eq!(check_uri_str("coap://[fe80::1%25en2]/"), Ok(()));
+        assert_eq!(
+            check_uri_str("coap://[fe80::1%25]/"),
+            Err(Error::MalformedHost)
+        );
+        assert_eq!(check_uri_str("coap://[::1/"), Err(Error::MalformedHost));
+        assert_eq!(check_uri_str("coap://[v1]/"), Err(Error::MalformedHost));
+        assert_eq!(check_uri_ref_str("//[::1]/"), Ok(()));
+        assert_eq!(check_uri_ref_str("//[a.b]/"), Err(Error::MalformedHost));
+    }
 }
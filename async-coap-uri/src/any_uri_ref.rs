@@ -15,6 +15,7 @@
 
 use super::*;
 use core::fmt::Display;
+#[cfg(feature = "std")]
 use core::ops::Deref;
 
 /// Trait for objects that represent logical URI-references. Useful for generic programming.
@@ -49,7 +50,12 @@ pub trait AnyUriRef {
     #[cfg(feature = "std")]
     #[must_use]
     fn to_uri_ref_buf(&self) -> UriRefBuf {
-        unsafe { UriRefBuf::from_string_unchecked(self.display().to_string()) }
+        let mut ret = UriRefBuf::new();
+
+        // Writing to a `SmallString` can't fail.
+        let _ = self.write_to(&mut ret.0);
+
+        ret
     }
 
     /// Hook for custom URI serialization implementation via [`AnyUriRefExt::write_to`].
@@ -132,191 +138,415 @@ pub trait AnyUriRefExt: AnyUriRef {
 
     /// Writes out to a [`core::fmt::Write`] instance the result of performing URI resolution
     /// against `target`, with `self` being the base URI.
+    ///
+    /// Removing dot segments from the resolved path needs to allocate, so this method is
+    /// only available with the `std` feature.
+    #[cfg(feature = "std")]
     fn write_resolved<T: core::fmt::Write + ?Sized, D: AnyUriRef + ?Sized>(
         &self,
         target: &D,
         f: &mut T,
     ) -> Result<(), ResolveError> {
-        // This implementation is kind of a mess, but it does work and it does
-        // pass the rather large corpus of unit tests. It eventually needs to be
-        // rewritten to avoid memory allocation.
-        // TODO(#9): Rewrite `AnyUriRef::write_resolved` to not use any memory allocation.
-
-        if target.is_empty() {
-            self.write_to(f)?;
-            return Ok(());
+        write_resolved(self, target, f, false)
+    }
+
+    /// Like [`write_resolved`](AnyUriRefExt::write_resolved), but fails with
+    /// [`ResolveError::EscapesRoot`] instead of dropping `..` segments which would climb
+    /// above the root of the resolved path.
+    ///
+    /// Silently dropping these segments is what [IETF-RFC3986 Section 5.2.4] calls for,
+    /// but applications which map URIs onto something like a file system may want to
+    /// detect such references, since they are a common way of attempting to access things
+    /// that shouldn't be accessible.
+    ///
+    /// [IETF-RFC3986 Section 5.2.4]: https://tools.ietf.org/html/rfc3986#section-5.2.4
+    #[cfg(feature = "std")]
+    fn write_resolved_strict<T: core::fmt::Write + ?Sized, D: AnyUriRef + ?Sized>(
+        &self,
+        target: &D,
+        f: &mut T,
+    ) -> Result<(), ResolveError> {
+        write_resolved(self, target, f, true)
+    }
+
+    /// Creates a new [`UriRefBuf`] that contains the result of performing URI resolution with
+    /// `dest`.
+    #[cfg(feature = "std")]
+    fn resolved<T: AnyUriRef + ?Sized>(&self, dest: &T) -> Result<UriRefBuf, ResolveError> {
+        if dest.is_empty() {
+            return Ok(self.to_uri_ref_buf());
         }
 
-        let target_type = target.uri_type();
+        let mut ret = String::new();
 
-        let target_components = target.components();
+        self.write_resolved(dest, &mut ret)?;
 
-        let base_type = self.uri_type();
+        // SAFETY: `write_resolved` is guaranteed to write well-formed UriRefs.
+        Ok(unsafe { UriRefBuf::from_string_unchecked(ret) })
+    }
 
-        // Handle some exceptions.
-        if base_type.cannot_be_a_base() {
-            match target_type {
-                UriType::Fragment => {
-                    self.components().trim_fragment().write_to(f)?;
-                    target.write_to(f)?;
-                    return Ok(());
-                }
-                UriType::Query => {
-                    self.components().trim_query().write_to(f)?;
-                    target.write_to(f)?;
-                    return Ok(());
-                }
-                x if x.is_ietf_rfc3986_relative_reference() => {
-                    return Err(ResolveError::CannotBeABase);
-                }
-                _ => (),
-            }
+    /// Like [`resolved`](AnyUriRefExt::resolved), but fails with
+    /// [`ResolveError::EscapesRoot`] if `dest` has more `..` segments than can be removed
+    /// from the resolved path. See [`write_resolved_strict`] for more information.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use async_coap_uri::prelude::*;
+    /// use async_coap_uri::ResolveError;
+    /// let base = uri_ref!("coap://example.com/files/a");
+    ///
+    /// assert_eq!(
+    ///     base.resolved_strict(rel_ref!("../b")),
+    ///     Ok(uri_ref!("coap://example.com/b").to_owned())
+    /// );
+    /// assert_eq!(
+    ///     base.resolved_strict(rel_ref!("../../etc/passwd")),
+    ///     Err(ResolveError::EscapesRoot)
+    /// );
+    /// assert_eq!(
+    ///     base.resolved(rel_ref!("../../etc/passwd")),
+    ///     Ok(uri_ref!("coap://example.com/etc/passwd").to_owned())
+    /// );
+    /// ```
+    ///
+    /// [`write_resolved_strict`]: AnyUriRefExt::write_resolved_strict
+    #[cfg(feature = "std")]
+    fn resolved_strict<T: AnyUriRef + ?Sized>(&self, dest: &T) -> Result<UriRefBuf, ResolveError> {
+        if dest.is_empty() {
+            return Ok(self.to_uri_ref_buf());
         }
 
-        if target_components.scheme.is_some() {
-            target.write_to(f)?;
-            return Ok(());
-        }
+        let mut ret = String::new();
 
-        let mut components = self.components();
+        self.write_resolved_strict(dest, &mut ret)?;
 
-        if target_components.authority.is_some() {
-            components.authority = target_components.authority;
-        }
+        // SAFETY: `write_resolved_strict` is guaranteed to write well-formed UriRefs.
+        Ok(unsafe { UriRefBuf::from_string_unchecked(ret) })
+    }
 
-        // Target fragment always gets used.
-        components.fragment = target_components.fragment;
-        if target_components.query.is_some() {
-            components.query = target_components.query;
-        } else if !target_components.path.is_empty() || target_components.authority.is_some() {
-            components.query = None;
-        }
+    /// Computes the shortest relative reference that, when [resolved](AnyUriRefExt::resolved)
+    /// against `self`, yields `target`. This is the inverse of URI-reference resolution.
+    ///
+    /// Unlike [`UriRef::trim_to_shorten`], this method will navigate up the path hierarchy
+    /// using `../` segments when needed, and will use query-only or fragment-only references
+    /// where possible.
+    ///
+    /// Returns `None` if `self` and `target` differ in their scheme or authority, or if there
+    /// is otherwise no relative reference that would resolve to `target` (which is the case
+    /// if `target` contains unnormalized `.` or `..` path segments).
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use async_coap_uri::prelude::*;
+    /// let base = uri_ref!("coap://example.com/a/b/c");
+    ///
+    /// assert_eq!(base.make_relative(uri_ref!("coap://example.com/a/x/y")), Some(rel_ref!("../x/y").to_owned()));
+    /// assert_eq!(base.make_relative(uri_ref!("coap://example.com/a/b/c?q")), Some(rel_ref!("?q").to_owned()));
+    /// assert_eq!(base.make_relative(uri_ref!("coap://example.com/a/b/")), Some(rel_ref!(".").to_owned()));
+    /// assert_eq!(base.make_relative(uri_ref!("coap://example.org/a/b/c")), None);
+    /// ```
+    #[cfg(feature = "std")]
+    fn make_relative<T: AnyUriRef + ?Sized>(&self, target: &T) -> Option<RelRefBuf> {
+        let base = self.components();
+        let dest = target.components();
+
+        let same_scheme = match (base.scheme, dest.scheme) {
+            (Some(a), Some(b)) => a.eq_ignore_ascii_case(b),
+            (None, None) => true,
+            _ => false,
+        };
 
-        if let Some(scheme) = components.scheme {
-            f.write_str(scheme)?;
-            f.write_char(':')?;
+        if !same_scheme || base.authority != dest.authority {
+            return None;
         }
 
-        if let Some(authority) = components.authority {
-            f.write_str("//")?;
-            f.write_str(authority)?;
+        let suffix = |query: bool| {
+            let mut ret = String::new();
+            if let (true, Some(query)) = (query, dest.query) {
+                ret.push('?');
+                ret.push_str(query);
+            }
+            if let Some(fragment) = dest.fragment {
+                ret.push('#');
+                ret.push_str(fragment);
+            }
+            ret
+        };
+
+        let mut candidates: Vec<String> = Vec::new();
+
+        if base.path == dest.path {
+            if base.query == dest.query {
+                candidates.push(suffix(false));
+            }
+            if dest.query.is_some() {
+                candidates.push(suffix(true));
+            }
         }
 
-        let mut base_path = components.path_as_rel_ref();
-        let target_path = target_components.path_as_rel_ref();
+        let base_path = if base.authority.is_some() && base.path.is_empty() {
+            "/"
+        } else {
+            base.path
+        };
+        let dest_path = if dest.authority.is_some() && dest.path.is_empty() {
+            "/"
+        } else {
+            dest.path
+        };
+
+        if !base.uri_type().cannot_be_a_base() {
+            let base_dir = match base_path.rfind('/') {
+                Some(i) => &base_path[..i],
+                None => "",
+            };
+            let base_dir_segs: Vec<&str> = if base_path.contains('/') {
+                base_dir.split('/').collect()
+            } else {
+                Vec::new()
+            };
+            let dest_segs: Vec<&str> = dest_path.split('/').collect();
 
-        if !target_path.is_empty() || !target_type.has_absolute_path() {
-            let target_starts_with_slash = target_path.starts_with('/');
-            let base_starts_with_slash = base_path.starts_with('/');
+            let common = base_dir_segs
+                .iter()
+                .zip(dest_segs[..dest_segs.len() - 1].iter())
+                .take_while(|(a, b)| a == b)
+                .count();
+            let ups = base_dir_segs.len() - common;
+            let rest = &dest_segs[common..];
 
-            if target_type.has_absolute_path() {
-                if base_starts_with_slash {
-                    base_path = irel_ref!("");
+            let mut path = String::new();
+
+            if rest == [""] {
+                if ups == 0 {
+                    path.push('.');
                 } else {
-                    base_path = irel_ref!("/");
+                    path.push_str(&"../".repeat(ups - 1));
+                    path.push_str("..");
+                }
+            } else {
+                path.push_str(&"../".repeat(ups));
+                let rest = rest.join("/");
+                if ups == 0
+                    && (rest.starts_with('/')
+                        || RelRef::from_str(&rest)
+                            .ok()
+                            .and_then(RelRef::colon_in_first_path_segment)
+                            .is_some())
+                {
+                    path.push_str("./");
                 }
-            } else if !target_path.is_empty() {
-                base_path = base_path.trim_resource();
+                path.push_str(&rest);
             }
 
-            let mut out_path_vec = Vec::new();
+            path.push_str(&suffix(true));
+            candidates.push(path);
 
-            let seg_iter = base_path
-                .raw_path_segments()
-                .chain(target_path.raw_path_segments());
+            if dest_path.starts_with('/') && !dest_path.starts_with("//") {
+                candidates.push(dest_path.to_string() + &suffix(true));
+            }
+        }
 
-            let path_will_be_absolute = target_starts_with_slash
-                || base_starts_with_slash
-                || (base_type.has_absolute_path() && !target_path.is_empty());
+        // The scheme and authority were verified to be equivalent above, so we
+        // only need to compare what comes after them.
+        let expected = target.to_uri_ref_buf();
+        let expected = expected.rel();
 
-            for seg in seg_iter {
-                match seg {
-                    "." => {
-                        let last = out_path_vec.last().copied();
+        candidates.sort_by_key(String::len);
 
-                        if last.map(str::is_empty) == Some(false) {
-                            out_path_vec.push("");
-                        }
-                        continue;
-                    }
-                    ".." => {
-                        let mut last = out_path_vec.pop();
+        candidates
+            .into_iter()
+            // SAFETY: Candidates are assembled entirely from the well-formed components
+            //         of `target`, delimiters, and dot-segments.
+            .map(|candidate| unsafe { RelRefBuf::from_string_unchecked(candidate) })
+            .find(|candidate| match self.resolved(candidate) {
+                Ok(resolved) => resolved.rel() == expected,
+                Err(_) => false,
+            })
+    }
+}
 
-                        if last == Some("") {
-                            last = out_path_vec.pop();
-                        }
+/// Writes the result of resolving `target` against `base` to `f`. If `strict` is true,
+/// `..` segments which would climb above the root are an error rather than being dropped.
+#[cfg(feature = "std")]
+fn write_resolved<B, D, T>(
+    base: &B,
+    target: &D,
+    f: &mut T,
+    strict: bool,
+) -> Result<(), ResolveError>
+where
+    B: AnyUriRef + ?Sized,
+    D: AnyUriRef + ?Sized,
+    T: core::fmt::Write + ?Sized,
+{
+    // This implementation is kind of a mess, but it does work and it does
+    // pass the rather large corpus of unit tests. It eventually needs to be
+    // rewritten to avoid memory allocation.
+    // TODO(#9): Rewrite `AnyUriRef::write_resolved` to not use any memory allocation.
 
-                        match (last, path_will_be_absolute, out_path_vec.is_empty()) {
-                            (Some("."), false, _) => out_path_vec.push(".."),
-                            (Some(".."), false, _) => {
-                                out_path_vec.push("..");
-                                out_path_vec.push("..");
-                            }
-                            (Some(_), true, _) => out_path_vec.push(""),
-                            (Some(_), false, false) => out_path_vec.push(""),
-                            (Some(_), false, true) => out_path_vec.push("."),
-                            (None, _, _) => (),
-                        };
-                    }
-                    seg => {
-                        match out_path_vec.last().copied() {
-                            Some(".") if seg.is_empty() => continue,
-                            Some(".") | Some("") => {
-                                out_path_vec.pop();
-                            }
-                            _ => (),
-                        };
-                        out_path_vec.push(seg)
-                    }
-                }
+    if target.is_empty() {
+        base.write_to(f)?;
+        return Ok(());
+    }
+
+    let target_type = target.uri_type();
+
+    let target_components = target.components();
+
+    let base_type = base.uri_type();
+
+    // Handle some exceptions.
+    if base_type.cannot_be_a_base() {
+        match target_type {
+            UriType::Fragment => {
+                base.components().trim_fragment().write_to(f)?;
+                target.write_to(f)?;
+                return Ok(());
             }
+            UriType::Query => {
+                base.components().trim_query().write_to(f)?;
+                target.write_to(f)?;
+                return Ok(());
+            }
+            x if x.is_ietf_rfc3986_relative_reference() => {
+                return Err(ResolveError::CannotBeABase);
+            }
+            _ => (),
+        }
+    }
 
-            if path_will_be_absolute {
-                f.write_char('/')?;
+    if target_components.scheme.is_some() {
+        target.write_to(f)?;
+        return Ok(());
+    }
+
+    let mut components = base.components();
+
+    if target_components.authority.is_some() {
+        components.authority = target_components.authority;
+    }
+
+    // Target fragment always gets used.
+    components.fragment = target_components.fragment;
+    if target_components.query.is_some() {
+        components.query = target_components.query;
+    } else if !target_components.path.is_empty() || target_components.authority.is_some() {
+        components.query = None;
+    }
+
+    if let Some(scheme) = components.scheme {
+        f.write_str(scheme)?;
+        f.write_char(':')?;
+    }
+
+    if let Some(authority) = components.authority {
+        f.write_str("//")?;
+        f.write_str(authority)?;
+    }
+
+    let mut base_path = components.path_as_rel_ref();
+    let target_path = target_components.path_as_rel_ref();
+
+    if !target_path.is_empty() || !target_type.has_absolute_path() {
+        let target_starts_with_slash = target_path.starts_with('/');
+        let base_starts_with_slash = base_path.starts_with('/');
+
+        if target_type.has_absolute_path() {
+            if base_starts_with_slash {
+                base_path = irel_ref!(unsafe "");
+            } else {
+                base_path = irel_ref!(unsafe "/");
             }
+        } else if !target_path.is_empty() {
+            base_path = base_path.trim_resource();
+        }
+
+        let mut out_path_vec = Vec::new();
+
+        let seg_iter = base_path
+            .raw_path_segments()
+            .chain(target_path.raw_path_segments());
 
-            for (n, seg) in out_path_vec.into_iter().enumerate() {
-                if n != 0 {
-                    f.write_char('/')?;
+        let path_will_be_absolute = target_starts_with_slash
+            || base_starts_with_slash
+            || (base_type.has_absolute_path() && !target_path.is_empty());
+
+        for seg in seg_iter {
+            match seg {
+                "." => {
+                    let last = out_path_vec.last().copied();
+
+                    if last.map(str::is_empty) == Some(false) {
+                        out_path_vec.push("");
+                    }
+                    continue;
+                }
+                ".." => {
+                    let mut last = out_path_vec.pop();
+
+                    if last == Some("") {
+                        last = out_path_vec.pop();
+                    }
+
+                    match (last, path_will_be_absolute, out_path_vec.is_empty()) {
+                        (Some("."), false, _) => out_path_vec.push(".."),
+                        (Some(".."), false, _) => {
+                            out_path_vec.push("..");
+                            out_path_vec.push("..");
+                        }
+                        (Some(_), true, _) => out_path_vec.push(""),
+                        (Some(_), false, false) => out_path_vec.push(""),
+                        (Some(_), false, true) => out_path_vec.push("."),
+                        (None, _, _) if strict => return Err(ResolveError::EscapesRoot),
+                        (None, _, _) => (),
+                    };
+                }
+                seg => {
+                    match out_path_vec.last().copied() {
+                        Some(".") if seg.is_empty() => continue,
+                        Some(".") | Some("") => {
+                            out_path_vec.pop();
+                        }
+                        _ => (),
+                    };
+                    out_path_vec.push(seg)
                 }
-                f.write_str(seg)?;
             }
         }
 
-        if let Some(query) = components.query {
-            f.write_char('?')?;
-            f.write_str(query)?;
+        if path_will_be_absolute {
+            f.write_char('/')?;
         }
 
-        if let Some(fragment) = components.fragment {
-            f.write_char('#')?;
-            f.write_str(fragment)?;
+        for (n, seg) in out_path_vec.into_iter().enumerate() {
+            if n != 0 {
+                f.write_char('/')?;
+            }
+            f.write_str(seg)?;
         }
-
-        Ok(())
     }
 
-    /// Creates a new [`UriRefBuf`] that contains the result of performing URI resolution with
-    /// `dest`.
-    #[cfg(feature = "std")]
-    fn resolved<T: AnyUriRef + ?Sized>(&self, dest: &T) -> Result<UriRefBuf, ResolveError> {
-        if dest.is_empty() {
-            return Ok(self.to_uri_ref_buf());
-        }
-
-        let mut ret = String::new();
-
-        self.write_resolved(dest, &mut ret)?;
+    if let Some(query) = components.query {
+        f.write_char('?')?;
+        f.write_str(query)?;
+    }
 
-        // SAFETY: `write_resolved` is guaranteed to write well-formed UriRefs.
-        Ok(unsafe { UriRefBuf::from_string_unchecked(ret) })
+    if let Some(fragment) = components.fragment {
+        f.write_char('#')?;
+        f.write_str(fragment)?;
     }
+
+    Ok(())
 }
 
 /// Blanket implementation of `AnyUriRefExt` for all `AnyUriRef` instances.
 impl<T: AnyUriRef + ?Sized> AnyUriRefExt for T {}
 
 /// Blanket implementation for Copy-On-Write types.
+#[cfg(feature = "std")]
 impl<'a, T: AnyUriRef + Clone + ?Sized> AnyUriRef for Cow<'a, T> {
     fn components(&self) -> UriRawComponents<'_> {
         self.deref().components()
@@ -644,6 +874,99 @@ mod test {
         }
     }
 
+    #[test]
+    fn make_relative() {
+        let uri_test_table = vec![
+            ("http://a/b/c/d;p?q", "http://a/b/c/g", Some(irel_ref!("g"))),
+            (
+                "http://a/b/c/d;p?q",
+                "http://a/b/c/g/",
+                Some(irel_ref!("g/")),
+            ),
+            ("http://a/b/c/d;p?q", "http://a/b/c/", Some(irel_ref!("."))),
+            ("http://a/b/c/d;p?q", "http://a/b/", Some(irel_ref!(".."))),
+            (
+                "http://a/b/c/d;p?q",
+                "http://a/b/g",
+                Some(irel_ref!("../g")),
+            ),
+            ("http://a/b/c/d;p?q", "http://a/g", Some(irel_ref!("/g"))),
+            ("http://a/b/c/d;p?q", "http://a/", Some(irel_ref!("/"))),
+            (
+                "http://a/b/c/d;p?q",
+                "http://a/b/c/d;p?y",
+                Some(irel_ref!("?y")),
+            ),
+            (
+                "http://a/b/c/d;p?q",
+                "http://a/b/c/d;p?q",
+                Some(irel_ref!("")),
+            ),
+            (
+                "http://a/b/c/d;p?q",
+                "http://a/b/c/d;p?q#s",
+                Some(irel_ref!("#s")),
+            ),
+            (
+                "http://a/b/c/d;p?q",
+                "http://a/b/c/d;p",
+                Some(irel_ref!("d;p")),
+            ),
+            (
+                "http://a/b/c/d;p?q",
+                "http://a/b/c/g?y#s",
+                Some(irel_ref!("g?y#s")),
+            ),
+            (
+                "http://a/b/c/d;p?q",
+                "http://a/b/c/g:h",
+                Some(irel_ref!("./g:h")),
+            ),
+            ("http://a/b/c/d;p?q", "http://a/b/c//g", None),
+            (
+                "http://a/b/c/d;p?q",
+                "http://a/x/y/z",
+                Some(irel_ref!("/x/y/z")),
+            ),
+            (
+                "http://a/b/c/d/e/f",
+                "http://a/b/c/d/x",
+                Some(irel_ref!("../x")),
+            ),
+            ("http://a/b/c/d;p?q", "HTTP://a/b/c/g", Some(irel_ref!("g"))),
+            ("http://a", "http://a/g", Some(irel_ref!("g"))),
+            ("b/c/d", "b/g", Some(irel_ref!("../g"))),
+            ("http://a/b/c/d;p?q", "http://b/b/c/d", None),
+            ("http://a/b/c/d;p?q", "coap://a/b/c/d", None),
+            ("http://a/b/c/d;p?q", "http://a/b/c/../g", None),
+            ("s:123", "s:123#frag", Some(irel_ref!("#frag"))),
+            ("s:123", "s:456", None),
+        ];
+
+        for (a, b, c) in uri_test_table {
+            let uri_a = UriRef::from_str(a).expect(a);
+            let uri_b = UriRef::from_str(b).expect(b);
+            let rel = uri_a.make_relative(uri_b);
+            assert_eq!(
+                rel.as_ref().map(RelRefBuf::as_str),
+                c.map(RelRef::as_str),
+                "uri_a.make_relative(): a:{} b:{} c:{:?}",
+                a,
+                b,
+                c
+            );
+            if let Some(rel) = rel {
+                assert!(
+                    uri_a.resolved(&rel).unwrap().eq_ignore_ascii_case(uri_b),
+                    "uri_a.resolved(): a:{} b:{} rel:{}",
+                    a,
+                    b,
+                    rel
+                );
+            }
+        }
+    }
+
     #[test]
     fn resolve_rfc3986_dot_dot() {
         let uri_test_table = vec![
@@ -718,4 +1041,50 @@ mod test {
             );
         }
     }
+
+    #[test]
+    fn resolve_strict() {
+        let uri_test_table = vec![
+            // Abnormal examples from RFC3986 Section 5.4.2, which climb above the root.
+            ("http://a/b/c/d;p?q", "../../../g", None),
+            ("http://a/b/c/d;p?q", "../../../../g", None),
+            ("http://a/b/c/d;p?q", "/../g", None),
+            ("http://a/b/c/d;p?q", "../../..", None),
+            ("http://a", "c/../../d", None),
+            ("/a/b", "../../c", None),
+            // These stay within the root.
+            (
+                "http://a/b/c/d;p?q",
+                "../../g",
+                Some(iuri_ref!("http://a/g")),
+            ),
+            ("http://a/b/c/d;p?q", "../..", Some(iuri_ref!("http://a/"))),
+            ("http://a/b/c/d;p?q", "/./g", Some(iuri_ref!("http://a/g"))),
+            (
+                "http://a/b/c/d;p?q",
+                "g/../..",
+                Some(iuri_ref!("http://a/b/")),
+            ),
+            (
+                "http://a/b/c/d;p?q",
+                "g:h/../..",
+                Some(iuri_ref!("g:h/../..")),
+            ),
+            ("/a/b", "../c", Some(iuri_ref!("/c"))),
+            ("a/b", "../../c", Some(iuri_ref!("../c"))),
+        ];
+
+        for (a, b, c) in uri_test_table {
+            let uri_a = UriRef::from_str(a).expect(a);
+            let uri_b = UriRef::from_str(b).expect(b);
+            assert_eq!(
+                uri_a.resolved_strict(uri_b),
+                c.map(|x| x.to_owned()).ok_or(ResolveError::EscapesRoot),
+                "uri_a.resolved_strict(): a:{} b:{} c:{:?}",
+                a,
+                b,
+                c
+            );
+        }
+    }
 }
@@ -13,8 +13,9 @@
 // limitations under the License.
 //
 
-use std::fmt;
-use std::ops::Range;
+use crate::escape::{DecodingError, UnescapeError};
+use core::fmt;
+use core::ops::Range;
 
 /// Error type for resolving a target URI against a base URI.
 ///
@@ -31,6 +32,17 @@ pub enum ResolveError {
 
     /// Unable to write to the given [`core::fmt::Write`] instance.
     WriteFailure,
+
+    /// The target URI-reference has more `..` segments than can be removed without
+    /// leaving the root it is being resolved within.
+    ///
+    /// Emitted by [`RelRef::resolved_within_root`], [`RelRefBuf::resolve_within_root`], and
+    /// [`AnyUriRefExt::resolved_strict`].
+    ///
+    /// [`AnyUriRefExt::resolved_strict`]: crate::AnyUriRefExt::resolved_strict
+    /// [`RelRef::resolved_within_root`]: crate::RelRef::resolved_within_root
+    /// [`RelRefBuf::resolve_within_root`]: crate::RelRefBuf::resolve_within_root
+    EscapesRoot,
 }
 
 impl fmt::Display for ResolveError {
@@ -41,6 +53,7 @@ impl fmt::Display for ResolveError {
                 "given uri-ref cannot be used as a base for the target uri-ref"
             ),
             Self::WriteFailure => write!(f, "unable to write to the given `fmt::Write` instance"),
+            Self::EscapesRoot => write!(f, "target uri-ref refers to a path outside of the root"),
         }
     }
 }
@@ -55,67 +68,140 @@ impl From<::core::fmt::Error> for ResolveError {
     }
 }
 
-/// URI parse error type.
+/// Identifies one of the components of a URI-reference.
 ///
-/// This type indicates the details of an error that occurs while parsing a URI.
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
-pub struct ParseError {
-    desc: ParseErrorKind,
-    span: Option<Range<usize>>,
+/// Used by [`ParseError`] to indicate which part of the input was being parsed
+/// when an error was encountered.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub enum UriComponent {
+    /// The scheme, like `http` or `coap`.
+    Scheme,
+    /// The entire authority, like `user@example.com:1234`.
+    Authority,
+    /// The userinfo part of the authority.
+    Userinfo,
+    /// The host part of the authority.
+    Host,
+    /// The port part of the authority.
+    Port,
+    /// The path.
+    Path,
+    /// The query, not including the leading `?`.
+    Query,
+    /// The fragment, not including the leading `#`.
+    Fragment,
 }
 
-impl ParseError {
-    /// Constructor for URI parse errors.
-    pub fn new(desc: &'static str, span: Option<Range<usize>>) -> ParseError {
-        ParseError {
-            desc: desc.into(),
-            span,
+impl UriComponent {
+    /// Returns a short, lowercase name for this component.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Scheme => "scheme",
+            Self::Authority => "authority",
+            Self::Userinfo => "userinfo",
+            Self::Host => "host",
+            Self::Port => "port",
+            Self::Path => "path",
+            Self::Query => "query",
+            Self::Fragment => "fragment",
         }
     }
 
-    /// The location in the input string of the error. Optional.
-    pub fn span(&self) -> Option<Range<usize>> {
-        self.span.clone()
-    }
+    /// Determines which component of `uri` contains the byte at `index`. If `relative`
+    /// is true, `uri` is assumed to be a relative reference with no scheme or authority.
+    ///
+    /// If `index` is equal to the length of `uri`, then this returns the component that
+    /// would contain any characters appended to `uri`.
+    pub(crate) fn containing(uri: &str, index: usize, relative: bool) -> UriComponent {
+        let mut start = 0;
 
-    /// A debugging description of the error.
-    pub fn desc(&self) -> &'static str {
-        self.desc.as_str()
+        if !relative {
+            if let Some(i) = uri.find(&[':', '/', '?', '#'][..]) {
+                if i > 0 && uri[i..].starts_with(':') && !uri[..i].contains('%') {
+                    if index < i {
+                        return UriComponent::Scheme;
+                    }
+                    start = i + 1;
+                }
+            }
+
+            if uri[start..].starts_with("//") {
+                start += 2;
+                let end = uri[start..]
+                    .find(&['/', '?', '#'][..])
+                    .map(|i| i + start)
+                    .unwrap_or_else(|| uri.len());
+                if index < end || end == uri.len() {
+                    return UriComponent::Authority;
+                }
+                start = end;
+            }
+        }
+
+        let rest = &uri[start..];
+        match (rest.find('?'), rest.find('#')) {
+            (_, Some(f)) if index >= start + f => UriComponent::Fragment,
+            (Some(q), Some(f)) if q < f && index >= start + q => UriComponent::Query,
+            (Some(q), None) if index >= start + q => UriComponent::Query,
+            _ => UriComponent::Path,
+        }
     }
 }
 
-impl fmt::Display for ParseError {
+impl fmt::Display for UriComponent {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.desc)
+        f.write_str(self.as_str())
     }
 }
 
-#[cfg(feature = "std")]
-impl ::std::error::Error for ParseError {}
+/// The specific cause of a [`ParseError`].
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ParseErrorKind {
+    /// Bad percent encoding.
+    InvalidEscape {
+        /// The underlying decoding error.
+        error: UnescapeError,
 
-impl From<crate::escape::UnescapeError> for ParseError {
-    fn from(error: crate::escape::UnescapeError) -> Self {
-        Self {
-            span: Some(error.index..error.index + 1),
-            desc: ParseErrorKind::EncodingError(error),
-        }
-    }
-}
+        /// The component containing the bad escape sequence, if known.
+        component: Option<UriComponent>,
+    },
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-enum ParseErrorKind {
-    /// Bad percent encoding or illegal characters
-    EncodingError(crate::escape::UnescapeError),
-    /// Missing scheme or authority
+    /// A character was found that is not allowed to appear unescaped in the given component.
+    IllegalCharacter {
+        /// The offending character.
+        char: char,
+
+        /// The component containing the offending character, if known.
+        component: Option<UriComponent>,
+    },
+
+    /// Invalid URI scheme.
+    InvalidScheme,
+
+    /// Invalid URI authority.
+    InvalidAuthority,
+
+    /// Invalid URI host, such as a malformed IPv6 address literal.
+    InvalidHost,
+
+    /// The given component is well-formed on its own, but it cannot be combined with
+    /// the other components without changing the meaning of the URI-reference.
+    InconsistentComponent(UriComponent),
+
+    /// Missing scheme or authority.
     MissingSchemeOrAuthority,
-    /// Cannot find URI components
+
+    /// Cannot find URI components.
     MissingUriComponents,
-    /// Invalid URI scheme
-    InvalidUriScheme,
-    /// Not a URI
+
+    /// Not a URI.
     InvalidUri,
-    #[allow(dead_code)]
-    Custom { desc: &'static str },
+
+    /// Some other error, described by the given string.
+    Custom {
+        /// Description of the error.
+        desc: &'static str,
+    },
 }
 
 impl From<&'static str> for ParseErrorKind {
@@ -123,32 +209,187 @@ impl From<&'static str> for ParseErrorKind {
         match desc {
             "Missing scheme or authority" => Self::MissingSchemeOrAuthority,
             "Cannot find URI components" => Self::MissingUriComponents,
-            "Invalid URI scheme" => Self::InvalidUriScheme,
+            "Invalid URI scheme" => Self::InvalidScheme,
+            "Invalid URI authority" => Self::InvalidAuthority,
+            "Invalid URI host" => Self::InvalidHost,
             "Not a URI" => Self::InvalidUri,
             _ => Self::Custom { desc },
         }
     }
 }
 
+impl From<UnescapeError> for ParseErrorKind {
+    fn from(error: UnescapeError) -> Self {
+        match error.inner() {
+            DecodingError::Space => Self::IllegalCharacter {
+                char: ' ',
+                component: None,
+            },
+            DecodingError::UnescapedAsciiControl(c) => Self::IllegalCharacter {
+                char: c,
+                component: None,
+            },
+            _ => Self::InvalidEscape {
+                error,
+                component: None,
+            },
+        }
+    }
+}
+
 impl ParseErrorKind {
+    /// A short, static description of this kind of error.
     pub fn as_str(&self) -> &'static str {
         match self {
-            Self::EncodingError(_) => "Bad percent encoding or illegal characters",
+            Self::InvalidEscape { .. } => "Bad percent encoding",
+            Self::IllegalCharacter { .. } => "Illegal character",
+            Self::InvalidScheme => "Invalid URI scheme",
+            Self::InvalidAuthority => "Invalid URI authority",
+            Self::InvalidHost => "Invalid URI host",
+            Self::InconsistentComponent(_) => "Inconsistent URI components",
             Self::MissingSchemeOrAuthority => "Missing scheme or authority",
             Self::MissingUriComponents => "Cannot find URI components",
-            Self::InvalidUriScheme => "Invalid URI scheme",
             Self::InvalidUri => "Not a URI",
             Self::Custom { desc } => desc,
         }
     }
+
+    /// The component that this error pertains to, if known.
+    pub fn component(&self) -> Option<UriComponent> {
+        match self {
+            Self::InvalidEscape { component, .. } | Self::IllegalCharacter { component, .. } => {
+                *component
+            }
+            Self::InvalidScheme => Some(UriComponent::Scheme),
+            Self::InvalidAuthority => Some(UriComponent::Authority),
+            Self::InvalidHost => Some(UriComponent::Host),
+            Self::InconsistentComponent(component) => Some(*component),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for ParseErrorKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        if let Self::EncodingError(e) = &self {
-            write!(f, "{}", e)
-        } else {
-            write!(f, "{}", self.as_str())
+        match self {
+            Self::InvalidEscape { error, .. } => write!(f, "{}", error)?,
+            Self::IllegalCharacter { char, .. } => write!(f, "{} {:?}", self.as_str(), char)?,
+            Self::InconsistentComponent(component) => {
+                write!(f, "{}: {}", self.as_str(), component)?;
+                return Ok(());
+            }
+            _ => return f.write_str(self.as_str()),
+        }
+
+        if let Some(component) = self.component() {
+            write!(f, " in {}", component)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// URI parse error type.
+///
+/// This type indicates the details of an error that occurs while parsing a URI.
+/// The cause of the error can be determined using [`ParseError::kind`], and
+/// the location in the input string using [`ParseError::span`].
+///
+/// ```
+/// # use async_coap_uri::*;
+/// let err = UriRef::from_str("http://example.com/a b").unwrap_err();
+///
+/// assert_eq!(err.component(), Some(UriComponent::Path));
+/// assert_eq!(err.span(), Some(20..21));
+/// assert_eq!(
+///     err.kind(),
+///     &ParseErrorKind::IllegalCharacter {
+///         char: ' ',
+///         component: Some(UriComponent::Path)
+///     }
+/// );
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct ParseError {
+    kind: ParseErrorKind,
+    span: Option<Range<usize>>,
+}
+
+impl ParseError {
+    /// Constructor for URI parse errors.
+    pub fn new(desc: &'static str, span: Option<Range<usize>>) -> ParseError {
+        ParseError {
+            kind: desc.into(),
+            span,
+        }
+    }
+
+    /// Constructs a URI parse error of the given kind.
+    pub fn with_kind(kind: ParseErrorKind, span: Range<usize>) -> ParseError {
+        ParseError {
+            kind,
+            span: Some(span),
+        }
+    }
+
+    /// The location in the input string of the error.
+    ///
+    /// Errors emitted while parsing by this crate will always have a span.
+    pub fn span(&self) -> Option<Range<usize>> {
+        self.span.clone()
+    }
+
+    /// The specific cause of the error.
+    pub fn kind(&self) -> &ParseErrorKind {
+        &self.kind
+    }
+
+    /// The component of the URI-reference that was being parsed when the error was
+    /// encountered, if known.
+    pub fn component(&self) -> Option<UriComponent> {
+        self.kind.component()
+    }
+
+    /// A debugging description of the error.
+    pub fn desc(&self) -> &'static str {
+        self.kind.as_str()
+    }
+
+    /// Replaces the span of this error with the result of passing it to `f`.
+    #[cfg(feature = "std")]
+    pub(crate) fn map_span<F: FnOnce(Range<usize>) -> Range<usize>>(mut self, f: F) -> ParseError {
+        self.span = self.span.map(f);
+        self
+    }
+
+    /// Attributes this error to `component`, where the component starts at `offset`
+    /// in the input string.
+    pub(crate) fn in_component(mut self, component: UriComponent, offset: usize) -> ParseError {
+        match &mut self.kind {
+            ParseErrorKind::InvalidEscape { component: c, .. }
+            | ParseErrorKind::IllegalCharacter { component: c, .. } => *c = Some(component),
+            _ => (),
+        }
+        self.span = self.span.map(|span| span.start + offset..span.end + offset);
+        self
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind)
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg(feature = "std")]
+impl ::std::error::Error for ParseError {}
+
+impl From<UnescapeError> for ParseError {
+    fn from(error: UnescapeError) -> Self {
+        Self {
+            span: Some(error.index..error.index + 1),
+            kind: error.into(),
         }
     }
 }
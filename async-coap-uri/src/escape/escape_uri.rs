@@ -13,10 +13,11 @@
 // limitations under the License.
 //
 
+use core::fmt::Display;
 use core::fmt::Write;
-use std::fmt::Display;
-use std::iter::FusedIterator;
-use std::str::from_utf8_unchecked;
+use core::iter::FusedIterator;
+#[cfg(feature = "std")]
+use core::str::from_utf8_unchecked;
 
 #[cfg(feature = "std")]
 use std::borrow::Cow;
@@ -110,6 +111,19 @@ impl NeedsEscape for EscapeUriAuthority {
     }
 }
 
+/// A zero-sized implementor of [`NeedsEscape`] for escaping a registered name in the
+/// host component.
+///
+/// Its behavior is subject to change and is not considered stable.
+#[doc(hidden)]
+#[derive(Default, Copy, Clone, Debug)]
+pub struct EscapeUriHost;
+impl NeedsEscape for EscapeUriHost {
+    fn char_needs_escape(c: char) -> bool {
+        !is_char_uri_unreserved(c) && !is_char_uri_sub_delim(c)
+    }
+}
+
 /// A zero-sized implementor of [`NeedsEscape`] for escaping query items.
 ///
 /// Its behavior is subject to change and is not considered stable.
@@ -149,7 +163,7 @@ impl NeedsEscape for EscapeUriFragment {
 /// [`escape_uri()`]: trait.StrExt.html#tymethod.escape_uri
 #[derive(Debug, Clone)]
 pub struct EscapeUri<'a, X: NeedsEscape = EscapeUriSegment> {
-    pub(super) iter: std::slice::Iter<'a, u8>,
+    pub(super) iter: core::slice::Iter<'a, u8>,
     pub(super) state: EscapeUriState,
     pub(super) needs_escape: X,
 }
@@ -218,6 +232,16 @@ impl<'a, X: NeedsEscape> EscapeUri<'a, X> {
             needs_escape: EscapeUriAuthority,
         }
     }
+
+    /// Converts this iterator into one optimized for escaping a registered name in the
+    /// host component.
+    pub fn for_host(self) -> EscapeUri<'a, EscapeUriHost> {
+        EscapeUri {
+            iter: self.iter,
+            state: self.state,
+            needs_escape: EscapeUriHost,
+        }
+    }
 }
 
 impl<'a, X: NeedsEscape> Display for EscapeUri<'a, X> {
@@ -113,6 +113,19 @@
 //! * [`try_to_string()`]\: Returns an unescaped [`String`] only if no encoding errors were present.
 //! * [`try_to_cow()`]\: Returns an unescaped [`Cow<str>`] only if no encoding errors were present.
 //!
+//! # Decoding Bytes
+//!
+//! Percent-encoded data isn't always UTF-8 text. For opaque data, like binary blobs in
+//! query parameters, [`UnescapeUri::bytes()`] returns an iterator over the decoded bytes,
+//! which yields an error with its position wherever the string is malformed:
+//!
+//! ```
+//! # use async_coap_uri::prelude::*;
+//! let bytes = "%DE%AD%BE%EF".unescape_uri().bytes().collect::<Result<Vec<u8>, _>>();
+//!
+//! assert_eq!(bytes, Ok(vec![0xDE, 0xAD, 0xBE, 0xEF]));
+//! ```
+//!
 //! [U+FFFD]: core::char::REPLACEMENT_CHARACTER
 //! [Unicode Control Pictures]: https://www.unicode.org/charts/PDF/U2400.pdf
 //! [`escape_uri()`]: #method.escape_uri
@@ -123,6 +136,7 @@
 //! [`try_to_cow()`]: struct.UnescapeUri.html#method.try_to_cow
 //! [`EscapeUri`]: struct.EscapeUri.html
 //! [`UnescapeUri`]: struct.UnescapeUri.html
+//! [`UnescapeUri::bytes()`]: struct.UnescapeUri.html#method.bytes
 //!
 mod escape_uri;
 pub use escape_uri::*;
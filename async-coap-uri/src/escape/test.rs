@@ -210,3 +210,39 @@ test_unescape_garbage!(truncated_utf8_1, "fan�say", "fan%E2%8say");
 test_unescape_garbage!(truncated_utf8_2, "fan�say", "fan%E2%82say");
 test_unescape_garbage!(truncated_utf8_3, "fan�say", "fan%E2%82%say");
 test_unescape_garbage!(bad_percent_escape, "bloat%1zface", "bloat%1zface");
+
+#[test]
+fn unescape_bytes() {
+    let bytes = |s: &str| s.unescape_uri().bytes().collect::<Result<Vec<u8>, _>>();
+
+    assert_eq!(Ok(b"a-simple-test".to_vec()), bytes("a-simple-test"));
+    assert_eq!(Ok(vec![0x00, 0x0A, 0x7F]), bytes("%00%0a%7F"));
+    assert_eq!(Ok(vec![b'x', 0xE2, 0xF2, b'y']), bytes("x%E2%F2y"));
+    assert_eq!(Ok("blåb".as_bytes().to_vec()), bytes("bl%C3%A5b"));
+    assert_eq!(Ok("blåb".as_bytes().to_vec()), bytes("blåb"));
+    assert_eq!(Ok(b"a/b".to_vec()), bytes("a%2Fb"));
+    assert_eq!(
+        Ok(b"a%2Fb".to_vec()),
+        "a%2Fb"
+            .unescape_uri()
+            .skip_slashes()
+            .bytes()
+            .collect::<Result<Vec<u8>, _>>()
+    );
+}
+
+#[test]
+fn unescape_bytes_errors() {
+    let results = |s: &str| {
+        s.unescape_uri()
+            .bytes()
+            .map(|x| x.map_err(|e| e.index))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(vec![Ok(b'a'), Err(1), Ok(b'b')], results("a b"));
+    assert_eq!(vec![Ok(b'a'), Err(1), Ok(b'b')], results("a\nb"));
+    assert_eq!(vec![Err(2), Ok(b'z'), Ok(b'y')], results("%1zy"));
+    assert_eq!(vec![Ok(b'a'), Err(1)], results("a%"));
+    assert_eq!(vec![Ok(b'a'), Err(1)], results("a%4"));
+}
@@ -13,14 +13,16 @@
 // limitations under the License.
 //
 
+use core::char::REPLACEMENT_CHARACTER;
+#[cfg(feature = "std")]
+use core::convert::TryInto;
+use core::fmt;
+use core::fmt::Display;
 use core::fmt::Write;
+use core::iter::FusedIterator;
+use core::str::{from_utf8, from_utf8_unchecked};
+#[cfg(feature = "std")]
 use std::borrow::Cow;
-use std::char::REPLACEMENT_CHARACTER;
-use std::convert::TryInto;
-use std::fmt;
-use std::fmt::Display;
-use std::iter::FusedIterator;
-use std::str::{from_utf8, from_utf8_unchecked};
 
 /// Used for determining how long a given UTF8 sequence is.
 /// Table values come from https://tools.ietf.org/html/rfc3629
@@ -57,7 +59,7 @@ static UTF8_CHAR_WIDTH: [u8; 256] = [
 /// [`first_error()`]: #method.first_error
 #[derive(Debug, Clone)]
 pub struct UnescapeUri<'a> {
-    pub(super) iter: std::str::Chars<'a>,
+    pub(super) iter: core::str::Chars<'a>,
     pub(super) iter_index: usize,
     pub(super) next_c: Option<(char, Option<char>)>,
     pub(super) had_error: Option<DecodingError>,
@@ -70,6 +72,7 @@ impl<'a> Display for UnescapeUri<'a> {
     }
 }
 
+#[cfg(feature = "std")]
 impl<'a> From<UnescapeUri<'a>> for Cow<'a, str> {
     fn from(iter: UnescapeUri<'a>) -> Self {
         iter.to_cow()
@@ -160,6 +163,42 @@ impl<'a> UnescapeUri<'a> {
         self.clone().try_into()
     }
 
+    /// Returns an iterator over the bytes of the decoded string, for data which isn't
+    /// necessarily UTF-8 text, like opaque blobs in query parameters.
+    ///
+    /// Unlike this iterator, percent-encoded bytes are decoded exactly as they are, even if
+    /// they aren't valid UTF-8 or are ASCII control codes. Instead of being replaced,
+    /// encoding errors are returned in place of bytes, along with their position.
+    /// [Skipping slashes] is preserved.
+    ///
+    /// The returned iterator starts at the current position of this iterator, so this is
+    /// usually called before this iterator is advanced.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use async_coap_uri::prelude::*;
+    /// let blob = "%00%FF%C3x";
+    /// assert_eq!(
+    ///     Ok(vec![0x00, 0xFF, 0xC3, b'x']),
+    ///     blob.unescape_uri().bytes().collect::<Result<Vec<u8>, _>>()
+    /// );
+    ///
+    /// let err = "ab%2".unescape_uri().bytes().collect::<Result<Vec<u8>, _>>().unwrap_err();
+    /// assert_eq!(2, err.index);
+    /// ```
+    ///
+    /// [Skipping slashes]: trait.StrExt.html#Skipping_Slashes
+    pub fn bytes(&self) -> UnescapeUriBytes<'a> {
+        UnescapeUriBytes {
+            iter: self.iter.clone(),
+            iter_index: self.iter_index,
+            pending: [0; 4],
+            pending_range: 0..0,
+            skip_slashes: self.skip_slashes,
+        }
+    }
+
     /// Checks to see if this iterator has the given *unescaped* prefix,
     /// and, if it does, returns the index of the end of the pattern in the haystack.
     ///
@@ -219,6 +258,106 @@ impl<'a> TryInto<String> for UnescapeUri<'a> {
     }
 }
 
+/// An iterator over the bytes of a percent-decoded string, constructed by
+/// [`UnescapeUri::bytes`].
+///
+/// Each item is either a decoded byte or an [`UnescapeError`] indicating where the string
+/// is malformed. After an error, decoding continues with the next character.
+#[derive(Debug, Clone)]
+pub struct UnescapeUriBytes<'a> {
+    iter: core::str::Chars<'a>,
+    iter_index: usize,
+    pending: [u8; 4],
+    pending_range: core::ops::Range<u8>,
+    skip_slashes: bool,
+}
+
+impl<'a> UnescapeUriBytes<'a> {
+    /// Indicates the number of characters that have been read by this iterator
+    /// from the source string.
+    pub fn index(&self) -> usize {
+        self.iter_index
+    }
+
+    /// Decodes the two hex digits following the `%` at `index`.
+    fn next_escaped(&mut self, index: usize) -> Result<u8, UnescapeError> {
+        let mut digits = [0u8; 2];
+
+        for (i, digit) in digits.iter_mut().enumerate() {
+            // Only consume the next character if it is valid, so that decoding continues
+            // from it after an error.
+            let mut iter = self.iter.clone();
+
+            match iter.next() {
+                Some(c) if c.is_ascii_hexdigit() => {
+                    *digit = c as u8;
+                    self.iter = iter;
+                    self.iter_index += 1;
+                }
+                Some(c) => {
+                    let err = DecodingError::InvalidEscape(c);
+                    return Err(UnescapeError::new(err, self.iter_index));
+                }
+                None => {
+                    let err = DecodingError::MissingChar(2 - i as u8);
+                    return Err(UnescapeError::new(err, index));
+                }
+            }
+        }
+
+        // Unwrap safety: Both digits were verified to be ASCII hex digits.
+        let nibble = |x: u8| (x as char).to_digit(16).unwrap() as u8;
+        let decoded = nibble(digits[0]) << 4 | nibble(digits[1]);
+
+        if self.skip_slashes && decoded == b'/' {
+            // Skip decoding escaped slashes.
+            self.pending[..2].copy_from_slice(&digits);
+            self.pending_range = 0..2;
+            return Ok(b'%');
+        }
+
+        Ok(decoded)
+    }
+}
+
+impl<'a> Iterator for UnescapeUriBytes<'a> {
+    type Item = Result<u8, UnescapeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(i) = self.pending_range.next() {
+            return Some(Ok(self.pending[i as usize]));
+        }
+
+        let c = self.iter.next()?;
+        let index = self.iter_index;
+        self.iter_index += 1;
+
+        Some(match c {
+            '%' => self.next_escaped(index),
+            ' ' => Err(UnescapeError::new(DecodingError::Space, index)),
+            c if c.is_ascii_control() => Err(UnescapeError::new(
+                DecodingError::UnescapedAsciiControl(c),
+                index,
+            )),
+            c if c.is_ascii() => Ok(c as u8),
+            c => {
+                // Pass along the UTF-8 encoding of unescaped non-ASCII characters.
+                let len = c.encode_utf8(&mut self.pending).len();
+                self.pending_range = 1..len as u8;
+                Ok(self.pending[0])
+            }
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let pending = self.pending_range.len();
+        let remaining = self.iter.as_str().len();
+        (pending + remaining.div_ceil(3), Some(pending + remaining))
+    }
+}
+
+impl<'a> FusedIterator for UnescapeUriBytes<'a> {}
+
 /// This is returned, when an error occured while decoding.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct UnescapeError {
@@ -240,6 +379,10 @@ impl UnescapeError {
     pub(crate) fn new(inner: DecodingError, index: usize) -> Self {
         Self { inner, index }
     }
+
+    pub(crate) fn inner(&self) -> DecodingError {
+        self.inner
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
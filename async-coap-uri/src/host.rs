@@ -0,0 +1,257 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// The parsed *host* component of a URI's authority, as defined by [RFC3986 Section 3.2.2].
+///
+/// Instances of this type are usually obtained by calling [`UriRawComponents::host`]:
+///
+/// ```
+/// # use async_coap_uri::*;
+/// # use std::net::Ipv6Addr;
+/// let uri = uri!("coap://[::1]:5683/a/b");
+///
+/// assert_eq!(
+///     uri.components().host(),
+///     Some(UriHost::Ipv6 {
+///         addr: Ipv6Addr::LOCALHOST,
+///         zone: None
+///     })
+/// );
+///
+/// let uri = uri!("coap://example%2Ecom/a/b");
+///
+/// assert_eq!(
+///     uri.components().host(),
+///     Some(UriHost::RegName("example.com".into()))
+/// );
+/// ```
+///
+/// [RFC3986 Section 3.2.2]: https://tools.ietf.org/html/rfc3986#section-3.2.2
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum UriHost<'a> {
+    /// A registered name, like `example.com`. The name is percent-decoded.
+    RegName(Cow<'a, str>),
+
+    /// An IPv4 address in dotted-decimal form, like `192.168.1.1`.
+    Ipv4(Ipv4Addr),
+
+    /// An IPv6 address literal, like `[2001:db8::1]` or `[fe80::1%25en0]`.
+    Ipv6 {
+        /// The IPv6 address.
+        addr: Ipv6Addr,
+
+        /// The percent-decoded zone identifier from [RFC6874], if present.
+        ///
+        /// [RFC6874]: https://tools.ietf.org/html/rfc6874
+        zone: Option<Cow<'a, str>>,
+    },
+
+    /// An `IPvFuture` literal, like `[v1.fe80::a+en1]`. The contained string slice
+    /// does not include the surrounding brackets.
+    IpvFuture(&'a str),
+}
+
+impl<'a> UriHost<'a> {
+    /// Parses the given raw (percent-encoded) host, as it would appear in a URI.
+    ///
+    /// IP literals must be surrounded by brackets (`[` and `]`).
+    pub fn from_raw(raw: &'a str) -> Result<UriHost<'a>, ParseError> {
+        let err = || {
+            Err(ParseError::with_kind(
+                ParseErrorKind::InvalidHost,
+                0..raw.len(),
+            ))
+        };
+
+        if raw.starts_with('[') {
+            if !raw.ends_with(']') || raw.len() < 2 {
+                return err();
+            }
+
+            let literal = &raw[1..raw.len() - 1];
+
+            if literal.starts_with('v') || literal.starts_with('V') {
+                if is_ipv_future(literal) {
+                    return Ok(UriHost::IpvFuture(literal));
+                }
+            } else {
+                let (addr, zone) = match literal.find("%25") {
+                    Some(i) => (&literal[..i], Some(&literal[i + 3..])),
+                    None => (literal, None),
+                };
+
+                let zone = match zone {
+                    Some(zone) if zone.is_empty() || zone.contains(&[':', '[', ']'][..]) => {
+                        return err();
+                    }
+                    Some(zone) => match zone.unescape_uri().try_to_cow() {
+                        Ok(zone) => Some(zone),
+                        Err(_) => return err(),
+                    },
+                    None => None,
+                };
+
+                if let Ok(addr) = addr.parse::<Ipv6Addr>() {
+                    return Ok(UriHost::Ipv6 { addr, zone });
+                }
+            }
+
+            return err();
+        }
+
+        if raw.contains(&['[', ']', ':', '@', '/', '?', '#'][..]) {
+            return err();
+        }
+
+        if let Ok(addr) = raw.parse::<Ipv4Addr>() {
+            return Ok(UriHost::Ipv4(addr));
+        }
+
+        match raw.unescape_uri().try_to_cow() {
+            Ok(name) => Ok(UriHost::RegName(name)),
+            Err(e) => Err(ParseError::from(e).in_component(UriComponent::Host, 0)),
+        }
+    }
+
+    /// Returns the IP address of this host, if it is an IPv4 or IPv6 address.
+    ///
+    /// Note that any IPv6 zone identifier is not included.
+    pub fn ip_addr(&self) -> Option<IpAddr> {
+        match self {
+            UriHost::Ipv4(addr) => Some(IpAddr::V4(*addr)),
+            UriHost::Ipv6 { addr, .. } => Some(IpAddr::V6(*addr)),
+            _ => None,
+        }
+    }
+
+    /// Returns the registered name of this host, if it is a registered name.
+    pub fn reg_name(&self) -> Option<&str> {
+        match self {
+            UriHost::RegName(name) => Some(name),
+            _ => None,
+        }
+    }
+}
+
+/// Renders the host in a form suitable for name resolution: registered names
+/// are shown percent-decoded and IP literals are shown without brackets.
+impl fmt::Display for UriHost<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UriHost::RegName(name) => f.write_str(name),
+            UriHost::Ipv4(addr) => write!(f, "{}", addr),
+            UriHost::Ipv6 { addr, zone: None } => write!(f, "{}", addr),
+            UriHost::Ipv6 {
+                addr,
+                zone: Some(zone),
+            } => write!(f, "{}%{}", addr, zone),
+            UriHost::IpvFuture(literal) => f.write_str(literal),
+        }
+    }
+}
+
+/// Checks `literal` against the `IPvFuture` rule from RFC3986:
+///
+/// ```text
+/// IPvFuture  = "v" 1*HEXDIG "." 1*( unreserved / sub-delims / ":" )
+/// ```
+fn is_ipv_future(literal: &str) -> bool {
+    let mut parts = literal[1..].splitn(2, '.');
+    let version = parts.next().unwrap_or("");
+    let address = match parts.next() {
+        Some(x) => x,
+        None => return false,
+    };
+
+    !version.is_empty()
+        && version.chars().all(|c| c.is_ascii_hexdigit())
+        && !address.is_empty()
+        && address
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-._~!$&'()*+,;=:".contains(c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_raw() {
+        assert_eq!(
+            Ok(UriHost::RegName("example.com".into())),
+            UriHost::from_raw("example.com")
+        );
+        assert_eq!(Ok(UriHost::RegName("".into())), UriHost::from_raw(""));
+        assert_eq!(
+            Ok(UriHost::Ipv4(Ipv4Addr::new(192, 168, 1, 1))),
+            UriHost::from_raw("192.168.1.1")
+        );
+        assert_eq!(
+            Ok(UriHost::Ipv6 {
+                addr: Ipv6Addr::LOCALHOST,
+                zone: None
+            }),
+            UriHost::from_raw("[::1]")
+        );
+        assert_eq!(
+            Ok(UriHost::Ipv6 {
+                addr: "::ffff:192.0.2.1".parse().unwrap(),
+                zone: None
+            }),
+            UriHost::from_raw("[::ffff:192.0.2.1]")
+        );
+        assert_eq!(
+            Ok(UriHost::Ipv6 {
+                addr: "fe80::1".parse().unwrap(),
+                zone: Some("en2".into())
+            }),
+            UriHost::from_raw("[fe80::1%25en2]")
+        );
+        assert_eq!(
+            "fe80::1%en2",
+            UriHost::from_raw("[fe80::1%25en2]").unwrap().to_string()
+        );
+        assert_eq!(
+            Ok(UriHost::IpvFuture("v1.fe80::a+en1")),
+            UriHost::from_raw("[v1.fe80::a+en1]")
+        );
+    }
+
+    #[test]
+    fn from_raw_invalid() {
+        assert!(UriHost::from_raw("[]").is_err());
+        assert!(UriHost::from_raw("[::1").is_err());
+        assert!(UriHost::from_raw("[example.com]").is_err());
+        assert!(UriHost::from_raw("[::1::2]").is_err());
+        assert!(UriHost::from_raw("[fe80::1%en2]").is_err());
+        assert!(UriHost::from_raw("[fe80::1%25]").is_err());
+        assert!(UriHost::from_raw("[1.2.3.4]").is_err());
+        assert!(UriHost::from_raw("[v1]").is_err());
+        assert!(UriHost::from_raw("[v.abc]").is_err());
+        assert!(UriHost::from_raw("[vx.abc]").is_err());
+        assert!(UriHost::from_raw("[v1.a/b]").is_err());
+        assert!(UriHost::from_raw("a]b").is_err());
+        assert!(UriHost::from_raw("%zz").is_err());
+
+        assert_eq!(
+            Some(UriComponent::Host),
+            UriHost::from_raw("[::1::2]").unwrap_err().component()
+        );
+    }
+}
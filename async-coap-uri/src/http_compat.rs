@@ -0,0 +1,139 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+use std::convert::TryFrom;
+
+/// Writes `uri` in a form that will be interpreted the same way by this crate.
+///
+/// An [`http::Uri`] in *authority-form* (like `example.com:80`) has no leading `//`, so it
+/// would otherwise be mistaken for a URI with a scheme of `example.com`.
+fn http_uri_to_string(uri: &::http::Uri) -> String {
+    match (uri.scheme(), uri.authority()) {
+        (None, Some(authority)) => format!("//{}{}", authority, uri.path()),
+        _ => uri.to_string(),
+    }
+}
+
+impl TryFrom<&::http::Uri> for UriRefBuf {
+    type Error = ParseError;
+
+    fn try_from(uri: &::http::Uri) -> Result<Self, Self::Error> {
+        UriRefBuf::from_string(http_uri_to_string(uri))
+    }
+}
+
+impl TryFrom<::http::Uri> for UriRefBuf {
+    type Error = ParseError;
+
+    fn try_from(uri: ::http::Uri) -> Result<Self, Self::Error> {
+        UriRefBuf::try_from(&uri)
+    }
+}
+
+impl TryFrom<&::http::Uri> for UriBuf {
+    type Error = ParseError;
+
+    fn try_from(uri: &::http::Uri) -> Result<Self, Self::Error> {
+        UriBuf::from_string(http_uri_to_string(uri))
+    }
+}
+
+impl TryFrom<::http::Uri> for UriBuf {
+    type Error = ParseError;
+
+    fn try_from(uri: ::http::Uri) -> Result<Self, Self::Error> {
+        UriBuf::try_from(&uri)
+    }
+}
+
+impl TryFrom<&UriRef> for ::http::Uri {
+    type Error = ::http::uri::InvalidUri;
+
+    fn try_from(uri_ref: &UriRef) -> Result<Self, Self::Error> {
+        ::http::Uri::try_from(uri_ref.as_str())
+    }
+}
+
+impl TryFrom<&Uri> for ::http::Uri {
+    type Error = ::http::uri::InvalidUri;
+
+    fn try_from(uri: &Uri) -> Result<Self, Self::Error> {
+        ::http::Uri::try_from(uri.as_str())
+    }
+}
+
+impl TryFrom<&UriRefBuf> for ::http::Uri {
+    type Error = ::http::uri::InvalidUri;
+
+    fn try_from(uri_ref: &UriRefBuf) -> Result<Self, Self::Error> {
+        ::http::Uri::try_from(uri_ref.as_str())
+    }
+}
+
+impl TryFrom<&UriBuf> for ::http::Uri {
+    type Error = ::http::uri::InvalidUri;
+
+    fn try_from(uri: &UriBuf) -> Result<Self, Self::Error> {
+        ::http::Uri::try_from(uri.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_http_uri() {
+        let uri: ::http::Uri = "http://example.com:8080/a/b?c".parse().unwrap();
+        assert_eq!(
+            UriBuf::try_from(&uri).unwrap(),
+            uri!("http://example.com:8080/a/b?c")
+        );
+
+        let uri: ::http::Uri = "/a/b?c".parse().unwrap();
+        assert_eq!(UriRefBuf::try_from(uri).unwrap(), uri_ref!("/a/b?c"));
+
+        let uri: ::http::Uri = "example.com:8080".parse().unwrap();
+        assert_eq!(
+            UriRefBuf::try_from(&uri).unwrap(),
+            uri_ref!("//example.com:8080")
+        );
+        assert_eq!(UriBuf::try_from(&uri).unwrap(), uri!("//example.com:8080"));
+
+        let uri: ::http::Uri = "/a/b".parse().unwrap();
+        assert!(UriBuf::try_from(&uri).is_err());
+    }
+
+    #[test]
+    fn to_http_uri() {
+        let uri = ::http::Uri::try_from(uri!("http://example.com:8080/a/b?c")).unwrap();
+        assert_eq!(Some("example.com"), uri.host());
+        assert_eq!(Some(8080), uri.port_u16());
+        assert_eq!("/a/b", uri.path());
+        assert_eq!(Some("c"), uri.query());
+
+        let uri = ::http::Uri::try_from(uri_ref!("/a%20b?c")).unwrap();
+        assert_eq!("/a%20b", uri.path());
+
+        let uri_buf = UriBuf::from_str("coap://example.com/").unwrap();
+        assert_eq!(
+            "coap://example.com/",
+            ::http::Uri::try_from(&uri_buf).unwrap().to_string()
+        );
+
+        assert!(::http::Uri::try_from(uri_ref!("a/b")).is_err());
+    }
+}
@@ -0,0 +1,207 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+use core::ops::Range;
+use std::fmt::Write;
+
+/// Determines how strictly a string is checked when it is parsed into one of the
+/// owned URI types.
+///
+/// Used by [`UriRefBuf::from_str_with_mode`], [`UriBuf::from_str_with_mode`], and
+/// [`RelRefBuf::from_str_with_mode`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ParseMode {
+    /// The input must already be a well-formed URI-reference. This is the same behavior
+    /// as methods like [`UriRefBuf::from_str`].
+    Strict,
+
+    /// Characters that are not allowed to appear unescaped in a URI-reference are
+    /// percent-encoded instead of causing an error. Each escaped character is reported
+    /// as a [`LenientFix`].
+    ///
+    /// This fixes unescaped spaces, ASCII control characters, the characters
+    /// `"`, `<`, `>`, `\`, `^`, `` ` ``, `{`, `|`, and `}`, as well as any `%` that isn't
+    /// followed by two hex digits. Other problems, like an invalid scheme or a
+    /// percent-encoded ASCII control character, are still reported as errors.
+    Lenient,
+}
+
+/// The default parse mode is [`ParseMode::Strict`].
+impl Default for ParseMode {
+    fn default() -> Self {
+        ParseMode::Strict
+    }
+}
+
+/// Describes a character that was percent-encoded while parsing in [`ParseMode::Lenient`].
+///
+/// ```
+/// # use async_coap_uri::*;
+/// let (uri, fixes) =
+///     UriRefBuf::from_str_with_mode("http://example.com/a b|c", ParseMode::Lenient).unwrap();
+///
+/// assert_eq!(uri, uri_ref!("http://example.com/a%20b%7Cc"));
+/// assert_eq!(fixes.len(), 2);
+/// assert_eq!(fixes[0].char(), ' ');
+/// assert_eq!(fixes[0].span(), 20..21);
+/// assert_eq!(fixes[0].component(), UriComponent::Path);
+/// assert_eq!(fixes[1].char(), '|');
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct LenientFix {
+    char: char,
+    span: Range<usize>,
+    component: UriComponent,
+}
+
+impl LenientFix {
+    /// The character that was percent-encoded.
+    pub fn char(&self) -> char {
+        self.char
+    }
+
+    /// The location of the character in the original input string.
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    /// The component of the URI-reference that contained the character.
+    pub fn component(&self) -> UriComponent {
+        self.component
+    }
+}
+
+impl std::fmt::Display for LenientFix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "escaped {:?} at {} in {}",
+            self.char, self.span.start, self.component
+        )
+    }
+}
+
+fn needs_lenient_escape(s: &str, i: usize, c: char) -> bool {
+    match c {
+        '%' => match s.as_bytes().get(i + 1..i + 3) {
+            Some(hex) => !hex.iter().all(u8::is_ascii_hexdigit),
+            None => true,
+        },
+        ' ' | '"' | '<' | '>' | '\\' | '^' | '`' | '{' | '|' | '}' => true,
+        c => c.is_ascii_control(),
+    }
+}
+
+/// Percent-encodes every character in `s` that would otherwise prevent it from being
+/// parsed. If `relative` is true, `s` is assumed to be a relative reference when
+/// determining the components of the fixes.
+pub(crate) fn lenient_escape(s: &str, relative: bool) -> (String, Vec<LenientFix>) {
+    let mut ret = String::with_capacity(s.len());
+    let mut fixes = Vec::new();
+
+    for (i, c) in s.char_indices() {
+        if needs_lenient_escape(s, i, c) {
+            // All of the characters we escape are ASCII, so they occupy a single byte.
+            write!(ret, "%{:02X}", c as u8).unwrap();
+            fixes.push(LenientFix {
+                char: c,
+                span: i..i + 1,
+                component: UriComponent::containing(s, i, relative),
+            });
+        } else {
+            ret.push(c);
+        }
+    }
+
+    (ret, fixes)
+}
+
+/// Maps the span of an error encountered while parsing the output of [`lenient_escape`]
+/// back onto the original input string.
+pub(crate) fn unmap_lenient_error(err: ParseError, fixes: &[LenientFix]) -> ParseError {
+    // Each fix expanded a single byte into three.
+    let unmap = |index: usize| {
+        let mut shift = 0;
+        for fix in fixes {
+            let escaped_start = fix.span.start + shift;
+            if index <= escaped_start {
+                break;
+            }
+            if index < escaped_start + 3 {
+                return fix.span.end;
+            }
+            shift += 2;
+        }
+        index - shift
+    };
+
+    err.map_span(|span| unmap(span.start)..unmap(span.end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lenient_escape() {
+        let (escaped, fixes) = super::lenient_escape("a b", true);
+        assert_eq!("a%20b", escaped);
+        assert_eq!(1, fixes.len());
+
+        let (escaped, fixes) = super::lenient_escape("http://h/%zz?q={x}#%2", false);
+        assert_eq!("http://h/%25zz?q=%7Bx%7D#%252", escaped);
+        assert_eq!(
+            vec![
+                ('%', 9..10, UriComponent::Path),
+                ('{', 15..16, UriComponent::Query),
+                ('}', 17..18, UriComponent::Query),
+                ('%', 19..20, UriComponent::Fragment),
+            ],
+            fixes
+                .iter()
+                .map(|f| (f.char(), f.span(), f.component()))
+                .collect::<Vec<_>>()
+        );
+
+        let (escaped, fixes) = super::lenient_escape("/a/%41/b%c3%a9", true);
+        assert_eq!("/a/%41/b%c3%a9", escaped);
+        assert!(fixes.is_empty());
+    }
+
+    #[test]
+    fn from_str_with_mode() {
+        assert!(UriRefBuf::from_str_with_mode("a b", ParseMode::Strict).is_err());
+        assert_eq!(
+            RelRefBuf::from_str_with_mode("a b", ParseMode::Lenient)
+                .unwrap()
+                .0,
+            rel_ref!("a%20b")
+        );
+
+        let (uri, fixes) = UriBuf::from_str_with_mode("coap://h/a|b", ParseMode::Lenient).unwrap();
+        assert_eq!(uri, uri!("coap://h/a%7Cb"));
+        assert_eq!(1, fixes.len());
+        assert!(UriBuf::from_str_with_mode("/a b", ParseMode::Lenient).is_err());
+    }
+
+    #[test]
+    fn lenient_error_span() {
+        let err = UriRefBuf::from_str_with_mode("a b|c/%00", ParseMode::Lenient).unwrap_err();
+        let strict_err = UriRefBuf::from_str("a_b_c/%00").unwrap_err();
+        assert_eq!(strict_err.span(), err.span());
+        assert_eq!(Some(UriComponent::Path), err.component());
+    }
+}
@@ -98,6 +98,69 @@
 //! isn't safe. So while there is a [`RelRef.as_uri_ref()`], it returns a `Cow<UriRef>` instead of
 //! a [`&UriRef`]. For more information, see [this section](struct.RelRef.html#relref-and-deref).
 //!
+//! ## Comparison and Ordering
+//!
+//! All of the URI types can be compared with each other, as well as with [`str`] and
+//! [`String`], in both borrowed and owned forms. Comparisons and ordering are lexicographic
+//! on the raw (escaped) string: no normalization is performed, so `/a%2Fb` and `/a%2fb`
+//! are not equal. Because the owned types implement [`Borrow`] for their unsized
+//! counterparts, they can be used as keys in a [`BTreeMap`] or [`HashMap`] and looked up
+//! using a borrowed reference.
+//!
+//! ```
+//! use async_coap_uri::prelude::*;
+//! use std::collections::BTreeMap;
+//!
+//! let mut map = BTreeMap::new();
+//! map.insert(uri!("coap://example.com/").to_owned(), 1);
+//!
+//! assert_eq!(map.get(uri!("coap://example.com/")), Some(&1));
+//! assert_eq!(uri!("coap://example.com/"), map.keys().next().unwrap().clone());
+//! assert!(rel_ref!("a/b") < rel_ref!("a/c"));
+//! ```
+//!
+//! ## Interoperability with `url`
+//!
+//! When the `url` feature is enabled, [`TryFrom`] conversions are provided in both directions
+//! between [`url::Url`] and [`UriBuf`]/[`UriRefBuf`]. Conversions into [`url::Url`] are
+//! also provided for [`&Uri`] and [`&UriRef`]. Converting a relative reference into a
+//! [`url::Url`] will fail, since a [`url::Url`] must always have a scheme.
+//!
+//! [`TryFrom`]: core::convert::TryFrom
+//! [`url::Url`]: https://docs.rs/url/2/url/struct.Url.html
+//!
+//! ## Interoperability with `http`
+//!
+//! When the `http` feature is enabled, [`TryFrom`] conversions are provided in both
+//! directions between [`http::Uri`] and [`UriBuf`]/[`UriRefBuf`], as well as from
+//! [`&Uri`] and [`&UriRef`] into [`http::Uri`]. An [`http::Uri`] in *authority-form*
+//! (like `example.com:80`) is converted into a network path (`//example.com:80`).
+//!
+//! ```
+//! # #[cfg(feature = "http")] {
+//! use async_coap_uri::prelude::*;
+//! use std::convert::TryFrom;
+//!
+//! let http_uri = http::Uri::try_from(uri!("http://example.com/a/b?c")).unwrap();
+//! assert_eq!(http_uri.path(), "/a/b");
+//!
+//! let uri = UriBuf::try_from(&http_uri).unwrap();
+//! assert_eq!(uri, uri!("http://example.com/a/b?c"));
+//! # }
+//! ```
+//!
+//! [`http::Uri`]: https://docs.rs/http/0.2/http/uri/struct.Uri.html
+//!
+//! ## Property testing
+//!
+//! When the `proptest-support` feature is enabled, the [`proptest_support`] module provides
+//! [`proptest`] strategies for [`UriRefBuf`] and [`RelRefBuf`] (including implementations of
+//! `Arbitrary`), as well as helpers for checking the invariants of the trimming and
+//! resolution methods against arbitrary references.
+//!
+//! [`proptest`]: https://docs.rs/proptest/1/proptest/
+//! [`proptest_support`]: proptest_support/index.html
+//!
 //! ## URI "Literals"
 //!
 //! For cases where you need a URI "literal", you can use the [`uri_ref!`], [`rel_ref!`],
@@ -121,6 +184,28 @@
 //! let x = uri!("%00 invalid %ff");
 //! ```
 //!
+//! ## Features
+//!
+//! The core of this crate (the unsized URI types, parsing, escaping, and unescaping)
+//! doesn't depend on any other crates, and doesn't need `std`. The rest is layered on top
+//! of it with these features, which are enabled by default:
+//!
+//! * `std`: The owned types like [`UriBuf`], percent-decoded accessors which return
+//!   [`Cow<str>`], URI resolution, and the `*_format!` macros. Without it, the crate is
+//!   `no_std`, and IP literal hosts are only checked for matching brackets.
+//! * `macros`: Checking the literals given to [`uri!`], [`uri_ref!`], and [`rel_ref!`] at
+//!   compile time, which needs a procedural macro crate. Without it, only the unchecked
+//!   forms of these macros (like `uri!(unsafe "coap://example.com/")`) are available.
+//!
+//! To use only the core, disable the default features:
+//!
+//! ```toml
+//! [dependencies]
+//! async-coap-uri = { version = "0.1.0", default-features = false }
+//! ```
+//!
+//! [`Cow<str>`]: std::borrow::Cow
+//!
 //! [^1]: This unsized/sized pattern is useful because there are often cases where you would want to have
 //! a method or function take a URI as an argument. If you just passed a `&str` or a [`String`],
 //! you would need to verify that the URI was well-formed each time the method or function was
@@ -141,6 +226,9 @@
 //! [`&RelRef`]: RelRef
 //! [`&UriRef`]: UriRef
 //! [`&str`]: str
+//! [`Borrow`]: core::borrow::Borrow
+//! [`BTreeMap`]: std::collections::BTreeMap
+//! [`HashMap`]: std::collections::HashMap
 //! [`Deref<Target=str>`]: core::ops::Deref
 //! [`Deref<Target=UriRef>`]: core::ops::Deref
 //! [`len()`]: https://doc.rust-lang.org/nightly/std/primitive.str.html#method.len
@@ -156,10 +244,6 @@
 #![warn(clippy::all)]
 #![cfg_attr(not(feature = "std"), no_std)]
 
-#[cfg(feature = "std")]
-#[macro_use]
-extern crate lazy_static;
-
 pub mod escape;
 use escape::*;
 
@@ -183,13 +267,33 @@ pub use uri::Uri;
 mod uri_type;
 pub use uri_type::UriType;
 
+mod stable_hash;
+pub use stable_hash::StableHasher;
+
+#[cfg(feature = "std")]
+mod uri_diff;
+#[cfg(feature = "std")]
+pub use uri_diff::UriDiff;
+
 mod any_uri_ref;
 pub use any_uri_ref::AnyUriRef;
 pub use any_uri_ref::AnyUriRefExt;
 pub use any_uri_ref::UriDisplay;
 
+#[cfg(feature = "std")]
+mod host;
+#[cfg(feature = "std")]
+pub use host::UriHost;
+
 mod error;
-pub use error::{ParseError, ResolveError};
+pub use error::{ParseError, ParseErrorKind, ResolveError, UriComponent};
+
+#[cfg(feature = "std")]
+mod lenient;
+#[cfg(feature = "std")]
+pub(crate) use lenient::{lenient_escape, unmap_lenient_error};
+#[cfg(feature = "std")]
+pub use lenient::{LenientFix, ParseMode};
 
 #[cfg(feature = "std")]
 mod rel_ref_buf;
@@ -202,14 +306,26 @@ mod uri_buf;
 pub use uri_buf::UriBuf;
 
 #[cfg(feature = "std")]
-mod uri_unescape_buf;
+mod small_string;
 #[cfg(feature = "std")]
-pub use uri_unescape_buf::UriUnescapeBuf;
+use small_string::SmallString;
 
 #[cfg(feature = "std")]
-mod regexes;
+mod uri_unescape_buf;
 #[cfg(feature = "std")]
-pub(crate) use regexes::*;
+pub use uri_unescape_buf::UriUnescapeBuf;
+
+#[cfg(all(feature = "url", feature = "std"))]
+mod url_compat;
+
+#[cfg(all(feature = "http", feature = "std"))]
+mod http_compat;
+
+#[cfg(feature = "proptest-support")]
+pub mod proptest_support;
+
+mod split;
+pub(crate) use split::{is_valid_scheme, SplitAuthority, SplitUriRef};
 
 #[cfg(test)]
 mod test;
@@ -232,23 +348,26 @@ pub type UriRefCow<'a> = Cow<'a, UriRef>;
 #[cfg(feature = "std")]
 pub type RelRefCow<'a> = Cow<'a, RelRef>;
 
-use proc_macro_hack::proc_macro_hack;
-
 /// Used by the `uri` macro to verify correctness at compile-time.
 #[doc(hidden)]
-#[proc_macro_hack]
+#[cfg(feature = "macros")]
 pub use async_coap_uri_macros::assert_uri_literal;
 
 /// Used by the `uri_ref` macro to verify correctness at compile-time.
 #[doc(hidden)]
-#[proc_macro_hack]
+#[cfg(feature = "macros")]
 pub use async_coap_uri_macros::assert_uri_ref_literal;
 
 /// Used by the `rel_ref` macro to verify correctness at compile-time.
 #[doc(hidden)]
-#[proc_macro_hack]
+#[cfg(feature = "macros")]
 pub use async_coap_uri_macros::assert_rel_ref_literal;
 
+/// Used by the checked format macros to verify correctness at compile-time.
+#[doc(hidden)]
+#[cfg(all(feature = "macros", feature = "std"))]
+pub use async_coap_uri_macros::uri_format_checked as _uri_format_checked;
+
 #[doc(hidden)]
 pub mod prelude {
     pub use super::escape::StrExt;
@@ -257,7 +376,8 @@ pub mod prelude {
     pub use super::{AnyUriRef, AnyUriRefExt};
     pub use super::{RelRef, Uri, UriRef};
 
-    pub use {assert_rel_ref_literal, assert_uri_literal, assert_uri_ref_literal};
+    #[doc(hidden)]
+    pub use super::{assert_rel_ref_literal, assert_uri_literal, assert_uri_ref_literal};
 
     #[cfg(feature = "std")]
     pub use super::{RelRefBuf, UriBuf, UriRefBuf};
@@ -267,4 +387,7 @@ pub mod prelude {
 
     #[cfg(feature = "std")]
     pub use super::{rel_ref_format, uri_format, uri_ref_format};
+
+    #[cfg(feature = "std")]
+    pub use super::{rel_ref_format_checked, uri_format_checked, uri_ref_format_checked};
 }
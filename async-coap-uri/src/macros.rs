@@ -17,11 +17,54 @@
 
 pub use super::{impl_uri_buf_traits, impl_uri_traits};
 pub use super::{rel_ref, uri, uri_ref};
+#[cfg(feature = "std")]
 pub use super::{rel_ref_format, uri_format, uri_ref_format};
+#[cfg(feature = "std")]
+pub use super::{rel_ref_format_checked, uri_format_checked, uri_ref_format_checked};
 
 // Internal macros.
 #[doc(hidden)]
 pub use super::{_impl_uri_buf_traits_base, _impl_uri_traits, _impl_uri_traits_base};
+#[doc(hidden)]
+pub use super::{_uri_format_args, _uri_format_string};
+
+// Without the `macros` feature, string literals can't be checked at compile-time, so
+// only the `unsafe` forms of `uri!`, `uri_ref!`, and `rel_ref!` are available.
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(feature = "macros"))]
+macro_rules! assert_uri_literal {
+    ( $S:expr ) => {
+        compile_error!("Checking URI literals requires the `macros` feature of `async-coap-uri`")
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(feature = "macros"))]
+macro_rules! assert_uri_ref_literal {
+    ( $S:expr ) => {
+        compile_error!("Checking URI literals requires the `macros` feature of `async-coap-uri`")
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(feature = "macros"))]
+macro_rules! assert_rel_ref_literal {
+    ( $S:expr ) => {
+        compile_error!("Checking URI literals requires the `macros` feature of `async-coap-uri`")
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(all(not(feature = "macros"), feature = "std"))]
+macro_rules! _uri_format_checked {
+    ( $($arg:tt)* ) => {
+        compile_error!("Checked URI formatting requires the `macros` feature of `async-coap-uri`")
+    };
+}
 
 #[doc(hidden)]
 #[macro_export]
@@ -98,7 +141,7 @@ macro_rules! iuri_ref {
         $crate::_uri_const!($S, $crate::UriRef)
     }};
     ( $S:expr ) => {{
-        assert_uri_ref_literal!($S);
+        $crate::assert_uri_ref_literal!($S);
         $crate::_uri_const!($S, $crate::UriRef)
     }};
     ( ) => {
@@ -189,7 +232,7 @@ macro_rules! irel_ref {
         $crate::_uri_const!($S, $crate::RelRef)
     }};
     ( $S:expr ) => {{
-        assert_rel_ref_literal!($S);
+        $crate::assert_rel_ref_literal!($S);
         $crate::_uri_const!($S, $crate::RelRef)
     }};
     ( ) => {
@@ -243,14 +286,8 @@ macro_rules! uri {
 }
 
 #[doc(hidden)]
-// This macro should be used by `async-coap-uri` and the uri macro is used for downstream crates.
-//
-// This prevents prevents the error:
-// > macro-expanded `macro_export` macros from the current
-// > crate cannot be referred to by absolute paths
-//
-// and allows downstream crates to use the uri macro without having to import
-// the `assert_uri_literal` macro.
+// Historically used within `async-coap-uri` in place of `uri!`; retained so
+// that existing code continues to compile.
 #[macro_export]
 macro_rules! iuri {
     ( unsafe $S:expr ) => {{
@@ -258,7 +295,7 @@ macro_rules! iuri {
         $crate::_uri_const!($S, $crate::Uri)
     }};
     ( $S:expr ) => {{
-        assert_uri_literal!($S);
+        $crate::assert_uri_literal!($S);
         $crate::_uri_const!($S, $crate::Uri)
     }};
     ( ) => {
@@ -266,31 +303,318 @@ macro_rules! iuri {
     };
 }
 
-/// Creates a `Option<UriRefBuf>` from the given string format and arguments.
+/// Creates a `Result<UriRefBuf, ParseError>` from the given string format and arguments.
 ///
-/// The resulting string is checked at runtime to ensure it is well-formed.
+/// See [`uri_format!`] for more information.
+///
+/// ```
+/// # use async_coap_uri::prelude::*;
+/// let name = "temp 1";
+/// let uri_ref = uri_ref_format!("/sensors/{}?units={}", name, "°C").unwrap();
+///
+/// assert_eq!(uri_ref, uri_ref!("/sensors/temp%201?units=%C2%B0C"));
+/// ```
 #[cfg(feature = "std")]
 #[macro_export]
 macro_rules! uri_ref_format {
-    ($($arg:tt)*) => ($crate::UriRefBuf::from_string(::std::format!($($arg)*)))
+    ($($arg:tt)*) => ($crate::UriRefBuf::from_string($crate::_uri_format_string!(false, $($arg)*)))
 }
 
-/// Creates a `Option<UriBuf>` from the given string format and arguments.
+/// Creates a `Result<UriBuf, ParseError>` from the given string format and arguments.
+///
+/// This macro accepts the same syntax as [`format!`], but each argument is
+/// percent-encoded according to the component of the URI it ends up in:
+///
+/// * In the authority, characters that are not allowed in an authority are escaped.
+/// * In the path, the argument is escaped as a single path segment, so `/` is escaped.
+/// * In the query, the argument is escaped as a query item, so `&`, `;`, and `=` are
+///   escaped and spaces are encoded as `+`.
+/// * In the fragment, characters that are not allowed in a fragment are escaped.
+///
+/// The literal parts of the format string are not escaped, and the resulting string is
+/// checked at runtime to ensure it is well-formed. Arguments that are implicitly
+/// captured by name (like `{name}` without a corresponding `name = ...` argument)
+/// are **not** escaped.
+///
+/// ```
+/// # use async_coap_uri::prelude::*;
+/// let host = "example.com";
+/// let path = "a/b c";
+/// let uri = uri_format!("coap://{}/files/{}?q={query}", host, path, query = "x&y").unwrap();
 ///
-/// The resulting string is checked at runtime to ensure it is well-formed.
+/// assert_eq!(uri, uri!("coap://example.com/files/a%2Fb%20c?q=x%26y"));
+/// ```
+///
+/// Use [`uri_format_checked!`] to also verify the format string at compile time.
 #[cfg(feature = "std")]
 #[macro_export]
 macro_rules! uri_format {
-    ($($arg:tt)*) => ($crate::UriBuf::from_string(::std::format!($($arg)*)))
+    ($($arg:tt)*) => ($crate::UriBuf::from_string($crate::_uri_format_string!(false, $($arg)*)))
 }
 
-/// Creates a `Option<RelRefBuf>` from the given string format and arguments.
+/// Creates a `Result<RelRefBuf, ParseError>` from the given string format and arguments.
+///
+/// See [`uri_format!`] for more information. Colons in arguments that appear in the
+/// first path segment are escaped so that the result cannot be confused with a URI.
+///
+/// ```
+/// # use async_coap_uri::prelude::*;
+/// let rel_ref = rel_ref_format!("{}/{:03}#{}", "a:b", 7, "frag ment").unwrap();
 ///
-/// The resulting string is checked at runtime to ensure it is well-formed.
+/// assert_eq!(rel_ref, rel_ref!("a%3Ab/007#frag%20ment"));
+/// ```
 #[cfg(feature = "std")]
 #[macro_export]
 macro_rules! rel_ref_format {
-    ($($arg:tt)*) => ($crate::RelRefBuf::from_string(::std::format!($($arg)*)))
+    ($($arg:tt)*) => ($crate::RelRefBuf::from_string($crate::_uri_format_string!(true, $($arg)*)))
+}
+
+/// Creates a `Result<UriBuf, ParseError>` from the given string format and arguments,
+/// verifying the format string at compile time.
+///
+/// The literal parts of the format string are checked when the macro is expanded,
+/// with each placeholder standing in for a short, valid value. Each argument is
+/// then percent-encoded at runtime according to the component of the URI it appears
+/// in: arguments in the path are escaped as a single path segment (so `/` is
+/// escaped), arguments in the query are escaped as query items, and so on.
+///
+/// Placeholders are not allowed in the scheme. Width and precision arguments
+/// (like `{:1$}`) are not supported.
+///
+/// ```
+/// # use async_coap_uri::prelude::*;
+/// let host = "example.com";
+/// let name = "a/b c";
+/// let uri = uri_format_checked!("coap://{}/files/{}?user={user}", host, name, user = "x&y")
+///     .unwrap();
+///
+/// assert_eq!(uri, uri!("coap://example.com/files/a%2Fb%20c?user=x%26y"));
+/// ```
+///
+/// Problems with the skeleton of the URI are reported at compile time:
+///
+/// ```compile_fail
+/// # use async_coap_uri::prelude::*;
+/// // This will not compile because the scheme is invalid.
+/// let uri = uri_format_checked!("co ap://{}/", "example.com");
+/// ```
+///
+/// The resulting string is still parsed at runtime, so any remaining problems are
+/// reported as a [`ParseError`](crate::ParseError) rather than causing a panic.
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! uri_format_checked {
+    ($($arg:tt)*) => ($crate::_uri_format_checked!($crate, Uri, $($arg)*))
+}
+
+/// Creates a `Result<UriRefBuf, ParseError>` from the given string format and arguments,
+/// verifying the format string at compile time.
+///
+/// See [`uri_format_checked!`] for more information.
+///
+/// ```
+/// # use async_coap_uri::prelude::*;
+/// let uri_ref = uri_ref_format_checked!("/sensors/{}?units={}", "temp 1", "°C").unwrap();
+///
+/// assert_eq!(uri_ref, uri_ref!("/sensors/temp%201?units=%C2%B0C"));
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! uri_ref_format_checked {
+    ($($arg:tt)*) => ($crate::_uri_format_checked!($crate, UriRef, $($arg)*))
+}
+
+/// Creates a `Result<RelRefBuf, ParseError>` from the given string format and arguments,
+/// verifying the format string at compile time.
+///
+/// See [`uri_format_checked!`] for more information. Colons in arguments that appear
+/// in the first path segment are escaped so that the result cannot be confused with a URI.
+///
+/// ```
+/// # use async_coap_uri::prelude::*;
+/// let rel_ref = rel_ref_format_checked!("{}/{}", "a:b", 5).unwrap();
+///
+/// assert_eq!(rel_ref, rel_ref!("a%3Ab/5"));
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! rel_ref_format_checked {
+    ($($arg:tt)*) => ($crate::_uri_format_checked!($crate, RelRef, $($arg)*))
+}
+
+/// How an argument to one of the format macros is percent-encoded.
+#[doc(hidden)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum FormatEscape {
+    /// Escape all reserved characters.
+    Full,
+    /// Escape for use in the authority.
+    Authority,
+    /// Escape for use as a single path segment.
+    Segment,
+    /// Escape for use as a query item or as the key or value of a query item.
+    Query,
+    /// Escape for use in the fragment.
+    Fragment,
+}
+
+impl FormatEscape {
+    /// Determines how an argument should be escaped if it were to be appended
+    /// to `prefix`, which is the partially written URI-reference.
+    #[cfg(feature = "std")]
+    fn for_prefix(prefix: &str, relative: bool) -> FormatEscape {
+        match crate::UriComponent::containing(prefix, prefix.len(), relative) {
+            crate::UriComponent::Path => {
+                let has_scheme = !relative
+                    && !prefix.is_empty()
+                    && crate::UriComponent::containing(prefix, 0, false)
+                        == crate::UriComponent::Scheme;
+
+                if !has_scheme && !prefix.contains('/') {
+                    // Colons must be escaped in the first path segment
+                    // when there is no scheme.
+                    FormatEscape::Full
+                } else {
+                    FormatEscape::Segment
+                }
+            }
+            crate::UriComponent::Query => FormatEscape::Query,
+            crate::UriComponent::Fragment => FormatEscape::Fragment,
+            _ => FormatEscape::Authority,
+        }
+    }
+
+    /// Appends `value` to `buf`, percent-encoded as indicated by `self`.
+    #[cfg(feature = "std")]
+    fn push_str(self, buf: &mut String, value: &str) {
+        use crate::escape::StrExt;
+        let value = value.escape_uri();
+
+        match self {
+            FormatEscape::Full => buf.extend(value.full()),
+            FormatEscape::Authority => buf.extend(value.for_authority()),
+            FormatEscape::Segment => buf.extend(value),
+            FormatEscape::Query => {
+                // Also escape the characters that delimit query items and key/value pairs.
+                for c in value.for_query() {
+                    match c {
+                        '&' => buf.push_str("%26"),
+                        ';' => buf.push_str("%3B"),
+                        '=' => buf.push_str("%3D"),
+                        c => buf.push(c),
+                    }
+                }
+            }
+            FormatEscape::Fragment => buf.extend(value.for_fragment()),
+        }
+    }
+}
+
+/// Formats `args` and appends the result to `buf`, percent-encoded as indicated by `escape`.
+#[doc(hidden)]
+#[cfg(feature = "std")]
+pub fn push_escaped(buf: &mut String, escape: FormatEscape, args: ::core::fmt::Arguments<'_>) {
+    escape.push_str(buf, &args.to_string());
+}
+
+/// Accumulates the output of [`uri_format!`] and friends, keeping track of which
+/// component of the URI-reference is currently being written.
+#[doc(hidden)]
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct UriFormatState {
+    buf: ::core::cell::RefCell<String>,
+    escape: ::core::cell::Cell<Option<FormatEscape>>,
+    relative: bool,
+}
+
+#[cfg(feature = "std")]
+impl UriFormatState {
+    /// Creates a new, empty `UriFormatState`. If `relative` is true, the result is
+    /// assumed to be a relative reference.
+    pub fn new(relative: bool) -> UriFormatState {
+        UriFormatState {
+            buf: Default::default(),
+            escape: Default::default(),
+            relative,
+        }
+    }
+
+    /// Writes the given format arguments. Any arguments wrapped with [`FormatArg`]
+    /// are escaped appropriately.
+    pub fn write_args(&self, args: ::core::fmt::Arguments<'_>) {
+        struct Writer<'a>(&'a UriFormatState);
+
+        impl ::core::fmt::Write for Writer<'_> {
+            fn write_str(&mut self, s: &str) -> ::core::fmt::Result {
+                let mut buf = self.0.buf.borrow_mut();
+                match self.0.escape.get() {
+                    Some(escape) => escape.push_str(&mut buf, s),
+                    None => buf.push_str(s),
+                }
+                Ok(())
+            }
+        }
+
+        ::core::fmt::write(&mut Writer(self), args).expect("Formatting argument failed");
+    }
+
+    /// Consumes this object, returning the formatted string.
+    pub fn into_string(self) -> String {
+        self.buf.into_inner()
+    }
+}
+
+/// Wrapper for arguments to [`uri_format!`] and friends, which causes the
+/// argument to be escaped according to the component it is written into.
+#[doc(hidden)]
+#[cfg(feature = "std")]
+pub struct FormatArg<'a, T: ?Sized>(pub &'a UriFormatState, pub &'a T);
+
+macro_rules! impl_format_arg {
+    ($($trait:ident),*) => {$(
+        #[cfg(feature = "std")]
+        impl<T: ::core::fmt::$trait + ?Sized> ::core::fmt::$trait for FormatArg<'_, T> {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                let escape = FormatEscape::for_prefix(&self.0.buf.borrow(), self.0.relative);
+                self.0.escape.set(Some(escape));
+                let ret = ::core::fmt::$trait::fmt(self.1, f);
+                self.0.escape.set(None);
+                ret
+            }
+        }
+    )*};
+}
+
+impl_format_arg!(Display, Debug, LowerHex, UpperHex, Octal, Binary, LowerExp, UpperExp);
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _uri_format_args {
+    (@ $st:ident [$($out:tt)*]) => (::core::format_args!($($out)*));
+    (@ $st:ident [$($out:tt)*] ,) => (::core::format_args!($($out)*));
+    (@ $st:ident [$($out:tt)*] , $name:ident = $val:expr $(, $($rest:tt)*)?) => (
+        $crate::_uri_format_args!(
+            @ $st [$($out)*, $name = $crate::macros::FormatArg(&$st, &$val)] $(, $($rest)*)?
+        )
+    );
+    (@ $st:ident [$($out:tt)*] , $val:expr $(, $($rest:tt)*)?) => (
+        $crate::_uri_format_args!(
+            @ $st [$($out)*, $crate::macros::FormatArg(&$st, &$val)] $(, $($rest)*)?
+        )
+    );
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _uri_format_string {
+    ($relative:expr, $fmt:tt $($args:tt)*) => {{
+        let __uri_format_state = $crate::macros::UriFormatState::new($relative);
+        __uri_format_state.write_args(
+            $crate::_uri_format_args!(@ __uri_format_state [$fmt] $($args)*)
+        );
+        __uri_format_state.into_string()
+    }};
 }
 
 #[doc(hidden)]
@@ -314,11 +638,16 @@ macro_rules! _impl_uri_traits {
         }
 
         impl ::core::cmp::Ord for $C {
-            fn cmp(&self, other: &Self) -> ::std::cmp::Ordering {
+            fn cmp(&self, other: &Self) -> ::core::cmp::Ordering {
                 ::core::cmp::Ord::cmp(self.as_str(), other.as_str())
             }
         }
 
+        $crate::_impl_uri_cmp_rev!(str, $C);
+        $crate::_impl_uri_cmp_rev!(&str, $C);
+        #[cfg(feature = "std")]
+        $crate::_impl_uri_cmp_rev!(::std::string::String, $C);
+
         impl ::core::fmt::Debug for $C {
             fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
                 f.write_str(concat!(stringify!($C), "<"))?;
@@ -339,18 +668,56 @@ macro_rules! _impl_uri_traits {
     };
 }
 
+/// Implements `PartialEq<$C>` and `PartialOrd<$C>` for `$L`, which is the reverse of
+/// the comparisons provided by [`_impl_uri_traits`].
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _impl_uri_cmp_rev {
+    ( $L:ty, $C:ty ) => {
+        impl<'a> ::core::cmp::PartialEq<$C> for $L {
+            fn eq(&self, other: &$C) -> bool {
+                ::core::cmp::PartialEq::eq(
+                    ::core::convert::AsRef::<str>::as_ref(self),
+                    other.as_str(),
+                )
+            }
+        }
+
+        impl<'a> ::core::cmp::PartialOrd<$C> for $L {
+            fn partial_cmp(&self, other: &$C) -> ::core::option::Option<::core::cmp::Ordering> {
+                ::core::cmp::PartialOrd::partial_cmp(
+                    ::core::convert::AsRef::<str>::as_ref(self),
+                    other.as_str(),
+                )
+            }
+        }
+    };
+}
+
+/// Allows the owned type `$C` to appear on the right-hand side of comparisons with
+/// references to each of the given unsized types.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _impl_uri_buf_cmp_borrowed {
+    ( $C:ty ; $($B:ty),+ ) => {
+        $( $crate::_impl_uri_cmp_rev!(&'a $B, $C); )+
+    };
+}
+
 #[doc(hidden)]
 #[macro_export]
 macro_rules! _impl_uri_traits_base {
     ( $C:ty ) => {
         _impl_uri_traits!($C);
 
+        #[cfg(feature = "std")]
         impl ::core::convert::From<&$C> for ::std::string::String {
             fn from(x: &$C) -> Self {
                 ::std::string::String::from(&x.0)
             }
         }
 
+        #[cfg(feature = "std")]
         impl ::core::convert::From<&$C> for $crate::UriRefBuf {
             fn from(x: &$C) -> Self {
                 unsafe {
@@ -412,16 +779,6 @@ macro_rules! _impl_uri_buf_traits_base {
             }
         }
 
-        impl ::std::borrow::ToOwned for $B {
-            type Owned = $C;
-
-            fn to_owned(&self) -> Self::Owned {
-                unsafe {
-                    <$C>::from_string_unchecked(<Self as ::std::string::ToString>::to_string(self))
-                }
-            }
-        }
-
         impl ::core::borrow::Borrow<$B> for $C {
             fn borrow(&self) -> &$B {
                 unsafe { <$B>::from_str_unchecked(self.as_str()) }
@@ -466,12 +823,6 @@ macro_rules! impl_uri_buf_traits {
     ( $C:ty , $B:ty) => {
         _impl_uri_buf_traits_base!($C, $B);
 
-        impl ::core::convert::AsRef<::std::string::String> for $C {
-            fn as_ref(&self) -> &std::string::String {
-                ::core::convert::AsRef::<::std::string::String>::as_ref(&self.0)
-            }
-        }
-
         impl ::core::convert::AsRef<$crate::UriRefBuf> for $C {
             fn as_ref(&self) -> &$crate::UriRefBuf {
                 ::core::convert::AsRef::<$crate::UriRefBuf>::as_ref(&self.0)
@@ -0,0 +1,297 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! [`proptest`] strategies for URI references, along with helpers for checking the
+//! invariants of the URI manipulation methods in this crate.
+//!
+//! The generated references are biased towards the things that tend to be mishandled:
+//! empty and dot path segments, percent-escapes, colons, and empty queries and fragments.
+//! Percent-escapes are limited to printable ASCII characters.
+//!
+//! ```
+//! use async_coap_uri::prelude::*;
+//! use async_coap_uri::proptest_support::*;
+//! use proptest::prelude::*;
+//! use proptest::test_runner::TestRunner;
+//!
+//! let mut runner = TestRunner::default();
+//!
+//! runner
+//!     .run(&any::<UriRefBuf>(), |uri_ref| check_trim_invariants(&uri_ref))
+//!     .unwrap();
+//! ```
+//!
+//! Inside of the [`proptest!`] macro, the checks can be used with the `?` operator.
+//!
+//! [`proptest`]: https://docs.rs/proptest/1/proptest/
+//! [`proptest!`]: https://docs.rs/proptest/1/proptest/macro.proptest.html
+
+use super::*;
+use proptest::prelude::*;
+use proptest::test_runner::TestCaseError;
+
+fn scheme() -> impl Strategy<Value = String> {
+    prop_oneof![
+        Just("coap".to_string()),
+        Just("coaps".to_string()),
+        Just("http".to_string()),
+        "[a-z][a-z0-9+.-]{0,5}",
+    ]
+}
+
+fn authority() -> impl Strategy<Value = String> {
+    let host = prop_oneof![
+        "[a-z0-9-]{0,8}(\\.[a-z0-9-]{1,8}){0,2}",
+        "[0-9]{1,3}(\\.[0-9]{1,3}){3}",
+        Just("[::1]".to_string()),
+    ];
+
+    (
+        proptest::option::of("[a-z]{1,4}(:[a-z]{0,4})?"),
+        host,
+        proptest::option::of(any::<u16>()),
+    )
+        .prop_map(|(userinfo, host, port)| {
+            let mut ret = String::new();
+            if let Some(userinfo) = userinfo {
+                ret.push_str(&userinfo);
+                ret.push('@');
+            }
+            ret.push_str(&host);
+            if let Some(port) = port {
+                ret.push_str(&format!(":{}", port));
+            }
+            ret
+        })
+}
+
+fn path_segment() -> impl Strategy<Value = String> {
+    prop_oneof![
+        Just(String::new()),
+        Just(".".to_string()),
+        Just("..".to_string()),
+        "[a-zA-Z0-9._~!$&'()*+,;=:@-]{1,8}",
+        "[a-z]{0,3}%[2-7][0-9A-E][a-z]{0,3}",
+    ]
+}
+
+fn path() -> impl Strategy<Value = String> {
+    (
+        any::<bool>(),
+        proptest::collection::vec(path_segment(), 0..6),
+    )
+        .prop_map(|(absolute, segments)| {
+            let path = segments.join("/");
+            if absolute {
+                format!("/{}", path)
+            } else {
+                path
+            }
+        })
+}
+
+fn query_or_fragment() -> impl Strategy<Value = String> {
+    prop_oneof![
+        "[a-zA-Z0-9._~!$&'()*+,;=:@/?-]{0,10}",
+        "[a-z=&]{0,3}%[2-7][0-9A-E]",
+    ]
+}
+
+/// Returns a strategy for generating [`RelRefBuf`]s, which is used by the [`Arbitrary`]
+/// implementation for [`RelRefBuf`].
+pub fn rel_ref_buf() -> impl Strategy<Value = RelRefBuf> {
+    (
+        path(),
+        proptest::option::of(query_or_fragment()),
+        proptest::option::of(query_or_fragment()),
+    )
+        .prop_filter_map("invalid relative reference", |(path, query, fragment)| {
+            let mut ret = path;
+            if let Some(query) = query {
+                ret.push('?');
+                ret.push_str(&query);
+            }
+            if let Some(fragment) = fragment {
+                ret.push('#');
+                ret.push_str(&fragment);
+            }
+            RelRefBuf::from_string(ret).ok()
+        })
+}
+
+/// Returns a strategy for generating [`UriRefBuf`]s, which is used by the [`Arbitrary`]
+/// implementation for [`UriRefBuf`].
+///
+/// Roughly half of the generated values are relative references.
+pub fn uri_ref_buf() -> impl Strategy<Value = UriRefBuf> {
+    (
+        proptest::option::of(scheme()),
+        proptest::option::of(authority()),
+        rel_ref_buf(),
+    )
+        .prop_filter_map("invalid URI reference", |(scheme, authority, rel)| {
+            let mut ret = String::new();
+            if let Some(scheme) = scheme {
+                ret.push_str(&scheme);
+                ret.push(':');
+            }
+            if let Some(authority) = authority {
+                ret.push_str("//");
+                ret.push_str(&authority);
+                if !rel.is_empty() && !rel.starts_with(&['/', '?', '#'][..]) {
+                    ret.push('/');
+                }
+            }
+            ret.push_str(rel.as_str());
+            UriRefBuf::from_string(ret).ok()
+        })
+}
+
+impl Arbitrary for UriRefBuf {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<UriRefBuf>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        uri_ref_buf().boxed()
+    }
+}
+
+impl Arbitrary for RelRefBuf {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<RelRefBuf>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        rel_ref_buf().boxed()
+    }
+}
+
+fn check_trimmed(
+    original: &str,
+    trimmed: &str,
+    method: &str,
+    is_valid: bool,
+) -> Result<(), TestCaseError> {
+    prop_assert!(
+        is_valid,
+        "{}({:?}) returned an invalid reference: {:?}",
+        method,
+        original,
+        trimmed
+    );
+    prop_assert!(
+        original.contains(trimmed),
+        "{}({:?}) returned something that isn't a slice of the original: {:?}",
+        method,
+        original,
+        trimmed
+    );
+    Ok(())
+}
+
+/// Checks that the `trim_*` methods of [`UriRef`] (and of [`RelRef`], for the relative part)
+/// always return well-formed slices of `uri_ref`, and that trimming the query or fragment
+/// is idempotent.
+pub fn check_trim_invariants(uri_ref: &UriRef) -> Result<(), TestCaseError> {
+    let trimmed = [
+        ("trim_fragment", uri_ref.trim_fragment()),
+        ("trim_query", uri_ref.trim_query()),
+        ("trim_path", uri_ref.trim_path()),
+        ("trim_heir_part", uri_ref.trim_heir_part()),
+        ("trim_resource", uri_ref.trim_resource()),
+        ("trim_trailing_slash", uri_ref.trim_trailing_slash()),
+    ];
+
+    for (method, trimmed) in trimmed.iter() {
+        let is_valid = UriRef::is_str_valid(trimmed);
+        check_trimmed(uri_ref, trimmed, method, is_valid)?;
+    }
+
+    prop_assert_eq!(
+        uri_ref.trim_fragment().trim_fragment(),
+        uri_ref.trim_fragment()
+    );
+    prop_assert_eq!(uri_ref.trim_query().trim_query(), uri_ref.trim_query());
+
+    check_rel_ref_trim_invariants(uri_ref.rel())
+}
+
+/// Checks that the `trim_*` methods of [`RelRef`] always return well-formed slices of `rel`.
+///
+/// Since a [`RelRef`] may be degenerate (see [`RelRef::is_degenerate`]), the slices are
+/// only required to be well-formed [`RelRef`]s rather than [`UriRef`]s.
+pub fn check_rel_ref_trim_invariants(rel: &RelRef) -> Result<(), TestCaseError> {
+    let (segment, rest) = rel.trim_leading_path_segment();
+    let trimmed = [
+        ("trim_fragment", rel.trim_fragment().as_str()),
+        ("trim_query", rel.trim_query()),
+        ("trim_resource", rel.trim_resource()),
+        ("trim_trailing_slash", rel.trim_trailing_slash()),
+        ("trim_leading_slashes", rel.trim_leading_slashes()),
+        ("trim_leading_dot_slashes", rel.trim_leading_dot_slashes()),
+        ("trim_leading_path_segment", segment),
+        ("trim_leading_path_segment", rest),
+    ];
+
+    for (method, trimmed) in trimmed.iter() {
+        let is_valid = RelRef::is_str_valid(trimmed);
+        check_trimmed(rel, trimmed, method, is_valid)?;
+    }
+
+    Ok(())
+}
+
+/// Checks that if [`make_relative`](AnyUriRefExt::make_relative) finds a relative
+/// reference from `base` to `target`, then resolving that reference against `base`
+/// yields `target`.
+pub fn check_make_relative_round_trip(base: &UriRef, target: &UriRef) -> Result<(), TestCaseError> {
+    if let Some(rel) = base.make_relative(target) {
+        let resolved = base.resolved(&rel);
+        prop_assert!(
+            resolved.as_ref().map(UriRefBuf::as_uri_ref) == Ok(target),
+            "make_relative({:?}, {:?}) returned {:?}, which resolves to {:?}",
+            base.as_str(),
+            target.as_str(),
+            rel.as_str(),
+            resolved
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn trim_invariants(uri_ref in any::<UriRefBuf>()) {
+            check_trim_invariants(&uri_ref)?;
+        }
+
+        #[test]
+        fn rel_ref_trim_invariants(rel_ref in any::<RelRefBuf>()) {
+            check_rel_ref_trim_invariants(&rel_ref)?;
+        }
+
+        #[test]
+        fn make_relative_round_trip(base in any::<UriRefBuf>(), rel in any::<RelRefBuf>()) {
+            // Build the target by resolving against `base`, since two arbitrary
+            // references rarely share a scheme and authority.
+            let target = base.resolved(&rel);
+            prop_assume!(target.is_ok());
+            check_make_relative_round_trip(&base, &target.unwrap())?;
+        }
+    }
+}
@@ -14,9 +14,9 @@
 //
 
 use super::*;
-use std::fmt;
-use std::ops::Deref;
-use std::ptr;
+use core::fmt;
+use core::ops::Deref;
+use core::ptr;
 
 /// Unsized string-slice type guaranteed to contain a well-formed [IETF-RFC3986] [relative reference].
 ///
@@ -95,7 +95,7 @@ impl Default for &RelRef {
     /// Empty relative references do nothing but clear the base fragment when resolved
     /// against a base.
     fn default() -> Self {
-        irel_ref!("")
+        irel_ref!(unsafe "")
     }
 }
 
@@ -104,11 +104,11 @@ impl Default for &mut RelRef {
     ///
     /// Despite being marked mutable, since the length is zero the value is effectively immutable.
     fn default() -> Self {
-        use std::slice::from_raw_parts_mut;
-        use std::str::from_utf8_unchecked_mut;
+        use core::slice::from_raw_parts_mut;
+        use core::str::from_utf8_unchecked_mut;
         unsafe {
             // SAFETY: An empty slice is pretty harmless, mutable or not.
-            let empty_slice = from_raw_parts_mut(ptr::null_mut::<u8>(), 0);
+            let empty_slice = from_raw_parts_mut(ptr::NonNull::<u8>::dangling().as_ptr(), 0);
             let empty_string = from_utf8_unchecked_mut(empty_slice);
             RelRef::from_str_unchecked_mut(empty_string)
         }
@@ -178,7 +178,8 @@ impl RelRef {
     /// if the string slice contains data that is not a valid relative-reference.
     pub fn from_str(s: &str) -> Result<&RelRef, ParseError> {
         if let Some(first_error) = s.unescape_uri().first_error() {
-            Err(ParseError::from(first_error))
+            Err(ParseError::from(first_error)
+                .in_component(UriComponent::containing(s, first_error.index, true), 0))
         } else {
             Ok(unsafe { Self::from_str_unchecked(s) })
         }
@@ -192,11 +193,13 @@ impl RelRef {
 
     /// Constructs a new `RelRefBuf` from a `&RelRef`, disambiguating if degenerate.
     #[inline(always)]
+    #[cfg(feature = "std")]
     pub fn to_rel_ref_buf(&self) -> RelRefBuf {
         RelRefBuf::from_rel_ref(self)
     }
 
     /// Constructs a new `UriRefBuf` from a `&RelRef`, disambiguating if degenerate.
+    #[cfg(feature = "std")]
     pub fn to_uri_ref_buf(&self) -> UriRefBuf {
         self.to_rel_ref_buf().into()
     }
@@ -207,6 +210,12 @@ impl RelRef {
         self.0.as_str()
     }
 
+    /// Returns a hash of this relative reference which is the same in every process and
+    /// every version of this crate. See [`UriRef::stable_hash`].
+    pub fn stable_hash(&self) -> u64 {
+        self.0.stable_hash()
+    }
+
     /// Casts a non-degenerate relative reference to a `&UriRef`.
     /// Returns `None` if the relative reference [is degenerate][RelRef::is_degenerate].
     pub fn try_as_uri_ref(&self) -> Option<&UriRef> {
@@ -381,6 +390,105 @@ impl RelRef {
 
         ret
     }
+
+    /// Resolves a relative URI against this relative URI, treating the empty path as a
+    /// root that can't be escaped, like `chroot` does.
+    ///
+    /// This is useful for safely mapping request paths onto a filesystem or another
+    /// tree-structured store. Both `self` and `dest` are interpreted relative to the root,
+    /// even if they start with a slash, and the resulting path never does. Instead of
+    /// being dropped, `..` segments which would remove the root cause
+    /// [`ResolveError::EscapesRoot`] to be returned.
+    ///
+    /// Segments which are `.` or `..` once unescaped (like `%2E%2E`) are treated the
+    /// same way as their unescaped forms, and empty segments are removed.
+    ///
+    /// ```
+    /// # use async_coap_uri::prelude::*;
+    /// # use async_coap_uri::ResolveError;
+    /// let base = rel_ref!("sensors/temp");
+    ///
+    /// assert_eq!(
+    ///     base.resolved_within_root(rel_ref!("../lights/1?on")),
+    ///     Ok(rel_ref!("lights/1?on").to_rel_ref_buf())
+    /// );
+    /// assert_eq!(
+    ///     base.resolved_within_root(rel_ref!("/index")),
+    ///     Ok(rel_ref!("index").to_rel_ref_buf())
+    /// );
+    /// assert_eq!(
+    ///     base.resolved_within_root(rel_ref!("../../etc/passwd")),
+    ///     Err(ResolveError::EscapesRoot)
+    /// );
+    /// assert_eq!(
+    ///     base.resolved_within_root(rel_ref!("%2E%2E/%2e%2e/etc/passwd")),
+    ///     Err(ResolveError::EscapesRoot)
+    /// );
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn resolved_within_root<UF: AsRef<RelRef>>(
+        &self,
+        dest: UF,
+    ) -> Result<RelRefBuf, ResolveError> {
+        let dest = dest.as_ref();
+        let dest_path = dest.path_as_rel_ref();
+
+        let (base_path, query) = if dest_path.is_empty() {
+            (
+                self.path_as_rel_ref(),
+                dest.raw_query().or_else(|| self.raw_query()),
+            )
+        } else if dest_path.starts_with('/') {
+            (irel_ref!(unsafe ""), dest.raw_query())
+        } else {
+            (self.path_as_rel_ref().trim_resource(), dest.raw_query())
+        };
+
+        let mut segments = Vec::new();
+        let mut trailing_slash = false;
+
+        for seg in base_path
+            .raw_path_segments()
+            .chain(dest_path.raw_path_segments())
+        {
+            trailing_slash = true;
+
+            match seg.unescape_uri().to_cow().as_ref() {
+                "" | "." => (),
+                ".." => {
+                    segments.pop().ok_or(ResolveError::EscapesRoot)?;
+                }
+                _ => {
+                    segments.push(seg);
+                    trailing_slash = false;
+                }
+            }
+        }
+
+        let mut ret = segments.join("/");
+
+        if trailing_slash && !ret.is_empty() {
+            ret.push('/');
+        }
+
+        if let Some(query) = query {
+            ret.push('?');
+            ret.push_str(query);
+        }
+
+        if let Some(fragment) = dest.raw_fragment() {
+            ret.push('#');
+            ret.push_str(fragment);
+        }
+
+        // SAFETY: The path segments, query, and fragment were all taken from
+        //         well-formed RelRefs, and the path doesn't start with a slash.
+        let mut ret = unsafe { RelRefBuf::from_string_unchecked(ret) };
+
+        ret.disambiguate();
+
+        Ok(ret)
+    }
 }
 
 /// # Trimming
@@ -616,7 +724,7 @@ impl RelRef {
         let mut_bytes = path.as_mut_str().as_bytes_mut();
 
         let mut ret = mut_bytes.split_mut(|b| *b == b'/').filter_map(|seg| {
-            let seg = std::str::from_utf8_unchecked_mut(seg);
+            let seg = core::str::from_utf8_unchecked_mut(seg);
             if seg == "." {
                 None
             } else {
@@ -656,7 +764,7 @@ impl RelRef {
         let mut ret = mut_bytes
             .split_mut(|b| *b == b'&' || *b == b';')
             .map(|seg| {
-                let seg = std::str::from_utf8_unchecked_mut(seg);
+                let seg = core::str::from_utf8_unchecked_mut(seg);
                 &*seg.unescape_uri_in_place()
             });
 
@@ -761,6 +869,44 @@ mod tests {
         );
     }
 
+    #[test]
+    fn resolved_within_root() {
+        let resolved = |base: &str, dest: &str| {
+            RelRef::from_str(base)
+                .unwrap()
+                .resolved_within_root(RelRef::from_str(dest).unwrap())
+                .map(|ret| ret.to_string())
+        };
+
+        assert_eq!(Ok("a/c".to_string()), resolved("a/b", "c"));
+        assert_eq!(Ok("a/c".to_string()), resolved("/a/b", "c"));
+        assert_eq!(Ok("c".to_string()), resolved("a/b", "/c"));
+        assert_eq!(Ok("c".to_string()), resolved("a/b", "../c"));
+        assert_eq!(Ok("a/".to_string()), resolved("a/b", "."));
+        assert_eq!(Ok("".to_string()), resolved("a/b", ".."));
+        assert_eq!(Ok("a/b?q".to_string()), resolved("a/b?q", ""));
+        assert_eq!(Ok("a/b?r".to_string()), resolved("a/b?q", "?r"));
+        assert_eq!(Ok("a/c#f".to_string()), resolved("a/b?q", "c#f"));
+        assert_eq!(Ok("a/c/d".to_string()), resolved("a/b", "c//d"));
+        assert_eq!(Ok("x%3Ay".to_string()), resolved("a/b", "../x:y"));
+        assert_eq!(Ok("x/".to_string()), resolved("", "x/y/%2E%2E"));
+
+        assert_eq!(Err(ResolveError::EscapesRoot), resolved("", ".."));
+        assert_eq!(Err(ResolveError::EscapesRoot), resolved("a/b", "../.."));
+        assert_eq!(Err(ResolveError::EscapesRoot), resolved("a/b", "/../a"));
+        assert_eq!(Err(ResolveError::EscapesRoot), resolved("../a", "b"));
+        assert_eq!(Err(ResolveError::EscapesRoot), resolved("a/b", "%2e%2E/.."));
+
+        let mut buf = irel_ref!("a/b").to_rel_ref_buf();
+        assert_eq!(
+            Err(ResolveError::EscapesRoot),
+            buf.resolve_within_root(irel_ref!("../.."))
+        );
+        assert_eq!(irel_ref!("a/b"), buf.as_rel_ref());
+        assert_eq!(Ok(()), buf.resolve_within_root(irel_ref!("c/")));
+        assert_eq!(irel_ref!("a/c/"), buf.as_rel_ref());
+    }
+
     #[test]
     fn trim_leading_n_path_segments() {
         assert_eq!(
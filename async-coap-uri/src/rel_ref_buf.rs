@@ -14,7 +14,9 @@
 //
 
 use super::*;
+use std::convert::TryFrom;
 use std::ops::Deref;
+use std::str::FromStr;
 
 /// Sized, heap-allocated string type guaranteed to contain a well-formed [IETF-RFC3986]
 /// [relative-reference].
@@ -35,6 +37,7 @@ use std::ops::Deref;
 pub struct RelRefBuf(pub(super) UriRefBuf);
 
 impl_uri_buf_traits!(RelRefBuf, RelRef);
+_impl_uri_buf_cmp_borrowed!(RelRefBuf; UriRef, Uri, RelRef);
 
 impl Default for RelRefBuf {
     fn default() -> Self {
@@ -68,6 +71,46 @@ impl From<&RelRef> for RelRefBuf {
     }
 }
 
+impl ToOwned for RelRef {
+    type Owned = RelRefBuf;
+
+    fn to_owned(&self) -> Self::Owned {
+        RelRefBuf::from_rel_ref(self)
+    }
+}
+
+impl FromStr for RelRefBuf {
+    type Err = ParseError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        Self::from_str(input)
+    }
+}
+
+impl TryFrom<&str> for RelRefBuf {
+    type Error = ParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Self::from_str(value)
+    }
+}
+
+impl TryFrom<String> for RelRefBuf {
+    type Error = ParseError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::from_string(value)
+    }
+}
+
+impl<'a> TryFrom<&'a String> for RelRefBuf {
+    type Error = <Self as TryFrom<&'a str>>::Error;
+
+    fn try_from(value: &'a String) -> Result<Self, Self::Error> {
+        <Self as TryFrom<&'a str>>::try_from(value.as_str())
+    }
+}
+
 /// # Constructors
 impl RelRefBuf {
     /// Constructs a new, empty relative reference buffer.
@@ -88,7 +131,8 @@ impl RelRefBuf {
     /// Attempts to create a new [`RelRefBuf`] from a [`String`].
     pub fn from_string(s: String) -> Result<RelRefBuf, ParseError> {
         if let Some(first_error) = s.as_str().unescape_uri().first_error() {
-            return Err(ParseError::from(first_error));
+            let component = UriComponent::containing(&s, first_error.index, true);
+            Err(ParseError::from(first_error).in_component(component, 0))
         } else {
             let mut ret = unsafe { Self::from_string_unchecked(s) };
 
@@ -98,6 +142,27 @@ impl RelRefBuf {
         }
     }
 
+    /// Attempts to create a new [`RelRefBuf`] from a string reference using the given
+    /// [`ParseMode`].
+    ///
+    /// See [`UriRefBuf::from_str_with_mode`] for more information.
+    pub fn from_str_with_mode<S: AsRef<str>>(
+        s: S,
+        mode: ParseMode,
+    ) -> Result<(RelRefBuf, Vec<LenientFix>), ParseError> {
+        let s = s.as_ref();
+        match mode {
+            ParseMode::Strict => Self::from_str(s).map(|x| (x, Vec::new())),
+            ParseMode::Lenient => {
+                let (escaped, fixes) = lenient_escape(s, true);
+                match Self::from_string(escaped) {
+                    Ok(x) => Ok((x, fixes)),
+                    Err(err) => Err(unmap_lenient_error(err, &fixes)),
+                }
+            }
+        }
+    }
+
     /// Attempts to create a new [`RelRefBuf`] from a [`UriRef`] reference.
     pub fn from_uri_ref<S: AsRef<UriRef>>(s: S) -> Option<RelRefBuf> {
         s.as_ref()
@@ -108,7 +173,12 @@ impl RelRefBuf {
 
     /// Attempts to create a new [`RelRefBuf`] from a [`RelRef`] reference.
     pub fn from_rel_ref<S: AsRef<RelRef>>(s: S) -> RelRefBuf {
-        unsafe { Self::from_string_unchecked(s.as_ref().to_string()) }
+        let mut ret = RelRefBuf::new();
+
+        // Writing to a `SmallString` can't fail.
+        let _ = s.as_ref().write_to(&mut (ret.0).0);
+
+        ret
     }
 }
 
@@ -216,6 +286,18 @@ impl RelRefBuf {
         }
     }
 
+    /// Using this relative-reference as the base, resolves another relative reference
+    /// within the root formed by the empty path, updating the content of this `RelRefBuf`
+    /// with the result.
+    ///
+    /// If `dest` would escape the root, [`ResolveError::EscapesRoot`] is returned and this
+    /// `RelRefBuf` is left unchanged. See [`RelRef::resolved_within_root`] for more
+    /// information.
+    pub fn resolve_within_root<T: AsRef<RelRef>>(&mut self, dest: T) -> Result<(), ResolveError> {
+        *self = self.resolved_within_root(dest)?;
+        Ok(())
+    }
+
     /// Completely clears this `RelRefBuf`, leaving it empty.
     #[inline(always)]
     pub fn clear(&mut self) {
@@ -240,4 +322,15 @@ impl RelRefBuf {
     pub unsafe fn from_string_unchecked(s: String) -> RelRefBuf {
         RelRefBuf(UriRefBuf::from_string_unchecked(s))
     }
+
+    /// Unchecked version of [`RelRefBuf::from_str`].
+    ///
+    /// # Safety
+    ///
+    /// This method is marked as unsafe because it allows you to construct a `RelRefBuf` with
+    /// a value that is not a well-formed relative-reference.
+    #[inline(always)]
+    pub unsafe fn from_str_unchecked(s: &str) -> RelRefBuf {
+        RelRefBuf(UriRefBuf::from_str_unchecked(s))
+    }
 }
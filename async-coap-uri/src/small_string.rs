@@ -0,0 +1,375 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use core::hash::{Hash, Hasher};
+use core::ops::Range;
+
+/// The number of bytes that a [`SmallString`] can hold without a heap allocation. This
+/// keeps a `SmallString` the same size as a `String` plus one word on 64-bit platforms.
+pub(crate) const INLINE_CAPACITY: usize = 30;
+
+/// Growable string which stores short strings inline, only moving its contents to a
+/// heap-allocated [`String`] once they no longer fit.
+///
+/// Most URI references are short, so this is used as the storage for [`UriRefBuf`] to
+/// avoid allocations when handling them. Once a `SmallString` has moved to the heap, it
+/// stays there.
+///
+/// [`UriRefBuf`]: crate::UriRefBuf
+#[derive(Clone)]
+pub(crate) enum SmallString {
+    /// The first `len` bytes of `buf` are always valid UTF-8.
+    Inline {
+        len: u8,
+        buf: [u8; INLINE_CAPACITY],
+    },
+    Heap(String),
+}
+
+impl SmallString {
+    /// Creates a new, empty `SmallString`.
+    pub const fn new() -> SmallString {
+        SmallString::Inline {
+            len: 0,
+            buf: [0; INLINE_CAPACITY],
+        }
+    }
+
+    /// Creates a new, empty `SmallString` which can hold at least `capacity` bytes
+    /// without reallocating.
+    pub fn with_capacity(capacity: usize) -> SmallString {
+        if capacity <= INLINE_CAPACITY {
+            SmallString::new()
+        } else {
+            SmallString::Heap(String::with_capacity(capacity))
+        }
+    }
+
+    /// Returns true if the contents are stored inline.
+    #[cfg(test)]
+    pub fn is_inline(&self) -> bool {
+        matches!(self, SmallString::Inline { .. })
+    }
+
+    /// Returns the length of this string, in bytes.
+    pub fn len(&self) -> usize {
+        match self {
+            SmallString::Inline { len, .. } => *len as usize,
+            SmallString::Heap(s) => s.len(),
+        }
+    }
+
+    /// Borrows the contents as a string slice.
+    pub fn as_str(&self) -> &str {
+        match self {
+            SmallString::Inline { len, buf } => unsafe {
+                core::str::from_utf8_unchecked(&buf[..*len as usize])
+            },
+            SmallString::Heap(s) => s.as_str(),
+        }
+    }
+
+    /// Borrows the contents as a mutable string slice.
+    pub fn as_mut_str(&mut self) -> &mut str {
+        match self {
+            SmallString::Inline { len, buf } => unsafe {
+                core::str::from_utf8_unchecked_mut(&mut buf[..*len as usize])
+            },
+            SmallString::Heap(s) => s.as_mut_str(),
+        }
+    }
+
+    /// Moves the contents to the heap, if they aren't there already, and returns a
+    /// mutable reference to the resulting `String`.
+    pub fn as_mut_string(&mut self) -> &mut String {
+        if let SmallString::Inline { .. } = self {
+            *self = SmallString::Heap(String::from(self.as_str()));
+        }
+
+        match self {
+            SmallString::Heap(s) => s,
+            SmallString::Inline { .. } => unreachable!(),
+        }
+    }
+
+    /// Converts this `SmallString` into a `String`.
+    pub fn into_string(self) -> String {
+        match self {
+            SmallString::Heap(s) => s,
+            inline => String::from(inline.as_str()),
+        }
+    }
+
+    /// Moves the contents to the heap if `additional` more bytes wouldn't fit inline.
+    fn reserve(&mut self, additional: usize) {
+        if let SmallString::Inline { len, .. } = self {
+            let needed = *len as usize + additional;
+            if needed > INLINE_CAPACITY {
+                let mut s = String::with_capacity(needed);
+                s.push_str(self.as_str());
+                *self = SmallString::Heap(s);
+            }
+        }
+    }
+
+    /// Appends `string` to the end of this string.
+    pub fn push_str(&mut self, string: &str) {
+        self.reserve(string.len());
+
+        match self {
+            SmallString::Inline { len, buf } => {
+                let start = *len as usize;
+                buf[start..start + string.len()].copy_from_slice(string.as_bytes());
+                *len += string.len() as u8;
+            }
+            SmallString::Heap(s) => s.push_str(string),
+        }
+    }
+
+    /// Appends `c` to the end of this string.
+    pub fn push(&mut self, c: char) {
+        self.push_str(c.encode_utf8(&mut [0; 4]));
+    }
+
+    /// Inserts `c` at the byte offset `idx`.
+    ///
+    /// Panics if `idx` isn't on a char boundary, like [`String::insert`].
+    pub fn insert(&mut self, idx: usize, c: char) {
+        self.insert_str(idx, c.encode_utf8(&mut [0; 4]));
+    }
+
+    /// Inserts `string` at the byte offset `idx`.
+    ///
+    /// Panics if `idx` isn't on a char boundary, like [`String::insert_str`].
+    pub fn insert_str(&mut self, idx: usize, string: &str) {
+        assert!(self.as_str().is_char_boundary(idx));
+
+        self.reserve(string.len());
+
+        match self {
+            SmallString::Inline { len, buf } => {
+                let end = *len as usize;
+                buf.copy_within(idx..end, idx + string.len());
+                buf[idx..idx + string.len()].copy_from_slice(string.as_bytes());
+                *len += string.len() as u8;
+            }
+            SmallString::Heap(s) => s.insert_str(idx, string),
+        }
+    }
+
+    /// Replaces the bytes in `range` with `replace_with`.
+    ///
+    /// Panics if either end of `range` isn't on a char boundary, like
+    /// [`String::replace_range`].
+    pub fn replace_range(&mut self, range: Range<usize>, replace_with: &str) {
+        match self {
+            SmallString::Inline { .. } => {
+                let copy = self.clone();
+                let tail = &copy.as_str()[range.start..][range.end - range.start..];
+                self.truncate(range.start);
+                self.push_str(replace_with);
+                self.push_str(tail);
+            }
+            SmallString::Heap(s) => s.replace_range(range, replace_with),
+        }
+    }
+
+    /// Shortens this string to `new_len` bytes. Does nothing if `new_len` is greater
+    /// than the current length.
+    ///
+    /// Panics if `new_len` isn't on a char boundary, like [`String::truncate`].
+    pub fn truncate(&mut self, new_len: usize) {
+        if new_len >= self.len() {
+            return;
+        }
+
+        assert!(self.as_str().is_char_boundary(new_len));
+
+        match self {
+            SmallString::Inline { len, .. } => *len = new_len as u8,
+            SmallString::Heap(s) => s.truncate(new_len),
+        }
+    }
+
+    /// Truncates this string to zero length.
+    pub fn clear(&mut self) {
+        self.truncate(0)
+    }
+}
+
+impl Default for SmallString {
+    fn default() -> Self {
+        SmallString::new()
+    }
+}
+
+impl From<&str> for SmallString {
+    fn from(s: &str) -> Self {
+        let mut ret = SmallString::with_capacity(s.len());
+        ret.push_str(s);
+        ret
+    }
+}
+
+/// Takes ownership of the allocation of `s`, so no copy is made.
+impl From<String> for SmallString {
+    fn from(s: String) -> Self {
+        SmallString::Heap(s)
+    }
+}
+
+impl From<SmallString> for String {
+    fn from(s: SmallString) -> Self {
+        s.into_string()
+    }
+}
+
+impl Extend<char> for SmallString {
+    fn extend<I: IntoIterator<Item = char>>(&mut self, iter: I) {
+        for c in iter {
+            self.push(c);
+        }
+    }
+}
+
+impl core::fmt::Write for SmallString {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.push_str(s);
+        Ok(())
+    }
+}
+
+impl core::fmt::Debug for SmallString {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl PartialEq for SmallString {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for SmallString {}
+
+/// Hashes the same way as `String` and `str`, as required by `Borrow`.
+impl Hash for SmallString {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inline() {
+        #[cfg(target_pointer_width = "64")]
+        assert_eq!(32, core::mem::size_of::<SmallString>());
+
+        let mut s = SmallString::new();
+        assert!(s.is_inline());
+        assert_eq!("", s.as_str());
+
+        s.push_str("coap://a");
+        s.push('/');
+        s.extend("é".chars());
+        s.insert(7, 'b');
+        assert_eq!("coap://ba/é", s.as_str());
+        assert_eq!(12, s.len());
+        assert!(s.is_inline());
+
+        s.insert_str(0, "<");
+        s.replace_range(5..6, "%3A");
+        s.replace_range(0..1, "");
+        assert_eq!("coap%3A//ba/é", s.as_str());
+        s.replace_range(4..7, ":");
+
+        s.truncate(9);
+        assert_eq!("coap://ba", s.as_str());
+        s.truncate(100);
+        assert_eq!("coap://ba", s.as_str());
+
+        s.clear();
+        assert_eq!("", s.as_str());
+        assert!(s.is_inline());
+
+        let s = SmallString::from("x".repeat(INLINE_CAPACITY).as_str());
+        assert!(s.is_inline());
+        assert_eq!(INLINE_CAPACITY, s.len());
+    }
+
+    #[test]
+    fn spill() {
+        let mut s = SmallString::from("/".repeat(INLINE_CAPACITY).as_str());
+        s.push('a');
+        assert!(!s.is_inline());
+        assert_eq!(format!("{}a", "/".repeat(INLINE_CAPACITY)), s.as_str());
+
+        // Spilled strings stay on the heap.
+        s.truncate(1);
+        assert!(!s.is_inline());
+        assert_eq!("/", s.as_str());
+
+        let mut s = SmallString::from("/".repeat(INLINE_CAPACITY - 1).as_str());
+        s.insert(0, 'é');
+        assert!(!s.is_inline());
+        assert_eq!(format!("é{}", "/".repeat(INLINE_CAPACITY - 1)), s.as_str());
+
+        let mut s = SmallString::from("/".repeat(INLINE_CAPACITY - 1).as_str());
+        s.replace_range(0..1, "%2F");
+        assert!(!s.is_inline());
+        assert_eq!(
+            format!("%2F{}", "/".repeat(INLINE_CAPACITY - 2)),
+            s.as_str()
+        );
+
+        let mut s = SmallString::from("a/b");
+        s.as_mut_string().push_str("/c");
+        assert!(!s.is_inline());
+        assert_eq!("a/b/c", s.into_string());
+
+        assert!(!SmallString::with_capacity(INLINE_CAPACITY + 1).is_inline());
+        assert!(!SmallString::from(String::from("a")).is_inline());
+    }
+
+    #[test]
+    #[should_panic]
+    fn truncate_not_char_boundary() {
+        SmallString::from("é").truncate(1);
+    }
+
+    #[test]
+    fn eq_and_hash() {
+        use std::collections::hash_map::DefaultHasher;
+
+        let hash = |x: &dyn Fn(&mut DefaultHasher)| {
+            let mut hasher = DefaultHasher::new();
+            x(&mut hasher);
+            hasher.finish()
+        };
+
+        let inline = SmallString::from("a/b");
+        let heap = SmallString::from(String::from("a/b"));
+        assert_eq!(inline, heap);
+        assert_eq!(
+            hash(&|h| inline.hash(h)),
+            hash(&|h| String::from("a/b").hash(h))
+        );
+        assert_eq!(hash(&|h| inline.hash(h)), hash(&|h| heap.hash(h)));
+    }
+}
@@ -0,0 +1,167 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Hand-written splitting of URI-references into their components, so that parsing
+//! doesn't need a regular expression engine.
+
+/// The components of a URI-reference, as split by the regular expression in
+/// [IETF-RFC3986 Appendix B]:
+///
+/// ```text
+/// ^(([^:/?#]+):)?(//([^/?#]*))?([^?#]*)(\?([^#]*))?(#(.*))?
+/// ```
+///
+/// One difference from the regular expression given in RFC3986 is that the scheme may
+/// not contain the `%` character, which is illegal.
+///
+/// [IETF-RFC3986 Appendix B]: https://tools.ietf.org/html/rfc3986#appendix-B
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) struct SplitUriRef<'a> {
+    pub scheme: Option<&'a str>,
+    pub authority: Option<&'a str>,
+    pub path: &'a str,
+    pub query: Option<&'a str>,
+    pub fragment: Option<&'a str>,
+}
+
+impl<'a> SplitUriRef<'a> {
+    /// Splits `uri` into its components. Every string can be split this way, except for
+    /// ones with a line feed in the fragment.
+    pub fn new(uri: &'a str) -> Option<SplitUriRef<'a>> {
+        let mut rest = uri;
+
+        let scheme = match rest.find(&[':', '/', '?', '#', '%'][..]) {
+            Some(i) if i > 0 && rest.as_bytes()[i] == b':' => {
+                let scheme = &rest[..i];
+                rest = &rest[i + 1..];
+                Some(scheme)
+            }
+            _ => None,
+        };
+
+        let authority = if rest.starts_with("//") {
+            rest = &rest[2..];
+            let end = rest.find(&['/', '?', '#'][..]).unwrap_or(rest.len());
+            let authority = &rest[..end];
+            rest = &rest[end..];
+            Some(authority)
+        } else {
+            None
+        };
+
+        let fragment = match rest.find('#') {
+            Some(i) => {
+                let fragment = &rest[i + 1..];
+                rest = &rest[..i];
+                Some(fragment)
+            }
+            None => None,
+        };
+
+        if fragment.is_some_and(|x| x.contains('\n')) {
+            return None;
+        }
+
+        let query = match rest.find('?') {
+            Some(i) => {
+                let query = &rest[i + 1..];
+                rest = &rest[..i];
+                Some(query)
+            }
+            None => None,
+        };
+
+        Some(SplitUriRef {
+            scheme,
+            authority,
+            path: rest,
+            query,
+            fragment,
+        })
+    }
+}
+
+/// Returns true if `scheme` is a well-formed URI scheme.
+pub(crate) fn is_valid_scheme(scheme: &str) -> bool {
+    let mut chars = scheme.chars();
+
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '+' | '.'))
+}
+
+/// The components of an authority, as split by this regular expression:
+///
+/// ```text
+/// ^(([^@/?#]+)@)?([^\[\]:]+|\[[^\]]+\])(:([0-9]+))?$
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) struct SplitAuthority<'a> {
+    pub userinfo: Option<&'a str>,
+    pub host: &'a str,
+    pub port: Option<&'a str>,
+}
+
+impl<'a> SplitAuthority<'a> {
+    /// Splits `authority` into its components, or returns `None` if it is malformed.
+    pub fn new(authority: &'a str) -> Option<SplitAuthority<'a>> {
+        if let Some(i) = authority.find('@') {
+            let userinfo = &authority[..i];
+            if !userinfo.is_empty() && !userinfo.contains(&['/', '?', '#'][..]) {
+                if let Some((host, port)) = split_host_port(&authority[i + 1..]) {
+                    return Some(SplitAuthority {
+                        userinfo: Some(userinfo),
+                        host,
+                        port,
+                    });
+                }
+            }
+        }
+
+        split_host_port(authority).map(|(host, port)| SplitAuthority {
+            userinfo: None,
+            host,
+            port,
+        })
+    }
+}
+
+fn split_host_port(s: &str) -> Option<(&str, Option<&str>)> {
+    let host_len = if s.starts_with('[') {
+        match s.find(']') {
+            Some(i) if i > 1 => i + 1,
+            _ => return None,
+        }
+    } else {
+        match s.find(&['[', ']', ':'][..]) {
+            Some(0) => return None,
+            Some(i) => i,
+            None if s.is_empty() => return None,
+            None => s.len(),
+        }
+    };
+
+    let (host, rest) = s.split_at(host_len);
+
+    if rest.is_empty() {
+        return Some((host, None));
+    }
+
+    match rest.strip_prefix(':') {
+        Some(port) if !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()) => {
+            Some((host, Some(port)))
+        }
+        _ => None,
+    }
+}
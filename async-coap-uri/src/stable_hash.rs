@@ -0,0 +1,110 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use core::hash::Hasher;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// A [`Hasher`] whose output only depends on the bytes written to it, for hashes which
+/// must be consistent across processes, platforms, and versions of this crate.
+///
+/// The standard library's default hasher is randomly keyed for every process, so its
+/// hashes can't be persisted or shared. This hasher implements 64-bit [FNV-1a], which
+/// is unkeyed, and integers are always written in little-endian byte order. The
+/// algorithm is part of this type's contract and won't change.
+///
+/// Since FNV-1a isn't resistant to collision attacks, this shouldn't be used for hash
+/// tables keyed by untrusted data.
+///
+/// [FNV-1a]: http://www.isthe.com/chongo/tech/comp/fnv/index.html#FNV-1a
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct StableHasher(u64);
+
+impl StableHasher {
+    /// Creates a new hasher.
+    pub const fn new() -> StableHasher {
+        StableHasher(FNV_OFFSET_BASIS)
+    }
+
+    /// Returns the stable hash of `bytes`.
+    pub fn hash_bytes(bytes: &[u8]) -> u64 {
+        let mut hasher = StableHasher::new();
+        hasher.write(bytes);
+        hasher.finish()
+    }
+}
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        StableHasher::new()
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 = (self.0 ^ u64::from(*b)).wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes())
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes())
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes())
+    }
+
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes())
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        // Written as a `u64`, so that the hash doesn't depend on the platform.
+        self.write_u64(i as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fnv1a_vectors() {
+        assert_eq!(0xcbf29ce484222325, StableHasher::hash_bytes(b""));
+        assert_eq!(0xaf63dc4c8601ec8c, StableHasher::hash_bytes(b"a"));
+        assert_eq!(0x85944171f73967e8, StableHasher::hash_bytes(b"foobar"));
+    }
+
+    #[test]
+    fn integers_are_little_endian() {
+        let mut hasher = StableHasher::new();
+        hasher.write_u32(0x0403_0201);
+        hasher.write_usize(5);
+        assert_eq!(
+            StableHasher::hash_bytes(&[1, 2, 3, 4, 5, 0, 0, 0, 0, 0, 0, 0]),
+            hasher.finish()
+        );
+    }
+}
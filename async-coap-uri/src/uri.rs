@@ -14,7 +14,7 @@
 //
 
 use super::*;
-use std::ops::Deref;
+use core::ops::Deref;
 
 /// Unsized string-slice type guaranteed to contain a well-formed [IETF-RFC3986] URI
 /// *or* [network path](index.html#network-path-support).
@@ -95,8 +95,8 @@ impl AnyUriRef for Uri {
     }
 }
 
-impl std::fmt::Display for Uri {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+impl core::fmt::Display for Uri {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::result::Result<(), core::fmt::Error> {
         self.write_to(f)
     }
 }
@@ -123,7 +123,7 @@ impl Uri {
         {
             Ok(unsafe { Self::from_str_unchecked(input) })
         } else {
-            Err(ParseError::new("Not a URI", None))
+            Err(ParseError::with_kind(ParseErrorKind::InvalidUri, 0..0))
         }
     }
 
@@ -164,6 +164,7 @@ impl Uri {
     }
 
     /// Copy the content of this [`&Uri`][Uri] into a new [`UriBuf`] and return it.
+    #[cfg(feature = "std")]
     pub fn to_uri_buf(&self) -> UriBuf {
         unsafe { UriBuf::from_string_unchecked(self.to_string()) }
     }
@@ -289,4 +290,23 @@ mod tests {
             UriType::UriCannotBeABase
         );
     }
+
+    #[test]
+    fn stable_hash() {
+        let uri = iuri!("coap://example.com/sensors/temp");
+
+        assert_eq!(
+            StableHasher::hash_bytes(b"coap://example.com/sensors/temp"),
+            uri.stable_hash()
+        );
+        assert_eq!(uri.stable_hash(), uri.to_uri_ref_buf().stable_hash());
+        assert_eq!(
+            irel_ref!("sensors/temp").stable_hash(),
+            iuri_ref!("sensors/temp").stable_hash()
+        );
+        assert_ne!(
+            uri.stable_hash(),
+            iuri!("coap://example.com/sensors/humidity").stable_hash()
+        );
+    }
 }
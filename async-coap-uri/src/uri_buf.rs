@@ -14,6 +14,7 @@
 //
 
 use super::*;
+use std::borrow::Borrow;
 use std::convert::TryFrom;
 use std::fmt::Write;
 use std::ops::Deref;
@@ -33,6 +34,7 @@ use std::str::FromStr;
 pub struct UriBuf(pub(super) UriRefBuf);
 
 impl_uri_buf_traits!(UriBuf, Uri);
+_impl_uri_buf_cmp_borrowed!(UriBuf; UriRef, Uri, RelRef);
 
 impl Deref for UriBuf {
     type Target = Uri;
@@ -48,12 +50,32 @@ impl AsRef<Uri> for UriBuf {
     }
 }
 
+impl AsRef<UriRef> for UriBuf {
+    fn as_ref(&self) -> &UriRef {
+        self.as_uri_ref()
+    }
+}
+
+impl Borrow<UriRef> for UriBuf {
+    fn borrow(&self) -> &UriRef {
+        self.as_uri_ref()
+    }
+}
+
 impl From<&Uri> for UriBuf {
     fn from(x: &Uri) -> Self {
         x.to_uri_buf()
     }
 }
 
+impl ToOwned for Uri {
+    type Owned = UriBuf;
+
+    fn to_owned(&self) -> Self::Owned {
+        unsafe { UriBuf::from_str_unchecked(self.as_str()) }
+    }
+}
+
 impl FromStr for UriBuf {
     type Err = ParseError;
 
@@ -107,6 +129,17 @@ impl UriBuf {
     pub unsafe fn from_string_unchecked(s: String) -> UriBuf {
         UriBuf(UriRefBuf::from_string_unchecked(s))
     }
+
+    /// Unchecked version of [`UriBuf::from_str`].
+    ///
+    /// # Safety
+    ///
+    /// This method is marked as unsafe because it allows you to construct a `UriBuf` with
+    /// a value that is not a well-formed URI.
+    #[inline(always)]
+    pub unsafe fn from_str_unchecked(s: &str) -> UriBuf {
+        UriBuf(UriRefBuf::from_str_unchecked(s))
+    }
 }
 
 impl UriBuf {
@@ -229,13 +262,30 @@ impl UriBuf {
         // UNWRAP-SAFETY: This is safe because we are fully
         // escaping the host and we already know rel_ref to
         // be well-formed.
-        uri_format!("//{}/{}", host.escape_uri().full(), rel_ref).unwrap()
+        let mut ret = String::from("//");
+        ret.extend(host.escape_uri().full());
+        ret.push('/');
+        ret.push_str(rel_ref.as_str());
+        UriBuf::from_string(ret).unwrap()
     }
 
     /// Constructs a `UriBuf` from a scheme, host and an optional port number.
     ///
-    /// The host should not be percent encoded. If the given scheme contains invalid
-    /// characters, this method will panic.
+    /// The host should not be percent encoded. Hosts containing colons are assumed to be
+    /// IPv6 address literals (optionally followed by a zone identifier) and are enclosed
+    /// in brackets. If the given scheme contains invalid characters, this method will panic.
+    ///
+    /// ```
+    /// # use async_coap_uri::prelude::*;
+    /// assert_eq!(
+    ///     UriBuf::from_scheme_host_port("coap", "example.com", Some(1234)),
+    ///     uri!("coap://example.com:1234")
+    /// );
+    /// assert_eq!(
+    ///     UriBuf::from_scheme_host_port("coap", "fe80::1%eth0", None),
+    ///     uri!("coap://[fe80::1%25eth0]")
+    /// );
+    /// ```
     pub fn from_scheme_host_port<Sch, Hos>(scheme: Sch, host: Hos, port: Option<u16>) -> UriBuf
     where
         Sch: Into<String>,
@@ -258,13 +308,15 @@ impl UriBuf {
 
         // Trim enclosing brackets.
         if host.starts_with('[') && host.ends_with(']') {
-            host = &host[1..host.len()];
+            host = &host[1..host.len() - 1];
         }
 
         if host.find(':').is_some() {
             ret.push('[');
-            ret.extend(host.escape_uri());
+            ret.extend(host.escape_uri().for_authority());
             ret.push(']');
+        } else {
+            ret.extend(host.escape_uri().for_host());
         }
 
         if let Some(port) = port {
@@ -280,9 +332,12 @@ impl UriBuf {
         let components = UriRawComponents::from_str(s)?;
 
         if components.uri_type().can_borrow_as_uri() {
-            Ok(unsafe { Self::from_string_unchecked(s.to_string()) })
+            Ok(unsafe { Self::from_str_unchecked(s) })
         } else {
-            Err(ParseError::new("Missing scheme or authority", None))
+            Err(ParseError::with_kind(
+                ParseErrorKind::MissingSchemeOrAuthority,
+                0..0,
+            ))
         }
     }
 
@@ -293,7 +348,30 @@ impl UriBuf {
         if components.uri_type().can_borrow_as_uri() {
             Ok(unsafe { Self::from_string_unchecked(s) })
         } else {
-            Err(ParseError::new("Missing scheme or authority", None))
+            Err(ParseError::with_kind(
+                ParseErrorKind::MissingSchemeOrAuthority,
+                0..0,
+            ))
+        }
+    }
+
+    /// Attempts to create a new [`UriBuf`] from a string slice using the given
+    /// [`ParseMode`].
+    ///
+    /// See [`UriRefBuf::from_str_with_mode`] for more information.
+    pub fn from_str_with_mode<S: AsRef<str>>(
+        s: S,
+        mode: ParseMode,
+    ) -> Result<(UriBuf, Vec<LenientFix>), ParseError> {
+        let (uri_ref, fixes) = UriRefBuf::from_str_with_mode(s, mode)?;
+
+        if uri_ref.uri_type().can_borrow_as_uri() {
+            Ok((UriBuf(uri_ref), fixes))
+        } else {
+            Err(ParseError::with_kind(
+                ParseErrorKind::MissingSchemeOrAuthority,
+                0..0,
+            ))
         }
     }
 
@@ -360,4 +438,36 @@ mod tests {
             UriBuf::try_from("https://www.google.com/"),
         );
     }
+
+    #[test]
+    fn test_from_scheme_host_port() {
+        assert_eq!(
+            UriBuf::from_scheme_host_port("coap", "example.com", None),
+            uri!("coap://example.com")
+        );
+        assert_eq!(
+            UriBuf::from_scheme_host_port("coap", "192.168.1.1", Some(1234)),
+            uri!("coap://192.168.1.1:1234")
+        );
+        assert_eq!(
+            UriBuf::from_scheme_host_port("coap", "::1", Some(1234)),
+            uri!("coap://[::1]:1234")
+        );
+        assert_eq!(
+            UriBuf::from_scheme_host_port("coap", "[::1]", None),
+            uri!("coap://[::1]")
+        );
+        assert_eq!(
+            UriBuf::from_scheme_host_port("coap", "fe80::1%eth0", None),
+            uri!("coap://[fe80::1%25eth0]")
+        );
+        assert_eq!(
+            UriBuf::from_scheme_host_port("coap", "bad host@", None),
+            uri!("coap://bad%20host%40")
+        );
+        assert_eq!(
+            UriBuf::from_scheme_host_port("coap+sms", "+15551234567", None),
+            uri!("coap+sms://+15551234567")
+        );
+    }
 }
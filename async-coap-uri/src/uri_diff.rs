@@ -0,0 +1,205 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+
+/// A component-level difference between two URI references, which can be applied to
+/// other URI references.
+///
+/// A `UriDiff` records only the components which differ, so it is useful for protocols
+/// which transmit updates like "same as before, but with a different path". Components
+/// are compared in their raw, percent-encoded form.
+///
+/// For each component other than the path, `None` means that the component is
+/// unchanged and `Some(None)` means that the component is removed.
+///
+/// ## Example
+///
+/// ```
+/// use async_coap_uri::prelude::*;
+/// use async_coap_uri::UriDiff;
+///
+/// let diff = UriDiff::between(
+///     uri!("coap://example.com/sensors/temp?unit=c"),
+///     uri!("coap://example.com/sensors/humidity?unit=c"),
+/// );
+///
+/// assert_eq!(diff.path(), Some("/sensors/humidity"));
+/// assert_eq!(diff.query(), None);
+///
+/// assert_eq!(
+///     diff.apply(uri!("coap://[::1]/sensors/temp?unit=c")).unwrap(),
+///     uri_ref!("coap://[::1]/sensors/humidity?unit=c"),
+/// );
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default)]
+pub struct UriDiff {
+    scheme: Option<Option<String>>,
+    authority: Option<Option<String>>,
+    path: Option<String>,
+    query: Option<Option<String>>,
+    fragment: Option<Option<String>>,
+}
+
+fn diff_component(from: Option<&str>, to: Option<&str>) -> Option<Option<String>> {
+    if from == to {
+        None
+    } else {
+        Some(to.map(ToString::to_string))
+    }
+}
+
+fn apply_component<'a>(
+    change: &'a Option<Option<String>>,
+    current: Option<&'a str>,
+) -> Option<&'a str> {
+    match change {
+        Some(x) => x.as_deref(),
+        None => current,
+    }
+}
+
+impl UriDiff {
+    /// Computes the components which must change to turn `from` into `to`.
+    ///
+    /// Applying the returned diff to `from` will always yield `to`.
+    pub fn between<A: AnyUriRef + ?Sized, B: AnyUriRef + ?Sized>(from: &A, to: &B) -> UriDiff {
+        let from = from.components();
+        let to = to.components();
+
+        UriDiff {
+            scheme: diff_component(from.scheme(), to.scheme()),
+            authority: diff_component(from.raw_authority(), to.raw_authority()),
+            path: if from.raw_path() == to.raw_path() {
+                None
+            } else {
+                Some(to.raw_path().to_string())
+            },
+            query: diff_component(from.raw_query(), to.raw_query()),
+            fragment: diff_component(from.raw_fragment(), to.raw_fragment()),
+        }
+    }
+
+    /// Returns true if this diff doesn't change any components.
+    pub fn is_empty(&self) -> bool {
+        self == &UriDiff::default()
+    }
+
+    /// Returns the changed scheme, or `None` if the scheme is unchanged.
+    pub fn scheme(&self) -> Option<Option<&str>> {
+        self.scheme.as_ref().map(Option::as_deref)
+    }
+
+    /// Returns the changed raw authority, or `None` if the authority is unchanged.
+    pub fn authority(&self) -> Option<Option<&str>> {
+        self.authority.as_ref().map(Option::as_deref)
+    }
+
+    /// Returns the changed raw path, or `None` if the path is unchanged.
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    /// Returns the changed raw query, or `None` if the query is unchanged.
+    pub fn query(&self) -> Option<Option<&str>> {
+        self.query.as_ref().map(Option::as_deref)
+    }
+
+    /// Returns the changed raw fragment, or `None` if the fragment is unchanged.
+    pub fn fragment(&self) -> Option<Option<&str>> {
+        self.fragment.as_ref().map(Option::as_deref)
+    }
+
+    /// Applies this diff to `uri_ref`, returning the updated URI reference.
+    ///
+    /// All of the changes are applied at once, so an error is only returned if the
+    /// resulting combination of components is inconsistent, such as a relative path
+    /// combined with an authority.
+    pub fn apply<T: AnyUriRef + ?Sized>(&self, uri_ref: &T) -> Result<UriRefBuf, ParseError> {
+        let current = uri_ref.components();
+
+        UriRawComponents::from_components(
+            apply_component(&self.scheme, current.scheme()),
+            apply_component(&self.authority, current.raw_authority()),
+            self.path.as_deref().unwrap_or(current.raw_path()),
+            apply_component(&self.query, current.raw_query()),
+            apply_component(&self.fragment, current.raw_fragment()),
+        )
+        .map(|components| components.to_uri_ref_buf())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn between() {
+        let diff = UriDiff::between(uri_ref!("coap://a/b?c#d"), uri_ref!("coap://a/b?c#d"));
+        assert!(diff.is_empty());
+
+        let diff = UriDiff::between(uri_ref!("coap://a/b?c#d"), uri_ref!("coaps://a/e#d"));
+        assert!(!diff.is_empty());
+        assert_eq!(diff.scheme(), Some(Some("coaps")));
+        assert_eq!(diff.authority(), None);
+        assert_eq!(diff.path(), Some("/e"));
+        assert_eq!(diff.query(), Some(None));
+        assert_eq!(diff.fragment(), None);
+
+        let diff = UriDiff::between(uri_ref!("coap://a/b?c"), uri_ref!("coap://a/b?c="));
+        assert_eq!(diff.query(), Some(Some("c=")));
+
+        let diff = UriDiff::between(uri_ref!("coap://a/b?"), uri_ref!("coap://a/b"));
+        assert_eq!(diff.query(), Some(None));
+    }
+
+    #[test]
+    fn round_trip() {
+        let uris = [
+            uri_ref!(""),
+            uri_ref!("a"),
+            uri_ref!("/a/b"),
+            uri_ref!("?q"),
+            uri_ref!("#f"),
+            uri_ref!("//host"),
+            uri_ref!("//host/a?q#f"),
+            uri_ref!("coap://host"),
+            uri_ref!("coap://user@host:5683/a/b?c=d&e#f"),
+            uri_ref!("coap:a/b"),
+            uri_ref!("mailto:user@example.com"),
+            uri_ref!("coap://[::1]/"),
+        ];
+
+        for from in uris.iter() {
+            for to in uris.iter() {
+                let diff = UriDiff::between(*from, *to);
+                assert_eq!(diff.apply(*from).unwrap(), **to, "{:?} -> {:?}", from, to);
+            }
+        }
+    }
+
+    #[test]
+    fn apply_to_other() {
+        let diff = UriDiff::between(uri!("coap://a/rd?ep=node"), uri!("coap://a/rd/1234"));
+        assert_eq!(
+            diff.apply(uri!("coap://b:61616/rd?ep=node")).unwrap(),
+            uri_ref!("coap://b:61616/rd/1234")
+        );
+
+        // Removing the authority would make the path ambiguous.
+        let diff = UriDiff::between(uri_ref!("//a/b"), uri_ref!("/b"));
+        assert!(diff.apply(uri_ref!("//a//b")).is_err());
+    }
+}
@@ -15,8 +15,8 @@
 
 use super::*;
 
-use std::fmt;
-use std::str::FromStr;
+use core::fmt;
+use core::str::FromStr;
 
 /// Struct that holds parsed URI components.
 ///
@@ -117,12 +117,14 @@ impl fmt::Display for UriRawComponents<'_> {
     }
 }
 
+#[cfg(feature = "std")]
 impl From<UriRawComponents<'_>> for String {
     fn from(comp: UriRawComponents<'_>) -> Self {
         String::from(&comp)
     }
 }
 
+#[cfg(feature = "std")]
 impl From<&UriRawComponents<'_>> for String {
     fn from(comp: &UriRawComponents<'_>) -> Self {
         comp.to_string()
@@ -133,11 +135,12 @@ impl<'a> UriRawComponents<'a> {
     /// Constructs a new `UriRawComponents` from the given string slice, which is assumed
     /// to contain a URI-reference.
     pub fn from_str(uri: &'a str) -> Result<UriRawComponents<'a>, ParseError> {
-        if let Some(i) = uri.unescape_uri().first_error() {
-            return Err(ParseError::from(i));
+        if let Some(err) = uri.unescape_uri().first_error() {
+            let component = UriComponent::containing(uri, err.index, false);
+            return Err(ParseError::from(err).in_component(component, 0));
         }
 
-        let captures = match RFC3986_APPENDIX_B.captures(uri) {
+        let split = match SplitUriRef::new(uri) {
             Some(x) => x,
             None => {
                 return Err(ParseError::new(
@@ -147,32 +150,31 @@ impl<'a> UriRawComponents<'a> {
             }
         };
 
-        let scheme = if let Some(x) = captures.get(2) {
+        if let Some(scheme) = split.scheme {
             // Do an additional syntax check on the scheme to make sure it is valid.
-            if URI_CHECK_SCHEME.captures(x.as_str()).is_some() {
-                Some(x.as_str())
-            } else {
-                return Err(ParseError::new(
-                    "Invalid URI scheme",
-                    Some(x.start()..x.end()),
+            if !is_valid_scheme(scheme) {
+                return Err(ParseError::with_kind(
+                    ParseErrorKind::InvalidScheme,
+                    0..scheme.len(),
                 ));
             }
-        } else {
-            None
-        };
-
-        let authority = captures.get(4).map(|x| x.as_str());
-        let query = captures.get(7).map(|x| x.as_str());
-        let fragment = captures.get(9).map(|x| x.as_str());
+        }
 
-        // Unwrap safety: Capture 5 is not an optional capture in the regex.
-        let path = captures.get(5).unwrap().as_str();
+        let ret = unsafe {
+            UriRawComponents::from_components_unchecked(
+                split.scheme,
+                split.authority,
+                split.path,
+                split.query,
+                split.fragment,
+            )
+        };
 
-        unsafe {
-            Ok(UriRawComponents::from_components_unchecked(
-                scheme, authority, path, query, fragment,
-            ))
+        if ret.authority.is_some() {
+            ret.check_host(split.scheme.map_or(0, |x| x.len() + 1) + 2)?;
         }
+
+        Ok(ret)
     }
 
     #[inline(always)]
@@ -285,36 +287,42 @@ impl<'a> UriRawComponents<'a> {
 
     /// Unescaped (percent-decoded) version of [`UriRawComponents::raw_fragment`], using
     /// `std::borrow::Cow<str>` instead of `&str`.
+    #[cfg(feature = "std")]
     pub fn fragment(&self) -> Option<Cow<'_, str>> {
         self.raw_fragment().map(|f| f.unescape_uri().to_cow())
     }
 
-    /// Unescaped (percent-decoded) version of [`UriRawComponents::raw_host`], using
-    /// `std::borrow::Cow<str>` instead of `&str`.
-    pub fn host(&self) -> Option<Cow<'_, str>> {
-        self.raw_host().map(|f| f.unescape_uri().to_cow())
+    /// Parsed version of [`UriRawComponents::raw_host`], which distinguishes
+    /// registered names from IP address literals. Registered names are percent-decoded.
+    #[cfg(feature = "std")]
+    pub fn host(&self) -> Option<UriHost<'a>> {
+        self.raw_host().and_then(|f| UriHost::from_raw(f).ok())
     }
 
     /// Unescaped (percent-decoded) version of [`UriRawComponents::raw_authority`], using
     /// `std::borrow::Cow<str>` instead of `&str`.
+    #[cfg(feature = "std")]
     pub fn authority(&self) -> Option<Cow<'_, str>> {
         self.raw_authority().map(|f| f.unescape_uri().to_cow())
     }
 
     /// Unescaped (percent-decoded) version of [`UriRawComponents::raw_userinfo`], using
     /// `std::borrow::Cow<str>` instead of `&str`.
+    #[cfg(feature = "std")]
     pub fn userinfo(&self) -> Option<Cow<'_, str>> {
         self.raw_userinfo().map(|f| f.unescape_uri().to_cow())
     }
 
     /// Unescaped (percent-decoded) version of [`UriRawComponents::raw_query`], using
     /// `std::borrow::Cow<str>` instead of `&str`.
+    #[cfg(feature = "std")]
     pub fn query(&self) -> Option<Cow<'_, str>> {
         self.raw_query().map(|f| f.unescape_uri().to_cow())
     }
 
     /// Unescaped (percent-decoded) version of [`UriRawComponents::raw_path_segments`], using
     /// `std::borrow::Cow<str>` instead of `&str`.
+    #[cfg(feature = "std")]
     pub fn path_segments(&self) -> impl Iterator<Item = Cow<'_, str>> {
         self.raw_path_segments()
             .map(|item| item.unescape_uri().to_cow())
@@ -322,6 +330,7 @@ impl<'a> UriRawComponents<'a> {
 
     /// Unescaped (percent-decoded) version of [`UriRawComponents::raw_query_items`], using
     /// `std::borrow::Cow<str>` instead of `&str`.
+    #[cfg(feature = "std")]
     pub fn query_items(&self) -> impl Iterator<Item = Cow<'_, str>> {
         self.raw_query_items()
             .map(|item| item.unescape_uri().to_cow())
@@ -329,6 +338,7 @@ impl<'a> UriRawComponents<'a> {
 
     /// Unescaped (percent-decoded) version of [`UriRawComponents::raw_query_key_values`], using
     /// `std::borrow::Cow<str>` instead of `&str`.
+    #[cfg(feature = "std")]
     pub fn query_key_values(&self) -> impl Iterator<Item = (Cow<'_, str>, Cow<'_, str>)> {
         self.raw_query_key_values().map(|item| {
             (
@@ -367,6 +377,281 @@ impl<'a> UriRawComponents<'a> {
     }
 }
 
+/// The default `UriRawComponents` is the empty URI-reference.
+impl Default for UriRawComponents<'_> {
+    fn default() -> Self {
+        UriRawComponents::new()
+    }
+}
+
+/// # Construction and Editing
+///
+/// In addition to being produced by parsing, a `UriRawComponents` can be assembled
+/// component-by-component, making it a lightweight borrowed builder: since it implements
+/// [`AnyUriRef`], the result can be written out, displayed, or used for resolution without
+/// allocating any intermediate strings.
+///
+/// All components are given in their raw, percent-encoded form. Each setter checks both the
+/// component itself and its consistency with the other components, so a `UriRawComponents`
+/// always describes a well-formed URI-reference that will be parsed back into the same
+/// components. The span of any returned [`ParseError`] is relative to the offending component.
+///
+/// ```
+/// use async_coap_uri::prelude::*;
+///
+/// let components = UriRawComponents::new()
+///     .with_scheme(Some("coap"))?
+///     .with_authority(Some("example.com:1234"))?
+///     .with_path("/sensors/temp")?
+///     .with_query(Some("units=C"))?;
+///
+/// assert_eq!(components.raw_host(), Some("example.com"));
+/// assert_eq!(components.port(), Some(1234));
+/// assert_eq!(
+///     components.to_uri_ref_buf(),
+///     uri_ref!("coap://example.com:1234/sensors/temp?units=C")
+/// );
+///
+/// // Paths must start with a slash when there is an authority.
+/// assert!(components.with_path("relative").is_err());
+/// # Ok::<(), async_coap_uri::ParseError>(())
+/// ```
+impl<'a> UriRawComponents<'a> {
+    /// Creates a new, empty `UriRawComponents`, representing the empty URI-reference.
+    pub const fn new() -> UriRawComponents<'a> {
+        UriRawComponents {
+            scheme: None,
+            authority: None,
+            userinfo: None,
+            host: None,
+            port: None,
+            path: "",
+            query: None,
+            fragment: None,
+        }
+    }
+
+    /// Constructs a new `UriRawComponents` from the given raw, percent-encoded components,
+    /// checking that they are valid and consistent with each other.
+    ///
+    /// This is the checked version of [`UriRawComponents::from_components_unchecked`].
+    pub fn from_components(
+        scheme: Option<&'a str>,
+        authority: Option<&'a str>,
+        path: &'a str,
+        query: Option<&'a str>,
+        fragment: Option<&'a str>,
+    ) -> Result<UriRawComponents<'a>, ParseError> {
+        let ret =
+            unsafe { Self::from_components_unchecked(scheme, authority, path, query, fragment) };
+        ret.check()?;
+        Ok(ret)
+    }
+
+    /// Replaces the scheme, returning an error if the scheme is malformed.
+    ///
+    /// Removing the scheme will fail if the first path segment contains a colon, since
+    /// the result would be ambiguous.
+    pub fn set_scheme(&mut self, scheme: Option<&'a str>) -> Result<(), ParseError> {
+        *self = self.with_scheme(scheme)?;
+        Ok(())
+    }
+
+    /// Replaces the authority, returning an error if the authority is malformed or if
+    /// it is inconsistent with the current path.
+    ///
+    /// The values returned by [`raw_userinfo`], [`raw_host`], and [`port`] are updated to
+    /// reflect the new authority.
+    ///
+    /// [`raw_userinfo`]: UriRawComponents::raw_userinfo
+    /// [`raw_host`]: UriRawComponents::raw_host
+    /// [`port`]: UriRawComponents::port
+    pub fn set_authority(&mut self, authority: Option<&'a str>) -> Result<(), ParseError> {
+        *self = self.with_authority(authority)?;
+        Ok(())
+    }
+
+    /// Replaces the path, returning an error if the path is malformed or if it is
+    /// inconsistent with the other components.
+    pub fn set_path(&mut self, path: &'a str) -> Result<(), ParseError> {
+        *self = self.with_path(path)?;
+        Ok(())
+    }
+
+    /// Replaces the query, returning an error if the query is malformed.
+    pub fn set_query(&mut self, query: Option<&'a str>) -> Result<(), ParseError> {
+        *self = self.with_query(query)?;
+        Ok(())
+    }
+
+    /// Replaces the fragment, returning an error if the fragment is malformed.
+    pub fn set_fragment(&mut self, fragment: Option<&'a str>) -> Result<(), ParseError> {
+        *self = self.with_fragment(fragment)?;
+        Ok(())
+    }
+
+    /// Returns a copy of this `UriRawComponents` with the scheme replaced.
+    ///
+    /// See [`UriRawComponents::set_scheme`] for more information.
+    pub fn with_scheme(&self, scheme: Option<&'a str>) -> Result<Self, ParseError> {
+        let ret = UriRawComponents { scheme, ..*self };
+        ret.check()?;
+        Ok(ret)
+    }
+
+    /// Returns a copy of this `UriRawComponents` with the authority replaced.
+    ///
+    /// See [`UriRawComponents::set_authority`] for more information.
+    pub fn with_authority(&self, authority: Option<&'a str>) -> Result<Self, ParseError> {
+        Self::from_components(self.scheme, authority, self.path, self.query, self.fragment)
+    }
+
+    /// Returns a copy of this `UriRawComponents` with the path replaced.
+    ///
+    /// See [`UriRawComponents::set_path`] for more information.
+    pub fn with_path(&self, path: &'a str) -> Result<Self, ParseError> {
+        let ret = UriRawComponents { path, ..*self };
+        ret.check()?;
+        Ok(ret)
+    }
+
+    /// Returns a copy of this `UriRawComponents` with the query replaced.
+    ///
+    /// See [`UriRawComponents::set_query`] for more information.
+    pub fn with_query(&self, query: Option<&'a str>) -> Result<Self, ParseError> {
+        let ret = UriRawComponents { query, ..*self };
+        ret.check()?;
+        Ok(ret)
+    }
+
+    /// Returns a copy of this `UriRawComponents` with the fragment replaced.
+    ///
+    /// See [`UriRawComponents::set_fragment`] for more information.
+    pub fn with_fragment(&self, fragment: Option<&'a str>) -> Result<Self, ParseError> {
+        let ret = UriRawComponents { fragment, ..*self };
+        ret.check()?;
+        Ok(ret)
+    }
+
+    /// Verifies that the host is well-formed if it is an IP literal, using
+    /// `authority_offset` to compute the span of any returned error.
+    ///
+    /// Without the `std` feature, only the brackets around IP literals are checked.
+    fn check_host(&self, authority_offset: usize) -> Result<(), ParseError> {
+        let authority = match self.authority {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+
+        match self.host {
+            #[cfg(feature = "std")]
+            Some(host) if host.starts_with('[') => {
+                // `host` is always a subslice of `authority`.
+                let offset =
+                    authority_offset + (host.as_ptr() as usize - authority.as_ptr() as usize);
+                UriHost::from_raw(host).map(|_| ()).map_err(|_| {
+                    ParseError::with_kind(ParseErrorKind::InvalidHost, offset..offset + host.len())
+                })
+            }
+            None if authority.contains(&['[', ']'][..]) => Err(ParseError::with_kind(
+                ParseErrorKind::InvalidHost,
+                authority_offset..authority_offset + authority.len(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Verifies that every component is well-formed and that the components, when written
+    /// out together, would be parsed back into the same components.
+    ///
+    /// The spans of any returned errors are relative to the written-out URI-reference.
+    fn check(&self) -> Result<(), ParseError> {
+        fn check_component(
+            s: &str,
+            delimiters: &[char],
+            component: UriComponent,
+            offset: usize,
+        ) -> Result<(), ParseError> {
+            if let Some(i) = s.find(delimiters) {
+                let kind = ParseErrorKind::IllegalCharacter {
+                    char: s[i..].chars().next().unwrap(),
+                    component: Some(component),
+                };
+                return Err(ParseError::with_kind(kind, i + offset..i + offset + 1));
+            }
+
+            match s.unescape_uri().first_error() {
+                Some(err) => Err(ParseError::from(err).in_component(component, offset)),
+                None => Ok(()),
+            }
+        }
+
+        let mut offset = 0;
+
+        if let Some(scheme) = self.scheme {
+            if !is_valid_scheme(scheme) {
+                return Err(ParseError::with_kind(
+                    ParseErrorKind::InvalidScheme,
+                    0..scheme.len(),
+                ));
+            }
+            offset += scheme.len() + 1;
+        }
+
+        if let Some(authority) = self.authority {
+            offset += 2;
+            check_component(authority, &['/', '?', '#'], UriComponent::Authority, offset)?;
+            if !authority.is_empty() && SplitAuthority::new(authority).is_none() {
+                return Err(ParseError::with_kind(
+                    ParseErrorKind::InvalidAuthority,
+                    offset..offset + authority.len(),
+                ));
+            }
+            self.check_host(offset)?;
+            offset += authority.len();
+        }
+
+        check_component(self.path, &['?', '#'], UriComponent::Path, offset)?;
+
+        let inconsistent_path = |range: core::ops::Range<usize>| {
+            Err(ParseError::with_kind(
+                ParseErrorKind::InconsistentComponent(UriComponent::Path),
+                range.start + offset..range.end + offset,
+            ))
+        };
+
+        if self.authority.is_some() {
+            if !self.path.is_empty() && !self.path.starts_with('/') {
+                // Path must be empty or start with a slash when authority is present.
+                return inconsistent_path(0..1);
+            }
+        } else if self.path.starts_with("//") {
+            // Path cannot start with two slashes when authority is absent.
+            return inconsistent_path(0..2);
+        } else if self.scheme.is_none() {
+            if let Some(i) = self.path_as_rel_ref().colon_in_first_path_segment() {
+                // Colon in first path segment when scheme is absent.
+                return inconsistent_path(i..i + 1);
+            }
+        }
+
+        offset += self.path.len();
+
+        if let Some(query) = self.query {
+            offset += 1;
+            check_component(query, &['#'], UriComponent::Query, offset)?;
+            offset += query.len();
+        }
+
+        if let Some(fragment) = self.fragment {
+            offset += 1;
+            check_component(fragment, &[], UriComponent::Fragment, offset)?;
+        }
+
+        Ok(())
+    }
+}
+
 impl<'a> UriRawComponents<'a> {
     /// Constructs a new `UriRawComponents` from the given raw, percent-encoded components,
     /// without checking that the components are valid.
@@ -383,36 +668,18 @@ impl<'a> UriRawComponents<'a> {
         let host;
         let port;
 
-        if let Some(authority) = authority {
-            match URI_AUTHORITY.captures(authority) {
-                Some(y) => {
-                    userinfo = if let Some(x) = y.get(2) {
-                        Some(x.as_str())
-                    } else {
-                        None
-                    };
-                    host = if let Some(x) = y.get(3) {
-                        Some(x.as_str())
-                    } else {
-                        None
-                    };
-                    port = if let Some(x) = y.get(5) {
-                        u16::from_str(x.as_str()).ok()
-                    } else {
-                        None
-                    };
-                }
-                None => {
-                    userinfo = None;
-                    host = None;
-                    port = None;
-                }
+        match authority.and_then(SplitAuthority::new) {
+            Some(x) => {
+                userinfo = x.userinfo;
+                host = Some(x.host);
+                port = x.port.and_then(|x| u16::from_str(x).ok());
             }
-        } else {
-            userinfo = None;
-            host = None;
-            port = None;
-        };
+            None => {
+                userinfo = None;
+                host = None;
+                port = None;
+            }
+        }
 
         UriRawComponents {
             scheme,
@@ -431,6 +698,140 @@ impl<'a> UriRawComponents<'a> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn build_components() {
+        let components = UriRawComponents::new()
+            .with_scheme(Some("coap"))
+            .and_then(|c| c.with_authority(Some("user@[::1]:5683")))
+            .and_then(|c| c.with_path("/a/b%20c"))
+            .and_then(|c| c.with_query(Some("q=1")))
+            .and_then(|c| c.with_fragment(Some("frag")))
+            .unwrap();
+        assert_eq!(Some("user"), components.raw_userinfo());
+        assert_eq!(Some("[::1]"), components.raw_host());
+        assert_eq!(Some(5683), components.port());
+        assert_eq!(
+            components.to_uri_ref_buf(),
+            iuri_ref!("coap://user@[::1]:5683/a/b%20c?q=1#frag")
+        );
+        assert_eq!(
+            Ok(components),
+            UriRawComponents::from_str("coap://user@[::1]:5683/a/b%20c?q=1#frag")
+        );
+
+        let mut components = components;
+        components.set_authority(None).unwrap();
+        assert_eq!(None, components.raw_host());
+        assert_eq!(None, components.port());
+        components.set_query(None).unwrap();
+        components.set_fragment(None).unwrap();
+        assert_eq!(components.to_string(), "coap:/a/b%20c");
+
+        assert_eq!(UriRawComponents::default(), UriRawComponents::new());
+        assert!(UriRawComponents::new().is_empty());
+    }
+
+    #[test]
+    fn build_components_invalid() {
+        let empty = UriRawComponents::new();
+        assert!(empty.with_scheme(Some("1http")).is_err());
+        assert!(empty.with_authority(Some("a/b")).is_err());
+        assert!(empty.with_path("a?b").is_err());
+        assert!(empty.with_path("//a/b").is_err());
+        assert!(empty.with_path("a:b").is_err());
+        assert!(empty.with_path("%zz").is_err());
+        assert!(empty.with_query(Some("a#b")).is_err());
+        assert!(empty.with_fragment(Some("%")).is_err());
+
+        let with_path = empty.with_path("a/b").unwrap();
+        assert!(with_path.with_authority(Some("example.com")).is_err());
+
+        let with_scheme = empty.with_scheme(Some("g")).unwrap();
+        let with_colon = with_scheme.with_path("a:b").unwrap();
+        assert!(with_colon.with_scheme(None).is_err());
+
+        assert_eq!(
+            Some(1..2),
+            empty.with_path("a?b").err().and_then(|e| e.span())
+        );
+
+        let with_authority = with_scheme.with_authority(Some("example.com")).unwrap();
+        let err = with_authority.with_query(Some("a#b")).unwrap_err();
+        assert_eq!(Some(UriComponent::Query), err.component());
+        assert_eq!(Some(17..18), err.span());
+    }
+
+    #[test]
+    fn ip_literal_hosts() {
+        let components = UriRawComponents::from_str("coap://[fe80::1]:1234/").unwrap();
+        assert_eq!(Some("[fe80::1]"), components.raw_host());
+        assert_eq!(
+            Some(UriHost::Ipv6 {
+                addr: "fe80::1".parse().unwrap(),
+                zone: None
+            }),
+            components.host()
+        );
+        assert_eq!(Some(1234), components.port());
+
+        let components = UriRawComponents::from_str("coap://[v7.a:b]/").unwrap();
+        assert_eq!(Some(UriHost::IpvFuture("v7.a:b")), components.host());
+
+        let err = UriRawComponents::from_str("coap://[fe80::1::2]:1234/").unwrap_err();
+        assert_eq!(&ParseErrorKind::InvalidHost, err.kind());
+        assert_eq!(Some(7..19), err.span());
+
+        let err = UriRawComponents::from_str("coap://user@[::1/").unwrap_err();
+        assert_eq!(&ParseErrorKind::InvalidHost, err.kind());
+        assert_eq!(Some(7..16), err.span());
+
+        assert!(UriRawComponents::from_str("coap://[example.com]/").is_err());
+        assert!(UriRawComponents::new()
+            .with_authority(Some("[::1]x"))
+            .is_err());
+        assert!(UriRawComponents::new()
+            .with_authority(Some("[v1.x]:1"))
+            .is_ok());
+    }
+
+    #[test]
+    fn parse_error_kinds() {
+        let err = UriRawComponents::from_str("1http://example.com/").unwrap_err();
+        assert_eq!(&ParseErrorKind::InvalidScheme, err.kind());
+        assert_eq!(Some(0..5), err.span());
+
+        let err = UriRawComponents::from_str("http://exa mple.com/").unwrap_err();
+        assert_eq!(
+            &ParseErrorKind::IllegalCharacter {
+                char: ' ',
+                component: Some(UriComponent::Authority)
+            },
+            err.kind()
+        );
+        assert_eq!(Some(10..11), err.span());
+
+        let err = UriRawComponents::from_str("http://example.com/a?b%zz").unwrap_err();
+        assert_eq!(Some(UriComponent::Query), err.component());
+        match err.kind() {
+            ParseErrorKind::InvalidEscape { .. } => (),
+            kind => panic!("Unexpected error kind: {:?}", kind),
+        }
+
+        let err = UriRawComponents::from_str("a:b#c\x07").unwrap_err();
+        assert_eq!(Some(UriComponent::Fragment), err.component());
+
+        let err = RelRef::from_str("a b?c").unwrap_err();
+        assert_eq!(Some(UriComponent::Path), err.component());
+
+        let err = UriBuf::from_str("/a/b/c").unwrap_err();
+        assert_eq!(&ParseErrorKind::MissingSchemeOrAuthority, err.kind());
+        assert!(err.span().is_some());
+
+        let err = Uri::from_str("/a/b/c").unwrap_err();
+        assert_eq!(&ParseErrorKind::InvalidUri, err.kind());
+        assert!(err.span().is_some());
+    }
+
     #[test]
     fn components() {
         {
@@ -91,7 +91,7 @@ impl Deref for UriRef {
 /// The default `&UriRef` is an empty URI-reference.
 impl Default for &UriRef {
     fn default() -> Self {
-        iuri_ref!("")
+        iuri_ref!(unsafe "")
     }
 }
 
@@ -101,11 +101,12 @@ impl Default for &UriRef {
 /// is empty it is mutable in name only.
 impl Default for &mut UriRef {
     fn default() -> Self {
-        use std::slice::from_raw_parts_mut;
-        use std::str::from_utf8_unchecked_mut;
+        use core::slice::from_raw_parts_mut;
+        use core::str::from_utf8_unchecked_mut;
         unsafe {
             // SAFETY: An empty slice is pretty harmless, mutable or not.
-            let empty_slice = from_raw_parts_mut(::core::ptr::null_mut::<u8>(), 0);
+            let empty_slice =
+                from_raw_parts_mut(::core::ptr::NonNull::<u8>::dangling().as_ptr(), 0);
             let empty_string = from_utf8_unchecked_mut(empty_slice);
             UriRef::from_str_unchecked_mut(empty_string)
         }
@@ -160,8 +161,8 @@ impl AnyUriRef for UriRef {
     }
 }
 
-impl std::fmt::Display for UriRef {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+impl core::fmt::Display for UriRef {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::result::Result<(), core::fmt::Error> {
         self.write_to(f)
     }
 }
@@ -213,6 +214,18 @@ impl UriRef {
         &self.0
     }
 
+    /// Returns a hash of this URI-reference which, unlike the one computed by its [`Hash`]
+    /// implementation, is the same in every process and every version of this crate.
+    ///
+    /// This is suitable for distributed caches and persisted indices. Like equality, the
+    /// hash is computed on the string as-is, without normalizing it. See
+    /// [`StableHasher`] for details.
+    ///
+    /// [`Hash`]: core::hash::Hash
+    pub fn stable_hash(&self) -> u64 {
+        StableHasher::hash_bytes(self.as_bytes())
+    }
+
     /// Attempts to interpret this [`&UriRef`][UriRef] as a [`&Uri`][Uri], returning `None`
     /// if this `UriRef` doesn't contain a proper URI.
     pub fn as_uri(&self) -> Option<&Uri> {
@@ -479,11 +492,13 @@ impl UriRef {
         })
     }
 
-    /// Percent-decoded *host* as a `std::borrow::Cow<str>`, if present.
+    /// Parsed *host*, if present. Registered names are percent-decoded.
+    ///
+    /// See [`UriHost`] for more information.
     #[cfg(feature = "std")]
-    pub fn host(&self) -> Option<Cow<'_, str>> {
+    pub fn host(&self) -> Option<UriHost<'_>> {
         self.raw_userinfo_host_port()
-            .map(|item| item.1.unescape_uri().to_cow())
+            .and_then(|item| UriHost::from_raw(item.1).ok())
     }
 
     /// Returns a string slice containing the raw, percent-encoded value of the path.
@@ -862,7 +877,7 @@ impl UriRef {
                 ret = unsafe { Self::from_str_unchecked(&self[..i + 1]) };
             }
         } else if path_start == 0 {
-            ret = iuri_ref!("");
+            ret = iuri_ref!(unsafe "");
         }
 
         ret
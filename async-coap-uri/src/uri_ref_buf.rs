@@ -25,10 +25,24 @@ use std::str::FromStr;
 ///
 /// This type implements [`std::ops::Deref<UriRef>`], so you can also use all of the
 /// methods from [`UriRef`](crate::UriRef) on this type.
+///
+/// Short URI references, which are the most common kind, are stored inline rather than
+/// in a separate heap allocation. The contents are only moved to the heap once they grow
+/// too large, or when the `UriRefBuf` is created from a [`String`], whose allocation is
+/// reused.
 #[derive(Clone, Eq, Hash)]
-pub struct UriRefBuf(pub(super) String);
+pub struct UriRefBuf(pub(super) SmallString);
 
 _impl_uri_buf_traits_base!(UriRefBuf, UriRef);
+_impl_uri_buf_cmp_borrowed!(UriRefBuf; UriRef, Uri, RelRef);
+
+impl ToOwned for UriRef {
+    type Owned = UriRefBuf;
+
+    fn to_owned(&self) -> Self::Owned {
+        unsafe { UriRefBuf::from_str_unchecked(self.as_str()) }
+    }
+}
 
 impl FromStr for UriRefBuf {
     type Err = ParseError;
@@ -76,9 +90,9 @@ impl Deref for UriRefBuf {
     }
 }
 
-impl AsRef<String> for UriRefBuf {
-    fn as_ref(&self) -> &String {
-        &self.0
+impl AsRef<UriRef> for UriRefBuf {
+    fn as_ref(&self) -> &UriRef {
+        self.as_uri_ref()
     }
 }
 
@@ -92,25 +106,59 @@ impl std::fmt::Display for UriRefBuf {
 impl UriRefBuf {
     /// Creates a new, empty [`UriRefBuf`].
     pub fn new() -> UriRefBuf {
-        UriRefBuf(String::new())
+        UriRefBuf(SmallString::new())
     }
 
     /// Creates a new, empty [`UriRefBuf`] with a capacity of `capacity`.
     pub fn with_capacity(capacity: usize) -> UriRefBuf {
-        UriRefBuf(String::with_capacity(capacity))
+        UriRefBuf(SmallString::with_capacity(capacity))
     }
 
     /// Attempts to create a new [`UriRefBuf`] from a string reference.
     pub fn from_str<S: AsRef<str> + Copy>(s: S) -> Result<Self, ParseError> {
         let str_ref = s.as_ref();
         UriRef::from_str(str_ref)?;
-        Ok(UriRefBuf(str_ref.to_string()))
+        Ok(UriRefBuf(SmallString::from(str_ref)))
     }
 
     /// Attempts to create a new [`UriRefBuf`] from a [`String`].
     pub fn from_string(s: String) -> Result<Self, ParseError> {
         UriRef::from_str(s.as_str())?;
-        Ok(UriRefBuf(s))
+        Ok(UriRefBuf(SmallString::from(s)))
+    }
+
+    /// Attempts to create a new [`UriRefBuf`] from a string reference using the given
+    /// [`ParseMode`].
+    ///
+    /// In [`ParseMode::Lenient`], the returned vector describes each character that
+    /// was percent-encoded to make the input well-formed. It is always empty in
+    /// [`ParseMode::Strict`].
+    ///
+    /// ```
+    /// # use async_coap_uri::*;
+    /// let input = "coap://example.com/a b";
+    ///
+    /// assert!(UriRefBuf::from_str_with_mode(input, ParseMode::Strict).is_err());
+    ///
+    /// let (uri, fixes) = UriRefBuf::from_str_with_mode(input, ParseMode::Lenient).unwrap();
+    /// assert_eq!(uri, uri_ref!("coap://example.com/a%20b"));
+    /// assert_eq!(fixes[0].span(), 20..21);
+    /// ```
+    pub fn from_str_with_mode<S: AsRef<str>>(
+        s: S,
+        mode: ParseMode,
+    ) -> Result<(Self, Vec<LenientFix>), ParseError> {
+        let s = s.as_ref();
+        match mode {
+            ParseMode::Strict => Self::from_str(s).map(|x| (x, Vec::new())),
+            ParseMode::Lenient => {
+                let (escaped, fixes) = lenient_escape(s, false);
+                match Self::from_string(escaped) {
+                    Ok(x) => Ok((x, fixes)),
+                    Err(err) => Err(unmap_lenient_error(err, &fixes)),
+                }
+            }
+        }
     }
 }
 
@@ -469,7 +517,17 @@ impl UriRefBuf {
     /// This method is marked as unsafe because it allows you to construct a `UriRefBuf` with
     /// a value that is not a well-formed URI reference.
     pub unsafe fn from_string_unchecked(s: String) -> UriRefBuf {
-        UriRefBuf(s)
+        UriRefBuf(SmallString::from(s))
+    }
+
+    /// Unchecked version of [`UriRefBuf::from_str`].
+    ///
+    /// # Safety
+    ///
+    /// This method is marked as unsafe because it allows you to construct a `UriRefBuf` with
+    /// a value that is not a well-formed URI reference.
+    pub unsafe fn from_str_unchecked(s: &str) -> UriRefBuf {
+        UriRefBuf(SmallString::from(s))
     }
 
     /// Borrows a mutable string slice containing this URI reference.
@@ -481,12 +539,14 @@ impl UriRefBuf {
         self.0.as_mut_str()
     }
 
-    /// Borrows a mutable [`String`] reference containing this URI reference.
+    /// Borrows a mutable [`String`] reference containing this URI reference, moving the
+    /// content to the heap first if it is stored inline. The content stays on the heap
+    /// from then on, even if it is later made short enough to be stored inline.
     ///
     /// This method is marked as unsafe because it allows you to change the
     /// content of the URI reference without any checks on syntax.
     pub unsafe fn as_mut_string_ref(&mut self) -> &mut String {
-        &mut self.0
+        self.0.as_mut_string()
     }
 }
 
@@ -650,6 +710,33 @@ mod tests {
         assert!(UriRefBuf::from_str("http://example.com/").is_ok());
     }
 
+    #[test]
+    fn outgrow_inline_storage() {
+        let mut uri = UriRefBuf::from_str("coap://example.com/").unwrap();
+        let mut expected = uri.as_str().to_string();
+
+        for segment in ["sensors", "temperature", "a b", "history"].iter() {
+            uri.push_path_segment(segment, false);
+            expected = format!(
+                "{}/{}",
+                expected.trim_end_matches('/'),
+                segment.escape_uri()
+            );
+            assert_eq!(expected, uri.as_str());
+        }
+
+        uri.push_query_key_value("since", "1h");
+        assert_eq!(
+            uri,
+            iuri_ref!("coap://example.com/sensors/temperature/a%20b/history?since=1h")
+        );
+
+        uri.truncate_path();
+        assert_eq!(uri, iuri_ref!("coap://example.com"));
+        assert_eq!(uri.to_owned(), uri.as_uri_ref().to_owned());
+        assert_eq!("coap://example.com", String::from(uri));
+    }
+
     #[test]
     fn push_path_segment() {
         let mut uri = iuri_ref!("").to_uri_ref_buf();
@@ -52,7 +52,6 @@ use super::*;
 /// assert_eq!(path_seg_iter.next(), Some("blåbær"));
 /// assert_eq!(path_seg_iter.next(), None);
 /// ```
-#[doc(unstable)]
 #[derive(Default, Clone, Debug)]
 pub struct UriUnescapeBuf {
     buf: RelRefBuf,
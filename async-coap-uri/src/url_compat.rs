@@ -0,0 +1,150 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Conversions between the types in this crate and [`url::Url`], enabled by the
+//! `url` feature.
+//!
+//! A [`url::Url`] is always an absolute URL, so it can be converted into a
+//! [`UriBuf`] or [`UriRefBuf`]. The conversion can still fail, since [`url::Url`] will
+//! happily pass through sequences like `%00` and `%zz` which this crate rejects.
+//!
+//! Going the other way, only URI-references that have a scheme can be converted into a
+//! [`url::Url`]. The conversion may also normalize the URI, as described in the
+//! [WHATWG URL Standard](https://url.spec.whatwg.org/).
+//!
+//! ```
+//! # use async_coap_uri::*;
+//! use std::convert::TryFrom;
+//!
+//! let url = url::Url::parse("coap://example.com/a/b?c").unwrap();
+//! let uri = UriBuf::try_from(&url).unwrap();
+//! assert_eq!(uri, uri!("coap://example.com/a/b?c"));
+//!
+//! let url = url::Url::try_from(uri.as_uri()).unwrap();
+//! assert_eq!(url.path(), "/a/b");
+//!
+//! assert!(url::Url::try_from(uri_ref!("a/b")).is_err());
+//! ```
+
+use super::*;
+use std::convert::TryFrom;
+
+impl TryFrom<&::url::Url> for UriRefBuf {
+    type Error = ParseError;
+
+    fn try_from(url: &::url::Url) -> Result<Self, Self::Error> {
+        UriRefBuf::from_str(url.as_str())
+    }
+}
+
+impl TryFrom<::url::Url> for UriRefBuf {
+    type Error = ParseError;
+
+    fn try_from(url: ::url::Url) -> Result<Self, Self::Error> {
+        UriRefBuf::from_string(url.into())
+    }
+}
+
+impl TryFrom<&::url::Url> for UriBuf {
+    type Error = ParseError;
+
+    fn try_from(url: &::url::Url) -> Result<Self, Self::Error> {
+        UriBuf::from_str(url.as_str())
+    }
+}
+
+impl TryFrom<::url::Url> for UriBuf {
+    type Error = ParseError;
+
+    fn try_from(url: ::url::Url) -> Result<Self, Self::Error> {
+        UriBuf::from_string(url.into())
+    }
+}
+
+impl TryFrom<&UriRef> for ::url::Url {
+    type Error = ::url::ParseError;
+
+    fn try_from(uri_ref: &UriRef) -> Result<Self, Self::Error> {
+        ::url::Url::parse(uri_ref.as_str())
+    }
+}
+
+impl TryFrom<&Uri> for ::url::Url {
+    type Error = ::url::ParseError;
+
+    fn try_from(uri: &Uri) -> Result<Self, Self::Error> {
+        ::url::Url::parse(uri.as_str())
+    }
+}
+
+impl TryFrom<&UriRefBuf> for ::url::Url {
+    type Error = ::url::ParseError;
+
+    fn try_from(uri_ref: &UriRefBuf) -> Result<Self, Self::Error> {
+        ::url::Url::parse(uri_ref.as_str())
+    }
+}
+
+impl TryFrom<&UriBuf> for ::url::Url {
+    type Error = ::url::ParseError;
+
+    fn try_from(uri: &UriBuf) -> Result<Self, Self::Error> {
+        ::url::Url::parse(uri.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::url::Url;
+
+    #[test]
+    fn from_url() {
+        let url = Url::parse("http://example.com/a b/c?d|e#f").unwrap();
+        assert_eq!(
+            UriBuf::try_from(&url).unwrap(),
+            uri!("http://example.com/a%20b/c?d|e#f")
+        );
+        assert_eq!(
+            UriRefBuf::try_from(url).unwrap(),
+            uri_ref!("http://example.com/a%20b/c?d|e#f")
+        );
+
+        let url = Url::parse("http://example.com/%00").unwrap();
+        assert!(UriBuf::try_from(&url).is_err());
+    }
+
+    #[test]
+    fn to_url() {
+        let url = Url::try_from(uri!("coap://[::1]:1234/a/b")).unwrap();
+        assert_eq!(
+            Some(::url::Host::Ipv6(std::net::Ipv6Addr::LOCALHOST)),
+            url.host()
+        );
+        assert_eq!(Some(1234), url.port());
+
+        let uri_buf = UriBuf::from_str("coap://example.com/").unwrap();
+        assert_eq!(
+            "coap://example.com/",
+            Url::try_from(&uri_buf).unwrap().as_str()
+        );
+
+        assert_eq!(
+            Err(::url::ParseError::RelativeUrlWithoutBase),
+            Url::try_from(uri_ref!("/a/b"))
+        );
+        assert!(Url::try_from(uri!("//example.com/")).is_err());
+    }
+}
@@ -14,3 +14,49 @@ fn test_rel_ref() {
 fn test_uri_ref() {
     let _ = uri_ref!("a/b/c?q=foobar#frag");
 }
+
+#[test]
+fn test_const() {
+    const URI: &async_coap_uri::Uri = uri!("coap://example.com/a/b");
+    assert_eq!(URI.raw_path(), "/a/b");
+}
+
+#[test]
+fn test_uri_format_checked() {
+    use async_coap_uri::{rel_ref_format_checked, uri_format_checked, uri_ref_format_checked};
+
+    let port = 5683;
+    let id = "a b";
+    let uri =
+        uri_format_checked!("coap://{host}:{port}/{id}/{:02x}#{}", 10, "x y", host = "h").unwrap();
+    assert_eq!(uri, uri!("coap://h:5683/a%20b/0a#x%20y"));
+
+    let uri_ref = uri_ref_format_checked!("//{}/{1}/{1}", "host", "a/b").unwrap();
+    assert_eq!(uri_ref, uri_ref!("//host/a%2Fb/a%2Fb"));
+
+    let rel_ref = rel_ref_format_checked!("{}?{}={}", "a:b", "k=", "v&").unwrap();
+    assert_eq!(rel_ref, rel_ref!("a%3Ab?k%3D=v%26"));
+}
+
+#[test]
+fn test_uri_format() {
+    use async_coap_uri::{rel_ref_format, uri_format, uri_ref_format};
+
+    let uri = uri_format!("coap://{}:{}/{}/{:02x}#{}", "h", 5683, "a b", 10, "x y").unwrap();
+    assert_eq!(uri, uri!("coap://h:5683/a%20b/0a#x%20y"));
+
+    let uri = uri_format!("coap://{}/", "[::1]:5683").unwrap();
+    assert_eq!(uri, uri!("coap://[::1]:5683/"));
+
+    let uri_ref = uri_ref_format!("//{}/{1}/{1}", "host", "a/b").unwrap();
+    assert_eq!(uri_ref, uri_ref!("//host/a%2Fb/a%2Fb"));
+
+    let rel_ref = rel_ref_format!("{}?{}={v}", "a:b", "k=", v = "v&").unwrap();
+    assert_eq!(rel_ref, rel_ref!("a%3Ab?k%3D=v%26"));
+
+    let rel_ref = rel_ref_format!("{:?}/{}", "a:b", "c:d").unwrap();
+    assert_eq!(rel_ref, rel_ref!("%22a%3Ab%22/c:d"));
+
+    assert!(uri_format!("{}", "a b").is_err());
+    assert!(uri_format!("coap://h/{}", "x").is_ok());
+}
@@ -107,6 +107,42 @@
 //! assert!(Arc::get_mut(&mut arc).is_some());
 //! ```
 //!
+//! ## Weak References and Lifecycle Callbacks
+//!
+//! A guard can also be constructed from a `Weak` reference using [`WeakGuardExt::guard_weak`].
+//! The resulting [`WeakGuard`] only holds a `Weak` reference to the head, so the contained
+//! future or stream can't borrow from it. Once the head has been dropped, the guarded
+//! future or stream terminates (yielding `None`). Heads contain a [`DropSignal`], which
+//! wakes the guard when the head is dropped so that it doesn't have to wait until it is
+//! woken up by something else.
+//!
+//! A callback can be registered with [`ArcGuard::on_drop`] (or [`WeakGuard::on_drop`]),
+//! which is called after the contained object has been dropped. This is useful for
+//! sequencing the shutdown of long-running futures like receive loops.
+//!
+//! ```
+//! # use async_coap::arc_guard; // Remove if spun off into own crate
+//! # use std::sync::Arc;
+//! # use std::sync::atomic::{AtomicBool, Ordering};
+//! # use futures::prelude::*;
+//! # use futures::executor::block_on;
+//! use arc_guard::{DropSignal, WeakGuardExt};
+//!
+//! let arc = Arc::new(DropSignal::new());
+//! let dropped = Arc::new(AtomicBool::new(false));
+//! let dropped_clone = dropped.clone();
+//!
+//! let guarded = Arc::downgrade(&arc)
+//!     .guard_weak(|_| future::pending::<()>())
+//!     .expect("Head was dropped")
+//!     .on_drop(move || dropped_clone.store(true, Ordering::SeqCst));
+//!
+//! core::mem::drop(arc);
+//!
+//! assert_eq!(block_on(guarded), None);
+//! assert!(dropped.load(Ordering::SeqCst));
+//! ```
+//!
 //! [^1]: I would have loved to call this crate `lifeguard`, because it is a "guard" on the
 //!       lifetime of the contained "head" instance, but sadly that name was
 //!       [already taken](https://crates.io/crates/lifeguard).
@@ -116,23 +152,33 @@
 #![warn(clippy::all)]
 
 use futures::prelude::*;
-use pin_utils::unsafe_pinned;
+use futures::task::{Context, Poll, Waker};
 use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::mem::ManuallyDrop;
 use std::ops::Deref;
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::atomic::{self, AtomicUsize};
+use std::sync::{Arc, Mutex, Weak};
+
+type OnDropFn<RC> = dyn FnOnce(&RC) + Send;
 
 /// A container for a single object with lifetime that is bound to that of an `Arc`.
 ///
 /// See [Module Documentation](index.html) for more information.
-#[derive(Debug, Clone)]
 pub struct ArcGuard<RC, T> {
-    inner: T,
+    inner: ManuallyDrop<T>,
     head: Arc<RC>,
+    on_drop: Option<Box<OnDropFn<RC>>>,
 }
 
 impl<RC, T> ArcGuard<RC, T> {
-    unsafe_pinned!(inner: T);
+    fn inner(self: Pin<&mut Self>) -> Pin<&mut T> {
+        // SAFETY: `inner` is structurally pinned: it is never moved out of `self`,
+        // the `Drop` implementation drops it in place, and `ArcGuard` is only `Unpin`
+        // if `T` is.
+        unsafe { self.map_unchecked_mut(|x| &mut *x.inner) }
+    }
 
     /// Constructs a new `ArcGuard<>` instance using the given `Arc<>` and getter closure.
     /// The use of a closure for the getter allows for a more convenient syntax while ensuring
@@ -149,15 +195,74 @@ impl<RC, T> ArcGuard<RC, T> {
         // and, by holding a reference to `head`, this class ensures that it does not live longer
         // than the contained reference.
         ArcGuard {
-            inner: getter(unsafe { std::mem::transmute::<&RC, &RC>(&head) }),
+            inner: ManuallyDrop::new(getter(unsafe { std::mem::transmute::<&RC, &RC>(&head) })),
             head,
+            on_drop: None,
         }
     }
 
+    /// Constructs a new `ArcGuard<>` instance from a `Weak<>` reference, or returns `None`
+    /// if the head has already been dropped.
+    ///
+    /// The returned guard holds a strong reference to the head like any other. Use
+    /// [`WeakGuard::new`] instead for a guard which terminates once the head is dropped.
+    pub fn from_weak<'head, F>(head: &Weak<RC>, getter: F) -> Option<ArcGuard<RC, T>>
+    where
+        F: FnOnce(&'head RC) -> T,
+        RC: 'head,
+        T: 'head,
+    {
+        head.upgrade().map(|head| ArcGuard::new(head, getter))
+    }
+
     /// Borrows a reference to the `Arc` that is being held to preserve the underlying value.
     pub fn head(&self) -> &Arc<RC> {
         &self.head
     }
+
+    /// Registers a callback that is called with the head when this guard is dropped, after
+    /// the contained object has been dropped. Replaces any previously registered callback.
+    ///
+    /// The callback is not carried over to clones of this guard.
+    pub fn on_drop<F>(mut self, callback: F) -> Self
+    where
+        F: FnOnce(&RC) + Send + 'static,
+    {
+        self.on_drop = Some(Box::new(callback));
+        self
+    }
+}
+
+impl<RC, T> Drop for ArcGuard<RC, T> {
+    fn drop(&mut self) {
+        // SAFETY: `inner` is dropped exactly once, here, and is never used afterward.
+        // It must be dropped before `head` since it may borrow from it.
+        unsafe { ManuallyDrop::drop(&mut self.inner) };
+
+        if let Some(on_drop) = self.on_drop.take() {
+            on_drop(&self.head);
+        }
+    }
+}
+
+impl<RC, T: Clone> Clone for ArcGuard<RC, T> {
+    fn clone(&self) -> Self {
+        ArcGuard {
+            inner: self.inner.clone(),
+            head: self.head.clone(),
+            on_drop: None,
+        }
+    }
+}
+
+impl<RC: std::fmt::Debug, T: std::fmt::Debug> std::fmt::Debug for ArcGuard<RC, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        f.debug_struct("ArcGuard")
+            .field("inner", &*self.inner)
+            .field("head", &self.head)
+            .field("has_on_drop", &self.on_drop.is_some())
+            .finish()
+    }
 }
 
 unsafe impl<RC: Send, T: Send> Send for ArcGuard<RC, T> {}
@@ -204,10 +309,7 @@ impl<RC, T: PartialOrd<R>, R> PartialOrd<R> for ArcGuard<RC, T> {
 impl<RC, T: Future> Future for ArcGuard<RC, T> {
     type Output = T::Output;
 
-    fn poll(
-        mut self: Pin<&mut Self>,
-        cx: &mut futures::task::Context<'_>,
-    ) -> futures::task::Poll<Self::Output> {
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         self.as_mut().inner().poll(cx)
     }
 }
@@ -215,10 +317,7 @@ impl<RC, T: Future> Future for ArcGuard<RC, T> {
 impl<RC, T: Stream> Stream for ArcGuard<RC, T> {
     type Item = T::Item;
 
-    fn poll_next(
-        mut self: Pin<&mut Self>,
-        cx: &mut futures::task::Context<'_>,
-    ) -> futures::task::Poll<Option<Self::Item>> {
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         self.as_mut().inner().poll_next(cx)
     }
 }
@@ -247,3 +346,283 @@ impl<RC> ArcGuardExt<RC> for Arc<RC> {
         ArcGuard::new(self.clone(), getter)
     }
 }
+
+/// Wakes the [`WeakGuard`]s of a head when the head is dropped.
+///
+/// Types which are used as the head of a [`WeakGuard`] contain one of these, and expose
+/// it by implementing `AsRef<DropSignal>`.
+///
+/// See [Module Documentation](index.html) for more information.
+#[derive(Debug, Default)]
+pub struct DropSignal {
+    wakers: Mutex<HashMap<usize, Waker>>,
+}
+
+impl DropSignal {
+    /// Constructs a new `DropSignal` instance.
+    pub fn new() -> DropSignal {
+        DropSignal::default()
+    }
+
+    fn register(&self, id: usize, waker: &Waker) {
+        let mut wakers = self.wakers.lock().expect("Lock failed");
+
+        match wakers.get(&id) {
+            Some(registered) if registered.will_wake(waker) => (),
+            _ => {
+                wakers.insert(id, waker.clone());
+            }
+        }
+    }
+
+    fn unregister(&self, id: usize) {
+        self.wakers.lock().expect("Lock failed").remove(&id);
+    }
+}
+
+impl AsRef<DropSignal> for DropSignal {
+    fn as_ref(&self) -> &DropSignal {
+        self
+    }
+}
+
+impl Drop for DropSignal {
+    fn drop(&mut self) {
+        let wakers = std::mem::take(self.wakers.get_mut().expect("Lock failed"));
+
+        for (_, waker) in wakers {
+            waker.wake();
+        }
+    }
+}
+
+type WeakOnDropFn = dyn FnOnce() + Send;
+
+/// A guard constructed from a `Weak<>` reference which terminates once the head has been
+/// dropped.
+///
+/// Unlike [`ArcGuard<>`](ArcGuard), a `WeakGuard<>` doesn't keep the head alive, so the contained
+/// object can't borrow from it. Whenever the guard is polled, it registers its waker with
+/// the [`DropSignal`] of the head. Once the head has been dropped, the guard is woken up,
+/// the contained object is dropped and the guard resolves to `None` (or, for streams,
+/// ends).
+///
+/// See [Module Documentation](index.html) for more information.
+pub struct WeakGuard<RC: AsRef<DropSignal>, T> {
+    inner: Option<T>,
+    head: Weak<RC>,
+    id: usize,
+    on_drop: Option<Box<WeakOnDropFn>>,
+}
+
+impl<RC: AsRef<DropSignal>, T> WeakGuard<RC, T> {
+    /// Constructs a new `WeakGuard<>` instance using the given `Weak<>` reference and getter
+    /// closure, or returns `None` if the head has already been dropped.
+    pub fn new<F>(head: &Weak<RC>, getter: F) -> Option<WeakGuard<RC, T>>
+    where
+        F: FnOnce(&RC) -> T,
+    {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+        let inner = getter(&*head.upgrade()?);
+
+        Some(WeakGuard {
+            inner: Some(inner),
+            head: head.clone(),
+            id: NEXT_ID.fetch_add(1, atomic::Ordering::Relaxed),
+            on_drop: None,
+        })
+    }
+
+    /// Returns true if the head has been dropped, or if this guard has already terminated.
+    pub fn is_orphaned(&self) -> bool {
+        self.inner.is_none() || self.head.strong_count() == 0
+    }
+
+    /// Registers a callback that is called when the contained object is dropped, either
+    /// because the head was dropped or because this guard was itself dropped. Replaces any
+    /// previously registered callback.
+    pub fn on_drop<F>(mut self, callback: F) -> Self
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.on_drop = Some(Box::new(callback));
+        self
+    }
+
+    /// Drops the contained object in place, if it hasn't been dropped yet.
+    fn terminate(&mut self) {
+        if self.inner.is_some() {
+            self.inner = None;
+
+            if let Some(on_drop) = self.on_drop.take() {
+                on_drop();
+            }
+        }
+    }
+
+    /// Returns the pinned contained object, dropping it first if the head has been dropped.
+    /// Otherwise, the waker of `cx` is woken up once the head is dropped.
+    fn live_inner(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Option<Pin<&mut T>> {
+        // SAFETY: `inner` is structurally pinned: it is never moved out of `self`, it is
+        // only ever dropped in place, and `WeakGuard` is only `Unpin` if `T` is.
+        let this = unsafe { self.get_unchecked_mut() };
+
+        match this.head.upgrade() {
+            Some(head) if this.inner.is_some() => (*head).as_ref().register(this.id, cx.waker()),
+            _ => this.terminate(),
+        }
+
+        this.inner
+            .as_mut()
+            .map(|inner| unsafe { Pin::new_unchecked(inner) })
+    }
+}
+
+impl<RC: AsRef<DropSignal>, T> Drop for WeakGuard<RC, T> {
+    fn drop(&mut self) {
+        if let Some(head) = self.head.upgrade() {
+            (*head).as_ref().unregister(self.id);
+        }
+
+        self.terminate();
+    }
+}
+
+impl<RC: AsRef<DropSignal>, T: std::fmt::Debug> std::fmt::Debug for WeakGuard<RC, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        f.debug_struct("WeakGuard")
+            .field("inner", &self.inner)
+            .field("is_orphaned", &self.is_orphaned())
+            .field("has_on_drop", &self.on_drop.is_some())
+            .finish()
+    }
+}
+
+impl<RC: AsRef<DropSignal>, T: Future> Future for WeakGuard<RC, T> {
+    type Output = Option<T::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.live_inner(cx) {
+            Some(inner) => inner.poll(cx).map(Some),
+            None => Poll::Ready(None),
+        }
+    }
+}
+
+impl<RC: AsRef<DropSignal>, T: Stream> Stream for WeakGuard<RC, T> {
+    type Item = T::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.live_inner(cx) {
+            Some(inner) => inner.poll_next(cx),
+            None => Poll::Ready(None),
+        }
+    }
+}
+
+/// A convenience trait for `Weak<>` that makes it easier to construct `WeakGuard<>` instances.
+///
+/// See [Module Documentation](index.html) for more information.
+pub trait WeakGuardExt<RC: AsRef<DropSignal>> {
+    /// Convenience method for constructing `WeakGuard<>` instances. Returns `None` if the
+    /// head has already been dropped.
+    ///
+    /// See [Module Documentation](index.html) for more information.
+    fn guard_weak<F, T>(&self, getter: F) -> Option<WeakGuard<RC, T>>
+    where
+        F: FnOnce(&RC) -> T;
+}
+
+impl<RC: AsRef<DropSignal>> WeakGuardExt<RC> for Weak<RC> {
+    fn guard_weak<F, T>(&self, getter: F) -> Option<WeakGuard<RC, T>>
+    where
+        F: FnOnce(&RC) -> T,
+    {
+        WeakGuard::new(self, getter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::task::{noop_waker_ref, waker, ArcWake};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[test]
+    fn on_drop() {
+        let arc = Arc::new(AtomicBool::new(false));
+        let called = Arc::new(AtomicBool::new(false));
+        let called_clone = called.clone();
+
+        let guarded = arc
+            .guard(|flag| flag)
+            .on_drop(move |flag| called_clone.store(flag.load(Ordering::SeqCst), Ordering::SeqCst));
+
+        guarded.store(true, Ordering::SeqCst);
+        core::mem::drop(guarded.clone());
+        assert!(!called.load(Ordering::SeqCst));
+
+        core::mem::drop(guarded);
+        assert!(called.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn weak_guard_terminates_when_orphaned() {
+        let arc = Arc::new(DropSignal::new());
+        let called = Arc::new(AtomicBool::new(false));
+        let called_clone = called.clone();
+        let mut cx = Context::from_waker(noop_waker_ref());
+
+        let mut guarded = Arc::downgrade(&arc)
+            .guard_weak(|_| future::pending::<()>())
+            .unwrap()
+            .on_drop(move || called_clone.store(true, Ordering::SeqCst));
+
+        // Other guards don't keep the head alive.
+        let mut other = Arc::downgrade(&arc)
+            .guard_weak(|_| future::pending::<()>())
+            .unwrap();
+
+        assert!(!guarded.is_orphaned());
+        assert_eq!(Poll::Pending, guarded.poll_unpin(&mut cx));
+        assert_eq!(Poll::Pending, other.poll_unpin(&mut cx));
+
+        let weak = Arc::downgrade(&arc);
+        core::mem::drop(arc);
+        assert!(weak.upgrade().is_none());
+        assert!(guarded.is_orphaned());
+
+        assert_eq!(Poll::Ready(None), guarded.poll_unpin(&mut cx));
+        assert_eq!(Poll::Ready(None), other.poll_unpin(&mut cx));
+        assert!(called.load(Ordering::SeqCst));
+        assert!(weak.guard_weak(|_| ()).is_none());
+    }
+
+    #[test]
+    fn weak_guard_woken_when_orphaned() {
+        struct Woken(AtomicBool);
+
+        impl ArcWake for Woken {
+            fn wake_by_ref(arc_self: &Arc<Self>) {
+                arc_self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let arc = Arc::new(DropSignal::new());
+        let woken = Arc::new(Woken(AtomicBool::new(false)));
+        let waker = waker(woken.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let mut guarded = Arc::downgrade(&arc)
+            .guard_weak(|_| future::pending::<()>())
+            .unwrap();
+
+        assert_eq!(Poll::Pending, guarded.poll_unpin(&mut cx));
+        assert!(!woken.0.load(Ordering::SeqCst));
+
+        core::mem::drop(arc);
+        assert!(woken.0.load(Ordering::SeqCst));
+        assert_eq!(Poll::Ready(None), guarded.poll_unpin(&mut cx));
+    }
+}
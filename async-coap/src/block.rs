@@ -160,23 +160,111 @@ impl Debug for BlockInfo {
     }
 }
 
+/// A record of which parts of a block-wise transfer have been received.
+///
+/// Since the block size may change in the middle of a transfer, the received
+/// blocks are tracked as byte ranges rather than as block numbers.
+#[cfg(feature = "block")]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct BlockMap {
+    received: Vec<std::ops::Range<usize>>,
+    end: Option<usize>,
+}
+
+#[cfg(feature = "block")]
+impl BlockMap {
+    /// Records that `block` was received with a payload of `len` bytes.
+    pub fn insert(&mut self, block: BlockInfo, len: usize) {
+        let mut range = block.offset()..block.offset() + len;
+
+        if !block.more_flag() {
+            self.end = Some(range.end);
+        }
+
+        // Merge with any overlapping or adjacent ranges.
+        self.received.retain(|r| {
+            if r.start <= range.end && range.start <= r.end {
+                range = r.start.min(range.start)..r.end.max(range.end);
+                false
+            } else {
+                true
+            }
+        });
+
+        let index = self
+            .received
+            .iter()
+            .position(|r| r.start > range.start)
+            .unwrap_or(self.received.len());
+        self.received.insert(index, range);
+    }
+
+    /// The byte ranges that have been received, in order.
+    pub fn received(&self) -> &[std::ops::Range<usize>] {
+        &self.received
+    }
+
+    /// Returns true if every byte of `block` has been received.
+    pub fn contains(&self, block: BlockInfo) -> bool {
+        let start = block.offset();
+        let end = match self.end {
+            Some(end) if end > start => end.min(start + block.len()),
+            _ => start + block.len(),
+        };
+        self.received
+            .iter()
+            .any(|r| r.start <= start && end <= r.end)
+    }
+
+    /// The length of the content, if the final block has been received.
+    pub fn end(&self) -> Option<usize> {
+        self.end
+    }
+
+    /// The byte ranges that are missing before the last received byte.
+    pub fn gaps(&self) -> Vec<std::ops::Range<usize>> {
+        let mut gaps = Vec::new();
+        let mut offset = 0;
+        for r in self.received.iter() {
+            if r.start > offset {
+                gaps.push(offset..r.start);
+            }
+            offset = r.end;
+        }
+        gaps
+    }
+
+    /// Returns true if the final block and everything before it has been received.
+    pub fn is_complete(&self) -> bool {
+        match self.end {
+            Some(end) => self.received.first() == Some(&(0..end)),
+            None => false,
+        }
+    }
+}
+
 /// Tool for reconstructing block-wise messages.
 ///
 /// This mechanism was designed so that it can be used successfully with `block1` (request payload)
 /// and `block2` (response payload) messages.
+#[cfg(feature = "block")]
 #[derive(Debug)]
 pub struct BlockReconstructor<F> {
     next_block: BlockInfo,
     is_finished: bool,
+    is_strict: bool,
+    block_map: BlockMap,
     write: F,
 }
 
+#[cfg(feature = "block")]
 impl<F: Default + std::io::Write> Default for BlockReconstructor<F> {
     fn default() -> Self {
         BlockReconstructor::new(F::default(), Default::default())
     }
 }
 
+#[cfg(feature = "block")]
 impl<F> BlockReconstructor<F>
 where
     F: std::io::Write,
@@ -186,10 +274,27 @@ where
         BlockReconstructor {
             next_block: next_block.without_more_flag(),
             is_finished: false,
+            is_strict: false,
+            block_map: BlockMap::default(),
             write,
         }
     }
 
+    /// Determines if blocks that precede the next block should be rejected.
+    ///
+    /// By default, such blocks are assumed to be duplicates and are ignored. If strict
+    /// sequencing is enabled, [`BlockReconstructor::feed`] fails instead. Blocks that
+    /// come after the next block are always rejected.
+    pub fn set_strict_sequencing(&mut self, is_strict: bool) {
+        self.is_strict = is_strict;
+    }
+
+    /// The parts of the transfer that have been passed to [`BlockReconstructor::feed`],
+    /// including blocks that were rejected or ignored.
+    pub fn block_map(&self) -> &BlockMap {
+        &self.block_map
+    }
+
     /// The next block this object wants.
     pub fn next_block(&self) -> BlockInfo {
         self.next_block
@@ -212,7 +317,13 @@ where
             return Ok(true);
         }
 
+        self.block_map.insert(block, payload.len());
+
         if block.offset() < self.next_block.offset() {
+            if self.is_strict {
+                return Err(());
+            }
+
             // Ignore blocks we have already seen.
             return Ok(false);
         }
@@ -387,4 +498,53 @@ mod tests {
         assert_eq!(None, block.next());
         assert_eq!(None, block.valid());
     }
+
+    #[test]
+    fn block_map() {
+        let mut map = BlockMap::default();
+        map.insert(BlockInfo::new(0, true, 0).unwrap(), 16);
+        map.insert(BlockInfo::new(2, true, 0).unwrap(), 16);
+        assert_eq!(&[0..16, 32..48][..], map.received());
+        assert_eq!(vec![16..32], map.gaps());
+        assert_eq!(None, map.end());
+        assert!(!map.is_complete());
+        assert!(map.contains(BlockInfo::new(2, false, 0).unwrap()));
+        assert!(!map.contains(BlockInfo::new(1, false, 0).unwrap()));
+        assert!(!map.contains(BlockInfo::new(0, false, 1).unwrap()));
+
+        map.insert(BlockInfo::new(3, false, 0).unwrap(), 5);
+        assert_eq!(Some(53), map.end());
+        assert!(map.contains(BlockInfo::new(3, false, 0).unwrap()));
+        assert!(!map.is_complete());
+
+        map.insert(BlockInfo::new(1, true, 0).unwrap(), 16);
+        assert_eq!(1, map.received().len());
+        assert_eq!(0..53, map.received()[0]);
+        assert!(map.gaps().is_empty());
+        assert!(map.is_complete());
+        assert!(map.contains(BlockInfo::new(0, false, 1).unwrap()));
+    }
+
+    #[test]
+    fn reconstructor_sequencing() {
+        let block = |num, more| BlockInfo::new(num, more, 0).unwrap();
+
+        let mut recons = BlockReconstructor::new(Vec::new(), block(0, false));
+        assert_eq!(Ok(false), recons.feed(block(0, true), &[0; 16]));
+        assert_eq!(Ok(false), recons.feed(block(0, true), &[0; 16]));
+        assert_eq!(Err(()), recons.feed(block(2, false), &[2; 4]));
+        assert_eq!(vec![16..32], recons.block_map().gaps());
+
+        let mut recons = BlockReconstructor::new(Vec::new(), block(0, false));
+        recons.set_strict_sequencing(true);
+        assert_eq!(Ok(false), recons.feed(block(0, true), &[0; 16]));
+        assert_eq!(Err(()), recons.feed(block(0, true), &[0; 16]));
+
+        let mut recons = BlockReconstructor::new(Vec::new(), block(0, false));
+        recons.set_strict_sequencing(true);
+        assert_eq!(Ok(false), recons.feed(block(0, true), &[0; 16]));
+        assert_eq!(Ok(true), recons.feed(block(1, false), &[1; 4]));
+        assert!(recons.block_map().is_complete());
+        assert_eq!([&[0; 16][..], &[1; 4][..]].concat(), recons.into_inner());
+    }
 }
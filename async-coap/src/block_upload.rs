@@ -0,0 +1,523 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+use crate::option::OptionInsertExt;
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use futures::io::AsyncRead;
+use futures::task::{Context, Poll};
+use futures::StreamExt;
+use std::pin::Pin;
+
+enum Chunk {
+    Data(Vec<u8>),
+    End,
+    Abort(std::io::ErrorKind),
+}
+
+impl std::fmt::Debug for Chunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Chunk::Data(data) => write!(f, "Data({} bytes)", data.len()),
+            Chunk::End => f.write_str("End"),
+            Chunk::Abort(kind) => write!(f, "Abort({:?})", kind),
+        }
+    }
+}
+
+/// The result of passing an inbound request to [`Block1Upload::handle`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Block1Status {
+    /// The block was accepted and a `2.31 Continue` response has already been sent.
+    /// More blocks are expected.
+    Continue,
+
+    /// The final block was accepted and the body is complete. The handler is responsible
+    /// for sending the final response, which should include the contained `Block1` option
+    /// (if any) as described in [IETF-RFC7959 Section 2.3].
+    ///
+    /// [IETF-RFC7959 Section 2.3]: https://tools.ietf.org/html/rfc7959#section-2.3
+    Complete(Option<BlockInfo>),
+
+    /// The block was rejected and an error response with the given code has already been
+    /// sent. Reading from the [`Block1Body`] will fail.
+    Rejected(MsgCode),
+}
+
+/// Server-side receiver for request bodies uploaded using [Block1 transfers][IETF-RFC7959].
+///
+/// Each inbound request belonging to the upload is passed to [`Block1Upload::handle`],
+/// which acknowledges intermediate blocks with `2.31 Continue` and forwards their payloads
+/// to the associated [`Block1Body`]. The body implements [`AsyncRead`], allowing it to be
+/// consumed while the upload is still in progress instead of buffering the entire body
+/// before the handler can act on it.
+///
+/// Uploads larger than the configured maximum body size are rejected with
+/// `4.13 Request Entity Too Large` (including a `Size1` option indicating the maximum),
+/// and blocks which arrive out of order are rejected with
+/// `4.08 Request Entity Incomplete`. A retransmission of the last block received (because
+/// the `2.31 Continue` response to it was lost) is acknowledged again, without being
+/// passed along a second time.
+///
+/// Requests without a `Block1` option are treated as a single, final block.
+///
+/// ```
+/// # use async_coap::prelude::*;
+/// # use async_coap::message::MessageWrite;
+/// # use async_coap::{Block1Status, Block1Upload, Error, RespondableInboundContext};
+/// fn handle_put<T: RespondableInboundContext>(
+///     upload: &mut Block1Upload,
+///     context: &T,
+/// ) -> Result<(), Error> {
+///     match upload.handle(context)? {
+///         Block1Status::Complete(block1) => context.respond(|msg_out| {
+///             msg_out.set_msg_code(MsgCode::SuccessChanged);
+///             if let Some(block1) = block1 {
+///                 msg_out.insert_option(option::BLOCK1, block1)?;
+///             }
+///             Ok(())
+///         }),
+///
+///         // We have either already responded with a `2.31 Continue`
+///         // or an error response.
+///         _ => Ok(()),
+///     }
+/// }
+/// ```
+///
+/// [IETF-RFC7959]: https://tools.ietf.org/html/rfc7959
+#[derive(Debug)]
+pub struct Block1Upload {
+    max_body_size: usize,
+    received: usize,
+    is_finished: bool,
+    sender: UnboundedSender<Chunk>,
+}
+
+impl Block1Upload {
+    /// The maximum body size used by [`Block1Upload::default_pair`].
+    pub const DEFAULT_MAX_BODY_SIZE: usize = 64 * 1024;
+
+    /// Creates a new upload receiver which accepts bodies of up to `max_body_size` bytes,
+    /// along with the [`Block1Body`] that the body can be read from.
+    pub fn new(max_body_size: usize) -> (Block1Upload, Block1Body) {
+        let (sender, receiver) = unbounded();
+
+        let upload = Block1Upload {
+            max_body_size,
+            received: 0,
+            is_finished: false,
+            sender,
+        };
+
+        let body = Block1Body {
+            receiver,
+            current: Vec::new(),
+            pos: 0,
+            is_finished: false,
+        };
+
+        (upload, body)
+    }
+
+    /// Creates a new upload receiver which accepts bodies of up to
+    /// [`Block1Upload::DEFAULT_MAX_BODY_SIZE`] bytes.
+    pub fn default_pair() -> (Block1Upload, Block1Body) {
+        Self::new(Self::DEFAULT_MAX_BODY_SIZE)
+    }
+
+    /// The maximum body size, in bytes.
+    pub fn max_body_size(&self) -> usize {
+        self.max_body_size
+    }
+
+    /// The number of bytes that have been received so far.
+    pub fn received(&self) -> usize {
+        self.received
+    }
+
+    /// Returns true if the upload has either completed or been rejected.
+    pub fn is_finished(&self) -> bool {
+        self.is_finished
+    }
+
+    /// Processes an inbound request that is a part of this upload.
+    ///
+    /// Intermediate blocks and rejected blocks are responded to by this method; once the
+    /// final block has been received, it is up to the caller to respond. Returns
+    /// [`Error::InvalidArgument`] if the upload has already finished.
+    pub fn handle<T: RespondableInboundContext>(
+        &mut self,
+        context: &T,
+    ) -> Result<Block1Status, Error> {
+        if self.is_finished {
+            return Err(Error::InvalidArgument);
+        }
+
+        let msg = context.message();
+        let payload = msg.payload();
+        let block1 = msg.block1();
+        let (offset, more) = block1
+            .map(|block| (block.offset(), block.more_flag()))
+            .unwrap_or((0, false));
+
+        if more && offset < self.received && offset + payload.len() == self.received {
+            Self::acknowledge(context, block1.unwrap())?;
+            return Ok(Block1Status::Continue);
+        }
+
+        if offset != self.received {
+            return self.reject(context, MsgCode::ClientErrorRequestEntityIncomplete);
+        }
+
+        if more && block1.map(|block| block.len()) != Some(payload.len()) {
+            return self.reject(context, MsgCode::ClientErrorBadRequest);
+        }
+
+        let size1 = msg
+            .options()
+            .find_next_of(option::SIZE1)
+            .transpose()?
+            .map(|size| size as usize);
+
+        if size1.unwrap_or(0) > self.max_body_size
+            || self.received + payload.len() > self.max_body_size
+        {
+            return self.reject(context, MsgCode::ClientErrorRequestEntityTooLarge);
+        }
+
+        self.received += payload.len();
+
+        // If nobody is reading the body anymore we still go through the motions,
+        // so that the client gets a sensible response.
+        let _ = self.sender.unbounded_send(Chunk::Data(payload.to_vec()));
+
+        if more {
+            Self::acknowledge(context, block1.unwrap())?;
+            Ok(Block1Status::Continue)
+        } else {
+            let _ = self.sender.unbounded_send(Chunk::End);
+            self.is_finished = true;
+            Ok(Block1Status::Complete(block1))
+        }
+    }
+
+    /// Responds to an intermediate block with `2.31 Continue`.
+    fn acknowledge<T: RespondableInboundContext>(
+        context: &T,
+        block1: BlockInfo,
+    ) -> Result<(), Error> {
+        context.respond(|msg_out| {
+            msg_out.set_msg_code(MsgCode::SuccessContinue);
+            msg_out.insert_option(option::BLOCK1, block1)
+        })
+    }
+
+    fn reject<T: RespondableInboundContext>(
+        &mut self,
+        context: &T,
+        code: MsgCode,
+    ) -> Result<Block1Status, Error> {
+        let kind = match code {
+            MsgCode::ClientErrorRequestEntityTooLarge => std::io::ErrorKind::InvalidData,
+            _ => std::io::ErrorKind::UnexpectedEof,
+        };
+
+        let _ = self.sender.unbounded_send(Chunk::Abort(kind));
+        self.is_finished = true;
+
+        let max_body_size = self.max_body_size as u32;
+        context.respond(|msg_out| {
+            msg_out.set_msg_code(code);
+            if code == MsgCode::ClientErrorRequestEntityTooLarge {
+                msg_out.insert_option(option::SIZE1, max_body_size)?;
+            }
+            Ok(())
+        })?;
+
+        Ok(Block1Status::Rejected(code))
+    }
+}
+
+/// The body of a request being received by a [`Block1Upload`], which can be read
+/// incrementally as blocks arrive.
+///
+/// Reading fails with [`std::io::ErrorKind::InvalidData`] if the upload was rejected
+/// for being too large, or with [`std::io::ErrorKind::UnexpectedEof`] if the upload was
+/// otherwise aborted before it was complete (including when the [`Block1Upload`] is
+/// dropped).
+#[derive(Debug)]
+pub struct Block1Body {
+    receiver: UnboundedReceiver<Chunk>,
+    current: Vec<u8>,
+    pos: usize,
+    is_finished: bool,
+}
+
+impl AsyncRead for Block1Body {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let this = self.get_mut();
+
+        loop {
+            if this.pos < this.current.len() {
+                let len = buf.len().min(this.current.len() - this.pos);
+                buf[..len].copy_from_slice(&this.current[this.pos..this.pos + len]);
+                this.pos += len;
+                return Poll::Ready(Ok(len));
+            }
+
+            if this.is_finished {
+                return Poll::Ready(Ok(0));
+            }
+
+            match this.receiver.poll_next_unpin(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Some(Chunk::Data(data))) => {
+                    this.current = data;
+                    this.pos = 0;
+                }
+                Poll::Ready(Some(Chunk::End)) => this.is_finished = true,
+                Poll::Ready(Some(Chunk::Abort(kind))) => {
+                    return Poll::Ready(Err(std::io::Error::new(kind, "Block1 upload aborted")));
+                }
+                Poll::Ready(None) => {
+                    return Poll::Ready(Err(std::io::ErrorKind::UnexpectedEof.into()));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::{MessageRead, MessageWrite, OwnedImmutableMessage, VecMessageEncoder};
+    use futures::executor::block_on;
+    use futures::io::AsyncReadExt;
+    use std::cell::RefCell;
+
+    struct TestContext {
+        msg: OwnedImmutableMessage,
+        response: RefCell<Option<OwnedImmutableMessage>>,
+    }
+
+    impl TestContext {
+        fn new(block1: Option<BlockInfo>, size1: Option<u32>, payload: &[u8]) -> TestContext {
+            let mut encoder = VecMessageEncoder::new();
+            encoder.set_msg_code(MsgCode::MethodPut);
+            if let Some(block1) = block1 {
+                encoder.insert_option(option::BLOCK1, block1).unwrap();
+            }
+            if let Some(size1) = size1 {
+                encoder.insert_option(option::SIZE1, size1).unwrap();
+            }
+            encoder.append_payload_bytes(payload).unwrap();
+
+            TestContext {
+                msg: encoder.into(),
+                response: RefCell::new(None),
+            }
+        }
+
+        fn response_code(&self) -> Option<MsgCode> {
+            self.response.borrow().as_ref().map(|x| x.msg_code())
+        }
+    }
+
+    impl InboundContext for TestContext {
+        type SocketAddr = std::net::SocketAddr;
+
+        fn remote_socket_addr(&self) -> Self::SocketAddr {
+            "127.0.0.1:5683".parse().unwrap()
+        }
+
+        fn is_dupe(&self) -> bool {
+            false
+        }
+
+        fn message(&self) -> &dyn MessageRead {
+            &self.msg
+        }
+    }
+
+    impl RespondableInboundContext for TestContext {
+        fn is_multicast(&self) -> bool {
+            false
+        }
+
+        fn is_fake(&self) -> bool {
+            false
+        }
+
+        fn respond<F>(&self, msg_gen: F) -> Result<(), Error>
+        where
+            F: Fn(&mut dyn MessageWrite) -> Result<(), Error>,
+        {
+            let mut encoder = VecMessageEncoder::new();
+            msg_gen(&mut encoder)?;
+            self.response.replace(Some(encoder.into()));
+            Ok(())
+        }
+    }
+
+    fn block(num: u32, more: bool) -> Option<BlockInfo> {
+        // 16-byte blocks
+        BlockInfo::new(num, more, 0)
+    }
+
+    #[test]
+    fn upload() {
+        let (mut upload, mut body) = Block1Upload::new(64);
+
+        let context = TestContext::new(block(0, true), None, &[1; 16]);
+        assert_eq!(Ok(Block1Status::Continue), upload.handle(&context));
+        assert_eq!(Some(MsgCode::SuccessContinue), context.response_code());
+
+        // The first block can be read before the rest of the upload arrives.
+        let mut buf = [0u8; 16];
+        block_on(body.read_exact(&mut buf)).unwrap();
+        assert_eq!([1; 16], buf);
+
+        let context = TestContext::new(block(1, true), None, &[2; 16]);
+        assert_eq!(Ok(Block1Status::Continue), upload.handle(&context));
+
+        let context = TestContext::new(block(2, false), None, &[3; 5]);
+        assert_eq!(
+            Ok(Block1Status::Complete(block(2, false))),
+            upload.handle(&context)
+        );
+        assert_eq!(None, context.response_code());
+        assert!(upload.is_finished());
+        assert_eq!(37, upload.received());
+
+        let mut rest = Vec::new();
+        block_on(body.read_to_end(&mut rest)).unwrap();
+        assert_eq!([&[2; 16][..], &[3; 5][..]].concat(), rest);
+
+        assert_eq!(Err(Error::InvalidArgument), upload.handle(&context));
+    }
+
+    #[test]
+    fn single_message() {
+        let (mut upload, mut body) = Block1Upload::default_pair();
+
+        let context = TestContext::new(None, None, b"hello");
+        assert_eq!(Ok(Block1Status::Complete(None)), upload.handle(&context));
+
+        let mut data = Vec::new();
+        block_on(body.read_to_end(&mut data)).unwrap();
+        assert_eq!(b"hello", data.as_slice());
+    }
+
+    #[test]
+    fn too_large() {
+        let (mut upload, mut body) = Block1Upload::new(20);
+
+        let context = TestContext::new(block(0, true), None, &[1; 16]);
+        assert_eq!(Ok(Block1Status::Continue), upload.handle(&context));
+
+        let context = TestContext::new(block(1, true), None, &[2; 16]);
+        assert_eq!(
+            Ok(Block1Status::Rejected(
+                MsgCode::ClientErrorRequestEntityTooLarge
+            )),
+            upload.handle(&context)
+        );
+        let response = context.response.borrow().clone().unwrap();
+        assert_eq!(Some(Ok(20)), response.options().find_next_of(option::SIZE1));
+
+        let mut data = Vec::new();
+        assert_eq!(
+            std::io::ErrorKind::InvalidData,
+            block_on(body.read_to_end(&mut data)).unwrap_err().kind()
+        );
+
+        // A `Size1` option lets us reject the upload up front.
+        let (mut upload, _body) = Block1Upload::new(20);
+        let context = TestContext::new(block(0, true), Some(100), &[1; 16]);
+        assert_eq!(
+            Ok(Block1Status::Rejected(
+                MsgCode::ClientErrorRequestEntityTooLarge
+            )),
+            upload.handle(&context)
+        );
+    }
+
+    #[test]
+    fn out_of_order() {
+        let (mut upload, mut body) = Block1Upload::default_pair();
+
+        let context = TestContext::new(block(1, true), None, &[1; 16]);
+        assert_eq!(
+            Ok(Block1Status::Rejected(
+                MsgCode::ClientErrorRequestEntityIncomplete
+            )),
+            upload.handle(&context)
+        );
+        assert_eq!(
+            Some(MsgCode::ClientErrorRequestEntityIncomplete),
+            context.response_code()
+        );
+
+        let mut data = Vec::new();
+        assert_eq!(
+            std::io::ErrorKind::UnexpectedEof,
+            block_on(body.read_to_end(&mut data)).unwrap_err().kind()
+        );
+    }
+
+    #[test]
+    fn lost_ack() {
+        let (mut upload, mut body) = Block1Upload::default_pair();
+
+        let context = TestContext::new(block(0, true), None, &[1; 16]);
+        assert_eq!(Ok(Block1Status::Continue), upload.handle(&context));
+
+        // The client didn't get our response, so it sends the same block again.
+        let context = TestContext::new(block(0, true), None, &[1; 16]);
+        assert_eq!(Ok(Block1Status::Continue), upload.handle(&context));
+        let response = context.response.borrow().clone().unwrap();
+        assert_eq!(MsgCode::SuccessContinue, response.msg_code());
+        assert_eq!(block(0, true), response.block1());
+        assert_eq!(16, upload.received());
+
+        let context = TestContext::new(block(1, false), None, &[2; 5]);
+        assert_eq!(
+            Ok(Block1Status::Complete(block(1, false))),
+            upload.handle(&context)
+        );
+
+        // The retransmitted block was only passed along once.
+        let mut data = Vec::new();
+        block_on(body.read_to_end(&mut data)).unwrap();
+        assert_eq!([&[1; 16][..], &[2; 5][..]].concat(), data);
+    }
+
+    #[test]
+    fn dropped_upload() {
+        let (upload, mut body) = Block1Upload::default_pair();
+        drop(upload);
+
+        let mut data = Vec::new();
+        assert_eq!(
+            std::io::ErrorKind::UnexpectedEof,
+            block_on(body.read_to_end(&mut data)).unwrap_err().kind()
+        );
+    }
+}
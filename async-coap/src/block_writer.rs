@@ -0,0 +1,360 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+use crate::option::OptionInsertExt;
+use std::cell::Cell;
+
+/// The number of bytes to leave free for the `Block2` option when fitting a block into
+/// the capacity of a response.
+const BLOCK2_OPTION_RESERVE: usize = 4;
+
+/// Server-side writer for response bodies that are served using
+/// [Block2 transfers][IETF-RFC7959].
+///
+/// The body is written using [`core::fmt::Write`] or [`std::io::Write`], without regard
+/// to how large the response message can be. Each inbound request for the resource is
+/// then passed to [`Block2Writer::respond`], which responds with the block that was
+/// asked for. Whatever doesn't fit into that block is carried over to the requests for
+/// the following blocks, and data is discarded once a request for a later block shows
+/// that it has been received.
+///
+/// The block size is the smallest of the size requested by the client, the maximum
+/// block size of the writer, and the largest size that still fits into the response
+/// message (as reported by [`MessageWrite::max_payload_len`]).
+///
+/// The content should be completely written by the time the block containing its end is
+/// served, since the `more` flag of each block is determined by whether any data follows.
+///
+/// ```
+/// # use async_coap::prelude::*;
+/// # use async_coap::{Block2Writer, Error, RespondableInboundContext};
+/// # use std::fmt::Write;
+/// fn handle_get<T: RespondableInboundContext>(
+///     writer: &mut Block2Writer,
+///     context: &T,
+/// ) -> Result<(), Error> {
+///     // Only write the content for the first request.
+///     if writer.is_empty() {
+///         for i in 0..100 {
+///             writeln!(writer, "Line {}", i)?;
+///         }
+///     }
+///
+///     writer.respond(context, |msg_out| {
+///         msg_out.set_msg_code(MsgCode::SuccessContent);
+///         msg_out.insert_option(option::CONTENT_FORMAT, ContentFormat::TEXT_PLAIN_UTF8)
+///     })?;
+///
+///     Ok(())
+/// }
+/// ```
+///
+/// [IETF-RFC7959]: https://tools.ietf.org/html/rfc7959
+#[derive(Debug)]
+pub struct Block2Writer {
+    max_szx: u8,
+    buffer: Vec<u8>,
+    offset: usize,
+    written: usize,
+    is_finished: bool,
+}
+
+impl Default for Block2Writer {
+    fn default() -> Self {
+        Block2Writer::new(BlockInfo::SZX_MAX)
+    }
+}
+
+impl Block2Writer {
+    /// Creates a new writer whose blocks are no larger than the block size with the
+    /// size exponent `max_szx`. Values larger than [`BlockInfo::SZX_MAX`] are clamped.
+    pub fn new(max_szx: u8) -> Block2Writer {
+        Block2Writer {
+            max_szx: max_szx.min(BlockInfo::SZX_MAX),
+            buffer: Vec::new(),
+            offset: 0,
+            written: 0,
+            is_finished: false,
+        }
+    }
+
+    /// The number of bytes that have been written to this writer so far, including data
+    /// that has already been served and discarded.
+    pub fn len(&self) -> usize {
+        self.written
+    }
+
+    /// Returns true if nothing has been written to this writer.
+    pub fn is_empty(&self) -> bool {
+        self.written == 0
+    }
+
+    /// Returns true if the block containing the end of the content has been served.
+    pub fn is_finished(&self) -> bool {
+        self.is_finished
+    }
+
+    /// Appends `data` to the content.
+    pub fn append(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+        self.written += data.len();
+    }
+
+    /// Responds to `context` with the block of the content that it asks for, returning
+    /// the `Block2` option of the response if one was sent.
+    ///
+    /// `msg_gen` is called to set the message code and any options other than `Block2`;
+    /// the `Block2` option and payload are added afterward. Requests for blocks which have
+    /// already been discarded or which are past the end of the content are responded to
+    /// with `4.02 Bad Option`, in which case `None` is returned.
+    pub fn respond<T, F>(&mut self, context: &T, msg_gen: F) -> Result<Option<BlockInfo>, Error>
+    where
+        T: RespondableInboundContext,
+        F: Fn(&mut dyn MessageWrite) -> Result<(), Error>,
+    {
+        let requested = context.message().block2();
+        let offset = requested.map(|block| block.offset()).unwrap_or(0);
+        let szx = requested
+            .map(|block| block.szx().min(self.max_szx))
+            .unwrap_or(self.max_szx);
+
+        if offset < self.offset || offset > self.written || (offset == self.written && offset != 0)
+        {
+            context.respond(|msg_out| {
+                msg_out.set_msg_code(MsgCode::ClientErrorBadOption);
+                Ok(())
+            })?;
+            return Ok(None);
+        }
+
+        // Everything before the requested block has been received by the client.
+        self.buffer.drain(..offset - self.offset);
+        self.offset = offset;
+
+        let sent = Cell::new(None);
+
+        context.respond(|msg_out| {
+            msg_gen(msg_out)?;
+
+            let capacity = msg_out
+                .max_payload_len()
+                .saturating_sub(BLOCK2_OPTION_RESERVE);
+            let mut szx = szx;
+            while szx > 0 && 1 << (szx as usize + 4) > capacity {
+                szx -= 1;
+            }
+
+            let len = self.buffer.len().min(1 << (szx as usize + 4));
+            let more = len < self.buffer.len();
+            let block = BlockInfo::new((offset >> (szx as usize + 4)) as u32, more, szx)
+                .ok_or(Error::OutOfSpace)?;
+
+            msg_out.insert_option(option::BLOCK2, block)?;
+            msg_out.append_payload_bytes(&self.buffer[..len])?;
+            sent.set(Some(block));
+            Ok(())
+        })?;
+
+        let sent = sent.get();
+
+        if let Some(block) = sent {
+            self.is_finished = !block.more_flag();
+        }
+
+        Ok(sent)
+    }
+}
+
+impl core::fmt::Write for Block2Writer {
+    fn write_str(&mut self, s: &str) -> Result<(), core::fmt::Error> {
+        self.append(s.as_bytes());
+        Ok(())
+    }
+}
+
+impl std::io::Write for Block2Writer {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, std::io::Error> {
+        self.append(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), std::io::Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::{MessageRead, OwnedImmutableMessage, VecMessageEncoder};
+    use std::cell::RefCell;
+    use std::fmt::Write;
+
+    struct TestContext {
+        msg: OwnedImmutableMessage,
+        max_len: usize,
+        response: RefCell<Option<OwnedImmutableMessage>>,
+    }
+
+    impl TestContext {
+        fn new(block2: Option<BlockInfo>, max_len: usize) -> TestContext {
+            let mut encoder = VecMessageEncoder::new();
+            encoder.set_msg_code(MsgCode::MethodGet);
+            if let Some(block2) = block2 {
+                encoder.insert_option(option::BLOCK2, block2).unwrap();
+            }
+
+            TestContext {
+                msg: encoder.into(),
+                max_len,
+                response: RefCell::new(None),
+            }
+        }
+
+        fn response(&self) -> OwnedImmutableMessage {
+            self.response.borrow().clone().unwrap()
+        }
+    }
+
+    impl InboundContext for TestContext {
+        type SocketAddr = std::net::SocketAddr;
+
+        fn remote_socket_addr(&self) -> Self::SocketAddr {
+            "127.0.0.1:5683".parse().unwrap()
+        }
+
+        fn is_dupe(&self) -> bool {
+            false
+        }
+
+        fn message(&self) -> &dyn MessageRead {
+            &self.msg
+        }
+    }
+
+    impl RespondableInboundContext for TestContext {
+        fn is_multicast(&self) -> bool {
+            false
+        }
+
+        fn is_fake(&self) -> bool {
+            false
+        }
+
+        fn respond<F>(&self, msg_gen: F) -> Result<(), Error>
+        where
+            F: Fn(&mut dyn MessageWrite) -> Result<(), Error>,
+        {
+            let mut encoder = VecMessageEncoder::new();
+            encoder.set_max_len(self.max_len);
+            msg_gen(&mut encoder)?;
+            self.response.replace(Some(encoder.into()));
+            Ok(())
+        }
+    }
+
+    fn content(msg_out: &mut dyn MessageWrite) -> Result<(), Error> {
+        msg_out.set_msg_code(MsgCode::SuccessContent);
+        Ok(())
+    }
+
+    #[test]
+    fn respond() {
+        let mut writer = Block2Writer::new(0);
+        for i in 0..10 {
+            write!(writer, "{:04}", i).unwrap();
+        }
+        assert_eq!(40, writer.len());
+
+        let context = TestContext::new(None, 1024);
+        assert_eq!(
+            Ok(BlockInfo::new(0, true, 0)),
+            writer.respond(&context, content)
+        );
+        let response = context.response();
+        assert_eq!(MsgCode::SuccessContent, response.msg_code());
+        assert_eq!(BlockInfo::new(0, true, 0), response.block2());
+        assert_eq!(b"0000000100020003", response.payload());
+
+        // Retransmissions of the same block are still served.
+        let context = TestContext::new(BlockInfo::new(0, false, 0), 1024);
+        assert_eq!(
+            Ok(BlockInfo::new(0, true, 0)),
+            writer.respond(&context, content)
+        );
+
+        let context = TestContext::new(BlockInfo::new(1, false, 0), 1024);
+        assert_eq!(
+            Ok(BlockInfo::new(1, true, 0)),
+            writer.respond(&context, content)
+        );
+        assert_eq!(b"0004000500060007", context.response().payload());
+        assert!(!writer.is_finished());
+
+        let context = TestContext::new(BlockInfo::new(2, false, 0), 1024);
+        assert_eq!(
+            Ok(BlockInfo::new(2, false, 0)),
+            writer.respond(&context, content)
+        );
+        assert_eq!(b"00080009", context.response().payload());
+        assert!(writer.is_finished());
+
+        // Earlier blocks have been discarded.
+        let context = TestContext::new(BlockInfo::new(0, false, 0), 1024);
+        assert_eq!(Ok(None), writer.respond(&context, content));
+        assert_eq!(MsgCode::ClientErrorBadOption, context.response().msg_code());
+    }
+
+    #[test]
+    fn respond_fits_capacity() {
+        let mut writer = Block2Writer::default();
+        writer.append(&[1; 100]);
+
+        // Only 32 bytes of payload fit into a 48-byte message.
+        let context = TestContext::new(None, 48);
+        assert_eq!(
+            Ok(BlockInfo::new(0, true, 1)),
+            writer.respond(&context, content)
+        );
+        assert_eq!(&[1; 32][..], context.response().payload());
+
+        // The client may ask for a smaller block size than the one it was given.
+        let context = TestContext::new(BlockInfo::new(2, false, 0), 1024);
+        assert_eq!(
+            Ok(BlockInfo::new(2, true, 0)),
+            writer.respond(&context, content)
+        );
+        assert_eq!(&[1; 16][..], context.response().payload());
+
+        let context = TestContext::new(BlockInfo::new(3, false, 0), 1024);
+        assert_eq!(
+            Ok(BlockInfo::new(3, true, 0)),
+            writer.respond(&context, content)
+        );
+
+        // The remaining 36 bytes fit into a single block.
+        let context = TestContext::new(BlockInfo::new(1, false, 2), 1024);
+        assert_eq!(
+            Ok(BlockInfo::new(1, false, 2)),
+            writer.respond(&context, content)
+        );
+        assert_eq!(&[1; 36][..], context.response().payload());
+        assert!(writer.is_finished());
+
+        let context = TestContext::new(BlockInfo::new(2, false, 2), 1024);
+        assert_eq!(Ok(None), writer.respond(&context, content));
+    }
+}
@@ -0,0 +1,249 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+use crate::message::MessageRead;
+use std::convert::TryFrom;
+
+/// The canonical cache key of a CoAP request, as described in [IETF-RFC7252 Section 5.6].
+///
+/// Two requests have equal cache keys if they have the same method and the same options,
+/// ignoring options which are marked as "NoCacheKey" (see [`OptionNumber::is_no_cache_key`])
+/// like `Size1`. Since the payload of a `FETCH` request identifies the requested content,
+/// it is also part of the cache key of `FETCH` requests ([IETF-RFC8132 Section 2]).
+///
+/// The key does not identify the endpoint that the request was sent to, since the
+/// `Uri-Host` and `Uri-Port` options are omitted when they have their default values.
+/// Caches which store responses from more than one endpoint should key on both.
+///
+/// ```
+/// # use async_coap::prelude::*;
+/// # use async_coap::CacheKey;
+/// # use async_coap::message::{MessageWrite, StandardMessageParser, VecMessageEncoder};
+/// let mut request = VecMessageEncoder::new();
+/// request.set_msg_code(MsgCode::MethodGet);
+/// request.insert_option(option::URI_PATH, "temp")?;
+/// let key_a = CacheKey::from_request(&StandardMessageParser::new(request.as_bytes())?)?;
+///
+/// // `Size1` is a NoCacheKey option, so it doesn't change the key.
+/// request.insert_option(option::SIZE1, 1234)?;
+/// let key_b = CacheKey::from_request(&StandardMessageParser::new(request.as_bytes())?)?;
+/// assert_eq!(key_a, key_b);
+///
+/// // `Accept` is part of the cache key.
+/// request.insert_option(option::ACCEPT, ContentFormat::APPLICATION_JSON)?;
+/// let key_c = CacheKey::from_request(&StandardMessageParser::new(request.as_bytes())?)?;
+/// assert_ne!(key_a, key_c);
+/// # Ok::<(), async_coap::Error>(())
+/// ```
+///
+/// [IETF-RFC7252 Section 5.6]: https://tools.ietf.org/html/rfc7252#section-5.6
+/// [IETF-RFC8132 Section 2]: https://tools.ietf.org/html/rfc8132#section-2
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct CacheKey(Vec<u8>);
+
+impl CacheKey {
+    /// Computes the cache key for the given request message.
+    ///
+    /// Fails with [`Error::ParseFailure`] if the options couldn't be parsed, or with
+    /// [`Error::InvalidArgument`] if the message isn't a request.
+    pub fn from_request(msg: &dyn MessageRead) -> Result<CacheKey, Error> {
+        CacheKey::from_parts(msg.msg_code(), msg.options(), msg.payload())
+    }
+
+    /// Computes the cache key for a request with the given method, options, and payload.
+    ///
+    /// The options must be in the order in which they would appear in the message.
+    ///
+    /// Fails with [`Error::InvalidArgument`] if `method` isn't a request method, or if
+    /// an option value is longer than `u32::MAX` bytes.
+    pub fn from_parts<'a, I>(method: MsgCode, options: I, payload: &[u8]) -> Result<CacheKey, Error>
+    where
+        I: IntoIterator<Item = Result<(OptionNumber, &'a [u8]), Error>>,
+    {
+        if !method.is_method() {
+            return Err(Error::InvalidArgument);
+        }
+
+        // Each option is encoded as its number and its length, followed by its value.
+        // Since the encoding is unambiguous, equal keys imply equal requests.
+        let mut key = vec![method as u8];
+
+        for option in options {
+            let (number, value) = option?;
+
+            if number.is_no_cache_key() {
+                continue;
+            }
+
+            key.extend_from_slice(&number.0.to_be_bytes());
+            let len = u32::try_from(value.len()).map_err(|_| Error::InvalidArgument)?;
+            key.extend_from_slice(&len.to_be_bytes());
+            key.extend_from_slice(value);
+        }
+
+        if method == MsgCode::MethodFetch {
+            key.extend_from_slice(payload);
+        }
+
+        Ok(CacheKey(key))
+    }
+
+    /// Returns the canonical encoding of this cache key, suitable for use as a
+    /// key in external storage.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Returns a 64-bit hash of this cache key which, unlike the one computed by its
+    /// [`Hash`] implementation, is the same on every platform and in every process.
+    pub fn stable_hash(&self) -> u64 {
+        async_coap_uri::StableHasher::hash_bytes(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::{MessageWrite, OwnedImmutableMessage, VecMessageEncoder};
+
+    fn key_for<F>(f: F) -> CacheKey
+    where
+        F: FnOnce(&mut VecMessageEncoder) -> Result<(), Error>,
+    {
+        let mut encoder = VecMessageEncoder::new();
+        f(&mut encoder).unwrap();
+        CacheKey::from_request(&OwnedImmutableMessage::from(encoder)).unwrap()
+    }
+
+    #[test]
+    fn cache_key() {
+        let get = |encoder: &mut VecMessageEncoder| {
+            encoder.set_msg_code(MsgCode::MethodGet);
+            encoder.set_msg_id(1);
+            encoder.insert_option(option::URI_PATH, "a")?;
+            encoder.insert_option(option::URI_PATH, "b")
+        };
+
+        let key = key_for(get);
+
+        // Message ids, tokens, NoCacheKey options and GET payloads aren't part of the key.
+        assert_eq!(
+            key,
+            key_for(|encoder| {
+                encoder.set_msg_token(MsgToken::from(1234u32));
+                get(encoder)?;
+                encoder.set_msg_id(2);
+                encoder.insert_option(option::SIZE1, 10)?;
+                encoder.append_payload_string("ignored")
+            })
+        );
+
+        // Everything else is.
+        assert_ne!(
+            key,
+            key_for(|encoder| {
+                get(encoder)?;
+                encoder.set_msg_code(MsgCode::MethodDelete);
+                Ok(())
+            })
+        );
+        assert_ne!(
+            key,
+            key_for(|encoder| {
+                encoder.set_msg_code(MsgCode::MethodGet);
+                encoder.insert_option(option::URI_PATH, "ab")
+            })
+        );
+        assert_ne!(
+            key,
+            key_for(|encoder| {
+                get(encoder)?;
+                encoder.insert_option(option::ACCEPT, ContentFormat::TEXT_PLAIN_UTF8)
+            })
+        );
+
+        // The payload is part of the key for FETCH.
+        let fetch = |payload: &'static str| {
+            move |encoder: &mut VecMessageEncoder| {
+                encoder.set_msg_code(MsgCode::MethodFetch);
+                encoder.append_payload_string(payload)
+            }
+        };
+        assert_eq!(key_for(fetch("a")), key_for(fetch("a")));
+        assert_ne!(key_for(fetch("a")), key_for(fetch("b")));
+    }
+
+    #[test]
+    fn cache_key_long_option_value() {
+        // A value of 65536 bytes which looks like an option with a 16-bit length. If the
+        // length of the value was truncated to 16 bits, the key would be the same as the
+        // key for an empty option followed by that option.
+        let short = vec![b'x'; 65532];
+        let mut long = OptionNumber::URI_PATH.0.to_be_bytes().to_vec();
+        long.extend_from_slice(&(short.len() as u16).to_be_bytes());
+        long.extend_from_slice(&short);
+        assert_eq!(65536, long.len());
+
+        let key_long = CacheKey::from_parts(
+            MsgCode::MethodGet,
+            vec![Ok((OptionNumber::URI_PATH, &long[..]))],
+            &[],
+        )
+        .unwrap();
+        let key_short = CacheKey::from_parts(
+            MsgCode::MethodGet,
+            vec![
+                Ok((OptionNumber::URI_PATH, &[][..])),
+                Ok((OptionNumber::URI_PATH, &short[..])),
+            ],
+            &[],
+        )
+        .unwrap();
+
+        assert_ne!(key_long, key_short);
+    }
+
+    #[test]
+    fn cache_key_stable_hash() {
+        let key = key_for(|encoder| {
+            encoder.set_msg_code(MsgCode::MethodGet);
+            encoder.insert_option(option::URI_PATH, "a")
+        });
+
+        assert_eq!(
+            async_coap_uri::StableHasher::hash_bytes(key.as_bytes()),
+            key.stable_hash()
+        );
+        assert_ne!(
+            key.stable_hash(),
+            key_for(|encoder| {
+                encoder.set_msg_code(MsgCode::MethodGet);
+                encoder.insert_option(option::URI_PATH, "b")
+            })
+            .stable_hash()
+        );
+    }
+
+    #[test]
+    fn cache_key_not_request() {
+        let mut encoder = VecMessageEncoder::new();
+        encoder.set_msg_code(MsgCode::SuccessContent);
+        assert_eq!(
+            Err(Error::InvalidArgument),
+            CacheKey::from_request(&OwnedImmutableMessage::from(encoder))
+        );
+    }
+}
@@ -0,0 +1,176 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+
+use futures::channel::oneshot;
+use futures::future::Shared;
+use futures::task::{Context, Poll};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug)]
+struct CancellationInner {
+    sender: Mutex<Option<oneshot::Sender<()>>>,
+    receiver: Shared<oneshot::Receiver<()>>,
+}
+
+/// Handle for explicitly cancelling operations like
+/// [`LocalEndpointExt::send_with_cancel`] and [`RemoteEndpointExt::send_with_cancel`].
+///
+/// Clones of a token share its state, so any of them can be used to cancel all of the
+/// operations that were given one of the others. Once cancelled, a token stays cancelled.
+///
+/// Dropping the future of an operation also stops it, but only takes effect when and
+/// where the future happens to be dropped. Cancelling a token can be done from anywhere,
+/// and makes the future resolve to [`Error::Cancelled`] the next time it is polled, even
+/// if it could also have resolved to a result at that point.
+///
+/// ```
+/// # use async_coap::prelude::*;
+/// # use async_coap::{CancellationToken, Error, RemoteEndpoint, RemoteEndpointExt};
+/// # async fn get<RE: RemoteEndpoint>(remote_endpoint: RE) {
+/// let token = CancellationToken::new();
+///
+/// let future = remote_endpoint
+///     .send_with_cancel(CoapRequest::get().emit_successful_response(), token.clone());
+///
+/// // ...possibly on another thread:
+/// token.cancel();
+///
+/// assert_eq!(Err(Error::Cancelled), future.await.map(|_| ()));
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct CancellationToken {
+    inner: Arc<CancellationInner>,
+}
+
+impl Default for CancellationToken {
+    fn default() -> Self {
+        let (sender, receiver) = oneshot::channel();
+
+        CancellationToken {
+            inner: Arc::new(CancellationInner {
+                sender: Mutex::new(Some(sender)),
+                receiver: receiver.shared(),
+            }),
+        }
+    }
+}
+
+impl CancellationToken {
+    /// Creates a new token which hasn't been cancelled.
+    pub fn new() -> CancellationToken {
+        Self::default()
+    }
+
+    /// Cancels all of the operations using this token or its clones, including ones that
+    /// are given one of them later on.
+    pub fn cancel(&self) {
+        // Dropping the sender completes all of the receivers.
+        self.inner.sender.lock().expect("Lock failed").take();
+    }
+
+    /// Returns true if this token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.sender.lock().expect("Lock failed").is_none()
+    }
+
+    /// Returns a future which completes once this token is cancelled.
+    pub fn cancelled(&self) -> impl Future<Output = ()> + Send + Unpin {
+        self.inner.receiver.clone().map(|_| ())
+    }
+
+    /// Wraps `future` so that it resolves to [`Error::Cancelled`] as soon as this token
+    /// is cancelled, dropping `future` without polling it again.
+    pub(crate) fn guard<'a, T, F>(self, future: F) -> BoxFuture<'a, Result<T, Error>>
+    where
+        T: Send + 'a,
+        F: Future<Output = Result<T, Error>> + Send + 'a,
+    {
+        Guarded {
+            cancelled: self.cancelled(),
+            _token: self,
+            future: Some(future.boxed()),
+        }
+        .boxed()
+    }
+}
+
+/// Future returned by [`CancellationToken::guard`].
+struct Guarded<'a, T, C> {
+    // Holding on to the token keeps it from being cancelled by being dropped.
+    _token: CancellationToken,
+    cancelled: C,
+    future: Option<BoxFuture<'a, Result<T, Error>>>,
+}
+
+impl<'a, T, C: Future<Output = ()> + Unpin> Future for Guarded<'a, T, C> {
+    type Output = Result<T, Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Cancellation is checked first, so that it always takes precedence.
+        if self.cancelled.poll_unpin(cx).is_ready() {
+            self.future = None;
+            return Poll::Ready(Err(Error::Cancelled));
+        }
+
+        match self.future.as_mut() {
+            Some(future) => future.poll_unpin(cx),
+            None => Poll::Ready(Err(Error::Cancelled)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+
+    #[test]
+    fn cancel() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert!(!token.is_cancelled());
+
+        clone.cancel();
+        assert!(token.is_cancelled());
+        block_on(token.cancelled());
+
+        // Cancellation takes precedence over futures which are ready.
+        let future = token.guard(futures::future::ready(Ok(())));
+        assert_eq!(Err(Error::Cancelled), block_on(future));
+    }
+
+    #[test]
+    fn guard() {
+        let token = CancellationToken::new();
+        let future = token.clone().guard(futures::future::ready(Ok(1)));
+        assert_eq!(Ok(1), block_on(future));
+
+        // Dropping all of the other clones doesn't cancel the token.
+        let future = CancellationToken::new().guard(futures::future::ready(Ok(2)));
+        assert_eq!(Ok(2), block_on(future));
+
+        let (sender, receiver) = oneshot::channel::<()>();
+        let future = token.clone().guard(receiver.map(|_| Ok::<(), Error>(())));
+        token.cancel();
+        assert_eq!(Err(Error::Cancelled), block_on(future));
+
+        // The guarded future was dropped.
+        assert!(sender.is_canceled());
+    }
+}
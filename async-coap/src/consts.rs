@@ -27,23 +27,38 @@ pub const DEFAULT_PORT_COAP_TCP: u16 = 5683;
 /// The standard default IP port number used for CoAP-over-TLS.
 pub const DEFAULT_PORT_COAP_TLS: u16 = 5684;
 
+/// The standard default IP port number used for CoAP-over-WebSockets, which is the
+/// port used for HTTP.
+pub const DEFAULT_PORT_COAP_WS: u16 = 80;
+
 /// The standard URI scheme for vanilla CoAP-over-UDP on IP networks.
-pub const URI_SCHEME_COAP: &'static str = "coap";
+pub const URI_SCHEME_COAP: &str = "coap";
 
 /// The standard URI scheme for CoAP-over-DTLS on IP networks.
-pub const URI_SCHEME_COAPS: &'static str = "coaps";
+pub const URI_SCHEME_COAPS: &str = "coaps";
 
 /// The standard URI scheme for CoAP-over-TCP on IP networks.
-pub const URI_SCHEME_COAP_TCP: &'static str = "coap+tcp";
+pub const URI_SCHEME_COAP_TCP: &str = "coap+tcp";
 
 /// The standard URI scheme for CoAP-over-TLS on IP networks.
-pub const URI_SCHEME_COAPS_TCP: &'static str = "coaps+tcp";
+pub const URI_SCHEME_COAPS_TCP: &str = "coaps+tcp";
+
+/// The standard URI scheme for CoAP-over-WebSockets.
+pub const URI_SCHEME_COAP_WS: &str = "coap+ws";
+
+/// The URI scheme for CoAP-over-SMS, as described in [draft-becker-core-coap-sms-gprs].
+///
+/// CoAP-over-SMS has no port numbers: the host component of the URI is the
+/// [E.164](https://en.wikipedia.org/wiki/E.164) telephone number of the endpoint.
+///
+/// [draft-becker-core-coap-sms-gprs]: https://tools.ietf.org/html/draft-becker-core-coap-sms-gprs
+pub const URI_SCHEME_COAP_SMS: &str = "coap+sms";
 
 /// Non-standard URI scheme for a [loopback interface](https://en.wikipedia.org/wiki/Loopback).
-pub const URI_SCHEME_LOOPBACK: &'static str = "loop";
+pub const URI_SCHEME_LOOPBACK: &str = "loop";
 
 /// Non-standard URI scheme for a [null interface](https://en.wikipedia.org/wiki/Black_hole_(networking)).
-pub const URI_SCHEME_NULL: &'static str = "null";
+pub const URI_SCHEME_NULL: &str = "null";
 
 /// A fake hostname representing the "all CoAP devices" multicast addresses, or
 /// the equivalent for a given network layer.
@@ -51,16 +66,16 @@ pub const URI_SCHEME_NULL: &'static str = "null";
 /// Note that the value of this string has been chosen somewhat arbitrarily and
 /// is unlikely to be supported outside of this library. The trailing "dot" is to
 /// ensure that it can never be interpreted as a partial domain name.
-pub const ALL_COAP_DEVICES_HOSTNAME: &'static str = "all-coap-devices.";
+pub const ALL_COAP_DEVICES_HOSTNAME: &str = "all-coap-devices.";
 
 /// String slice containing the "All CoAP Devices" IPv6 **Link**-Local Multicast Address: `FF02::FD`
-pub const ALL_COAP_DEVICES_V6_LL: &'static str = "FF02::FD";
+pub const ALL_COAP_DEVICES_V6_LL: &str = "FF02::FD";
 
 /// String slice containing the "All CoAP Devices" IPv6 **Realm**-Local Multicast Address: `FF03::FD`
-pub const ALL_COAP_DEVICES_V6_RL: &'static str = "FF03::FD";
+pub const ALL_COAP_DEVICES_V6_RL: &str = "FF03::FD";
 
 /// String slice containing the "All CoAP Devices" IPv4 **Link**-Local Multicast Address: `224.0.1.187`
-pub const ALL_COAP_DEVICES_V4: &'static str = "224.0.1.187";
+pub const ALL_COAP_DEVICES_V4: &str = "224.0.1.187";
 
 /// Value for `OptionNumber::OBSERVE` when registering an observer.
 ///
@@ -98,6 +98,17 @@ impl ContentFormat {
     /// XML-formatted RFC8428 Sensor Streaming Measurement List (SenSML)
     pub const APPLICATION_SENSML_XML: ContentFormat = ContentFormat(311);
 
+    /// CBOR-encoded YANG data with SIDs as identifiers ([IETF-RFC9254]).
+    ///
+    /// [IETF-RFC9254]: https://tools.ietf.org/html/rfc9254
+    pub const APPLICATION_YANG_DATA_CBOR_SID: ContentFormat = ContentFormat(140);
+
+    /// CBOR sequence of YANG instance identifiers, from draft-ietf-core-comi.
+    pub const APPLICATION_YANG_IDENTIFIERS_CBOR_SEQ: ContentFormat = ContentFormat(141);
+
+    /// CBOR sequence of YANG instances, from draft-ietf-core-comi.
+    pub const APPLICATION_YANG_INSTANCES_CBOR_SEQ: ContentFormat = ContentFormat(142);
+
     /// [IETF-RFC7389] Group Communication for the Constrained Application Protocol
     ///
     /// [IETF-RFC7389]: https://tools.ietf.org/html/rfc7390#section-6.2
@@ -144,6 +155,10 @@ impl ContentFormat {
             Self::APPLICATION_SENML_XML => "application/senml+xml",
             Self::APPLICATION_SENSML_XML => "application/sensml+xml",
 
+            Self::APPLICATION_YANG_DATA_CBOR_SID => "application/yang-data+cbor;id=sid",
+            Self::APPLICATION_YANG_IDENTIFIERS_CBOR_SEQ => "application/yang-identifiers+cbor-seq",
+            Self::APPLICATION_YANG_INSTANCES_CBOR_SEQ => "application/yang-instances+cbor-seq",
+
             Self::APPLICATION_COAP_GROUP_JSON => "application/coap-group+json",
 
             Self::APPLICATION_OSCORE => "application/oscore",
@@ -154,6 +169,94 @@ impl ContentFormat {
         })
     }
 
+    /// Attempts to determine the content format from the given MIME name (like the value of
+    /// an HTTP `Content-Type` header), as described in [IETF-RFC8075 Section 6.1].
+    ///
+    /// Matching is case-insensitive and ignores whitespace around parameters. `text/plain`
+    /// is considered equivalent to `text/plain;charset=utf-8`, and names of the form
+    /// `application/x-coap-N` (as emitted by the [`Display`] implementation for unregistered
+    /// content formats) are mapped back to `ContentFormat(N)`.
+    ///
+    /// ```
+    /// # use async_coap::ContentFormat;
+    /// assert_eq!(
+    ///     ContentFormat::from_name("application/json"),
+    ///     Some(ContentFormat::APPLICATION_JSON)
+    /// );
+    /// assert_eq!(
+    ///     ContentFormat::from_name("Text/Plain; charset=UTF-8"),
+    ///     Some(ContentFormat::TEXT_PLAIN_UTF8)
+    /// );
+    /// assert_eq!(
+    ///     ContentFormat::from_name("application/x-coap-65000"),
+    ///     Some(ContentFormat(65000))
+    /// );
+    /// assert_eq!(ContentFormat::from_name("image/png"), None);
+    /// ```
+    ///
+    /// [IETF-RFC8075 Section 6.1]: https://tools.ietf.org/html/rfc8075#section-6.1
+    /// [`Display`]: core::fmt::Display
+    pub fn from_name(name: &str) -> Option<ContentFormat> {
+        let name = name
+            .split(';')
+            .map(str::trim)
+            .filter(|x| !x.is_empty())
+            .collect::<Vec<_>>()
+            .join(";")
+            .to_ascii_lowercase();
+
+        match name.as_str() {
+            "text/plain" | "text/plain;charset=utf-8" => return Some(Self::TEXT_PLAIN_UTF8),
+            _ => (),
+        }
+
+        if let Some(number) = name.strip_prefix("application/x-coap-") {
+            return number.parse().ok().map(ContentFormat);
+        }
+
+        Self::KNOWN
+            .iter()
+            .cloned()
+            .find(|x| x.static_name().map(str::to_ascii_lowercase).as_ref() == Some(&name))
+    }
+
+    /// All of the content formats with a [static name](ContentFormat::static_name).
+    const KNOWN: &'static [ContentFormat] = &[
+        Self::TEXT_PLAIN_UTF8,
+        Self::APPLICATION_COSE_COSE_ENCRYPT0,
+        Self::APPLICATION_COSE_COSE_MAC0,
+        Self::APPLICATION_COSE_COSE_SIGN1,
+        Self::APPLICATION_LINK_FORMAT,
+        Self::APPLICATION_XML,
+        Self::APPLICATION_OCTET_STREAM,
+        Self::APPLICATION_EXI,
+        Self::APPLICATION_JSON,
+        Self::APPLICATION_JSON_PATCH_JSON,
+        Self::APPLICATION_MERGE_PATCH_JSON,
+        Self::APPLICATION_CBOR,
+        Self::APPLICATION_CWT,
+        Self::APPLICATION_COSE_COSE_ENCRYPT,
+        Self::APPLICATION_COSE_COSE_MAC,
+        Self::APPLICATION_COSE_COSE_SIGN,
+        Self::APPLICATION_COSE_KEY,
+        Self::APPLICATION_COSE_KEY_SET,
+        Self::APPLICATION_SENML_JSON,
+        Self::APPLICATION_SENSML_JSON,
+        Self::APPLICATION_SENML_CBOR,
+        Self::APPLICATION_SENSML_CBOR,
+        Self::APPLICATION_SENML_EXI,
+        Self::APPLICATION_SENSML_EXI,
+        Self::APPLICATION_SENML_XML,
+        Self::APPLICATION_SENSML_XML,
+        Self::APPLICATION_YANG_DATA_CBOR_SID,
+        Self::APPLICATION_YANG_IDENTIFIERS_CBOR_SEQ,
+        Self::APPLICATION_YANG_INSTANCES_CBOR_SEQ,
+        Self::APPLICATION_COAP_GROUP_JSON,
+        Self::APPLICATION_OSCORE,
+        Self::APPLICATION_JSON_DEFLATE,
+        Self::APPLICATION_CBOR_DEFLATE,
+    ];
+
     /// Returns a MIME name for this content format.
     pub fn name(&self) -> Cow<'static, str> {
         if let Some(name) = self.static_name() {
@@ -223,6 +326,9 @@ impl ContentFormat {
             Self::APPLICATION_CWT => true,
             Self::APPLICATION_SENML_CBOR => true,
             Self::APPLICATION_SENSML_CBOR => true,
+            Self::APPLICATION_YANG_DATA_CBOR_SID => true,
+            Self::APPLICATION_YANG_IDENTIFIERS_CBOR_SEQ => true,
+            Self::APPLICATION_YANG_INSTANCES_CBOR_SEQ => true,
             Self::APPLICATION_OSCORE => true,
             _ => false,
         }
@@ -0,0 +1,865 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Experimental support for the CoAP Management Interface (CORECONF).
+//!
+//! CORECONF ([draft-ietf-core-comi]) manages devices using YANG data models. Data nodes
+//! are identified by numeric YANG Schema Item iDentifiers (SIDs), and their values are
+//! encoded using CBOR as described in [IETF-RFC9254]. This module implements those
+//! encoding rules along with the handling of requests to a datastore resource, leaving
+//! the storage of the data to an implementation of [`Datastore`]:
+//!
+//! * `GET` returns the whole datastore.
+//! * `FETCH` returns the instances identified in the request payload.
+//! * `iPATCH` replaces or deletes the instances in the request payload.
+//!
+//! Only the subset of CBOR used by YANG data is supported: indefinite-length items,
+//! floating-point values, and tags other than the one for absolute SIDs are rejected.
+//!
+//! ```
+//! # use async_coap::prelude::*;
+//! # use async_coap::datagram::*;
+//! # use async_coap::{Error, RespondableInboundContext};
+//! # use async_coap::coreconf::{DatastoreResource, MemoryDatastore, Value};
+//! # let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+//! let resource = DatastoreResource::new(
+//!     MemoryDatastore::new().with_node(1721, Value::Text("router-1".to_string())),
+//! );
+//!
+//! let handler = |context: &DatagramRespondableInboundContext<_>| -> Result<(), Error> {
+//!     if resource.handle_request(context)? {
+//!         return Ok(());
+//!     }
+//!
+//!     // Handle other requests...
+//!     context.respond_not_found()
+//! };
+//! # let _ = local_endpoint.receive_loop(handler);
+//! ```
+//!
+//! [draft-ietf-core-comi]: https://tools.ietf.org/html/draft-ietf-core-comi
+//! [IETF-RFC9254]: https://tools.ietf.org/html/rfc9254
+
+use super::*;
+use crate::option::RequestOptions;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::sync::Mutex;
+
+/// A YANG Schema Item iDentifier.
+pub type Sid = u64;
+
+/// The CBOR tag for SIDs which are encoded as absolute values rather than as deltas.
+const TAG_ABSOLUTE_SID: u64 = 47;
+
+/// The maximum nesting depth of decoded values.
+const MAX_DEPTH: usize = 32;
+
+/// The value of a YANG data node.
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Value {
+    /// The value of a leaf of type `empty`, or the absence of a value.
+    Null,
+
+    /// The value of a leaf of type `boolean`.
+    Bool(bool),
+
+    /// The value of a leaf of one of the integer types, or of an `enumeration`.
+    Integer(i128),
+
+    /// The value of a leaf of type `binary`.
+    Bytes(Vec<u8>),
+
+    /// The value of a leaf of type `string`, or of another type encoded as a string.
+    Text(String),
+
+    /// The entries of a `leaf-list` or `list`.
+    Array(Vec<Value>),
+
+    /// The children of a container or list entry, keyed by their SIDs.
+    ///
+    /// When encoded, the SIDs of the children are replaced by their delta from the SID
+    /// of the parent node ([IETF-RFC9254 Section 3.2]).
+    ///
+    /// [IETF-RFC9254 Section 3.2]: https://tools.ietf.org/html/rfc9254#section-3.2
+    Container(BTreeMap<Sid, Value>),
+}
+
+impl Value {
+    /// Encodes this value as CBOR, as the value of the data node with the SID `parent`.
+    ///
+    /// Fails with [`Error::InvalidArgument`] if an integer is out of the range that can be
+    /// represented in CBOR.
+    pub fn to_cbor(&self, parent: Sid) -> Result<Vec<u8>, Error> {
+        let mut ret = Vec::new();
+        self.write(parent, &mut ret)?;
+        Ok(ret)
+    }
+
+    /// Decodes a value from CBOR, as the value of the data node with the SID `parent`.
+    ///
+    /// Fails with [`Error::ParseFailure`] if `buffer` doesn't contain exactly one
+    /// supported CBOR data item.
+    pub fn from_cbor(buffer: &[u8], parent: Sid) -> Result<Value, Error> {
+        let mut decoder = Decoder { buffer };
+        let ret = decoder.value(parent, 0)?;
+
+        if !decoder.is_empty() {
+            return Err(Error::ParseFailure);
+        }
+
+        Ok(ret)
+    }
+
+    fn write(&self, parent: Sid, out: &mut Vec<u8>) -> Result<(), Error> {
+        match self {
+            Value::Null => out.push(0xF6),
+            Value::Bool(false) => out.push(0xF4),
+            Value::Bool(true) => out.push(0xF5),
+            Value::Integer(x) if *x >= 0 => write_head(
+                out,
+                0,
+                u64::try_from(*x).map_err(|_| Error::InvalidArgument)?,
+            ),
+            Value::Integer(x) => write_head(
+                out,
+                1,
+                u64::try_from(-1 - *x).map_err(|_| Error::InvalidArgument)?,
+            ),
+            Value::Bytes(bytes) => {
+                write_head(out, 2, bytes.len() as u64);
+                out.extend_from_slice(bytes);
+            }
+            Value::Text(text) => {
+                write_head(out, 3, text.len() as u64);
+                out.extend_from_slice(text.as_bytes());
+            }
+            Value::Array(items) => {
+                write_head(out, 4, items.len() as u64);
+                for item in items {
+                    item.write(parent, out)?;
+                }
+            }
+            Value::Container(children) => {
+                write_head(out, 5, children.len() as u64);
+                for (sid, value) in children {
+                    write_sid(out, *sid, parent);
+                    value.write(*sid, out)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Identifies an instance of a data node: its SID, along with the keys of the list entry
+/// containing it, if any.
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct InstanceId {
+    /// The SID of the data node.
+    pub sid: Sid,
+
+    /// The values of the keys identifying the list entry, in the order in which they are
+    /// defined by the data model.
+    pub keys: Vec<Value>,
+}
+
+impl InstanceId {
+    /// Creates an instance identifier for a data node which isn't part of a list.
+    pub fn new(sid: Sid) -> InstanceId {
+        InstanceId::with_keys(sid, Vec::new())
+    }
+
+    /// Creates an instance identifier for a data node in the list entry identified by
+    /// `keys`.
+    pub fn with_keys(sid: Sid, keys: Vec<Value>) -> InstanceId {
+        InstanceId { sid, keys }
+    }
+
+    fn write(&self, out: &mut Vec<u8>) -> Result<(), Error> {
+        if self.keys.is_empty() {
+            write_head(out, 0, self.sid);
+        } else {
+            write_head(out, 4, 1 + self.keys.len() as u64);
+            write_head(out, 0, self.sid);
+            for key in self.keys.iter() {
+                key.write(self.sid, out)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl From<Sid> for InstanceId {
+    fn from(sid: Sid) -> Self {
+        InstanceId::new(sid)
+    }
+}
+
+/// Writes the head of a CBOR data item with the given major type and argument.
+fn write_head(out: &mut Vec<u8>, major: u8, arg: u64) {
+    let major = major << 5;
+
+    if arg < 24 {
+        out.push(major | arg as u8);
+    } else if arg <= 0xFF {
+        out.push(major | 24);
+        out.push(arg as u8);
+    } else if arg <= 0xFFFF {
+        out.push(major | 25);
+        out.extend_from_slice(&(arg as u16).to_be_bytes());
+    } else if arg <= 0xFFFF_FFFF {
+        out.push(major | 26);
+        out.extend_from_slice(&(arg as u32).to_be_bytes());
+    } else {
+        out.push(major | 27);
+        out.extend_from_slice(&arg.to_be_bytes());
+    }
+}
+
+/// Writes `sid` as a delta from `parent`.
+fn write_sid(out: &mut Vec<u8>, sid: Sid, parent: Sid) {
+    if sid >= parent {
+        write_head(out, 0, sid - parent);
+    } else {
+        write_head(out, 1, parent - sid - 1);
+    }
+}
+
+/// Decoder for the subset of CBOR used by CORECONF.
+#[derive(Debug)]
+struct Decoder<'a> {
+    buffer: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    fn peek_major(&self) -> Option<u8> {
+        self.buffer.first().map(|first| first >> 5)
+    }
+
+    /// Decodes the head of the next data item, returning its major type, additional
+    /// information, and argument.
+    fn head(&mut self) -> Result<(u8, u8, u64), Error> {
+        let (first, rest) = self.buffer.split_first().ok_or(Error::ParseFailure)?;
+        let info = first & 0x1F;
+        let len = match info {
+            0..=23 => 0,
+            24 => 1,
+            25 => 2,
+            26 => 4,
+            27 => 8,
+            _ => return Err(Error::ParseFailure),
+        };
+
+        if rest.len() < len {
+            return Err(Error::ParseFailure);
+        }
+
+        let arg = match len {
+            0 => info as u64,
+            len => rest[..len].iter().fold(0u64, |x, b| (x << 8) | *b as u64),
+        };
+
+        self.buffer = &rest[len..];
+
+        Ok((first >> 5, info, arg))
+    }
+
+    fn bytes(&mut self, len: u64) -> Result<&'a [u8], Error> {
+        if len > self.buffer.len() as u64 {
+            return Err(Error::ParseFailure);
+        }
+
+        let (ret, rest) = self.buffer.split_at(len as usize);
+        self.buffer = rest;
+        Ok(ret)
+    }
+
+    fn value(&mut self, parent: Sid, depth: usize) -> Result<Value, Error> {
+        if depth > MAX_DEPTH {
+            return Err(Error::ParseFailure);
+        }
+
+        Ok(match self.head()? {
+            (0, _, arg) => Value::Integer(arg as i128),
+            (1, _, arg) => Value::Integer(-1 - arg as i128),
+            (2, _, len) => Value::Bytes(self.bytes(len)?.to_vec()),
+            (3, _, len) => Value::Text(
+                String::from_utf8(self.bytes(len)?.to_vec()).map_err(|_| Error::ParseFailure)?,
+            ),
+            (4, _, len) => {
+                let mut items = Vec::new();
+                for _ in 0..len {
+                    items.push(self.value(parent, depth + 1)?);
+                }
+                Value::Array(items)
+            }
+            (5, _, len) => {
+                let mut children = BTreeMap::new();
+                for _ in 0..len {
+                    let sid = self.sid(parent)?;
+                    let value = self.value(sid, depth + 1)?;
+                    if children.insert(sid, value).is_some() {
+                        return Err(Error::ParseFailure);
+                    }
+                }
+                Value::Container(children)
+            }
+            (7, 20, _) => Value::Bool(false),
+            (7, 21, _) => Value::Bool(true),
+            (7, 22, _) => Value::Null,
+            _ => return Err(Error::ParseFailure),
+        })
+    }
+
+    /// Decodes a SID, which is either a delta from `parent` or an absolute SID.
+    fn sid(&mut self, parent: Sid) -> Result<Sid, Error> {
+        match self.head()? {
+            (0, _, delta) => parent.checked_add(delta),
+            (1, _, delta) => parent.checked_sub(delta).and_then(|x| x.checked_sub(1)),
+            (6, _, TAG_ABSOLUTE_SID) => match self.head()? {
+                (0, _, sid) => Some(sid),
+                _ => None,
+            },
+            _ => None,
+        }
+        .ok_or(Error::ParseFailure)
+    }
+
+    /// Decodes an instance identifier: either a SID, or an array containing a SID and
+    /// the keys of a list entry.
+    fn instance_id(&mut self) -> Result<InstanceId, Error> {
+        if self.peek_major() != Some(4) {
+            return Ok(InstanceId::new(self.sid(0)?));
+        }
+
+        let (_, _, len) = self.head()?;
+        if len == 0 {
+            return Err(Error::ParseFailure);
+        }
+
+        let sid = self.sid(0)?;
+        let mut keys = Vec::new();
+        for _ in 1..len {
+            keys.push(self.value(sid, 1)?);
+        }
+
+        Ok(InstanceId::with_keys(sid, keys))
+    }
+}
+
+/// Storage for the data nodes served by a [`DatastoreResource`].
+///
+/// Errors returned by these methods are reported to the client: [`Error::ResourceNotFound`]
+/// as `4.04 Not Found`, [`Error::InvalidArgument`] as `4.00 Bad Request`,
+/// [`Error::Unauthorized`] and [`Error::Forbidden`] as `4.01` and `4.03`, and anything
+/// else as `5.00 Internal Server Error`.
+pub trait Datastore: Send + Sync {
+    /// Returns the values of the top-level data nodes, for `GET` requests.
+    fn read_all(&self) -> Result<BTreeMap<Sid, Value>, Error>;
+
+    /// Returns the value of the instance identified by `id`, or `None` if there is no
+    /// such instance, for `FETCH` requests.
+    fn read(&self, id: &InstanceId) -> Result<Option<Value>, Error>;
+
+    /// Replaces the value of the instance identified by `id` with `value`, or deletes the
+    /// instance if `value` is `None`.
+    fn write(&self, id: &InstanceId, value: Option<Value>) -> Result<(), Error>;
+
+    /// Applies all of the changes from an `iPATCH` request, in order.
+    ///
+    /// The default implementation calls [`Datastore::write`] for each change, stopping
+    /// at the first error. Datastores which can apply the changes atomically, as the
+    /// specification requires, should override this.
+    fn write_all(&self, changes: Vec<(InstanceId, Option<Value>)>) -> Result<(), Error> {
+        for (id, value) in changes {
+            self.write(&id, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// [`Datastore`] which keeps the data nodes in memory, for testing and for simple
+/// applications.
+///
+/// Data nodes can be read and written using the SIDs of top-level nodes and of the
+/// children of containers, but not of nodes inside of lists: instance identifiers with
+/// keys are rejected with [`Error::InvalidArgument`].
+#[derive(Debug, Default)]
+pub struct MemoryDatastore {
+    nodes: Mutex<BTreeMap<Sid, Value>>,
+}
+
+impl MemoryDatastore {
+    /// Creates a new, empty datastore.
+    pub fn new() -> MemoryDatastore {
+        Default::default()
+    }
+
+    /// Adds a top-level data node to this datastore.
+    pub fn with_node(self, sid: Sid, value: Value) -> MemoryDatastore {
+        self.nodes.lock().expect("Lock failed").insert(sid, value);
+        self
+    }
+}
+
+/// Returns the container in `nodes` (or `nodes` itself) which holds the node with the
+/// given SID.
+fn find_parent(nodes: &mut BTreeMap<Sid, Value>, sid: Sid) -> Option<&mut BTreeMap<Sid, Value>> {
+    if nodes.contains_key(&sid) {
+        return Some(nodes);
+    }
+
+    nodes.values_mut().find_map(|value| match value {
+        Value::Container(children) => find_parent(children, sid),
+        _ => None,
+    })
+}
+
+impl Datastore for MemoryDatastore {
+    fn read_all(&self) -> Result<BTreeMap<Sid, Value>, Error> {
+        Ok(self.nodes.lock().expect("Lock failed").clone())
+    }
+
+    fn read(&self, id: &InstanceId) -> Result<Option<Value>, Error> {
+        if !id.keys.is_empty() {
+            return Err(Error::InvalidArgument);
+        }
+
+        let mut nodes = self.nodes.lock().expect("Lock failed");
+
+        Ok(find_parent(&mut nodes, id.sid).and_then(|parent| parent.get(&id.sid).cloned()))
+    }
+
+    fn write(&self, id: &InstanceId, value: Option<Value>) -> Result<(), Error> {
+        if !id.keys.is_empty() {
+            return Err(Error::InvalidArgument);
+        }
+
+        let mut nodes = self.nodes.lock().expect("Lock failed");
+
+        match (find_parent(&mut nodes, id.sid), value) {
+            (Some(parent), Some(value)) => {
+                parent.insert(id.sid, value);
+            }
+            (Some(parent), None) => {
+                parent.remove(&id.sid);
+            }
+            (None, Some(value)) => {
+                nodes.insert(id.sid, value);
+            }
+            (None, None) => (),
+        }
+
+        Ok(())
+    }
+}
+
+/// Serves a [`Datastore`] as a CORECONF datastore resource.
+#[derive(Debug)]
+pub struct DatastoreResource<D> {
+    datastore: D,
+    path: Vec<String>,
+}
+
+impl<D: Datastore> DatastoreResource<D> {
+    /// Creates a new datastore resource at the default path, `/c`.
+    pub fn new(datastore: D) -> DatastoreResource<D> {
+        DatastoreResource {
+            datastore,
+            path: vec!["c".to_string()],
+        }
+    }
+
+    /// Changes the path of the datastore resource. The path is relative to the root of the
+    /// endpoint, and consists of unescaped path segments separated by slashes.
+    pub fn with_path(mut self, path: &str) -> DatastoreResource<D> {
+        self.path = path
+            .trim_start_matches('/')
+            .split('/')
+            .map(str::to_string)
+            .collect();
+        self
+    }
+
+    /// Returns a reference to the datastore being served.
+    pub fn datastore(&self) -> &D {
+        &self.datastore
+    }
+
+    /// Responds to the inbound request if it is for the datastore resource, returning
+    /// true if the request was handled.
+    ///
+    /// This method is intended to be called from the handler passed to
+    /// [`LocalEndpoint::receive`].
+    pub fn handle_request<T: RespondableInboundContext>(&self, context: &T) -> Result<bool, Error> {
+        let msg = context.message();
+
+        let options = match RequestOptions::parse(msg.options()) {
+            Ok(options) => options,
+            Err(_) => return Ok(false),
+        };
+
+        if options.uri_path != self.path {
+            return Ok(false);
+        }
+
+        let result = match msg.msg_code() {
+            MsgCode::MethodGet => self.get(&options),
+            MsgCode::MethodFetch => self.fetch(&options, msg.payload()),
+            MsgCode::MethodIPatch => self.ipatch(&options, msg.payload()),
+            _ => {
+                context.respond_method_not_allowed(&[
+                    MsgCode::MethodGet,
+                    MsgCode::MethodFetch,
+                    MsgCode::MethodIPatch,
+                ])?;
+                return Ok(true);
+            }
+        };
+
+        match result {
+            Ok(Some((content_format, payload))) => context.respond(|msg_out| {
+                msg_out.set_msg_code(MsgCode::SuccessContent);
+                msg_out.insert_option(option::CONTENT_FORMAT, content_format)?;
+                msg_out.append_payload_bytes(&payload)
+            })?,
+            Ok(None) => context.respond(|msg_out| {
+                msg_out.set_msg_code(MsgCode::SuccessChanged);
+                Ok(())
+            })?,
+            Err(code) => context.respond_error(code, "")?,
+        }
+
+        Ok(true)
+    }
+
+    fn get(&self, options: &RequestOptions) -> Result<Option<(ContentFormat, Vec<u8>)>, MsgCode> {
+        check_accept(options, ContentFormat::APPLICATION_YANG_DATA_CBOR_SID)?;
+
+        let nodes = self.datastore.read_all().map_err(error_code)?;
+        let payload = Value::Container(nodes).to_cbor(0).map_err(error_code)?;
+
+        Ok(Some((
+            ContentFormat::APPLICATION_YANG_DATA_CBOR_SID,
+            payload,
+        )))
+    }
+
+    fn fetch(
+        &self,
+        options: &RequestOptions,
+        payload: &[u8],
+    ) -> Result<Option<(ContentFormat, Vec<u8>)>, MsgCode> {
+        check_content_format(
+            options,
+            ContentFormat::APPLICATION_YANG_IDENTIFIERS_CBOR_SEQ,
+        )?;
+        check_accept(options, ContentFormat::APPLICATION_YANG_INSTANCES_CBOR_SEQ)?;
+
+        let mut decoder = Decoder { buffer: payload };
+        let mut ret = Vec::new();
+
+        while !decoder.is_empty() {
+            let id = decoder
+                .instance_id()
+                .map_err(|_| MsgCode::ClientErrorBadRequest)?;
+            let value = self
+                .datastore
+                .read(&id)
+                .map_err(error_code)?
+                .unwrap_or(Value::Null);
+
+            write_head(&mut ret, 5, 1);
+            id.write(&mut ret).map_err(error_code)?;
+            value.write(id.sid, &mut ret).map_err(error_code)?;
+        }
+
+        Ok(Some((
+            ContentFormat::APPLICATION_YANG_INSTANCES_CBOR_SEQ,
+            ret,
+        )))
+    }
+
+    fn ipatch(
+        &self,
+        options: &RequestOptions,
+        payload: &[u8],
+    ) -> Result<Option<(ContentFormat, Vec<u8>)>, MsgCode> {
+        check_content_format(options, ContentFormat::APPLICATION_YANG_INSTANCES_CBOR_SEQ)?;
+
+        let mut decoder = Decoder { buffer: payload };
+        let mut changes = Vec::new();
+
+        while !decoder.is_empty() {
+            let (major, _, len) = decoder.head().map_err(|_| MsgCode::ClientErrorBadRequest)?;
+            if major != 5 {
+                return Err(MsgCode::ClientErrorBadRequest);
+            }
+
+            for _ in 0..len {
+                let id = decoder
+                    .instance_id()
+                    .map_err(|_| MsgCode::ClientErrorBadRequest)?;
+                let value = match decoder.value(id.sid, 1) {
+                    Ok(Value::Null) => None,
+                    Ok(value) => Some(value),
+                    Err(_) => return Err(MsgCode::ClientErrorBadRequest),
+                };
+                changes.push((id, value));
+            }
+        }
+
+        self.datastore.write_all(changes).map_err(error_code)?;
+
+        Ok(None)
+    }
+}
+
+fn check_accept(options: &RequestOptions, content_format: ContentFormat) -> Result<(), MsgCode> {
+    match options.accept {
+        Some(accept) if accept != content_format => Err(MsgCode::ClientErrorNotAcceptable),
+        _ => Ok(()),
+    }
+}
+
+fn check_content_format(
+    options: &RequestOptions,
+    content_format: ContentFormat,
+) -> Result<(), MsgCode> {
+    if options.content_format == Some(content_format) {
+        Ok(())
+    } else {
+        Err(MsgCode::ClientErrorUnsupportedMediaType)
+    }
+}
+
+/// Returns the response code for an error returned by a [`Datastore`].
+fn error_code(error: Error) -> MsgCode {
+    match error {
+        Error::ResourceNotFound => MsgCode::ClientErrorNotFound,
+        Error::InvalidArgument => MsgCode::ClientErrorBadRequest,
+        Error::Unauthorized => MsgCode::ClientErrorUnauthorized,
+        Error::Forbidden => MsgCode::ClientErrorForbidden,
+        _ => MsgCode::ServerErrorInternalServerError,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datagram::{
+        DatagramInboundContext, DatagramLocalEndpoint, DatagramRespondableInboundContext,
+        LoopbackSocket, LoopbackSocketAddr,
+    };
+    use futures::executor::block_on;
+    use futures::future::{select, Either};
+
+    type Context = DatagramRespondableInboundContext<LoopbackSocketAddr>;
+
+    // From the example in IETF-RFC9254 Section 4.2.1: the `system-state/clock` container
+    // of the `ietf-system` module.
+    const CLOCK: Sid = 1721;
+    const CURRENT_DATETIME: Sid = 1723;
+    const BOOT_DATETIME: Sid = 1722;
+
+    fn clock() -> Value {
+        let mut children = BTreeMap::new();
+        children.insert(
+            CURRENT_DATETIME,
+            Value::Text("2015-10-02T14:47:24Z-05:00".to_string()),
+        );
+        children.insert(
+            BOOT_DATETIME,
+            Value::Text("2015-09-15T09:12:58Z-05:00".to_string()),
+        );
+        Value::Container(children)
+    }
+
+    #[test]
+    fn encoding() {
+        let cbor = clock().to_cbor(CLOCK).unwrap();
+
+        let mut expected = vec![0xA2, 0x01, 0x78, 0x1A];
+        expected.extend_from_slice(b"2015-09-15T09:12:58Z-05:00");
+        expected.extend_from_slice(&[0x02, 0x78, 0x1A]);
+        expected.extend_from_slice(b"2015-10-02T14:47:24Z-05:00");
+        assert_eq!(expected, cbor);
+
+        assert_eq!(Ok(clock()), Value::from_cbor(&cbor, CLOCK));
+
+        // Children can be encoded as negative deltas, or as absolute SIDs.
+        let mut children = BTreeMap::new();
+        children.insert(1000, Value::Integer(-5));
+        children.insert(CLOCK, Value::Null);
+        let value = Value::Container(children);
+        assert_eq!(
+            Ok(value.clone()),
+            Value::from_cbor(
+                &[0xA2, 0x38, 0xF9, 0x24, 0xD8, 0x2F, 0x19, 0x06, 0xB9, 0xF6],
+                1250
+            )
+        );
+        assert_eq!(
+            Ok(value.clone()),
+            Value::from_cbor(&value.to_cbor(1250).unwrap(), 1250)
+        );
+
+        assert_eq!(Err(Error::ParseFailure), Value::from_cbor(&[0xF5, 0xF5], 0));
+        assert_eq!(Err(Error::ParseFailure), Value::from_cbor(&[0x9F, 0xFF], 0));
+        assert_eq!(
+            Err(Error::ParseFailure),
+            Value::from_cbor(&[0xA1, 0x20, 0xF6], 0)
+        );
+        assert_eq!(
+            Err(Error::InvalidArgument),
+            Value::Integer(i128::MAX).to_cbor(0)
+        );
+    }
+
+    fn send<SD>(
+        resource: &DatastoreResource<MemoryDatastore>,
+        send_desc: SD,
+    ) -> Response<LoopbackSocketAddr>
+    where
+        SD: SendDesc<DatagramInboundContext<LoopbackSocketAddr>, ()> + Send,
+    {
+        let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+        let future =
+            local_endpoint.send(LoopbackSocketAddr::Unicast, send_desc.emit_any_response());
+        let future_receive = local_endpoint.receive_loop(|context: &Context| {
+            if !resource.handle_request(context)? {
+                context.respond_not_found()?;
+            }
+            Ok(())
+        });
+
+        let (ret, _) = match block_on(select(future, future_receive)) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left(ret) => ret,
+        };
+
+        ret.unwrap()
+    }
+
+    fn request(
+        method: MsgCode,
+        content_format: ContentFormat,
+        payload: Vec<u8>,
+    ) -> impl SendDesc<DatagramInboundContext<LoopbackSocketAddr>, ()> {
+        CoapRequest::method(method)
+            .uri_host_path(None, rel_ref!("c"))
+            .content_format(content_format)
+            .payload_writer(move |msg_out| {
+                msg_out.set_msg_code(method);
+                msg_out.append_payload_bytes(&payload)
+            })
+    }
+
+    #[test]
+    fn datastore_resource() {
+        let resource = DatastoreResource::new(MemoryDatastore::new().with_node(CLOCK, clock()));
+
+        let response = send(
+            &resource,
+            CoapRequest::get().uri_host_path(None, rel_ref!("c")),
+        );
+        assert_eq!(MsgCode::SuccessContent, response.msg_code());
+        assert_eq!(
+            Some(ContentFormat::APPLICATION_YANG_DATA_CBOR_SID),
+            response.content_format()
+        );
+        let mut expected = BTreeMap::new();
+        expected.insert(CLOCK, clock());
+        assert_eq!(
+            Ok(Value::Container(expected)),
+            Value::from_cbor(response.payload(), 0)
+        );
+
+        // FETCH the current time, and a node that doesn't exist.
+        let response = send(
+            &resource,
+            request(
+                MsgCode::MethodFetch,
+                ContentFormat::APPLICATION_YANG_IDENTIFIERS_CBOR_SEQ,
+                vec![0x19, 0x06, 0xBB, 0x01],
+            ),
+        );
+        assert_eq!(MsgCode::SuccessContent, response.msg_code());
+        let mut expected = vec![0xA1, 0x19, 0x06, 0xBB, 0x78, 0x1A];
+        expected.extend_from_slice(b"2015-10-02T14:47:24Z-05:00");
+        expected.extend_from_slice(&[0xA1, 0x01, 0xF6]);
+        assert_eq!(&expected[..], response.payload());
+
+        // iPATCH the current time, and delete the boot time.
+        let mut payload = vec![0xA2, 0x19, 0x06, 0xBB, 0x63];
+        payload.extend_from_slice(b"now");
+        payload.extend_from_slice(&[0x19, 0x06, 0xBA, 0xF6]);
+        let response = send(
+            &resource,
+            request(
+                MsgCode::MethodIPatch,
+                ContentFormat::APPLICATION_YANG_INSTANCES_CBOR_SEQ,
+                payload,
+            ),
+        );
+        assert_eq!(MsgCode::SuccessChanged, response.msg_code());
+        assert_eq!(
+            Ok(Some(Value::Text("now".to_string()))),
+            resource
+                .datastore()
+                .read(&InstanceId::new(CURRENT_DATETIME))
+        );
+        assert_eq!(
+            Ok(None),
+            resource.datastore().read(&InstanceId::new(BOOT_DATETIME))
+        );
+
+        // Requests with the wrong content format, or malformed payloads, are rejected.
+        let response = send(
+            &resource,
+            request(
+                MsgCode::MethodFetch,
+                ContentFormat::APPLICATION_CBOR,
+                vec![0x01],
+            ),
+        );
+        assert_eq!(
+            MsgCode::ClientErrorUnsupportedMediaType,
+            response.msg_code()
+        );
+        let response = send(
+            &resource,
+            request(
+                MsgCode::MethodFetch,
+                ContentFormat::APPLICATION_YANG_IDENTIFIERS_CBOR_SEQ,
+                vec![0x19, 0x06],
+            ),
+        );
+        assert_eq!(MsgCode::ClientErrorBadRequest, response.msg_code());
+
+        let response = send(
+            &resource,
+            CoapRequest::put().uri_host_path(None, rel_ref!("c")),
+        );
+        assert_eq!(MsgCode::ClientErrorMethodNotAllowed, response.msg_code());
+    }
+}
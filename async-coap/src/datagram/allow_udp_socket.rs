@@ -20,7 +20,7 @@ use std::net::{SocketAddr, SocketAddrV4, SocketAddrV6, UdpSocket};
 use std::ops::Deref;
 use std::pin::Pin;
 use std::sync::Mutex;
-use std::time::{Instant, Duration};
+use std::time::{Duration, Instant};
 
 /// A naive wrapper around [`std::net::UdpSocket`] that implements [`AsyncDatagramSocket`].
 ///
@@ -29,7 +29,7 @@ use std::time::{Instant, Duration};
 ///
 /// This can be used to allow the standard Rust [`std::net::UdpSocket`] (which doesn't provide an
 /// asynchronous API) to be used in an asynchronous fashion, similar in spirit to
-/// [`futures-preview::io::AllowStdio`].
+/// [`futures::io::AllowStdIo`].
 ///
 /// Note that by default this wrapper will block execution whenever one of the `poll` methods is
 /// called (An exception is any instance created with [`AllowStdUdpSocket::bind`]).
@@ -38,8 +38,22 @@ use std::time::{Instant, Duration};
 /// times out waiting for a response), a `futures_timer::Delay` is used to schedule an appropriate
 /// duration (set via `set_async_poll_interval()`) after which it can try again. This is obviously
 /// sub-optimal, but that's the best that can be offered without a real asynchronous event loop.
+///
+/// Datagrams sent with [`AsyncSendTo::poll_send_to_with_traffic_class`] are marked by setting
+/// the `IP_TOS` or `IPV6_TCLASS` socket option whenever the marking changes. This is supported
+/// on Linux, Android, macOS, iOS, and FreeBSD; elsewhere, datagrams are sent unmarked.
+///
+/// On Linux and Android, destinations which the operating system reports as unreachable
+/// are returned by [`AsyncDatagramSocket::take_unreachable`], so that transactions with
+/// them fail right away. Elsewhere, such transactions time out.
 #[derive(Debug)]
-pub struct AllowStdUdpSocket(UdpSocket, Mutex<Option<Delay>>, Option<Duration>);
+pub struct AllowStdUdpSocket(
+    UdpSocket,
+    Mutex<Option<Delay>>,
+    Option<Duration>,
+    Mutex<TrafficClass>,
+    #[cfg(any(target_os = "linux", target_os = "android"))] UnreachableReports,
+);
 
 impl AllowStdUdpSocket {
     /// The default interval between polling attempts.
@@ -49,14 +63,26 @@ impl AllowStdUdpSocket {
 
     /// Upgrades the given [`std::net::UdpSocket`] to an instance of [`AllowStdUdpSocket`].
     ///
-    /// Note that no operations are performed on `udp_socket` by this method. It is recommended
-    /// that you call [`std::net::UdpSocket::set_nonblocking`] on the socket before using this
-    /// method. See the documentation for [`AllowStdUdpSocket`] for more information.
+    /// Note that, other than enabling the reporting of unreachable destinations on Linux
+    /// and Android, no operations are performed on `udp_socket` by this method. It is
+    /// recommended that you call [`std::net::UdpSocket::set_nonblocking`] on the socket
+    /// before using this method. See the documentation for [`AllowStdUdpSocket`] for more
+    /// information.
     pub fn from_std(udp_socket: UdpSocket) -> AllowStdUdpSocket {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            if let Ok(local_addr) = udp_socket.local_addr() {
+                let _ = UnreachableReports::enable(&udp_socket, local_addr);
+            }
+        }
+
         AllowStdUdpSocket(
             udp_socket,
             Mutex::new(None),
             Some(Self::DEFAULT_ASYNC_POLL_INTERVAL),
+            Mutex::new(TrafficClass::DEFAULT),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            UnreachableReports::new(),
         )
     }
 
@@ -96,7 +122,7 @@ impl AllowStdUdpSocket {
                 delay = opt_mut.as_mut().unwrap();
             } else {
                 delay = opt_mut.as_mut().unwrap();
-                delay.reset(Instant::now() + d);
+                delay.reset(Instant::now().saturating_add(d));
             }
 
             let _ = Pin::new(delay).poll(cx);
@@ -106,7 +132,12 @@ impl AllowStdUdpSocket {
 
 impl Unpin for AllowStdUdpSocket {}
 
-impl AsyncDatagramSocket for AllowStdUdpSocket {}
+impl AsyncDatagramSocket for AllowStdUdpSocket {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn take_unreachable(&self) -> Option<Self::SocketAddr> {
+        self.4.take()
+    }
+}
 
 impl DatagramSocketTypes for AllowStdUdpSocket {
     type SocketAddr = std::net::SocketAddr;
@@ -156,10 +187,32 @@ impl AsyncSendTo for AllowStdUdpSocket {
         buf: &[u8],
         addr: B,
     ) -> Poll<Result<usize, Self::Error>>
+    where
+        B: super::ToSocketAddrs<SocketAddr = Self::SocketAddr, Error = Self::Error>,
+    {
+        self.poll_send_to_with_traffic_class(cx, buf, addr, TrafficClass::DEFAULT)
+    }
+
+    fn poll_send_to_with_traffic_class<B>(
+        self: Pin<&Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+        addr: B,
+        traffic_class: TrafficClass,
+    ) -> Poll<Result<usize, Self::Error>>
     where
         B: super::ToSocketAddrs<SocketAddr = Self::SocketAddr, Error = Self::Error>,
     {
         if let Some(addr) = addr.to_socket_addrs()?.next() {
+            // The lock is held while sending, so that the marking can't be changed by
+            // another send in the meantime.
+            let mut current = self.3.lock().expect("Lock failed");
+
+            if *current != traffic_class {
+                set_traffic_class(&self.0, traffic_class)?;
+                *current = traffic_class;
+            }
+
             match self.get_ref().0.send_to(buf, addr) {
                 Ok(written) => Poll::Ready(Ok(written)),
                 Err(e) => {
@@ -180,6 +233,62 @@ impl AsyncSendTo for AllowStdUdpSocket {
     }
 }
 
+/// Marks the datagrams subsequently sent from `socket` with `traffic_class`.
+///
+/// On IPv6 sockets, `IP_TOS` is also set on a best-effort basis, since it is what applies
+/// to datagrams sent to IPv4-mapped addresses.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd"
+))]
+fn set_traffic_class(socket: &UdpSocket, traffic_class: TrafficClass) -> std::io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let setsockopt = |level, name| {
+        let value = libc::c_int::from(traffic_class.octet());
+
+        // SAFETY: `value` outlives the call, and its size is passed along with it.
+        let ret = unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                level,
+                name,
+                &value as *const libc::c_int as *const libc::c_void,
+                std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+
+        if ret == 0 {
+            Ok(())
+        } else {
+            Err(std::io::Error::last_os_error())
+        }
+    };
+
+    match socket.local_addr()? {
+        SocketAddr::V4(_) => setsockopt(libc::IPPROTO_IP, libc::IP_TOS),
+        SocketAddr::V6(_) => {
+            let _ = setsockopt(libc::IPPROTO_IP, libc::IP_TOS);
+            setsockopt(libc::IPPROTO_IPV6, libc::IPV6_TCLASS)
+        }
+    }
+}
+
+/// Marking isn't supported on this platform, so datagrams are always sent unmarked.
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd"
+)))]
+fn set_traffic_class(_socket: &UdpSocket, _traffic_class: TrafficClass) -> std::io::Result<()> {
+    Ok(())
+}
+
 impl AsyncRecvFrom for AllowStdUdpSocket {
     fn poll_recv_from(
         self: Pin<&Self>,
@@ -193,7 +302,12 @@ impl AsyncRecvFrom for AllowStdUdpSocket {
                     self.wait_for_data(cx);
                     Poll::Pending
                 }
-                _ => Poll::Ready(Err(e)),
+                _ => {
+                    #[cfg(any(target_os = "linux", target_os = "android"))]
+                    self.4.read_error_queue(&self.0);
+
+                    Poll::Ready(Err(e))
+                }
             },
         }
     }
@@ -270,3 +384,64 @@ impl MulticastSocket for AllowStdUdpSocket {
         }
     }
 }
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use crate::datagram::{DatagramLocalEndpoint, DatagramRespondableInboundContext};
+    use futures::executor::block_on;
+    use futures::future::{select, Either};
+    use std::os::unix::io::AsRawFd;
+
+    fn ip_tos(socket: &UdpSocket) -> u8 {
+        let mut value: libc::c_int = 0;
+        let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+
+        let ret = unsafe {
+            libc::getsockopt(
+                socket.as_raw_fd(),
+                libc::IPPROTO_IP,
+                libc::IP_TOS,
+                &mut value as *mut libc::c_int as *mut libc::c_void,
+                &mut len,
+            )
+        };
+
+        assert_eq!(0, ret);
+        value as u8
+    }
+
+    #[test]
+    fn dscp() {
+        let socket = AllowStdUdpSocket::bind("127.0.0.1:0").expect("UDP bind failed");
+        let addr = socket.local_addr().unwrap();
+        let local_endpoint = DatagramLocalEndpoint::new(socket);
+        let request_marking = Mutex::new(None);
+
+        let handler = |context: &DatagramRespondableInboundContext<SocketAddr>| {
+            // The request is the most recently sent datagram at this point.
+            *request_marking.lock().unwrap() = Some(ip_tos(local_endpoint.socket()));
+
+            context.respond(|msg_out| {
+                msg_out.set_msg_code(MsgCode::SuccessContent);
+                Ok(())
+            })
+        };
+
+        let send_desc = CoapRequest::get()
+            .dscp(TrafficClass::DSCP_EF)
+            .emit_msg_code();
+        let future = local_endpoint.send(addr, send_desc);
+
+        let ret = match block_on(select(future, local_endpoint.receive_loop(handler))) {
+            Either::Left((ret, _)) => ret,
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+        };
+
+        assert_eq!(Ok(MsgCode::SuccessContent), ret);
+        assert_eq!(Some(0xB8), *request_marking.lock().unwrap());
+
+        // The response isn't marked.
+        assert_eq!(0, ip_tos(local_endpoint.socket()));
+    }
+}
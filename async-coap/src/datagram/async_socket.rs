@@ -20,7 +20,7 @@ use std::pin::Pin;
 
 /// A trait for asynchronous datagram sockets.
 ///
-/// This is an empty convenience trait that requires several additional traits to be implemented:
+/// This is a convenience trait that requires several additional traits to be implemented:
 /// [`DatagramSocketTypes`], [`AsyncSendTo`], [`AsyncRecvFrom`], [`MulticastSocket`],
 /// and [`Send`]+[`Sync`].
 ///
@@ -28,6 +28,38 @@ use std::pin::Pin;
 pub trait AsyncDatagramSocket:
     DatagramSocketTypes + AsyncSendTo + AsyncRecvFrom + MulticastSocket + Send + Sync
 {
+    /// Returns the next remote address that has been reported as unreachable (for example,
+    /// by an ICMP "port unreachable" message read from the socket's error queue), if any.
+    ///
+    /// [`DatagramLocalEndpoint`] calls this method each time [`AsyncRecvFrom::poll_recv_from`]
+    /// completes, and fails any pending transactions with the returned addresses with
+    /// [`Error::HostUnreachable`] instead of waiting for them to time out. Implementations
+    /// that support this should make `poll_recv_from` complete (typically with an error)
+    /// when a new report becomes available.
+    ///
+    /// The default implementation never reports anything. On Linux and Android,
+    /// [`AllowStdUdpSocket`] implements this using [`UnreachableReports`], which other
+    /// backends can use as well.
+    ///
+    /// [`AllowStdUdpSocket`]: super::AllowStdUdpSocket
+    /// [`UnreachableReports`]: super::UnreachableReports
+    fn take_unreachable(&self) -> Option<Self::SocketAddr> {
+        None
+    }
+
+    /// Returns the next path MTU update that the operating system has reported for a
+    /// remote address (for example, after an ICMP "packet too big" message), if any.
+    ///
+    /// The reported size is the largest datagram payload that can be sent to the remote
+    /// address without fragmentation: the path MTU minus the size of the IP and UDP headers.
+    /// [`DatagramLocalEndpoint`] uses these reports the same way as calls to
+    /// [`DatagramLocalEndpoint::set_path_mtu`], and checks for them each time
+    /// [`AsyncRecvFrom::poll_recv_from`] completes and before each message is sent.
+    ///
+    /// The default implementation never reports anything.
+    fn take_path_mtu(&self) -> Option<(Self::SocketAddr, usize)> {
+        None
+    }
 }
 
 /// Trait implemented by a "socket" that describes the underlying `SocketAddr` and socket error
@@ -74,8 +106,40 @@ pub trait AsyncSendTo: DatagramSocketTypes {
     where
         B: super::ToSocketAddrs<SocketAddr = Self::SocketAddr, Error = Self::Error>;
 
+    /// Variant of [`AsyncSendTo::poll_send_to`] which marks the datagram with
+    /// `traffic_class`.
+    ///
+    /// The default implementation ignores `traffic_class`, which is appropriate for sockets
+    /// that aren't able to mark datagrams. Sockets which can should override this method.
+    fn poll_send_to_with_traffic_class<B>(
+        self: Pin<&Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+        addr: B,
+        traffic_class: TrafficClass,
+    ) -> Poll<Result<usize, Self::Error>>
+    where
+        B: super::ToSocketAddrs<SocketAddr = Self::SocketAddr, Error = Self::Error>,
+    {
+        let _ = traffic_class;
+        self.poll_send_to(cx, buf, addr)
+    }
+
     /// Returns a future that uses [`AsyncSendTo::poll_send_to`].
     fn send_to<'a, 'b, B>(&'a self, buf: &'b [u8], addr: B) -> SendToFuture<'a, 'b, Self>
+    where
+        B: super::ToSocketAddrs<SocketAddr = Self::SocketAddr, Error = Self::Error>,
+    {
+        self.send_to_with_traffic_class(buf, addr, TrafficClass::DEFAULT)
+    }
+
+    /// Returns a future that uses [`AsyncSendTo::poll_send_to_with_traffic_class`].
+    fn send_to_with_traffic_class<'a, 'b, B>(
+        &'a self,
+        buf: &'b [u8],
+        addr: B,
+        traffic_class: TrafficClass,
+    ) -> SendToFuture<'a, 'b, Self>
     where
         B: super::ToSocketAddrs<SocketAddr = Self::SocketAddr, Error = Self::Error>,
     {
@@ -84,6 +148,7 @@ pub trait AsyncSendTo: DatagramSocketTypes {
             socket: self,
             buffer: buf,
             addr: addr,
+            traffic_class,
         }
     }
 }
@@ -97,6 +162,7 @@ where
     socket: &'a T,
     buffer: &'b [u8],
     addr: T::SocketAddr,
+    traffic_class: TrafficClass,
 }
 
 impl<'a, 'b, T> SendToFuture<'a, 'b, T>
@@ -107,7 +173,12 @@ where
         self: &mut Self,
         cx: &mut futures::task::Context<'_>,
     ) -> futures::task::Poll<Result<usize, T::Error>> {
-        Pin::new(self.socket).poll_send_to(cx, self.buffer, self.addr.clone())
+        Pin::new(self.socket).poll_send_to_with_traffic_class(
+            cx,
+            self.buffer,
+            self.addr,
+            self.traffic_class,
+        )
     }
 }
 
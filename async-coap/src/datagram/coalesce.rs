@@ -0,0 +1,163 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+use crate::option::OptionIteratorExt;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+
+/// An exchange which can share its responses with identical concurrent requests, or
+/// receive the responses of another exchange instead of sending its own request.
+pub(crate) trait CoalescedExchange<IC: InboundContext>: HandleResponse<IC> {
+    /// Makes `follower` receive the responses to this exchange, returning false if this
+    /// exchange is no longer waiting for a response.
+    fn add_follower(&mut self, follower: WeakCoalescedExchange<IC>) -> bool;
+
+    /// Handles a response (or error) of the exchange this exchange is following.
+    fn handle_coalesced_response(&mut self, context: Result<&IC, Error>);
+
+    /// Called when the exchange this exchange is following ends without a response, so
+    /// that this exchange sends its own request instead.
+    fn restart(&mut self);
+}
+
+/// Weak reference to a [`CoalescedExchange`].
+pub(super) type WeakCoalescedExchange<IC> = Weak<Mutex<dyn CoalescedExchange<IC>>>;
+
+/// How an exchange was coalesced by [`CoalescedExchanges::join`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(super) enum Coalesced {
+    /// Request coalescing is disabled, or the in-flight exchange couldn't be followed.
+    No,
+
+    /// The exchange was registered with the given id, so that identical requests can
+    /// follow it.
+    Leading(u64),
+
+    /// The exchange follows an in-flight exchange of an identical request.
+    Following,
+}
+
+/// The in-flight exchanges that identical requests can be coalesced with, keyed on the
+/// destination and the cache key of the request.
+pub(super) struct CoalescedExchanges<IC: InboundContext> {
+    enabled: bool,
+    next_id: u64,
+    leaders: HashMap<(IC::SocketAddr, CacheKey), (u64, WeakCoalescedExchange<IC>)>,
+}
+
+impl<IC: InboundContext> Default for CoalescedExchanges<IC> {
+    fn default() -> Self {
+        CoalescedExchanges {
+            enabled: false,
+            next_id: 0,
+            leaders: HashMap::new(),
+        }
+    }
+}
+
+impl<IC: InboundContext> std::fmt::Debug for CoalescedExchanges<IC> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CoalescedExchanges")
+            .field("enabled", &self.enabled)
+            .field("leaders", &self.leaders.len())
+            .finish()
+    }
+}
+
+impl<IC: InboundContext> CoalescedExchanges<IC> {
+    pub(super) fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+
+        if !enabled {
+            self.leaders.clear();
+        }
+    }
+
+    /// Makes `exchange` follow the in-flight exchange of an identical request to `dest`, if
+    /// there is one, or else registers `exchange` so that identical requests can follow it.
+    pub(super) fn join<'a>(
+        &mut self,
+        dest: IC::SocketAddr,
+        key: CacheKey,
+        exchange: Arc<Mutex<dyn CoalescedExchange<IC> + 'a>>,
+    ) -> Coalesced {
+        if !self.enabled {
+            return Coalesced::No;
+        }
+
+        // TODO(#3): Eliminate the need for this transmute.
+        //       Same hack as in `UdpResponseTracker::add_response_handler`.
+        let exchange: Arc<Mutex<dyn CoalescedExchange<IC>>> =
+            unsafe { std::mem::transmute(exchange) };
+        let key = (dest, key);
+
+        if let Some(leader) = self
+            .leaders
+            .get(&key)
+            .and_then(|(_, leader)| leader.upgrade())
+        {
+            // The leader takes its own lock before ours, so we must not block on it here.
+            match leader.try_lock() {
+                Ok(mut leader) => {
+                    if leader.add_follower(Arc::downgrade(&exchange)) {
+                        return Coalesced::Following;
+                    }
+                }
+                Err(_) => return Coalesced::No,
+            }
+        }
+
+        self.lead(key, &exchange)
+    }
+
+    fn lead(
+        &mut self,
+        key: (IC::SocketAddr, CacheKey),
+        exchange: &Arc<Mutex<dyn CoalescedExchange<IC>>>,
+    ) -> Coalesced {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        self.leaders.insert(key, (id, Arc::downgrade(exchange)));
+        Coalesced::Leading(id)
+    }
+
+    /// Removes the exchange that [`CoalescedExchanges::join`] registered with `id`.
+    pub(super) fn remove(&mut self, dest: IC::SocketAddr, key: CacheKey, id: u64) {
+        let key = (dest, key);
+
+        if self.leaders.get(&key).map(|(leader_id, _)| *leader_id) == Some(id) {
+            self.leaders.remove(&key);
+        }
+    }
+}
+
+/// Returns the key to coalesce the request `msg` on, or `None` if it must not be
+/// coalesced.
+///
+/// Only `GET` and `FETCH` requests are coalesced, since they are safe. Observations are
+/// never coalesced, since their lifetimes are controlled by the client.
+pub(super) fn coalesce_key(msg: &dyn MessageRead) -> Option<CacheKey> {
+    match msg.msg_code() {
+        MsgCode::MethodGet | MsgCode::MethodFetch => (),
+        _ => return None,
+    }
+
+    if msg.options().find_next_of(option::OBSERVE).is_some() {
+        return None;
+    }
+
+    CacheKey::from_request(msg).ok()
+}
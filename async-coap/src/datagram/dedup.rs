@@ -0,0 +1,89 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+/// The requests received recently by a [`DatagramLocalEndpoint`], for detecting
+/// duplicates as described in [IETF-RFC7252 Section 4.5].
+///
+/// A request is identified by its source and message id, and is remembered for
+/// `EXCHANGE_LIFETIME`, which is the longest time that a sender may retransmit it.
+///
+/// [IETF-RFC7252 Section 4.5]: https://tools.ietf.org/html/rfc7252#section-4.5
+#[derive(Debug)]
+pub(super) struct ReceivedRequests<SA> {
+    counts: HashMap<(SA, MsgId), u32>,
+
+    // The requests in `counts`, in the order in which they were first received.
+    expiry: VecDeque<(Instant, SA, MsgId)>,
+}
+
+impl<SA> Default for ReceivedRequests<SA> {
+    fn default() -> Self {
+        ReceivedRequests {
+            counts: HashMap::new(),
+            expiry: VecDeque::new(),
+        }
+    }
+}
+
+impl<SA: Copy + Eq + Hash> ReceivedRequests<SA> {
+    const LIFETIME: Duration = StandardCoapConstants::COAP_EXCHANGE_LIFETIME;
+
+    /// Records the receipt of the request from `source` with `msg_id` at `now`, returning
+    /// the number of times it was received before.
+    pub(super) fn record(&mut self, source: SA, msg_id: MsgId, now: Instant) -> u32 {
+        while let Some(&(received_at, addr, id)) = self.expiry.front() {
+            if now.saturating_duration_since(received_at) < Self::LIFETIME {
+                break;
+            }
+            self.expiry.pop_front();
+            self.counts.remove(&(addr, id));
+        }
+
+        let count = self.counts.entry((source, msg_id)).or_insert(0);
+        if *count == 0 {
+            self.expiry.push_back((now, source, msg_id));
+        }
+        *count += 1;
+        *count - 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn received_requests() {
+        let mut received = ReceivedRequests::default();
+        let start = Instant::now();
+        let later = start + ReceivedRequests::<u8>::LIFETIME / 2;
+
+        assert_eq!(0, received.record(1u8, 100, start));
+        assert_eq!(1, received.record(1, 100, start));
+        assert_eq!(0, received.record(2, 100, later));
+        assert_eq!(0, received.record(1, 101, later));
+        assert_eq!(2, received.record(1, 100, later));
+
+        // The first request is forgotten once its exchange lifetime is over.
+        let expired = start + ReceivedRequests::<u8>::LIFETIME;
+        assert_eq!(0, received.record(1, 100, expired));
+        assert_eq!(1, received.record(2, 100, expired));
+    }
+}
@@ -0,0 +1,716 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+use futures::task::{Context, Poll};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// The most that a DTLS record can add to the size of the data it protects, which is
+/// used to size the buffer for receiving records.
+const MAX_RECORD_OVERHEAD: usize = 256;
+
+/// The most datagrams that are queued for a peer while a handshake with it is in
+/// progress. The oldest ones are dropped first.
+const MAX_PENDING_DATAGRAMS: usize = 16;
+
+/// The most server sessions whose handshake hasn't completed yet that are kept at once.
+/// Since anyone can send a datagram from a spoofed address, the oldest of them are
+/// discarded to make room for new ones.
+const MAX_HANDSHAKING_SERVER_SESSIONS: usize = 64;
+
+/// Datagrams which are ready to be sent to a peer.
+type Datagrams = Vec<Vec<u8>>;
+
+/// The state of a DTLS session with a single peer, as used by [`DtlsSocket`].
+///
+/// This trait is implemented on top of a DTLS library, which does the actual work of
+/// the handshake and of protecting records. A session only ever deals in whole
+/// datagrams: [`DtlsSocket`] takes care of sending and receiving them.
+pub trait DtlsSession: Send {
+    /// Returns true if the handshake has completed, so that application data can be
+    /// [sent][DtlsSession::send].
+    ///
+    /// This may go back to being false if the session is renegotiated, for example
+    /// to rekey it, until the new handshake has completed.
+    fn is_established(&self) -> bool;
+
+    /// Appends the datagrams that should be sent to the peer to advance the handshake
+    /// to `out`.
+    ///
+    /// This is called when the session is created, and whenever something is sent to the
+    /// peer while the handshake is in progress. Since CoAP retransmits its messages, the
+    /// latter serves to retransmit flights of the handshake that were lost.
+    fn handshake(&mut self, out: &mut Vec<Vec<u8>>) -> Result<(), Error>;
+
+    /// Processes a datagram received from the peer, appending any datagrams that should
+    /// be sent to the peer in reply to `out`, and returns the application data that it
+    /// contained, if any.
+    ///
+    /// Returning an error (for example after a fatal alert) discards the session.
+    fn receive(
+        &mut self,
+        datagram: &[u8],
+        out: &mut Vec<Vec<u8>>,
+    ) -> Result<Option<Vec<u8>>, Error>;
+
+    /// Protects `data` as application data for the peer, returning the datagram to send.
+    ///
+    /// This is only called while the session is [established][DtlsSession::is_established].
+    fn send(&mut self, data: &[u8]) -> Result<Vec<u8>, Error>;
+}
+
+/// Creates the [`DtlsSession`]s used by a [`DtlsSocket`], holding the configuration that
+/// they share, like credentials and any cache for resuming sessions.
+pub trait DtlsContext<SA>: Send + Sync {
+    /// The type of the sessions created by this context.
+    type Session: DtlsSession;
+
+    /// Creates the client side of a new session with `peer`, which is about to be sent
+    /// something. If the session was established before, it can be resumed.
+    fn connect(&self, peer: SA) -> Result<Self::Session, Error>;
+
+    /// Creates the server side of a new session with `peer`, which sent a datagram while
+    /// there was no session with it. Returning an error drops the datagram.
+    fn accept(&self, peer: SA) -> Result<Self::Session, Error>;
+}
+
+#[derive(Debug)]
+struct Peer<S> {
+    session: S,
+
+    /// True if the session was created by [`DtlsContext::connect`].
+    is_client: bool,
+
+    /// Datagrams waiting for the handshake to complete.
+    pending: VecDeque<Vec<u8>>,
+
+    /// When the session was created.
+    created: Instant,
+}
+
+impl<S: DtlsSession> Peer<S> {
+    fn new(session: S, is_client: bool) -> Peer<S> {
+        Peer {
+            session,
+            is_client,
+            pending: VecDeque::new(),
+            created: Instant::now(),
+        }
+    }
+
+    /// Queues `data` until the handshake completes, ignoring retransmissions of data
+    /// that is already queued.
+    fn enqueue(&mut self, data: &[u8]) {
+        if self.pending.iter().any(|x| x.as_slice() == data) {
+            return;
+        }
+
+        if self.pending.len() >= MAX_PENDING_DATAGRAMS {
+            self.pending.pop_front();
+        }
+
+        self.pending.push_back(data.to_vec());
+    }
+
+    /// Protects the queued datagrams, if the session is established.
+    fn flush(&mut self, out: &mut Vec<Vec<u8>>) -> Result<(), Error> {
+        while self.session.is_established() {
+            match self.pending.pop_front() {
+                Some(data) => out.push(self.session.send(&data)?),
+                None => break,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An [`AsyncDatagramSocket`] that protects the datagrams sent over another socket
+/// using DTLS, for use with `coaps:` URIs.
+///
+/// A separate [`DtlsSession`] is kept for each peer. Sessions are created by a
+/// [`DtlsContext`], which is implemented on top of a DTLS library: by
+/// [`DtlsContext::connect`] the first time something is sent to a peer, or by
+/// [`DtlsContext::accept`] the first time something is received from one.
+///
+/// Datagrams which are sent while the handshake with a peer is in progress, either
+/// because the session was just created or because it is being renegotiated, are
+/// queued and sent once the handshake has completed. As far as the
+/// [`DatagramLocalEndpoint`] is concerned, they were sent right away, so requests that
+/// are in flight aren't affected by the handshake, other than being delayed by it. If a
+/// client session fails, it is replaced by a new one from [`DtlsContext::connect`]
+/// (which may resume the previous session), and anything that was still queued is sent
+/// once that has been established.
+///
+/// To keep datagrams from spoofed addresses from using up memory, at most 64 server
+/// sessions are kept while their handshake is in progress. When a datagram arrives from
+/// another new peer, the oldest of them is discarded to make room for it.
+///
+/// DTLS doesn't support multicast, so sending to multicast addresses and joining
+/// multicast groups fails with [`Error::InvalidArgument`].
+///
+/// ```
+/// # use async_coap::prelude::*;
+/// # use async_coap::datagram::{AllowStdUdpSocket, DatagramLocalEndpoint, DtlsContext};
+/// # use async_coap::datagram::DtlsSocket;
+/// # use async_coap::{DEFAULT_PORT_COAP_DTLS, URI_SCHEME_COAPS};
+/// # use std::net::SocketAddr;
+/// # fn run<C: DtlsContext<SocketAddr>>(context: C) -> std::io::Result<()> {
+/// let socket = DtlsSocket::new(AllowStdUdpSocket::bind("[::]:0")?, context);
+///
+/// let local_endpoint = DatagramLocalEndpoint::with_scheme_and_port(
+///     socket,
+///     URI_SCHEME_COAPS,
+///     DEFAULT_PORT_COAP_DTLS,
+/// );
+///
+/// let remote_endpoint = local_endpoint.remote_endpoint_from_uri(uri!("coaps://[::1]/"));
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`DatagramLocalEndpoint`]: crate::datagram::DatagramLocalEndpoint
+pub struct DtlsSocket<US: AsyncDatagramSocket, C: DtlsContext<US::SocketAddr>> {
+    socket: US,
+    context: C,
+    peers: Mutex<HashMap<US::SocketAddr, Peer<C::Session>>>,
+    recv_buffer: Mutex<Vec<u8>>,
+}
+
+impl<US, C> core::fmt::Debug for DtlsSocket<US, C>
+where
+    US: AsyncDatagramSocket + core::fmt::Debug,
+    C: DtlsContext<US::SocketAddr>,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("DtlsSocket")
+            .field("socket", &self.socket)
+            .field("peers", &self.peers.lock().expect("Lock failed").len())
+            .finish()
+    }
+}
+
+impl<US, C> DtlsSocket<US, C>
+where
+    US: AsyncDatagramSocket,
+    US::SocketAddr: Eq + std::hash::Hash,
+    US::Error: From<Error>,
+    C: DtlsContext<US::SocketAddr>,
+{
+    /// Creates a new `DtlsSocket` which sends and receives DTLS records using `socket`,
+    /// with sessions created by `context`.
+    pub fn new(socket: US, context: C) -> DtlsSocket<US, C> {
+        DtlsSocket {
+            socket,
+            context,
+            peers: Mutex::new(HashMap::new()),
+            recv_buffer: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Returns the underlying socket.
+    pub fn socket(&self) -> &US {
+        &self.socket
+    }
+
+    /// Returns the context which creates the sessions of this socket.
+    pub fn context(&self) -> &C {
+        &self.context
+    }
+
+    /// Returns true if there is an established session with `peer`.
+    pub fn is_established(&self, peer: US::SocketAddr) -> bool {
+        self.peers
+            .lock()
+            .expect("Lock failed")
+            .get(&peer)
+            .map(|peer| peer.session.is_established())
+            .unwrap_or(false)
+    }
+
+    /// Discards the session with `peer`, along with anything queued for it. A new session
+    /// is created the next time something is sent to or received from `peer`.
+    pub fn close_session(&self, peer: US::SocketAddr) {
+        self.peers.lock().expect("Lock failed").remove(&peer);
+    }
+
+    /// Prepares sending `data` to `dest`. Returns the handshake messages to send to `dest`,
+    /// along with the record protecting `data` if it isn't queued until the handshake
+    /// completes.
+    fn prepare_send(
+        &self,
+        data: &[u8],
+        dest: US::SocketAddr,
+    ) -> Result<(Datagrams, Option<Vec<u8>>), Error> {
+        let mut peers = self.peers.lock().expect("Lock failed");
+        let mut out = Vec::new();
+
+        let peer = match peers.entry(dest) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(Peer::new(self.context.connect(dest)?, true)),
+        };
+
+        if peer.session.is_established() {
+            return Ok((out, Some(peer.session.send(data)?)));
+        }
+
+        peer.enqueue(data);
+        peer.session.handshake(&mut out)?;
+
+        Ok((out, None))
+    }
+
+    /// Processes `datagram`, which was received from `src`. Returns the datagrams to send
+    /// to `src` in reply, and the application data from `datagram`, if any.
+    fn process_received(
+        &self,
+        datagram: &[u8],
+        src: US::SocketAddr,
+    ) -> Result<(Datagrams, Option<Vec<u8>>), Error> {
+        let mut peers = self.peers.lock().expect("Lock failed");
+        let mut out = Vec::new();
+
+        if !peers.contains_key(&src) {
+            Self::evict_handshaking(&mut peers);
+        }
+
+        let peer = match peers.entry(src) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(Peer::new(self.context.accept(src)?, false)),
+        };
+
+        let data = match peer.session.receive(datagram, &mut out) {
+            Ok(data) => data,
+            Err(e) if peer.is_client => {
+                debug!("DTLS session with {} failed ({}), reconnecting", src, e);
+                peer.session = self.context.connect(src)?;
+                peer.session.handshake(&mut out)?;
+                None
+            }
+            Err(e) => {
+                debug!("DTLS session with {} failed ({})", src, e);
+                peers.remove(&src);
+                return Ok((out, None));
+            }
+        };
+
+        peer.flush(&mut out)?;
+
+        Ok((out, data))
+    }
+
+    /// Makes room for a new server session by discarding the oldest server session whose
+    /// handshake is still in progress, if there are too many of them.
+    fn evict_handshaking(peers: &mut HashMap<US::SocketAddr, Peer<C::Session>>) {
+        let handshaking = peers
+            .iter()
+            .filter(|(_, peer)| !peer.is_client && !peer.session.is_established());
+
+        if handshaking.clone().count() < MAX_HANDSHAKING_SERVER_SESSIONS {
+            return;
+        }
+
+        if let Some(oldest) = handshaking
+            .min_by_key(|(_, peer)| peer.created)
+            .map(|(addr, _)| *addr)
+        {
+            debug!("Discarding unfinished DTLS handshake with {}", oldest);
+            peers.remove(&oldest);
+        }
+    }
+
+    /// Sends `datagrams` to `dest` on a best-effort basis, like the handshake messages
+    /// that are sent while receiving.
+    fn send_all(&self, cx: &mut Context<'_>, datagrams: &[Vec<u8>], dest: US::SocketAddr) {
+        for datagram in datagrams {
+            match Pin::new(&self.socket).poll_send_to(cx, datagram, dest) {
+                Poll::Ready(Ok(_)) => (),
+                Poll::Ready(Err(e)) => debug!("Unable to send DTLS record to {}: {}", dest, e),
+                Poll::Pending => debug!("Unable to send DTLS record to {}: would block", dest),
+            }
+        }
+    }
+}
+
+// Nothing is ever pinned through a `DtlsSocket`.
+impl<US, C> Unpin for DtlsSocket<US, C>
+where
+    US: AsyncDatagramSocket,
+    C: DtlsContext<US::SocketAddr>,
+{
+}
+
+impl<US, C> AsyncDatagramSocket for DtlsSocket<US, C>
+where
+    US: AsyncDatagramSocket,
+    US::SocketAddr: Eq + std::hash::Hash,
+    US::Error: From<Error>,
+    C: DtlsContext<US::SocketAddr>,
+{
+    fn take_unreachable(&self) -> Option<Self::SocketAddr> {
+        self.socket.take_unreachable()
+    }
+}
+
+impl<US, C> DatagramSocketTypes for DtlsSocket<US, C>
+where
+    US: AsyncDatagramSocket,
+    C: DtlsContext<US::SocketAddr>,
+{
+    type SocketAddr = US::SocketAddr;
+    type Error = US::Error;
+
+    fn local_addr(&self) -> Result<Self::SocketAddr, Self::Error> {
+        self.socket.local_addr()
+    }
+
+    fn lookup_host(
+        host: &str,
+        port: u16,
+    ) -> Result<std::vec::IntoIter<Self::SocketAddr>, Self::Error>
+    where
+        Self: Sized,
+    {
+        US::lookup_host(host, port)
+    }
+}
+
+impl<US, C> AsyncSendTo for DtlsSocket<US, C>
+where
+    US: AsyncDatagramSocket,
+    US::SocketAddr: Eq + std::hash::Hash,
+    US::Error: From<Error>,
+    C: DtlsContext<US::SocketAddr>,
+{
+    fn poll_send_to<B>(
+        self: Pin<&Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+        addr: B,
+    ) -> Poll<Result<usize, Self::Error>>
+    where
+        B: super::ToSocketAddrs<SocketAddr = Self::SocketAddr, Error = Self::Error>,
+    {
+        self.poll_send_to_with_traffic_class(cx, buf, addr, TrafficClass::DEFAULT)
+    }
+
+    /// The record protecting `buf` is marked with `traffic_class`. Handshake messages,
+    /// and data which is held back until the handshake completes, are sent unmarked.
+    fn poll_send_to_with_traffic_class<B>(
+        self: Pin<&Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+        addr: B,
+        traffic_class: TrafficClass,
+    ) -> Poll<Result<usize, Self::Error>>
+    where
+        B: super::ToSocketAddrs<SocketAddr = Self::SocketAddr, Error = Self::Error>,
+    {
+        let dest = match addr.to_socket_addrs()?.next() {
+            Some(dest) => dest,
+            None => return Poll::Ready(Err(Error::HostNotFound.into())),
+        };
+
+        if dest.is_multicast() {
+            return Poll::Ready(Err(Error::InvalidArgument.into()));
+        }
+
+        let (handshake, record) = self.prepare_send(buf, dest)?;
+        self.send_all(cx, &handshake, dest);
+
+        match record {
+            Some(record) => {
+                let socket = Pin::new(&self.socket);
+                match socket.poll_send_to_with_traffic_class(cx, &record, dest, traffic_class) {
+                    Poll::Ready(Ok(_)) => Poll::Ready(Ok(buf.len())),
+                    other => other,
+                }
+            }
+
+            // The data will be sent once the handshake completes.
+            None => Poll::Ready(Ok(buf.len())),
+        }
+    }
+}
+
+impl<US, C> AsyncRecvFrom for DtlsSocket<US, C>
+where
+    US: AsyncDatagramSocket,
+    US::SocketAddr: Eq + std::hash::Hash,
+    US::Error: From<Error>,
+    C: DtlsContext<US::SocketAddr>,
+{
+    fn poll_recv_from(
+        self: Pin<&Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<(usize, Self::SocketAddr, Option<Self::SocketAddr>), Self::Error>> {
+        let mut datagram = self.recv_buffer.lock().expect("Lock failed");
+        datagram.resize(buf.len() + MAX_RECORD_OVERHEAD, 0);
+
+        // Datagrams which don't contain application data, like handshake messages, are
+        // handled here rather than being passed along.
+        loop {
+            let (len, src, local) = match Pin::new(&self.socket).poll_recv_from(cx, &mut datagram) {
+                Poll::Ready(Ok(x)) => x,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            };
+
+            let (out, data) = match self.process_received(&datagram[..len], src) {
+                Ok(x) => x,
+                Err(e) => {
+                    debug!("Dropping DTLS record from {}: {}", src, e);
+                    continue;
+                }
+            };
+
+            self.send_all(cx, &out, src);
+
+            if let Some(data) = data {
+                if data.len() > buf.len() {
+                    debug!("Dropping oversized datagram from {}", src);
+                    continue;
+                }
+
+                buf[..data.len()].copy_from_slice(&data);
+                return Poll::Ready(Ok((data.len(), src, local)));
+            }
+        }
+    }
+}
+
+impl<US, C> MulticastSocket for DtlsSocket<US, C>
+where
+    US: AsyncDatagramSocket,
+    US::Error: From<Error>,
+    C: DtlsContext<US::SocketAddr>,
+{
+    type IpAddr = US::IpAddr;
+
+    fn join_multicast<A>(&self, _addr: A) -> Result<(), Self::Error>
+    where
+        A: std::convert::Into<Self::IpAddr>,
+    {
+        Err(Error::InvalidArgument.into())
+    }
+
+    fn leave_multicast<A>(&self, _addr: A) -> Result<(), Self::Error>
+    where
+        A: std::convert::Into<Self::IpAddr>,
+    {
+        Err(Error::InvalidArgument.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datagram::{DatagramLocalEndpoint, DatagramRespondableInboundContext};
+    use crate::datagram::{SmsNetwork, SmsSocket, SmsSocketAddr};
+    use futures::executor::block_on;
+    use futures::future::{select, Either};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    const HANDSHAKE: u8 = 22;
+    const APPLICATION_DATA: u8 = 23;
+
+    /// A stand-in for a real DTLS session, with a handshake of two messages and
+    /// application data that is merely obfuscated.
+    #[derive(Debug)]
+    struct TestSession {
+        is_client: bool,
+        established: bool,
+    }
+
+    impl DtlsSession for TestSession {
+        fn is_established(&self) -> bool {
+            self.established
+        }
+
+        fn handshake(&mut self, out: &mut Vec<Vec<u8>>) -> Result<(), Error> {
+            if self.is_client {
+                out.push(vec![HANDSHAKE, 1]);
+            }
+            Ok(())
+        }
+
+        fn receive(
+            &mut self,
+            datagram: &[u8],
+            out: &mut Vec<Vec<u8>>,
+        ) -> Result<Option<Vec<u8>>, Error> {
+            match datagram {
+                // Like a `ClientHello` which is answered with a `HelloVerifyRequest`.
+                [HANDSHAKE, 0] if !self.is_client => Ok(None),
+                [HANDSHAKE, 1] if !self.is_client => {
+                    self.established = true;
+                    out.push(vec![HANDSHAKE, 2]);
+                    Ok(None)
+                }
+                [HANDSHAKE, 2] if self.is_client => {
+                    self.established = true;
+                    Ok(None)
+                }
+                [APPLICATION_DATA, data @ ..] if self.established => {
+                    Ok(Some(data.iter().map(|b| b ^ 0x5A).collect()))
+                }
+                _ => Err(Error::ParseFailure),
+            }
+        }
+
+        fn send(&mut self, data: &[u8]) -> Result<Vec<u8>, Error> {
+            assert!(self.established);
+            Ok(std::iter::once(APPLICATION_DATA)
+                .chain(data.iter().map(|b| b ^ 0x5A))
+                .collect())
+        }
+    }
+
+    #[derive(Debug, Default, Clone)]
+    struct TestContext {
+        connects: Arc<AtomicUsize>,
+        accepts: Arc<AtomicUsize>,
+    }
+
+    impl DtlsContext<SmsSocketAddr> for TestContext {
+        type Session = TestSession;
+
+        fn connect(&self, _peer: SmsSocketAddr) -> Result<TestSession, Error> {
+            self.connects.fetch_add(1, Ordering::Relaxed);
+            Ok(TestSession {
+                is_client: true,
+                established: false,
+            })
+        }
+
+        fn accept(&self, _peer: SmsSocketAddr) -> Result<TestSession, Error> {
+            self.accepts.fetch_add(1, Ordering::Relaxed);
+            Ok(TestSession {
+                is_client: false,
+                established: false,
+            })
+        }
+    }
+
+    type Endpoint = DatagramLocalEndpoint<DtlsSocket<SmsSocket, TestContext>>;
+
+    fn get(client: &Endpoint, server: &Endpoint, dest: SmsSocketAddr) -> Result<MsgCode, Error> {
+        let handler = |context: &DatagramRespondableInboundContext<SmsSocketAddr>| {
+            context.respond(|msg_out| {
+                msg_out.set_msg_code(MsgCode::SuccessContent);
+                Ok(())
+            })
+        };
+
+        let future = client.send(dest, CoapRequest::get().emit_msg_code());
+        let receive_loops = select(
+            server.receive_loop(handler),
+            client.receive_loop(null_receiver!()),
+        );
+
+        match block_on(select(future, receive_loops)) {
+            Either::Left((ret, _)) => ret,
+            Either::Right(_) => panic!("Receive loop finished unexpectedly"),
+        }
+    }
+
+    #[test]
+    fn dtls_socket() {
+        let network = SmsNetwork::new();
+        let server_addr: SmsSocketAddr = "+15551234567".parse().unwrap();
+        let client_addr: SmsSocketAddr = "+15557654321".parse().unwrap();
+
+        let server_context = TestContext::default();
+        let server = DatagramLocalEndpoint::with_scheme_and_port(
+            DtlsSocket::new(network.attach(server_addr).unwrap(), server_context.clone()),
+            URI_SCHEME_COAPS,
+            0,
+        );
+
+        let client_context = TestContext::default();
+        let client = DatagramLocalEndpoint::with_scheme_and_port(
+            DtlsSocket::new(network.attach(client_addr).unwrap(), client_context.clone()),
+            URI_SCHEME_COAPS,
+            0,
+        );
+
+        // The request is held back until the handshake has completed.
+        assert_eq!(
+            Ok(MsgCode::SuccessContent),
+            get(&client, &server, server_addr)
+        );
+        assert!(client.socket().is_established(server_addr));
+        assert!(server.socket().is_established(client_addr));
+        assert_eq!(1, client_context.connects.load(Ordering::Relaxed));
+        assert_eq!(1, server_context.accepts.load(Ordering::Relaxed));
+
+        // Established sessions are reused.
+        assert_eq!(
+            Ok(MsgCode::SuccessContent),
+            get(&client, &server, server_addr)
+        );
+        assert_eq!(1, client_context.connects.load(Ordering::Relaxed));
+
+        // A new session is negotiated with a peer that still has the old one.
+        client.socket().close_session(server_addr);
+        assert!(!client.socket().is_established(server_addr));
+        assert_eq!(
+            Ok(MsgCode::SuccessContent),
+            get(&client, &server, server_addr)
+        );
+        assert_eq!(2, client_context.connects.load(Ordering::Relaxed));
+        assert_eq!(1, server_context.accepts.load(Ordering::Relaxed));
+
+        // Multicast isn't supported.
+        assert_eq!(
+            Err(Error::InvalidArgument),
+            client.socket().join_multicast("ff02::fd".to_string())
+        );
+    }
+
+    #[test]
+    fn handshaking_sessions_are_bounded() {
+        let network = SmsNetwork::new();
+        let server_addr: SmsSocketAddr = "+15551234567".parse().unwrap();
+        let socket = DtlsSocket::new(network.attach(server_addr).unwrap(), TestContext::default());
+        let peer_addr =
+            |i: usize| -> SmsSocketAddr { format!("+1555000{:04}", i).parse().unwrap() };
+
+        let (out, data) = socket
+            .process_received(&[HANDSHAKE, 1], peer_addr(0))
+            .unwrap();
+        assert_eq!((vec![vec![HANDSHAKE, 2]], None), (out, data));
+        assert!(socket.is_established(peer_addr(0)));
+
+        for i in 1..=MAX_HANDSHAKING_SERVER_SESSIONS * 2 {
+            socket
+                .process_received(&[HANDSHAKE, 0], peer_addr(i))
+                .unwrap();
+        }
+
+        let peers = socket.peers.lock().unwrap();
+        assert_eq!(MAX_HANDSHAKING_SERVER_SESSIONS + 1, peers.len());
+
+        // The established session and the newest handshakes are kept.
+        assert!(peers.contains_key(&peer_addr(0)));
+        assert!(!peers.contains_key(&peer_addr(1)));
+        assert!(peers.contains_key(&peer_addr(MAX_HANDSHAKING_SERVER_SESSIONS * 2)));
+    }
+}
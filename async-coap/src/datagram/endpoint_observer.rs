@@ -0,0 +1,67 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+use crate::message::StandardMessageParser;
+
+/// Callbacks for significant events in the life of a [`DatagramLocalEndpoint`], set with
+/// [`DatagramLocalEndpoint::set_endpoint_observer`].
+///
+/// This gives applications a single place to keep their own state in sync with the
+/// local endpoint, or to log or display what it is doing. Every method has an empty
+/// default implementation, so implementations only need to provide the callbacks they
+/// are interested in.
+///
+/// The callbacks are called synchronously from the task that is sending or receiving, so
+/// they should return quickly.
+pub trait EndpointObserver<SA>: Send + Sync {
+    /// Called the first time a datagram is received from `addr` after the observer was
+    /// set.
+    fn peer_added(&self, _addr: SA) {}
+
+    /// Called when a response is sent which registers `addr` as an observer of a
+    /// resource, as described in [IETF-RFC7641 Section 4.1]. `token` is the token that
+    /// notifications will be sent with.
+    ///
+    /// [IETF-RFC7641 Section 4.1]: https://tools.ietf.org/html/rfc7641#section-4.1
+    fn observation_created(&self, _addr: SA, _token: MsgToken) {}
+
+    /// Called when an outbound transaction with `dest` fails, with the same details that
+    /// [`DatagramLocalEndpoint::send_with_failure_details`] would return.
+    fn transaction_failed(&self, _dest: SA, _failure: &TransactionFailure) {}
+}
+
+impl<SA> std::fmt::Debug for dyn EndpointObserver<SA> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EndpointObserver")
+    }
+}
+
+/// Returns true if `request` asks to register its sender as an observer.
+pub(super) fn is_observe_registration(request: &dyn MessageRead) -> bool {
+    request.msg_code() == MsgCode::MethodGet
+        && request.options().find_next_of(option::OBSERVE) == Some(Ok(OBSERVE_REGISTER))
+}
+
+/// Returns true if `response`, sent in reply to a registration, accepts it.
+pub(super) fn is_observe_accepted(response: &[u8]) -> bool {
+    match StandardMessageParser::new(response) {
+        Ok(response) => {
+            response.msg_code().is_success()
+                && response.options().find_next_of(option::OBSERVE).is_some()
+        }
+        Err(_) => false,
+    }
+}
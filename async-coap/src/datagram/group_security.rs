@@ -0,0 +1,65 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+use std::sync::Arc;
+
+/// Security context for a group of endpoints which communicate using multicast, such as
+/// a [Group OSCORE] security context.
+///
+/// This crate doesn't implement Group OSCORE itself: this trait is the hook that allows
+/// implementations to protect the messages sent and received by a
+/// [`DatagramLocalEndpoint`] without having to fork it. Contexts are registered using
+/// [`DatagramLocalEndpoint::add_group_security_context`].
+///
+/// Only messages sent to a multicast address, and messages received on a multicast
+/// address, are passed through the context for that group. Unicast messages (including
+/// responses to group requests) are not affected.
+///
+/// [Group OSCORE]: https://tools.ietf.org/html/draft-ietf-core-oscore-groupcomm
+pub trait GroupSecurityContext<SA>: Send + Sync {
+    /// Returns true if this context protects communication with the multicast address
+    /// `group`.
+    fn is_for_group(&self, group: SA) -> bool;
+
+    /// Protects `message`, an encoded CoAP message about to be sent to `group`, returning
+    /// the bytes that are to be sent instead.
+    ///
+    /// If this fails, the message isn't sent and the send future fails with the returned
+    /// error.
+    fn protect(&self, group: SA, message: &[u8]) -> Result<Vec<u8>, Error>;
+
+    /// Verifies `message`, which was received from `source` on `group`, returning the
+    /// encoded CoAP message that it protects.
+    ///
+    /// If this fails, the datagram is dropped and [`LocalEndpoint::receive`] returns the
+    /// error.
+    fn verify(&self, group: SA, source: SA, message: &[u8]) -> Result<Vec<u8>, Error>;
+}
+
+/// The group security contexts registered with a [`DatagramLocalEndpoint`].
+pub(super) struct GroupSecurityContexts<SA>(pub(super) Vec<Arc<dyn GroupSecurityContext<SA>>>);
+
+impl<SA> Default for GroupSecurityContexts<SA> {
+    fn default() -> Self {
+        GroupSecurityContexts(Vec::new())
+    }
+}
+
+impl<SA> std::fmt::Debug for GroupSecurityContexts<SA> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "GroupSecurityContexts({})", self.0.len())
+    }
+}
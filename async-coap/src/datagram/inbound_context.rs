@@ -26,6 +26,8 @@ where
     message_out: Cell<Option<VecMessageEncoder>>,
     remote: SA,
     is_multicast: bool,
+    pub(super) dupe_count: u32,
+    pub(super) max_response_len: usize,
 }
 
 impl<SA> core::fmt::Debug for DatagramRespondableInboundContext<SA>
@@ -39,6 +41,8 @@ where
             .field("message_out", &"")
             .field("remote", &self.remote)
             .field("is_multicast", &self.is_multicast)
+            .field("dupe_count", &self.dupe_count)
+            .field("max_response_len", &self.max_response_len)
             .finish()
     }
 }
@@ -57,9 +61,16 @@ impl<SA: SocketAddrExt> DatagramRespondableInboundContext<SA> {
             message_out: Cell::new(Default::default()),
             remote,
             is_multicast,
+            dupe_count: 0,
+            max_response_len: StandardCoapConstants::MAX_OUTBOUND_PACKET_LENGTH,
         })
     }
 
+    /// The raw bytes of the received datagram.
+    pub fn raw_bytes(&self) -> &[u8] {
+        self.message.as_bytes()
+    }
+
     pub(super) fn into_message_out(self) -> Option<VecMessageEncoder> {
         self.message_out.take()
     }
@@ -81,6 +92,7 @@ impl<UA: SocketAddrExt> RespondableInboundContext for DatagramRespondableInbound
     {
         let mut builder = VecMessageEncoder::new();
 
+        builder.set_max_len(self.max_response_len);
         builder.set_msg_type(MsgType::Ack);
         builder.set_msg_token(self.message().msg_token());
 
@@ -101,12 +113,287 @@ impl<SA: SocketAddrExt> InboundContext for DatagramRespondableInboundContext<SA>
         self.remote
     }
 
+    /// Returns true if this request was received before. Since the datagram local endpoint
+    /// doesn't store the responses it sends, duplicate requests are passed to the handler
+    /// again, which must respond without repeating any side effects.
     fn is_dupe(&self) -> bool {
-        // TODO: Determine how best to handle `is_dupe()` on the Datagram local endpoint.
-        false
+        self.dupe_count > 0
+    }
+
+    fn dupe_count(&self) -> u32 {
+        self.dupe_count
     }
 
     fn message(&self) -> &dyn MessageRead {
         &self.message
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::StandardMessageParser;
+
+    fn context_for(msg_type: MsgType) -> DatagramRespondableInboundContext<LoopbackSocketAddr> {
+        let mut request = VecMessageEncoder::new();
+        request.set_msg_type(msg_type);
+        request.set_msg_id(0x1234);
+        request.set_msg_token(MsgToken::from(0xABCDu32));
+        request.set_msg_code(MsgCode::MethodGet);
+        request.insert_option(option::URI_PATH, "test").unwrap();
+
+        DatagramRespondableInboundContext::new(request.into(), LoopbackSocketAddr::Unicast, false)
+            .unwrap()
+    }
+
+    fn empty_reply(msg_out: &VecMessageEncoder) -> (MsgType, MsgCode, u16, MsgToken) {
+        let msg = StandardMessageParser::new(msg_out.as_bytes()).unwrap();
+        assert_eq!(msg.options().count(), 0);
+        assert!(msg.payload().is_empty());
+        (
+            msg.msg_type(),
+            msg.msg_code(),
+            msg.msg_id(),
+            msg.msg_token(),
+        )
+    }
+
+    #[test]
+    fn send_reset() {
+        let context = context_for(MsgType::Con);
+        context
+            .respond(|msg_out| {
+                msg_out.set_msg_code(MsgCode::SuccessContent);
+                msg_out.append_payload_string("replaced")
+            })
+            .unwrap();
+        context.send_reset().unwrap();
+
+        let msg_out = context.into_message_out().unwrap();
+        assert_eq!(
+            empty_reply(&msg_out),
+            (MsgType::Res, MsgCode::Empty, 0x1234, MsgToken::EMPTY)
+        );
+    }
+
+    #[test]
+    fn acknowledge() {
+        let context = context_for(MsgType::Con);
+        context.acknowledge().unwrap();
+
+        let msg_out = context.into_message_out().unwrap();
+        assert_eq!(
+            empty_reply(&msg_out),
+            (MsgType::Ack, MsgCode::Empty, 0x1234, MsgToken::EMPTY)
+        );
+
+        let context = context_for(MsgType::Non);
+        assert_eq!(context.acknowledge(), Err(Error::InvalidArgument));
+        assert!(context.into_message_out().is_none());
+    }
+
+    fn error_reply(msg_out: &VecMessageEncoder) -> (MsgCode, usize, String) {
+        let msg = StandardMessageParser::new(msg_out.as_bytes()).unwrap();
+        (
+            msg.msg_code(),
+            msg.options().count(),
+            msg.payload_as_str().unwrap().to_string(),
+        )
+    }
+
+    #[test]
+    fn respond_error() {
+        let context = context_for(MsgType::Con);
+        context
+            .respond_error(MsgCode::ServerErrorInternalServerError, "Out of memory")
+            .unwrap();
+        assert_eq!(
+            error_reply(&context.into_message_out().unwrap()),
+            (
+                MsgCode::ServerErrorInternalServerError,
+                0,
+                "Out of memory".to_string()
+            )
+        );
+
+        let context = context_for(MsgType::Con);
+        assert_eq!(
+            context.respond_error(MsgCode::SuccessContent, "Not an error"),
+            Err(Error::InvalidArgument)
+        );
+        assert!(context.into_message_out().is_none());
+
+        let context = context_for(MsgType::Con);
+        context.respond_not_found().unwrap();
+        assert_eq!(
+            error_reply(&context.into_message_out().unwrap()),
+            (MsgCode::ClientErrorNotFound, 0, String::new())
+        );
+
+        let context = context_for(MsgType::Con);
+        context
+            .respond_method_not_allowed(&[MsgCode::MethodGet, MsgCode::Empty, MsgCode::MethodPut])
+            .unwrap();
+        assert_eq!(
+            error_reply(&context.into_message_out().unwrap()),
+            (
+                MsgCode::ClientErrorMethodNotAllowed,
+                0,
+                "Allow: GET, PUT".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn respond_with_etag() {
+        let content = |msg_out: &mut dyn MessageWrite| {
+            msg_out.set_msg_code(MsgCode::SuccessContent);
+            msg_out.append_payload_string("22.3 C")
+        };
+        let etag = ETag::new(&[1, 2, 3]);
+
+        let context_with_etags = |etags: &[ETag]| {
+            let mut request = VecMessageEncoder::new();
+            request.set_msg_type(MsgType::Con);
+            request.set_msg_code(MsgCode::MethodGet);
+            for etag in etags {
+                request.insert_option(option::ETAG, *etag).unwrap();
+            }
+            request.insert_option(option::URI_PATH, "test").unwrap();
+
+            DatagramRespondableInboundContext::new(
+                request.into(),
+                LoopbackSocketAddr::Unicast,
+                false,
+            )
+            .unwrap()
+        };
+
+        let reply = |context: DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+            let msg_out = context.into_message_out().unwrap();
+            let msg = StandardMessageParser::new(msg_out.as_bytes()).unwrap();
+            (
+                msg.msg_code(),
+                msg.options().find_next_of(option::ETAG),
+                msg.payload_as_str().unwrap().to_string(),
+            )
+        };
+
+        let context = context_with_etags(&[]);
+        context.respond_with_etag(content, etag).unwrap();
+        assert_eq!(
+            reply(context),
+            (
+                MsgCode::SuccessContent,
+                Some(Ok(etag)),
+                "22.3 C".to_string()
+            )
+        );
+
+        let context = context_with_etags(&[ETag::new(&[4]), ETag::new(&[5])]);
+        context.respond_with_etag(content, etag).unwrap();
+        assert_eq!(
+            reply(context),
+            (
+                MsgCode::SuccessContent,
+                Some(Ok(etag)),
+                "22.3 C".to_string()
+            )
+        );
+
+        let context = context_with_etags(&[ETag::new(&[4]), etag]);
+        context.respond_with_etag(content, etag).unwrap();
+        assert_eq!(
+            reply(context),
+            (MsgCode::SuccessValid, Some(Ok(etag)), String::new())
+        );
+    }
+
+    #[test]
+    fn respond_with_payload_etag() {
+        let content = |msg_out: &mut dyn MessageWrite| {
+            msg_out.set_msg_code(MsgCode::SuccessContent);
+            msg_out.insert_option(option::MAX_AGE, 30)?;
+            msg_out.insert_option(option::CONTENT_FORMAT, ContentFormat::TEXT_PLAIN_UTF8)?;
+            msg_out.append_payload_string("22.3 C")
+        };
+        let etag = ETag::from_hash(b"22.3 C");
+
+        let context_with_etag = |etag: Option<ETag>| {
+            let mut request = VecMessageEncoder::new();
+            request.set_msg_type(MsgType::Con);
+            request.set_msg_code(MsgCode::MethodGet);
+            if let Some(etag) = etag {
+                request.insert_option(option::ETAG, etag).unwrap();
+            }
+
+            DatagramRespondableInboundContext::new(
+                request.into(),
+                LoopbackSocketAddr::Unicast,
+                false,
+            )
+            .unwrap()
+        };
+
+        let reply = |context: DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+            let msg_out = context.into_message_out().unwrap();
+            let msg = StandardMessageParser::new(msg_out.as_bytes()).unwrap();
+            (
+                msg.msg_code(),
+                msg.options()
+                    .map(|option| option.unwrap().0)
+                    .collect::<Vec<_>>(),
+                msg.options().find_next_of(option::ETAG),
+                msg.payload_as_str().unwrap().to_string(),
+            )
+        };
+
+        let context = context_with_etag(None);
+        context.respond_with_payload_etag(content).unwrap();
+        assert_eq!(
+            reply(context),
+            (
+                MsgCode::SuccessContent,
+                vec![
+                    OptionNumber::ETAG,
+                    OptionNumber::CONTENT_FORMAT,
+                    OptionNumber::MAX_AGE
+                ],
+                Some(Ok(etag)),
+                "22.3 C".to_string()
+            )
+        );
+
+        // `Max-Age` refreshes the client's cached response, but `Content-Format`
+        // describes the payload, which isn't sent again.
+        let context = context_with_etag(Some(etag));
+        context.respond_with_payload_etag(content).unwrap();
+        assert_eq!(
+            reply(context),
+            (
+                MsgCode::SuccessValid,
+                vec![OptionNumber::ETAG, OptionNumber::MAX_AGE],
+                Some(Ok(etag)),
+                String::new()
+            )
+        );
+
+        // Errors don't get an ETag.
+        let context = context_with_etag(None);
+        context
+            .respond_with_payload_etag(|msg_out| {
+                msg_out.set_msg_code(MsgCode::ClientErrorNotFound);
+                msg_out.append_payload_string("gone")
+            })
+            .unwrap();
+        assert_eq!(
+            reply(context),
+            (
+                MsgCode::ClientErrorNotFound,
+                vec![],
+                None,
+                "gone".to_string()
+            )
+        );
+    }
+}
@@ -0,0 +1,65 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+
+/// What a [`DatagramLocalEndpoint`] should do with a received datagram after passing it to
+/// an [`InboundHook`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum InboundDisposition {
+    /// The datagram, including any changes made by the hook, is parsed and handled
+    /// normally.
+    Continue,
+
+    /// The hook has claimed the datagram, so the local endpoint ignores it.
+    Claimed,
+}
+
+/// Hook which is given every datagram that a [`DatagramLocalEndpoint`] receives, before it
+/// is parsed, set with [`DatagramLocalEndpoint::set_inbound_hook`].
+///
+/// The hook can claim a datagram for an alternative processor, such as an OSCORE layer or
+/// an experimental transport, change it in place before it is parsed, or reject it. This
+/// is the inbound counterpart of [`OutboundHook`], allowing layered extensions without
+/// having to fork the local endpoint. Closures with the same signature as
+/// [`InboundHook::inbound`] implement this trait.
+///
+/// The hook is called right after the datagram is read from the socket: before it has been
+/// verified with a [`GroupSecurityContext`], and before any duplicate detection. Claimed
+/// datagrams are still counted in the [endpoint statistics](DatagramLocalEndpoint::stats),
+/// but are otherwise invisible to the local endpoint. The hook is called synchronously
+/// from the task that is receiving, so it should return quickly.
+pub trait InboundHook<SA>: Send + Sync {
+    /// Called with `message`, a datagram received from `source`.
+    ///
+    /// If this fails, the datagram is dropped and the error is returned from
+    /// [`receive`](LocalEndpoint::receive).
+    fn inbound(&self, source: SA, message: &mut Vec<u8>) -> Result<InboundDisposition, Error>;
+}
+
+impl<SA, F> InboundHook<SA> for F
+where
+    F: Fn(SA, &mut Vec<u8>) -> Result<InboundDisposition, Error> + Send + Sync,
+{
+    fn inbound(&self, source: SA, message: &mut Vec<u8>) -> Result<InboundDisposition, Error> {
+        self(source, message)
+    }
+}
+
+impl<SA> std::fmt::Debug for dyn InboundHook<SA> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("InboundHook")
+    }
+}
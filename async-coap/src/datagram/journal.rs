@@ -0,0 +1,121 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+use crate::message::{MessageWrite, OwnedImmutableMessage};
+use std::ops::{Bound, RangeBounds};
+
+/// A confirmable request recorded in a [`TransactionJournal`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct JournalEntry<SA> {
+    /// The remote address the request was sent to.
+    pub dest: SA,
+
+    /// The request, as it was encoded before being protected by a [`GroupSecurityContext`]
+    /// or passed to the [`OutboundHook`]. It can be persisted using
+    /// [`OwnedImmutableMessage::as_bytes`] and restored with [`OwnedImmutableMessage::new`].
+    pub message: OwnedImmutableMessage,
+}
+
+impl<SA> JournalEntry<SA> {
+    /// Returns the token of the recorded request, which identifies the transaction
+    /// together with [`JournalEntry::dest`].
+    pub fn msg_token(&self) -> MsgToken {
+        self.message.msg_token()
+    }
+}
+
+/// Journal of outbound confirmable requests, set with
+/// [`DatagramLocalEndpoint::set_transaction_journal`], for applications like gateways that
+/// need at-least-once delivery of important requests across crashes and restarts.
+///
+/// Every confirmable request is [recorded](TransactionJournal::record) right before it is
+/// first transmitted, and the transaction is [finished](TransactionJournal::finished) once
+/// its send future completes. An implementation would typically write entries to
+/// persistent storage and remove them when they are finished successfully. After a
+/// restart, the entries that are left over can be re-issued with their original tokens
+/// using [`DatagramLocalEndpoint::reissue`].
+///
+/// Since a re-issued request has a new message id, the remote endpoint can't detect it as
+/// a duplicate, so it may be processed more than once. If a send future is dropped before
+/// it completes, its transaction is never finished. The callbacks are called
+/// synchronously from the task that is sending, so they should return quickly.
+pub trait TransactionJournal<SA>: Send + Sync {
+    /// Called when a confirmable request is transmitted.
+    ///
+    /// Requests which are transmitted more than once with the same token, such as the
+    /// blocks of a block-wise transfer, are recorded each time, and each entry should replace
+    /// the previous one for the same destination and token. Retransmissions of the same
+    /// message aren't recorded.
+    fn record(&self, entry: JournalEntry<SA>);
+
+    /// Called when the send future for the transaction identified by `dest` and `msg_token`
+    /// has completed with `result`.
+    fn finished(&self, dest: SA, msg_token: MsgToken, result: Result<(), Error>);
+}
+
+impl<SA> std::fmt::Debug for dyn TransactionJournal<SA> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TransactionJournal")
+    }
+}
+
+/// Send descriptor which sends a copy of a journaled request, created by
+/// [`DatagramLocalEndpoint::reissue`].
+#[derive(Debug)]
+pub(super) struct Reissue(pub(super) OwnedImmutableMessage);
+
+impl SendDescUnicast for Reissue {}
+
+impl<IC: InboundContext> SendDesc<IC, OwnedImmutableMessage> for Reissue {
+    fn write_options(
+        &self,
+        msg: &mut dyn OptionInsert,
+        _socket_addr: &IC::SocketAddr,
+        start: Bound<OptionNumber>,
+        end: Bound<OptionNumber>,
+    ) -> Result<(), Error> {
+        let range = (start, end);
+
+        for result in self.0.options() {
+            let (number, value) = result?;
+            if range.contains(&number) {
+                msg.insert_option_with_bytes(number, value)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_payload(
+        &self,
+        msg: &mut dyn MessageWrite,
+        _socket_addr: &IC::SocketAddr,
+    ) -> Result<(), Error> {
+        msg.set_msg_code(self.0.msg_code());
+        msg.append_payload_bytes(self.0.payload())
+    }
+
+    fn payload_size_hint(&self) -> usize {
+        self.0.payload().len()
+    }
+
+    fn handler(
+        &mut self,
+        context: Result<&IC, Error>,
+    ) -> Result<ResponseStatus<OwnedImmutableMessage>, Error> {
+        Ok(ResponseStatus::Done(context?.message().to_owned()))
+    }
+}
@@ -16,8 +16,13 @@
 use super::*;
 use crate::message::BufferMessageEncoder;
 use crate::message::CoapByteDisplayFormatter;
-use std::sync::atomic::Ordering;
+use crate::message::OwnedImmutableMessage;
+use crate::option::OptionRegistry;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 /// Generic, datagram-based CoAP local endpoint implementation.
 #[derive(Debug)]
@@ -32,9 +37,28 @@ where
 pub(crate) struct DatagramLocalEndpointInner<US: AsyncDatagramSocket> {
     socket: US,
     next_msg_id: std::sync::atomic::AtomicU16,
+    max_inbound_message_size: AtomicUsize,
+    max_outbound_message_size: AtomicUsize,
     response_tracker: Mutex<UdpResponseTracker<DatagramInboundContext<US::SocketAddr>>>,
     scheme: &'static str,
     default_port: u16,
+    stats: StatCounters,
+    path_mtus: Mutex<HashMap<US::SocketAddr, usize>>,
+    parse_error_handler: Mutex<Option<ParseErrorHandler<US::SocketAddr>>>,
+    version_policy: Mutex<VersionPolicy>,
+    group_security: Mutex<GroupSecurityContexts<US::SocketAddr>>,
+    random_source: Mutex<Arc<dyn RandomSource>>,
+    rtt_estimates: Mutex<HashMap<US::SocketAddr, RttEstimate>>,
+    adaptive_ack_timeout: Mutex<Option<AdaptiveAckTimeout>>,
+    coalesced_exchanges: Mutex<CoalescedExchanges<DatagramInboundContext<US::SocketAddr>>>,
+    option_registry: Mutex<Arc<OptionRegistry>>,
+    endpoint_observer: Mutex<Option<Arc<dyn EndpointObserver<US::SocketAddr>>>>,
+    inbound_hook: Mutex<Option<Arc<dyn InboundHook<US::SocketAddr>>>>,
+    transaction_journal: Mutex<Option<Arc<dyn TransactionJournal<US::SocketAddr>>>>,
+    outbound_hook: Mutex<Option<Arc<dyn OutboundHook<US::SocketAddr>>>>,
+    received_requests: Mutex<ReceivedRequests<US::SocketAddr>>,
+    known_peers: Mutex<HashSet<US::SocketAddr>>,
+    shutdown: ShutdownSignal,
 }
 
 impl<US: AsyncDatagramSocket> DatagramLocalEndpointInner<US> {
@@ -42,6 +66,10 @@ impl<US: AsyncDatagramSocket> DatagramLocalEndpointInner<US> {
         &self.socket
     }
 
+    pub(super) fn stats(&self) -> &StatCounters {
+        &self.stats
+    }
+
     pub(crate) fn next_msg_id(&self) -> MsgId {
         self.next_msg_id.fetch_add(1, Ordering::Relaxed)
     }
@@ -54,6 +82,230 @@ impl<US: AsyncDatagramSocket> DatagramLocalEndpointInner<US> {
         self.default_port
     }
 
+    pub(super) fn max_inbound_message_size(&self) -> usize {
+        self.max_inbound_message_size.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn max_outbound_message_size(&self) -> usize {
+        self.max_outbound_message_size.load(Ordering::Relaxed)
+    }
+
+    /// Applies any path MTU updates reported by the socket.
+    fn update_path_mtus(&self) {
+        while let Some((addr, mtu)) = self.socket.take_path_mtu() {
+            debug!("Path MTU to {} is {}", addr, mtu);
+            self.path_mtus
+                .lock()
+                .expect("Lock failed")
+                .insert(addr, mtu);
+        }
+    }
+
+    /// Fails with [`Error::MessageTooLarge`] if a message of `len` bytes is larger than
+    /// the known path MTU to `dest`.
+    pub(super) fn check_path_mtu(&self, dest: US::SocketAddr, len: usize) -> Result<(), Error> {
+        self.update_path_mtus();
+
+        match self.path_mtus.lock().expect("Lock failed").get(&dest) {
+            Some(&mtu) if len > mtu => {
+                debug!(
+                    "{} byte message exceeds path MTU to {} ({})",
+                    len, dest, mtu
+                );
+                Err(Error::MessageTooLarge)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the group security context for `group`, if it is a multicast address that
+    /// one has been registered for.
+    fn group_security_context(
+        &self,
+        group: US::SocketAddr,
+    ) -> Option<Arc<dyn GroupSecurityContext<US::SocketAddr>>> {
+        if !group.is_multicast() {
+            return None;
+        }
+
+        self.group_security
+            .lock()
+            .expect("Lock failed")
+            .0
+            .iter()
+            .find(|context| context.is_for_group(group))
+            .cloned()
+    }
+
+    /// Protects `message` using the group security context for `dest`, if there is one.
+    pub(super) fn protect_outbound<'a>(
+        &self,
+        dest: US::SocketAddr,
+        message: &'a [u8],
+    ) -> Result<Cow<'a, [u8]>, Error> {
+        match self.group_security_context(dest) {
+            Some(context) => context.protect(dest, message).map(Cow::Owned),
+            None => Ok(Cow::Borrowed(message)),
+        }
+    }
+
+    /// Passes `message` to the [`OutboundHook`], if one is set, returning the bytes that
+    /// are to be sent instead.
+    pub(super) fn intercept_outbound<'a>(
+        &self,
+        dest: US::SocketAddr,
+        message: &'a [u8],
+    ) -> Result<Cow<'a, [u8]>, Error> {
+        let hook = self.outbound_hook.lock().expect("Lock failed").clone();
+
+        match hook {
+            Some(hook) => {
+                let mut message = message.to_vec();
+                hook.outbound(dest, &mut message)?;
+                Ok(Cow::Owned(message))
+            }
+            None => Ok(Cow::Borrowed(message)),
+        }
+    }
+
+    /// Passes `message`, received from `source`, to the [`InboundHook`], if one is set,
+    /// returning the bytes that are to be parsed instead, or `None` if the hook claimed it.
+    fn intercept_inbound<'a>(
+        &self,
+        source: US::SocketAddr,
+        message: &'a [u8],
+    ) -> Result<Option<Cow<'a, [u8]>>, Error> {
+        let hook = self.inbound_hook.lock().expect("Lock failed").clone();
+
+        match hook {
+            Some(hook) => {
+                let mut message = message.to_vec();
+                match hook.inbound(source, &mut message)? {
+                    InboundDisposition::Continue => Ok(Some(Cow::Owned(message))),
+                    InboundDisposition::Claimed => Ok(None),
+                }
+            }
+            None => Ok(Some(Cow::Borrowed(message))),
+        }
+    }
+
+    /// Verifies `message` using the group security context for `group`, if there is one.
+    fn verify_inbound<'a>(
+        &self,
+        group: US::SocketAddr,
+        source: US::SocketAddr,
+        message: &'a [u8],
+    ) -> Result<Cow<'a, [u8]>, Error> {
+        match self.group_security_context(group) {
+            Some(context) => context.verify(group, source, message).map(Cow::Owned),
+            None => Ok(Cow::Borrowed(message)),
+        }
+    }
+
+    /// Updates the round-trip time estimate for `dest` with a new sample.
+    pub(super) fn add_rtt_sample(&self, dest: US::SocketAddr, sample: Duration) {
+        let mut rtt_estimates = self.rtt_estimates.lock().expect("Lock failed");
+
+        match rtt_estimates.get_mut(&dest) {
+            Some(estimate) => estimate.update(sample),
+            None => {
+                rtt_estimates.insert(dest, RttEstimate::new(sample));
+            }
+        }
+    }
+
+    /// Returns the factor by which delays derived from `ack_timeout` are to be scaled for
+    /// `dest`, or `None` if the adaptive ACK timeout is disabled or nothing is known
+    /// about the round-trip time to `dest` yet.
+    pub(super) fn ack_timeout_scale_factor(
+        &self,
+        dest: US::SocketAddr,
+        ack_timeout: Duration,
+    ) -> Option<f64> {
+        let adaptive = (*self.adaptive_ack_timeout.lock().expect("Lock failed"))?;
+
+        self.rtt_estimates
+            .lock()
+            .expect("Lock failed")
+            .get(&dest)
+            .map(|estimate| adaptive.scale_factor(estimate, ack_timeout))
+    }
+
+    /// Calls `f` with this endpoint's random source in effect, for jittering
+    /// retransmission timeouts.
+    pub(super) fn with_random_source<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        let source = self.random_source.lock().expect("Lock failed").clone();
+        crate::random::with_random_source(&source, f)
+    }
+
+    /// Returns the [`EndpointObserver`] set for this endpoint, if any.
+    pub(super) fn endpoint_observer(&self) -> Option<Arc<dyn EndpointObserver<US::SocketAddr>>> {
+        self.endpoint_observer.lock().expect("Lock failed").clone()
+    }
+
+    /// Returns the [`TransactionJournal`] set for this endpoint, if any.
+    pub(super) fn transaction_journal(
+        &self,
+    ) -> Option<Arc<dyn TransactionJournal<US::SocketAddr>>> {
+        self.transaction_journal
+            .lock()
+            .expect("Lock failed")
+            .clone()
+    }
+
+    /// Notifies the [`EndpointObserver`] if this is the first datagram from `addr`.
+    fn note_peer(&self, addr: US::SocketAddr) {
+        if let Some(observer) = self.endpoint_observer() {
+            if self.known_peers.lock().expect("Lock failed").insert(addr) {
+                observer.peer_added(addr);
+            }
+        }
+    }
+
+    /// Calls `f` with this endpoint's option registry in effect, for displaying and
+    /// validating messages.
+    pub(super) fn with_option_registry<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        let registry = self.option_registry.lock().expect("Lock failed").clone();
+        option::with_option_registry(&registry, f)
+    }
+
+    /// Returns a future which completes once this local endpoint is shut down or dropped.
+    pub(super) fn shutdown_future(&self) -> ShutdownFuture {
+        self.shutdown.wait()
+    }
+
+    /// Makes `exchange` follow the in-flight exchange of an identical request to `dest`,
+    /// if request coalescing is enabled. See [`CoalescedExchanges::join`].
+    pub(super) fn join_coalesced_exchange<'a>(
+        &self,
+        dest: US::SocketAddr,
+        key: CacheKey,
+        exchange: Arc<Mutex<dyn CoalescedExchange<DatagramInboundContext<US::SocketAddr>> + 'a>>,
+    ) -> Coalesced {
+        self.coalesced_exchanges
+            .lock()
+            .expect("Lock failed")
+            .join(dest, key, exchange)
+    }
+
+    pub(super) fn remove_coalesced_exchange(&self, dest: US::SocketAddr, key: CacheKey, id: u64) {
+        let mut guard = match self.coalesced_exchanges.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => {
+                debug!("Recovering from mutex poisoning");
+                poisoned.into_inner()
+            }
+        };
+
+        guard.remove(dest, key, id)
+    }
+
     pub(crate) fn add_response_handler<'a>(
         &self,
         msg_id: MsgId,
@@ -102,9 +354,32 @@ impl<US: AsyncDatagramSocket> DatagramLocalEndpoint<US> {
             inner: Arc::new(DatagramLocalEndpointInner {
                 socket,
                 next_msg_id: std::sync::atomic::AtomicU16::new(1),
+                max_inbound_message_size: AtomicUsize::new(
+                    StandardCoapConstants::MAX_OUTBOUND_PACKET_LENGTH,
+                ),
+                max_outbound_message_size: AtomicUsize::new(
+                    StandardCoapConstants::MAX_OUTBOUND_PACKET_LENGTH,
+                ),
                 response_tracker: Mutex::new(UdpResponseTracker::new()),
                 scheme,
                 default_port,
+                stats: Default::default(),
+                path_mtus: Default::default(),
+                parse_error_handler: Default::default(),
+                version_policy: Default::default(),
+                group_security: Default::default(),
+                random_source: Mutex::new(Arc::new(ThreadRandom)),
+                rtt_estimates: Default::default(),
+                adaptive_ack_timeout: Default::default(),
+                coalesced_exchanges: Default::default(),
+                option_registry: Default::default(),
+                endpoint_observer: Default::default(),
+                inbound_hook: Default::default(),
+                transaction_journal: Default::default(),
+                outbound_hook: Default::default(),
+                received_requests: Default::default(),
+                known_peers: Default::default(),
+                shutdown: Default::default(),
             }),
         }
     }
@@ -113,6 +388,601 @@ impl<US: AsyncDatagramSocket> DatagramLocalEndpoint<US> {
     pub fn socket(&self) -> &US {
         self.inner.socket()
     }
+
+    /// Returns a snapshot of the statistics for this local endpoint.
+    pub fn stats(&self) -> EndpointStats {
+        let active_exchanges = self
+            .inner
+            .response_tracker
+            .lock()
+            .expect("Lock failed")
+            .len();
+        self.inner.stats.snapshot(active_exchanges)
+    }
+
+    /// Sets the path MTU to `dest`, or forgets it if `mtu` is `None`.
+    ///
+    /// `mtu` is the largest message, in bytes, that can be sent to `dest` in a single
+    /// datagram without fragmentation: the path MTU minus the size of the IP and UDP
+    /// headers. Sending a larger request to `dest` will then immediately fail with
+    /// [`Error::MessageTooLarge`] rather than being silently dropped by the network.
+    ///
+    /// Path MTUs can also be reported by the socket; see
+    /// [`AsyncDatagramSocket::take_path_mtu`].
+    pub fn set_path_mtu(&self, dest: US::SocketAddr, mtu: Option<usize>) {
+        let mut path_mtus = self.inner.path_mtus.lock().expect("Lock failed");
+
+        if let Some(mtu) = mtu {
+            path_mtus.insert(dest, mtu);
+        } else {
+            path_mtus.remove(&dest);
+        }
+    }
+
+    /// Sets the largest message, in bytes, that this endpoint will accept. Larger datagrams
+    /// are dropped as soon as they are received, without being parsed, and
+    /// [`receive`](LocalEndpoint::receive) fails with [`Error::MessageTooLarge`].
+    ///
+    /// The default is [`TransParams::MAX_OUTBOUND_PACKET_LENGTH`] (1152 bytes), the
+    /// typical limit for CoAP over UDP. Raising it is useful on networks which are known
+    /// to carry larger datagrams, and lowering it bounds the memory used by each receive.
+    /// The limit for each peer on reliable transports would be negotiated on top of this,
+    /// but [`DatagramLocalEndpoint`] only supports datagram transports.
+    pub fn set_max_inbound_message_size(&self, size: usize) {
+        self.inner
+            .max_inbound_message_size
+            .store(size, Ordering::Relaxed);
+    }
+
+    /// Returns the largest message, in bytes, that this endpoint will accept.
+    pub fn max_inbound_message_size(&self) -> usize {
+        self.inner.max_inbound_message_size()
+    }
+
+    /// Sets the largest message, in bytes, that this endpoint will send. Requests which
+    /// are larger fail with [`Error::OutOfSpace`] while they are being encoded, before
+    /// anything is sent, and responses which are larger fail in
+    /// [`respond`](RespondableInboundContext::respond).
+    ///
+    /// The default is [`TransParams::MAX_OUTBOUND_PACKET_LENGTH`] (1152 bytes). Unlike
+    /// [`set_path_mtu`](Self::set_path_mtu), this applies to every destination.
+    pub fn set_max_outbound_message_size(&self, size: usize) {
+        self.inner
+            .max_outbound_message_size
+            .store(size, Ordering::Relaxed);
+    }
+
+    /// Returns the largest message, in bytes, that this endpoint will send.
+    pub fn max_outbound_message_size(&self) -> usize {
+        self.inner.max_outbound_message_size()
+    }
+
+    /// Returns the known path MTU to `dest`, as set by [`DatagramLocalEndpoint::set_path_mtu`]
+    /// or reported by the socket.
+    pub fn path_mtu(&self, dest: US::SocketAddr) -> Option<usize> {
+        self.inner.update_path_mtus();
+        self.inner
+            .path_mtus
+            .lock()
+            .expect("Lock failed")
+            .get(&dest)
+            .copied()
+    }
+
+    /// Limits or disables lenient matching of acknowledgements to requests.
+    ///
+    /// Acknowledgements must have the same message id as the request they acknowledge,
+    /// but some non-conformant stacks get this wrong. If `window` is `Some`, an
+    /// acknowledgement with the wrong message id is instead matched to a request by its
+    /// token, as long as that request was transmitted no longer than `window` ago. Each
+    /// such match is counted in [`EndpointStats::lenient_matches`]. If `window` is `None`,
+    /// these acknowledgements are discarded (and counted in
+    /// [`EndpointStats::unmatched_responses`]).
+    ///
+    /// By default, acknowledgements are matched by their token no matter how long ago
+    /// the request was transmitted, which is how earlier versions behaved. Since tokens
+    /// are only unique among outstanding requests, the window should be kept as short as
+    /// the non-conformant devices allow.
+    pub fn set_lenient_msg_id_matching(&self, window: Option<Duration>) {
+        self.inner
+            .response_tracker
+            .lock()
+            .expect("Lock failed")
+            .set_lenient_window(window);
+    }
+
+    /// Shuts down this local endpoint.
+    ///
+    /// All pending futures and streams returned by this local endpoint (and by its remote
+    /// endpoints) resolve with [`Error::Cancelled`], and any that are created afterwards
+    /// fail with it immediately. Dropping the local endpoint has the same effect.
+    pub fn shutdown(&self) {
+        self.inner.shutdown.raise();
+    }
+
+    /// Returns true if [`DatagramLocalEndpoint::shutdown`] was called.
+    pub fn is_shut_down(&self) -> bool {
+        self.inner.shutdown.is_raised()
+    }
+
+    /// Enables or disables coalescing of identical concurrent requests.
+    ///
+    /// When enabled, a `GET` or `FETCH` request that is sent while an identical request to
+    /// the same destination is still waiting for its response isn't sent at all. Instead,
+    /// it receives the responses to the request already in flight. Requests are identical
+    /// if they have the same [`CacheKey`]. Observations and multicast requests are never
+    /// coalesced.
+    ///
+    /// If the request in flight is dropped before it gets a response, the requests that
+    /// were coalesced with it are sent after all.
+    ///
+    /// This is disabled by default.
+    pub fn set_request_coalescing(&self, enabled: bool) {
+        self.inner
+            .coalesced_exchanges
+            .lock()
+            .expect("Lock failed")
+            .set_enabled(enabled);
+    }
+
+    /// Returns the estimated round-trip time to `dest`, if any requests sent to it have
+    /// been answered without being retransmitted.
+    pub fn rtt_estimate(&self, dest: US::SocketAddr) -> Option<RttEstimate> {
+        self.inner
+            .rtt_estimates
+            .lock()
+            .expect("Lock failed")
+            .get(&dest)
+            .copied()
+    }
+
+    /// Enables or disables scaling `ACK_TIMEOUT` with the estimated round-trip time to
+    /// each destination.
+    ///
+    /// When enabled, the retransmission delays (and the overall time to wait for an
+    /// acknowledgement, if it is longer) for requests sent to a destination with an
+    /// [`RttEstimate`] are scaled as if `ACK_TIMEOUT` was the estimate's
+    /// [`retransmit_timeout`](RttEstimate::retransmit_timeout), clamped to the bounds in
+    /// `adaptive`. This makes retransmissions faster on local networks, and avoids
+    /// spurious ones on high-latency links. Destinations without an estimate use the
+    /// regular transmission parameters.
+    ///
+    /// This is disabled by default.
+    pub fn set_adaptive_ack_timeout(&self, adaptive: Option<AdaptiveAckTimeout>) {
+        *self.inner.adaptive_ack_timeout.lock().expect("Lock failed") = adaptive;
+    }
+
+    /// Sets a handler which is called for each received datagram that is discarded because
+    /// it couldn't be parsed, replacing any previously set handler.
+    ///
+    /// Malformed datagrams are always counted in [`EndpointStats::parse_errors`]; this
+    /// handler additionally gets the raw bytes and a [`ParseDiagnostic`] describing what
+    /// was wrong with them, which is useful for logging and interop debugging.
+    ///
+    /// ```
+    /// # use async_coap::datagram::{DatagramLocalEndpoint, LoopbackSocket};
+    /// let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+    ///
+    /// local_endpoint.set_parse_error_handler(|diagnostic| {
+    ///     println!("{}", diagnostic);
+    /// });
+    /// ```
+    pub fn set_parse_error_handler<F>(&self, handler: F)
+    where
+        F: Fn(&ParseDiagnostic<'_, US::SocketAddr>) + Send + Sync + 'static,
+    {
+        self.inner
+            .parse_error_handler
+            .lock()
+            .expect("Lock failed")
+            .replace(ParseErrorHandler(Box::new(handler)));
+    }
+
+    /// Sets how received datagrams whose header has a version other than 1 are treated.
+    ///
+    /// With [`VersionPolicy::Tolerant`], datagrams from future protocol revisions, or
+    /// that are otherwise identifiable but malformed, are counted separately in
+    /// [`EndpointStats::unknown_versions`] instead of as parse errors, and can be
+    /// inspected with the parse error handler:
+    ///
+    /// ```
+    /// # use async_coap::datagram::{DatagramLocalEndpoint, LoopbackSocket, VersionPolicy};
+    /// # use async_coap::message::MessageRead;
+    /// let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+    ///
+    /// local_endpoint.set_version_policy(VersionPolicy::Tolerant);
+    /// local_endpoint.set_parse_error_handler(|diagnostic| {
+    ///     if let (Some(version), Some(msg)) = (diagnostic.version(), diagnostic.message()) {
+    ///         println!("Version {} message: {}", version, msg);
+    ///     }
+    /// });
+    /// ```
+    ///
+    /// The default is [`VersionPolicy::Strict`].
+    pub fn set_version_policy(&self, policy: VersionPolicy) {
+        *self.inner.version_policy.lock().expect("Lock failed") = policy;
+    }
+
+    /// Sets the source of random numbers used to jitter retransmission timeouts.
+    ///
+    /// The default is [`ThreadRandom`]. Use [`SeededRandom`] to make the timeouts
+    /// deterministic, or [`NoJitter`] to disable jitter entirely.
+    pub fn set_random_source<R>(&self, source: R)
+    where
+        R: RandomSource + 'static,
+    {
+        *self.inner.random_source.lock().expect("Lock failed") = Arc::new(source);
+    }
+
+    /// Sets the [`EndpointObserver`] which is told about significant events, like new
+    /// peers and failed transactions, replacing any previously set observer.
+    ///
+    /// ```
+    /// # use async_coap::datagram::{
+    /// #     DatagramLocalEndpoint, EndpointObserver, LoopbackSocket, LoopbackSocketAddr,
+    /// #     TransactionFailure,
+    /// # };
+    /// struct LogFailures;
+    ///
+    /// impl EndpointObserver<LoopbackSocketAddr> for LogFailures {
+    ///     fn transaction_failed(&self, dest: LoopbackSocketAddr, failure: &TransactionFailure) {
+    ///         println!("Request to {} failed: {}", dest, failure);
+    ///     }
+    /// }
+    ///
+    /// let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+    /// local_endpoint.set_endpoint_observer(LogFailures);
+    /// ```
+    pub fn set_endpoint_observer<O>(&self, observer: O)
+    where
+        O: EndpointObserver<US::SocketAddr> + 'static,
+    {
+        *self.inner.endpoint_observer.lock().expect("Lock failed") = Some(Arc::new(observer));
+        self.inner.known_peers.lock().expect("Lock failed").clear();
+    }
+
+    /// Sets the vendor-specific or experimental options that this local endpoint
+    /// understands, replacing any previously set registry.
+    ///
+    /// The registry is in effect while messages are handled, so the registered options
+    /// are displayed with their names, and critical ones are accepted by
+    /// [`RequestOptions::parse`] and [`SendDesc::supports_option`]. See
+    /// [`OptionRegistry`] for details.
+    ///
+    /// [`RequestOptions::parse`]: crate::option::RequestOptions::parse
+    /// [`SendDesc::supports_option`]: crate::send_desc::SendDesc::supports_option
+    ///
+    /// ```
+    /// # use async_coap::datagram::{DatagramLocalEndpoint, LoopbackSocket};
+    /// # use async_coap::option::*;
+    /// const VENDOR_MODE: CustomOption = CustomOption {
+    ///     number: OptionNumber(65001),
+    ///     name: "Vendor-Mode",
+    ///     value_type: OptionValueType::String,
+    ///     critical: true,
+    ///     repeatable: false,
+    /// };
+    ///
+    /// let mut registry = OptionRegistry::new();
+    /// registry.register(VENDOR_MODE).unwrap();
+    ///
+    /// let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+    /// local_endpoint.set_option_registry(registry);
+    /// ```
+    pub fn set_option_registry(&self, registry: OptionRegistry) {
+        *self.inner.option_registry.lock().expect("Lock failed") = Arc::new(registry);
+    }
+
+    /// Sets the [`OutboundHook`] which is given every datagram before it is sent, replacing
+    /// any previously set hook.
+    ///
+    /// ```
+    /// # use async_coap::datagram::{DatagramLocalEndpoint, LoopbackSocket, LoopbackSocketAddr};
+    /// # use async_coap::Error;
+    /// let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+    ///
+    /// local_endpoint.set_outbound_hook(|dest: LoopbackSocketAddr, message: &mut Vec<u8>| {
+    ///     if message.len() > 1024 {
+    ///         return Err(Error::MessageTooLarge);
+    ///     }
+    ///     println!("Sending {} bytes to {}", message.len(), dest);
+    ///     Ok(())
+    /// });
+    /// ```
+    pub fn set_outbound_hook<H>(&self, hook: H)
+    where
+        H: OutboundHook<US::SocketAddr> + 'static,
+    {
+        *self.inner.outbound_hook.lock().expect("Lock failed") = Some(Arc::new(hook));
+    }
+
+    /// Removes the hook set with [`DatagramLocalEndpoint::set_outbound_hook`].
+    pub fn clear_outbound_hook(&self) {
+        *self.inner.outbound_hook.lock().expect("Lock failed") = None;
+    }
+
+    /// Sets the [`InboundHook`] which is given every received datagram before it is parsed,
+    /// replacing any previously set hook.
+    ///
+    /// ```
+    /// # use async_coap::datagram::{DatagramLocalEndpoint, LoopbackSocket, LoopbackSocketAddr};
+    /// # use async_coap::datagram::InboundDisposition;
+    /// let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+    ///
+    /// local_endpoint.set_inbound_hook(|source: LoopbackSocketAddr, message: &mut Vec<u8>| {
+    ///     // Claim anything that isn't CoAP version 1 for some other protocol.
+    ///     if message.first().map(|b| b >> 6) != Some(1) {
+    ///         println!("Claimed {} bytes from {}", message.len(), source);
+    ///         return Ok(InboundDisposition::Claimed);
+    ///     }
+    ///     Ok(InboundDisposition::Continue)
+    /// });
+    /// ```
+    pub fn set_inbound_hook<H>(&self, hook: H)
+    where
+        H: InboundHook<US::SocketAddr> + 'static,
+    {
+        *self.inner.inbound_hook.lock().expect("Lock failed") = Some(Arc::new(hook));
+    }
+
+    /// Removes the hook set with [`DatagramLocalEndpoint::set_inbound_hook`].
+    pub fn clear_inbound_hook(&self) {
+        *self.inner.inbound_hook.lock().expect("Lock failed") = None;
+    }
+
+    /// Sets the [`TransactionJournal`] which records outbound confirmable requests until
+    /// they are finished, replacing any previously set journal.
+    pub fn set_transaction_journal<J>(&self, journal: J)
+    where
+        J: TransactionJournal<US::SocketAddr> + 'static,
+    {
+        *self.inner.transaction_journal.lock().expect("Lock failed") = Some(Arc::new(journal));
+    }
+
+    /// Removes the journal set with [`DatagramLocalEndpoint::set_transaction_journal`].
+    pub fn clear_transaction_journal(&self) {
+        *self.inner.transaction_journal.lock().expect("Lock failed") = None;
+    }
+
+    /// Sends a copy of the request in `entry`, typically left unfinished in a
+    /// [`TransactionJournal`] by a previous run of the application, with its original
+    /// token, returning the first response.
+    ///
+    /// The method, options, and payload of the journaled request are sent as they are, and
+    /// the request is recorded in the journal again.
+    ///
+    /// ```
+    /// # use async_coap::datagram::{DatagramLocalEndpoint, JournalEntry, LoopbackSocket};
+    /// # use async_coap::datagram::LoopbackSocketAddr;
+    /// async fn resume(
+    ///     local_endpoint: &DatagramLocalEndpoint<LoopbackSocket>,
+    ///     unfinished: Vec<JournalEntry<LoopbackSocketAddr>>,
+    /// ) {
+    ///     for entry in unfinished {
+    ///         match local_endpoint.reissue(entry).await {
+    ///             Ok(response) => println!("Delivered: {}", response),
+    ///             Err(e) => println!("Still undelivered: {:?}", e),
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub fn reissue(
+        &self,
+        entry: JournalEntry<US::SocketAddr>,
+    ) -> BoxFuture<'_, Result<OwnedImmutableMessage, Error>> {
+        let msg_token = entry.msg_token();
+        self.send_with_token(entry.dest, msg_token, Reissue(entry.message))
+    }
+
+    /// Registers a security context, such as a [Group OSCORE] context, for protecting
+    /// multicast messages.
+    ///
+    /// Messages sent to a multicast address for which [`GroupSecurityContext::is_for_group`]
+    /// returns true are protected by the context before being sent, and datagrams received
+    /// on such an address are verified by it before being parsed. If more than one context
+    /// is for the same group, the one registered first is used.
+    ///
+    /// [Group OSCORE]: https://tools.ietf.org/html/draft-ietf-core-oscore-groupcomm
+    pub fn add_group_security_context<C>(&self, context: C)
+    where
+        C: GroupSecurityContext<US::SocketAddr> + 'static,
+    {
+        self.inner
+            .group_security
+            .lock()
+            .expect("Lock failed")
+            .0
+            .push(Arc::new(context));
+    }
+
+    /// Removes all security contexts registered with
+    /// [`DatagramLocalEndpoint::add_group_security_context`].
+    pub fn clear_group_security_contexts(&self) {
+        self.inner
+            .group_security
+            .lock()
+            .expect("Lock failed")
+            .0
+            .clear();
+    }
+
+    /// Like [`LocalEndpoint::send`], but additionally returns a [`SendStatusHandle`] which can
+    /// be used to inspect the state of the request while it is in flight.
+    ///
+    /// This is useful for diagnosing transactions that appear to be stuck:
+    ///
+    /// ```
+    /// # use async_coap::prelude::*;
+    /// # use async_coap::datagram::{DatagramLocalEndpoint, LoopbackSocket, LoopbackSocketAddr};
+    /// let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+    ///
+    /// let (future, handle) =
+    ///     local_endpoint.send_with_status(LoopbackSocketAddr::Unicast, CoapRequest::get());
+    ///
+    /// // Call `handle.status()` from elsewhere while `future` is being awaited.
+    /// println!("{}", handle.status().unwrap());
+    /// # drop(future);
+    /// ```
+    pub fn send_with_status<'a, S, R, SD>(
+        &'a self,
+        dest: S,
+        send_desc: SD,
+    ) -> (
+        BoxFuture<'a, Result<R, Error>>,
+        SendStatusHandle<US::SocketAddr>,
+    )
+    where
+        S: ToSocketAddrs<SocketAddr = US::SocketAddr, Error = US::Error> + 'a,
+        SD: SendDesc<DatagramInboundContext<US::SocketAddr>, R> + 'a,
+        R: Send + 'a,
+    {
+        let socket_addr = match dest.to_socket_addrs().map(|mut iter| iter.next()) {
+            Ok(Some(socket_addr)) => socket_addr,
+            Ok(None) => {
+                return (
+                    futures::future::ready(Err(Error::HostNotFound)).boxed(),
+                    SendStatusHandle::detached(),
+                )
+            }
+            Err(_) => {
+                return (
+                    futures::future::ready(Err(Error::HostLookupFailure)).boxed(),
+                    SendStatusHandle::detached(),
+                )
+            }
+        };
+
+        if let Some(trans_params) = send_desc.trans_params() {
+            let future = UdpSendFuture::new(&self.inner, socket_addr, send_desc, trans_params);
+            let handle = future.status_handle();
+            (future.boxed(), handle)
+        } else {
+            let future =
+                UdpSendFuture::new(&self.inner, socket_addr, send_desc, StandardCoapConstants);
+            let handle = future.status_handle();
+            (future.boxed(), handle)
+        }
+    }
+
+    /// Like [`LocalEndpoint::send`], but if the request fails, the error is a
+    /// [`TransactionFailure`], which also describes how many attempts were made, how long
+    /// they took, and what the remote endpoint did (or didn't do).
+    ///
+    /// This makes it possible to tell a host which is silent from one which rejected the
+    /// request:
+    ///
+    /// ```
+    /// # use async_coap::prelude::*;
+    /// # use async_coap::datagram::{DatagramLocalEndpoint, FailureCause, LoopbackSocket};
+    /// # use async_coap::datagram::LoopbackSocketAddr;
+    /// async fn probe(local_endpoint: &DatagramLocalEndpoint<LoopbackSocket>) {
+    ///     let send_desc = CoapRequest::get().emit_successful_response();
+    ///
+    ///     match local_endpoint
+    ///         .send_with_failure_details(LoopbackSocketAddr::Unicast, send_desc)
+    ///         .await
+    ///     {
+    ///         Ok(_) => println!("Host is up"),
+    ///         Err(failure) if failure.cause == Some(FailureCause::NoAck) => {
+    ///             println!("Host is silent, gave up after {:?}", failure.elapsed)
+    ///         }
+    ///         Err(failure) => println!("Request failed: {}", failure),
+    ///     }
+    /// }
+    /// ```
+    pub fn send_with_failure_details<'a, S, R, SD>(
+        &'a self,
+        dest: S,
+        send_desc: SD,
+    ) -> BoxFuture<'a, Result<R, TransactionFailure>>
+    where
+        S: ToSocketAddrs<SocketAddr = US::SocketAddr, Error = US::Error> + 'a,
+        SD: SendDesc<DatagramInboundContext<US::SocketAddr>, R> + 'a,
+        R: Send + 'a,
+    {
+        let socket_addr = match dest.to_socket_addrs().map(|mut iter| iter.next()) {
+            Ok(Some(socket_addr)) => socket_addr,
+            Ok(None) => return futures::future::ready(Err(Error::HostNotFound.into())).boxed(),
+            Err(_) => return futures::future::ready(Err(Error::HostLookupFailure.into())).boxed(),
+        };
+
+        if let Some(trans_params) = send_desc.trans_params() {
+            WithFailureDetails(UdpSendFuture::new(
+                &self.inner,
+                socket_addr,
+                send_desc,
+                trans_params,
+            ))
+            .boxed()
+        } else {
+            WithFailureDetails(UdpSendFuture::new(
+                &self.inner,
+                socket_addr,
+                send_desc,
+                StandardCoapConstants,
+            ))
+            .boxed()
+        }
+    }
+
+    /// Sends `message`, a reply to a datagram received from `dest`, after passing it to the
+    /// [`OutboundHook`]. Replies vetoed by the hook are dropped.
+    async fn send_reply(&self, dest: US::SocketAddr, message: &[u8]) -> Result<(), Error> {
+        let message = match self.inner.intercept_outbound(dest, message) {
+            Ok(message) => message,
+            Err(e) => {
+                debug!("Dropping reply to {}: vetoed ({:?})", dest, e);
+                return Ok(());
+            }
+        };
+
+        self.inner.stats().message_out();
+        if let Some(e) = self.socket().send_to(&message, dest).await.err() {
+            error!("send_to: io error: {:?} (dest={:?})", e, dest);
+            return Err(Error::IOError);
+        }
+
+        Ok(())
+    }
+
+    /// Fails the pending transactions with any remote addresses that the socket has
+    /// reported as unreachable, returning true if there were any such reports.
+    fn handle_unreachable(&self) -> bool {
+        let mut unreachable = false;
+
+        while let Some(addr) = self.socket().take_unreachable() {
+            unreachable = true;
+
+            let mut tracker = self.inner.response_tracker.lock().expect("Lock failed");
+            let count = tracker.handle_error(addr, Error::HostUnreachable);
+            debug!("{} is unreachable, failed {} transactions", addr, count);
+        }
+
+        unreachable
+    }
+
+    /// Like [`LocalEndpoint::send`], except that the message is sent with the token
+    /// `msg_token` instead of a newly allocated one.
+    pub(super) fn send_with_token<'a, R, SD>(
+        &'a self,
+        dest: US::SocketAddr,
+        msg_token: MsgToken,
+        send_desc: SD,
+    ) -> BoxFuture<'a, Result<R, Error>>
+    where
+        SD: SendDesc<DatagramInboundContext<US::SocketAddr>, R> + 'a,
+        R: Send + 'a,
+    {
+        if let Some(trans_params) = send_desc.trans_params() {
+            UdpSendFuture::new(&self.inner, dest, send_desc, trans_params)
+                .with_msg_token(msg_token)
+                .boxed()
+        } else {
+            UdpSendFuture::new(&self.inner, dest, send_desc, StandardCoapConstants)
+                .with_msg_token(msg_token)
+                .boxed()
+        }
+    }
 }
 
 impl<US: AsyncDatagramSocket> LocalEndpoint for DatagramLocalEndpoint<US> {
@@ -142,24 +1012,46 @@ impl<US: AsyncDatagramSocket> LocalEndpoint for DatagramLocalEndpoint<US> {
             }
         }
 
-        if let Some((_userinfo, host, port)) = uri.raw_userinfo_host_port() {
-            let host = host
-                .unescape_uri()
-                .try_to_cow()
-                .expect("Host in URI is corrupted");
+        let components = uri.components();
+        let host = components.host().ok_or(Error::HostNotFound)?;
+        let port = components.port().unwrap_or(0);
 
-            let mut lookup_stream = self.lookup(&host, port.unwrap_or(0))?;
+        // IP address literals were already parsed along with the URI,
+        // so we can skip the lookup entirely. Zone identifiers still
+        // require a lookup.
+        let has_zone = match &host {
+            uri::UriHost::Ipv6 { zone, .. } => zone.is_some(),
+            _ => false,
+        };
 
-            // TODO: Eventually remove the call to "now_or_never()"
-            if let Some(socket_addr) = lookup_stream
-                .next()
-                .now_or_never()
-                .expect("Lookup stream not ready")
-            {
-                Ok(self.remote_endpoint(socket_addr, Some(host), uri.trim_fragment().rel()))
-            } else {
-                Err(Error::HostNotFound)
-            }
+        if let Some(socket_addr) = host.ip_addr().filter(|_| !has_zone).and_then(|addr| {
+            let port = if port == 0 { self.default_port() } else { port };
+            Self::SocketAddr::from_ip_addr(addr, port)
+        }) {
+            let socket_addr = match self.socket().local_addr() {
+                Ok(local) => socket_addr
+                    .conforming_to(local)
+                    .ok_or(Error::HostNotFound)?,
+                Err(_) => socket_addr,
+            };
+
+            return Ok(self.remote_endpoint(
+                socket_addr,
+                None::<String>,
+                uri.trim_fragment().rel(),
+            ));
+        }
+
+        let host = host.to_string();
+        let mut lookup_stream = self.lookup(&host, port)?;
+
+        // TODO: Eventually remove the call to "now_or_never()"
+        if let Some(socket_addr) = lookup_stream
+            .next()
+            .now_or_never()
+            .expect("Lookup stream not ready")
+        {
+            Ok(self.remote_endpoint(socket_addr, Some(host), uri.trim_fragment().rel()))
         } else {
             Err(Error::HostNotFound)
         }
@@ -198,21 +1090,114 @@ impl<US: AsyncDatagramSocket> LocalEndpoint for DatagramLocalEndpoint<US> {
         F: FnMut(&Self::RespondableInboundContext) -> Result<(), Error> + 'a + Send,
     {
         async move {
-            let mut buffer = [0u8; StandardCoapConstants::MAX_OUTBOUND_PACKET_LENGTH];
-            let (len, source, dest) = match self.socket().recv_from(&mut buffer).await {
-                Ok(x) => x,
-                Err(_) => return Err(Error::IOError),
+            if self.is_shut_down() {
+                return Err(Error::Cancelled);
+            }
+
+            // One extra byte, so that oversized datagrams can be told apart.
+            let max_len = self.inner.max_inbound_message_size();
+            let mut buffer = vec![0u8; max_len + 1];
+            let result = match futures::future::select(
+                self.inner.shutdown_future(),
+                self.socket().recv_from(&mut buffer),
+            )
+            .await
+            {
+                futures::future::Either::Left(_) => return Err(Error::Cancelled),
+                futures::future::Either::Right((result, _)) => result.ok(),
             };
+            let unreachable = self.handle_unreachable();
+            self.inner.update_path_mtus();
+            let (len, source, dest) = match result {
+                Some(x) => x,
+                None if unreachable => return Err(Error::HostUnreachable),
+                None => return Err(Error::IOError),
+            };
+            if len > max_len {
+                debug!(
+                    "Dropping datagram from {}: larger than {} bytes",
+                    source, max_len
+                );
+                self.inner.stats().message_in();
+                self.inner.stats().parse_error();
+                return Err(Error::MessageTooLarge);
+            }
             let buffer = &buffer[..len];
-            debug!("INBOUND: {} {}", source, CoapByteDisplayFormatter(buffer));
+            self.inner.with_option_registry(|| {
+                debug!("INBOUND: {} {}", source, CoapByteDisplayFormatter(buffer))
+            });
+            self.inner.note_peer(source);
+
+            let stats = self.inner.stats();
+            stats.message_in();
+
+            let buffer = match self.inner.intercept_inbound(source, buffer) {
+                Ok(Some(buffer)) => buffer,
+                Ok(None) => {
+                    debug!("Datagram from {} claimed by inbound hook", source);
+                    return Ok(());
+                }
+                Err(e) => {
+                    debug!(
+                        "Dropping datagram from {}: rejected by inbound hook",
+                        source
+                    );
+                    return Err(e);
+                }
+            };
 
             let is_multicast = match dest {
                 Some(local_addr) => local_addr.is_multicast(),
                 None => false,
             };
 
-            let inbound_context: Self::RespondableInboundContext =
-                DatagramRespondableInboundContext::new(buffer.to_vec(), source, is_multicast)?;
+            let buffer = match dest {
+                Some(group) if is_multicast => {
+                    match self.inner.verify_inbound(group, source, &buffer) {
+                        Ok(buffer) => buffer,
+                        Err(e) => {
+                            debug!("Dropping datagram from {}: verification failed", source);
+                            return Err(e);
+                        }
+                    }
+                }
+                _ => Cow::Borrowed(&buffer[..]),
+            };
+
+            let mut inbound_context: Self::RespondableInboundContext =
+                match DatagramRespondableInboundContext::new(buffer.to_vec(), source, is_multicast)
+                {
+                    Ok(mut inbound_context) => {
+                        inbound_context.max_response_len = self.inner.max_outbound_message_size();
+                        inbound_context
+                    }
+                    Err(e) => {
+                        let diagnostic = ParseDiagnostic::new(source, &buffer, e);
+                        let tolerated = match diagnostic.reason() {
+                            ParseFailureReason::UnsupportedVersion(_) => {
+                                *self.inner.version_policy.lock().expect("Lock failed")
+                                    == VersionPolicy::Tolerant
+                            }
+                            _ => false,
+                        };
+                        if tolerated {
+                            stats.unknown_version();
+                        } else {
+                            stats.parse_error();
+                        }
+                        debug!("{}", diagnostic);
+                        if let Some(handler) = self
+                            .inner
+                            .parse_error_handler
+                            .lock()
+                            .expect("Lock failed")
+                            .as_ref()
+                        {
+                            (handler.0)(&diagnostic);
+                        }
+                        return if tolerated { Ok(()) } else { Err(e) };
+                    }
+                };
 
             let msg_code = inbound_context.message().msg_code();
             let msg_type = inbound_context.message().msg_type();
@@ -221,12 +1206,36 @@ impl<US: AsyncDatagramSocket> LocalEndpoint for DatagramLocalEndpoint<US> {
             let ret = if msg_code.is_method() {
                 // This is a request
                 debug!("Message is a request.");
-                handler(&inbound_context)?;
+
+                inbound_context.dupe_count = self
+                    .inner
+                    .received_requests
+                    .lock()
+                    .expect("Lock failed")
+                    .record(source, msg_id, std::time::Instant::now());
+
+                // TODO: Handlers are synchronous, so the number of concurrently executing
+                //       handlers is already bounded by the number of concurrent calls to
+                //       `receive()`. Once handlers can be asynchronous, add a configurable
+                //       cap on them with an overflow behavior (queue, reject with 5.03,
+                //       or drop) to keep memory bounded under load.
+                self.inner
+                    .with_option_registry(|| handler(&inbound_context))?;
+
+                let registration = self
+                    .inner
+                    .endpoint_observer()
+                    .filter(|_| is_observe_registration(inbound_context.message()))
+                    .map(|observer| (observer, inbound_context.message().msg_token()));
 
                 if let Some(message) = inbound_context.into_message_out() {
-                    if let Some(e) = self.socket().send_to(&message, source).await.err() {
-                        error!("send_to: io error: {:?} (dest={:?})", e, source);
+                    if let Some((observer, token)) = registration {
+                        if is_observe_accepted(&message) {
+                            observer.observation_created(source, token);
+                        }
                     }
+
+                    let _ = self.send_reply(source, &message).await;
                 } else {
                     let mut buffer = [0u8; 12];
                     let mut builder = BufferMessageEncoder::new(&mut buffer);
@@ -235,40 +1244,42 @@ impl<US: AsyncDatagramSocket> LocalEndpoint for DatagramLocalEndpoint<US> {
 
                     let _ = message::ResetMessage.write_msg_to(&mut builder);
 
-                    if let Some(e) = self.socket().send_to(&builder, source).await.err() {
-                        error!("send_to: io error: {:?} (dest={:?})", e, source);
-                    }
+                    let _ = self.send_reply(source, &builder).await;
                 }
                 Ok(())
             } else if !msg_code.is_empty() || msg_type.is_ack() || msg_type.is_res() {
                 // This is a response
                 debug!("Message is a response.");
-                let was_handled = {
+                let response_match = {
                     let mut tracker = self.inner.response_tracker.lock().expect("Lock failed");
-                    tracker.handle_response(&inbound_context)
+                    self.inner
+                        .with_option_registry(|| tracker.handle_response(&inbound_context))
                 };
-                debug!("was_handled: {}", was_handled);
+                debug!("response_match: {:?}", response_match);
+
+                match response_match {
+                    ResponseMatch::Unmatched => stats.unmatched_response(),
+                    ResponseMatch::MatchedLeniently => stats.lenient_match(),
+                    ResponseMatch::Matched => (),
+                    ResponseMatch::Deferred => debug!("Response deferred, not acknowledging"),
+                }
 
                 // Drop the inbound context so that we don't cross a `.await` holding it.
                 core::mem::drop(inbound_context);
 
-                if msg_type.is_con() {
+                // A deferred response is left unacknowledged, so that it is retransmitted.
+                if msg_type.is_con() && response_match != ResponseMatch::Deferred {
                     let mut buffer = [0u8; 12];
                     let mut builder = BufferMessageEncoder::new(&mut buffer);
                     builder.set_msg_id(msg_id);
 
-                    if was_handled {
+                    if response_match != ResponseMatch::Unmatched {
                         let _ = message::AckMessage.write_msg_to(&mut builder);
                     } else {
                         let _ = message::ResetMessage.write_msg_to(&mut builder);
                     }
 
-                    if let Some(e) = self.socket().send_to(&builder, source).await.err() {
-                        error!("send_to: io error: {:?} (dest={:?})", e, source);
-                        Err(Error::IOError)
-                    } else {
-                        Ok(())
-                    }
+                    self.send_reply(source, &builder).await
                 } else {
                     Ok(())
                 }
@@ -285,12 +1296,11 @@ impl<US: AsyncDatagramSocket> LocalEndpoint for DatagramLocalEndpoint<US> {
 
                 let _ = message::ResetMessage.write_msg_to(&mut builder);
 
-                if let Some(e) = self.socket().send_to(&builder, source).await.err() {
-                    error!("send_to: io error: {:?} (dest={:?})", e, source);
-                }
+                let _ = self.send_reply(source, &builder).await;
 
                 Ok(())
             } else {
+                stats.parse_error();
                 Err(Error::ParseFailure)
             };
 
@@ -338,12 +1348,13 @@ impl<US: AsyncDatagramSocket> LocalEndpoint for DatagramLocalEndpoint<US> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::message::{MessageDisplay, MessageRead};
+    use crate::message::MessageDisplay;
     use crate::ContentFormat;
     use futures::executor::block_on;
     use futures::future::select;
     use futures::future::Either;
     use futures_timer::Delay;
+    use std::ops::Bound;
     use std::time::Duration;
 
     fn test_process_request<LE, F, R>(local_endpoint: &LE, future: F) -> R
@@ -376,213 +1387,1966 @@ mod tests {
         assert_eq!(Ok(()), test_process_request(&local_endpoint, future));
     }
 
-    /// Test that verifies that timeouts are working properly.
-    /// This can currently take a while to execute, so it is currently disabled.
     #[test]
-    #[ignore]
-    fn ping_null() {
-        let socket = NullSocket::new();
+    fn block1_loopback() {
+        use futures::io::AsyncReadExt;
+
+        let socket = LoopbackSocket::new();
         let local_endpoint = DatagramLocalEndpoint::new(socket);
-        let dest = NullSocketAddr;
-        let send_desc = Ping::new();
-        let future = local_endpoint.send(dest, send_desc);
+
+        let (upload, mut body) = Block1Upload::new(1000);
+        let upload = Arc::new(Mutex::new(upload));
+        let handler = move |context: &DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+            let status = upload.lock().unwrap().handle(context)?;
+            match status {
+                Block1Status::Complete(block1) => context.respond(|msg_out| {
+                    msg_out.set_msg_code(MsgCode::SuccessChanged);
+                    msg_out.insert_option(option::BLOCK1, block1.unwrap())
+                }),
+                _ => Ok(()),
+            }
+        };
+
+        let payload = (0..100u8).collect::<Vec<_>>();
+        let progress = Arc::new(Mutex::new(Vec::new()));
+        let progress_clone = progress.clone();
+
+        let send_desc = CoapRequest::put()
+            .block1(payload.clone(), BlockInfo::new(0, false, 1))
+            .inspect_upload(move |sent, total| progress_clone.lock().unwrap().push((sent, total)))
+            .emit_msg_code();
+
+        let future = local_endpoint.send(LoopbackSocketAddr::Unicast, send_desc);
+        let future_receive = local_endpoint.receive_loop(handler);
+
+        match block_on(select(future, future_receive)) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((ret, _)) => assert_eq!(Ok(MsgCode::SuccessChanged), ret),
+        }
 
         assert_eq!(
-            Err(Error::ResponseTimeout),
-            test_process_request(&local_endpoint, future)
+            vec![(32, 100), (64, 100), (96, 100), (100, 100)],
+            *progress.lock().unwrap()
         );
+
+        let mut uploaded = Vec::new();
+        block_on(body.read_to_end(&mut uploaded)).unwrap();
+        assert_eq!(payload, uploaded);
     }
 
     #[test]
-    fn ping_localhost() {
-        let socket = AllowStdUdpSocket::bind("127.0.0.1:12345").expect("UDP bind failed");
+    fn block1_inner_payload_loopback() {
+        use futures::io::AsyncReadExt;
+
+        let socket = LoopbackSocket::new();
         let local_endpoint = DatagramLocalEndpoint::new(socket);
-        let dest = "127.0.0.1:12345";
-        let send_desc = Ping::new();
-        let future = local_endpoint.send(dest, send_desc);
 
-        assert_eq!(Ok(()), test_process_request(&local_endpoint, future));
+        let (upload, mut body) = Block1Upload::new(1000);
+        let upload = Arc::new(Mutex::new(upload));
+        let handler = move |context: &DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+            let status = upload.lock().unwrap().handle(context)?;
+            match status {
+                Block1Status::Complete(_) => context.respond(|msg_out| {
+                    msg_out.set_msg_code(MsgCode::SuccessChanged);
+                    Ok(())
+                }),
+                _ => Ok(()),
+            }
+        };
+
+        let payload = (0..70u8).collect::<Vec<_>>();
+        let payload_clone = payload.clone();
+        let progress = Arc::new(Mutex::new(Vec::new()));
+        let progress_clone = progress.clone();
+
+        // The payload of the wrapped send descriptor comes first.
+        let send_desc = CoapRequest::put()
+            .payload_writer(move |msg_out| {
+                msg_out.set_msg_code(MsgCode::MethodPut);
+                msg_out.append_payload_bytes(&payload_clone)
+            })
+            .block1(vec![0xFFu8; 30], BlockInfo::new(0, false, 1))
+            .inspect_upload(move |sent, total| progress_clone.lock().unwrap().push((sent, total)))
+            .emit_msg_code();
+
+        let future = local_endpoint.send(LoopbackSocketAddr::Unicast, send_desc);
+        let future_receive = local_endpoint.receive_loop(handler);
+
+        match block_on(select(future, future_receive)) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((ret, _)) => assert_eq!(Ok(MsgCode::SuccessChanged), ret),
+        }
+
+        assert_eq!(
+            vec![(32, 100), (64, 100), (96, 100), (100, 100)],
+            *progress.lock().unwrap()
+        );
+
+        let mut uploaded = Vec::new();
+        block_on(body.read_to_end(&mut uploaded)).unwrap();
+        assert_eq!(&payload[..], &uploaded[..70]);
+        assert_eq!(&[0xFFu8; 30][..], &uploaded[70..]);
     }
 
     #[test]
-    fn ping_coap_me() {
-        let socket = AllowStdUdpSocket::bind("0.0.0.0:0").expect("UDP bind failed");
+    fn request_tag_block1_loopback() {
+        let socket = LoopbackSocket::new();
         let local_endpoint = DatagramLocalEndpoint::new(socket);
 
-        let mut lookup_results = local_endpoint
-            .lookup("coap.me", 5683)
-            .expect("DNS lookup failure");
-        let dest = block_on(lookup_results.next()).expect("DNS lookup failure");
-        let send_desc = Ping::new();
-        let future = local_endpoint.send(dest, send_desc);
+        let tags = Arc::new(Mutex::new(Vec::new()));
+        let tags_clone = tags.clone();
+        let (upload, _body) = Block1Upload::new(1000);
+        let upload = Arc::new(Mutex::new(upload));
+        let handler = move |context: &DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+            let tag = context
+                .message()
+                .options()
+                .find_next_of(option::REQUEST_TAG);
+            if let Some(tag) = tag {
+                tags_clone.lock().unwrap().push(tag?);
+            }
+            let status = upload.lock().unwrap().handle(context)?;
+            match status {
+                Block1Status::Complete(_) => context.respond(|msg_out| {
+                    msg_out.set_msg_code(MsgCode::SuccessChanged);
+                    Ok(())
+                }),
+                _ => Ok(()),
+            }
+        };
 
-        assert_eq!(Ok(()), test_process_request(&local_endpoint, future));
+        let tag = RequestTag::random();
+        let send_desc = CoapRequest::post()
+            .request_tag(tag)
+            .block1(vec![0u8; 100], BlockInfo::new(0, false, 1))
+            .emit_msg_code();
+
+        let future = local_endpoint.send(LoopbackSocketAddr::Unicast, send_desc);
+        let future_receive = local_endpoint.receive_loop(handler);
+
+        match block_on(select(future, future_receive)) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((ret, _)) => assert_eq!(Ok(MsgCode::SuccessChanged), ret),
+        }
+
+        // Every block must carry the same tag.
+        assert_eq!(vec![tag; 4], *tags.lock().unwrap());
     }
 
     #[test]
-    fn host_lookup_failure() {
-        let socket = AllowStdUdpSocket::bind("[::]:0").expect("UDP bind failed");
+    fn block1_payload_too_large() {
+        let socket = LoopbackSocket::new();
         let local_endpoint = DatagramLocalEndpoint::new(socket);
 
-        let dest = "[127.0.0.1]:5683";
-        let send_desc = Ping::new();
-        let future = local_endpoint.send(dest, send_desc);
+        let received = Arc::new(Mutex::new(0));
+        let received_clone = received.clone();
+        let handler = move |_: &DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+            *received_clone.lock().unwrap() += 1;
+            Ok(())
+        };
 
-        assert_eq!(
-            Err(Error::HostLookupFailure),
-            test_process_request(&local_endpoint, future)
-        );
+        // One byte more than can be numbered using 16-byte blocks.
+        let payload = vec![0u8; (BlockInfo::NUM_MAX as usize + 1) * 16 + 1];
+        let send_desc = CoapRequest::put()
+            .block1(payload, BlockInfo::new(0, false, 0))
+            .emit_msg_code();
+
+        let future = local_endpoint.send(LoopbackSocketAddr::Unicast, send_desc);
+        let future_receive = local_endpoint.receive_loop(handler);
+
+        match block_on(select(future, future_receive)) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((ret, _)) => assert_eq!(Err(Error::InvalidArgument), ret),
+        }
+
+        assert_eq!(0, *received.lock().unwrap());
     }
 
-    /// Test that performs a `GET` on <coap://coap.me/test>
     #[test]
-    fn get_coap_me_test() {
-        let socket = AllowStdUdpSocket::bind("0.0.0.0:0").expect("UDP bind failed");
+    fn block2_loopback() {
+        let socket = LoopbackSocket::new();
         let local_endpoint = DatagramLocalEndpoint::new(socket);
 
-        let remote_endpoint = local_endpoint
-            .remote_endpoint_from_uri(uri!("coap://coap.me/"))
-            .expect("client construct failed");
+        let payload = (0..100u8).collect::<Vec<_>>();
+        let mut writer = Block2Writer::new(1);
+        writer.append(&payload);
+        let writer = Arc::new(Mutex::new(writer));
+        let handler = move |context: &DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+            writer.lock().unwrap().respond(context, |msg_out| {
+                msg_out.set_msg_code(MsgCode::SuccessContent);
+                Ok(())
+            })?;
+            Ok(())
+        };
 
         let send_desc = CoapRequest::get()
-            .add_option(option::ACCEPT, ContentFormat::APPLICATION_JSON)
-            .inspect(|ctx| {
-                debug!("Got Response: {}", MessageDisplay(ctx.message()));
-                assert_eq!(ctx.message().msg_code(), MsgCode::SuccessContent);
-            });
+            .block2(None)
+            .strict_block_sequencing()
+            .emit_successful_collected_response();
 
-        let future = remote_endpoint.send_to(rel_ref!("test"), send_desc);
+        let future = local_endpoint.send(LoopbackSocketAddr::Unicast, send_desc);
+        let future_receive = local_endpoint.receive_loop(handler);
 
-        assert_eq!(Ok(()), test_process_request(&local_endpoint, future));
+        match block_on(select(future, future_receive)) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((ret, _)) => assert_eq!(payload, ret.unwrap().payload()),
+        };
     }
 
-    /// Test that performs a `GET` on <coap://coap.me/bl%C3%A5b%C3%A6rsyltet%C3%B8y>
     #[test]
-    fn get_coap_me_blabaersyltetoy() {
-        let socket = AllowStdUdpSocket::bind("0.0.0.0:0").expect("UDP bind failed");
+    fn block2_gap_loopback() {
+        let socket = LoopbackSocket::new();
         let local_endpoint = DatagramLocalEndpoint::new(socket);
 
-        let remote_endpoint = local_endpoint
-            .remote_endpoint_from_uri(uri!("coap://coap.me/bl%C3%A5b%C3%A6rsyltet%C3%B8y"))
-            .expect("client construct failed");
+        // Skips the second block.
+        let handler = |context: &DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+            let num = context.message().block2().map(|x| x.num()).unwrap_or(0);
+            let block2 = match num {
+                0 => BlockInfo::new(0, true, 0),
+                _ => BlockInfo::new(num + 1, false, 0),
+            };
+            context.respond(|msg_out| {
+                msg_out.set_msg_code(MsgCode::SuccessContent);
+                msg_out.insert_option(option::BLOCK2, block2.unwrap())?;
+                msg_out.append_payload_bytes(&[0u8; 16])
+            })
+        };
 
-        let send_desc = CoapRequest::get().emit_msg_code();
+        let block_map = Arc::new(Mutex::new(None));
+        let block_map_clone = block_map.clone();
 
-        let future = remote_endpoint.send(send_desc);
+        let send_desc = CoapRequest::get()
+            .block2(None)
+            .emit_successful_collected_response()
+            .inspect_incomplete(move |map| *block_map_clone.lock().unwrap() = Some(map.clone()));
+
+        let future = local_endpoint.send(LoopbackSocketAddr::Unicast, send_desc);
+        let future_receive = local_endpoint.receive_loop(handler);
+
+        match block_on(select(future, future_receive)) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((ret, _)) => {
+                assert_eq!(Err(Error::IncompleteBlockTransfer), ret.map(|_| ()))
+            }
+        }
+
+        let block_map = block_map.lock().unwrap().take().unwrap();
+        assert_eq!(&[0..16, 32..48][..], block_map.received());
+        assert_eq!(vec![16..32], block_map.gaps());
+        assert_eq!(Some(48), block_map.end());
+    }
+
+    #[test]
+    fn validate_payload_loopback() {
+        let payload = (0..100u8).collect::<Vec<_>>();
+
+        let download = |size2: u32, expected_sum: u32| {
+            let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+
+            let mut writer = Block2Writer::new(1);
+            writer.append(&payload);
+            let writer = Arc::new(Mutex::new(writer));
+            let handler = move |context: &DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+                writer.lock().unwrap().respond(context, |msg_out| {
+                    msg_out.set_msg_code(MsgCode::SuccessContent);
+                    msg_out.insert_option(option::SIZE2, size2)
+                })?;
+                Ok(())
+            };
+
+            let send_desc = CoapRequest::get()
+                .block2(None)
+                .emit_successful_collected_response()
+                .expect_size2_matches()
+                .validate_payload(move |bytes| {
+                    bytes.iter().map(|&b| b as u32).sum::<u32>() == expected_sum
+                });
+
+            let future = local_endpoint.send(LoopbackSocketAddr::Unicast, send_desc);
+            let future_receive = local_endpoint.receive_loop(handler);
+
+            let ret = match block_on(select(future, future_receive)) {
+                Either::Right(_) => panic!("Receive future finished unexpectedly"),
+                Either::Left((ret, _)) => ret.map(|response| response.payload().to_vec()),
+            };
 
+            ret
+        };
+
+        assert_eq!(Ok(payload.clone()), download(100, 4950));
+        assert_eq!(Err(Error::InvalidPayload), download(101, 4950));
+        assert_eq!(Err(Error::InvalidPayload), download(100, 4951));
+    }
+
+    #[test]
+    fn observe_timeouts_loopback() {
+        let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+        let send_desc = CoapRequest::observe()
+            .initial_timeout(Duration::from_millis(300))
+            .notification_timeout(Duration::from_millis(100));
+
+        // Nothing receives the registration, so it is never answered.
+        let stream = local_endpoint.send_as_stream(
+            LoopbackSocketAddr::Unicast,
+            send_desc.clone().emit_successful_response(),
+        );
         assert_eq!(
-            Ok(MsgCode::SuccessContent),
-            test_process_request(&local_endpoint, future)
+            vec![Err(Error::ObserveRegistrationTimeout)],
+            block_on(stream.map(|x| x.map(|_| ())).collect::<Vec<_>>())
+        );
+
+        // The registration is answered, but no notifications follow.
+        let handler = |context: &DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+            context.respond(|msg_out| {
+                msg_out.set_msg_code(MsgCode::SuccessContent);
+                msg_out.insert_option(option::OBSERVE, 1)
+            })
+        };
+        let stream = local_endpoint.send_as_stream(
+            LoopbackSocketAddr::Unicast,
+            send_desc.emit_successful_response(),
         );
+        let future = stream.map(|x| x.map(|_| ())).collect::<Vec<_>>();
+
+        match block_on(select(future, local_endpoint.receive_loop(handler))) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((ret, _)) => assert_eq!(vec![Ok(()), Err(Error::ObservationLapsed)], ret),
+        };
     }
 
-    /// Test that performs a `GET` on <coap://coap.me/separate>
     #[test]
-    fn get_coap_me_separate() {
-        let socket = AllowStdUdpSocket::bind("0.0.0.0:0").expect("UDP bind failed");
-        let local_endpoint = DatagramLocalEndpoint::new(socket);
+    fn observe_drops_stale_notifications() {
+        let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+        let mut stream = local_endpoint.send_as_stream(
+            LoopbackSocketAddr::Unicast,
+            CoapRequest::observe()
+                .use_handler(|context| Ok(ResponseStatus::Done(context?.message().payload()[0]))),
+        );
+        let mut cx = futures::task::Context::from_waker(futures::task::noop_waker_ref());
+        assert!(stream.poll_next_unpin(&mut cx).is_pending());
 
-        let remote_endpoint = local_endpoint
-            .remote_endpoint_from_uri(uri!("coap://coap.me/"))
-            .expect("client construct failed");
+        // The notification with the sequence number 2 arrives after the one with 3, and
+        // the one with 3 is duplicated.
+        let seqs = [1u8, 3, 2, 3, 4];
 
-        let send_desc = CoapRequest::get().inspect(|ctx| {
-            debug!("Got Response: {}", MessageDisplay(ctx.message()));
-            assert_eq!(ctx.message().msg_code(), MsgCode::SuccessContent);
-        });
+        // Failing the handler keeps the local endpoint from responding by itself.
+        let handler = |context: &DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+            for (i, seq) in seqs.iter().enumerate() {
+                let mut buffer = [0u8; 32];
+                let mut builder = BufferMessageEncoder::new(&mut buffer);
+                builder.set_msg_type(MsgType::Con);
+                builder.set_msg_code(MsgCode::SuccessContent);
+                builder.set_msg_id(0x1000 + i as MsgId);
+                builder.set_msg_token(context.message().msg_token());
+                builder.insert_option(option::OBSERVE, u32::from(*seq))?;
+                builder.append_payload_bytes(&[*seq])?;
+                local_endpoint
+                    .socket()
+                    .send_to(&builder, LoopbackSocketAddr::Unicast)
+                    .now_or_never()
+                    .unwrap()?;
+            }
+            Err(Error::Unspecified)
+        };
+        assert_eq!(
+            Err(Error::Unspecified),
+            block_on(local_endpoint.receive(handler))
+        );
 
-        let future = remote_endpoint.send_to(rel_ref!("separate"), send_desc);
+        let messages_out = local_endpoint.stats().messages_out;
+        for _ in seqs.iter() {
+            assert_eq!(
+                Ok(()),
+                block_on(local_endpoint.receive(|_| panic!("Unexpected request")))
+            );
+        }
 
-        assert_eq!(Ok(()), test_process_request(&local_endpoint, future));
+        // Stale notifications are still acknowledged.
+        assert_eq!(
+            seqs.len() as u64,
+            local_endpoint.stats().messages_out - messages_out
+        );
+
+        let mut results = vec![];
+        while let futures::task::Poll::Ready(Some(result)) = stream.poll_next_unpin(&mut cx) {
+            results.push(result);
+        }
+        assert_eq!(vec![Ok(1), Ok(3), Ok(4)], results);
     }
 
-    /// Test that performs a `GET` on <coap://coap.me/large>, which exercises the block2 stuff.
     #[test]
-    fn get_coap_me_large() {
-        let socket = AllowStdUdpSocket::bind("0.0.0.0:0").expect("UDP bind failed");
+    fn stats_loopback() {
+        let socket = LoopbackSocket::new();
         let local_endpoint = DatagramLocalEndpoint::new(socket);
 
-        let remote_endpoint = local_endpoint
-            .remote_endpoint_from_uri(uri!("coap://coap.me/large"))
-            .expect("client construct failed");
+        assert_eq!(EndpointStats::default(), local_endpoint.stats());
 
-        debug!("Requesting <{}>", remote_endpoint.uri());
+        let handler = |context: &DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+            if local_endpoint.stats().handle_request(context)? {
+                return Ok(());
+            }
+            context.respond(|msg_out| {
+                msg_out.set_msg_code(MsgCode::ClientErrorNotFound);
+                Ok(())
+            })
+        };
 
         let send_desc = CoapRequest::get()
-            .accept(ContentFormat::TEXT_PLAIN_UTF8)
-            .block2(None)
-            .emit_successful_collected_response()
-            .inspect(|ctx| {
-                assert!(ctx.message().msg_code().is_success());
-            });
+            .uri_host_path(None, rel_ref!(".well-known/stats"))
+            .emit_successful_response();
 
-        let future = remote_endpoint
-            .send(send_desc);
+        let future = local_endpoint.send(LoopbackSocketAddr::Unicast, send_desc);
+        let future_receive = local_endpoint.receive_loop(handler);
 
-        let future = select(future, Delay::new(Duration::new(5, 0)))
-            .map(|f| {
-                if let Either::Left(x) = f {
-                    x.0
-                }else{
-                    Err(Error::ResponseTimeout)
-                }
-            });
+        let msg = match block_on(select(future, future_receive)) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((ret, _)) => ret.unwrap(),
+        };
 
-        let result = test_process_request(&local_endpoint, future);
-        assert!(result.is_ok(), "{:?}", result);
+        // When the request was handled, only the request itself had been sent and received.
+        assert_eq!(Some(ContentFormat::APPLICATION_JSON), msg.content_format());
+        assert_eq!(
+            Some(concat!(
+                "{\"messages_in\":1,\"messages_out\":1,\"retransmits\":0,",
+                "\"unmatched_responses\":0,\"lenient_matches\":0,\"parse_errors\":0,",
+                "\"unknown_versions\":0,\"active_exchanges\":1}"
+            )),
+            msg.payload_as_str()
+        );
 
-        let collected_message = result.unwrap();
-        let content_string = collected_message.payload_as_str().unwrap();
+        assert_eq!(
+            EndpointStats {
+                messages_in: 2,
+                messages_out: 2,
+                ..Default::default()
+            },
+            local_endpoint.stats()
+        );
+    }
 
-        debug!("Full response: {}", content_string);
-        assert_eq!(1700, content_string.len());
+    #[test]
+    fn prepared_loopback() {
+        use std::fmt::Write;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let socket = LoopbackSocket::new();
+        let local_endpoint = DatagramLocalEndpoint::new(socket);
+
+        let handler = |context: &DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+            let path = context.message().options().extract_uri()?;
+            let payload = context.message().payload_as_str().unwrap_or_default();
+            context.respond(|msg_out| {
+                msg_out.set_msg_code(MsgCode::SuccessContent);
+                write!(msg_out, "{} {}", path, payload)?;
+                Ok(())
+            })
+        };
+
+        let writes = Arc::new(AtomicUsize::new(0));
+        let writes_clone = writes.clone();
+        let send_desc = CoapRequest::post()
+            .uri_host_path(None, rel_ref!("a/b"))
+            .payload_writer(move |msg_out| {
+                writes_clone.fetch_add(1, Ordering::SeqCst);
+                msg_out.set_msg_code(MsgCode::MethodPost);
+                msg_out.append_payload_string("hello")
+            })
+            .prepare();
+
+        for _ in 0..3 {
+            let future = local_endpoint.send(
+                LoopbackSocketAddr::Unicast,
+                send_desc.clone().emit_successful_response(),
+            );
+            let future_receive = local_endpoint.receive_loop(handler);
+
+            let response = match block_on(select(future, future_receive)) {
+                Either::Right(_) => panic!("Receive future finished unexpectedly"),
+                Either::Left((ret, _)) => ret.unwrap(),
+            };
+
+            assert_eq!(Some("a/b hello"), response.payload_as_str());
+        }
+
+        assert!(send_desc.is_prepared());
+        assert_eq!(1, writes.load(Ordering::SeqCst));
     }
 
-    /// Test that performs a `GET` on <coap://coap.me/path/sub1>
     #[test]
-    fn get_coap_me_path_sub1() {
-        let socket = AllowStdUdpSocket::bind("0.0.0.0:0").expect("UDP bind failed");
+    fn latency_duplication_loopback() {
+        let socket = LoopbackSocket::new()
+            .with_latency(std::time::Duration::from_millis(20))
+            .with_duplication(1.0);
         let local_endpoint = DatagramLocalEndpoint::new(socket);
 
-        let remote_endpoint = local_endpoint
-            .remote_endpoint_from_uri(uri!("coap://coap.me/"))
-            .expect("client construct failed");
+        let handler = |context: &DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+            context.respond(|msg_out| {
+                msg_out.set_msg_code(MsgCode::SuccessContent);
+                Ok(())
+            })
+        };
 
-        let send_desc = CoapRequest::get()
-            .accept(ContentFormat::APPLICATION_JSON)
-            .add_option_iter(option::URI_PATH, vec!["path", "sub1"])
-            .inspect(|ctx| {
-                assert_eq!(ctx.message().msg_code(), MsgCode::SuccessContent);
-                assert_eq!(
-                    ctx.message().content_format(),
-                    Some(ContentFormat::APPLICATION_JSON)
-                );
-            });
+        let send_desc = CoapRequest::get().emit_successful_response();
 
-        let future = remote_endpoint.send(send_desc);
+        let future = local_endpoint.send(LoopbackSocketAddr::Unicast, send_desc);
+        let future_receive = local_endpoint.receive_loop(handler);
 
-        assert_eq!(Ok(()), test_process_request(&local_endpoint, future));
+        let response = match block_on(select(future, future_receive)) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((ret, _)) => ret.unwrap(),
+        };
+
+        // Both the request and the response were delayed.
+        assert!(response.elapsed() >= std::time::Duration::from_millis(40));
+
+        // Both copies of the request arrived before the response.
+        assert!(local_endpoint.stats().messages_in >= 3);
     }
 
     #[test]
-    fn client_get_coap_me_path_sub2() {
-        let socket = AllowStdUdpSocket::bind("0.0.0.0:0").expect("UDP bind failed");
+    fn dupe_count_loopback() {
+        let socket = LoopbackSocket::new().with_duplication(1.0);
+        let local_endpoint = DatagramLocalEndpoint::new(socket);
+        let dupe_counts = Mutex::new(Vec::new());
+
+        let handler = |context: &DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+            dupe_counts
+                .lock()
+                .unwrap()
+                .push((context.is_dupe(), context.dupe_count()));
+            context.respond(|msg_out| {
+                msg_out.set_msg_code(MsgCode::SuccessContent);
+                Ok(())
+            })
+        };
+
+        for _ in 0..2 {
+            let future = local_endpoint.send(
+                LoopbackSocketAddr::Unicast,
+                CoapRequest::get().emit_successful_response(),
+            );
+            match block_on(select(future, local_endpoint.receive_loop(handler))) {
+                Either::Right(_) => panic!("Receive future finished unexpectedly"),
+                Either::Left((ret, _)) => assert!(ret.is_ok()),
+            };
+        }
+
+        // Each request has a new message id, so only the copies are duplicates.
+        assert_eq!(
+            vec![(false, 0), (true, 1), (false, 0), (true, 1)],
+            *dupe_counts.lock().unwrap()
+        );
+    }
 
+    #[test]
+    fn parse_error_loopback() {
+        let socket = LoopbackSocket::new();
         let local_endpoint = DatagramLocalEndpoint::new(socket);
 
-        let remote_endpoint = local_endpoint
-            .remote_endpoint_from_uri(uri!("coap://coap.me/path/"))
-            .expect("client construct failed");
+        let diagnostics = Arc::new(Mutex::new(Vec::new()));
+        let diagnostics_clone = diagnostics.clone();
+        local_endpoint.set_parse_error_handler(move |diagnostic| {
+            diagnostics_clone.lock().unwrap().push((
+                diagnostic.remote_socket_addr(),
+                diagnostic.reason(),
+                diagnostic.raw_bytes().to_vec(),
+            ));
+        });
 
-        let send_desc = CoapRequest::get()
-            .accept(ContentFormat::APPLICATION_JSON)
-            .add_option_iter(option::URI_PATH, vec![])
-            .inspect(|ctx| {
-                assert_eq!(ctx.message().msg_code(), MsgCode::SuccessContent);
-            });
+        let truncated = [0x42, 0x01, 0x00, 0x01, 0xAA];
+        block_on(
+            local_endpoint
+                .socket()
+                .send_to(&truncated, LoopbackSocketAddr::Unicast),
+        )
+        .unwrap();
 
-        let future = remote_endpoint.send_to(rel_ref!("sub2"), send_desc);
+        assert_eq!(
+            Err(Error::ParseFailure),
+            block_on(local_endpoint.receive(null_receiver!()))
+        );
+        assert_eq!(
+            vec![(
+                LoopbackSocketAddr::Unicast,
+                ParseFailureReason::TruncatedToken,
+                truncated.to_vec()
+            )],
+            *diagnostics.lock().unwrap()
+        );
+        assert_eq!(1, local_endpoint.stats().parse_errors);
+
+        // Well-formed requests are available as raw bytes, too.
+        let request = [0x50, 0x01, 0x00, 0x02];
+        block_on(
+            local_endpoint
+                .socket()
+                .send_to(&request, LoopbackSocketAddr::Unicast),
+        )
+        .unwrap();
+
+        let raw_bytes = Mutex::new(Vec::new());
+        block_on(local_endpoint.receive(|context| {
+            *raw_bytes.lock().unwrap() = context.raw_bytes().to_vec();
+            Ok(())
+        }))
+        .unwrap();
+        assert_eq!(request.to_vec(), *raw_bytes.lock().unwrap());
+        assert_eq!(1, diagnostics.lock().unwrap().len());
+    }
 
-        assert_eq!(Ok(()), test_process_request(&local_endpoint, future));
+    #[test]
+    fn version_policy_loopback() {
+        let socket = LoopbackSocket::new();
+        let local_endpoint = DatagramLocalEndpoint::new(socket);
+
+        let versions = Arc::new(Mutex::new(Vec::new()));
+        let versions_clone = versions.clone();
+        local_endpoint.set_parse_error_handler(move |diagnostic| {
+            let msg = diagnostic.message().unwrap();
+            versions_clone.lock().unwrap().push((
+                diagnostic.version(),
+                msg.msg_code(),
+                msg.msg_id(),
+            ));
+        });
+
+        // Version 2 GET, which must never reach the request handler.
+        let future_version = [0x90, 0x01, 0x00, 0x03];
+        let receive_future_version = || {
+            block_on(
+                local_endpoint
+                    .socket()
+                    .send_to(&future_version, LoopbackSocketAddr::Unicast),
+            )
+            .unwrap();
+            block_on(local_endpoint.receive(|_| panic!("Handler called")))
+        };
+
+        assert_eq!(Err(Error::ParseFailure), receive_future_version());
+        assert_eq!(1, local_endpoint.stats().parse_errors);
+        assert_eq!(0, local_endpoint.stats().unknown_versions);
+
+        local_endpoint.set_version_policy(VersionPolicy::Tolerant);
+        assert_eq!(Ok(()), receive_future_version());
+        assert_eq!(1, local_endpoint.stats().parse_errors);
+        assert_eq!(1, local_endpoint.stats().unknown_versions);
+
+        assert_eq!(
+            vec![
+                (Some(2), MsgCode::MethodGet, 3),
+                (Some(2), MsgCode::MethodGet, 3)
+            ],
+            *versions.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn send_with_cancel() {
+        let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+        let mut cx = futures::task::Context::from_waker(futures::task::noop_waker_ref());
+
+        // Nothing is sent if the token was cancelled before the future is polled.
+        let token = CancellationToken::new();
+        token.cancel();
+        let mut future = local_endpoint.send_with_cancel(
+            LoopbackSocketAddr::Unicast,
+            CoapRequest::get().emit_msg_code(),
+            token,
+        );
+        assert_eq!(
+            futures::task::Poll::Ready(Err(Error::Cancelled)),
+            future.poll_unpin(&mut cx)
+        );
+        assert_eq!(0, local_endpoint.stats().messages_out);
+
+        let token = CancellationToken::new();
+        let mut future = local_endpoint.send_with_cancel(
+            LoopbackSocketAddr::Unicast,
+            CoapRequest::get().emit_msg_code(),
+            token.clone(),
+        );
+        assert!(future.poll_unpin(&mut cx).is_pending());
+        assert_eq!(1, local_endpoint.stats().messages_out);
+        assert_eq!(1, local_endpoint.stats().active_exchanges);
+
+        token.cancel();
+        assert_eq!(
+            futures::task::Poll::Ready(Err(Error::Cancelled)),
+            future.poll_unpin(&mut cx)
+        );
+
+        // The transaction ended as soon as it was cancelled.
+        assert_eq!(0, local_endpoint.stats().active_exchanges);
+    }
+
+    #[test]
+    fn unreachable_loopback() {
+        let socket = LoopbackSocket::new();
+        socket.set_unreachable(true);
+        let local_endpoint = DatagramLocalEndpoint::new(socket);
+
+        let send_desc = CoapRequest::get()
+            .uri_host_path(None, rel_ref!("test"))
+            .emit_successful_response();
+
+        let start = std::time::Instant::now();
+        let future = local_endpoint.send(LoopbackSocketAddr::Unicast, send_desc);
+        let future_receive = local_endpoint.receive_loop(null_receiver!());
+
+        match block_on(select(future, future_receive)) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((ret, _)) => assert_eq!(Err(Error::HostUnreachable), ret),
+        };
+
+        // The first retransmission would have happened after at least two seconds.
+        assert!(start.elapsed() < std::time::Duration::from_secs(1));
+        assert_eq!(0, local_endpoint.stats().active_exchanges);
+    }
+
+    #[test]
+    fn failure_details_unreachable() {
+        let socket = LoopbackSocket::new();
+        socket.set_unreachable(true);
+        let local_endpoint = DatagramLocalEndpoint::new(socket);
+
+        let send_desc = CoapRequest::get().emit_successful_response();
+        let future =
+            local_endpoint.send_with_failure_details(LoopbackSocketAddr::Unicast, send_desc);
+        let future_receive = local_endpoint.receive_loop(null_receiver!());
+
+        let failure = match block_on(select(future, future_receive)) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((ret, _)) => ret.unwrap_err(),
+        };
+
+        assert_eq!(Error::HostUnreachable, failure.error);
+        assert_eq!(1, failure.attempts);
+        assert_eq!(Some(FailureCause::Unreachable), failure.cause);
+    }
+
+    /// Retransmits every few milliseconds, so that a request to a silent host fails
+    /// quickly.
+    struct FastRetransmit<SD>(SD);
+
+    impl<SD, IC, R> SendDesc<IC, R> for FastRetransmit<SD>
+    where
+        SD: SendDesc<IC, R>,
+        IC: InboundContext,
+        R: Send,
+    {
+        fn delay_to_retransmit(&self, retransmits_sent: u32) -> Option<Duration> {
+            if retransmits_sent < 2 {
+                Some(Duration::from_millis(5))
+            } else {
+                None
+            }
+        }
+
+        fn max_rtt(&self) -> Duration {
+            Duration::from_millis(5)
+        }
+
+        send_desc_passthru_options!(0);
+        send_desc_passthru_payload!(0);
+        send_desc_passthru_handler!(0, R);
+    }
+
+    #[test]
+    fn failure_details_silent() {
+        let local_endpoint = DatagramLocalEndpoint::new(NullSocket::new());
+
+        let send_desc = FastRetransmit(CoapRequest::get().emit_successful_response());
+        let failure = block_on(local_endpoint.send_with_failure_details(NullSocketAddr, send_desc))
+            .unwrap_err();
+
+        assert_eq!(Error::ResponseTimeout, failure.error);
+        assert_eq!(3, failure.attempts);
+        assert_eq!(Some(FailureCause::NoAck), failure.cause);
+        assert!(failure.elapsed >= Duration::from_millis(15));
+    }
+
+    #[test]
+    fn path_mtu_loopback() {
+        let socket = LoopbackSocket::new();
+        let local_endpoint = DatagramLocalEndpoint::new(socket);
+
+        local_endpoint.set_path_mtu(LoopbackSocketAddr::Unicast, Some(32));
+        assert_eq!(
+            Some(32),
+            local_endpoint.path_mtu(LoopbackSocketAddr::Unicast)
+        );
+        assert_eq!(None, local_endpoint.path_mtu(LoopbackSocketAddr::Multicast));
+
+        let handler = |context: &DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+            context.respond(|msg_out| {
+                msg_out.set_msg_code(MsgCode::SuccessContent);
+                Ok(())
+            })
+        };
+
+        let send_desc = |path| {
+            CoapRequest::get()
+                .uri_host_path(None, path)
+                .emit_successful_response()
+        };
+
+        // This request is too large, so it fails without anything being sent.
+        let future = local_endpoint.send(
+            LoopbackSocketAddr::Unicast,
+            send_desc(rel_ref!("this/path/is/much/too/long/to/fit")),
+        );
+        let future_receive = local_endpoint.receive_loop(handler);
+
+        match block_on(select(future, future_receive)) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((ret, _)) => assert_eq!(Err(Error::MessageTooLarge), ret.map(|_| ())),
+        };
+        assert_eq!(0, local_endpoint.stats().messages_out);
+
+        // This one fits.
+        let future = local_endpoint.send(LoopbackSocketAddr::Unicast, send_desc(rel_ref!("short")));
+        let future_receive = local_endpoint.receive_loop(handler);
+
+        match block_on(select(future, future_receive)) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((ret, _)) => assert!(ret.is_ok()),
+        };
+
+        local_endpoint.set_path_mtu(LoopbackSocketAddr::Unicast, None);
+        assert_eq!(None, local_endpoint.path_mtu(LoopbackSocketAddr::Unicast));
+    }
+
+    #[test]
+    fn max_message_size_loopback() {
+        let socket = LoopbackSocket::new();
+        let local_endpoint = DatagramLocalEndpoint::new(socket);
+
+        assert_eq!(1152, local_endpoint.max_inbound_message_size());
+        assert_eq!(1152, local_endpoint.max_outbound_message_size());
+
+        let handler = |context: &DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+            context.respond(|msg_out| {
+                msg_out.set_msg_code(MsgCode::SuccessContent);
+                msg_out.append_payload_bytes(context.message().payload())
+            })
+        };
+
+        let send_desc = |len| {
+            CoapRequest::post()
+                .payload_writer(move |msg_out| {
+                    msg_out.set_msg_code(MsgCode::MethodPost);
+                    msg_out.append_payload_bytes(&vec![0x55; len])
+                })
+                .emit_successful_response()
+        };
+
+        // Larger than the default, so it fails without anything being sent.
+        let future = local_endpoint.send(LoopbackSocketAddr::Unicast, send_desc(2000));
+        let future_receive = local_endpoint.receive_loop(handler);
+
+        match block_on(select(future, future_receive)) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((ret, _)) => assert_eq!(Err(Error::OutOfSpace), ret.map(|_| ())),
+        };
+        assert_eq!(0, local_endpoint.stats().messages_out);
+
+        // It fits once both limits are raised.
+        local_endpoint.set_max_inbound_message_size(4096);
+        local_endpoint.set_max_outbound_message_size(4096);
+
+        let future = local_endpoint.send(LoopbackSocketAddr::Unicast, send_desc(2000));
+        let future_receive = local_endpoint.receive_loop(handler);
+
+        match block_on(select(future, future_receive)) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((ret, _)) => assert!(ret.is_ok()),
+        };
+
+        // Oversized datagrams are dropped before they are parsed.
+        local_endpoint.set_max_inbound_message_size(64);
+        let parse_errors = local_endpoint.stats().parse_errors;
+
+        let mut request = vec![0x50, 0x02, 0x00, 0x01, 0xFF];
+        request.resize(100, 0x55);
+        block_on(
+            local_endpoint
+                .socket()
+                .send_to(&request, LoopbackSocketAddr::Unicast),
+        )
+        .unwrap();
+
+        assert_eq!(
+            Err(Error::MessageTooLarge),
+            block_on(local_endpoint.receive(|_| panic!("Handler called")))
+        );
+        assert_eq!(parse_errors + 1, local_endpoint.stats().parse_errors);
+    }
+
+    #[test]
+    fn remote_endpoint_from_ip_literal() {
+        let socket = AllowStdUdpSocket::bind("[::]:0").expect("UDP bind failed");
+        let local_endpoint = DatagramLocalEndpoint::new(socket);
+
+        let remote_endpoint = local_endpoint
+            .remote_endpoint_from_uri(uri!("coap://[::1]:1234/a/b"))
+            .expect("remote_endpoint_from_uri failed");
+        assert_eq!(remote_endpoint.uri(), uri!("coap://[::1]:1234/a/b"));
+
+        let remote_endpoint = local_endpoint
+            .remote_endpoint_from_uri(uri!("coap://[::1]/"))
+            .expect("remote_endpoint_from_uri failed");
+        assert_eq!(remote_endpoint.uri(), uri!("coap://[::1]/"));
+    }
+
+    /// Test that verifies that timeouts are working properly.
+    /// This can currently take a while to execute, so it is currently disabled.
+    #[test]
+    #[ignore]
+    fn ping_null() {
+        let socket = NullSocket::new();
+        let local_endpoint = DatagramLocalEndpoint::new(socket);
+        let dest = NullSocketAddr;
+        let send_desc = Ping::new();
+        let future = local_endpoint.send(dest, send_desc);
+
+        assert_eq!(
+            Err(Error::ResponseTimeout),
+            test_process_request(&local_endpoint, future)
+        );
+    }
+
+    #[test]
+    fn ping_localhost() {
+        let socket = AllowStdUdpSocket::bind("127.0.0.1:12345").expect("UDP bind failed");
+        let local_endpoint = DatagramLocalEndpoint::new(socket);
+        let dest = "127.0.0.1:12345";
+        let send_desc = Ping::new();
+        let future = local_endpoint.send(dest, send_desc);
+
+        assert_eq!(Ok(()), test_process_request(&local_endpoint, future));
+    }
+
+    #[test]
+    fn ping_coap_me() {
+        let socket = AllowStdUdpSocket::bind("0.0.0.0:0").expect("UDP bind failed");
+        let local_endpoint = DatagramLocalEndpoint::new(socket);
+
+        let mut lookup_results = local_endpoint
+            .lookup("coap.me", 5683)
+            .expect("DNS lookup failure");
+        let dest = block_on(lookup_results.next()).expect("DNS lookup failure");
+        let send_desc = Ping::new();
+        let future = local_endpoint.send(dest, send_desc);
+
+        assert_eq!(Ok(()), test_process_request(&local_endpoint, future));
+    }
+
+    #[test]
+    fn host_lookup_failure() {
+        let socket = AllowStdUdpSocket::bind("[::]:0").expect("UDP bind failed");
+        let local_endpoint = DatagramLocalEndpoint::new(socket);
+
+        let dest = "[127.0.0.1]:5683";
+        let send_desc = Ping::new();
+        let future = local_endpoint.send(dest, send_desc);
+
+        assert_eq!(
+            Err(Error::HostLookupFailure),
+            test_process_request(&local_endpoint, future)
+        );
+    }
+
+    /// Test that performs a `GET` on <coap://coap.me/test>
+    #[test]
+    fn get_coap_me_test() {
+        let socket = AllowStdUdpSocket::bind("0.0.0.0:0").expect("UDP bind failed");
+        let local_endpoint = DatagramLocalEndpoint::new(socket);
+
+        let remote_endpoint = local_endpoint
+            .remote_endpoint_from_uri(uri!("coap://coap.me/"))
+            .expect("client construct failed");
+
+        let send_desc = CoapRequest::get()
+            .add_option(option::ACCEPT, ContentFormat::APPLICATION_JSON)
+            .inspect(|ctx| {
+                debug!("Got Response: {}", MessageDisplay(ctx.message()));
+                assert_eq!(ctx.message().msg_code(), MsgCode::SuccessContent);
+            });
+
+        let future = remote_endpoint.send_to(rel_ref!("test"), send_desc);
+
+        assert_eq!(Ok(()), test_process_request(&local_endpoint, future));
+    }
+
+    /// Test that performs a `GET` on <coap://coap.me/bl%C3%A5b%C3%A6rsyltet%C3%B8y>
+    #[test]
+    fn get_coap_me_blabaersyltetoy() {
+        let socket = AllowStdUdpSocket::bind("0.0.0.0:0").expect("UDP bind failed");
+        let local_endpoint = DatagramLocalEndpoint::new(socket);
+
+        let remote_endpoint = local_endpoint
+            .remote_endpoint_from_uri(uri!("coap://coap.me/bl%C3%A5b%C3%A6rsyltet%C3%B8y"))
+            .expect("client construct failed");
+
+        let send_desc = CoapRequest::get().emit_msg_code();
+
+        let future = remote_endpoint.send(send_desc);
+
+        assert_eq!(
+            Ok(MsgCode::SuccessContent),
+            test_process_request(&local_endpoint, future)
+        );
+    }
+
+    /// Test that performs a `GET` on <coap://coap.me/separate>
+    #[test]
+    fn get_coap_me_separate() {
+        let socket = AllowStdUdpSocket::bind("0.0.0.0:0").expect("UDP bind failed");
+        let local_endpoint = DatagramLocalEndpoint::new(socket);
+
+        let remote_endpoint = local_endpoint
+            .remote_endpoint_from_uri(uri!("coap://coap.me/"))
+            .expect("client construct failed");
+
+        let send_desc = CoapRequest::get().inspect(|ctx| {
+            debug!("Got Response: {}", MessageDisplay(ctx.message()));
+            assert_eq!(ctx.message().msg_code(), MsgCode::SuccessContent);
+        });
+
+        let future = remote_endpoint.send_to(rel_ref!("separate"), send_desc);
+
+        assert_eq!(Ok(()), test_process_request(&local_endpoint, future));
+    }
+
+    /// Test that performs a `GET` on <coap://coap.me/large>, which exercises the block2 stuff.
+    #[test]
+    fn get_coap_me_large() {
+        let socket = AllowStdUdpSocket::bind("0.0.0.0:0").expect("UDP bind failed");
+        let local_endpoint = DatagramLocalEndpoint::new(socket);
+
+        let remote_endpoint = local_endpoint
+            .remote_endpoint_from_uri(uri!("coap://coap.me/large"))
+            .expect("client construct failed");
+
+        debug!("Requesting <{}>", remote_endpoint.uri());
+
+        let send_desc = CoapRequest::get()
+            .accept(ContentFormat::TEXT_PLAIN_UTF8)
+            .block2(None)
+            .emit_successful_collected_response()
+            .inspect(|ctx| {
+                assert!(ctx.message().msg_code().is_success());
+            });
+
+        let future = remote_endpoint
+            .send(send_desc);
+
+        let future = select(future, Delay::new(Duration::new(5, 0)))
+            .map(|f| {
+                if let Either::Left(x) = f {
+                    x.0
+                }else{
+                    Err(Error::ResponseTimeout)
+                }
+            });
+
+        let result = test_process_request(&local_endpoint, future);
+        assert!(result.is_ok(), "{:?}", result);
+
+        let collected_message = result.unwrap();
+        let content_string = collected_message.payload_as_str().unwrap();
+
+        debug!("Full response: {}", content_string);
+        assert_eq!(1700, content_string.len());
+    }
+
+    /// Test that performs a `GET` on <coap://coap.me/path/sub1>
+    #[test]
+    fn get_coap_me_path_sub1() {
+        let socket = AllowStdUdpSocket::bind("0.0.0.0:0").expect("UDP bind failed");
+        let local_endpoint = DatagramLocalEndpoint::new(socket);
+
+        let remote_endpoint = local_endpoint
+            .remote_endpoint_from_uri(uri!("coap://coap.me/"))
+            .expect("client construct failed");
+
+        let send_desc = CoapRequest::get()
+            .accept(ContentFormat::APPLICATION_JSON)
+            .add_option_iter(option::URI_PATH, vec!["path", "sub1"])
+            .inspect(|ctx| {
+                assert_eq!(ctx.message().msg_code(), MsgCode::SuccessContent);
+                assert_eq!(
+                    ctx.message().content_format(),
+                    Some(ContentFormat::APPLICATION_JSON)
+                );
+            });
+
+        let future = remote_endpoint.send(send_desc);
+
+        assert_eq!(Ok(()), test_process_request(&local_endpoint, future));
+    }
+
+    #[test]
+    fn client_get_coap_me_path_sub2() {
+        let socket = AllowStdUdpSocket::bind("0.0.0.0:0").expect("UDP bind failed");
+
+        let local_endpoint = DatagramLocalEndpoint::new(socket);
+
+        let remote_endpoint = local_endpoint
+            .remote_endpoint_from_uri(uri!("coap://coap.me/path/"))
+            .expect("client construct failed");
+
+        let send_desc = CoapRequest::get()
+            .accept(ContentFormat::APPLICATION_JSON)
+            .add_option_iter(option::URI_PATH, vec![])
+            .inspect(|ctx| {
+                assert_eq!(ctx.message().msg_code(), MsgCode::SuccessContent);
+            });
+
+        let future = remote_endpoint.send_to(rel_ref!("sub2"), send_desc);
+
+        assert_eq!(Ok(()), test_process_request(&local_endpoint, future));
+    }
+
+    #[test]
+    fn accept_any_of_loopback() {
+        let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+
+        let handler = |context: &DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+            let accept = context.message().accept();
+            context.respond(|msg_out| {
+                msg_out.set_msg_code(match accept {
+                    Some(ContentFormat::APPLICATION_CBOR) => MsgCode::SuccessContent,
+                    _ => MsgCode::ClientErrorNotAcceptable,
+                });
+                Ok(())
+            })
+        };
+
+        let send = |formats: &[ContentFormat]| {
+            let future = local_endpoint.send(
+                LoopbackSocketAddr::Unicast,
+                CoapRequest::get().emit_msg_code().accept_any_of(formats),
+            );
+
+            match block_on(select(future, local_endpoint.receive_loop(handler))) {
+                Either::Right(_) => panic!("Receive future finished unexpectedly"),
+                Either::Left((ret, _)) => ret,
+            }
+        };
+
+        assert_eq!(
+            Ok((MsgCode::SuccessContent, ContentFormat::APPLICATION_CBOR)),
+            send(&[
+                ContentFormat::APPLICATION_JSON,
+                ContentFormat::TEXT_PLAIN_UTF8,
+                ContentFormat::APPLICATION_CBOR
+            ])
+        );
+
+        // Once all of the formats are rejected, the final response is passed along.
+        assert_eq!(
+            Err(Error::ClientRequestError),
+            send(&[
+                ContentFormat::APPLICATION_JSON,
+                ContentFormat::TEXT_PLAIN_UTF8
+            ])
+        );
+    }
+
+    #[test]
+    fn send_with_status() {
+        let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+
+        let (mut future, handle) = local_endpoint.send_with_status(
+            LoopbackSocketAddr::Unicast,
+            CoapRequest::get().emit_msg_code(),
+        );
+
+        let status = handle.status().unwrap();
+        assert_eq!(SendState::NotSent, status.state);
+        assert_eq!(LoopbackSocketAddr::Unicast, status.dest);
+
+        let mut cx = futures::task::Context::from_waker(futures::task::noop_waker_ref());
+        assert!(future.poll_unpin(&mut cx).is_pending());
+
+        let status = handle.status().unwrap();
+        assert_eq!(SendState::WaitingForAck, status.state);
+        assert_ne!(0, status.msg_id);
+        assert_eq!(0, status.retransmit_count);
+        assert!(status.next_timeout.is_some());
+
+        let handler = |context: &DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+            context.respond(|msg_out| {
+                msg_out.set_msg_code(MsgCode::SuccessContent);
+                Ok(())
+            })
+        };
+
+        match block_on(select(future, local_endpoint.receive_loop(handler))) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((ret, _)) => assert_eq!(Ok(MsgCode::SuccessContent), ret),
+        };
+
+        assert_eq!(None, handle.status());
+    }
+
+    #[test]
+    fn random_source_used_for_retransmit_delay() {
+        use std::sync::atomic::AtomicUsize;
+
+        #[derive(Default)]
+        struct CountingRandom(AtomicUsize);
+
+        impl RandomSource for Arc<CountingRandom> {
+            fn next_u64(&self) -> u64 {
+                self.0.fetch_add(1, Ordering::Relaxed);
+                0
+            }
+        }
+
+        let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+        let random = Arc::new(CountingRandom::default());
+        local_endpoint.set_random_source(random.clone());
+
+        let (mut future, handle) = local_endpoint.send_with_status(
+            LoopbackSocketAddr::Unicast,
+            CoapRequest::get().emit_msg_code(),
+        );
+
+        let mut cx = futures::task::Context::from_waker(futures::task::noop_waker_ref());
+        assert!(future.poll_unpin(&mut cx).is_pending());
+
+        assert_eq!(1, random.0.load(Ordering::Relaxed));
+        assert_eq!(SendState::WaitingForAck, handle.status().unwrap().state);
+    }
+
+    #[test]
+    fn adaptive_ack_timeout() {
+        let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+        let dest = LoopbackSocketAddr::Unicast;
+
+        assert_eq!(None, local_endpoint.rtt_estimate(dest));
+
+        let future = local_endpoint.send(dest, Ping::new());
+        assert_eq!(Ok(()), test_process_request(&local_endpoint, future));
+
+        let estimate = local_endpoint.rtt_estimate(dest).expect("No RTT estimate");
+        assert_eq!(1, estimate.samples);
+        assert!(estimate.srtt < Duration::from_secs(1));
+
+        let next_timeout = |local_endpoint: &DatagramLocalEndpoint<LoopbackSocket>| {
+            let (mut future, handle) =
+                local_endpoint.send_with_status(dest, CoapRequest::get().emit_msg_code());
+            let mut cx = futures::task::Context::from_waker(futures::task::noop_waker_ref());
+            assert!(future.poll_unpin(&mut cx).is_pending());
+            let status = handle.status().unwrap();
+            status.next_timeout.unwrap() - std::time::Instant::now()
+        };
+
+        // By default, the first retransmission is after at least ACK_TIMEOUT.
+        assert!(next_timeout(&local_endpoint) > Duration::from_millis(1500));
+
+        // With the adaptive ACK timeout, it is after the minimum (plus jitter) instead.
+        local_endpoint.set_adaptive_ack_timeout(Some(AdaptiveAckTimeout::default()));
+        local_endpoint.set_random_source(NoJitter);
+        assert!(next_timeout(&local_endpoint) <= Duration::from_millis(200));
+    }
+
+    #[test]
+    fn request_coalescing() {
+        let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+        let dest = LoopbackSocketAddr::Unicast;
+        local_endpoint.set_request_coalescing(true);
+
+        let mut first = local_endpoint.send(dest, CoapRequest::get().emit_msg_code());
+        let mut second = local_endpoint.send(dest, CoapRequest::get().emit_msg_code());
+        let mut cx = futures::task::Context::from_waker(futures::task::noop_waker_ref());
+        assert!(first.poll_unpin(&mut cx).is_pending());
+        assert!(second.poll_unpin(&mut cx).is_pending());
+
+        // Only the first request is sent, and its response is received by both.
+        let mut requests = 0;
+        assert_eq!(
+            Ok(()),
+            block_on(local_endpoint.receive(|context| {
+                requests += 1;
+                context.respond(|msg_out| {
+                    msg_out.set_msg_code(MsgCode::SuccessContent);
+                    Ok(())
+                })
+            }))
+        );
+        assert_eq!(1, requests);
+        assert_eq!(
+            Ok(()),
+            block_on(local_endpoint.receive(|_| panic!("Unexpected request")))
+        );
+
+        let first = match first.poll_unpin(&mut cx) {
+            futures::task::Poll::Ready(result) => result,
+            futures::task::Poll::Pending => panic!("First request still pending"),
+        };
+        assert_eq!(Ok(MsgCode::SuccessContent), first);
+        assert_eq!(
+            futures::task::Poll::Ready(first),
+            second.poll_unpin(&mut cx)
+        );
+
+        // The request and its response.
+        assert_eq!(2, local_endpoint.stats().messages_out);
+    }
+
+    #[test]
+    fn request_coalescing_leader_dropped() {
+        let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+        let dest = LoopbackSocketAddr::Unicast;
+        local_endpoint.set_request_coalescing(true);
+
+        let mut first = local_endpoint.send(dest, CoapRequest::get().emit_msg_code());
+        let mut second = local_endpoint.send(dest, CoapRequest::get().emit_msg_code());
+        let mut cx = futures::task::Context::from_waker(futures::task::noop_waker_ref());
+        assert!(first.poll_unpin(&mut cx).is_pending());
+        assert!(second.poll_unpin(&mut cx).is_pending());
+
+        // Once the first request is cancelled, the second one is sent after all.
+        drop(first);
+        assert_eq!(
+            Err(Error::ServerError),
+            test_process_request(&local_endpoint, second)
+        );
+
+        // Both requests and their responses.
+        assert_eq!(4, local_endpoint.stats().messages_out);
+    }
+
+    #[test]
+    fn shutdown_cancels_pending_futures() {
+        let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+        let dest = LoopbackSocketAddr::Unicast;
+        let mut cx = futures::task::Context::from_waker(futures::task::noop_waker_ref());
+
+        let mut receive = local_endpoint.receive(|_| panic!("Unexpected request"));
+        assert!(receive.poll_unpin(&mut cx).is_pending());
+
+        let mut send = local_endpoint.send(dest, CoapRequest::get().emit_msg_code());
+        assert!(send.poll_unpin(&mut cx).is_pending());
+
+        let mut stream = local_endpoint.send_as_stream(dest, CoapRequest::observe());
+        assert!(stream.poll_next_unpin(&mut cx).is_pending());
+
+        local_endpoint.shutdown();
+        assert!(local_endpoint.is_shut_down());
+
+        assert_eq!(
+            futures::task::Poll::Ready(Err(Error::Cancelled)),
+            receive.poll_unpin(&mut cx)
+        );
+        assert_eq!(
+            futures::task::Poll::Ready(Err(Error::Cancelled)),
+            send.poll_unpin(&mut cx)
+        );
+        assert_eq!(Some(Err(Error::Cancelled)), block_on(stream.next()));
+        assert_eq!(None, block_on(stream.next()));
+
+        // Anything started after shutting down fails immediately.
+        assert_eq!(
+            Err(Error::Cancelled),
+            block_on(local_endpoint.send(dest, Ping::new()))
+        );
+        assert_eq!(
+            Err(Error::Cancelled),
+            block_on(local_endpoint.receive(|_| panic!("Unexpected request")))
+        );
+    }
+
+    #[test]
+    fn drop_cancels_pending_futures() {
+        let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+        let remote_endpoint = local_endpoint.remote_endpoint(
+            LoopbackSocketAddr::Unicast,
+            None::<String>,
+            rel_ref!(""),
+        );
+        let mut cx = futures::task::Context::from_waker(futures::task::noop_waker_ref());
+
+        let mut send = remote_endpoint.send(CoapRequest::get().emit_msg_code());
+        assert!(send.poll_unpin(&mut cx).is_pending());
+
+        drop(local_endpoint);
+
+        assert_eq!(
+            futures::task::Poll::Ready(Err(Error::Cancelled)),
+            send.poll_unpin(&mut cx)
+        );
+    }
+
+    #[test]
+    fn lenient_msg_id_matching() {
+        let send_bad_ack = |local_endpoint: DatagramLocalEndpoint<LoopbackSocket>| {
+            let mut future = local_endpoint.send(
+                LoopbackSocketAddr::Unicast,
+                CoapRequest::get().emit_msg_code(),
+            );
+            let mut cx = futures::task::Context::from_waker(futures::task::noop_waker_ref());
+            assert!(future.poll_unpin(&mut cx).is_pending());
+
+            // Answer the request with a piggybacked response with the wrong message id.
+            // Failing the handler keeps the local endpoint from responding by itself.
+            let handler = |context: &DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+                let request = context.message();
+                let mut buffer = [0u8; 32];
+                let mut builder = BufferMessageEncoder::new(&mut buffer);
+                builder.set_msg_type(MsgType::Ack);
+                builder.set_msg_code(MsgCode::SuccessContent);
+                builder.set_msg_id(request.msg_id().wrapping_add(1));
+                builder.set_msg_token(request.msg_token());
+                local_endpoint
+                    .socket()
+                    .send_to(&builder, LoopbackSocketAddr::Unicast)
+                    .now_or_never()
+                    .unwrap()?;
+                Err(Error::Unspecified)
+            };
+            assert_eq!(
+                Err(Error::Unspecified),
+                block_on(local_endpoint.receive(handler))
+            );
+            assert_eq!(
+                Ok(()),
+                block_on(local_endpoint.receive(|_| panic!("Unexpected request")))
+            );
+
+            (future.poll_unpin(&mut cx), local_endpoint.stats())
+        };
+
+        let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+        local_endpoint.set_lenient_msg_id_matching(None);
+        let (result, stats) = send_bad_ack(local_endpoint);
+        assert!(result.is_pending());
+        assert_eq!(1, stats.unmatched_responses);
+        assert_eq!(0, stats.lenient_matches);
+
+        let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+        local_endpoint.set_lenient_msg_id_matching(Some(Duration::from_secs(10)));
+        let (result, stats) = send_bad_ack(local_endpoint);
+        assert_eq!(
+            futures::task::Poll::Ready(Ok(MsgCode::SuccessContent)),
+            result
+        );
+        assert_eq!(0, stats.unmatched_responses);
+        assert_eq!(1, stats.lenient_matches);
+
+        // Lenient matching is enabled by default.
+        let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+        let (result, stats) = send_bad_ack(local_endpoint);
+        assert_eq!(
+            futures::task::Poll::Ready(Ok(MsgCode::SuccessContent)),
+            result
+        );
+        assert_eq!(0, stats.unmatched_responses);
+        assert_eq!(1, stats.lenient_matches);
+    }
+
+    #[test]
+    fn send_next_with() {
+        let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+
+        // Responds with the query of the request, which names the page to get.
+        let handler = |context: &DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+            let query = context
+                .message()
+                .options()
+                .find_next_of(option::URI_QUERY)
+                .transpose()?
+                .unwrap_or("page=1")
+                .to_string();
+            context.respond(|msg_out| {
+                msg_out.set_msg_code(MsgCode::SuccessContent);
+                msg_out.append_payload_string(&query)
+            })
+        };
+
+        let mut pages = Vec::new();
+        let send_desc = CoapRequest::get().use_handler(move |context| {
+            let message = context?.message();
+            pages.push(message.payload_as_str().unwrap_or("").to_string());
+
+            if pages.len() < 3 {
+                let query = format!("page={}", pages.len() + 1);
+                Ok(ResponseStatus::SendNextWith(ModifyRequest::new(
+                    move |msg_out| msg_out.insert_option(option::URI_QUERY, query.as_str()),
+                )))
+            } else {
+                Ok(ResponseStatus::Done(pages.clone()))
+            }
+        });
+
+        let future = local_endpoint.send(LoopbackSocketAddr::Unicast, send_desc);
+        let future_receive = local_endpoint.receive_loop(handler);
+
+        let pages = match block_on(select(future, future_receive)) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((pages, _)) => pages,
+        };
+
+        assert_eq!(
+            Ok(vec![
+                "page=1".to_string(),
+                "page=2".to_string(),
+                "page=3".to_string()
+            ]),
+            pages
+        );
+    }
+
+    #[test]
+    fn group_security_context_loopback() {
+        use std::sync::atomic::AtomicUsize;
+
+        const MARKER: u8 = 0xA5;
+
+        #[derive(Default)]
+        struct MarkerContext {
+            protected: AtomicUsize,
+            verified: AtomicUsize,
+        }
+
+        impl GroupSecurityContext<LoopbackSocketAddr> for Arc<MarkerContext> {
+            fn is_for_group(&self, group: LoopbackSocketAddr) -> bool {
+                group == LoopbackSocketAddr::Multicast
+            }
+
+            fn protect(
+                &self,
+                _group: LoopbackSocketAddr,
+                message: &[u8],
+            ) -> Result<Vec<u8>, Error> {
+                self.protected.fetch_add(1, Ordering::Relaxed);
+                let mut protected = vec![MARKER];
+                protected.extend_from_slice(message);
+                Ok(protected)
+            }
+
+            fn verify(
+                &self,
+                _group: LoopbackSocketAddr,
+                _source: LoopbackSocketAddr,
+                message: &[u8],
+            ) -> Result<Vec<u8>, Error> {
+                match message.split_first() {
+                    Some((&MARKER, rest)) => {
+                        self.verified.fetch_add(1, Ordering::Relaxed);
+                        Ok(rest.to_vec())
+                    }
+                    _ => Err(Error::ParseFailure),
+                }
+            }
+        }
+
+        let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+        let context = Arc::new(MarkerContext::default());
+        local_endpoint.add_group_security_context(context.clone());
+
+        let handler = |context: &DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+            context.respond(|msg_out| {
+                msg_out.set_msg_code(MsgCode::SuccessContent);
+                Ok(())
+            })
+        };
+
+        let future = local_endpoint.send(
+            LoopbackSocketAddr::Multicast,
+            CoapRequest::get().emit_msg_code(),
+        );
+
+        match block_on(select(future, local_endpoint.receive_loop(handler))) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((ret, _)) => assert_eq!(Ok(MsgCode::SuccessContent), ret),
+        };
+
+        // The unicast response isn't passed through the context.
+        assert_eq!(1, context.protected.load(Ordering::Relaxed));
+        assert_eq!(1, context.verified.load(Ordering::Relaxed));
+
+        // Unprotected datagrams received on the group are dropped.
+        let mut buffer = [0u8; StandardCoapConstants::MAX_OUTBOUND_PACKET_LENGTH];
+        let mut builder = BufferMessageEncoder::new(&mut buffer);
+        builder.set_msg_type(MsgType::Non);
+        builder.set_msg_code(MsgCode::MethodGet);
+        block_on(
+            local_endpoint
+                .socket()
+                .send_to(&builder, LoopbackSocketAddr::Multicast),
+        )
+        .unwrap();
+
+        assert_eq!(
+            Err(Error::ParseFailure),
+            block_on(local_endpoint.receive(|_| panic!("Unverified message was handled")))
+        );
+    }
+
+    #[test]
+    fn endpoint_observer() {
+        #[derive(Debug, Clone, PartialEq)]
+        enum Event {
+            PeerAdded(LoopbackSocketAddr),
+            ObservationCreated(LoopbackSocketAddr, MsgToken),
+            TransactionFailed(LoopbackSocketAddr, Error),
+        }
+
+        #[derive(Default)]
+        struct Recorder(Arc<Mutex<Vec<Event>>>);
+
+        impl EndpointObserver<LoopbackSocketAddr> for Recorder {
+            fn peer_added(&self, addr: LoopbackSocketAddr) {
+                self.0.lock().unwrap().push(Event::PeerAdded(addr));
+            }
+
+            fn observation_created(&self, addr: LoopbackSocketAddr, token: MsgToken) {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push(Event::ObservationCreated(addr, token));
+            }
+
+            fn transaction_failed(&self, dest: LoopbackSocketAddr, failure: &TransactionFailure) {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push(Event::TransactionFailed(dest, failure.error));
+            }
+        }
+
+        let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+        let recorder = Recorder::default();
+        let events = recorder.0.clone();
+        local_endpoint.set_endpoint_observer(recorder);
+
+        let handler = |context: &DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+            context.respond(|msg_out| {
+                msg_out.set_msg_code(MsgCode::SuccessContent);
+                msg_out.insert_option(option::OBSERVE, 1)?;
+                Ok(())
+            })
+        };
+
+        let send_desc = CoapRequest::get()
+            .add_option(option::OBSERVE, OBSERVE_REGISTER)
+            .emit_successful_response()
+            .include_socket_addr();
+        let future = local_endpoint.send(LoopbackSocketAddr::Unicast, send_desc);
+        let token = match block_on(select(future, local_endpoint.receive_loop(handler))) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((ret, _)) => ret.unwrap().0.msg_token(),
+        };
+
+        local_endpoint.socket().set_unreachable(true);
+        let future = local_endpoint.send(
+            LoopbackSocketAddr::Unicast,
+            CoapRequest::get().emit_successful_response(),
+        );
+        match block_on(select(future, local_endpoint.receive_loop(handler))) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((ret, _)) => assert!(ret.is_err()),
+        };
+
+        assert_eq!(
+            vec![
+                Event::PeerAdded(LoopbackSocketAddr::Unicast),
+                Event::ObservationCreated(LoopbackSocketAddr::Unicast, token),
+                Event::TransactionFailed(LoopbackSocketAddr::Unicast, Error::HostUnreachable),
+            ],
+            *events.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn option_registry() {
+        use crate::option::{CustomOption, OptionNumber, OptionValueType, RequestOptions};
+
+        const VENDOR_MODE: CustomOption = CustomOption {
+            number: OptionNumber(65001),
+            name: "Vendor-Mode",
+            value_type: OptionValueType::String,
+            critical: true,
+            repeatable: false,
+        };
+
+        let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+        let dest = LoopbackSocketAddr::Unicast;
+
+        let handler = |context: &DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+            let request = RequestOptions::parse(context.message().options());
+            context.respond(|msg_out| {
+                msg_out.set_msg_code(match request {
+                    Ok(_) => MsgCode::SuccessContent,
+                    Err(_) => MsgCode::ClientErrorBadOption,
+                });
+                Ok(())
+            })
+        };
+        let send = |local_endpoint: &DatagramLocalEndpoint<LoopbackSocket>| {
+            let future = local_endpoint.send(
+                dest,
+                CoapRequest::get()
+                    .add_option(VENDOR_MODE.key(), "fast")
+                    .emit_any_response(),
+            );
+            match block_on(select(future, local_endpoint.receive_loop(handler))) {
+                Either::Right(_) => panic!("Receive future finished unexpectedly"),
+                Either::Left((ret, _)) => ret.map(|response| response.msg_code()),
+            }
+        };
+
+        // Unregistered critical options are rejected.
+        assert_eq!(Ok(MsgCode::ClientErrorBadOption), send(&local_endpoint));
+
+        let mut registry = OptionRegistry::new();
+        registry.register(VENDOR_MODE).unwrap();
+        local_endpoint.set_option_registry(registry);
+
+        assert_eq!(Ok(MsgCode::SuccessContent), send(&local_endpoint));
+    }
+
+    #[test]
+    fn outbound_hook() {
+        let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+        let codes = Arc::new(Mutex::new(Vec::new()));
+
+        let hook_codes = codes.clone();
+        local_endpoint.set_outbound_hook(
+            move |_dest: LoopbackSocketAddr, message: &mut Vec<u8>| {
+                let msg_code = MsgCode::try_from(message[1]).unwrap();
+                hook_codes.lock().unwrap().push(msg_code);
+
+                match msg_code {
+                    MsgCode::MethodDelete => Err(Error::Forbidden),
+                    MsgCode::MethodGet => {
+                        // Stamp requests with a payload.
+                        message.push(0xFF);
+                        message.extend_from_slice(b"stamped");
+                        Ok(())
+                    }
+                    _ => Ok(()),
+                }
+            },
+        );
+
+        let handler = |context: &DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+            let payload = context.message().payload().to_vec();
+            context.respond(|msg_out| {
+                msg_out.set_msg_code(MsgCode::SuccessContent);
+                msg_out.append_payload_bytes(&payload)
+            })
+        };
+
+        let future = local_endpoint.send(
+            LoopbackSocketAddr::Unicast,
+            CoapRequest::get().emit_successful_response(),
+        );
+        match block_on(select(future, local_endpoint.receive_loop(handler))) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((ret, _)) => assert_eq!(Some("stamped"), ret.unwrap().payload_as_str()),
+        };
+
+        let future = local_endpoint.send(LoopbackSocketAddr::Unicast, CoapRequest::delete());
+        match block_on(select(future, local_endpoint.receive_loop(handler))) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((ret, _)) => assert_eq!(Err(Error::Forbidden), ret),
+        };
+
+        local_endpoint.clear_outbound_hook();
+        let future = local_endpoint.send(
+            LoopbackSocketAddr::Unicast,
+            CoapRequest::get().emit_successful_response(),
+        );
+        match block_on(select(future, local_endpoint.receive_loop(handler))) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((ret, _)) => assert_eq!(Some(""), ret.unwrap().payload_as_str()),
+        };
+
+        assert_eq!(
+            vec![
+                MsgCode::MethodGet,
+                MsgCode::SuccessContent,
+                MsgCode::MethodDelete
+            ],
+            *codes.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn inbound_hook() {
+        let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+        let claimed = Arc::new(Mutex::new(Vec::new()));
+
+        // An experimental framing which prefixes CoAP messages with a marker byte. Anything
+        // without the marker is claimed by the hook.
+        local_endpoint.set_outbound_hook(|_dest: LoopbackSocketAddr, message: &mut Vec<u8>| {
+            message.insert(0, b'X');
+            Ok(())
+        });
+
+        let hook_claimed = claimed.clone();
+        local_endpoint.set_inbound_hook(
+            move |_source: LoopbackSocketAddr, message: &mut Vec<u8>| match message.first() {
+                Some(b'X') => {
+                    message.remove(0);
+                    Ok(InboundDisposition::Continue)
+                }
+                Some(b'!') => Err(Error::Forbidden),
+                _ => {
+                    hook_claimed.lock().unwrap().push(message.clone());
+                    Ok(InboundDisposition::Claimed)
+                }
+            },
+        );
+
+        let handler = |context: &DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+            context.respond(|msg_out| {
+                msg_out.set_msg_code(MsgCode::SuccessContent);
+                msg_out.append_payload_string("framed")
+            })
+        };
+
+        let future = local_endpoint.send(
+            LoopbackSocketAddr::Unicast,
+            CoapRequest::get().emit_successful_response(),
+        );
+        match block_on(select(future, local_endpoint.receive_loop(handler))) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((ret, _)) => assert_eq!(Some("framed"), ret.unwrap().payload_as_str()),
+        };
+
+        let unexpected = |_: &DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+            panic!("Claimed datagram was handled")
+        };
+
+        block_on(
+            local_endpoint
+                .socket()
+                .send_to(b"raw", LoopbackSocketAddr::Unicast),
+        )
+        .unwrap();
+        assert_eq!(Ok(()), block_on(local_endpoint.receive(unexpected)));
+        assert_eq!(vec![b"raw".to_vec()], *claimed.lock().unwrap());
+
+        block_on(
+            local_endpoint
+                .socket()
+                .send_to(b"!", LoopbackSocketAddr::Unicast),
+        )
+        .unwrap();
+        assert_eq!(
+            Err(Error::Forbidden),
+            block_on(local_endpoint.receive(unexpected))
+        );
+
+        local_endpoint.clear_inbound_hook();
+        local_endpoint.clear_outbound_hook();
+        let future = local_endpoint.send(
+            LoopbackSocketAddr::Unicast,
+            CoapRequest::get().emit_successful_response(),
+        );
+        match block_on(select(future, local_endpoint.receive_loop(handler))) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((ret, _)) => assert_eq!(Some("framed"), ret.unwrap().payload_as_str()),
+        };
+
+        assert_eq!(1, claimed.lock().unwrap().len());
+    }
+
+    #[test]
+    fn transaction_journal() {
+        #[derive(Debug, PartialEq)]
+        enum Event {
+            Recorded(MsgToken, MsgCode),
+            Finished(MsgToken, Result<(), Error>),
+        }
+
+        struct Recorder(
+            Arc<Mutex<Vec<Event>>>,
+            Arc<Mutex<Vec<JournalEntry<LoopbackSocketAddr>>>>,
+        );
+
+        impl TransactionJournal<LoopbackSocketAddr> for Recorder {
+            fn record(&self, entry: JournalEntry<LoopbackSocketAddr>) {
+                let event = Event::Recorded(entry.msg_token(), entry.message.msg_code());
+                self.0.lock().unwrap().push(event);
+                self.1.lock().unwrap().push(entry);
+            }
+
+            fn finished(
+                &self,
+                _dest: LoopbackSocketAddr,
+                msg_token: MsgToken,
+                result: Result<(), Error>,
+            ) {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push(Event::Finished(msg_token, result));
+            }
+        }
+
+        let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let entries = Arc::new(Mutex::new(Vec::new()));
+        local_endpoint.set_transaction_journal(Recorder(events.clone(), entries.clone()));
+
+        let handler = |context: &DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+            let path = context.message().options().extract_uri()?;
+            context.respond(|msg_out| {
+                msg_out.set_msg_code(MsgCode::SuccessChanged);
+                msg_out.append_payload_string(path.as_str())
+            })
+        };
+
+        let future = local_endpoint.send(
+            LoopbackSocketAddr::Unicast,
+            CoapRequest::post()
+                .uri_host_path(None, rel_ref!("lock"))
+                .emit_successful_response(),
+        );
+        let token = match block_on(select(future, local_endpoint.receive_loop(handler))) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((ret, _)) => ret.unwrap().msg_token(),
+        };
+
+        // Non-confirmable requests aren't journaled.
+        let future = local_endpoint.send(
+            LoopbackSocketAddr::Unicast,
+            CoapRequest::post().nonconfirmable(),
+        );
+        match block_on(select(future, local_endpoint.receive_loop(handler))) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((ret, _)) => assert_eq!(Ok(()), ret),
+        };
+
+        assert_eq!(
+            vec![
+                Event::Recorded(token, MsgCode::MethodPost),
+                Event::Finished(token, Ok(())),
+            ],
+            *events.lock().unwrap()
+        );
+
+        // Re-issue the journaled request, as an application would after a restart.
+        let entry = entries.lock().unwrap()[0].clone();
+        let future = local_endpoint.reissue(entry);
+        match block_on(select(future, local_endpoint.receive_loop(handler))) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((ret, _)) => {
+                let response = ret.unwrap();
+                assert_eq!(MsgCode::SuccessChanged, response.msg_code());
+                assert_eq!(token, response.msg_token());
+                assert_eq!(b"lock", response.payload());
+            }
+        };
+
+        assert_eq!(4, events.lock().unwrap().len());
+        assert_eq!(Event::Finished(token, Ok(())), events.lock().unwrap()[3]);
     }
 }
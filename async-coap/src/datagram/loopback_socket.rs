@@ -18,8 +18,12 @@ use futures::channel::mpsc::{channel, Receiver, Sender};
 use futures::lock::Mutex;
 use futures::prelude::*;
 use futures::task::{Context, Poll};
+use futures_timer::Delay;
+use std::collections::VecDeque;
 use std::fmt::{Debug, Display, Formatter};
 use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
 /// Simplified "SocketAddr" for [`LoopbackSocket`]. Allows for two different types of addresses:
 /// Unicast addresses and Multicast addresses.
@@ -73,13 +77,44 @@ impl ToSocketAddrs for LoopbackSocketAddr {
     }
 }
 
+// Either (packet_bytes, dest_addr), or the address of an unreachable destination.
+type LoopbackPacket = Result<(Vec<u8>, LoopbackSocketAddr), LoopbackSocketAddr>;
+
+// A packet along with the instant at which it is delivered.
+type DelayedPacket = (Instant, LoopbackPacket);
+
+#[derive(Debug)]
+struct LoopbackReceiver {
+    receiver: Receiver<DelayedPacket>,
+
+    // The packet at the head of the queue, if it isn't due yet.
+    delayed: Option<(Delay, LoopbackPacket)>,
+}
+
 /// An instance of [`AsyncDatagramSocket`] that implements a simple loopback interface, where
 /// all packets that are sent are looped back to the input.
+///
+/// By default, every packet is delivered exactly once and immediately. To exercise the
+/// retransmission and deduplication logic of a local endpoint, the socket can be
+/// configured to delay, drop, or duplicate packets:
+///
+/// ```
+/// # use async_coap::datagram::LoopbackSocket;
+/// # use std::time::Duration;
+/// let socket = LoopbackSocket::new()
+///     .with_latency(Duration::from_millis(10))
+///     .with_loss_probability(0.1)
+///     .with_duplication(0.1);
+/// ```
 #[derive(Debug)]
 pub struct LoopbackSocket {
-    // Message is (packet_bytes, dest_addr)
-    sender: Sender<(Vec<u8>, LoopbackSocketAddr)>,
-    receiver: futures::lock::Mutex<Receiver<(Vec<u8>, LoopbackSocketAddr)>>,
+    sender: Sender<DelayedPacket>,
+    receiver: futures::lock::Mutex<LoopbackReceiver>,
+    unreachable: AtomicBool,
+    unreachable_reports: std::sync::Mutex<VecDeque<LoopbackSocketAddr>>,
+    latency: Duration,
+    loss_probability: f32,
+    duplication_probability: f32,
 }
 
 impl LoopbackSocket {
@@ -88,14 +123,75 @@ impl LoopbackSocket {
         let (sender, receiver) = channel(3);
         LoopbackSocket {
             sender,
-            receiver: Mutex::new(receiver),
+            receiver: Mutex::new(LoopbackReceiver {
+                receiver,
+                delayed: None,
+            }),
+            unreachable: AtomicBool::new(false),
+            unreachable_reports: Default::default(),
+            latency: Duration::from_secs(0),
+            loss_probability: 0.0,
+            duplication_probability: 0.0,
         }
     }
+
+    /// Delays the delivery of every packet by `latency`.
+    ///
+    /// Packets are still delivered in the order in which they were sent.
+    pub fn with_latency(mut self, latency: Duration) -> LoopbackSocket {
+        self.latency = latency;
+        self
+    }
+
+    /// Drops each sent packet with the given probability, which is clamped to the range
+    /// `0.0..=1.0`.
+    ///
+    /// Dropped packets are still reported as having been sent successfully.
+    pub fn with_loss_probability(mut self, probability: f32) -> LoopbackSocket {
+        self.loss_probability = clamp_probability(probability);
+        self
+    }
+
+    /// Delivers each packet which isn't dropped a second time with the given probability,
+    /// which is clamped to the range `0.0..=1.0`.
+    pub fn with_duplication(mut self, probability: f32) -> LoopbackSocket {
+        self.duplication_probability = clamp_probability(probability);
+        self
+    }
+
+    /// Simulates [`LoopbackSocketAddr::Unicast`] becoming unreachable (or reachable again).
+    ///
+    /// While unreachable, packets sent to the unicast address are dropped and reported
+    /// via [`AsyncDatagramSocket::take_unreachable`], as if an ICMP "port unreachable"
+    /// message had been received for each of them.
+    pub fn set_unreachable(&self, unreachable: bool) {
+        self.unreachable.store(unreachable, Ordering::Relaxed);
+    }
+}
+
+fn clamp_probability(probability: f32) -> f32 {
+    if probability > 0.0 {
+        probability.min(1.0)
+    } else {
+        // Also catches NaN.
+        0.0
+    }
+}
+
+fn chance(probability: f32) -> bool {
+    probability > 0.0 && rand::random::<f32>() < probability
 }
 
 impl Unpin for LoopbackSocket {}
 
-impl AsyncDatagramSocket for LoopbackSocket {}
+impl AsyncDatagramSocket for LoopbackSocket {
+    fn take_unreachable(&self) -> Option<Self::SocketAddr> {
+        self.unreachable_reports
+            .lock()
+            .expect("Lock failed")
+            .pop_front()
+    }
+}
 
 impl DatagramSocketTypes for LoopbackSocket {
     type SocketAddr = LoopbackSocketAddr;
@@ -131,10 +227,24 @@ impl AsyncSendTo for LoopbackSocket {
         B: super::ToSocketAddrs<SocketAddr = Self::SocketAddr, Error = Self::Error>,
     {
         if let Some(addr) = addr.to_socket_addrs()?.next() {
+            let packet = if !addr.is_multicast() && self.unreachable.load(Ordering::Relaxed) {
+                Err(addr)
+            } else {
+                Ok((buf.to_vec(), addr))
+            };
+            let deliver_at = Instant::now() + self.latency;
             let mut sender = self.get_ref().sender.clone();
             match sender.poll_ready(cx) {
-                Poll::Ready(Ok(())) => match sender.start_send((buf.to_vec(), addr)) {
-                    Ok(()) => Poll::Ready(Ok(buf.len())),
+                Poll::Ready(Ok(())) if chance(self.loss_probability) => Poll::Ready(Ok(buf.len())),
+                Poll::Ready(Ok(())) => match sender.start_send((deliver_at, packet.clone())) {
+                    Ok(()) => {
+                        if chance(self.duplication_probability) {
+                            // A fresh sender always has room for one more packet. If
+                            // this still fails, the duplicate is simply lost.
+                            let _ = self.sender.clone().try_send((deliver_at, packet));
+                        }
+                        Poll::Ready(Ok(buf.len()))
+                    }
                     Err(e) => {
                         if e.is_full() {
                             Poll::Pending
@@ -162,16 +272,42 @@ impl AsyncRecvFrom for LoopbackSocket {
         let receiver_lock_future = Pin::new(&mut receiver_lock_future);
 
         if let Poll::Ready(mut receiver_guard) = receiver_lock_future.poll(cx) {
-            let receiver: &mut Receiver<(Vec<u8>, LoopbackSocketAddr)> = &mut receiver_guard;
-            match receiver.poll_next_unpin(cx) {
-                Poll::Ready(Some((packet, addr))) => {
-                    let len = packet.len();
-                    if buf.len() >= len {
-                        buf[..len].copy_from_slice(&packet);
-                        Poll::Ready(Ok((len, self.local_addr().unwrap(), Some(addr))))
-                    } else {
-                        Poll::Ready(Err(Error::IOError))
+            let receiver: &mut LoopbackReceiver = &mut receiver_guard;
+
+            let next = loop {
+                if let Some((delay, _)) = receiver.delayed.as_mut() {
+                    if Pin::new(delay).poll(cx).is_pending() {
+                        return Poll::Pending;
                     }
+                    break Poll::Ready(receiver.delayed.take().map(|(_, packet)| packet));
+                }
+
+                match receiver.receiver.poll_next_unpin(cx) {
+                    Poll::Ready(Some((deliver_at, packet))) => {
+                        let now = Instant::now();
+                        if deliver_at <= now {
+                            break Poll::Ready(Some(packet));
+                        }
+                        receiver.delayed = Some((Delay::new(deliver_at - now), packet));
+                    }
+                    Poll::Ready(None) => break Poll::Ready(None),
+                    Poll::Pending => break Poll::Pending,
+                }
+            };
+
+            match next {
+                Poll::Ready(Some(Err(addr))) => {
+                    self.unreachable_reports
+                        .lock()
+                        .expect("Lock failed")
+                        .push_back(addr);
+                    Poll::Ready(Err(Error::HostUnreachable))
+                }
+                Poll::Ready(Some(Ok((packet, addr)))) => {
+                    // Like a UDP socket, truncate datagrams which don't fit in `buf`.
+                    let len = packet.len().min(buf.len());
+                    buf[..len].copy_from_slice(&packet[..len]);
+                    Poll::Ready(Ok((len, self.local_addr().unwrap(), Some(addr))))
                 }
                 Poll::Ready(None) => Poll::Ready(Err(Error::IOError)),
                 Poll::Pending => Poll::Pending,
@@ -39,6 +39,11 @@ pub use async_socket::{
 mod allow_udp_socket;
 pub use allow_udp_socket::AllowStdUdpSocket;
 
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod unreachable_reports;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use unreachable_reports::UnreachableReports;
+
 mod loopback_socket;
 pub use loopback_socket::LoopbackSocket;
 pub use loopback_socket::LoopbackSocketAddr;
@@ -47,6 +52,12 @@ mod null_socket;
 pub use null_socket::NullSocket;
 pub use null_socket::NullSocketAddr;
 
+mod sms;
+pub use sms::{SmsNetwork, SmsSocket, SmsSocketAddr, SMS_MAX_MESSAGE_LEN};
+
+mod dtls;
+pub use dtls::{DtlsContext, DtlsSession, DtlsSocket};
+
 mod response_tracker;
 use response_tracker::*;
 
@@ -61,3 +72,49 @@ pub use local_endpoint::*;
 
 mod inbound_context;
 pub use inbound_context::*;
+
+#[cfg(all(feature = "server", feature = "observe"))]
+mod observe;
+#[cfg(all(feature = "server", feature = "observe"))]
+pub use observe::*;
+
+mod coalesce;
+use coalesce::{Coalesced, CoalescedExchange, CoalescedExchanges, WeakCoalescedExchange};
+
+mod dedup;
+use dedup::ReceivedRequests;
+
+mod endpoint_observer;
+pub use endpoint_observer::EndpointObserver;
+use endpoint_observer::{is_observe_accepted, is_observe_registration};
+
+mod group_security;
+pub use group_security::GroupSecurityContext;
+use group_security::GroupSecurityContexts;
+
+mod inbound_hook;
+pub use inbound_hook::{InboundDisposition, InboundHook};
+
+mod journal;
+use journal::Reissue;
+pub use journal::{JournalEntry, TransactionJournal};
+
+mod outbound_hook;
+pub use outbound_hook::OutboundHook;
+
+mod parse_diagnostic;
+use parse_diagnostic::ParseErrorHandler;
+pub use parse_diagnostic::{ParseDiagnostic, ParseFailureReason, VersionPolicy};
+
+mod rtt;
+pub use rtt::{AdaptiveAckTimeout, RttEstimate};
+
+mod shutdown;
+use shutdown::{ShutdownFuture, ShutdownSignal};
+
+mod send_status;
+pub use send_status::{FailureCause, SendState, SendStatus, SendStatusHandle, TransactionFailure};
+
+mod stats;
+use stats::StatCounters;
+pub use stats::{EndpointStats, WELL_KNOWN_STATS};
@@ -0,0 +1,387 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+use std::ops::Bound;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Policy used by [`Observers`] to decide if a notification should be sent as a
+/// confirmable (CON) or a non-confirmable (NON) message.
+///
+/// [IETF-RFC7641 Section 4.5] requires that a server occasionally sends a confirmable
+/// notification in order to verify that the client is still interested. A notification
+/// is sent as CON if either of the conditions described by this policy is met.
+///
+/// [IETF-RFC7641 Section 4.5]: https://tools.ietf.org/html/rfc7641#section-4.5
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct NotifyPolicy {
+    /// Every `con_every`th notification sent to an observer is confirmable. A value of `1`
+    /// makes every notification confirmable, and a value of `0` disables this condition.
+    pub con_every: u32,
+
+    /// A notification is confirmable if it has been at least this long since the last
+    /// confirmable notification was sent to the observer (or since it registered).
+    pub con_interval: Duration,
+}
+
+impl NotifyPolicy {
+    /// Policy where every notification is sent as a confirmable message.
+    pub const ALWAYS_CON: NotifyPolicy = NotifyPolicy {
+        con_every: 1,
+        con_interval: Duration::from_secs(0),
+    };
+
+    fn is_con(&self, sent_since_con: u32, since_con: Duration) -> bool {
+        (self.con_every != 0 && sent_since_con + 1 >= self.con_every)
+            || since_con >= self.con_interval
+    }
+}
+
+impl Default for NotifyPolicy {
+    /// The default policy sends a confirmable notification at least once every 24 hours,
+    /// as recommended by [IETF-RFC7641 Section 4.5].
+    ///
+    /// [IETF-RFC7641 Section 4.5]: https://tools.ietf.org/html/rfc7641#section-4.5
+    fn default() -> Self {
+        NotifyPolicy {
+            con_every: 0,
+            con_interval: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Observer<SA> {
+    addr: SA,
+    token: MsgToken,
+    policy: Option<NotifyPolicy>,
+    sent_since_con: u32,
+    last_con: Instant,
+}
+
+#[derive(Debug)]
+struct ObserversInner<SA> {
+    observers: Vec<Observer<SA>>,
+    seq: ObserveSeq,
+}
+
+/// Tracks the clients observing a resource served by a [`DatagramLocalEndpoint`] and
+/// sends them notifications, as described in [IETF-RFC7641].
+///
+/// Requests for the resource are passed to [`Observers::handle_request`], which registers
+/// and deregisters observers. When the resource changes, [`Observers::notify`] sends a
+/// notification to every observer. Notifications are sent as confirmable or
+/// non-confirmable messages depending on the [`NotifyPolicy`], which can be overridden
+/// for individual observers using [`Observers::set_observer_policy`]. Observers which
+/// reject or fail to acknowledge a confirmable notification are removed automatically.
+///
+/// [IETF-RFC7641]: https://tools.ietf.org/html/rfc7641
+#[derive(Debug)]
+pub struct Observers<SA> {
+    policy: NotifyPolicy,
+    inner: Mutex<ObserversInner<SA>>,
+}
+
+impl<SA> Default for Observers<SA> {
+    fn default() -> Self {
+        Observers::with_policy(Default::default())
+    }
+}
+
+impl<SA> Observers<SA> {
+    /// Creates a new, empty set of observers using the default [`NotifyPolicy`].
+    pub fn new() -> Observers<SA> {
+        Default::default()
+    }
+
+    /// Creates a new, empty set of observers using the given [`NotifyPolicy`].
+    pub fn with_policy(policy: NotifyPolicy) -> Observers<SA> {
+        Observers {
+            policy,
+            inner: Mutex::new(ObserversInner {
+                observers: Vec::new(),
+                seq: ObserveSeq::default(),
+            }),
+        }
+    }
+
+    /// The [`NotifyPolicy`] used for observers that don't have their own policy.
+    pub fn policy(&self) -> NotifyPolicy {
+        self.policy
+    }
+
+    /// The number of registered observers.
+    pub fn len(&self) -> usize {
+        self.inner.lock().expect("Lock failed").observers.len()
+    }
+
+    /// Returns true if there are no registered observers.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<SA: SocketAddrExt> Observers<SA> {
+    /// Registers or deregisters the sender of an inbound `GET` request, based on the value
+    /// of its `Observe` option.
+    ///
+    /// If the sender was registered, the current sequence number is returned. The caller
+    /// must then respond to the request as usual, including an `Observe` option with the
+    /// returned value. Otherwise `None` is returned, and the caller should respond without
+    /// an `Observe` option.
+    ///
+    /// Requests with an empty token are never registered, since notifications could not be
+    /// distinguished from the responses to other requests.
+    pub fn handle_request<T>(&self, context: &T) -> Option<u32>
+    where
+        T: InboundContext<SocketAddr = SA> + ?Sized,
+    {
+        let msg = context.message();
+
+        if msg.msg_code() != MsgCode::MethodGet {
+            return None;
+        }
+
+        let addr = context.remote_socket_addr();
+        let token = msg.msg_token();
+
+        match msg.options().find_next_of(option::OBSERVE) {
+            Some(Ok(OBSERVE_REGISTER)) if !token.is_empty() => {
+                let mut inner = self.inner.lock().expect("Lock failed");
+                let observer = Observer {
+                    addr,
+                    token,
+                    policy: None,
+                    sent_since_con: 0,
+                    last_con: Instant::now(),
+                };
+
+                match inner
+                    .observers
+                    .iter_mut()
+                    .find(|x| x.addr == addr && x.token == token)
+                {
+                    Some(existing) => {
+                        // Re-registration keeps any policy override.
+                        existing.sent_since_con = 0;
+                        existing.last_con = observer.last_con;
+                    }
+                    None => inner.observers.push(observer),
+                }
+
+                Some(inner.seq.value())
+            }
+            Some(Ok(OBSERVE_DEREGISTER)) => {
+                self.remove(addr, token);
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Removes the observer with the given address and token, returning true if it was found.
+    pub fn remove(&self, addr: SA, token: MsgToken) -> bool {
+        let mut inner = self.inner.lock().expect("Lock failed");
+        let len = inner.observers.len();
+        inner
+            .observers
+            .retain(|x| !(x.addr == addr && x.token == token));
+        inner.observers.len() != len
+    }
+
+    /// Overrides the [`NotifyPolicy`] for the observer with the given address and token.
+    /// Passing `None` reverts the observer to using the default policy.
+    ///
+    /// Returns false if no such observer is registered.
+    pub fn set_observer_policy(
+        &self,
+        addr: SA,
+        token: MsgToken,
+        policy: Option<NotifyPolicy>,
+    ) -> bool {
+        let mut inner = self.inner.lock().expect("Lock failed");
+        match inner
+            .observers
+            .iter_mut()
+            .find(|x| x.addr == addr && x.token == token)
+        {
+            Some(observer) => {
+                observer.policy = policy;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Sends a notification to every registered observer, using `msg_gen` to write the
+    /// message code, options, and payload of the notification. The token, message type,
+    /// and `Observe` option are filled in automatically.
+    ///
+    /// The returned future finishes once every confirmable notification has been either
+    /// acknowledged or given up on, and evaluates to the number of observers that were
+    /// notified successfully. Observers which rejected a confirmable notification or
+    /// failed to acknowledge it are removed.
+    pub fn notify<'a, US, F>(
+        &'a self,
+        local_endpoint: &'a DatagramLocalEndpoint<US>,
+        msg_gen: F,
+    ) -> BoxFuture<'a, usize>
+    where
+        US: AsyncDatagramSocket<SocketAddr = SA>,
+        F: Fn(&mut dyn MessageWrite) -> Result<(), Error> + Send + Sync + 'a,
+    {
+        // Decide how each observer gets notified while holding the lock, but
+        // don't hold it while we are waiting for the notifications to be sent.
+        let (seq, targets) = {
+            let mut inner = self.inner.lock().expect("Lock failed");
+            inner.seq = inner.seq.next();
+
+            let now = Instant::now();
+            let default_policy = self.policy;
+            let targets = inner
+                .observers
+                .iter_mut()
+                .map(|observer| {
+                    let policy = observer.policy.unwrap_or(default_policy);
+                    let con = policy.is_con(
+                        observer.sent_since_con,
+                        now.duration_since(observer.last_con),
+                    );
+
+                    if con {
+                        observer.sent_since_con = 0;
+                        observer.last_con = now;
+                    } else {
+                        observer.sent_since_con += 1;
+                    }
+
+                    (observer.addr, observer.token, con)
+                })
+                .collect::<Vec<_>>();
+
+            (inner.seq, targets)
+        };
+
+        async move {
+            let msg_gen = &msg_gen;
+            let results = futures::future::join_all(targets.iter().map(|&(addr, token, con)| {
+                local_endpoint.send_with_token(addr, token, Notification { msg_gen, seq, con })
+            }))
+            .await;
+
+            let mut inner = self.inner.lock().expect("Lock failed");
+            let mut notified = 0;
+
+            for (&(addr, token, _), result) in targets.iter().zip(results) {
+                if result.is_ok() {
+                    notified += 1;
+                } else {
+                    debug!("Removing observer {} after failed notification", addr);
+                    inner
+                        .observers
+                        .retain(|x| !(x.addr == addr && x.token == token));
+                }
+            }
+
+            notified
+        }
+        .boxed()
+    }
+}
+
+/// Send descriptor for a single notification sent by [`Observers::notify`].
+struct Notification<'a, F> {
+    msg_gen: &'a F,
+    seq: ObserveSeq,
+    con: bool,
+}
+
+impl<'a, F, IC> SendDesc<IC, ()> for Notification<'a, F>
+where
+    F: Fn(&mut dyn MessageWrite) -> Result<(), Error> + Send + Sync,
+    IC: InboundContext,
+{
+    fn delay_to_retransmit(&self, retransmits_sent: u32) -> Option<Duration> {
+        if self.con && retransmits_sent <= StandardCoapConstants::COAP_MAX_RETRANSMIT {
+            Some(StandardCoapConstants.calc_retransmit_duration(retransmits_sent + 1))
+        } else {
+            None
+        }
+    }
+
+    fn max_rtt(&self) -> Duration {
+        if self.con {
+            StandardCoapConstants::COAP_MAX_RTT
+        } else {
+            // Nothing will be sent in reply to a non-confirmable notification,
+            // so there is no reason to wait.
+            Duration::from_secs(0)
+        }
+    }
+
+    fn write_options(
+        &self,
+        msg: &mut dyn OptionInsert,
+        socket_addr: &IC::SocketAddr,
+        start: Bound<OptionNumber>,
+        end: Bound<OptionNumber>,
+    ) -> Result<(), Error> {
+        write_options!((msg, socket_addr, start, end) {
+            OBSERVE => Some(self.seq.value()).into_iter(),
+        })
+    }
+
+    fn write_payload(
+        &self,
+        msg: &mut dyn MessageWrite,
+        _socket_addr: &IC::SocketAddr,
+    ) -> Result<(), Error> {
+        (self.msg_gen)(msg)?;
+        msg.set_msg_type(if self.con { MsgType::Con } else { MsgType::Non });
+        Ok(())
+    }
+
+    fn handler(&mut self, context: Result<&IC, Error>) -> Result<ResponseStatus<()>, Error> {
+        match context {
+            Ok(context) if context.message().msg_type().is_res() => Err(Error::Reset),
+            Ok(_) => Ok(ResponseStatus::Done(())),
+            Err(Error::ResponseTimeout) if !self.con => Ok(ResponseStatus::Done(())),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notify_policy() {
+        let hour = Duration::from_secs(60 * 60);
+        let policy = NotifyPolicy {
+            con_every: 3,
+            con_interval: hour,
+        };
+
+        assert!(!policy.is_con(0, Duration::from_secs(0)));
+        assert!(!policy.is_con(1, Duration::from_secs(0)));
+        assert!(policy.is_con(2, Duration::from_secs(0)));
+        assert!(policy.is_con(0, hour));
+
+        assert!(NotifyPolicy::ALWAYS_CON.is_con(0, Duration::from_secs(0)));
+        assert!(!NotifyPolicy::default().is_con(1000, hour));
+    }
+}
@@ -0,0 +1,53 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+
+/// Hook which is given every datagram that a [`DatagramLocalEndpoint`] is about to send,
+/// set with [`DatagramLocalEndpoint::set_outbound_hook`].
+///
+/// The hook can inspect the fully encoded message, change it in place, or veto it. This
+/// allows experimenting with things like adding an option to every message, stamping
+/// telemetry, or a security layer, without having to fork the local endpoint. Closures
+/// with the same signature as [`OutboundHook::outbound`] implement this trait.
+///
+/// The hook is called right before the datagram is handed to the socket: after the
+/// message has been checked against the path MTU, and after it has been protected by a
+/// [`GroupSecurityContext`], if there is one for the destination. Retransmissions are
+/// passed to the hook again. The hook is called synchronously from the task that is
+/// sending, so it should return quickly.
+pub trait OutboundHook<SA>: Send + Sync {
+    /// Called with `message`, an encoded datagram about to be sent to `dest`, which is
+    /// sent as it is after this returns.
+    ///
+    /// If this fails, the datagram isn't sent. For requests, the send future fails with
+    /// the returned error; responses and empty messages are silently dropped.
+    fn outbound(&self, dest: SA, message: &mut Vec<u8>) -> Result<(), Error>;
+}
+
+impl<SA, F> OutboundHook<SA> for F
+where
+    F: Fn(SA, &mut Vec<u8>) -> Result<(), Error> + Send + Sync,
+{
+    fn outbound(&self, dest: SA, message: &mut Vec<u8>) -> Result<(), Error> {
+        self(dest, message)
+    }
+}
+
+impl<SA> std::fmt::Debug for dyn OutboundHook<SA> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("OutboundHook")
+    }
+}
@@ -0,0 +1,221 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+use crate::message::{
+    AnyVersionDatagramFraming, CoapByteDisplayFormatter, StandardMessageParser, COAP_MSG_TKL_MASK,
+    COAP_MSG_VER_MASK, COAP_MSG_VER_OFFS,
+};
+
+/// How a [`DatagramLocalEndpoint`] treats received datagrams whose header has a version
+/// other than 1, as set with [`DatagramLocalEndpoint::set_version_policy`].
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub enum VersionPolicy {
+    /// Datagrams with an unknown version are discarded as unparsable: they are counted in
+    /// [`EndpointStats::parse_errors`] and [`LocalEndpoint::receive`] returns
+    /// [`Error::ParseFailure`]. This is the default.
+    #[default]
+    Strict,
+
+    /// Datagrams with an unknown version are treated as opaque, but well-formed, traffic:
+    /// they are counted in [`EndpointStats::unknown_versions`] instead of as parse errors,
+    /// and [`LocalEndpoint::receive`] returns `Ok(())`.
+    ///
+    /// They are still never passed to the request handler, since their meaning is
+    /// unknown. The parse error handler gets them instead, and can use
+    /// [`ParseDiagnostic::version`] and [`ParseDiagnostic::message`] to study them.
+    Tolerant,
+}
+
+/// The reason why a received datagram couldn't be parsed, as determined by
+/// [`ParseDiagnostic::reason`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ParseFailureReason {
+    /// The datagram is shorter than the four-byte CoAP header.
+    TooShort,
+
+    /// The version field of the header isn't 1.
+    UnsupportedVersion(u8),
+
+    /// The token length field of the header is larger than 8.
+    InvalidTokenLength(u8),
+
+    /// The datagram ends before the end of the token.
+    TruncatedToken,
+
+    /// The message code isn't recognized.
+    UnknownMessageCode(u8),
+
+    /// The header and token are valid, but the options that follow them are malformed.
+    MalformedOptions,
+}
+
+impl ParseFailureReason {
+    /// Determines why `bytes` couldn't be parsed, assuming that it couldn't be.
+    pub fn of(bytes: &[u8]) -> ParseFailureReason {
+        if bytes.len() < 4 {
+            return ParseFailureReason::TooShort;
+        }
+
+        let version = (bytes[0] & COAP_MSG_VER_MASK) >> COAP_MSG_VER_OFFS;
+        if version != 1 {
+            return ParseFailureReason::UnsupportedVersion(version);
+        }
+
+        let token_len = bytes[0] & COAP_MSG_TKL_MASK;
+        if token_len > 8 {
+            return ParseFailureReason::InvalidTokenLength(token_len);
+        }
+
+        if MsgCode::try_from(bytes[1]).is_none() {
+            return ParseFailureReason::UnknownMessageCode(bytes[1]);
+        }
+
+        if bytes.len() < 4 + token_len as usize {
+            return ParseFailureReason::TruncatedToken;
+        }
+
+        ParseFailureReason::MalformedOptions
+    }
+}
+
+impl std::fmt::Display for ParseFailureReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseFailureReason::TooShort => f.write_str("shorter than CoAP header"),
+            ParseFailureReason::UnsupportedVersion(version) => {
+                write!(f, "unsupported version {}", version)
+            }
+            ParseFailureReason::InvalidTokenLength(len) => {
+                write!(f, "invalid token length {}", len)
+            }
+            ParseFailureReason::TruncatedToken => f.write_str("truncated token"),
+            ParseFailureReason::UnknownMessageCode(code) => {
+                write!(f, "unknown message code 0x{:02X}", code)
+            }
+            ParseFailureReason::MalformedOptions => f.write_str("malformed options"),
+        }
+    }
+}
+
+/// Describes a received datagram which was discarded because it couldn't be parsed as a
+/// CoAP message. Passed to the handler set with
+/// [`DatagramLocalEndpoint::set_parse_error_handler`].
+#[derive(Debug, Copy, Clone)]
+pub struct ParseDiagnostic<'a, SA> {
+    remote: SA,
+    bytes: &'a [u8],
+    error: Error,
+}
+
+impl<'a, SA: SocketAddrExt> ParseDiagnostic<'a, SA> {
+    pub(super) fn new(remote: SA, bytes: &'a [u8], error: Error) -> Self {
+        ParseDiagnostic {
+            remote,
+            bytes,
+            error,
+        }
+    }
+
+    /// The socket address of the remote endpoint which sent the datagram.
+    pub fn remote_socket_addr(&self) -> SA {
+        self.remote
+    }
+
+    /// The raw bytes of the datagram.
+    pub fn raw_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// The error returned by the parser.
+    pub fn error(&self) -> Error {
+        self.error
+    }
+
+    /// Determines why the datagram couldn't be parsed.
+    pub fn reason(&self) -> ParseFailureReason {
+        ParseFailureReason::of(self.bytes)
+    }
+
+    /// The value of the version field of the datagram, or `None` if it is too short to
+    /// have a header.
+    pub fn version(&self) -> Option<u8> {
+        AnyVersionDatagramFraming::version(self.bytes)
+    }
+
+    /// Parses the datagram while ignoring its version field, which gives access to the
+    /// header, options, and payload of messages from unknown protocol versions.
+    ///
+    /// Returns `None` if the datagram is malformed in other ways, too.
+    pub fn message(&self) -> Option<StandardMessageParser<'a>> {
+        StandardMessageParser::with_framing(self.bytes, &AnyVersionDatagramFraming).ok()
+    }
+}
+
+impl<'a, SA: SocketAddrExt> std::fmt::Display for ParseDiagnostic<'a, SA> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Unparsable datagram from {} ({}): {}",
+            self.remote,
+            self.reason(),
+            CoapByteDisplayFormatter(self.bytes)
+        )
+    }
+}
+
+type ParseErrorFn<SA> = dyn Fn(&ParseDiagnostic<'_, SA>) + Send + Sync;
+
+/// Boxed parse error handler, as set with [`DatagramLocalEndpoint::set_parse_error_handler`].
+pub(super) struct ParseErrorHandler<SA>(pub(super) Box<ParseErrorFn<SA>>);
+
+impl<SA> std::fmt::Debug for ParseErrorHandler<SA> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ParseErrorHandler")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reason() {
+        assert_eq!(
+            ParseFailureReason::TooShort,
+            ParseFailureReason::of(&[0x40])
+        );
+        assert_eq!(
+            ParseFailureReason::UnsupportedVersion(2),
+            ParseFailureReason::of(&[0x80, 0x01, 0x00, 0x01])
+        );
+        assert_eq!(
+            ParseFailureReason::InvalidTokenLength(9),
+            ParseFailureReason::of(&[0x49, 0x01, 0x00, 0x01])
+        );
+        assert_eq!(
+            ParseFailureReason::UnknownMessageCode(0x1F),
+            ParseFailureReason::of(&[0x40, 0x1F, 0x00, 0x01])
+        );
+        assert_eq!(
+            ParseFailureReason::TruncatedToken,
+            ParseFailureReason::of(&[0x42, 0x01, 0x00, 0x01, 0xAA])
+        );
+        assert_eq!(
+            ParseFailureReason::MalformedOptions,
+            ParseFailureReason::of(&[0x40, 0x01, 0x00, 0x01, 0xF0])
+        );
+    }
+}
@@ -53,21 +53,19 @@ impl<US: AsyncDatagramSocket> RemoteEndpoint for DatagramRemoteEndpoint<US> {
         };
 
         let scheme = local_endpoint.scheme();
-        let path = &self.path;
-
-        let mut uri_abs = if self.host.is_some() && !self.host.as_ref().unwrap().is_empty() {
-            let host = self.host.as_ref().unwrap();
-            let port = self.socket_addr.port();
-            if port != local_endpoint.default_port() {
-                UriBuf::from_scheme_host_port(scheme, host, Some(port))
-            } else {
-                UriBuf::from_scheme_host_port(scheme, host, None)
-            }
-        } else {
-            uri_format!("{}://{}", scheme, self.socket_addr).unwrap()
+
+        let port = match self.socket_addr.port() {
+            0 => None,
+            port if port == local_endpoint.default_port() => None,
+            port => Some(port),
+        };
+
+        let mut uri_abs = match self.host_option() {
+            Some(host) if !host.is_empty() => UriBuf::from_scheme_host_port(scheme, host, port),
+            _ => UriBuf::from_scheme_host_port(scheme, self.socket_addr.addr_to_string(), port),
         };
 
-        uri_abs.replace_path(path);
+        uri_abs.replace_path(&self.path);
 
         uri_abs
     }
@@ -79,8 +77,16 @@ impl<US: AsyncDatagramSocket> RemoteEndpoint for DatagramRemoteEndpoint<US> {
         }
     }
 
-    fn remove_host_option(&mut self) {
-        self.host = None;
+    fn host_option(&self) -> Option<&str> {
+        self.host.as_deref()
+    }
+
+    fn default_path(&self) -> &RelRef {
+        &self.path
+    }
+
+    fn set_host_option(&mut self, host: Option<&str>) {
+        self.host = host.map(String::from);
     }
 
     fn clone_using_rel_ref(&self, uri: &RelRef) -> Self {
@@ -146,3 +152,123 @@ impl<US: AsyncDatagramSocket> RemoteEndpoint for DatagramRemoteEndpoint<US> {
         ret.boxed()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use futures::future::{select, Either};
+
+    #[test]
+    fn uri_round_trip() {
+        let socket = AllowStdUdpSocket::bind("[::]:0").expect("UDP bind failed");
+        let local_endpoint = DatagramLocalEndpoint::new(socket);
+
+        let check = |uri: &Uri, expected: &Uri| {
+            let remote_endpoint = local_endpoint
+                .remote_endpoint_from_uri(uri)
+                .expect("Remote endpoint lookup failed");
+            assert_eq!(remote_endpoint.uri(), expected, "uri: {:?}", uri);
+        };
+
+        check(uri!("coap://[::1]/"), uri!("coap://[::1]/"));
+        check(uri!("coap://[::1]"), uri!("coap://[::1]/"));
+        check(uri!("coap://[::1]:5683/a/b"), uri!("coap://[::1]/a/b"));
+        check(
+            uri!("coap://[::1]:1234/a/b?c&d=e"),
+            uri!("coap://[::1]:1234/a/b?c&d=e"),
+        );
+        check(
+            uri!("coap://127.0.0.1/a%20b/c%2Fd#frag"),
+            uri!("coap://127.0.0.1/a%20b/c%2Fd"),
+        );
+        check(
+            uri!("coap://localhost:1234/?q"),
+            uri!("coap://localhost:1234/?q"),
+        );
+    }
+
+    #[test]
+    fn accessors() {
+        let socket = AllowStdUdpSocket::bind("[::]:0").expect("UDP bind failed");
+        let local_endpoint = DatagramLocalEndpoint::new(socket);
+
+        let mut remote_endpoint = local_endpoint
+            .remote_endpoint_from_uri(uri!("coap://localhost/a/b?c"))
+            .expect("Remote endpoint lookup failed");
+        assert_eq!(remote_endpoint.host_option(), Some("localhost"));
+        assert_eq!(remote_endpoint.default_path(), rel_ref!("/a/b?c"));
+
+        let remote_endpoint_2 = remote_endpoint.clone_using_rel_ref(rel_ref!("d"));
+        assert_eq!(remote_endpoint_2.host_option(), Some("localhost"));
+        assert_eq!(remote_endpoint_2.default_path(), rel_ref!("/a/d"));
+
+        remote_endpoint.remove_host_option();
+        assert_eq!(remote_endpoint.host_option(), None);
+
+        let remote_endpoint = local_endpoint
+            .remote_endpoint_from_uri(uri!("coap://[::1]/"))
+            .expect("Remote endpoint lookup failed");
+        assert_eq!(remote_endpoint.host_option(), None);
+    }
+
+    #[test]
+    fn uri_host_loopback() {
+        let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+
+        // Responds with the values of all of the `Uri-Host` options in the request.
+        let handler = |context: &DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+            let hosts = context
+                .message()
+                .options()
+                .filter_map(Result::ok)
+                .filter(|(number, _)| *number == OptionNumber::URI_HOST)
+                .map(|(_, value)| String::from_utf8_lossy(value).into_owned())
+                .collect::<Vec<_>>();
+
+            context.respond(|msg_out| {
+                msg_out.set_msg_code(MsgCode::SuccessContent);
+                msg_out.append_payload_string(&hosts.join(","))
+            })
+        };
+
+        let send = |remote_endpoint: &DatagramRemoteEndpoint<LoopbackSocket>,
+                    host: Option<&str>| {
+            let request = CoapRequest::get();
+            let future = match host {
+                Some(host) => {
+                    remote_endpoint.send(request.uri_host(host).emit_successful_response())
+                }
+                None => remote_endpoint.send(request.emit_successful_response()),
+            };
+
+            match block_on(select(future, local_endpoint.receive_loop(handler))) {
+                Either::Right(_) => panic!("Receive future finished unexpectedly"),
+                Either::Left((ret, _)) => ret.unwrap().payload_as_str().unwrap().to_string(),
+            }
+        };
+
+        let mut remote_endpoint = local_endpoint.remote_endpoint(
+            LoopbackSocketAddr::Unicast,
+            Some("default.example.com"),
+            rel_ref!("test"),
+        );
+
+        assert_eq!(send(&remote_endpoint, None), "default.example.com");
+        assert_eq!(
+            send(&remote_endpoint, Some("other.example.com")),
+            "other.example.com"
+        );
+
+        remote_endpoint.set_host_option(Some("vhost.example.com"));
+        assert_eq!(remote_endpoint.host_option(), Some("vhost.example.com"));
+        assert_eq!(send(&remote_endpoint, None), "vhost.example.com");
+
+        remote_endpoint.set_host_option(None);
+        assert_eq!(send(&remote_endpoint, None), "");
+        assert_eq!(
+            send(&remote_endpoint, Some("other.example.com")),
+            "other.example.com"
+        );
+    }
+}
@@ -17,9 +17,16 @@ use super::*;
 use std::collections::HashMap;
 use std::fmt::{Debug, Formatter};
 use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
 
 pub(crate) trait HandleResponse<IC: InboundContext>: Send {
     fn handle_response(&mut self, context: Result<&IC, Error>) -> bool;
+
+    /// Returns true if the last response passed to `handle_response` was deferred by the
+    /// send descriptor with [`ResponseStatus::Defer`], so it shouldn't be acknowledged.
+    fn is_deferred(&self) -> bool {
+        false
+    }
 }
 
 pub(super) trait ResponseTracker<IC: InboundContext> {
@@ -39,9 +46,35 @@ pub(super) trait ResponseTracker<IC: InboundContext> {
     );
 }
 
+/// How [`UdpResponseTracker::handle_response`] matched a response to an exchange.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(super) enum ResponseMatch {
+    /// The response didn't match any exchange.
+    Unmatched,
+
+    /// The response matched an exchange.
+    Matched,
+
+    /// The response is an acknowledgement with the wrong message id, which only matched
+    /// an exchange because lenient matching is enabled.
+    MatchedLeniently,
+
+    /// The response matched an exchange, but it was deferred, so it shouldn't be
+    /// acknowledged.
+    Deferred,
+}
+
+/// A weak reference to the handler of an outstanding exchange.
+type WeakHandler<IC> = Weak<Mutex<dyn HandleResponse<IC>>>;
+
+/// The handler of an exchange matched by token, along with when its request was
+/// transmitted.
+type TokenEntry<IC> = (WeakHandler<IC>, Instant);
+
 pub(crate) struct UdpResponseTracker<IC: InboundContext> {
-    msg_id_map: HashMap<(MsgId, Option<IC::SocketAddr>), Weak<Mutex<dyn HandleResponse<IC>>>>,
-    msg_token_map: HashMap<(MsgToken, Option<IC::SocketAddr>), Weak<Mutex<dyn HandleResponse<IC>>>>,
+    msg_id_map: HashMap<(MsgId, Option<IC::SocketAddr>), WeakHandler<IC>>,
+    msg_token_map: HashMap<(MsgToken, Option<IC::SocketAddr>), TokenEntry<IC>>,
+    lenient_window: Option<Duration>,
 }
 
 impl<IC: InboundContext> Debug for UdpResponseTracker<IC> {
@@ -58,12 +91,27 @@ impl<IC: InboundContext> UdpResponseTracker<IC> {
         UdpResponseTracker {
             msg_id_map: HashMap::new(),
             msg_token_map: HashMap::new(),
+            // Matches acknowledgements by token no matter when the request was
+            // transmitted, like earlier versions did.
+            lenient_window: Some(Duration::MAX),
         }
     }
 
-    pub(super) fn handle_response(&mut self, context: &IC) -> bool {
+    /// Allows acknowledgements whose message id doesn't match to be matched by their token
+    /// instead, if the request was transmitted no longer than `window` ago.
+    pub(super) fn set_lenient_window(&mut self, window: Option<Duration>) {
+        self.lenient_window = window;
+    }
+
+    /// The number of exchanges that are waiting for a response.
+    pub(super) fn len(&self) -> usize {
+        self.msg_token_map.len()
+    }
+
+    pub(super) fn handle_response(&mut self, context: &IC) -> ResponseMatch {
         let message = context.message();
         let socket_addr = context.remote_socket_addr();
+        let msg_type = message.msg_type();
 
         if let Some(weak) = self
             .msg_id_map
@@ -78,26 +126,77 @@ impl<IC: InboundContext> UdpResponseTracker<IC> {
                     self.remove_by_token(message.msg_token(), socket_addr);
                 }
 
-                return true;
+                return if handler.is_deferred() {
+                    ResponseMatch::Deferred
+                } else {
+                    ResponseMatch::Matched
+                };
             }
-        } else if let Some(weak) = self
+        } else if let Some((weak, transmitted_at)) = self
             .msg_token_map
             .get(&(message.msg_token(), Some(socket_addr)))
             .or(self.msg_token_map.get(&(message.msg_token(), None)))
         {
-            debug!("Matched response on token");
-            if let Some(mutex) = weak.upgrade() {
-                let mut handler = mutex.lock().expect("lock failure");
-                let finished = handler.handle_response(Ok(context));
-                if finished {
-                    self.remove_by_token(message.msg_token(), socket_addr);
+            // Acknowledgements and resets must echo the message id of the message they
+            // refer to, so normally only separate responses are matched on the token.
+            let matched = if msg_type.is_ack() || msg_type.is_res() {
+                match self.lenient_window {
+                    Some(window) if msg_type.is_ack() && !message.msg_token().is_empty() => {
+                        transmitted_at.elapsed() <= window
+                    }
+                    _ => false,
                 }
+            } else {
+                true
+            };
+
+            if matched {
+                debug!("Matched response on token");
+                if let Some(mutex) = weak.upgrade() {
+                    let mut handler = mutex.lock().expect("lock failure");
+                    let finished = handler.handle_response(Ok(context));
+                    if finished {
+                        self.remove_by_token(message.msg_token(), socket_addr);
+                    }
 
-                return true;
+                    return if msg_type.is_ack() {
+                        debug!("Matched ack with wrong msgid {:04X}", message.msg_id());
+                        ResponseMatch::MatchedLeniently
+                    } else if handler.is_deferred() {
+                        ResponseMatch::Deferred
+                    } else {
+                        ResponseMatch::Matched
+                    };
+                }
             }
         }
         debug!("Response did not match.");
-        false
+        ResponseMatch::Unmatched
+    }
+
+    /// Fails all of the exchanges with `socket_addr` with `error`, returning the number
+    /// of exchanges that were affected.
+    pub(super) fn handle_error(&mut self, socket_addr: IC::SocketAddr, error: Error) -> usize {
+        let handlers: Vec<_> = self
+            .msg_token_map
+            .iter()
+            .filter(|((_, addr), _)| *addr == Some(socket_addr))
+            .map(|((token, _), (weak, _))| (*token, weak.clone()))
+            .collect();
+
+        let mut count = 0;
+
+        for (token, weak) in handlers {
+            if let Some(mutex) = weak.upgrade() {
+                let mut handler = mutex.lock().expect("lock failure");
+                if handler.handle_response(Err(error)) {
+                    self.remove_by_token(token, socket_addr);
+                }
+                count += 1;
+            }
+        }
+
+        count
     }
 
     fn remove_by_token(&mut self, token: MsgToken, socket_addr: IC::SocketAddr) {
@@ -132,8 +231,10 @@ impl<IC: InboundContext> ResponseTracker<IC> for UdpResponseTracker<IC> {
 
         self.msg_id_map
             .insert((msg_id, socket_addr), Arc::downgrade(&handler));
-        self.msg_token_map
-            .insert((msg_token, socket_addr), Arc::downgrade(&handler));
+        self.msg_token_map.insert(
+            (msg_token, socket_addr),
+            (Arc::downgrade(&handler), Instant::now()),
+        );
     }
 
     fn remove_response_handler(
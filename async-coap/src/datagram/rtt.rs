@@ -0,0 +1,139 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use std::time::Duration;
+
+/// The round-trip time to a remote endpoint, as estimated by [`DatagramLocalEndpoint`].
+///
+/// The estimate is an exponential moving average of the time between sending a request
+/// and receiving the acknowledgement or response to it, calculated as described in
+/// [IETF-RFC6298 Section 2]. Requests that had to be retransmitted are not sampled, since
+/// it is ambiguous which transmission was answered.
+///
+/// [IETF-RFC6298 Section 2]: https://tools.ietf.org/html/rfc6298#section-2
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct RttEstimate {
+    /// The smoothed round-trip time (`SRTT`).
+    pub srtt: Duration,
+
+    /// The round-trip time variation (`RTTVAR`).
+    pub rttvar: Duration,
+
+    /// The number of round-trip times that were sampled.
+    pub samples: u32,
+}
+
+impl RttEstimate {
+    /// Creates an estimate from the first sample.
+    pub(super) fn new(sample: Duration) -> RttEstimate {
+        RttEstimate {
+            srtt: sample,
+            rttvar: sample / 2,
+            samples: 1,
+        }
+    }
+
+    /// Updates the estimate with a new sample.
+    pub(super) fn update(&mut self, sample: Duration) {
+        let delta = self.srtt.abs_diff(sample);
+
+        // RTTVAR <- (1 - beta) * RTTVAR + beta * |SRTT - R'|, with beta = 1/4
+        self.rttvar = (self.rttvar * 3 + delta) / 4;
+
+        // SRTT <- (1 - alpha) * SRTT + alpha * R', with alpha = 1/8
+        self.srtt = (self.srtt * 7 + sample) / 8;
+
+        self.samples = self.samples.saturating_add(1);
+    }
+
+    /// The retransmission timeout suggested by this estimate: `SRTT + 4 * RTTVAR`.
+    pub fn retransmit_timeout(&self) -> Duration {
+        self.srtt + self.rttvar * 4
+    }
+}
+
+/// Configuration for scaling `ACK_TIMEOUT` with the estimated round-trip time to each
+/// destination. See [`DatagramLocalEndpoint::set_adaptive_ack_timeout`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct AdaptiveAckTimeout {
+    /// The smallest `ACK_TIMEOUT` to use, no matter how short the round-trip time is.
+    pub min: Duration,
+
+    /// The largest `ACK_TIMEOUT` to use, no matter how long the round-trip time is.
+    pub max: Duration,
+}
+
+impl Default for AdaptiveAckTimeout {
+    /// Allows the `ACK_TIMEOUT` to range between 200 milliseconds and 32 seconds.
+    fn default() -> Self {
+        AdaptiveAckTimeout {
+            min: Duration::from_millis(200),
+            max: Duration::from_secs(32),
+        }
+    }
+}
+
+impl AdaptiveAckTimeout {
+    /// Returns the factor by which delays derived from `ack_timeout` are to be scaled,
+    /// given the round-trip time `estimate`.
+    pub(super) fn scale_factor(&self, estimate: &RttEstimate, ack_timeout: Duration) -> f64 {
+        let adapted = estimate.retransmit_timeout().max(self.min).min(self.max);
+
+        if ack_timeout == Duration::from_secs(0) {
+            1.0
+        } else {
+            adapted.as_secs_f64() / ack_timeout.as_secs_f64()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimate() {
+        let mut estimate = RttEstimate::new(Duration::from_millis(100));
+        assert_eq!(Duration::from_millis(100), estimate.srtt);
+        assert_eq!(Duration::from_millis(50), estimate.rttvar);
+        assert_eq!(Duration::from_millis(300), estimate.retransmit_timeout());
+
+        estimate.update(Duration::from_millis(180));
+        assert_eq!(Duration::from_millis(110), estimate.srtt);
+        assert_eq!(Duration::from_millis(57500), estimate.rttvar * 1000);
+        assert_eq!(2, estimate.samples);
+
+        for _ in 0..100 {
+            estimate.update(Duration::from_millis(40));
+        }
+        assert!(estimate.srtt < Duration::from_millis(41));
+        assert!(estimate.rttvar < Duration::from_millis(1));
+    }
+
+    #[test]
+    fn scale_factor() {
+        let adaptive = AdaptiveAckTimeout::default();
+        let ack_timeout = Duration::from_secs(2);
+
+        let lan = RttEstimate::new(Duration::from_millis(10));
+        assert_eq!(0.1, adaptive.scale_factor(&lan, ack_timeout));
+
+        let satellite = RttEstimate::new(Duration::from_millis(1200));
+        assert_eq!(1.8, adaptive.scale_factor(&satellite, ack_timeout));
+
+        let broken = RttEstimate::new(Duration::from_secs(100));
+        assert_eq!(16.0, adaptive.scale_factor(&broken, ack_timeout));
+    }
+}
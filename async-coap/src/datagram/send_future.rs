@@ -14,11 +14,14 @@
 //
 
 use super::*;
-use crate::message::BufferMessageEncoder;
+use crate::message::{
+    BufferMessageEncoder, OwnedImmutableMessage, StandardMessageParser, VecMessageEncoder,
+};
 use futures::prelude::*;
 use futures::task::{Waker, Poll};
 use futures_timer::Delay;
 use std::cell::Cell;
+use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 use std::ops::Bound;
 use std::pin::Pin;
@@ -58,6 +61,15 @@ impl<R> Display for UdpSendFutureState<R> {
 }
 
 impl<R> UdpSendFutureState<R> {
+    pub fn send_state(&self) -> SendState {
+        match self {
+            UdpSendFutureState::Uninit => SendState::NotSent,
+            UdpSendFutureState::ActivelyWaiting => SendState::WaitingForAck,
+            UdpSendFutureState::PassivelyWaiting => SendState::WaitingForResponse,
+            UdpSendFutureState::Finished(_) | UdpSendFutureState::Expired => SendState::Finished,
+        }
+    }
+
     pub fn is_waiting(&self) -> bool {
         match self {
             UdpSendFutureState::ActivelyWaiting | UdpSendFutureState::PassivelyWaiting => true,
@@ -95,10 +107,37 @@ where
 
     msg_id: Cell<MsgId>,
     msg_token: Cell<MsgToken>,
+    sent_request: Cell<bool>,
     retransmit_count: Cell<u32>,
     delay: Option<Delay>,
-    timeout: Cell<Option<Instant>>,
-    _trans_params: TP, // <datagram::DatagramLocalEndpoint<US> as LocalEndpoint>::DefaultTransParams
+    delay_deadline: Option<Instant>,
+    timeout: Cell<Option<<StdClock as Clock>::Instant>>,
+    status: Option<Arc<Mutex<SendStatus<US::SocketAddr>>>>,
+    transmitted_at: Cell<Option<Instant>>,
+
+    /// When the first request of this exchange was transmitted.
+    started_at: Cell<Option<Instant>>,
+
+    /// Whether a request of this exchange was recorded in the [`TransactionJournal`].
+    journaled: Cell<bool>,
+    acked: Cell<bool>,
+
+    /// The change to apply to the requests, from `ResponseStatus::SendNextWith`.
+    modify_request: Option<ModifyRequest>,
+
+    /// Whether the send descriptor deferred the last response it was given.
+    deferred: bool,
+
+    /// The sequence number of the freshest notification from each remote endpoint, and
+    /// when it was received.
+    notifications: HashMap<US::SocketAddr, (ObserveSeq, Instant)>,
+    last_cause: Cell<Option<FailureCause>>,
+
+    /// The key and id this exchange was registered with, if identical requests can follow it.
+    coalesced: Option<(CacheKey, u64)>,
+    followers: Vec<WeakCoalescedExchange<DatagramInboundContext<US::SocketAddr>>>,
+    shutdown: ShutdownFuture,
+    trans_params: TP, // <datagram::DatagramLocalEndpoint<US> as LocalEndpoint>::DefaultTransParams
 }
 
 impl<R, SD, US, TP> UdpSendFutureInner<R, SD, US, TP>
@@ -112,6 +151,32 @@ where
         &self.state
     }
 
+    /// Returns false if `context` is a notification which is no fresher than the last one
+    /// received from the same remote endpoint, because it was reordered or duplicated in
+    /// transit. See [IETF-RFC7641 Section 3.4].
+    ///
+    /// [IETF-RFC7641 Section 3.4]: https://tools.ietf.org/html/rfc7641#section-3.4
+    fn is_fresh_notification(&mut self, context: &DatagramInboundContext<US::SocketAddr>) -> bool {
+        let seq = match context.message().options().find_next_of(option::OBSERVE) {
+            Some(Ok(seq)) => ObserveSeq::new(seq),
+            _ => return true,
+        };
+        let received = Instant::now();
+
+        match self.notifications.get(&context.remote_socket_addr()) {
+            Some(&(last_seq, last_received))
+                if !seq.is_fresher_than(received, last_seq, last_received) =>
+            {
+                false
+            }
+            _ => {
+                self.notifications
+                    .insert(context.remote_socket_addr(), (seq, received));
+                true
+            }
+        }
+    }
+
     fn change_state(&mut self, mut state: UdpSendFutureState<R>) -> UdpSendFutureState<R> {
         if state.is_finished() {
             self.update_timeout(None);
@@ -133,9 +198,11 @@ where
     }
 
     fn update_timeout(&mut self, d: Option<Duration>) {
+        self.delay_deadline = d.map(|d| Instant::now() + d);
+
         if let Some(d) = d {
             if let Some(delay) = self.delay.as_mut() {
-                delay.reset(Instant::now() + d);
+                delay.reset(StdClock.now().saturating_add(d));
             } else {
                 self.delay = Some(Delay::new(d));
             }
@@ -155,8 +222,45 @@ where
         }
     }
 
+    /// Polls the timeout so that we are woken up once it expires. If it has already
+    /// expired (which can happen for very short timeouts), we arrange to be polled again.
+    fn register_timeout(&mut self, cx: &mut futures::task::Context<'_>) {
+        if self.poll_timeout(cx).is_ready() {
+            cx.waker().wake_by_ref();
+        }
+    }
+
+    /// Updates the status reported to any [`SendStatusHandle`]s.
+    fn update_status(&self) {
+        if let Some(status) = self.status.as_ref() {
+            let mut status = status.lock().expect("Lock failed");
+            status.state = self.state.send_state();
+            status.msg_id = self.msg_id.get();
+            status.msg_token = self.msg_token.get();
+            status.retransmit_count = self.retransmit_count.get();
+            status.next_timeout = self.delay_deadline.filter(|_| self.state.is_waiting());
+        }
+    }
+
+    /// Records the block options of a transmitted request for any [`SendStatusHandle`]s.
+    fn update_status_blocks(&self, buffer: &[u8]) {
+        if let Some(status) = self.status.as_ref() {
+            if let Ok(msg) = StandardMessageParser::new(buffer) {
+                let mut status = status.lock().expect("Lock failed");
+                status.block1 = msg.block1();
+                status.block2 = msg.block2();
+            }
+        }
+    }
+
     pub fn transmit(&self) -> Result<(), Error> {
-        let mut buffer = [0u8; StandardCoapConstants::MAX_OUTBOUND_PACKET_LENGTH];
+        let mut buffer = vec![
+            0u8;
+            self.local_endpoint
+                .upgrade()
+                .ok_or(Error::Cancelled)?
+                .max_outbound_message_size()
+        ];
         let mut builder = BufferMessageEncoder::new(&mut buffer);
 
         let mut token = self.msg_token.get();
@@ -181,10 +285,15 @@ where
             Bound::Unbounded,
         )?;
         self.send_desc.write_payload(&mut builder, &self.dest)?;
+        if let Some(modify) = self.modify_request.as_ref() {
+            modify.apply(&mut builder)?;
+        }
 
         let builder_token = builder.msg_token();
 
         self.msg_token.replace(builder_token);
+        self.sent_request
+            .set(MsgCode::try_from(builder.as_bytes()[1]).map(MsgCode::is_method) == Some(true));
 
         // We always control the msg_id.
         builder.set_msg_id(self.msg_id.get());
@@ -193,12 +302,22 @@ where
 
         let buffer: &[u8] = &builder;
 
-        if let Some(e) = self
-            .local_endpoint
+        self.record_in_journal(buffer);
+
+        self.local_endpoint
             .upgrade()
             .ok_or(Error::Cancelled)?
+            .check_path_mtu(self.dest, buffer.len())?;
+
+        self.update_status_blocks(buffer);
+
+        let local_endpoint = self.local_endpoint.upgrade().ok_or(Error::Cancelled)?;
+        let buffer = local_endpoint.protect_outbound(self.dest, buffer)?;
+        let buffer = local_endpoint.intercept_outbound(self.dest, &buffer)?;
+
+        if let Some(e) = local_endpoint
             .socket()
-            .send_to(&buffer, self.dest)
+            .send_to_with_traffic_class(&buffer, self.dest, self.send_desc.traffic_class())
             .now_or_never()
             .expect("send_to blocked")
             .err()
@@ -209,17 +328,143 @@ where
 
         println!("Did transmit.");
 
+        self.transmitted_at.set(Some(Instant::now()));
+
+        if self.started_at.get().is_none() {
+            self.started_at.set(self.transmitted_at.get());
+        }
+
+        if let Some(local_endpoint) = self.local_endpoint.upgrade() {
+            local_endpoint.stats().message_out();
+        }
+
         self.retransmit_count.set(0);
+        self.acked.set(false);
+        self.last_cause.set(None);
 
         Ok(())
     }
 
+    /// Records `buffer` in the [`TransactionJournal`], if there is one and `buffer` is a
+    /// confirmable request.
+    fn record_in_journal(&self, buffer: &[u8]) {
+        let journal = match self
+            .local_endpoint
+            .upgrade()
+            .and_then(|local_endpoint| local_endpoint.transaction_journal())
+        {
+            Some(journal) => journal,
+            None => return,
+        };
+
+        if let Ok(message) = OwnedImmutableMessage::new(buffer.to_vec()) {
+            if message.msg_type().is_con() && message.msg_code().is_method() {
+                self.journaled.set(true);
+                journal.record(JournalEntry {
+                    dest: self.dest,
+                    message,
+                });
+            }
+        }
+    }
+
+    /// Describes how this exchange went, for when it has failed with `error`.
+    fn failure(&self, error: Error) -> TransactionFailure {
+        let attempts = match self.started_at.get() {
+            Some(_) => self.retransmit_count.get() + 1,
+            None => 0,
+        };
+
+        let is_timeout = matches!(
+            error,
+            Error::ResponseTimeout | Error::ObserveRegistrationTimeout | Error::ObservationLapsed
+        );
+
+        let cause = match self.last_cause.get() {
+            None if is_timeout && attempts > 0 && self.acked.get() => {
+                Some(FailureCause::NoResponse)
+            }
+            None if is_timeout && attempts > 0 => Some(FailureCause::NoAck),
+            cause => cause,
+        };
+
+        TransactionFailure {
+            error,
+            attempts,
+            elapsed: self
+                .started_at
+                .get()
+                .map(|started_at| started_at.elapsed())
+                .unwrap_or_default(),
+            cause,
+        }
+    }
+
+    /// Calls [`SendDesc::delay_to_retransmit`] with the local endpoint's random source in
+    /// effect, scaling the result if the local endpoint uses an adaptive ACK timeout.
+    ///
+    /// The delay never extends past the time when we give up on the exchange, so that
+    /// a [transmit wait duration](SendDesc::transmit_wait_duration) which is shorter than
+    /// the delay is still honored.
+    fn delay_to_retransmit(&self) -> Option<Duration> {
+        let retransmits_sent = self.retransmit_count.get();
+
+        let delay = match self.local_endpoint.upgrade() {
+            Some(local_endpoint) => {
+                let delay = local_endpoint
+                    .with_random_source(|| self.send_desc.delay_to_retransmit(retransmits_sent))?;
+
+                match self.ack_timeout_scale_factor(&local_endpoint) {
+                    Some(factor) => delay.mul_f64(factor),
+                    None => delay,
+                }
+            }
+            None => self.send_desc.delay_to_retransmit(retransmits_sent)?,
+        };
+
+        Some(match self.timeout.get() {
+            Some(timeout) => {
+                let remaining = timeout.remaining(StdClock.now()).unwrap_or_default();
+                std::cmp::min(delay, remaining)
+            }
+            None => delay,
+        })
+    }
+
+    /// Returns the time to wait for an acknowledgement before giving up, which is scaled
+    /// up (but never down) if the local endpoint uses an adaptive ACK timeout.
+    fn transmit_wait_duration(&self) -> Duration {
+        let duration = self.send_desc.transmit_wait_duration();
+
+        match self
+            .local_endpoint
+            .upgrade()
+            .and_then(|local_endpoint| self.ack_timeout_scale_factor(&local_endpoint))
+        {
+            Some(factor) if factor > 1.0 => duration.mul_f64(factor),
+            _ => duration,
+        }
+    }
+
+    fn ack_timeout_scale_factor(
+        &self,
+        local_endpoint: &DatagramLocalEndpointInner<US>,
+    ) -> Option<f64> {
+        local_endpoint.ack_timeout_scale_factor(self.dest, self.trans_params.coap_ack_timeout())
+    }
+
     pub fn retransmit(&self) -> Result<(), Error> {
-        let mut buffer = [0u8; StandardCoapConstants::MAX_OUTBOUND_PACKET_LENGTH];
+        let mut buffer = vec![
+            0u8;
+            self.local_endpoint
+                .upgrade()
+                .ok_or(Error::Cancelled)?
+                .max_outbound_message_size()
+        ];
         let mut builder = BufferMessageEncoder::new(&mut buffer);
 
         if let Some(timeout) = self.timeout.get() {
-            if Instant::now() > timeout {
+            if timeout.remaining(StdClock.now()).is_none() {
                 return Err(Error::ResponseTimeout);
             }
         }
@@ -233,6 +478,9 @@ where
             Bound::Unbounded,
         )?;
         self.send_desc.write_payload(&mut builder, &self.dest)?;
+        if let Some(modify) = self.modify_request.as_ref() {
+            modify.apply(&mut builder)?;
+        }
 
         builder.set_msg_id(self.msg_id.get());
 
@@ -245,12 +493,20 @@ where
 
         let buffer: &[u8] = &builder;
 
-        if let Some(e) = self
-            .local_endpoint
+        self.local_endpoint
             .upgrade()
             .ok_or(Error::Cancelled)?
+            .check_path_mtu(self.dest, buffer.len())?;
+
+        self.update_status_blocks(buffer);
+
+        let local_endpoint = self.local_endpoint.upgrade().ok_or(Error::Cancelled)?;
+        let buffer = local_endpoint.protect_outbound(self.dest, buffer)?;
+        let buffer = local_endpoint.intercept_outbound(self.dest, &buffer)?;
+
+        if let Some(e) = local_endpoint
             .socket()
-            .send_to(buffer, self.dest)
+            .send_to_with_traffic_class(&buffer, self.dest, self.send_desc.traffic_class())
             .now_or_never()
             .expect("send_to blocked")
             .err()
@@ -261,11 +517,82 @@ where
 
         self.retransmit_count.set(self.retransmit_count.get() + 1);
 
+        // It's now ambiguous which transmission gets answered, so we can't measure the
+        // round-trip time.
+        self.transmitted_at.set(None);
+
         println!("Did retransmit, count {}", self.retransmit_count.get());
 
+        if let Some(local_endpoint) = self.local_endpoint.upgrade() {
+            let stats = local_endpoint.stats();
+            stats.message_out();
+            stats.retransmit();
+        }
+
         Ok(())
     }
 
+    /// Returns the key on which this request can be coalesced with identical requests.
+    fn coalesce_key(&self) -> Option<CacheKey> {
+        if self.dest.is_multicast() {
+            return None;
+        }
+
+        let mut builder =
+            VecMessageEncoder::with_payload_capacity(self.send_desc.payload_size_hint());
+
+        self.send_desc
+            .write_options(&mut builder, &self.dest, Bound::Unbounded, Bound::Unbounded)
+            .ok()?;
+        self.send_desc
+            .write_payload(&mut builder, &self.dest)
+            .ok()?;
+
+        coalesce::coalesce_key(&OwnedImmutableMessage::from(builder))
+    }
+
+    /// Joins the in-flight exchange of an identical request, returning true if this
+    /// exchange now follows it instead of sending its own request.
+    fn join_coalesced_exchange<'a>(
+        &mut self,
+        this: Arc<Mutex<dyn CoalescedExchange<DatagramInboundContext<US::SocketAddr>> + 'a>>,
+    ) -> bool {
+        let local_endpoint = match self.local_endpoint.upgrade() {
+            Some(local_endpoint) => local_endpoint,
+            None => return false,
+        };
+
+        let key = match self.coalesce_key() {
+            Some(key) => key,
+            None => return false,
+        };
+
+        match local_endpoint.join_coalesced_exchange(self.dest, key.clone(), this) {
+            Coalesced::No => false,
+            Coalesced::Leading(id) => {
+                self.coalesced = Some((key, id));
+                false
+            }
+            Coalesced::Following => true,
+        }
+    }
+
+    /// Stops identical requests from following this exchange. Any followers which are
+    /// still waiting are restarted if `restart` is true.
+    fn end_coalesced_exchange(&mut self, restart: bool) {
+        if let Some((key, id)) = self.coalesced.take() {
+            if let Some(local_endpoint) = self.local_endpoint.upgrade() {
+                local_endpoint.remove_coalesced_exchange(self.dest, key, id);
+            }
+        }
+
+        for follower in self.followers.drain(..) {
+            if let Some(follower) = follower.upgrade().filter(|_| restart) {
+                follower.lock().expect("Lock failed").restart();
+            }
+        }
+    }
+
     fn wake(&mut self) {
         if let Some(waker) = self.waker.take() {
             waker.wake();
@@ -282,14 +609,67 @@ where
     TP: TransParams,
 {
     fn handle_response(&mut self, context: Result<&DatagramInboundContext<US::SocketAddr>, Error>) -> bool {
+        // Errors (like an unreachable destination) may be reported at any time, but
+        // they are only relevant if we are waiting for a response.
+        if context.is_err() && !self.state().is_waiting() {
+            return self.state.is_finished();
+        }
+
         // This should only be called if we are waiting for a response.
         assert!(self.state().is_waiting(), "Invalid state: {}", self.state());
+        self.deferred = false;
+
+        if let Ok(context) = context {
+            if !self.is_fresh_notification(context) {
+                // It is still acknowledged, but it must not be handled.
+                debug!(
+                    "Dropping stale notification from {}",
+                    context.remote_socket_addr()
+                );
+                return self.state.is_finished();
+            }
+        }
 
-        // If this is an ack, we don't pass this along to the send_desc.
+        // Any exchanges following this one get the same responses, but not our acks.
+        if context
+            .map(|context| !context.message().msg_code().is_empty())
+            .unwrap_or(true)
+        {
+            for follower in self.followers.iter().filter_map(Weak::upgrade) {
+                follower
+                    .lock()
+                    .expect("Lock failed")
+                    .handle_coalesced_response(context);
+            }
+        }
+
+        match context {
+            Err(Error::HostUnreachable) => self.last_cause.set(Some(FailureCause::Unreachable)),
+            Ok(context) if context.message().msg_type().is_res() => {
+                self.last_cause.set(Some(FailureCause::Reset))
+            }
+            Ok(_) => self.acked.set(true),
+            Err(_) => (),
+        }
+
+        // If this is an ack for a request, we don't pass this along to the send_desc.
+        // Acks for anything else (like notifications) are the only reply we will get,
+        // so those are passed along.
         if let Some(context) = context.ok() {
             let message = context.message();
 
+            if let Some(transmitted_at) = self.transmitted_at.take() {
+                if let Some(local_endpoint) = self
+                    .local_endpoint
+                    .upgrade()
+                    .filter(|_| !self.dest.is_multicast())
+                {
+                    local_endpoint.add_rtt_sample(self.dest, transmitted_at.elapsed());
+                }
+            }
+
             if !self.dest.is_multicast()
+                && self.sent_request.get()
                 && message.msg_code().is_empty()
                 && message.msg_type().is_ack()
             {
@@ -298,18 +678,22 @@ where
                 self.change_state(UdpSendFutureState::PassivelyWaiting);
                 let d = self.send_desc.max_rtt();
                 self.update_timeout(Some(d));
+                self.update_status();
                 self.wake();
                 return self.state.is_finished();
             }
         }
 
         // Pass the full context along to our `send_desc.handler()`
-        match self.send_desc.handler(context) {
+        let status = self.send_desc.handler(context);
+        self.deferred = matches!(status, Ok(ResponseStatus::Defer));
+
+        match status {
             Ok(ResponseStatus::Done(x)) => {
                 // Stick a fork in us, we are done.
                 self.change_state(UdpSendFutureState::Finished(Ok(x)));
             }
-            Ok(ResponseStatus::Continue) => {
+            Ok(ResponseStatus::Continue) | Ok(ResponseStatus::Defer) => {
                 if !self.dest.is_multicast() {
                     self.change_state(UdpSendFutureState::PassivelyWaiting);
                     let d = self.send_desc.max_rtt();
@@ -318,6 +702,11 @@ where
             }
             Ok(ResponseStatus::SendNext) => {
                 // Allocate a new msg-id, Reset retransmit count, and resend.
+                self.modify_request = None;
+                self.change_state(UdpSendFutureState::Uninit);
+            }
+            Ok(ResponseStatus::SendNextWith(modify)) => {
+                self.modify_request = Some(modify);
                 self.change_state(UdpSendFutureState::Uninit);
             }
             Err(e) => {
@@ -325,10 +714,57 @@ where
             }
         }
 
+        if !self.state.is_waiting() {
+            self.end_coalesced_exchange(false);
+        }
+
+        self.update_status();
         self.wake();
 
         self.state.is_finished()
     }
+
+    fn is_deferred(&self) -> bool {
+        self.deferred
+    }
+}
+
+impl<R, SD, US, TP> CoalescedExchange<DatagramInboundContext<US::SocketAddr>>
+    for UdpSendFutureInner<R, SD, US, TP>
+where
+    R: Send,
+    SD: SendDesc<DatagramInboundContext<US::SocketAddr>, R>,
+    US: AsyncDatagramSocket,
+    TP: TransParams,
+{
+    fn add_follower(
+        &mut self,
+        follower: WeakCoalescedExchange<DatagramInboundContext<US::SocketAddr>>,
+    ) -> bool {
+        if self.state.is_waiting() {
+            self.followers.push(follower);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn handle_coalesced_response(
+        &mut self,
+        context: Result<&DatagramInboundContext<US::SocketAddr>, Error>,
+    ) {
+        if self.state.is_waiting() {
+            self.handle_response(context);
+        }
+    }
+
+    fn restart(&mut self) {
+        if self.state.is_waiting() {
+            self.change_state(UdpSendFutureState::Uninit);
+            self.update_status();
+            self.wake();
+        }
+    }
 }
 
 pub(super) struct UdpSendFuture<R, SD, US, TP>
@@ -361,16 +797,57 @@ where
                 waker: None,
                 msg_id: Cell::new(0),
                 msg_token: Cell::new(MsgToken::EMPTY),
+                sent_request: Cell::new(true),
                 local_endpoint: Arc::downgrade(&local_endpoint),
                 dest,
                 retransmit_count: Cell::new(0),
                 delay: None,
+                delay_deadline: None,
                 timeout: Cell::new(None),
-                _trans_params: trans_params,
+                status: None,
+                transmitted_at: Cell::new(None),
+                started_at: Cell::new(None),
+                journaled: Cell::new(false),
+                acked: Cell::new(false),
+                deferred: false,
+                notifications: HashMap::new(),
+                modify_request: None,
+                last_cause: Cell::new(None),
+                coalesced: None,
+                followers: Vec::new(),
+                shutdown: local_endpoint.shutdown_future(),
+                trans_params,
             })),
         }
     }
 
+    /// Uses `msg_token` instead of a token derived from the message id.
+    pub(super) fn with_msg_token(self, msg_token: MsgToken) -> Self {
+        self.inner
+            .lock()
+            .expect("UdpSendFuture inner mutex poisoned")
+            .msg_token
+            .set(msg_token);
+        self
+    }
+
+    /// Returns a handle for inspecting the status of this send future.
+    pub(super) fn status_handle(&self) -> SendStatusHandle<US::SocketAddr> {
+        let mut inner = self
+            .inner
+            .lock()
+            .expect("UdpSendFuture inner mutex poisoned");
+
+        let dest = inner.dest;
+        let handle = SendStatusHandle::new(
+            inner
+                .status
+                .get_or_insert_with(|| Arc::new(Mutex::new(SendStatus::new(dest)))),
+        );
+        inner.update_status();
+        handle
+    }
+
     fn poll(
         &mut self,
         cx: &mut futures::task::Context<'_>,
@@ -380,14 +857,27 @@ where
             .lock()
             .expect("UdpSendFuture inner mutex poisoned");
 
+        if !inner.state().is_finished() && inner.shutdown.poll_unpin(cx).is_ready() {
+            inner.change_state(UdpSendFutureState::Finished(Err(Error::Cancelled)));
+        }
+
         match inner.state() {
             UdpSendFutureState::Uninit => {
                 // TODO(#4): Figure out how this can be set programmatically.
                 inner.timeout.set(Some(
-                    Instant::now() + inner.send_desc.transmit_wait_duration(),
+                    StdClock
+                        .now()
+                        .saturating_add(inner.transmit_wait_duration()),
                 ));
 
-                if let Some(error) = inner.transmit().err() {
+                if inner.join_coalesced_exchange(self.inner.clone()) {
+                    // We will get the responses to an identical request that is
+                    // already in flight, so we don't send our own.
+                    inner.change_state(UdpSendFutureState::PassivelyWaiting);
+                    let d = inner.transmit_wait_duration() + inner.send_desc.max_rtt();
+                    inner.update_timeout(Some(d));
+                    inner.register_timeout(cx);
+                } else if let Some(error) = inner.transmit().err() {
                     inner.change_state(UdpSendFutureState::Finished(Err(error)));
                 } else {
                     inner
@@ -401,18 +891,15 @@ where
                             self.inner.clone(),
                         );
 
-                    if let Some(d) = inner
-                        .send_desc
-                        .delay_to_retransmit(inner.retransmit_count.get())
-                    {
+                    if let Some(d) = inner.delay_to_retransmit() {
                         inner.change_state(UdpSendFutureState::ActivelyWaiting);
                         inner.update_timeout(Some(d));
-                        let _ = inner.poll_timeout(cx);
+                        inner.register_timeout(cx);
                     } else {
                         inner.change_state(UdpSendFutureState::PassivelyWaiting);
                         let d = inner.send_desc.max_rtt();
                         inner.update_timeout(Some(d));
-                        let _ = inner.poll_timeout(cx);
+                        inner.register_timeout(cx);
                     }
                 }
             }
@@ -421,18 +908,21 @@ where
                 // We are waiting to retransmit.
                 if inner.poll_timeout(cx).is_ready() {
                     if let Some(error) = inner.retransmit().err() {
-                        inner.change_state(UdpSendFutureState::Finished(Err(error)));
-                    } else if let Some(d) = inner
-                        .send_desc
-                        .delay_to_retransmit(inner.retransmit_count.get())
-                    {
+                        if error == Error::ResponseTimeout {
+                            // Let the send descriptor decide how to report the timeout,
+                            // like when waiting passively.
+                            inner.handle_response(Err(error));
+                        } else {
+                            inner.change_state(UdpSendFutureState::Finished(Err(error)));
+                        }
+                    } else if let Some(d) = inner.delay_to_retransmit() {
                         inner.update_timeout(Some(d));
-                        let _ = inner.poll_timeout(cx);
+                        inner.register_timeout(cx);
                     } else {
                         inner.change_state(UdpSendFutureState::PassivelyWaiting);
                         let d = inner.send_desc.max_rtt();
                         inner.update_timeout(Some(d));
-                        let _ = inner.poll_timeout(cx);
+                        inner.register_timeout(cx);
                     }
                 }
             }
@@ -449,11 +939,39 @@ where
             }
         }
 
+        if inner.state().is_finished() {
+            inner.end_coalesced_exchange(true);
+        }
+
+        inner.update_status();
+
         if inner.state().is_finished() {
             let ret = inner
                 .change_state(UdpSendFutureState::Expired)
                 .finished()
                 .unwrap();
+
+            if inner.journaled.get() {
+                if let Some(journal) = inner
+                    .local_endpoint
+                    .upgrade()
+                    .and_then(|local_endpoint| local_endpoint.transaction_journal())
+                {
+                    let result = ret.as_ref().map(|_| ()).map_err(|error| *error);
+                    journal.finished(inner.dest, inner.msg_token.get(), result);
+                }
+            }
+
+            if let Err(error) = ret.as_ref() {
+                if let Some(observer) = inner
+                    .local_endpoint
+                    .upgrade()
+                    .and_then(|local_endpoint| local_endpoint.endpoint_observer())
+                {
+                    observer.transaction_failed(inner.dest, &inner.failure(*error));
+                }
+            }
+
             futures::task::Poll::Ready(ret)
         } else {
             inner.update_waker(cx.waker());
@@ -471,7 +989,7 @@ where
     TP: TransParams,
 {
     fn drop(&mut self) {
-        let inner = match self.inner.lock() {
+        let mut inner = match self.inner.lock() {
             Ok(guard) => guard,
             Err(poisoned) => {
                 eprintln!("UdpSendFuture mutex inner was poisoned, locking anyway to drop");
@@ -479,6 +997,8 @@ where
             }
         };
 
+        inner.end_coalesced_exchange(true);
+
         if let Some(le) = inner.local_endpoint.upgrade() {
             le.remove_response_handler(inner.msg_id.get(), inner.msg_token.get(), inner.dest.clone());
         }
@@ -501,3 +1021,37 @@ where
         self.get_mut().poll(cx)
     }
 }
+
+/// Wraps a [`UdpSendFuture`] so that it fails with a [`TransactionFailure`] instead of
+/// just an [`Error`].
+pub(super) struct WithFailureDetails<R, SD, US, TP>(pub(super) UdpSendFuture<R, SD, US, TP>)
+where
+    R: Send,
+    SD: SendDesc<DatagramInboundContext<US::SocketAddr>, R>,
+    US: AsyncDatagramSocket,
+    TP: TransParams;
+
+impl<R, SD, US, TP> Future for WithFailureDetails<R, SD, US, TP>
+where
+    R: Send,
+    SD: SendDesc<DatagramInboundContext<US::SocketAddr>, R>,
+    US: AsyncDatagramSocket,
+    TP: TransParams,
+{
+    type Output = Result<R, TransactionFailure>;
+
+    fn poll(
+        self: Pin<&mut Self>,
+        cx: &mut futures::task::Context<'_>,
+    ) -> futures::task::Poll<Self::Output> {
+        let future = &mut self.get_mut().0;
+
+        future.poll(cx).map_err(|error| {
+            future
+                .inner
+                .lock()
+                .expect("UdpSendFuture inner mutex poisoned")
+                .failure(error)
+        })
+    }
+}
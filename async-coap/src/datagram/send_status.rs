@@ -0,0 +1,234 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+/// The state of an outbound request, as reported by [`SendStatus::state`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum SendState {
+    /// The request hasn't been transmitted yet. This is also the state right before the
+    /// next request of a multi-request exchange (like a block transfer) is transmitted.
+    NotSent,
+
+    /// The request has been transmitted and will be retransmitted until it is acknowledged
+    /// or answered.
+    WaitingForAck,
+
+    /// The request has been transmitted (and acknowledged, if it was confirmable), and we
+    /// are waiting for a response without retransmitting.
+    WaitingForResponse,
+
+    /// The exchange is finished, and the send future is about to resolve.
+    Finished,
+}
+
+impl std::fmt::Display for SendState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SendState::NotSent => f.write_str("not sent"),
+            SendState::WaitingForAck => f.write_str("waiting for ack"),
+            SendState::WaitingForResponse => f.write_str("waiting for response"),
+            SendState::Finished => f.write_str("finished"),
+        }
+    }
+}
+
+/// A snapshot of the status of an outbound request, obtained by calling
+/// [`SendStatusHandle::status`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SendStatus<SA> {
+    /// The socket address the request is sent to.
+    pub dest: SA,
+
+    /// What the send future is currently doing.
+    pub state: SendState,
+
+    /// The message ID of the most recently transmitted request.
+    pub msg_id: MsgId,
+
+    /// The token of the most recently transmitted request.
+    pub msg_token: MsgToken,
+
+    /// The number of times the most recently transmitted request has been retransmitted.
+    pub retransmit_count: u32,
+
+    /// When the send future will next time out, either to retransmit the request or to
+    /// give up waiting for a response.
+    pub next_timeout: Option<Instant>,
+
+    /// The `Block1` option of the most recently transmitted request, if any.
+    pub block1: Option<BlockInfo>,
+
+    /// The `Block2` option of the most recently transmitted request, if any.
+    pub block2: Option<BlockInfo>,
+}
+
+impl<SA> SendStatus<SA> {
+    pub(super) fn new(dest: SA) -> SendStatus<SA> {
+        SendStatus {
+            dest,
+            state: SendState::NotSent,
+            msg_id: 0,
+            msg_token: MsgToken::EMPTY,
+            retransmit_count: 0,
+            next_timeout: None,
+            block1: None,
+            block2: None,
+        }
+    }
+}
+
+impl<SA: SocketAddrExt> std::fmt::Display for SendStatus<SA> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} to {} (msg_id: {}, token: {}, retransmits: {}",
+            self.state, self.dest, self.msg_id, self.msg_token, self.retransmit_count
+        )?;
+
+        if let Some(next_timeout) = self.next_timeout {
+            let remaining = next_timeout.saturating_duration_since(Instant::now());
+            write!(f, ", timeout in {:?}", remaining)?;
+        }
+
+        if let Some(block1) = self.block1 {
+            write!(f, ", block1: {}", block1)?;
+        }
+
+        if let Some(block2) = self.block2 {
+            write!(f, ", block2: {}", block2)?;
+        }
+
+        f.write_str(")")
+    }
+}
+
+/// A handle for inspecting an outbound request while it is in flight, obtained from
+/// [`DatagramLocalEndpoint::send_with_status`].
+///
+/// This is intended for diagnosing transactions that appear to be stuck. The handle
+/// doesn't keep the send future alive: once it has been dropped,
+/// [`status`](SendStatusHandle::status) returns `None`.
+#[derive(Debug, Clone)]
+pub struct SendStatusHandle<SA>(Weak<Mutex<SendStatus<SA>>>);
+
+impl<SA: Clone> SendStatusHandle<SA> {
+    pub(super) fn new(status: &Arc<Mutex<SendStatus<SA>>>) -> SendStatusHandle<SA> {
+        SendStatusHandle(Arc::downgrade(status))
+    }
+
+    /// Returns a handle which isn't attached to any send future, for requests which
+    /// failed before they could be sent.
+    pub(super) fn detached() -> SendStatusHandle<SA> {
+        SendStatusHandle(Weak::new())
+    }
+
+    /// Returns a snapshot of the status of the request, or `None` if the send future has
+    /// been dropped.
+    pub fn status(&self) -> Option<SendStatus<SA>> {
+        self.0
+            .upgrade()
+            .map(|status| status.lock().expect("Lock failed").clone())
+    }
+}
+
+/// What the remote endpoint did (or didn't do) that caused a transaction to fail, as
+/// reported by [`TransactionFailure::cause`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum FailureCause {
+    /// The request was never acknowledged or answered, even after all retransmissions:
+    /// the host is silent.
+    NoAck,
+
+    /// The request was acknowledged, but no response followed before the timeout.
+    NoResponse,
+
+    /// The remote endpoint rejected the message with a reset (RST).
+    Reset,
+
+    /// The destination was reported as unreachable, usually by an ICMP message.
+    Unreachable,
+}
+
+impl std::fmt::Display for FailureCause {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FailureCause::NoAck => f.write_str("no ack"),
+            FailureCause::NoResponse => f.write_str("no response"),
+            FailureCause::Reset => f.write_str("reset"),
+            FailureCause::Unreachable => f.write_str("unreachable"),
+        }
+    }
+}
+
+/// The error returned by [`DatagramLocalEndpoint::send_with_failure_details`], which
+/// describes how the transaction went before it failed.
+///
+/// This allows distinguishing a host which is silent from one which actively rejected
+/// the request, which the [`Error`] alone doesn't always do.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct TransactionFailure {
+    /// The error the transaction failed with.
+    pub error: Error,
+
+    /// The number of times the last request of the transaction was transmitted,
+    /// including retransmissions. This is zero if the request was never sent.
+    pub attempts: u32,
+
+    /// The time from when the transaction was first transmitted until it failed.
+    pub elapsed: Duration,
+
+    /// The last thing the remote endpoint did (or didn't do) that explains the failure,
+    /// or `None` if the failure wasn't caused by the transport (for example, if the
+    /// request couldn't be sent or the response indicated an error).
+    pub cause: Option<FailureCause>,
+}
+
+impl From<Error> for TransactionFailure {
+    /// Creates a `TransactionFailure` for a transaction which failed before anything
+    /// was transmitted.
+    fn from(error: Error) -> Self {
+        TransactionFailure {
+            error,
+            attempts: 0,
+            elapsed: Duration::from_secs(0),
+            cause: None,
+        }
+    }
+}
+
+impl From<TransactionFailure> for Error {
+    fn from(failure: TransactionFailure) -> Self {
+        failure.error
+    }
+}
+
+impl std::fmt::Display for TransactionFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} after {} attempts in {:?}",
+            self.error, self.attempts, self.elapsed
+        )?;
+
+        if let Some(cause) = self.cause {
+            write!(f, " ({})", cause)?;
+        }
+
+        Ok(())
+    }
+}
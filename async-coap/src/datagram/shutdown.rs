@@ -0,0 +1,61 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use futures::channel::oneshot;
+use futures::future::Shared;
+use futures::prelude::*;
+use std::sync::Mutex;
+
+/// Future which completes once the local endpoint it was obtained from is shut down or
+/// dropped.
+pub(super) type ShutdownFuture = Shared<oneshot::Receiver<()>>;
+
+/// Signal which is raised when a local endpoint is shut down or dropped, waking all of the
+/// futures that are waiting on it.
+#[derive(Debug)]
+pub(super) struct ShutdownSignal {
+    sender: Mutex<Option<oneshot::Sender<()>>>,
+    receiver: ShutdownFuture,
+}
+
+impl Default for ShutdownSignal {
+    fn default() -> Self {
+        let (sender, receiver) = oneshot::channel();
+
+        ShutdownSignal {
+            sender: Mutex::new(Some(sender)),
+            receiver: receiver.shared(),
+        }
+    }
+}
+
+impl ShutdownSignal {
+    /// Raises the signal. Since dropping the signal raises it as well, this only needs to
+    /// be called to shut down early.
+    pub(super) fn raise(&self) {
+        // Dropping the sender completes all of the receivers.
+        self.sender.lock().expect("Lock failed").take();
+    }
+
+    /// Returns true if the signal was raised.
+    pub(super) fn is_raised(&self) -> bool {
+        self.sender.lock().expect("Lock failed").is_none()
+    }
+
+    /// Returns a future which completes once the signal is raised.
+    pub(super) fn wait(&self) -> ShutdownFuture {
+        self.receiver.clone()
+    }
+}
@@ -0,0 +1,408 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use futures::prelude::*;
+use futures::task::{Context, Poll};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+/// The maximum number of digits in an E.164 telephone number.
+const MAX_DIGITS: usize = 15;
+
+/// The largest datagram that fits into a single SMS using 8-bit encoding.
+pub const SMS_MAX_MESSAGE_LEN: usize = 140;
+
+/// "SocketAddr" for CoAP-over-SMS: an [E.164](https://en.wikipedia.org/wiki/E.164)
+/// international telephone number.
+///
+/// Telephone numbers are formatted and parsed with a leading `+`, followed by the country
+/// code and the subscriber number, without any separators. This is also how they appear
+/// in the host component of a `coap+sms:` URI:
+///
+/// ```
+/// # use async_coap::prelude::*;
+/// # use async_coap::datagram::SmsSocketAddr;
+/// # use async_coap::SocketAddrExt;
+/// let addr: SmsSocketAddr = "+15551234567".parse().unwrap();
+///
+/// assert_eq!(addr.number(), 15551234567);
+/// assert_eq!(addr.to_string(), "+15551234567");
+/// assert_eq!(addr.as_uri_buf("coap+sms"), uri!("coap+sms://+15551234567"));
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SmsSocketAddr(u64);
+
+impl SmsSocketAddr {
+    /// Creates a new [`SmsSocketAddr`] from the digits of an international telephone
+    /// number, or `None` if `number` is zero or has more than 15 digits.
+    pub fn new(number: u64) -> Option<SmsSocketAddr> {
+        if number == 0 || number >= 10u64.pow(MAX_DIGITS as u32) {
+            None
+        } else {
+            Some(SmsSocketAddr(number))
+        }
+    }
+
+    /// Returns the digits of the telephone number.
+    pub fn number(&self) -> u64 {
+        self.0
+    }
+}
+
+impl Display for SmsSocketAddr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "+{}", self.0)
+    }
+}
+
+impl FromStr for SmsSocketAddr {
+    type Err = Error;
+
+    /// Parses a telephone number in the form `+15551234567`. Fails with
+    /// [`Error::ParseFailure`] if the string isn't a `+` followed by decimal digits (the
+    /// first of which can't be zero), or with [`Error::InvalidArgument`] if the number
+    /// has more than 15 digits.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits = s.strip_prefix('+').ok_or(Error::ParseFailure)?;
+
+        if digits.is_empty()
+            || digits.starts_with('0')
+            || !digits.bytes().all(|b| b.is_ascii_digit())
+        {
+            return Err(Error::ParseFailure);
+        }
+
+        if digits.len() > MAX_DIGITS {
+            return Err(Error::InvalidArgument);
+        }
+
+        digits
+            .parse()
+            .ok()
+            .and_then(SmsSocketAddr::new)
+            .ok_or(Error::ParseFailure)
+    }
+}
+
+impl SocketAddrExt for SmsSocketAddr {
+    fn is_multicast(&self) -> bool {
+        false
+    }
+
+    fn port(&self) -> u16 {
+        0
+    }
+
+    fn addr_to_string(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl ToSocketAddrs for SmsSocketAddr {
+    type Iter = std::option::IntoIter<Self::SocketAddr>;
+    type SocketAddr = Self;
+    type Error = super::Error;
+
+    fn to_socket_addrs(&self) -> Result<Self::Iter, Self::Error> {
+        Ok(Some(*self).into_iter())
+    }
+}
+
+// A received SMS: (message_bytes, sender).
+type Sms = (Vec<u8>, SmsSocketAddr);
+
+/// A simulated SMS network, which delivers messages between the [`SmsSocket`]s attached
+/// to it.
+///
+/// This serves as an example of a transport whose socket addresses aren't IP addresses,
+/// and is useful for testing CoAP-over-SMS applications without a modem:
+///
+/// ```
+/// # use async_coap::prelude::*;
+/// # use async_coap::datagram::{DatagramLocalEndpoint, DatagramRespondableInboundContext};
+/// # use async_coap::datagram::{SmsNetwork, SmsSocketAddr};
+/// # use async_coap::message::MsgCode;
+/// # use async_coap::{RespondableInboundContext, URI_SCHEME_COAP_SMS};
+/// # use futures::executor::block_on;
+/// # use futures::future::{select, Either};
+/// let network = SmsNetwork::new();
+///
+/// let server_number = "+15551234567".parse().unwrap();
+/// let server_socket = network.attach(server_number).unwrap();
+/// let server = DatagramLocalEndpoint::with_scheme_and_port(
+///     server_socket,
+///     URI_SCHEME_COAP_SMS,
+///     0,
+/// );
+///
+/// let client_socket = network.attach("+15557654321".parse().unwrap()).unwrap();
+/// let client = DatagramLocalEndpoint::with_scheme_and_port(
+///     client_socket,
+///     URI_SCHEME_COAP_SMS,
+///     0,
+/// );
+///
+/// let remote_endpoint = client
+///     .remote_endpoint_from_uri(uri!("coap+sms://+15551234567/"))
+///     .unwrap();
+/// assert_eq!(remote_endpoint.uri(), uri!("coap+sms://+15551234567/"));
+///
+/// let future = remote_endpoint.send(CoapRequest::get().emit_msg_code());
+///
+/// let handler = |context: &DatagramRespondableInboundContext<SmsSocketAddr>| {
+///     context.respond(|msg_out| {
+///         msg_out.set_msg_code(MsgCode::SuccessContent);
+///         Ok(())
+///     })
+/// };
+///
+/// let receive_loops = select(
+///     server.receive_loop(handler),
+///     client.receive_loop(null_receiver!()),
+/// );
+///
+/// match block_on(select(future, receive_loops)) {
+///     Either::Left((result, _)) => assert_eq!(result, Ok(MsgCode::SuccessContent)),
+///     Either::Right(_) => panic!("Receive loop finished unexpectedly"),
+/// };
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SmsNetwork {
+    subscribers: Arc<Mutex<HashMap<SmsSocketAddr, UnboundedSender<Sms>>>>,
+}
+
+impl SmsNetwork {
+    /// Creates a new, empty [`SmsNetwork`].
+    pub fn new() -> SmsNetwork {
+        Default::default()
+    }
+
+    /// Attaches a new [`SmsSocket`] with the telephone number `number` to this network.
+    ///
+    /// Fails with [`Error::InvalidArgument`] if a socket with that number is already
+    /// attached. The number becomes available again once that socket is dropped.
+    pub fn attach(&self, number: SmsSocketAddr) -> Result<SmsSocket, Error> {
+        let mut subscribers = self.subscribers.lock().expect("Lock failed");
+
+        if subscribers.contains_key(&number) {
+            return Err(Error::InvalidArgument);
+        }
+
+        let (sender, receiver) = unbounded();
+        subscribers.insert(number, sender);
+
+        Ok(SmsSocket {
+            number,
+            network: self.clone(),
+            receiver: Mutex::new(receiver),
+        })
+    }
+}
+
+/// An instance of [`AsyncDatagramSocket`] for a telephone number attached to an
+/// [`SmsNetwork`].
+///
+/// Like real SMS, messages sent to numbers that aren't attached to the network are
+/// silently lost. Messages larger than [`SMS_MAX_MESSAGE_LEN`] are rejected with
+/// [`Error::MessageTooLarge`], and multicast isn't supported.
+#[derive(Debug)]
+pub struct SmsSocket {
+    number: SmsSocketAddr,
+    network: SmsNetwork,
+    receiver: Mutex<UnboundedReceiver<Sms>>,
+}
+
+impl Drop for SmsSocket {
+    fn drop(&mut self) {
+        if let Ok(mut subscribers) = self.network.subscribers.lock() {
+            subscribers.remove(&self.number);
+        }
+    }
+}
+
+impl Unpin for SmsSocket {}
+
+impl AsyncDatagramSocket for SmsSocket {}
+
+impl DatagramSocketTypes for SmsSocket {
+    type SocketAddr = SmsSocketAddr;
+    type Error = super::Error;
+
+    fn local_addr(&self) -> Result<Self::SocketAddr, Self::Error> {
+        Ok(self.number)
+    }
+
+    /// Parses `host` as a telephone number. The port is ignored.
+    fn lookup_host(
+        host: &str,
+        _port: u16,
+    ) -> Result<std::vec::IntoIter<Self::SocketAddr>, Self::Error>
+    where
+        Self: Sized,
+    {
+        Ok(vec![host.parse()?].into_iter())
+    }
+}
+
+impl AsyncSendTo for SmsSocket {
+    fn poll_send_to<B>(
+        self: Pin<&Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+        addr: B,
+    ) -> Poll<Result<usize, Self::Error>>
+    where
+        B: super::ToSocketAddrs<SocketAddr = Self::SocketAddr, Error = Self::Error>,
+    {
+        let addr = match addr.to_socket_addrs()?.next() {
+            Some(addr) => addr,
+            None => return Poll::Ready(Err(Error::HostNotFound)),
+        };
+
+        if buf.len() > SMS_MAX_MESSAGE_LEN {
+            return Poll::Ready(Err(Error::MessageTooLarge));
+        }
+
+        let subscribers = self.network.subscribers.lock().expect("Lock failed");
+
+        if let Some(sender) = subscribers.get(&addr) {
+            // If the recipient is going away, the message is simply lost.
+            let _ = sender.unbounded_send((buf.to_vec(), self.number));
+        }
+
+        Poll::Ready(Ok(buf.len()))
+    }
+}
+
+impl AsyncRecvFrom for SmsSocket {
+    fn poll_recv_from(
+        self: Pin<&Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<(usize, Self::SocketAddr, Option<Self::SocketAddr>), Self::Error>> {
+        let mut receiver = self.receiver.lock().expect("Lock failed");
+
+        match receiver.poll_next_unpin(cx) {
+            Poll::Ready(Some((packet, sender))) => {
+                let len = packet.len();
+                if buf.len() >= len {
+                    buf[..len].copy_from_slice(&packet);
+                    Poll::Ready(Ok((len, sender, Some(self.number))))
+                } else {
+                    Poll::Ready(Err(Error::IOError))
+                }
+            }
+            Poll::Ready(None) => Poll::Ready(Err(Error::IOError)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl MulticastSocket for SmsSocket {
+    type IpAddr = String;
+
+    fn join_multicast<A>(&self, _addr: A) -> Result<(), Self::Error>
+    where
+        A: std::convert::Into<Self::IpAddr>,
+    {
+        Err(Error::InvalidArgument)
+    }
+
+    fn leave_multicast<A>(&self, _addr: A) -> Result<(), Self::Error>
+    where
+        A: std::convert::Into<Self::IpAddr>,
+    {
+        Err(Error::InvalidArgument)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(Ok(SmsSocketAddr(15551234567)), "+15551234567".parse());
+        assert_eq!(Ok(SmsSocketAddr(1)), "+1".parse());
+        assert_eq!(
+            Ok(SmsSocketAddr(999999999999999)),
+            "+999999999999999".parse()
+        );
+
+        assert_eq!(
+            Err(Error::ParseFailure),
+            "15551234567".parse::<SmsSocketAddr>()
+        );
+        assert_eq!(Err(Error::ParseFailure), "+".parse::<SmsSocketAddr>());
+        assert_eq!(Err(Error::ParseFailure), "+0555".parse::<SmsSocketAddr>());
+        assert_eq!(
+            Err(Error::ParseFailure),
+            "+1-555-123-4567".parse::<SmsSocketAddr>()
+        );
+        assert_eq!(
+            Err(Error::InvalidArgument),
+            "+1234567890123456".parse::<SmsSocketAddr>()
+        );
+    }
+
+    #[test]
+    fn new() {
+        assert_eq!(None, SmsSocketAddr::new(0));
+        assert_eq!(None, SmsSocketAddr::new(1_000_000_000_000_000));
+        assert_eq!(
+            Some("+15551234567".parse().unwrap()),
+            SmsSocketAddr::new(15551234567)
+        );
+    }
+
+    #[test]
+    fn attach() {
+        let network = SmsNetwork::new();
+        let number = SmsSocketAddr(15551234567);
+
+        let socket = network.attach(number).unwrap();
+        assert_eq!(Ok(number), socket.local_addr());
+        assert_eq!(
+            Err(Error::InvalidArgument),
+            network.attach(number).map(drop)
+        );
+
+        drop(socket);
+        assert!(network.attach(number).is_ok());
+    }
+
+    #[test]
+    fn message_too_large() {
+        use futures::executor::block_on;
+
+        let network = SmsNetwork::new();
+        let socket = network.attach(SmsSocketAddr(15551234567)).unwrap();
+        let dest = SmsSocketAddr(15557654321);
+
+        assert_eq!(
+            Ok(SMS_MAX_MESSAGE_LEN),
+            block_on(socket.send_to(&[0; SMS_MAX_MESSAGE_LEN], dest))
+        );
+        assert_eq!(
+            Err(Error::MessageTooLarge),
+            block_on(socket.send_to(&[0; SMS_MAX_MESSAGE_LEN + 1], dest))
+        );
+    }
+}
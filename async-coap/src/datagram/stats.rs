@@ -0,0 +1,178 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+#[cfg(feature = "server")]
+use super::*;
+#[cfg(feature = "server")]
+use crate::option::RequestOptions;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// The path of the diagnostic resource served by [`EndpointStats::handle_request`].
+pub const WELL_KNOWN_STATS: &str = ".well-known/stats";
+
+/// The counters that are updated as a [`DatagramLocalEndpoint`] sends and receives messages.
+#[derive(Debug, Default)]
+pub(super) struct StatCounters {
+    messages_in: AtomicU64,
+    messages_out: AtomicU64,
+    retransmits: AtomicU64,
+    unmatched_responses: AtomicU64,
+    lenient_matches: AtomicU64,
+    parse_errors: AtomicU64,
+    unknown_versions: AtomicU64,
+}
+
+impl StatCounters {
+    pub(super) fn message_in(&self) {
+        self.messages_in.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn message_out(&self) {
+        self.messages_out.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn retransmit(&self) {
+        self.retransmits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn unmatched_response(&self) {
+        self.unmatched_responses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn lenient_match(&self) {
+        self.lenient_matches.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn parse_error(&self) {
+        self.parse_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn unknown_version(&self) {
+        self.unknown_versions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn snapshot(&self, active_exchanges: usize) -> EndpointStats {
+        EndpointStats {
+            messages_in: self.messages_in.load(Ordering::Relaxed),
+            messages_out: self.messages_out.load(Ordering::Relaxed),
+            retransmits: self.retransmits.load(Ordering::Relaxed),
+            unmatched_responses: self.unmatched_responses.load(Ordering::Relaxed),
+            lenient_matches: self.lenient_matches.load(Ordering::Relaxed),
+            parse_errors: self.parse_errors.load(Ordering::Relaxed),
+            unknown_versions: self.unknown_versions.load(Ordering::Relaxed),
+            active_exchanges,
+        }
+    }
+}
+
+/// A snapshot of the statistics of a [`DatagramLocalEndpoint`], obtained by calling
+/// [`DatagramLocalEndpoint::stats`].
+///
+/// All of the counters start at zero when the local endpoint is created.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub struct EndpointStats {
+    /// The number of messages received, including ones that couldn't be parsed.
+    pub messages_in: u64,
+
+    /// The number of messages sent, including retransmissions, acknowledgements, and resets.
+    pub messages_out: u64,
+
+    /// The number of times a confirmable message was retransmitted.
+    pub retransmits: u64,
+
+    /// The number of responses, acknowledgements, and resets that didn't match any
+    /// outstanding request. These are usually duplicates or late arrivals.
+    pub unmatched_responses: u64,
+
+    /// The number of acknowledgements that had the wrong message id, but were matched to a
+    /// request by their token because lenient matching is enabled, as it is by default. See
+    /// [`DatagramLocalEndpoint::set_lenient_msg_id_matching`].
+    pub lenient_matches: u64,
+
+    /// The number of received messages that couldn't be parsed.
+    pub parse_errors: u64,
+
+    /// The number of received messages with an unknown version which were tolerated
+    /// rather than counted as parse errors. See [`VersionPolicy::Tolerant`].
+    pub unknown_versions: u64,
+
+    /// The number of requests (including observations) that are waiting for a response.
+    pub active_exchanges: usize,
+}
+
+impl EndpointStats {
+    /// Responds to a `GET` request for [`WELL_KNOWN_STATS`] with a JSON representation of
+    /// these statistics, returning true if the request was handled.
+    ///
+    /// Since these statistics may be useful to an attacker, the diagnostic resource is
+    /// only served if the request handler explicitly calls this method:
+    ///
+    /// ```
+    /// # use async_coap::prelude::*;
+    /// # use async_coap::datagram::*;
+    /// # use async_coap::{Error, RespondableInboundContext};
+    /// # let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+    /// let handler = |context: &DatagramRespondableInboundContext<_>| -> Result<(), Error> {
+    ///     if local_endpoint.stats().handle_request(context)? {
+    ///         return Ok(());
+    ///     }
+    ///
+    ///     // Handle other requests...
+    ///     Ok(())
+    /// };
+    /// # let _ = local_endpoint.receive_loop(handler);
+    /// ```
+    #[cfg(feature = "server")]
+    pub fn handle_request<T: RespondableInboundContext>(&self, context: &T) -> Result<bool, Error> {
+        let msg = context.message();
+
+        if msg.msg_code() != MsgCode::MethodGet {
+            return Ok(false);
+        }
+
+        match RequestOptions::parse(msg.options()) {
+            Ok(ref options) if options.rel_ref().as_str() == WELL_KNOWN_STATS => (),
+            _ => return Ok(false),
+        }
+
+        context.respond(|msg_out| {
+            msg_out.set_msg_code(MsgCode::SuccessContent);
+            msg_out.insert_option(option::CONTENT_FORMAT, ContentFormat::APPLICATION_JSON)?;
+            msg_out.insert_option(option::MAX_AGE, 0)?;
+            msg_out.append_payload_string(&self.to_json())
+        })?;
+
+        Ok(true)
+    }
+
+    /// Returns a JSON object containing these statistics.
+    pub fn to_json(&self) -> String {
+        format!(
+            concat!(
+                "{{\"messages_in\":{},\"messages_out\":{},\"retransmits\":{},",
+                "\"unmatched_responses\":{},\"lenient_matches\":{},\"parse_errors\":{},",
+                "\"unknown_versions\":{},\"active_exchanges\":{}}}"
+            ),
+            self.messages_in,
+            self.messages_out,
+            self.retransmits,
+            self.unmatched_responses,
+            self.lenient_matches,
+            self.parse_errors,
+            self.unknown_versions,
+            self.active_exchanges
+        )
+    }
+}
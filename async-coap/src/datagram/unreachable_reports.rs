@@ -0,0 +1,257 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use std::collections::VecDeque;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::os::unix::io::AsRawFd;
+use std::sync::Mutex;
+
+/// Collects the remote addresses that the operating system has reported as unreachable
+/// for a UDP socket, for implementing [`AsyncDatagramSocket::take_unreachable`].
+///
+/// Once enabled with [`UnreachableReports::enable`], ICMP "port unreachable", "host
+/// unreachable" and "network unreachable" messages are queued on the socket's error
+/// queue, and receiving from the socket fails (usually with `ECONNREFUSED`). At that point,
+/// [`UnreachableReports::read_error_queue`] reads the destinations of the failed datagrams
+/// from the error queue, and [`UnreachableReports::take`] returns them one at a time.
+///
+/// This is used by [`AllowStdUdpSocket`], and is only available on Linux and Android.
+///
+/// [`AsyncDatagramSocket::take_unreachable`]: super::AsyncDatagramSocket::take_unreachable
+/// [`AllowStdUdpSocket`]: super::AllowStdUdpSocket
+#[derive(Debug, Default)]
+pub struct UnreachableReports {
+    reports: Mutex<VecDeque<SocketAddr>>,
+}
+
+impl UnreachableReports {
+    /// Creates an empty `UnreachableReports`.
+    pub fn new() -> UnreachableReports {
+        UnreachableReports::default()
+    }
+
+    /// Enables the reporting of ICMP errors for `socket`, which is bound to `local_addr`,
+    /// by setting `IP_RECVERR` (and `IPV6_RECVERR` for IPv6 sockets).
+    pub fn enable<S: AsRawFd>(socket: &S, local_addr: SocketAddr) -> std::io::Result<()> {
+        match local_addr {
+            SocketAddr::V4(_) => set_option(socket, libc::IPPROTO_IP, libc::IP_RECVERR),
+            SocketAddr::V6(_) => {
+                // Covers datagrams sent to IPv4-mapped addresses.
+                let _ = set_option(socket, libc::IPPROTO_IP, libc::IP_RECVERR);
+                set_option(socket, libc::IPPROTO_IPV6, libc::IPV6_RECVERR)
+            }
+        }
+    }
+
+    /// Reads all of the errors that are queued on `socket`, recording the destinations
+    /// of the datagrams which were reported as unreachable. Returns true if there were any.
+    pub fn read_error_queue<S: AsRawFd>(&self, socket: &S) -> bool {
+        let mut found = false;
+
+        while let Some(report) = read_error(socket) {
+            if let Some(addr) = report {
+                self.reports.lock().expect("Lock failed").push_back(addr);
+                found = true;
+            }
+        }
+
+        found
+    }
+
+    /// Returns the next address that has been reported as unreachable, if any.
+    pub fn take(&self) -> Option<SocketAddr> {
+        self.reports.lock().expect("Lock failed").pop_front()
+    }
+}
+
+fn set_option<S: AsRawFd>(
+    socket: &S,
+    level: libc::c_int,
+    name: libc::c_int,
+) -> std::io::Result<()> {
+    let value: libc::c_int = 1;
+
+    // SAFETY: `value` outlives the call, and its size is passed along with it.
+    let ret = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            level,
+            name,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+/// Reads one error from the error queue of `socket`. Returns `None` if the queue is empty,
+/// and `Some(None)` for errors other than unreachable destinations.
+fn read_error<S: AsRawFd>(socket: &S) -> Option<Option<SocketAddr>> {
+    // SAFETY: All of these are plain C structures, for which all zeroes is a valid value.
+    let mut name: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+
+    // `u64` keeps the buffer aligned for `cmsghdr`.
+    let mut control = [0u64; 64];
+    let mut data = [0u8; 1];
+    let mut iov = libc::iovec {
+        iov_base: data.as_mut_ptr() as *mut libc::c_void,
+        iov_len: data.len(),
+    };
+
+    msg.msg_name = &mut name as *mut libc::sockaddr_storage as *mut libc::c_void;
+    msg.msg_namelen = std::mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+    msg.msg_controllen = std::mem::size_of_val(&control) as _;
+
+    // SAFETY: Every buffer referenced by `msg` outlives the call, and their sizes are
+    // passed along with them.
+    let ret = unsafe {
+        libc::recvmsg(
+            socket.as_raw_fd(),
+            &mut msg,
+            libc::MSG_ERRQUEUE | libc::MSG_DONTWAIT,
+        )
+    };
+
+    if ret < 0 {
+        return None;
+    }
+
+    // SAFETY: `msg` was filled in by `recvmsg`, so the control messages are valid.
+    let mut cmsg = unsafe { libc::CMSG_FIRSTHDR(&msg) };
+
+    while !cmsg.is_null() {
+        let (level, kind) = unsafe { ((*cmsg).cmsg_level, (*cmsg).cmsg_type) };
+
+        if (level == libc::IPPROTO_IP && kind == libc::IP_RECVERR)
+            || (level == libc::IPPROTO_IPV6 && kind == libc::IPV6_RECVERR)
+        {
+            let err = unsafe {
+                std::ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const libc::sock_extended_err)
+            };
+
+            let is_icmp = err.ee_origin == libc::SO_EE_ORIGIN_ICMP
+                || err.ee_origin == libc::SO_EE_ORIGIN_ICMP6;
+            let is_unreachable = matches!(
+                err.ee_errno as libc::c_int,
+                libc::ECONNREFUSED | libc::EHOSTUNREACH | libc::ENETUNREACH
+            );
+
+            if is_icmp && is_unreachable {
+                return Some(socket_addr(&name));
+            }
+        }
+
+        cmsg = unsafe { libc::CMSG_NXTHDR(&msg, cmsg) };
+    }
+
+    Some(None)
+}
+
+/// Converts the destination address of a failed datagram.
+fn socket_addr(name: &libc::sockaddr_storage) -> Option<SocketAddr> {
+    match name.ss_family as libc::c_int {
+        libc::AF_INET => {
+            // SAFETY: The address family says that this is a `sockaddr_in`.
+            let addr = unsafe { &*(name as *const _ as *const libc::sockaddr_in) };
+            Some(SocketAddr::V4(SocketAddrV4::new(
+                Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr)),
+                u16::from_be(addr.sin_port),
+            )))
+        }
+        libc::AF_INET6 => {
+            // SAFETY: The address family says that this is a `sockaddr_in6`.
+            let addr = unsafe { &*(name as *const _ as *const libc::sockaddr_in6) };
+            Some(SocketAddr::V6(SocketAddrV6::new(
+                Ipv6Addr::from(addr.sin6_addr.s6_addr),
+                u16::from_be(addr.sin6_port),
+                addr.sin6_flowinfo,
+                addr.sin6_scope_id,
+            )))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datagram::{AllowStdUdpSocket, DatagramLocalEndpoint};
+    use crate::prelude::*;
+    use crate::Error;
+    use futures::executor::block_on;
+    use futures::future::{select, Either};
+    use std::net::UdpSocket;
+    use std::time::{Duration, Instant};
+
+    /// Returns the address of a port which nothing is listening on.
+    fn closed_port() -> SocketAddr {
+        UdpSocket::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+    }
+
+    #[test]
+    fn port_unreachable() {
+        let dest = closed_port();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        UnreachableReports::enable(&socket, socket.local_addr().unwrap()).unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        let reports = UnreachableReports::new();
+        assert!(!reports.read_error_queue(&socket));
+
+        socket.send_to(b"hello", dest).unwrap();
+
+        let err = socket.recv_from(&mut [0u8; 16]).unwrap_err();
+        assert_eq!(Some(libc::ECONNREFUSED), err.raw_os_error());
+
+        assert!(reports.read_error_queue(&socket));
+        assert_eq!(Some(dest), reports.take());
+        assert_eq!(None, reports.take());
+    }
+
+    #[test]
+    fn endpoint_fails_fast() {
+        let dest = closed_port();
+        let socket = AllowStdUdpSocket::bind("127.0.0.1:0").unwrap();
+        let local_endpoint = DatagramLocalEndpoint::new(socket);
+
+        let start = Instant::now();
+        let future = local_endpoint.send(dest, CoapRequest::get().emit_msg_code());
+        let future_receive = local_endpoint.receive_loop(null_receiver!());
+
+        let ret = match block_on(select(future, future_receive)) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((ret, _)) => ret,
+        };
+
+        assert_eq!(Err(Error::HostUnreachable), ret);
+
+        // Much sooner than the first retransmission.
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -30,6 +30,15 @@ pub enum Error {
     /// Operation timed out waiting for a response.
     ResponseTimeout,
 
+    /// No response was received to the request registering an observation (see
+    /// [`SendObserve::initial_timeout`](crate::send_desc::SendObserve::initial_timeout)).
+    ObserveRegistrationTimeout,
+
+    /// An observation had been registered, but no notification was received for longer
+    /// than its notification timeout (see
+    /// [`SendObserve::notification_timeout`](crate::send_desc::SendObserve::notification_timeout)).
+    ObservationLapsed,
+
     /// The response was well-formed, but not appropriate for the given request.
     BadResponse,
 
@@ -52,6 +61,10 @@ pub enum Error {
     /// Unable to look up the given host for an unspecified reason.
     HostLookupFailure,
 
+    /// The destination was reported as unreachable, for example by an ICMP
+    /// "port unreachable" or "host unreachable" message.
+    HostUnreachable,
+
     /// The response indicated that the given resource was not found.
     ResourceNotFound,
 
@@ -70,6 +83,20 @@ pub enum Error {
     /// The transaction was reset.
     Reset,
 
+    /// A block-wise transfer couldn't be completed because a block was missing or
+    /// out of sequence.
+    IncompleteBlockTransfer,
+
+    /// The payload of the response failed validation, for example because its length
+    /// didn't match the `Size2` option (see
+    /// [`SendDescExt::validate_payload`](crate::send_desc::SendDescExt::validate_payload)).
+    InvalidPayload,
+
+    /// The message is larger than the known path MTU to its destination, and would
+    /// likely be dropped by the network. Large payloads should be sent using block-wise
+    /// transfers (see [`SendDescUnicast::block1`](crate::send_desc::SendDescUnicast::block1)).
+    MessageTooLarge,
+
     /// More than one instance of an option marked as non-repeatable was encountered.
     OptionNotRepeatable,
 
@@ -87,6 +114,13 @@ impl std::convert::From<std::io::Error> for Error {
     }
 }
 
+#[cfg(feature = "std")]
+impl std::convert::From<Error> for std::io::Error {
+    fn from(err: Error) -> Self {
+        std::io::Error::other(err.to_string())
+    }
+}
+
 impl std::convert::From<Error> for core::fmt::Error {
     fn from(_: Error) -> Self {
         core::fmt::Error
@@ -39,6 +39,17 @@ impl ETag {
         ETag::from(x)
     }
 
+    /// Creates an ETag from a 64-bit hash of `bytes`, which is usually the content of the
+    /// representation that the ETag identifies.
+    ///
+    /// The hash is fast but not cryptographic, so this is suitable for detecting when a
+    /// representation changes, but not for protecting against deliberate collisions. It
+    /// is the same on every platform and in every process, so ETags remain valid across
+    /// restarts of a server.
+    pub fn from_hash(bytes: &[u8]) -> ETag {
+        ETag::new(&async_coap_uri::StableHasher::hash_bytes(bytes).to_be_bytes())
+    }
+
     /// Returns the length of this ETag in bytes.
     pub fn len(&self) -> usize {
         self.len as usize
@@ -119,3 +130,22 @@ impl core::convert::From<&[u8]> for ETag {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_hash() {
+        let etag = ETag::from_hash(b"22.3 C");
+        assert_eq!(ETag::MAX_LEN, etag.len());
+        assert_eq!(etag, ETag::from_hash(b"22.3 C"));
+        assert_ne!(etag, ETag::from_hash(b"22.4 C"));
+
+        // The FNV-1a hash of the empty string.
+        assert_eq!(
+            ETag::new(&[0xcb, 0xf2, 0x9c, 0xe4, 0x84, 0x22, 0x23, 0x25]),
+            ETag::from_hash(b"")
+        );
+    }
+}
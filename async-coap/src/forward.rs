@@ -0,0 +1,312 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+use crate::message::{MessageRead, MessageWrite, OwnedImmutableMessage};
+use crate::option::{registered_option, OptionSet};
+use std::ops::{Bound, RangeBounds};
+
+/// Returns true if the option `number` only applies to a single hop, so it must not be
+/// copied when a request or response is forwarded to the next hop.
+///
+/// These are the options which identify the target of a request ([IETF-RFC7252 Section
+/// 5.10.1] and [Section 5.10.2]), which are replaced by the destination that the
+/// request is forwarded to, and `Observe`, since relaying notifications requires more
+/// than forwarding a single request.
+///
+/// [IETF-RFC7252 Section 5.10.1]: https://tools.ietf.org/html/rfc7252#section-5.10.1
+/// [Section 5.10.2]: https://tools.ietf.org/html/rfc7252#section-5.10.2
+pub fn is_hop_by_hop(number: OptionNumber) -> bool {
+    matches!(
+        number,
+        OptionNumber::URI_HOST
+            | OptionNumber::URI_PORT
+            | OptionNumber::URI_PATH
+            | OptionNumber::URI_QUERY
+            | OptionNumber::PROXY_URI
+            | OptionNumber::PROXY_SCHEME
+            | OptionNumber::OBSERVE
+    )
+}
+
+/// Returns true if an option with the given number can be forwarded: either it is
+/// understood, or it is safe to forward without being understood, as described in
+/// [IETF-RFC7252 Section 5.7.1].
+///
+/// [IETF-RFC7252 Section 5.7.1]: https://tools.ietf.org/html/rfc7252#section-5.7.1
+fn is_forwardable(number: OptionNumber) -> bool {
+    !number.is_un_safe() || number.static_name().is_some() || registered_option(number).is_some()
+}
+
+/// Returns the end-to-end options in `iter`, skipping hop-by-hop ones. Options which
+/// can't be forwarded result in [`Error::UnhandledCriticalOption`].
+fn end_to_end_options(
+    iter: OptionIterator<'_>,
+) -> impl Iterator<Item = Result<(OptionNumber, &[u8]), Error>> {
+    iter.filter(|result| !matches!(result, Ok((number, _)) if is_hop_by_hop(*number)))
+        .map(|result| match result {
+            Ok((number, _)) if !is_forwardable(number) => Err(Error::UnhandledCriticalOption),
+            result => result,
+        })
+}
+
+/// Send descriptor which forwards an inbound request to another [`RemoteEndpoint`], as a
+/// reverse proxy or resource republisher would.
+///
+/// The method, payload, and end-to-end options of the inbound request are copied. The
+/// hop-by-hop options (see [`is_hop_by_hop`]) are replaced by those for the remote
+/// endpoint and path the descriptor is sent to. The first response is emitted as an
+/// [`OwnedImmutableMessage`], which can be relayed back to the client using
+/// [`write_forwarded_response`].
+///
+/// Request handlers are currently synchronous, so a handler must wait for the forwarded
+/// request to complete (using a different local endpoint than the one it is called from):
+///
+/// ```
+/// # use async_coap::prelude::*;
+/// # use async_coap::{write_forwarded_response, ForwardRequest, RespondableInboundContext};
+/// # use async_coap::message::MessageRead;
+/// # use futures::executor::block_on;
+/// fn handle<C, RE>(context: &C, upstream: &RE) -> Result<(), async_coap::Error>
+/// where
+///     C: RespondableInboundContext,
+///     RE: RemoteEndpoint,
+/// {
+///     let request = match ForwardRequest::new(context.message()) {
+///         Ok(request) => request,
+///         Err(_) => return context.respond_error(MsgCode::ServerErrorBadGateway, ""),
+///     };
+///
+///     match block_on(upstream.send(request)) {
+///         Ok(response) => context.respond(|msg_out| write_forwarded_response(&response, msg_out)),
+///         Err(_) => context.respond_error(MsgCode::ServerErrorGatewayTimeout, ""),
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ForwardRequest {
+    method: MsgCode,
+    options: OptionSet,
+    payload: Vec<u8>,
+}
+
+impl ForwardRequest {
+    /// Creates a send descriptor which forwards `request`.
+    ///
+    /// Returns [`Error::InvalidArgument`] if `request` isn't a request, or
+    /// [`Error::UnhandledCriticalOption`] if it has an option that is neither understood
+    /// nor safe to forward. In the latter case, the request should be answered with
+    /// `5.02 Bad Gateway`.
+    pub fn new(request: &dyn MessageRead) -> Result<ForwardRequest, Error> {
+        let method = request.msg_code();
+        if !method.is_method() {
+            return Err(Error::InvalidArgument);
+        }
+
+        let options = OptionSet::parse(end_to_end_options(request.options()))?;
+
+        Ok(ForwardRequest {
+            method,
+            options,
+            payload: request.payload().to_vec(),
+        })
+    }
+
+    /// Returns the method of the forwarded request.
+    pub fn method(&self) -> MsgCode {
+        self.method
+    }
+}
+
+impl SendDescUnicast for ForwardRequest {}
+
+impl<IC: InboundContext> SendDesc<IC, OwnedImmutableMessage> for ForwardRequest {
+    fn write_options(
+        &self,
+        msg: &mut dyn OptionInsert,
+        _socket_addr: &IC::SocketAddr,
+        start: Bound<OptionNumber>,
+        end: Bound<OptionNumber>,
+    ) -> Result<(), Error> {
+        let range = (start, end);
+
+        for (number, value) in self.options.iter() {
+            if range.contains(&number) {
+                msg.insert_option_with_bytes(number, value)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_payload(
+        &self,
+        msg: &mut dyn MessageWrite,
+        _socket_addr: &IC::SocketAddr,
+    ) -> Result<(), Error> {
+        msg.set_msg_code(self.method);
+        msg.append_payload_bytes(&self.payload)
+    }
+
+    fn payload_size_hint(&self) -> usize {
+        self.payload.len()
+    }
+
+    fn handler(
+        &mut self,
+        context: Result<&IC, Error>,
+    ) -> Result<ResponseStatus<OwnedImmutableMessage>, Error> {
+        Ok(ResponseStatus::Done(context?.message().to_owned()))
+    }
+}
+
+/// Writes a response to a forwarded request to `msg_out`, for relaying it back to the
+/// client from inside [`RespondableInboundContext::respond`].
+///
+/// The response code, payload, and end-to-end options of `response` are copied. Returns
+/// [`Error::UnhandledCriticalOption`] if `response` has an option that is neither
+/// understood nor safe to forward, in which case the client should be sent
+/// `5.02 Bad Gateway` instead.
+pub fn write_forwarded_response(
+    response: &dyn MessageRead,
+    msg_out: &mut dyn MessageWrite,
+) -> Result<(), Error> {
+    msg_out.set_msg_code(response.msg_code());
+
+    for result in end_to_end_options(response.options()) {
+        let (number, value) = result?;
+        msg_out.insert_option_with_bytes(number, value)?;
+    }
+
+    msg_out.append_payload_bytes(response.payload())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datagram::LoopbackSocketAddr;
+    use crate::message::{StandardMessageParser, VecMessageEncoder};
+
+    type IC = crate::datagram::DatagramInboundContext<LoopbackSocketAddr>;
+
+    #[test]
+    fn forward_request() {
+        let mut encoder = VecMessageEncoder::new();
+        encoder.set_msg_code(MsgCode::MethodPost);
+        encoder
+            .insert_option(option::URI_HOST, "proxy.example")
+            .unwrap();
+        encoder.insert_option(option::OBSERVE, 0).unwrap();
+        encoder.insert_option(option::URI_PATH, "sensors").unwrap();
+        encoder
+            .insert_option(option::CONTENT_FORMAT, ContentFormat::TEXT_PLAIN_UTF8)
+            .unwrap();
+        encoder.insert_option(option::URI_QUERY, "a=1").unwrap();
+        encoder.insert_option(option::SIZE1, 5).unwrap();
+        encoder.append_payload_string("hello").unwrap();
+        let request = StandardMessageParser::new(encoder.as_bytes()).unwrap();
+
+        let forward = ForwardRequest::new(&request).unwrap();
+        assert_eq!(MsgCode::MethodPost, forward.method());
+
+        let send_desc = CoapRequest::get().uri_host_path(None, rel_ref!("upstream/sensors"));
+        let mut encoder = VecMessageEncoder::new();
+        SendDesc::<IC, _>::write_options(
+            &forward,
+            &mut encoder,
+            &LoopbackSocketAddr::Unicast,
+            Bound::Unbounded,
+            Bound::Unbounded,
+        )
+        .unwrap();
+        SendDesc::<IC, ()>::write_options(
+            &send_desc,
+            &mut encoder,
+            &LoopbackSocketAddr::Unicast,
+            Bound::Unbounded,
+            Bound::Unbounded,
+        )
+        .unwrap();
+        SendDesc::<IC, _>::write_payload(&forward, &mut encoder, &LoopbackSocketAddr::Unicast)
+            .unwrap();
+        let forwarded = StandardMessageParser::new(encoder.as_bytes()).unwrap();
+
+        assert_eq!(MsgCode::MethodPost, forwarded.msg_code());
+        assert_eq!(b"hello", forwarded.payload());
+        assert_eq!(
+            vec![
+                (OptionNumber::URI_PATH, &b"upstream"[..]),
+                (OptionNumber::URI_PATH, &b"sensors"[..]),
+                (OptionNumber::CONTENT_FORMAT, &[][..]),
+                (OptionNumber::SIZE1, &[5][..]),
+            ],
+            forwarded.options().collect::<Result<Vec<_>, _>>().unwrap()
+        );
+    }
+
+    #[test]
+    fn forward_request_unsafe_option() {
+        let mut encoder = VecMessageEncoder::new();
+        encoder.set_msg_code(MsgCode::MethodGet);
+        encoder
+            .insert_option_with_bytes(OptionNumber(65002), b"x")
+            .unwrap();
+        let request = StandardMessageParser::new(encoder.as_bytes()).unwrap();
+
+        assert_eq!(
+            Err(Error::UnhandledCriticalOption),
+            ForwardRequest::new(&request)
+        );
+
+        // Unknown options which are safe to forward are copied.
+        let mut encoder = VecMessageEncoder::new();
+        encoder.set_msg_code(MsgCode::MethodGet);
+        encoder
+            .insert_option_with_bytes(OptionNumber(65004), b"x")
+            .unwrap();
+        let request = StandardMessageParser::new(encoder.as_bytes()).unwrap();
+
+        assert!(ForwardRequest::new(&request).is_ok());
+    }
+
+    #[test]
+    fn forwarded_response() {
+        let mut encoder = VecMessageEncoder::new();
+        encoder.set_msg_code(MsgCode::SuccessContent);
+        encoder.set_msg_token(MsgToken::from(0x1234));
+        encoder.insert_option(option::OBSERVE, 7).unwrap();
+        encoder
+            .insert_option(option::ETAG, ETag::new(b"v1"))
+            .unwrap();
+        encoder.insert_option(option::MAX_AGE, 30).unwrap();
+        encoder.append_payload_string("21.5").unwrap();
+        let response = StandardMessageParser::new(encoder.as_bytes()).unwrap();
+
+        let mut encoder = VecMessageEncoder::new();
+        write_forwarded_response(&response, &mut encoder).unwrap();
+        let relayed = StandardMessageParser::new(encoder.as_bytes()).unwrap();
+
+        assert_eq!(MsgCode::SuccessContent, relayed.msg_code());
+        assert_eq!(MsgToken::EMPTY, relayed.msg_token());
+        assert_eq!(b"21.5", relayed.payload());
+        assert_eq!(
+            vec![
+                (OptionNumber::ETAG, &b"v1"[..]),
+                (OptionNumber::MAX_AGE, &[30][..]),
+            ],
+            relayed.options().collect::<Result<Vec<_>, _>>().unwrap()
+        );
+    }
+}
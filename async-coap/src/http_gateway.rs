@@ -0,0 +1,728 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Translation between CoAP and HTTP messages, as described in [IETF-RFC8075].
+//!
+//! This module only deals with translating individual messages: methods, response codes,
+//! content formats, and the handful of options that have an HTTP equivalent. It is
+//! transport-agnostic, using the types from the [`http`] crate to represent HTTP messages.
+//! A working gateway built on top of these pieces can be found in the
+//! [`async-coap-tokio`](https://docs.rs/async-coap-tokio/) crate.
+//!
+//! This module is only available when the `http-gateway` feature is enabled.
+//!
+//! [IETF-RFC8075]: https://tools.ietf.org/html/rfc8075
+
+use super::*;
+use crate::message::{MessageRead, MessageWrite, OwnedImmutableMessage};
+use crate::option::{OptionInsertExt, OptionIteratorExt};
+use ::http::header::{self, HeaderValue};
+use ::http::{Method, Request, Response, StatusCode};
+use std::convert::TryFrom;
+use std::ops::Bound;
+
+/// The default path prefix used by HTTP clients to address CoAP resources through a gateway,
+/// from [IETF-RFC8075 Section 5.4](https://tools.ietf.org/html/rfc8075#section-5.4).
+pub const DEFAULT_HC_PREFIX: &str = "/hc/";
+
+/// Returns the HTTP method equivalent to the given CoAP method, or `None` if `code`
+/// isn't a method.
+///
+/// Note that both `PATCH` and `iPATCH` map to the HTTP `PATCH` method.
+pub fn http_method_from_coap(code: MsgCode) -> Option<Method> {
+    Some(match code {
+        MsgCode::MethodGet => Method::GET,
+        MsgCode::MethodPost => Method::POST,
+        MsgCode::MethodPut => Method::PUT,
+        MsgCode::MethodDelete => Method::DELETE,
+        MsgCode::MethodFetch => Method::from_bytes(b"FETCH").unwrap(),
+        MsgCode::MethodPatch | MsgCode::MethodIPatch => Method::PATCH,
+        _ => return None,
+    })
+}
+
+/// Returns the CoAP method equivalent to the given HTTP method, or `None` if there is no
+/// CoAP equivalent.
+pub fn coap_method_from_http(method: &Method) -> Option<MsgCode> {
+    Some(match method.as_str() {
+        "GET" => MsgCode::MethodGet,
+        "POST" => MsgCode::MethodPost,
+        "PUT" => MsgCode::MethodPut,
+        "DELETE" => MsgCode::MethodDelete,
+        "FETCH" => MsgCode::MethodFetch,
+        "PATCH" => MsgCode::MethodPatch,
+        _ => return None,
+    })
+}
+
+/// Returns the HTTP status code to use when forwarding a CoAP response with the given
+/// code, as described in [IETF-RFC8075 Section 7](https://tools.ietf.org/html/rfc8075#section-7).
+pub fn http_status_from_coap(code: MsgCode) -> StatusCode {
+    match code {
+        MsgCode::SuccessCreated => StatusCode::CREATED,
+        MsgCode::SuccessDeleted => StatusCode::OK,
+        MsgCode::SuccessValid => StatusCode::NOT_MODIFIED,
+        MsgCode::SuccessChanged => StatusCode::NO_CONTENT,
+        MsgCode::SuccessContent => StatusCode::OK,
+        MsgCode::SuccessContinue => StatusCode::CONTINUE,
+        MsgCode::ClientErrorBadOption => StatusCode::BAD_REQUEST,
+        MsgCode::ClientErrorRequestEntityIncomplete => StatusCode::BAD_REQUEST,
+        MsgCode::ServerErrorProxyingNotSupported => StatusCode::BAD_GATEWAY,
+        code if code.is_error() => {
+            let code = code as u16;
+            StatusCode::from_u16((code >> 5) * 100 + (code & 0b11111))
+                .unwrap_or(StatusCode::BAD_GATEWAY)
+        }
+        _ => StatusCode::BAD_GATEWAY,
+    }
+}
+
+/// Returns the CoAP response code to use when forwarding an HTTP response with the given
+/// status code to a request with the given CoAP method, as described in
+/// [IETF-RFC8075 Section 7](https://tools.ietf.org/html/rfc8075#section-7).
+pub fn coap_code_from_http(status: StatusCode, method: MsgCode) -> MsgCode {
+    match status.as_u16() {
+        200 => match method {
+            MsgCode::MethodGet | MsgCode::MethodFetch => MsgCode::SuccessContent,
+            MsgCode::MethodDelete => MsgCode::SuccessDeleted,
+            _ => MsgCode::SuccessChanged,
+        },
+        201 => MsgCode::SuccessCreated,
+        202 | 203 | 205 | 206 => MsgCode::SuccessContent,
+        204 => match method {
+            MsgCode::MethodDelete => MsgCode::SuccessDeleted,
+            _ => MsgCode::SuccessChanged,
+        },
+        304 => MsgCode::SuccessValid,
+        401 => MsgCode::ClientErrorUnauthorized,
+        403 => MsgCode::ClientErrorForbidden,
+        404 | 410 => MsgCode::ClientErrorNotFound,
+        405 => MsgCode::ClientErrorMethodNotAllowed,
+        406 => MsgCode::ClientErrorNotAcceptable,
+        412 => MsgCode::ClientErrorPreconditionFailed,
+        413 => MsgCode::ClientErrorRequestEntityTooLarge,
+        415 => MsgCode::ClientErrorUnsupportedMediaType,
+        429 => MsgCode::ClientErrorTooManyRequests,
+        501 => MsgCode::ServerErrorNotImplemented,
+        503 => MsgCode::ServerErrorServiceUnavailable,
+        504 => MsgCode::ServerErrorGatewayTimeout,
+        400..=499 => MsgCode::ClientErrorBadRequest,
+        500..=599 => MsgCode::ServerErrorInternalServerError,
+        _ => MsgCode::ServerErrorBadGateway,
+    }
+}
+
+/// Extracts the target of a CoAP-to-HTTP proxy request from the `Proxy-Uri` option, or from
+/// the `Proxy-Scheme`, `Uri-Host`, `Uri-Port`, `Uri-Path`, and `Uri-Query` options.
+fn proxy_target(msg: &dyn MessageRead) -> Result<String, MsgCode> {
+    let bad_option = |_| MsgCode::ClientErrorBadOption;
+
+    let target = if let Some(proxy_uri) = msg
+        .options()
+        .find_next_of(option::PROXY_URI)
+        .transpose()
+        .map_err(bad_option)?
+    {
+        proxy_uri.to_string()
+    } else if let Some(scheme) = msg
+        .options()
+        .find_next_of(option::PROXY_SCHEME)
+        .transpose()
+        .map_err(bad_option)?
+    {
+        let host = msg
+            .options()
+            .find_next_of(option::URI_HOST)
+            .transpose()
+            .map_err(bad_option)?
+            .ok_or(MsgCode::ClientErrorBadRequest)?;
+        let port = msg
+            .options()
+            .find_next_of(option::URI_PORT)
+            .transpose()
+            .map_err(bad_option)?;
+        let path = msg.options().extract_uri().map_err(bad_option)?;
+
+        let mut target = format!("{}://{}", scheme, host);
+        if let Some(port) = port {
+            target.push_str(&format!(":{}", port));
+        }
+        target.push('/');
+        target.push_str(path.as_str());
+        target
+    } else {
+        // Not a proxy request at all.
+        return Err(MsgCode::ServerErrorProxyingNotSupported);
+    };
+
+    match target.split(':').next() {
+        Some(scheme) if scheme.eq_ignore_ascii_case("http") => Ok(target),
+        Some(scheme) if scheme.eq_ignore_ascii_case("https") => Ok(target),
+        _ => Err(MsgCode::ServerErrorProxyingNotSupported),
+    }
+}
+
+/// Translates an inbound CoAP proxy request into the equivalent HTTP request.
+///
+/// The target of the request is taken from the `Proxy-Uri` option, or from the
+/// `Proxy-Scheme` option combined with the `Uri-*` options. The `Content-Format` and
+/// `Accept` options are translated into `Content-Type` and `Accept` headers.
+///
+/// If the request cannot be translated, the CoAP response code that should be sent back
+/// is returned as the error. In particular, requests for a scheme other than `http` or
+/// `https` (or requests without any proxy options) result in
+/// [`MsgCode::ServerErrorProxyingNotSupported`].
+pub fn http_request_from_coap(msg: &dyn MessageRead) -> Result<Request<Vec<u8>>, MsgCode> {
+    let method = http_method_from_coap(msg.msg_code()).ok_or(MsgCode::ClientErrorBadRequest)?;
+    let target = proxy_target(msg)?;
+
+    let mut builder = Request::builder().method(method).uri(target.as_str());
+
+    if let Some(content_format) = msg.content_format() {
+        builder = builder.header(header::CONTENT_TYPE, content_format.name().as_ref());
+    }
+
+    if let Some(accept) = msg.accept() {
+        builder = builder.header(header::ACCEPT, accept.name().as_ref());
+    }
+
+    builder
+        .body(msg.payload().to_vec())
+        .map_err(|_| MsgCode::ClientErrorBadOption)
+}
+
+/// Formats a CoAP ETag as an HTTP entity-tag.
+fn http_etag_from_coap(etag: ETag) -> String {
+    let mut ret = String::from("\"");
+    for b in etag.as_bytes() {
+        ret.push_str(&format!("{:02x}", b));
+    }
+    ret.push('"');
+    ret
+}
+
+/// Converts an HTTP entity-tag into a CoAP ETag. Entity-tags that were produced by
+/// [`http_etag_from_coap`] are decoded back into the original value, other short tags are
+/// used as-is, and anything longer than a CoAP ETag allows is dropped.
+fn coap_etag_from_http(etag: &str) -> Option<ETag> {
+    let opaque = etag.trim_start_matches("W/").trim_matches('"');
+
+    let is_hex_pair = |x: &[u8]| x.len() == 2 && x.iter().all(u8::is_ascii_hexdigit);
+
+    if opaque.len() <= ETag::MAX_LEN * 2 && opaque.as_bytes().chunks(2).all(is_hex_pair) {
+        let bytes = opaque
+            .as_bytes()
+            .chunks(2)
+            .map(|x| u8::from_str_radix(std::str::from_utf8(x).unwrap(), 16).unwrap())
+            .collect::<Vec<_>>();
+        Some(ETag::new(&bytes))
+    } else if !opaque.is_empty() && opaque.len() <= ETag::MAX_LEN {
+        Some(ETag::new(opaque.as_bytes()))
+    } else {
+        None
+    }
+}
+
+/// Extracts the `max-age` directive from an HTTP `Cache-Control` header.
+fn max_age_from_cache_control(value: &str) -> Option<u32> {
+    let mut max_age = None;
+
+    for directive in value.split(',').map(str::trim) {
+        if directive.eq_ignore_ascii_case("no-cache") || directive.eq_ignore_ascii_case("no-store")
+        {
+            return Some(0);
+        }
+
+        let mut iter = directive.splitn(2, '=');
+        if let (Some(key), Some(value)) = (iter.next(), iter.next()) {
+            if key.trim().eq_ignore_ascii_case("max-age") {
+                max_age = value.trim().trim_matches('"').parse().ok();
+            }
+        }
+    }
+
+    max_age
+}
+
+/// Writes the CoAP equivalent of the given HTTP response into `msg`, which is the response
+/// to a CoAP request with the method `method`.
+///
+/// In addition to the response code and payload, the `Content-Type`, `ETag`, `Location`
+/// and `Cache-Control` headers are translated into the `Content-Format`, `ETag`,
+/// `Location-Path`/`Location-Query` and `Max-Age` options. Content types which don't have
+/// a CoAP content format cause a [`MsgCode::ServerErrorBadGateway`] response to be written
+/// instead, since the recipient would have no way to interpret the payload.
+pub fn write_coap_response(
+    method: MsgCode,
+    response: &Response<Vec<u8>>,
+    msg: &mut dyn MessageWrite,
+) -> Result<(), Error> {
+    let headers = response.headers();
+    let header_str = |name| {
+        headers
+            .get(name)
+            .and_then(|x: &HeaderValue| x.to_str().ok())
+    };
+
+    let content_format = match header_str(header::CONTENT_TYPE) {
+        Some(content_type) => match ContentFormat::from_name(content_type) {
+            Some(content_format) => Some(content_format),
+            None => {
+                msg.set_msg_code(MsgCode::ServerErrorBadGateway);
+                return Ok(());
+            }
+        },
+        None => None,
+    };
+
+    msg.set_msg_code(coap_code_from_http(response.status(), method));
+
+    if let Some(etag) = header_str(header::ETAG).and_then(coap_etag_from_http) {
+        msg.insert_option(option::ETAG, etag)?;
+    }
+
+    if let Some(location) = header_str(header::LOCATION) {
+        // Only relative locations can be represented as CoAP options.
+        if let Ok(location) = RelRef::from_str(location) {
+            for segment in location.raw_path_segments().filter(|x| !x.is_empty()) {
+                msg.insert_option(option::LOCATION_PATH, &segment.unescape_uri().to_cow())?;
+            }
+            for item in location.raw_query_items() {
+                msg.insert_option(option::LOCATION_QUERY, &item.unescape_uri().to_cow())?;
+            }
+        }
+    }
+
+    if let Some(content_format) = content_format {
+        msg.insert_option(option::CONTENT_FORMAT, content_format)?;
+    }
+
+    if let Some(max_age) = header_str(header::CACHE_CONTROL).and_then(max_age_from_cache_control) {
+        msg.insert_option(option::MAX_AGE, max_age)?;
+    }
+
+    msg.append_payload_bytes(response.body())
+}
+
+/// Translates a CoAP response into the equivalent HTTP response.
+///
+/// The `Content-Format`, `ETag`, `Location-Path`/`Location-Query` and `Max-Age` options
+/// are translated into `Content-Type`, `ETag`, `Location` and `Cache-Control` headers.
+pub fn http_response_from_coap(msg: &dyn MessageRead) -> Response<Vec<u8>> {
+    let mut builder = Response::builder().status(http_status_from_coap(msg.msg_code()));
+
+    if let Some(content_format) = msg.content_format() {
+        builder = builder.header(header::CONTENT_TYPE, content_format.name().as_ref());
+    }
+
+    if let Some(Ok(etag)) = msg.options().find_next_of(option::ETAG) {
+        builder = builder.header(header::ETAG, http_etag_from_coap(etag));
+    }
+
+    if let Ok(location) = msg.options().extract_location() {
+        if !location.is_empty() {
+            builder = builder.header(header::LOCATION, format!("/{}", location));
+        }
+    }
+
+    if let Some(Ok(max_age)) = msg.options().find_next_of(option::MAX_AGE) {
+        builder = builder.header(header::CACHE_CONTROL, format!("max-age={}", max_age));
+    }
+
+    builder
+        .body(msg.payload().to_vec())
+        .unwrap_or_else(|_| bad_gateway_response())
+}
+
+/// Returns an empty `502 Bad Gateway` HTTP response.
+pub fn bad_gateway_response() -> Response<Vec<u8>> {
+    let mut response = Response::new(Vec::new());
+    *response.status_mut() = StatusCode::BAD_GATEWAY;
+    response
+}
+
+/// Extracts the CoAP URI targeted by an HTTP request to a gateway, using the default
+/// URI mapping template from [IETF-RFC8075 Section 5.3]. For example, with a `prefix` of
+/// [`DEFAULT_HC_PREFIX`], the request target `/hc/coap://example.com/a?b` maps to
+/// `coap://example.com/a?b`.
+///
+/// The CoAP URI may also be percent-encoded in its entirety (like
+/// `/hc/coap%3A%2F%2Fexample.com%2Fa`). Returns `None` if `target` doesn't start with
+/// `prefix` or doesn't contain an absolute `coap` or `coaps` URI.
+///
+/// [IETF-RFC8075 Section 5.3]: https://tools.ietf.org/html/rfc8075#section-5.3
+pub fn coap_uri_from_http_target(target: &::http::Uri, prefix: &str) -> Option<UriBuf> {
+    let path_and_query = target.path_and_query()?.as_str();
+
+    if !path_and_query.starts_with(prefix) {
+        return None;
+    }
+
+    let rest = &path_and_query[prefix.len()..];
+
+    let uri = UriBuf::from_str(rest)
+        .ok()
+        .filter(|uri| uri.raw_authority().is_some())
+        .or_else(|| UriBuf::from_string(rest.unescape_uri().to_string()).ok())?;
+
+    match uri.scheme() {
+        Some(scheme) if scheme.eq_ignore_ascii_case("coap") => Some(uri),
+        Some(scheme) if scheme.eq_ignore_ascii_case("coaps") => Some(uri),
+        _ => None,
+    }
+}
+
+/// Send descriptor for forwarding an HTTP request to a CoAP server.
+///
+/// The method, `Content-Type`, `Accept`, and body of the HTTP request are translated into
+/// the CoAP request. The destination is determined by the remote endpoint and path that
+/// this send descriptor is used with. The first response received is emitted as an
+/// [`OwnedImmutableMessage`], which can be translated back into HTTP using
+/// [`http_response_from_coap`].
+#[derive(Debug, Clone)]
+pub struct HttpRequestDesc {
+    method: MsgCode,
+    content_format: Option<ContentFormat>,
+    accept: Option<ContentFormat>,
+    payload: Vec<u8>,
+}
+
+impl HttpRequestDesc {
+    /// Creates a new send descriptor from the given HTTP request.
+    ///
+    /// If the request can't be translated into CoAP, the HTTP status code that should be
+    /// sent back is returned as the error.
+    pub fn from_http_request<B: AsRef<[u8]>>(request: &Request<B>) -> Result<Self, StatusCode> {
+        let method = coap_method_from_http(request.method()).ok_or(StatusCode::NOT_IMPLEMENTED)?;
+
+        let header_str = |name| {
+            request
+                .headers()
+                .get(name)
+                .map(|x: &HeaderValue| x.to_str().map_err(|_| StatusCode::BAD_REQUEST))
+                .transpose()
+        };
+
+        let content_format = match header_str(header::CONTENT_TYPE)? {
+            Some(content_type) => Some(
+                ContentFormat::from_name(content_type).ok_or(StatusCode::UNSUPPORTED_MEDIA_TYPE)?,
+            ),
+            None => None,
+        };
+
+        // CoAP only allows a single `Accept` option, so we use the first acceptable
+        // media type that has a CoAP equivalent.
+        let accept = header_str(header::ACCEPT)?.and_then(|accept| {
+            accept
+                .split(',')
+                .filter_map(|x| x.split(";q=").next())
+                .find_map(ContentFormat::from_name)
+        });
+
+        Ok(HttpRequestDesc {
+            method,
+            content_format,
+            accept,
+            payload: request.body().as_ref().to_vec(),
+        })
+    }
+
+    /// Returns the CoAP method that will be used for the request.
+    pub fn method(&self) -> MsgCode {
+        self.method
+    }
+}
+
+impl TryFrom<&Request<Vec<u8>>> for HttpRequestDesc {
+    type Error = StatusCode;
+
+    fn try_from(request: &Request<Vec<u8>>) -> Result<Self, Self::Error> {
+        HttpRequestDesc::from_http_request(request)
+    }
+}
+
+impl SendDescUnicast for HttpRequestDesc {}
+
+impl<IC: InboundContext> SendDesc<IC, OwnedImmutableMessage> for HttpRequestDesc {
+    fn write_options(
+        &self,
+        msg: &mut dyn OptionInsert,
+        socket_addr: &IC::SocketAddr,
+        start: Bound<OptionNumber>,
+        end: Bound<OptionNumber>,
+    ) -> Result<(), Error> {
+        write_options!((msg, socket_addr, start, end) {
+            CONTENT_FORMAT => self.content_format.into_iter(),
+            ACCEPT => self.accept.into_iter(),
+        })
+    }
+
+    fn write_payload(
+        &self,
+        msg: &mut dyn MessageWrite,
+        _socket_addr: &IC::SocketAddr,
+    ) -> Result<(), Error> {
+        msg.set_msg_code(self.method);
+        msg.append_payload_bytes(&self.payload)
+    }
+
+    fn payload_size_hint(&self) -> usize {
+        self.payload.len()
+    }
+
+    fn handler(
+        &mut self,
+        context: Result<&IC, Error>,
+    ) -> Result<ResponseStatus<OwnedImmutableMessage>, Error> {
+        Ok(ResponseStatus::Done(context?.message().to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::{MsgId, MsgToken, MsgType, VecMessageEncoder};
+
+    fn build_msg<F>(f: F) -> OwnedImmutableMessage
+    where
+        F: FnOnce(&mut dyn MessageWrite) -> Result<(), Error>,
+    {
+        let mut encoder = VecMessageEncoder::new();
+        encoder.set_msg_type(MsgType::Con);
+        encoder.set_msg_id(0x1234 as MsgId);
+        encoder.set_msg_token(MsgToken::EMPTY);
+        f(&mut encoder).unwrap();
+        OwnedImmutableMessage::new(encoder.as_bytes().to_vec()).unwrap()
+    }
+
+    #[test]
+    fn method_mapping() {
+        for code in &[
+            MsgCode::MethodGet,
+            MsgCode::MethodPost,
+            MsgCode::MethodPut,
+            MsgCode::MethodDelete,
+            MsgCode::MethodFetch,
+            MsgCode::MethodPatch,
+        ] {
+            let method = http_method_from_coap(*code).unwrap();
+            assert_eq!(Some(*code), coap_method_from_http(&method));
+        }
+
+        assert_eq!(
+            Some(Method::PATCH),
+            http_method_from_coap(MsgCode::MethodIPatch)
+        );
+        assert_eq!(None, http_method_from_coap(MsgCode::SuccessContent));
+        assert_eq!(None, coap_method_from_http(&Method::OPTIONS));
+    }
+
+    #[test]
+    fn code_mapping() {
+        assert_eq!(
+            StatusCode::OK,
+            http_status_from_coap(MsgCode::SuccessContent)
+        );
+        assert_eq!(
+            StatusCode::NOT_MODIFIED,
+            http_status_from_coap(MsgCode::SuccessValid)
+        );
+        assert_eq!(
+            StatusCode::NOT_FOUND,
+            http_status_from_coap(MsgCode::ClientErrorNotFound)
+        );
+        assert_eq!(
+            StatusCode::BAD_REQUEST,
+            http_status_from_coap(MsgCode::ClientErrorBadOption)
+        );
+        assert_eq!(
+            StatusCode::BAD_GATEWAY,
+            http_status_from_coap(MsgCode::ServerErrorProxyingNotSupported)
+        );
+
+        assert_eq!(
+            MsgCode::SuccessContent,
+            coap_code_from_http(StatusCode::OK, MsgCode::MethodGet)
+        );
+        assert_eq!(
+            MsgCode::SuccessChanged,
+            coap_code_from_http(StatusCode::OK, MsgCode::MethodPut)
+        );
+        assert_eq!(
+            MsgCode::SuccessDeleted,
+            coap_code_from_http(StatusCode::NO_CONTENT, MsgCode::MethodDelete)
+        );
+        assert_eq!(
+            MsgCode::ClientErrorNotFound,
+            coap_code_from_http(StatusCode::GONE, MsgCode::MethodGet)
+        );
+        assert_eq!(
+            MsgCode::ClientErrorBadRequest,
+            coap_code_from_http(StatusCode::IM_A_TEAPOT, MsgCode::MethodGet)
+        );
+        assert_eq!(
+            MsgCode::ServerErrorBadGateway,
+            coap_code_from_http(StatusCode::FOUND, MsgCode::MethodGet)
+        );
+    }
+
+    #[test]
+    fn request_from_coap() {
+        let msg = build_msg(|msg| {
+            msg.set_msg_code(MsgCode::MethodPost);
+            msg.insert_option(option::PROXY_URI, "http://example.com/a?b")?;
+            msg.insert_option(option::CONTENT_FORMAT, ContentFormat::APPLICATION_JSON)?;
+            msg.append_payload_string("{}")
+        });
+
+        let request = http_request_from_coap(&msg).unwrap();
+        assert_eq!(Method::POST, request.method());
+        assert_eq!("http://example.com/a?b", request.uri().to_string());
+        assert_eq!("application/json", request.headers()[header::CONTENT_TYPE]);
+        assert_eq!(b"{}", request.body().as_slice());
+
+        let msg = build_msg(|msg| {
+            msg.set_msg_code(MsgCode::MethodGet);
+            msg.insert_option(option::URI_HOST, "example.com")?;
+            msg.insert_option(option::URI_PORT, 8080)?;
+            msg.insert_option(option::URI_PATH, "a b")?;
+            msg.insert_option(option::URI_QUERY, "c")?;
+            msg.insert_option(option::PROXY_SCHEME, "https")
+        });
+
+        let request = http_request_from_coap(&msg).unwrap();
+        assert_eq!(
+            "https://example.com:8080/a%20b?c",
+            request.uri().to_string()
+        );
+
+        let msg = build_msg(|msg| {
+            msg.set_msg_code(MsgCode::MethodGet);
+            msg.insert_option(option::PROXY_URI, "coap://example.com/")
+        });
+        assert_eq!(
+            Err(MsgCode::ServerErrorProxyingNotSupported),
+            http_request_from_coap(&msg).map(|_| ())
+        );
+
+        let msg = build_msg(|msg| {
+            msg.set_msg_code(MsgCode::MethodGet);
+            msg.insert_option(option::URI_PATH, "a")
+        });
+        assert_eq!(
+            Err(MsgCode::ServerErrorProxyingNotSupported),
+            http_request_from_coap(&msg).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn response_to_coap() {
+        let response = Response::builder()
+            .status(StatusCode::CREATED)
+            .header(header::CONTENT_TYPE, "text/plain")
+            .header(header::LOCATION, "/a/b%20c?d")
+            .header(header::ETAG, "\"0a0b\"")
+            .header(header::CACHE_CONTROL, "public, max-age=30")
+            .body(b"hello".to_vec())
+            .unwrap();
+
+        let msg = build_msg(|msg| write_coap_response(MsgCode::MethodPost, &response, msg));
+
+        assert_eq!(MsgCode::SuccessCreated, msg.msg_code());
+        assert_eq!(Some(ContentFormat::TEXT_PLAIN_UTF8), msg.content_format());
+        assert_eq!(
+            "a/b%20c?d",
+            msg.options().extract_location().unwrap().as_str()
+        );
+        assert_eq!(
+            Some(Ok(ETag::new(&[0x0a, 0x0b]))),
+            msg.options().find_next_of(option::ETAG)
+        );
+        assert_eq!(Some(Ok(30)), msg.options().find_next_of(option::MAX_AGE));
+        assert_eq!(b"hello", msg.payload());
+
+        let round_trip = http_response_from_coap(&msg);
+        assert_eq!(StatusCode::CREATED, round_trip.status());
+        assert_eq!(
+            "text/plain;charset=utf-8",
+            round_trip.headers()[header::CONTENT_TYPE]
+        );
+        assert_eq!("\"0a0b\"", round_trip.headers()[header::ETAG]);
+        assert_eq!("/a/b%20c?d", round_trip.headers()[header::LOCATION]);
+        assert_eq!("max-age=30", round_trip.headers()[header::CACHE_CONTROL]);
+
+        let response = Response::builder()
+            .header(header::CONTENT_TYPE, "image/png")
+            .body(Vec::new())
+            .unwrap();
+        let msg = build_msg(|msg| write_coap_response(MsgCode::MethodGet, &response, msg));
+        assert_eq!(MsgCode::ServerErrorBadGateway, msg.msg_code());
+    }
+
+    #[test]
+    fn http_target() {
+        let target: ::http::Uri = "/hc/coap://example.com:1234/a/b?c".parse().unwrap();
+        assert_eq!(
+            Some(uri!("coap://example.com:1234/a/b?c").to_uri_buf()),
+            coap_uri_from_http_target(&target, DEFAULT_HC_PREFIX)
+        );
+
+        let target: ::http::Uri = "/hc/coap%3A%2F%2Fexample.com%2Fa".parse().unwrap();
+        assert_eq!(
+            Some(uri!("coap://example.com/a").to_uri_buf()),
+            coap_uri_from_http_target(&target, DEFAULT_HC_PREFIX)
+        );
+
+        let target: ::http::Uri = "/hc/http://example.com/".parse().unwrap();
+        assert_eq!(None, coap_uri_from_http_target(&target, DEFAULT_HC_PREFIX));
+
+        let target: ::http::Uri = "/other/coap://example.com/".parse().unwrap();
+        assert_eq!(None, coap_uri_from_http_target(&target, DEFAULT_HC_PREFIX));
+    }
+
+    #[test]
+    fn request_desc() {
+        let request = Request::builder()
+            .method(Method::PUT)
+            .header(header::CONTENT_TYPE, "application/cbor")
+            .header(header::ACCEPT, "image/png, application/json;q=0.5")
+            .body(vec![0xa0])
+            .unwrap();
+
+        let desc = HttpRequestDesc::try_from(&request).unwrap();
+        assert_eq!(MsgCode::MethodPut, desc.method());
+        assert_eq!(Some(ContentFormat::APPLICATION_CBOR), desc.content_format);
+        assert_eq!(Some(ContentFormat::APPLICATION_JSON), desc.accept);
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .header(header::CONTENT_TYPE, "image/png")
+            .body(Vec::new())
+            .unwrap();
+        assert_eq!(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            HttpRequestDesc::try_from(&request).unwrap_err()
+        );
+
+        let request = Request::builder()
+            .method(Method::OPTIONS)
+            .body(Vec::new())
+            .unwrap();
+        assert_eq!(
+            StatusCode::NOT_IMPLEMENTED,
+            HttpRequestDesc::try_from(&request).unwrap_err()
+        );
+    }
+}
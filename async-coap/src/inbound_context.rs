@@ -14,6 +14,7 @@
 //
 
 use super::*;
+use crate::message::{StandardMessageParser, VecMessageEncoder};
 
 /// Represents the context for processing an inbound message.
 pub trait InboundContext: Send {
@@ -30,6 +31,16 @@ pub trait InboundContext: Send {
     /// doesn't support support storing sent replies for this purpose.
     fn is_dupe(&self) -> bool;
 
+    /// Returns the number of times this message was received before, which is zero for the
+    /// first arrival of a message. This is useful for logging retransmissions, or for
+    /// asserting that a non-idempotent handler only acts on a request once.
+    ///
+    /// The default implementation only indicates whether [`is_dupe`](Self::is_dupe) is
+    /// true, returning one for duplicates.
+    fn dupe_count(&self) -> u32 {
+        self.is_dupe() as u32
+    }
+
     /// Returns a reference to a MessageRead trait to inspect the content
     /// of the inbound message.
     fn message(&self) -> &dyn MessageRead;
@@ -53,4 +64,222 @@ pub trait RespondableInboundContext: InboundContext {
     fn respond<F>(&self, msg_gen: F) -> Result<(), Error>
     where
         F: Fn(&mut dyn MessageWrite) -> Result<(), Error>;
+
+    /// Rejects this inbound request by replying with an empty reset (`RST`) message
+    /// instead of a response.
+    ///
+    /// This replaces any response previously set with [`respond`](Self::respond).
+    fn send_reset(&self) -> Result<(), Error> {
+        self.respond(|msg_out| {
+            msg_out.set_msg_type(MsgType::Res);
+            msg_out.set_msg_token(MsgToken::EMPTY);
+            msg_out.set_msg_code(MsgCode::Empty);
+            Ok(())
+        })
+    }
+
+    /// Replies to this confirmable request with an empty acknowledgement (`ACK`) instead
+    /// of a piggybacked response, indicating that the response will be sent separately.
+    ///
+    /// Fails with [`Error::InvalidArgument`] if the inbound request isn't confirmable.
+    /// This replaces any response previously set with [`respond`](Self::respond).
+    fn acknowledge(&self) -> Result<(), Error> {
+        if !self.message().msg_type().is_con() {
+            return Err(Error::InvalidArgument);
+        }
+
+        self.respond(|msg_out| {
+            msg_out.set_msg_type(MsgType::Ack);
+            msg_out.set_msg_token(MsgToken::EMPTY);
+            msg_out.set_msg_code(MsgCode::Empty);
+            Ok(())
+        })
+    }
+
+    /// Responds to this inbound request with the error `code`, using `diagnostic` as the
+    /// payload.
+    ///
+    /// As described in [IETF-RFC7252 Section 5.5.2], the diagnostic payload is a
+    /// human-readable UTF-8 string which is sent without a `Content-Format` option.
+    /// An empty `diagnostic` results in a response without a payload.
+    ///
+    /// Fails with [`Error::InvalidArgument`] if `code` isn't a client or server error.
+    ///
+    /// [IETF-RFC7252 Section 5.5.2]: https://tools.ietf.org/html/rfc7252#section-5.5.2
+    fn respond_error(&self, code: MsgCode, diagnostic: &str) -> Result<(), Error> {
+        if !code.is_error() {
+            return Err(Error::InvalidArgument);
+        }
+
+        self.respond(|msg_out| {
+            msg_out.set_msg_code(code);
+            if !diagnostic.is_empty() {
+                msg_out.append_payload_string(diagnostic)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Responds to this inbound request with `4.04 Not Found`.
+    fn respond_not_found(&self) -> Result<(), Error> {
+        self.respond_error(MsgCode::ClientErrorNotFound, "")
+    }
+
+    /// Responds to this inbound request with `4.05 Method Not Allowed`, listing
+    /// `allowed_methods` in the diagnostic payload (for example, `"Allow: GET, PUT"`).
+    ///
+    /// Message codes in `allowed_methods` which aren't methods are ignored.
+    fn respond_method_not_allowed(&self, allowed_methods: &[MsgCode]) -> Result<(), Error> {
+        let allowed = allowed_methods
+            .iter()
+            .filter_map(|code| method_name(*code))
+            .collect::<Vec<_>>();
+
+        let diagnostic = if allowed.is_empty() {
+            String::new()
+        } else {
+            format!("Allow: {}", allowed.join(", "))
+        };
+
+        self.respond_error(MsgCode::ClientErrorMethodNotAllowed, &diagnostic)
+    }
+
+    /// Responds to this inbound request with `2.03 Valid`, indicating that the cached
+    /// representation identified by `etag` is still current.
+    fn respond_valid(&self, etag: ETag) -> Result<(), Error> {
+        self.respond(|msg_out| {
+            msg_out.set_msg_code(MsgCode::SuccessValid);
+            msg_out.insert_option(option::ETAG, etag)
+        })
+    }
+
+    /// Responds to this inbound request with the representation generated by `msg_gen`,
+    /// adding an `ETag` option with the value `etag`.
+    ///
+    /// If this is a `GET` or `FETCH` request which includes an `ETag` option matching
+    /// `etag`, the client already has the current representation, so this responds with
+    /// [`respond_valid`](Self::respond_valid) instead and `msg_gen` isn't called.
+    /// See [IETF-RFC7252 Section 5.10.6.2] for more information.
+    ///
+    /// [IETF-RFC7252 Section 5.10.6.2]: https://tools.ietf.org/html/rfc7252#section-5.10.6.2
+    fn respond_with_etag<F>(&self, msg_gen: F, etag: ETag) -> Result<(), Error>
+    where
+        F: Fn(&mut dyn MessageWrite) -> Result<(), Error>,
+    {
+        if is_validated(self.message(), etag)? {
+            return self.respond_valid(etag);
+        }
+
+        self.respond(|msg_out| {
+            msg_out.insert_option(option::ETAG, etag)?;
+            msg_gen(msg_out)
+        })
+    }
+
+    /// Like [`respond_with_etag`](Self::respond_with_etag), but computes the ETag from
+    /// the payload written by `msg_gen` using [`ETag::from_hash`].
+    ///
+    /// This is convenient for resources which don't track versions of their
+    /// representations themselves, at the cost of always generating the representation.
+    /// An ETag is only added to `2.05 Content` responses which don't already have one,
+    /// so `msg_gen` may still respond with an error or its own ETag.
+    ///
+    /// If the client already has the current representation, this responds with
+    /// `2.03 Valid` instead. That response keeps the options written by `msg_gen` which
+    /// don't describe the payload (like `Max-Age`), so that the client's cached response
+    /// is refreshed as described in [IETF-RFC7252 Section 5.9.1.3].
+    ///
+    /// [IETF-RFC7252 Section 5.9.1.3]: https://tools.ietf.org/html/rfc7252#section-5.9.1.3
+    fn respond_with_payload_etag<F>(&self, msg_gen: F) -> Result<(), Error>
+    where
+        F: Fn(&mut dyn MessageWrite) -> Result<(), Error>,
+    {
+        let mut generated = VecMessageEncoder::new();
+        msg_gen(&mut generated)?;
+        let generated = StandardMessageParser::new(generated.as_bytes())?;
+
+        let etag = if generated.msg_code() == MsgCode::SuccessContent
+            && generated.options().find_next_of(option::ETAG).is_none()
+        {
+            Some(ETag::from_hash(generated.payload()))
+        } else {
+            None
+        };
+
+        if let Some(etag) = etag {
+            if is_validated(self.message(), etag)? {
+                return self.respond(|msg_out| {
+                    msg_out.set_msg_code(MsgCode::SuccessValid);
+                    insert_options_with_etag(msg_out, &generated, Some(etag), |number| {
+                        number != OptionNumber::CONTENT_FORMAT
+                            && number != OptionNumber::BLOCK2
+                            && number != OptionNumber::SIZE2
+                    })
+                });
+            }
+        }
+
+        self.respond(|msg_out| {
+            msg_out.set_msg_code(generated.msg_code());
+            insert_options_with_etag(msg_out, &generated, etag, |_| true)?;
+            msg_out.append_payload_bytes(generated.payload())
+        })
+    }
+}
+
+/// Copies the options of `msg` for which `filter` returns true to `msg_out`, along with
+/// an `ETag` option with the value `etag`.
+fn insert_options_with_etag<F>(
+    msg_out: &mut dyn MessageWrite,
+    msg: &dyn MessageRead,
+    mut etag: Option<ETag>,
+    filter: F,
+) -> Result<(), Error>
+where
+    F: Fn(OptionNumber) -> bool,
+{
+    for result in msg.options() {
+        let (number, value) = result?;
+        if number > OptionNumber::ETAG {
+            if let Some(etag) = etag.take() {
+                msg_out.insert_option(option::ETAG, etag)?;
+            }
+        }
+        if filter(number) {
+            msg_out.insert_option_with_bytes(number, value)?;
+        }
+    }
+    if let Some(etag) = etag {
+        msg_out.insert_option(option::ETAG, etag)?;
+    }
+
+    Ok(())
+}
+
+/// Returns true if `request` is a `GET` or `FETCH` request with an `ETag` option matching
+/// `etag`, so the client already has the current representation.
+fn is_validated(request: &dyn MessageRead, etag: ETag) -> Result<bool, Error> {
+    if let MsgCode::MethodGet | MsgCode::MethodFetch = request.msg_code() {
+        let mut iter = request.options();
+        while let Some(request_etag) = iter.find_next_of(option::ETAG).transpose()? {
+            if request_etag == etag {
+                return Ok(true);
+            }
+        }
+    }
+
+    Ok(false)
+}
+
+fn method_name(code: MsgCode) -> Option<&'static str> {
+    Some(match code {
+        MsgCode::MethodGet => "GET",
+        MsgCode::MethodPost => "POST",
+        MsgCode::MethodPut => "PUT",
+        MsgCode::MethodDelete => "DELETE",
+        MsgCode::MethodFetch => "FETCH",
+        MsgCode::MethodPatch => "PATCH",
+        MsgCode::MethodIPatch => "iPATCH",
+        _ => return None,
+    })
 }
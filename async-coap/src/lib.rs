@@ -221,7 +221,7 @@
 //!
 //! [`send_as_stream`]: RemoteEndpointExt::send_as_stream
 //! [`Future`]: std::future::Future
-//! [`Stream`]: futures-preview::stream::Stream
+//! [`Stream`]: futures::stream::Stream
 //!
 //! ## Future Work
 //!
@@ -229,7 +229,6 @@
 //! and mechanisms that aren't yet implemented. Here is a short list:
 //!
 //! * Support for "effortless" serving of [observable resources][Observing]
-//! * Support for [Block1][Block transfer] transfers.
 //! * Improved support for [observing][Observing] remote resources.
 //! * Make serving resources easier-to-use.
 //! * [OSCORE](https://tools.ietf.org/html/draft-ietf-core-object-security) support.
@@ -249,6 +248,31 @@
 //! [GAT]: https://github.com/rust-lang/rust/issues/44265
 //! [`AllowStdUdpSocket`]: crate::datagram::AllowStdUdpSocket
 //!
+//! ## Cargo Features
+//!
+//! Parts of this crate that not every application needs can be disabled using cargo
+//! features. All of the following are enabled by default:
+//!
+//! * `client`: Send descriptors for making requests, like [`CoapRequest`].
+//! * `server`: Helpers for serving resources, like [`Block1Upload`], [`Block2Writer`],
+//!   [`VirtualHosts`], and [`EndpointStats::handle_request`](datagram::EndpointStats::handle_request).
+//! * `observe`: Support for observing resources ([IETF-RFC7641]). With `client`, this
+//!   enables [`CoapRequest::observe`]; with `server`, it enables
+//!   [`datagram::Observers`].
+//! * `block`: Block-wise transfers ([IETF-RFC7959]), including [`BlockReconstructor`].
+//! * `link-format`: Parsing and writing [CoRE link format][link_format] ([IETF-RFC6690]).
+//!
+//! The following features are disabled by default, since they pull in more dependencies:
+//!
+//! * `http-gateway`: Translating between CoAP and HTTP messages ([IETF-RFC8075]).
+//! * `websocket`: CoAP over WebSockets for the [`stream`] backend ([IETF-RFC8323]).
+//!
+//! [IETF-RFC7641]: https://tools.ietf.org/html/rfc7641
+//! [IETF-RFC7959]: https://tools.ietf.org/html/rfc7959
+//! [IETF-RFC6690]: https://tools.ietf.org/html/rfc6690
+//! [IETF-RFC8075]: https://tools.ietf.org/html/rfc8075
+//! [IETF-RFC8323]: https://tools.ietf.org/html/rfc8323
+//!
 //! ## Full Example
 //!
 //! ```
@@ -291,7 +315,7 @@
 //!
 //! // Create a future that sends a request to a specific path
 //! // on the remote endpoint, collecting any blocks in the response
-//! // and returning `Ok(OwnedImmutableMessage)` upon success.
+//! // and returning `Ok(Response)` upon success.
 //! let future_result = remote_endpoint.send_to(
 //!     rel_ref!("large"),
 //!     CoapRequest::get()                          // This is a CoAP GET request
@@ -340,9 +364,9 @@ pub mod uri {
 
     pub use async_coap_uri::{AnyUriRef, UriDisplay, UriType};
 
-    pub use async_coap_uri::{ParseError, ResolveError};
+    pub use async_coap_uri::{ParseError, ParseErrorKind, ResolveError, UriComponent};
 
-    pub use async_coap_uri::UriRawComponents;
+    pub use async_coap_uri::{UriHost, UriRawComponents};
 
     #[doc(hidden)]
     pub(super) use async_coap_uri::prelude;
@@ -358,11 +382,28 @@ pub mod send_desc;
 use send_desc::*;
 
 mod response_status;
-pub use response_status::ResponseStatus;
+pub use response_status::{ModifyRequest, ResponseStatus};
+
+mod response;
+pub use response::Response;
 
 mod content_format;
 pub use content_format::ContentFormat;
 
+#[cfg(feature = "http-gateway")]
+pub mod http_gateway;
+
+#[cfg(feature = "server")]
+pub mod coreconf;
+
+pub mod lwm2m;
+
+#[cfg(all(feature = "std", feature = "client", feature = "block"))]
+pub mod simple;
+
+mod forward;
+pub use forward::{is_hop_by_hop, write_forwarded_response, ForwardRequest};
+
 mod socketaddr;
 pub use socketaddr::SocketAddrExt;
 pub use socketaddr::ToSocketAddrs;
@@ -370,9 +411,46 @@ pub use socketaddr::ToSocketAddrs;
 mod block;
 pub use block::*;
 
+#[cfg(all(feature = "server", feature = "block"))]
+mod block_upload;
+#[cfg(all(feature = "server", feature = "block"))]
+pub use block_upload::*;
+
+#[cfg(all(feature = "server", feature = "block"))]
+mod block_writer;
+#[cfg(all(feature = "server", feature = "block"))]
+pub use block_writer::Block2Writer;
+
+#[cfg(feature = "server")]
+pub mod resource;
+
+#[cfg(feature = "server")]
+mod virtual_hosts;
+#[cfg(feature = "server")]
+pub use virtual_hosts::VirtualHosts;
+
+#[cfg(all(feature = "server", feature = "block", feature = "link-format"))]
+mod static_resources;
+#[cfg(all(feature = "server", feature = "block", feature = "link-format"))]
+pub use static_resources::StaticResources;
+
+#[cfg(feature = "server")]
+mod transcoder;
+#[cfg(feature = "server")]
+pub use transcoder::Transcoder;
+
 mod trans_params;
 pub use trans_params::*;
 
+mod traffic_class;
+pub use traffic_class::TrafficClass;
+
+mod random;
+pub use random::{NoJitter, RandomSource, SeededRandom, ThreadRandom};
+
+mod time;
+pub use time::*;
+
 mod local_endpoint;
 pub use local_endpoint::*;
 
@@ -382,6 +460,21 @@ pub use remote_endpoint::*;
 mod send_as_stream;
 pub use send_as_stream::*;
 
+mod poll_as_stream;
+pub use poll_as_stream::*;
+
+mod observe_seq;
+pub use observe_seq::ObserveSeq;
+
+mod observe_reconnect;
+pub use observe_reconnect::*;
+
+mod observe_cancel;
+pub use observe_cancel::*;
+
+mod cancellation;
+pub use cancellation::CancellationToken;
+
 mod receive_as_stream;
 pub use receive_as_stream::*;
 
@@ -398,16 +491,25 @@ pub use error::*;
 mod util;
 use util::*;
 
+#[cfg(feature = "link-format")]
 pub mod link_format;
+#[cfg(feature = "link-format")]
 #[doc(hidden)]
 pub use link_format::*;
 
 pub mod datagram;
 pub mod null;
+pub mod stream;
 
 mod etag;
 pub use etag::ETag;
 
+mod request_tag;
+pub use request_tag::RequestTag;
+
+mod cache_key;
+pub use cache_key::CacheKey;
+
 use futures::future::BoxFuture;
 use message::MessageRead;
 use message::MessageWrite;
@@ -424,6 +526,7 @@ pub mod prelude {
     pub use super::RemoteEndpoint;
     pub use super::RemoteEndpointExt;
 
+    #[cfg(feature = "client")]
     pub use super::send_desc::CoapRequest;
     pub use super::send_desc::SendDescExt;
     pub use super::send_desc::SendDescMulticast;
@@ -28,17 +28,19 @@ use std::sync::Arc;
 /// `async-coap` comes with two: [`NullLocalEndpoint`] and [`DatagramLocalEndpoint`].
 ///
 /// [`NullLocalEndpoint`] does what you might expect: nothing. Attempts to send
-/// requests always results in [`Error::ResponseTimeout`] and [`LocalEndpoint::receive`]
-/// will block indefinitely. Creating an instance of it is quite straightforward:
+/// requests always results in [`Error::ResponseTimeout`] (unless it was given a
+/// [scripted responder]) and [`LocalEndpoint::receive`] will block indefinitely.
+/// Creating an instance of it is quite straightforward:
 ///
 /// [`NullLocalEndpoint`]: crate::null::NullLocalEndpoint
+/// [scripted responder]: crate::null::NullLocalEndpoint::with_responder
 /// [`DatagramLocalEndpoint`]: crate::datagram::DatagramLocalEndpoint
 ///
 /// ```
 /// use std::sync::Arc;
 /// use async_coap::null::NullLocalEndpoint;
 ///
-/// let local_endpoint = Arc::new(NullLocalEndpoint);
+/// let local_endpoint = Arc::new(NullLocalEndpoint::new());
 /// ```
 ///
 /// If you want to do something more useful, then [`DatagramLocalEndpoint`] is likely
@@ -75,7 +77,7 @@ use std::sync::Arc;
 /// # use async_coap::datagram::{DatagramLocalEndpoint, AllowStdUdpSocket, LoopbackSocket};
 /// # use async_coap::null::NullLocalEndpoint;
 /// #
-/// # let local_endpoint = Arc::new(NullLocalEndpoint);
+/// # let local_endpoint = Arc::new(NullLocalEndpoint::new());
 /// #
 /// use futures::{prelude::*,executor::ThreadPool,task::Spawn,task::SpawnExt};
 ///
@@ -101,7 +103,7 @@ use std::sync::Arc;
 /// # use async_coap::null::NullLocalEndpoint;
 /// #
 /// # // Using a NullLocalEndpoint since this is just a simple usage example.
-/// # let local_endpoint = Arc::new(NullLocalEndpoint);
+/// # let local_endpoint = Arc::new(NullLocalEndpoint::new());
 /// # let mut local_pool = LocalPool::new();
 /// #
 /// # local_pool.spawner().spawn_local(local_endpoint
@@ -131,7 +133,7 @@ use std::sync::Arc;
 /// # use async_coap::null::NullLocalEndpoint;
 /// #
 /// # // Using a NullLocalEndpoint since this is just a simple usage example.
-/// # let local_endpoint = Arc::new(NullLocalEndpoint);
+/// # let local_endpoint = Arc::new(NullLocalEndpoint::new());
 /// # let mut pool = LocalPool::new();
 /// #
 /// # pool.spawner().spawn_local(local_endpoint
@@ -483,30 +485,75 @@ pub trait LocalEndpointExt: LocalEndpoint {
     /// The stream can be cleanly ended by the handler eventually returning
     /// [`Error::ResponseTimeout`] or [`Error::Cancelled`], neither of which will be emitted
     /// as an error.
+    ///
+    /// Up to [`StreamBuffer::DEFAULT_DEPTH`] results are buffered until they are taken from
+    /// the stream, which fails with [`Error::OutOfSpace`] if more arrive. Use
+    /// [`send_as_stream_with_buffer`](LocalEndpointExt::send_as_stream_with_buffer) to
+    /// change this.
     fn send_as_stream<'a, S, R, SD>(&'a self, dest: S, send_desc: SD) -> SendAsStream<'a, R>
     where
         S: ToSocketAddrs<SocketAddr = Self::SocketAddr, Error = Self::SocketError> + 'a,
         SD: SendDesc<Self::InboundContext, R> + 'a,
         R: Send + 'a,
     {
-        let (sender, receiver) = futures::channel::mpsc::channel::<Result<R, Error>>(10);
+        self.send_as_stream_with_buffer(dest, send_desc, StreamBuffer::default())
+    }
+
+    /// Version of [`send_as_stream`](LocalEndpointExt::send_as_stream) which buffers the
+    /// results as described by `buffer`, which determines how many results are buffered
+    /// and what happens when a result arrives while the buffer is full.
+    fn send_as_stream_with_buffer<'a, S, R, SD>(
+        &'a self,
+        dest: S,
+        send_desc: SD,
+        buffer: StreamBuffer,
+    ) -> SendAsStream<'a, R>
+    where
+        S: ToSocketAddrs<SocketAddr = Self::SocketAddr, Error = Self::SocketError> + 'a,
+        SD: SendDesc<Self::InboundContext, R> + 'a,
+        R: Send + 'a,
+    {
+        SendAsStream::new(send_desc, buffer, |send_desc| self.send(dest, send_desc))
+    }
 
-        SendAsStream {
-            receiver,
-            send_future: self.send(dest, SendAsStreamDesc::new(send_desc, sender)),
-        }
+    /// Version of [`LocalEndpoint::send`] which can be explicitly cancelled using `token`.
+    ///
+    /// Once `token` is cancelled, the returned future resolves to [`Error::Cancelled`] the
+    /// next time it is polled, whether or not a result was available. The transaction is
+    /// ended the same way as if the future had been dropped: retransmissions stop,
+    /// responses are no longer handled, and block transfers that are in progress are
+    /// abandoned without requesting any more blocks. If `token` was cancelled before the
+    /// future is first polled, nothing is sent at all.
+    ///
+    /// Use [`CancelableObservation::with_cancellation_token`] to cancel observations, so
+    /// that they are deregistered properly.
+    ///
+    /// [`CancelableObservation::with_cancellation_token`]: crate::CancelableObservation::with_cancellation_token
+    fn send_with_cancel<'a, S, R, SD>(
+        &'a self,
+        dest: S,
+        send_desc: SD,
+        token: CancellationToken,
+    ) -> BoxFuture<'a, Result<R, Error>>
+    where
+        S: ToSocketAddrs<SocketAddr = Self::SocketAddr, Error = Self::SocketError> + 'a,
+        SD: SendDesc<Self::InboundContext, R> + 'a,
+        R: Send + 'a,
+    {
+        token.guard(self.send(dest, send_desc))
     }
 
     /// Version of [`LocalEndpoint::receive`] that handles more than one inbound message,
     /// returning a [`crate::ReceiveAsStream`] instead of a future.
     ///
-    /// This stream will terminate immediately after any of the following errors are emitted by the
-    /// underlying calls to [`LocalEndpoint::receive`]:
+    /// By default, this stream will terminate immediately after any of the following errors are
+    /// emitted by the underlying calls to [`LocalEndpoint::receive`]:
     ///
     /// * [`Error::IOError`](enum_Error.html#variant.IOError)
     /// * [`Error::Cancelled`](enum_Error.html#variant.Cancelled)
     ///
-    /// All other errors are ignored.
+    /// All other errors are ignored. Which errors are fatal can be changed using
+    /// [`ReceiveAsStream::with_error_policy`](crate::ReceiveAsStream::with_error_policy).
     fn receive_as_stream<'a, F>(&'a self, handler: F) -> ReceiveAsStream<'a, Self, F>
     where
         F: FnMut(&Self::RespondableInboundContext) -> Result<(), Error> + 'a + Clone + Unpin + Send,
@@ -543,7 +590,7 @@ pub trait LocalEndpointExt: LocalEndpoint {
     /// # use futures::executor::ThreadPool;
     /// # use futures::task::SpawnExt;
     ///
-    /// let local_endpoint = Arc::new(NullLocalEndpoint);
+    /// let local_endpoint = Arc::new(NullLocalEndpoint::new());
     /// let mut pool = ThreadPool::new().expect("Unable to start thread pool");
     ///
     /// pool.spawn(local_endpoint
@@ -0,0 +1,579 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Helpers for OMA Lightweight M2M (LwM2M).
+//!
+//! LwM2M addresses the data of a device using numeric paths of the form
+//! `/{object}/{instance}/{resource}/{resource-instance}`, which are represented by
+//! [`ObjectPath`]. A device registers the objects it implements with an LwM2M server
+//! using the registration interface, which is a profile of the CoRE Resource Directory
+//! ([IETF-RFC9176]). When the `client` feature is enabled, [`Registration`], [`update`],
+//! and [`deregister`] implement the client side of that interface:
+//!
+//! ```
+//! # use async_coap::prelude::*;
+//! # use async_coap::{RemoteEndpoint, Error};
+//! # use async_coap::lwm2m::{self, ObjectPath, Registration};
+//! # async fn run<RE: RemoteEndpoint>(server: RE) -> Result<(), Error> {
+//! let registration = Registration::new("urn:dev:ops:0024E8-sensor-123")
+//!     .with_lifetime(300)
+//!     .with_object("/1/0".parse()?)
+//!     .with_object("/3/0".parse()?);
+//!
+//! let location = registration.register(&server).await?;
+//!
+//! // Before the lifetime expires:
+//! lwm2m::update(&server, &location).await?;
+//!
+//! // When shutting down:
+//! lwm2m::deregister(&server, &location).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [IETF-RFC9176]: https://tools.ietf.org/html/rfc9176
+
+use super::*;
+use core::fmt::{self, Display, Formatter};
+use core::str::FromStr;
+
+/// A path identifying an LwM2M object, object instance, resource, or resource instance,
+/// like `/3/0/1`.
+///
+/// Paths are ordered so that a path sorts before the paths it [contains](Self::contains).
+///
+/// ```
+/// # use async_coap::lwm2m::ObjectPath;
+/// let path: ObjectPath = "/3/0/1".parse()?;
+/// assert_eq!(3, path.object_id());
+/// assert_eq!(Some(0), path.instance());
+/// assert_eq!(Some(1), path.resource());
+/// assert_eq!(None, path.resource_instance());
+/// assert_eq!("/3/0", path.parent().unwrap().to_string());
+/// # Ok::<(), async_coap::Error>(())
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct ObjectPath {
+    // Ids past `depth` are always zero, so that the derived traits are consistent.
+    ids: [u16; 4],
+    depth: u8,
+}
+
+impl ObjectPath {
+    /// The largest valid id. The id `65535` is reserved.
+    pub const MAX_ID: u16 = 65534;
+
+    /// Creates a path from its ids, starting with the object id.
+    ///
+    /// Returns `None` if `ids` is empty, has more than four ids, or contains an id
+    /// larger than [`ObjectPath::MAX_ID`].
+    pub fn new(ids: &[u16]) -> Option<ObjectPath> {
+        let mut ret = ObjectPath::object(*ids.first()?)?;
+
+        for id in &ids[1..] {
+            ret = ret.child(*id)?;
+        }
+
+        Some(ret)
+    }
+
+    /// Creates the path of the object with the given id.
+    pub fn object(object: u16) -> Option<ObjectPath> {
+        if object > Self::MAX_ID {
+            return None;
+        }
+
+        Some(ObjectPath {
+            ids: [object, 0, 0, 0],
+            depth: 1,
+        })
+    }
+
+    /// Parses a path from its unescaped segments, like the `Uri-Path` options of a request.
+    ///
+    /// Returns [`Error::ParseFailure`] if any segment isn't a valid id. Ids must not have
+    /// leading zeros, so that every path has only one representation.
+    pub fn from_segments<I, S>(segments: I) -> Result<ObjectPath, Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut ret: Option<ObjectPath> = None;
+
+        for segment in segments {
+            let id = parse_id(segment.as_ref()).ok_or(Error::ParseFailure)?;
+            ret = match ret {
+                None => ObjectPath::object(id),
+                Some(path) => path.child(id),
+            };
+            if ret.is_none() {
+                return Err(Error::ParseFailure);
+            }
+        }
+
+        ret.ok_or(Error::ParseFailure)
+    }
+
+    /// Returns the ids of this path, starting with the object id.
+    pub fn ids(&self) -> &[u16] {
+        &self.ids[..self.depth as usize]
+    }
+
+    /// Returns the number of ids in this path, from 1 for an object to 4 for a resource
+    /// instance.
+    pub fn depth(&self) -> usize {
+        self.depth as usize
+    }
+
+    /// Returns the object id.
+    pub fn object_id(&self) -> u16 {
+        self.ids[0]
+    }
+
+    /// Returns the object instance id, if this path has one.
+    pub fn instance(&self) -> Option<u16> {
+        self.ids().get(1).copied()
+    }
+
+    /// Returns the resource id, if this path has one.
+    pub fn resource(&self) -> Option<u16> {
+        self.ids().get(2).copied()
+    }
+
+    /// Returns the resource instance id, if this path has one.
+    pub fn resource_instance(&self) -> Option<u16> {
+        self.ids().get(3).copied()
+    }
+
+    /// Returns the path one level up from this one, or `None` if this is the path of
+    /// an object.
+    pub fn parent(&self) -> Option<ObjectPath> {
+        if self.depth == 1 {
+            return None;
+        }
+
+        let mut ret = *self;
+        ret.depth -= 1;
+        ret.ids[ret.depth as usize] = 0;
+        Some(ret)
+    }
+
+    /// Returns the path of the child of this path with the given id, or `None` if this is
+    /// the path of a resource instance or `id` is larger than [`ObjectPath::MAX_ID`].
+    pub fn child(&self, id: u16) -> Option<ObjectPath> {
+        if self.depth == 4 || id > Self::MAX_ID {
+            return None;
+        }
+
+        let mut ret = *self;
+        ret.ids[ret.depth as usize] = id;
+        ret.depth += 1;
+        Some(ret)
+    }
+
+    /// Returns true if `other` is this path or one of its descendants.
+    pub fn contains(&self, other: &ObjectPath) -> bool {
+        other.ids().starts_with(self.ids())
+    }
+}
+
+/// Parses a canonical decimal id.
+fn parse_id(segment: &str) -> Option<u16> {
+    if segment.is_empty()
+        || (segment.len() > 1 && segment.starts_with('0'))
+        || !segment.bytes().all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+
+    segment.parse().ok()
+}
+
+impl Display for ObjectPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for id in self.ids() {
+            write!(f, "/{}", id)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for ObjectPath {
+    type Err = Error;
+
+    /// Parses a path like `/3/0/1`. The leading slash is optional.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.strip_prefix('/').unwrap_or(s);
+        ObjectPath::from_segments(s.split('/'))
+    }
+}
+
+impl From<ObjectPath> for RelRefBuf {
+    fn from(path: ObjectPath) -> Self {
+        RelRefBuf::from_string(path.to_string()).expect("Object path was malformed")
+    }
+}
+
+#[cfg(feature = "client")]
+mod registration {
+    use super::*;
+    use crate::option::rel_ref_from_parts;
+    use futures::future::BoxFuture;
+
+    /// The parameters that a device registers with an LwM2M server.
+    ///
+    /// The server is sent a `POST` request to `/rd`, with the parameters as query items
+    /// and the objects and object instances as a link-format payload. The server responds
+    /// with the location of the registration, which is used to [`update`] or
+    /// [`deregister`] it.
+    #[derive(Debug, Clone, Eq, PartialEq)]
+    pub struct Registration {
+        endpoint_name: String,
+        lifetime: Option<u32>,
+        version: String,
+        binding: Option<String>,
+        objects: Vec<ObjectPath>,
+    }
+
+    impl Registration {
+        /// Creates the parameters for registering the device named `endpoint_name`,
+        /// using LwM2M version 1.1.
+        pub fn new<S: Into<String>>(endpoint_name: S) -> Registration {
+            Registration {
+                endpoint_name: endpoint_name.into(),
+                lifetime: None,
+                version: "1.1".to_string(),
+                binding: None,
+                objects: Vec::new(),
+            }
+        }
+
+        /// Sets the lifetime of the registration, in seconds. If not set, the server
+        /// uses a lifetime of 86400 seconds.
+        pub fn with_lifetime(mut self, lifetime: u32) -> Registration {
+            self.lifetime = Some(lifetime);
+            self
+        }
+
+        /// Sets the LwM2M version that the device implements.
+        pub fn with_version<S: Into<String>>(mut self, version: S) -> Registration {
+            self.version = version.into();
+            self
+        }
+
+        /// Sets the binding mode of the device, like `U` for UDP.
+        pub fn with_binding<S: Into<String>>(mut self, binding: S) -> Registration {
+            self.binding = Some(binding.into());
+            self
+        }
+
+        /// Adds an object or object instance implemented by the device.
+        pub fn with_object(mut self, path: ObjectPath) -> Registration {
+            self.objects.push(path);
+            self
+        }
+
+        /// Returns the endpoint name of the device.
+        pub fn endpoint_name(&self) -> &str {
+            &self.endpoint_name
+        }
+
+        /// Returns the objects and object instances implemented by the device.
+        pub fn objects(&self) -> &[ObjectPath] {
+            &self.objects
+        }
+
+        /// Registers the device with the LwM2M server at `remote_endpoint`, returning the
+        /// location of the registration.
+        ///
+        /// Fails with [`Error::BadResponse`] if the response doesn't include a location.
+        pub fn register<'a, RE: RemoteEndpoint>(
+            &self,
+            remote_endpoint: &'a RE,
+        ) -> BoxFuture<'a, Result<RelRefBuf, Error>> {
+            let mut query = vec![
+                format!("ep={}", self.endpoint_name),
+                format!("lwm2m={}", self.version),
+            ];
+            query.extend(self.update_query());
+
+            self.send(remote_endpoint, rel_ref!("rd"), query)
+                .map(|ret| {
+                    let location = ret?.message().options().extract_location()?;
+                    if location.is_empty() {
+                        Err(Error::BadResponse)
+                    } else {
+                        Ok(location)
+                    }
+                })
+                .boxed()
+        }
+
+        /// Updates the registration at `location` with the lifetime, binding mode, and
+        /// objects of this registration. The endpoint name and version can't be changed.
+        pub fn update<'a, RE: RemoteEndpoint>(
+            &self,
+            remote_endpoint: &'a RE,
+            location: &RelRef,
+        ) -> BoxFuture<'a, Result<(), Error>> {
+            self.send(remote_endpoint, location, self.update_query())
+                .map(|ret| ret.map(|_| ()))
+                .boxed()
+        }
+
+        fn update_query(&self) -> Vec<String> {
+            let mut query = Vec::new();
+            if let Some(lifetime) = self.lifetime {
+                query.push(format!("lt={}", lifetime));
+            }
+            if let Some(binding) = self.binding.as_ref() {
+                query.push(format!("b={}", binding));
+            }
+            query
+        }
+
+        fn send<'a, RE: RemoteEndpoint>(
+            &self,
+            remote_endpoint: &'a RE,
+            path: &RelRef,
+            query: Vec<String>,
+        ) -> BoxFuture<'a, Result<Response<RE::SocketAddr>, Error>> {
+            let path = match absolute_path(path, query) {
+                Ok(path) => path,
+                Err(e) => return futures::future::ready(Err(e)).boxed(),
+            };
+
+            let links = self
+                .objects
+                .iter()
+                .map(|path| format!("<{}>", path))
+                .collect::<Vec<_>>()
+                .join(",");
+
+            let request = CoapRequest::post()
+                .content_format(ContentFormat::APPLICATION_LINK_FORMAT)
+                .payload_writer(move |msg_out| {
+                    msg_out.set_msg_code(MsgCode::MethodPost);
+                    msg_out.append_payload_string(&links)
+                })
+                .emit_successful_response();
+
+            remote_endpoint.send_to(path, request)
+        }
+    }
+
+    /// Updates the registration at `location` without changing any of its parameters,
+    /// which keeps it from expiring.
+    pub fn update<'a, RE: RemoteEndpoint>(
+        remote_endpoint: &'a RE,
+        location: &RelRef,
+    ) -> BoxFuture<'a, Result<(), Error>> {
+        match absolute_path(location, Vec::new()) {
+            Ok(path) => remote_endpoint.send_to(path, CoapRequest::post()),
+            Err(e) => futures::future::ready(Err(e)).boxed(),
+        }
+    }
+
+    /// Removes the registration at `location` from the server.
+    pub fn deregister<'a, RE: RemoteEndpoint>(
+        remote_endpoint: &'a RE,
+        location: &RelRef,
+    ) -> BoxFuture<'a, Result<(), Error>> {
+        match absolute_path(location, Vec::new()) {
+            Ok(path) => remote_endpoint.send_to(path, CoapRequest::delete()),
+            Err(e) => futures::future::ready(Err(e)).boxed(),
+        }
+    }
+
+    /// Returns the path of `path` relative to the root of the server rather than to the
+    /// path of the remote endpoint, with the given query items.
+    ///
+    /// Registration locations are returned as `Location-Path` options, which are always
+    /// relative to the root, so the registration interface is placed there as well.
+    fn absolute_path(path: &RelRef, query: Vec<String>) -> Result<RelRefBuf, Error> {
+        let segments = path.path_segments().collect::<Vec<_>>();
+        let path = rel_ref_from_parts(
+            segments.iter().map(|segment| Ok(segment.as_ref())),
+            query.iter().map(|item| Ok(item.as_str())),
+        )?;
+        Ok(rel_ref!("/").resolved_rel_ref(path))
+    }
+}
+
+#[cfg(feature = "client")]
+pub use registration::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn object_path() {
+        let path: ObjectPath = "/3/0/1".parse().unwrap();
+        assert_eq!(&[3, 0, 1], path.ids());
+        assert_eq!(3, path.depth());
+        assert_eq!("/3/0/1", path.to_string());
+        assert_eq!(Ok(path), "3/0/1".parse());
+        assert_eq!(Some(path), ObjectPath::new(&[3, 0, 1]));
+        assert_eq!(
+            Ok(path),
+            ObjectPath::from_segments(vec!["3".to_string(), "0".to_string(), "1".to_string()])
+        );
+        assert_eq!(rel_ref!("/3/0/1"), RelRefBuf::from(path).as_rel_ref());
+
+        let object = ObjectPath::object(3).unwrap();
+        assert_eq!(Some(object), path.parent().unwrap().parent());
+        assert_eq!(None, object.parent());
+        assert_eq!(Some(path), object.child(0).unwrap().child(1));
+        assert_eq!(None, path.child(0).unwrap().child(0));
+
+        assert!(object.contains(&path));
+        assert!(path.contains(&path));
+        assert!(!path.contains(&object));
+        assert!(!object.contains(&"/33/0".parse().unwrap()));
+
+        let mut paths: Vec<ObjectPath> = ["/3/1", "/3/0/1", "/3", "/1/0", "/3/0"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        paths.sort();
+        assert_eq!(
+            vec!["/1/0", "/3", "/3/0", "/3/0/1", "/3/1"],
+            paths.iter().map(ToString::to_string).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn object_path_invalid() {
+        for s in &[
+            "",
+            "/",
+            "/3/",
+            "//3",
+            "/3//0",
+            "/03",
+            "/+3",
+            "/3/x",
+            "/65535",
+            "/1/2/3/4/5",
+        ] {
+            assert_eq!(Err(Error::ParseFailure), s.parse::<ObjectPath>(), "{:?}", s);
+        }
+
+        assert_eq!(None, ObjectPath::new(&[]));
+        assert_eq!(None, ObjectPath::new(&[3, 65535]));
+        assert_eq!(Ok(0), "/0".parse().map(|path: ObjectPath| path.object_id()));
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn registration() {
+        use crate::datagram::{
+            DatagramLocalEndpoint, DatagramRespondableInboundContext, LoopbackSocket,
+            LoopbackSocketAddr,
+        };
+        use crate::option::RequestOptions;
+        use futures::executor::block_on;
+        use futures::future::{select, Either};
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Mutex;
+
+        let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+        let remote_endpoint = local_endpoint.remote_endpoint(
+            LoopbackSocketAddr::Unicast,
+            None::<String>,
+            rel_ref!("ignored/"),
+        );
+        let requests = Mutex::new(Vec::new());
+        let registered = AtomicBool::new(false);
+
+        let handler = |context: &DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+            let msg = context.message();
+            let options = RequestOptions::parse(msg.options())?;
+            requests.lock().unwrap().push((
+                msg.msg_code(),
+                options.rel_ref().to_string(),
+                msg.payload_as_str().unwrap_or("").to_string(),
+            ));
+
+            match (msg.msg_code(), options.uri_path.join("/").as_str()) {
+                (MsgCode::MethodPost, "rd") => context.respond(|msg_out| {
+                    registered.store(true, Ordering::Relaxed);
+                    msg_out.set_msg_code(MsgCode::SuccessCreated);
+                    msg_out.insert_option(option::LOCATION_PATH, "rd")?;
+                    msg_out.insert_option(option::LOCATION_PATH, "5a3f")
+                }),
+                (MsgCode::MethodPost, "rd/5a3f") if registered.load(Ordering::Relaxed) => context
+                    .respond(|msg_out| {
+                        msg_out.set_msg_code(MsgCode::SuccessChanged);
+                        Ok(())
+                    }),
+                (MsgCode::MethodDelete, "rd/5a3f") => context.respond(|msg_out| {
+                    registered.store(false, Ordering::Relaxed);
+                    msg_out.set_msg_code(MsgCode::SuccessDeleted);
+                    Ok(())
+                }),
+                _ => context.respond_not_found(),
+            }
+        };
+
+        let registration = Registration::new("node&1")
+            .with_lifetime(300)
+            .with_binding("U")
+            .with_object("/1/0".parse().unwrap())
+            .with_object("/3/0".parse().unwrap());
+
+        let future = async {
+            let location = registration.register(&remote_endpoint).await?;
+            assert_eq!(rel_ref!("rd/5a3f"), location.as_rel_ref());
+            registration.update(&remote_endpoint, &location).await?;
+            update(&remote_endpoint, &location).await?;
+            deregister(&remote_endpoint, &location).await?;
+            assert_eq!(
+                Err(Error::ResourceNotFound),
+                update(&remote_endpoint, &location).await
+            );
+            Ok::<_, Error>(())
+        };
+
+        let (ret, _) = match block_on(select(
+            Box::pin(future),
+            local_endpoint.receive_loop(handler),
+        )) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left(ret) => ret,
+        };
+        ret.unwrap();
+
+        assert_eq!(
+            vec![
+                (
+                    MsgCode::MethodPost,
+                    "rd?ep=node%261&lwm2m=1.1&lt=300&b=U".to_string(),
+                    "</1/0>,</3/0>".to_string()
+                ),
+                (
+                    MsgCode::MethodPost,
+                    "rd/5a3f?lt=300&b=U".to_string(),
+                    "</1/0>,</3/0>".to_string()
+                ),
+                (MsgCode::MethodPost, "rd/5a3f".to_string(), String::new()),
+                (MsgCode::MethodDelete, "rd/5a3f".to_string(), String::new()),
+                (MsgCode::MethodPost, "rd/5a3f".to_string(), String::new()),
+            ],
+            *requests.lock().unwrap()
+        );
+    }
+}
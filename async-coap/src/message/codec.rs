@@ -207,10 +207,12 @@ fn insert_split_helper(
 
         let (next_key, next_value) = iter
             .next()
-            .expect(&format!(
-                "Unexpected end of options (prev: {}, iter: {:?})",
-                prev_option_key, iter
-            ))
+            .unwrap_or_else(|| {
+                panic!(
+                    "Unexpected end of options (prev: {}, iter: {:?})",
+                    prev_option_key, iter
+                )
+            })
             .expect("Wrote corrupt options");
 
         if next_key > key {
@@ -14,19 +14,149 @@
 //
 
 use super::*;
+use crate::option::{push_path_segment, push_query_item};
 use core::fmt::{Display, Formatter};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+static FULL_MESSAGE_DUMPS: AtomicBool = AtomicBool::new(false);
+
+/// Enables or disables full message dumps when formatting messages for display.
+///
+/// Payloads and query values may contain sensitive information, so by default they are
+/// redacted by [`MessageDisplay`] and [`CoapByteDisplayFormatter`] (and thus in all of the
+/// messages logged by this crate), leaving only their lengths. Calling this method with
+/// `true` causes them to be written out in full. This setting is global.
+pub fn set_full_message_dumps(enabled: bool) {
+    FULL_MESSAGE_DUMPS.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns true if full message dumps have been enabled with [`set_full_message_dumps`].
+pub fn full_message_dumps() -> bool {
+    FULL_MESSAGE_DUMPS.load(Ordering::Relaxed)
+}
+
+/// Appends the query item `value` to `buf`, with everything after the first `=` (or the
+/// entire item if there is no `=`) replaced with its length.
+fn push_redacted_query_item(buf: &mut String, value: &[u8]) {
+    match value.iter().position(|&b| b == b'=') {
+        Some(i) => {
+            push_query_item(buf, &String::from_utf8_lossy(&value[..i]));
+            buf.push_str(&format!("=<{} bytes>", value.len() - i - 1));
+        }
+        None => buf.push_str(&format!("<{} bytes>", value.len())),
+    }
+}
+
+/// Writes out the URI reassembled from the `Uri-Host`, `Uri-Port`, `Uri-Path`, and
+/// `Uri-Query` options of `msg`, or from the `Location-Path` and `Location-Query` options
+/// if `location` is true. Query values are redacted unless `full` is true.
+fn fmt_uri_options<T: MessageRead + ?Sized>(
+    f: &mut Formatter<'_>,
+    msg: &T,
+    location: bool,
+    full: bool,
+) -> core::fmt::Result {
+    let (name, path, query) = if location {
+        (
+            "Location",
+            OptionNumber::LOCATION_PATH,
+            OptionNumber::LOCATION_QUERY,
+        )
+    } else {
+        ("Uri", OptionNumber::URI_PATH, OptionNumber::URI_QUERY)
+    };
+
+    let mut buf = String::new();
+    let mut query_separator = '?';
+
+    // Malformed options are reported by the caller, so we just stop at the first one.
+    for (number, value) in msg.options().map_while(Result::ok) {
+        match number {
+            OptionNumber::URI_HOST if !location => {
+                let host = String::from_utf8_lossy(value);
+                buf.push_str("//");
+                if host.parse::<std::net::Ipv6Addr>().is_ok() {
+                    buf.push('[');
+                    buf.push_str(&host);
+                    buf.push(']');
+                } else {
+                    buf.extend(host.escape_uri().for_authority());
+                }
+            }
+            OptionNumber::URI_PORT if !location => {
+                if buf.is_empty() {
+                    buf.push_str("//");
+                }
+                match try_decode_u16(value) {
+                    Some(port) => buf.push_str(&format!(":{}", port)),
+                    None => buf.push_str(":ERR"),
+                }
+            }
+            number if number == path => {
+                buf.push('/');
+                push_path_segment(&mut buf, &String::from_utf8_lossy(value));
+            }
+            number if number == query => {
+                buf.push(query_separator);
+                query_separator = '&';
+                if full {
+                    push_query_item(&mut buf, &String::from_utf8_lossy(value));
+                } else {
+                    push_redacted_query_item(&mut buf, value);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    write!(f, " {}:{}", name, buf)
+}
+
+/// Writes out the name and value of an option, redacting the query in `Proxy-Uri`.
+fn fmt_option_redacted(
+    f: &mut Formatter<'_>,
+    number: OptionNumber,
+    value: &[u8],
+) -> core::fmt::Result {
+    match number {
+        OptionNumber::PROXY_URI => match value.iter().position(|&b| b == b'?') {
+            Some(i) => {
+                write!(f, "{}:{:?}", number, String::from_utf8_lossy(&value[..i]))?;
+                write!(f, "?<{} bytes>", value.len() - i - 1)
+            }
+            None => number.fmt_with_value(f, value),
+        },
+        _ => number.fmt_with_value(f, value),
+    }
+}
 
 /// Provides an implementation of [`core::fmt::Debug`] and [`core::fmt::Display`] for
 /// any type implementing [`MessageRead`].
+///
+/// Options are written out by name along with their decoded values: block options are
+/// written as `NUM/M/SIZE`, content formats are written by name, and the `Uri-*` and
+/// `Location-*` options are reassembled into single `Uri` and `Location` items. For
+/// example:
+///
+/// ```text
+/// <Con GET MID:1234 TOK:[5A0F] Uri://example.com/sensors/temp?unit=<1 bytes> Block2:0/0/64>
+/// ```
+///
+/// Unless [full message dumps][set_full_message_dumps] have been enabled, the payload
+/// and the query values in the `Uri-Query`, `Location-Query`, and `Proxy-Uri` options are
+/// redacted, leaving only their lengths.
 #[derive(Debug)]
 pub struct MessageDisplay<'a, T: MessageRead + ?Sized>(pub &'a T);
 
-impl<'a, T: MessageRead + ?Sized> Display for MessageDisplay<'a, T> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+impl<'a, T: MessageRead + ?Sized> MessageDisplay<'a, T> {
+    /// Writes out the message, which is only redacted if `full` is false.
+    fn fmt_with(&self, f: &mut Formatter<'_>, full: bool) -> core::fmt::Result {
         write!(f, "<{:?} {:?}", self.0.msg_type(), self.0.msg_code())?;
         write!(f, " MID:{:04X}", self.0.msg_id())?;
 
         let mut content_format: Option<u16> = None;
+        let mut wrote_uri = false;
+        let mut wrote_location = false;
 
         let token = self.0.msg_token();
         if !token.is_empty() {
@@ -35,19 +165,43 @@ impl<'a, T: MessageRead + ?Sized> Display for MessageDisplay<'a, T> {
 
         for option in self.0.options() {
             match option {
+                Ok((
+                    OptionNumber::URI_HOST
+                    | OptionNumber::URI_PORT
+                    | OptionNumber::URI_PATH
+                    | OptionNumber::URI_QUERY,
+                    _,
+                )) => {
+                    if !wrote_uri {
+                        fmt_uri_options(f, self.0, false, full)?;
+                        wrote_uri = true;
+                    }
+                }
+                Ok((OptionNumber::LOCATION_PATH | OptionNumber::LOCATION_QUERY, _)) => {
+                    if !wrote_location {
+                        fmt_uri_options(f, self.0, true, full)?;
+                        wrote_location = true;
+                    }
+                }
                 Ok((number, bytes)) => {
                     if number == OptionNumber::CONTENT_FORMAT {
                         content_format = try_decode_u16(bytes);
                     }
                     f.write_str(" ")?;
-                    number.fmt_with_value(f, bytes)?;
+                    if full {
+                        number.fmt_with_value(f, bytes)?;
+                    } else {
+                        fmt_option_redacted(f, number, bytes)?;
+                    }
                 }
                 Err(e) => return write!(f, " ERR:{:?}>", e),
             }
         }
 
         let payload = self.0.payload();
-        if !payload.is_empty() {
+        if !payload.is_empty() && !full {
+            write!(f, " <{} bytes>", payload.len())?;
+        } else if !payload.is_empty() {
             let payload_str_opt = if let Some(i) = content_format {
                 if ContentFormat(i).is_utf8() {
                     std::str::from_utf8(payload).ok()
@@ -69,31 +223,135 @@ impl<'a, T: MessageRead + ?Sized> Display for MessageDisplay<'a, T> {
     }
 }
 
+impl<'a, T: MessageRead + ?Sized> Display for MessageDisplay<'a, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        self.fmt_with(f, full_message_dumps())
+    }
+}
+
 /// Helper struct for formatting a CoAP buffer for display.
+///
+/// The message is redacted in the same way as [`MessageDisplay`], and the raw bytes
+/// are only included in the [`Debug`](core::fmt::Debug) output when
+/// [full message dumps][set_full_message_dumps] have been enabled.
 #[derive(Copy, Clone)]
 pub struct CoapByteDisplayFormatter<'buf>(pub &'buf [u8]);
 
+impl<'buf> CoapByteDisplayFormatter<'buf> {
+    fn fmt_corrupted(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if full_message_dumps() {
+            write!(f, "<CORRUPTED {:02x?}>", self.0)
+        } else {
+            write!(f, "<CORRUPTED {} bytes>", self.0.len())
+        }
+    }
+}
+
 impl<'buf> std::fmt::Display for CoapByteDisplayFormatter<'buf> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if let Ok(x) = StandardMessageParser::new(self.0) {
             MessageDisplay(&x).fmt(f)
         } else {
-            write!(f, "<CORRUPTED {:02x?}>", self.0)
+            self.fmt_corrupted(f)
         }
     }
 }
 
 impl<'buf> std::fmt::Debug for CoapByteDisplayFormatter<'buf> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if let Ok(x) = StandardMessageParser::new(self.0) {
-            write!(
+        match StandardMessageParser::new(self.0) {
+            Ok(x) if full_message_dumps() => write!(
                 f,
                 "CoapByteDisplayFormatter({}, {:02x?})",
                 MessageDisplay(&x),
                 self.0
-            )
-        } else {
-            write!(f, "<CORRUPTED {:02x?}>", self.0)
+            ),
+            Ok(x) => write!(f, "CoapByteDisplayFormatter({})", MessageDisplay(&x)),
+            Err(_) => self.fmt_corrupted(f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Formats messages in full, without depending on the global setting, which would
+    /// affect other tests running at the same time.
+    struct FullMessageDisplay<'a, T: MessageRead + ?Sized>(&'a T);
+
+    impl<'a, T: MessageRead + ?Sized> Display for FullMessageDisplay<'a, T> {
+        fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+            MessageDisplay(self.0).fmt_with(f, true)
         }
     }
+
+    #[test]
+    fn redaction() {
+        let mut encoder = VecMessageEncoder::new();
+        encoder.set_msg_code(MsgCode::MethodPost);
+        encoder.insert_option(option::URI_PATH, "login").unwrap();
+        encoder
+            .insert_option(option::URI_QUERY, "user=bob")
+            .unwrap();
+        encoder.insert_option(option::URI_QUERY, "secret").unwrap();
+        encoder.append_payload_string("hunter2").unwrap();
+
+        let redacted = encoder.to_string();
+        assert!(
+            redacted.contains(" Uri:/login?user=<3 bytes>&<6 bytes> "),
+            "{}",
+            redacted
+        );
+        assert!(redacted.contains("<7 bytes>"), "{}", redacted);
+        assert!(!redacted.contains("bob"), "{}", redacted);
+        assert!(!redacted.contains("secret"), "{}", redacted);
+        assert!(!redacted.contains("hunter2"), "{}", redacted);
+
+        let corrupted = CoapByteDisplayFormatter(&[0xff, 0xfe]).to_string();
+        assert_eq!("<CORRUPTED 2 bytes>", corrupted);
+
+        let msg = StandardMessageParser::new(&encoder).unwrap();
+        let full = FullMessageDisplay(&msg).to_string();
+
+        assert!(full.contains(" Uri:/login?user=bob&secret "), "{}", full);
+        assert!(full.contains("\"hunter2\""), "{}", full);
+    }
+
+    #[test]
+    fn option_values() {
+        let mut encoder = VecMessageEncoder::new();
+        encoder.set_msg_code(MsgCode::SuccessContent);
+        encoder.insert_option(option::URI_HOST, "::1").unwrap();
+        encoder.insert_option(option::URI_PORT, 5684).unwrap();
+        encoder.insert_option(option::LOCATION_PATH, "a b").unwrap();
+        encoder.insert_option(option::LOCATION_PATH, "..").unwrap();
+        encoder.insert_option(option::URI_PATH, "x/y").unwrap();
+        encoder
+            .insert_option(option::CONTENT_FORMAT, ContentFormat::APPLICATION_CBOR)
+            .unwrap();
+        encoder.insert_option(option::URI_QUERY, "a&b=c").unwrap();
+        encoder
+            .insert_option(option::BLOCK2, BlockInfo::new(2, true, 6).unwrap())
+            .unwrap();
+        encoder
+            .insert_option_with_bytes(OptionNumber(65003), &[0xAB])
+            .unwrap();
+
+        let display = encoder.to_string();
+        assert!(
+            display.contains(" Uri://[::1]:5684/x%2Fy?a%26b=<1 bytes> "),
+            "{}",
+            display
+        );
+        assert!(display.contains(" Location:/a%20b/%2E%2E "), "{}", display);
+        assert!(
+            display.contains(" Content-Format:application/cbor "),
+            "{}",
+            display
+        );
+        assert!(display.contains(" Block2:2/1/1024 "), "{}", display);
+        assert!(display.contains(" Crit-UnSafe-65003:AB>"), "{}", display);
+        assert_eq!(display.matches("Uri:").count(), 1, "{}", display);
+    }
 }
@@ -0,0 +1,454 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+use std::ops::Range;
+
+/// The fields of a CoAP message which are encoded in its header, rather than in its
+/// options or payload.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct MessageHeader {
+    /// The message type.
+    pub msg_type: MsgType,
+
+    /// The message code.
+    pub msg_code: MsgCode,
+
+    /// The message id.
+    pub msg_id: MsgId,
+
+    /// The message token.
+    pub msg_token: MsgToken,
+}
+
+/// Encoding of the header of a CoAP message, which differs between transports.
+///
+/// The options and payload which follow the header (the *body* of the message) are
+/// encoded the same way for every transport, so only the header needs to be handled
+/// differently. [`StandardMessageParser::with_framing`] and
+/// [`VecMessageEncoder::to_framed_vec`] use this trait to support other framings than
+/// the one for datagrams.
+pub trait MessageFraming {
+    /// The maximum length of a header, including the token.
+    const MAX_HEADER_LEN: usize;
+
+    /// Decodes the header at the start of `buffer`, returning it along with the range of
+    /// `buffer` which contains the body of the message.
+    fn decode_header(&self, buffer: &[u8]) -> Result<(MessageHeader, Range<usize>), Error>;
+
+    /// Encodes the header of a message whose body is `body_len` bytes long to the start of
+    /// `buffer`, returning the length of the header.
+    ///
+    /// Returns [`Error::OutOfSpace`] if `buffer` is too small for the header.
+    fn encode_header(
+        &self,
+        header: &MessageHeader,
+        body_len: usize,
+        buffer: &mut [u8],
+    ) -> Result<usize, Error>;
+}
+
+/// The message framing used by CoAP over unreliable datagram transports, like UDP, as
+/// described in [IETF-RFC7252 Section 3].
+///
+/// [IETF-RFC7252 Section 3]: https://tools.ietf.org/html/rfc7252#section-3
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct DatagramFraming;
+
+impl MessageFraming for DatagramFraming {
+    const MAX_HEADER_LEN: usize = 4 + MsgToken::MAX_LEN;
+
+    fn decode_header(&self, buffer: &[u8]) -> Result<(MessageHeader, Range<usize>), Error> {
+        if buffer.len() < 4 || (buffer[0] & COAP_MSG_VER_MASK) >> COAP_MSG_VER_OFFS != 1 {
+            return Err(Error::ParseFailure);
+        }
+
+        decode_datagram_header(buffer)
+    }
+
+    fn encode_header(
+        &self,
+        header: &MessageHeader,
+        _body_len: usize,
+        buffer: &mut [u8],
+    ) -> Result<usize, Error> {
+        let token = header.msg_token.as_bytes();
+        let len = 4 + token.len();
+
+        if buffer.len() < len {
+            return Err(Error::OutOfSpace);
+        }
+
+        buffer[0] = (1 << COAP_MSG_VER_OFFS)
+            | ((header.msg_type as u8) << COAP_MSG_T_OFFS)
+            | token.len() as u8;
+        buffer[1] = header.msg_code as u8;
+        buffer[2] = (header.msg_id >> 8) as u8;
+        buffer[3] = header.msg_id as u8;
+        buffer[4..len].copy_from_slice(token);
+
+        Ok(len)
+    }
+}
+
+/// Variant of [`DatagramFraming`] which decodes headers with any value in the version
+/// field, rather than only version 1.
+///
+/// Messages from other protocol versions can't be expected to mean the same thing as
+/// CoAP version 1 messages, so this should only be used to inspect traffic that would
+/// otherwise be dropped, never to handle it. Use [`AnyVersionDatagramFraming::version`]
+/// to find out which version a message claims to be. Headers are always encoded as
+/// version 1.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct AnyVersionDatagramFraming;
+
+impl AnyVersionDatagramFraming {
+    /// Returns the value of the version field of the datagram in `buffer`, or `None` if
+    /// `buffer` is too short to contain a header.
+    pub fn version(buffer: &[u8]) -> Option<u8> {
+        if buffer.len() < 4 {
+            return None;
+        }
+
+        Some((buffer[0] & COAP_MSG_VER_MASK) >> COAP_MSG_VER_OFFS)
+    }
+}
+
+impl MessageFraming for AnyVersionDatagramFraming {
+    const MAX_HEADER_LEN: usize = DatagramFraming::MAX_HEADER_LEN;
+
+    fn decode_header(&self, buffer: &[u8]) -> Result<(MessageHeader, Range<usize>), Error> {
+        if buffer.len() < 4 {
+            return Err(Error::ParseFailure);
+        }
+
+        decode_datagram_header(buffer)
+    }
+
+    fn encode_header(
+        &self,
+        header: &MessageHeader,
+        body_len: usize,
+        buffer: &mut [u8],
+    ) -> Result<usize, Error> {
+        DatagramFraming.encode_header(header, body_len, buffer)
+    }
+}
+
+/// Decodes the datagram header at the start of `buffer`, ignoring the version field.
+/// `buffer` must be at least four bytes long.
+fn decode_datagram_header(buffer: &[u8]) -> Result<(MessageHeader, Range<usize>), Error> {
+    let msg_code = MsgCode::try_from(buffer[1]).ok_or(Error::UnknownMessageCode)?;
+
+    let msg_type = MsgType::from((buffer[0] & COAP_MSG_T_MASK) >> COAP_MSG_T_OFFS);
+    let msg_id = buffer[3] as u16 | ((buffer[2] as u16) << 8);
+    let token_len = (buffer[0] & COAP_MSG_TKL_MASK) as usize;
+    if token_len > MsgToken::MAX_LEN || buffer.len() < 4 + token_len {
+        return Err(Error::ParseFailure);
+    }
+    let msg_token = MsgToken::new(&buffer[4..4 + token_len]);
+
+    let header = MessageHeader {
+        msg_type,
+        msg_code,
+        msg_id,
+        msg_token,
+    };
+
+    Ok((header, 4 + token_len..buffer.len()))
+}
+
+/// The message framing used by CoAP over reliable stream transports, like TCP and TLS,
+/// as described in [IETF-RFC8323 Section 3.2].
+///
+/// Messages framed this way have no type or message id: they are decoded as
+/// non-confirmable messages with a message id of zero, and both fields are ignored when
+/// encoding.
+///
+/// [IETF-RFC8323 Section 3.2]: https://tools.ietf.org/html/rfc8323#section-3.2
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct StreamFraming;
+
+impl StreamFraming {
+    /// Returns the total length of the message at the start of `buffer`, or `None` if
+    /// `buffer` doesn't contain enough of the header to determine it yet.
+    ///
+    /// This is used to split a stream into messages.
+    pub fn frame_len(buffer: &[u8]) -> Option<usize> {
+        let first = *buffer.first()?;
+        let token_len = (first & COAP_MSG_TKL_MASK) as usize;
+        let (ext_len, offset) = match first >> 4 {
+            13 => (1, 13),
+            14 => (2, 269),
+            15 => (4, 65805),
+            len => return Some(2 + token_len + len as usize),
+        };
+
+        let ext = buffer.get(1..1 + ext_len)?;
+        let body_len = ext.iter().fold(0usize, |x, b| (x << 8) | *b as usize) + offset;
+
+        Some(2 + ext_len + token_len + body_len)
+    }
+}
+
+impl MessageFraming for StreamFraming {
+    const MAX_HEADER_LEN: usize = 6 + MsgToken::MAX_LEN;
+
+    fn decode_header(&self, buffer: &[u8]) -> Result<(MessageHeader, Range<usize>), Error> {
+        let frame_len = StreamFraming::frame_len(buffer).ok_or(Error::ParseFailure)?;
+        if buffer.len() < frame_len {
+            return Err(Error::ParseFailure);
+        }
+
+        let token_len = (buffer[0] & COAP_MSG_TKL_MASK) as usize;
+        if token_len > MsgToken::MAX_LEN {
+            return Err(Error::ParseFailure);
+        }
+
+        let code_index = match buffer[0] >> 4 {
+            13 => 2,
+            14 => 3,
+            15 => 5,
+            _ => 1,
+        };
+
+        let msg_code = MsgCode::try_from(buffer[code_index]).ok_or(Error::UnknownMessageCode)?;
+        let token_start = code_index + 1;
+        let msg_token = MsgToken::new(&buffer[token_start..token_start + token_len]);
+
+        let header = MessageHeader {
+            msg_type: MsgType::Non,
+            msg_code,
+            msg_id: 0,
+            msg_token,
+        };
+
+        Ok((header, token_start + token_len..frame_len))
+    }
+
+    fn encode_header(
+        &self,
+        header: &MessageHeader,
+        body_len: usize,
+        buffer: &mut [u8],
+    ) -> Result<usize, Error> {
+        let token = header.msg_token.as_bytes();
+
+        let (len_nibble, ext_len, ext) = match body_len {
+            0..=12 => (body_len as u8, 0, 0),
+            13..=268 => (13, 1, body_len - 13),
+            269..=65804 => (14, 2, body_len - 269),
+            _ => (15, 4, body_len - 65805),
+        };
+
+        let len = 2 + ext_len + token.len();
+        if buffer.len() < len {
+            return Err(Error::OutOfSpace);
+        }
+
+        buffer[0] = (len_nibble << 4) | token.len() as u8;
+        for i in 0..ext_len {
+            buffer[1 + i] = (ext >> (8 * (ext_len - 1 - i))) as u8;
+        }
+        buffer[1 + ext_len] = header.msg_code as u8;
+        buffer[2 + ext_len..len].copy_from_slice(token);
+
+        Ok(len)
+    }
+}
+
+/// The message framing used by CoAP over WebSockets, as described in
+/// [IETF-RFC8323 Section 4.2].
+///
+/// This is like [`StreamFraming`], except that the length of a message is implied by the
+/// WebSocket frame which carries it. The length field is always zero, and there is no
+/// extended length, so decoding treats all of the buffer as a single message.
+///
+/// [IETF-RFC8323 Section 4.2]: https://tools.ietf.org/html/rfc8323#section-4.2
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct WebSocketFraming;
+
+impl MessageFraming for WebSocketFraming {
+    const MAX_HEADER_LEN: usize = 2 + MsgToken::MAX_LEN;
+
+    fn decode_header(&self, buffer: &[u8]) -> Result<(MessageHeader, Range<usize>), Error> {
+        if buffer.len() < 2 || buffer[0] >> 4 != 0 {
+            return Err(Error::ParseFailure);
+        }
+
+        let token_len = (buffer[0] & COAP_MSG_TKL_MASK) as usize;
+        if token_len > MsgToken::MAX_LEN || buffer.len() < 2 + token_len {
+            return Err(Error::ParseFailure);
+        }
+
+        let msg_code = MsgCode::try_from(buffer[1]).ok_or(Error::UnknownMessageCode)?;
+        let msg_token = MsgToken::new(&buffer[2..2 + token_len]);
+
+        let header = MessageHeader {
+            msg_type: MsgType::Non,
+            msg_code,
+            msg_id: 0,
+            msg_token,
+        };
+
+        Ok((header, 2 + token_len..buffer.len()))
+    }
+
+    fn encode_header(
+        &self,
+        header: &MessageHeader,
+        _body_len: usize,
+        buffer: &mut [u8],
+    ) -> Result<usize, Error> {
+        let token = header.msg_token.as_bytes();
+        let len = 2 + token.len();
+
+        if buffer.len() < len {
+            return Err(Error::OutOfSpace);
+        }
+
+        buffer[0] = token.len() as u8;
+        buffer[1] = header.msg_code as u8;
+        buffer[2..len].copy_from_slice(token);
+
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::option::*;
+
+    fn round_trip<F: MessageFraming>(framing: F, payload_len: usize) {
+        let payload = vec![0x5Au8; payload_len];
+
+        let mut encoder = VecMessageEncoder::new();
+        encoder.set_msg_type(MsgType::Non);
+        encoder.set_msg_code(MsgCode::MethodPost);
+        encoder.set_msg_token(MsgToken::from(0xC0FFEE));
+        encoder.insert_option(URI_PATH, "test").unwrap();
+        encoder.append_payload_bytes(&payload).unwrap();
+
+        let mut framed = encoder.to_framed_vec(&framing).unwrap();
+        framed.extend_from_slice(b"next message");
+
+        let parser = StandardMessageParser::with_framing(&framed, &framing).unwrap();
+        assert_eq!(MsgType::Non, parser.msg_type());
+        assert_eq!(MsgCode::MethodPost, parser.msg_code());
+        assert_eq!(MsgToken::from(0xC0FFEE), parser.msg_token());
+        assert_eq!(Some(Ok("test")), parser.options().find_next_of(URI_PATH));
+        assert_eq!(&payload[..], parser.payload());
+    }
+
+    #[test]
+    fn datagram_framing() {
+        let mut encoder = VecMessageEncoder::new();
+        encoder.set_msg_type(MsgType::Con);
+        encoder.set_msg_code(MsgCode::MethodGet);
+        encoder.set_msg_id(0x7d34);
+        encoder.insert_option(URI_PATH, "temperature").unwrap();
+
+        assert_eq!(
+            encoder.as_bytes(),
+            &encoder.to_framed_vec(&DatagramFraming).unwrap()[..]
+        );
+
+        let parser = StandardMessageParser::with_framing(&encoder, &DatagramFraming).unwrap();
+        assert_eq!(0x7d34, parser.msg_id());
+    }
+
+    #[test]
+    fn any_version_datagram_framing() {
+        // Version 2 NON POST with a one-byte token and a Uri-Path option.
+        let datagram = [0x91, 0x02, 0x12, 0x34, 0xAA, 0xB4, b't', b'e', b's', b't'];
+
+        assert_eq!(Some(2), AnyVersionDatagramFraming::version(&datagram));
+        assert_eq!(None, AnyVersionDatagramFraming::version(&datagram[..3]));
+        assert_eq!(
+            Err(Error::ParseFailure),
+            StandardMessageParser::new(&datagram).map(|_| ())
+        );
+
+        let parser =
+            StandardMessageParser::with_framing(&datagram, &AnyVersionDatagramFraming).unwrap();
+        assert_eq!(MsgType::Non, parser.msg_type());
+        assert_eq!(MsgCode::MethodPost, parser.msg_code());
+        assert_eq!(0x1234, parser.msg_id());
+        assert_eq!(MsgToken::new(&[0xAA]), parser.msg_token());
+        assert_eq!(Some(Ok("test")), parser.options().find_next_of(URI_PATH));
+
+        // Headers are always encoded as version 1.
+        let (header, _) = AnyVersionDatagramFraming.decode_header(&datagram).unwrap();
+        let mut buffer = [0u8; AnyVersionDatagramFraming::MAX_HEADER_LEN];
+        let len = AnyVersionDatagramFraming
+            .encode_header(&header, 0, &mut buffer)
+            .unwrap();
+        assert_eq!(&[0x51, 0x02, 0x12, 0x34, 0xAA], &buffer[..len]);
+    }
+
+    #[test]
+    fn stream_framing() {
+        for &payload_len in &[0, 3, 300, 70_000] {
+            round_trip(StreamFraming, payload_len);
+        }
+    }
+
+    #[test]
+    fn stream_frame_len() {
+        // Empty 7.01 CSM message from IETF-RFC8323 Section 5.3.
+        assert_eq!(Some(2), StreamFraming::frame_len(&[0x00, 0xE1]));
+        assert_eq!(
+            Some(2 + 2 + 2 + 300),
+            StreamFraming::frame_len(&[0xE2, 0x00, 0x1F])
+        );
+        assert_eq!(None, StreamFraming::frame_len(&[0xE2, 0x00]));
+        assert_eq!(None, StreamFraming::frame_len(&[]));
+
+        assert_eq!(
+            Err(Error::ParseFailure),
+            StreamFraming
+                .decode_header(&[0x30, 0x01, 0xFF])
+                .map(|(header, _)| header)
+        );
+    }
+
+    #[test]
+    fn websocket_framing() {
+        let mut encoder = VecMessageEncoder::new();
+        encoder.set_msg_code(MsgCode::MethodGet);
+        encoder.set_msg_token(MsgToken::new(&[0xAA, 0xBB]));
+        encoder.insert_option(URI_PATH, "test").unwrap();
+        encoder.append_payload_bytes(b"hello").unwrap();
+
+        let framed = encoder.to_framed_vec(&WebSocketFraming).unwrap();
+        assert_eq!(&[0x02, 0x01, 0xAA, 0xBB, 0xB4], &framed[..5]);
+
+        let parser = StandardMessageParser::with_framing(&framed, &WebSocketFraming).unwrap();
+        assert_eq!(MsgType::Non, parser.msg_type());
+        assert_eq!(MsgCode::MethodGet, parser.msg_code());
+        assert_eq!(MsgToken::new(&[0xAA, 0xBB]), parser.msg_token());
+        assert_eq!(Some(Ok("test")), parser.options().find_next_of(URI_PATH));
+        assert_eq!(b"hello", parser.payload());
+
+        // The length is implied by the WebSocket frame, so it must not be encoded.
+        assert_eq!(
+            Err(Error::ParseFailure),
+            WebSocketFraming
+                .decode_header(&[0x32, 0x01, 0xAA, 0xBB])
+                .map(|(header, _)| header)
+        );
+    }
+}
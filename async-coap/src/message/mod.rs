@@ -38,6 +38,13 @@ pub use msg_type::MsgType;
 mod display;
 pub use display::CoapByteDisplayFormatter;
 pub use display::MessageDisplay;
+pub use display::{full_message_dumps, set_full_message_dumps};
+
+mod framing;
+pub use framing::{
+    AnyVersionDatagramFraming, DatagramFraming, MessageFraming, MessageHeader, StreamFraming,
+    WebSocketFraming,
+};
 
 mod null;
 pub use null::NullMessageRead;
@@ -57,10 +64,10 @@ pub use token::*;
 pub mod codec;
 
 #[allow(dead_code)]
-const COAP_MSG_VER_MASK: u8 = 0b11000000;
+pub(crate) const COAP_MSG_VER_MASK: u8 = 0b11000000;
 
 #[allow(dead_code)]
-const COAP_MSG_VER_OFFS: u8 = 6;
+pub(crate) const COAP_MSG_VER_OFFS: u8 = 6;
 
 #[allow(dead_code)]
 const COAP_MSG_T_MASK: u8 = 0b00110000;
@@ -69,7 +76,7 @@ const COAP_MSG_T_MASK: u8 = 0b00110000;
 const COAP_MSG_T_OFFS: u8 = 4;
 
 #[allow(dead_code)]
-const COAP_MSG_TKL_MASK: u8 = 0b00001111;
+pub(crate) const COAP_MSG_TKL_MASK: u8 = 0b00001111;
 
 #[allow(dead_code)]
 const COAP_MSG_TKL_OFFS: u8 = 0;
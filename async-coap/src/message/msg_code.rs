@@ -184,6 +184,9 @@ impl MsgCode {
             0x02 => Some(MethodPost),
             0x03 => Some(MethodPut),
             0x04 => Some(MethodDelete),
+            0x05 => Some(MethodFetch),
+            0x06 => Some(MethodPatch),
+            0x07 => Some(MethodIPatch),
 
             0x41 => Some(SuccessCreated),
             0x42 => Some(SuccessDeleted),
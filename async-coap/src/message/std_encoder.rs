@@ -122,6 +122,19 @@ impl<'buf> MessageWrite for BufferMessageEncoder<'buf> {
         Ok(())
     }
 
+    fn remaining_capacity(&self) -> usize {
+        if self.len == self.payload_start {
+            self.max_payload_len()
+        } else {
+            self.buffer.len() - self.len
+        }
+    }
+
+    fn max_payload_len(&self) -> usize {
+        // Leave room for the end-of-options marker.
+        self.buffer.len().saturating_sub(self.payload_start + 1)
+    }
+
     fn clear(&mut self) {
         self.buffer[0] = 0b01000000;
         self.len = 4;
@@ -162,6 +175,7 @@ pub struct VecMessageEncoder {
     option_start: usize,
     payload_start: usize,
     last_option: OptionNumber,
+    max_len: usize,
 }
 
 impl VecMessageEncoder {
@@ -183,9 +197,24 @@ impl VecMessageEncoder {
             option_start: 4,
             payload_start: 4,
             last_option: Default::default(),
+            max_len: usize::MAX,
         }
     }
 
+    /// Reserves capacity for at least `additional` more bytes of options or payload, so
+    /// that writing them doesn't reallocate the buffer.
+    pub fn reserve(&mut self, additional: usize) {
+        self.buffer.reserve(additional);
+    }
+
+    /// Limits the length of the encoded message to `max_len` bytes. Options and payload
+    /// which would make the message any longer are rejected with [`Error::OutOfSpace`].
+    ///
+    /// By default, the length of the message is unlimited.
+    pub fn set_max_len(&mut self, max_len: usize) {
+        self.max_len = max_len;
+    }
+
     /// Returns a byte slice containing the encoded message.
     pub fn as_bytes(&self) -> &[u8] {
         &self.buffer
@@ -196,6 +225,20 @@ impl VecMessageEncoder {
         let token_len = (self.buffer[0] & COAP_MSG_TKL_MASK) as usize;
         MsgToken::new(&self.buffer[4..4 + token_len])
     }
+
+    /// Returns a copy of the encoded message, with its header encoded using `framing`
+    /// instead of for datagrams.
+    pub fn to_framed_vec<F: MessageFraming>(&self, framing: &F) -> Result<Vec<u8>, Error> {
+        let (header, body) = DatagramFraming.decode_header(&self.buffer)?;
+        let body = &self.buffer[body];
+
+        let mut ret = vec![0; F::MAX_HEADER_LEN + body.len()];
+        let len = framing.encode_header(&header, body.len(), &mut ret)?;
+        ret.truncate(len);
+        ret.extend_from_slice(body);
+
+        Ok(ret)
+    }
 }
 
 impl std::convert::From<VecMessageEncoder> for Vec<u8> {
@@ -256,6 +299,9 @@ impl MessageWrite for VecMessageEncoder {
     }
 
     fn append_payload_bytes(&mut self, body: &[u8]) -> Result<(), Error> {
+        if body.len() > self.remaining_capacity() {
+            return Err(Error::OutOfSpace);
+        }
         if self.buffer.len() == self.payload_start {
             // Append an end-of-options marker.
             self.buffer.push(0xFF);
@@ -264,6 +310,25 @@ impl MessageWrite for VecMessageEncoder {
         Ok(())
     }
 
+    fn remaining_capacity(&self) -> usize {
+        if self.max_len == usize::MAX {
+            usize::MAX
+        } else if self.buffer.len() == self.payload_start {
+            self.max_payload_len()
+        } else {
+            self.max_len.saturating_sub(self.buffer.len())
+        }
+    }
+
+    fn max_payload_len(&self) -> usize {
+        if self.max_len == usize::MAX {
+            usize::MAX
+        } else {
+            // Leave room for the end-of-options marker.
+            self.max_len.saturating_sub(self.payload_start + 1)
+        }
+    }
+
     fn clear(&mut self) {
         self.buffer[0] = 0b01000000;
         self.buffer.resize(4, 0);
@@ -284,15 +349,25 @@ impl OptionInsert for VecMessageEncoder {
         let workspace = value.len() + 5;
 
         let len = self.buffer.len();
-        self.buffer.resize(self.buffer.len() + workspace, 0);
 
-        let (mut len, last_option) = insert_option(
+        // Limiting the workspace to `max_len` makes `insert_option` fail before
+        // it touches any of the options that have already been written.
+        self.buffer
+            .resize((len + workspace).min(self.max_len.max(len)), 0);
+
+        let (mut len, last_option) = match insert_option(
             &mut self.buffer[option_start..],
             len - option_start,
             self.last_option,
             key,
             value,
-        )?;
+        ) {
+            Ok(x) => x,
+            Err(e) => {
+                self.buffer.truncate(len);
+                return Err(e);
+            }
+        };
 
         len += option_start;
         self.buffer.truncate(len);
@@ -543,4 +618,62 @@ mod tests {
         assert_eq!(None, parser.accept());
         assert_eq!(b"", parser.payload());
     }
+
+    #[test]
+    fn message_builder_capacity() {
+        let buffer = &mut [0u8; 20];
+        let mut builder = BufferMessageEncoder::new(buffer);
+        builder.set_msg_token(MsgToken::from(0x20));
+        assert_eq!(14, builder.max_payload_len());
+        assert_eq!(14, builder.remaining_capacity());
+        assert_eq!(Ok(()), builder.insert_option(URI_PATH, "temp"));
+        assert_eq!(9, builder.max_payload_len());
+        assert_eq!(9, builder.remaining_capacity());
+        assert_eq!(Ok(()), builder.append_payload_string("22.3"));
+        assert_eq!(9, builder.max_payload_len());
+        assert_eq!(5, builder.remaining_capacity());
+        assert_eq!(
+            Err(Error::OutOfSpace),
+            builder.append_payload_string("22.3 C")
+        );
+        assert_eq!(Ok(()), builder.append_payload_string(" C!!!"));
+        assert_eq!(0, builder.remaining_capacity());
+        assert_eq!(20, builder.len());
+
+        let mut builder = VecMessageEncoder::new();
+        assert_eq!(usize::MAX, builder.remaining_capacity());
+        builder.set_max_len(20);
+        builder.set_msg_token(MsgToken::from(0x20));
+        assert_eq!(14, builder.max_payload_len());
+        assert_eq!(
+            Err(Error::OutOfSpace),
+            builder.insert_option(URI_PATH, "temperature-sensor")
+        );
+        assert_eq!(Ok(()), builder.insert_option(URI_PATH, "temp"));
+        assert_eq!(9, builder.remaining_capacity());
+        assert_eq!(Ok(()), builder.append_payload_string("22.3"));
+        assert_eq!(5, builder.remaining_capacity());
+        assert_eq!(
+            Err(Error::OutOfSpace),
+            builder.append_payload_string("22.3 C")
+        );
+        assert_eq!(Ok(()), builder.append_payload_string(" C!!!"));
+        assert_eq!(0, builder.remaining_capacity());
+        assert_eq!(20, builder.len());
+    }
+
+    #[test]
+    fn message_builder_io_write_truncates() {
+        use std::io::Write;
+
+        let mut builder = VecMessageEncoder::new();
+        builder.set_max_len(10);
+        assert_eq!(5, builder.write(b"22.3 C").unwrap());
+        assert_eq!(0, builder.write(b"C").unwrap());
+        assert!(builder.write_all(b"C").is_err());
+        assert_eq!(
+            b"22.3 ",
+            StandardMessageParser::new(&builder).unwrap().payload()
+        );
+    }
 }
@@ -14,11 +14,27 @@
 //
 
 use super::util::encode_u32;
+use crate::Error;
+use core::borrow::Borrow;
+use core::cmp::Ordering;
 use core::convert::From;
+use core::hash::{Hash, Hasher};
 use core::ops::Deref;
+use core::str::FromStr;
 
 /// Type for holding the value of a CoAP message token.
-#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone, Ord, PartialOrd)]
+///
+/// Tokens are compared, ordered, and hashed the same way as their byte slices, so a
+/// `MsgToken` can be used as a map key that is looked up using a `&[u8]`. Tokens are
+/// displayed as (and can be parsed from) uppercase hexadecimal strings:
+///
+/// ```
+/// # use async_coap::message::MsgToken;
+/// let token: MsgToken = "C0FFEE".parse().unwrap();
+/// assert_eq!(token, MsgToken::try_new(&[0xC0, 0xFF, 0xEE]).unwrap());
+/// assert_eq!(token.to_string(), "C0FFEE");
+/// ```
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub struct MsgToken {
     len: u8,
     bytes: [u8; 8],
@@ -31,18 +47,34 @@ impl MsgToken {
         bytes: [0; 8],
     };
 
+    /// The maximum length of a token (8 bytes).
+    pub const MAX_LEN: usize = 8;
+
     /// Creates a new token from the given byte slice.
+    ///
+    /// Panics if `x` is longer than [`MsgToken::MAX_LEN`]. See [`MsgToken::try_new`]
+    /// for a version that doesn't panic.
     pub fn new(x: &[u8]) -> MsgToken {
         MsgToken::from(x)
     }
 
+    /// Creates a new token from the given byte slice, failing with
+    /// [`Error::InvalidArgument`] if it is longer than [`MsgToken::MAX_LEN`].
+    pub fn try_new(x: &[u8]) -> Result<MsgToken, Error> {
+        if x.len() > MsgToken::MAX_LEN {
+            return Err(Error::InvalidArgument);
+        }
+
+        Ok(MsgToken::from(x))
+    }
+
     /// Returns the length of this token.
-    pub fn len(&self) -> usize {
+    pub const fn len(&self) -> usize {
         self.len as usize
     }
 
     /// Returns true if the length of this token is zero.
-    pub fn is_empty(&self) -> bool {
+    pub const fn is_empty(&self) -> bool {
         self.len == 0
     }
 
@@ -50,6 +82,30 @@ impl MsgToken {
     pub fn as_bytes(&self) -> &[u8] {
         &self.bytes[..self.len as usize]
     }
+
+    /// Returns the bytes of this token padded with zeros to [`MsgToken::MAX_LEN`] bytes,
+    /// along with its length.
+    ///
+    /// This is useful for storing tokens in fixed-size records.
+    pub const fn to_padded_bytes(&self) -> ([u8; 8], usize) {
+        (self.bytes, self.len as usize)
+    }
+
+    /// Compares two tokens in constant time.
+    ///
+    /// Unlike `==`, this doesn't stop at the first byte that differs, so the time it
+    /// takes doesn't reveal how much of a token an attacker has guessed correctly. Use this
+    /// when a token is being used to authenticate a response.
+    pub fn ct_eq(&self, other: &MsgToken) -> bool {
+        // The unused bytes are always zero, so they can be compared too.
+        let diff = self
+            .bytes
+            .iter()
+            .zip(other.bytes.iter())
+            .fold(self.len ^ other.len, |diff, (a, b)| diff | (a ^ b));
+
+        diff == 0
+    }
 }
 
 impl std::fmt::Display for MsgToken {
@@ -61,6 +117,59 @@ impl std::fmt::Display for MsgToken {
     }
 }
 
+impl FromStr for MsgToken {
+    type Err = Error;
+
+    /// Parses a token from a string of up to 16 hexadecimal digits. Fails with
+    /// [`Error::ParseFailure`] if the string isn't valid hexadecimal, or with
+    /// [`Error::InvalidArgument`] if the token would be too long.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() & 1 != 0 || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(Error::ParseFailure);
+        }
+
+        if s.len() > MsgToken::MAX_LEN * 2 {
+            return Err(Error::InvalidArgument);
+        }
+
+        let mut bytes = [0u8; 8];
+
+        for (i, byte) in bytes.iter_mut().take(s.len() / 2).enumerate() {
+            *byte =
+                u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).map_err(|_| Error::ParseFailure)?;
+        }
+
+        Ok(MsgToken {
+            len: (s.len() / 2) as u8,
+            bytes,
+        })
+    }
+}
+
+impl Ord for MsgToken {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_bytes().cmp(other.as_bytes())
+    }
+}
+
+impl PartialOrd for MsgToken {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Hash for MsgToken {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_bytes().hash(state)
+    }
+}
+
+impl Borrow<[u8]> for MsgToken {
+    fn borrow(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
 impl Default for MsgToken {
     fn default() -> Self {
         MsgToken::EMPTY
@@ -105,7 +214,7 @@ impl core::convert::From<u16> for MsgToken {
 }
 
 impl core::convert::From<&[u8]> for MsgToken {
-    // Note: this will panic if x is too big.
+    // Note: this will panic if x is too big. Use `MsgToken::try_new` to avoid this.
     fn from(x: &[u8]) -> Self {
         let mut bytes = [0u8; 8];
         let len = x.len();
@@ -116,3 +225,64 @@ impl core::convert::From<&[u8]> for MsgToken {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn try_new() {
+        assert_eq!(MsgToken::try_new(&[]), Ok(MsgToken::EMPTY));
+        assert_eq!(MsgToken::try_new(&[1; 8]).map(|t| t.len()), Ok(8));
+        assert_eq!(MsgToken::try_new(&[1; 9]), Err(Error::InvalidArgument));
+    }
+
+    #[test]
+    fn from_str() {
+        assert_eq!("".parse(), Ok(MsgToken::EMPTY));
+        assert_eq!("0a0B".parse(), Ok(MsgToken::new(&[0x0A, 0x0B])));
+        assert_eq!(
+            "0102030405060708"
+                .parse::<MsgToken>()
+                .map(|t| t.to_string()),
+            Ok("0102030405060708".to_string())
+        );
+        assert_eq!("123".parse::<MsgToken>(), Err(Error::ParseFailure));
+        assert_eq!("zz".parse::<MsgToken>(), Err(Error::ParseFailure));
+        assert_eq!("+1".parse::<MsgToken>(), Err(Error::ParseFailure));
+        assert_eq!(
+            "010203040506070809".parse::<MsgToken>(),
+            Err(Error::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn ordering() {
+        let a = MsgToken::new(&[0x01, 0x02]);
+        let b = MsgToken::new(&[0x02]);
+
+        assert_eq!(a.cmp(&b), a.as_bytes().cmp(b.as_bytes()));
+        assert!(MsgToken::EMPTY < a);
+        assert!(a < b);
+    }
+
+    #[test]
+    fn ct_eq() {
+        let a = MsgToken::from(0x1234u32);
+
+        assert!(a.ct_eq(&MsgToken::new(&[0x12, 0x34])));
+        assert!(!a.ct_eq(&MsgToken::new(&[0x12, 0x35])));
+        assert!(!a.ct_eq(&MsgToken::new(&[0x12, 0x34, 0x00])));
+        assert!(MsgToken::EMPTY.ct_eq(&MsgToken::default()));
+    }
+
+    #[test]
+    fn borrow() {
+        let mut map = HashMap::new();
+        map.insert(MsgToken::from(0x1234u32), "a");
+
+        assert_eq!(map.get(&[0x12u8, 0x34][..]), Some(&"a"));
+        assert_eq!(map.get(&[0x12u8][..]), None);
+    }
+}
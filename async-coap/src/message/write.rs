@@ -65,6 +65,26 @@ pub trait MessageWrite: OptionInsert {
         self.append_payload_string(c.encode_utf8(&mut [0; 4]))
     }
 
+    /// Returns the number of payload bytes that can still be appended to the message
+    /// before [`MessageWrite::append_payload_bytes`] fails with [`Error::OutOfSpace`],
+    /// or `usize::MAX` if the message has no size limit.
+    ///
+    /// This takes into account the token, options, and payload written so far, as
+    /// well as the end-of-options marker if no payload has been written yet. Since
+    /// writing more options reduces the space left for the payload, this should only be
+    /// relied upon after all of the options have been set.
+    fn remaining_capacity(&self) -> usize {
+        self.max_payload_len()
+    }
+
+    /// Returns the maximum length of the payload that this message can hold given the token
+    /// and options written so far, or `usize::MAX` if the message has no size limit.
+    ///
+    /// This includes any payload that has already been written.
+    fn max_payload_len(&self) -> usize {
+        usize::MAX
+    }
+
     /// Removes the message payload along with all options.
     fn clear(&mut self);
 }
@@ -83,8 +103,9 @@ impl<'a> core::fmt::Write for dyn MessageWrite + 'a {
 
 impl<'a> std::io::Write for dyn MessageWrite + 'a {
     fn write(&mut self, buf: &[u8]) -> Result<usize, std::io::Error> {
-        self.append_payload_bytes(buf)
-            .map(|_| buf.len())
+        let len = buf.len().min(self.remaining_capacity());
+        self.append_payload_bytes(&buf[..len])
+            .map(|_| len)
             .map_err(|_| std::io::ErrorKind::Other.into())
     }
 
@@ -100,8 +121,9 @@ impl<'a> std::io::Write for dyn MessageWrite + 'a {
 
 impl<'a> std::io::Write for BufferMessageEncoder<'a> {
     fn write(&mut self, buf: &[u8]) -> Result<usize, std::io::Error> {
-        self.append_payload_bytes(buf)
-            .map(|_| buf.len())
+        let len = buf.len().min(self.remaining_capacity());
+        self.append_payload_bytes(&buf[..len])
+            .map(|_| len)
             .map_err(|_| std::io::ErrorKind::Other.into())
     }
 
@@ -117,8 +139,9 @@ impl<'a> std::io::Write for BufferMessageEncoder<'a> {
 
 impl std::io::Write for VecMessageEncoder {
     fn write(&mut self, buf: &[u8]) -> Result<usize, std::io::Error> {
-        self.append_payload_bytes(buf)
-            .map(|_| buf.len())
+        let len = buf.len().min(self.remaining_capacity());
+        self.append_payload_bytes(&buf[..len])
+            .map(|_| len)
             .map_err(|_| std::io::ErrorKind::Other.into())
     }
 
@@ -16,11 +16,91 @@
 //! NULL CoAP backend
 //!
 //! This is a CoAP back end that does nothing. It is used primarily for testing.
+//!
+//! By default, every request sent using a [`NullLocalEndpoint`] times out. For testing
+//! send descriptors, a [`NullLocalEndpoint`] can instead be created with a scripted
+//! responder using [`NullLocalEndpoint::with_responder`], which keeps everything
+//! in-memory while still exercising the send descriptor's handler.
 use super::*;
-use crate::message::NullMessageRead;
+use crate::message::{MsgToken, NullMessageRead, OwnedImmutableMessage, VecMessageEncoder};
 use crate::remote_endpoint::RemoteEndpoint;
 use futures::future::BoxFuture;
-use std::net::{IpAddr, Ipv4Addr};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::ops::Bound;
+use std::sync::Arc;
+
+/// The type of closure used as a scripted responder by [`NullLocalEndpoint::with_responder`].
+///
+/// The closure is called with the encoded request and returns either the encoded response,
+/// or the error that the send descriptor's handler should be given instead.
+pub type NullResponder = dyn Fn(&[u8]) -> Result<Vec<u8>, Error> + Send + Sync;
+
+fn null_socket_addr() -> SocketAddr {
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)
+}
+
+/// Sends the request described by `send_desc` to `responder`, feeding the responses to
+/// the handler of `send_desc` until it is done.
+fn send_scripted<'a, R, SD>(
+    responder: Option<&Arc<NullResponder>>,
+    socket_addr: SocketAddr,
+    mut send_desc: SD,
+) -> BoxFuture<'a, Result<R, Error>>
+where
+    SD: SendDesc<NullInboundContext, R> + 'a,
+    R: Send + 'a,
+{
+    let responder = match responder {
+        Some(responder) => responder.clone(),
+        None => return futures::future::ready(Err(Error::ResponseTimeout)).boxed(),
+    };
+
+    futures::future::lazy(move |_| {
+        let mut msg_id: MsgId = 0;
+        let mut modify: Option<ModifyRequest> = None;
+
+        loop {
+            msg_id = msg_id.wrapping_add(1);
+
+            let mut builder = VecMessageEncoder::new();
+            builder.set_msg_token(MsgToken::from(msg_id));
+            send_desc.write_options(
+                &mut builder,
+                &socket_addr,
+                Bound::Unbounded,
+                Bound::Unbounded,
+            )?;
+            send_desc.write_payload(&mut builder, &socket_addr)?;
+            if let Some(modify) = modify.as_ref() {
+                modify.apply(&mut builder)?;
+            }
+            builder.set_msg_id(msg_id);
+
+            let status = match responder(builder.as_bytes()).and_then(OwnedImmutableMessage::new) {
+                Ok(message) => send_desc.handler(Ok(&NullInboundContext {
+                    message,
+                    remote_socket_addr: socket_addr,
+                })),
+                Err(e) => send_desc.handler(Err(e)),
+            };
+
+            match status? {
+                ResponseStatus::Done(x) => return Ok(x),
+                ResponseStatus::SendNext => modify = None,
+                ResponseStatus::SendNextWith(next) => modify = Some(next),
+                ResponseStatus::Continue | ResponseStatus::Defer => {
+                    // The responder only produces a single response per request,
+                    // so there is nothing more to wait for.
+                    return match send_desc.handler(Err(Error::ResponseTimeout))? {
+                        ResponseStatus::Done(x) => Ok(x),
+                        _ => Err(Error::ResponseTimeout),
+                    };
+                }
+            }
+        }
+    })
+    .boxed()
+}
 
 /// Concrete instance of [`LocalEndpoint::RespondableInboundContext`] for [`NullLocalEndpoint`].
 #[derive(Debug)]
@@ -47,7 +127,7 @@ impl InboundContext for NullRespondableInboundContext {
     type SocketAddr = std::net::SocketAddr;
 
     fn remote_socket_addr(&self) -> Self::SocketAddr {
-        Self::SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)
+        null_socket_addr()
     }
 
     fn is_dupe(&self) -> bool {
@@ -59,16 +139,19 @@ impl InboundContext for NullRespondableInboundContext {
     }
 }
 
-/// Concrete instance of [`LocalEndpoint::InboundContext`] for [`NullLocalEndpoint`].
+/// Concrete instance of [`LocalEndpoint::InboundContext`] for [`NullLocalEndpoint`],
+/// containing a response produced by a scripted responder.
 #[derive(Debug)]
-pub struct NullInboundContext
-where
-    Self: Send + Sync;
+pub struct NullInboundContext {
+    message: OwnedImmutableMessage,
+    remote_socket_addr: SocketAddr,
+}
+
 impl InboundContext for NullInboundContext {
     type SocketAddr = std::net::SocketAddr;
 
     fn remote_socket_addr(&self) -> Self::SocketAddr {
-        Self::SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)
+        self.remote_socket_addr
     }
 
     fn is_dupe(&self) -> bool {
@@ -76,15 +159,29 @@ impl InboundContext for NullInboundContext {
     }
 
     fn message(&self) -> &dyn MessageRead {
-        &NullMessageRead
+        &self.message
     }
 }
 
 /// Concrete instance of [`LocalEndpoint::RemoteEndpoint`] for [`NullLocalEndpoint`].
-#[derive(Debug)]
-pub struct NullRemoteEndpoint
-where
-    Self: Send + Sync;
+#[derive(Clone)]
+pub struct NullRemoteEndpoint {
+    responder: Option<Arc<NullResponder>>,
+    socket_addr: SocketAddr,
+    host: Option<String>,
+    path: RelRefBuf,
+}
+
+impl std::fmt::Debug for NullRemoteEndpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NullRemoteEndpoint")
+            .field("responder", &self.responder.is_some())
+            .field("socket_addr", &self.socket_addr)
+            .field("host", &self.host)
+            .field("path", &self.path)
+            .finish()
+    }
+}
 
 impl RemoteEndpoint for NullRemoteEndpoint {
     type SocketAddr = std::net::SocketAddr;
@@ -95,42 +192,118 @@ impl RemoteEndpoint for NullRemoteEndpoint {
     }
 
     fn uri(&self) -> UriBuf {
-        uri!("null:///").to_owned()
+        let mut uri = uri!("null:///").to_owned();
+        uri.replace_path(&self.path);
+        uri
     }
 
-    fn send<'a, R, SD>(&'a self, _send_desc: SD) -> BoxFuture<'_, Result<R, Error>>
+    fn send<'a, R, SD>(&'a self, send_desc: SD) -> BoxFuture<'_, Result<R, Error>>
     where
-        SD: SendDesc<Self::InboundContext, R>,
+        SD: SendDesc<Self::InboundContext, R> + 'a,
         R: Send + 'a,
     {
-        futures::future::ready(Err(Error::ResponseTimeout)).boxed()
+        let send_desc = send_desc.uri_host_path(self.host.clone(), &self.path);
+
+        send_scripted(self.responder.as_ref(), self.socket_addr, send_desc)
     }
 
-    fn send_to<'a, R, SD, UF>(
-        &'a self,
-        _path: UF,
-        _send_desc: SD,
-    ) -> BoxFuture<'_, Result<R, Error>>
+    fn send_to<'a, R, SD, UF>(&'a self, path: UF, send_desc: SD) -> BoxFuture<'_, Result<R, Error>>
     where
         SD: SendDesc<Self::InboundContext, R> + 'a,
         R: Send + 'a,
         UF: AsRef<RelRef>,
     {
-        futures::future::ready(Err(Error::ResponseTimeout)).boxed()
+        let send_desc =
+            send_desc.uri_host_path(self.host.clone(), self.path.resolved_rel_ref(path));
+
+        send_scripted(self.responder.as_ref(), self.socket_addr, send_desc)
     }
 
-    fn remove_host_option(&mut self) {}
+    fn host_option(&self) -> Option<&str> {
+        self.host.as_deref()
+    }
+
+    fn default_path(&self) -> &RelRef {
+        &self.path
+    }
 
-    fn clone_using_rel_ref(&self, _uri: &RelRef) -> Self {
-        NullRemoteEndpoint
+    fn set_host_option(&mut self, host: Option<&str>) {
+        self.host = host.map(String::from);
+    }
+
+    fn clone_using_rel_ref(&self, uri: &RelRef) -> Self {
+        NullRemoteEndpoint {
+            responder: self.responder.clone(),
+            socket_addr: self.socket_addr,
+            host: self.host.clone(),
+            path: self.path.resolved_rel_ref(uri),
+        }
     }
 }
 
 /// A dummy endpoint implementation that doesn't do anything. Useful for testing.
-#[derive(Debug)]
-pub struct NullLocalEndpoint
-where
-    Self: Send + Sync;
+///
+/// Unless it was created using [`NullLocalEndpoint::with_responder`], every request
+/// sent using this endpoint times out.
+#[derive(Clone, Default)]
+pub struct NullLocalEndpoint {
+    responder: Option<Arc<NullResponder>>,
+}
+
+impl std::fmt::Debug for NullLocalEndpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NullLocalEndpoint")
+            .field("responder", &self.responder.is_some())
+            .finish()
+    }
+}
+
+impl NullLocalEndpoint {
+    /// Creates a new `NullLocalEndpoint` which lets every request time out.
+    pub fn new() -> NullLocalEndpoint {
+        Default::default()
+    }
+
+    /// Creates a new `NullLocalEndpoint` which passes every request to `responder`.
+    ///
+    /// The responder is called with each encoded request, including each of the requests
+    /// made by a send descriptor that asks for more than one, like Block2 transfers. The
+    /// encoded response it returns is handed to the send descriptor, and errors are passed
+    /// along to the send descriptor's handler as if they had come from the network.
+    ///
+    /// ```
+    /// # use async_coap::prelude::*;
+    /// # use async_coap::message::{MessageRead, MessageWrite, OwnedImmutableMessage, VecMessageEncoder};
+    /// # use async_coap::null::NullLocalEndpoint;
+    /// # use futures::executor::block_on;
+    /// let local_endpoint = NullLocalEndpoint::with_responder(|request| {
+    ///     let request = OwnedImmutableMessage::new(request.to_vec())?;
+    ///     assert_eq!(MsgCode::MethodGet, request.msg_code());
+    ///
+    ///     let mut response = VecMessageEncoder::new();
+    ///     response.set_msg_code(MsgCode::SuccessContent);
+    ///     response.append_payload_string("Hello")?;
+    ///     Ok(response.into())
+    /// });
+    ///
+    /// let remote_endpoint = local_endpoint
+    ///     .remote_endpoint_from_uri(uri!("null:///hello"))
+    ///     .unwrap();
+    ///
+    /// let response = block_on(remote_endpoint.send(CoapRequest::get().emit_any_response()))
+    ///     .unwrap();
+    ///
+    /// assert_eq!(Some("Hello"), response.payload_as_str());
+    /// ```
+    pub fn with_responder<F>(responder: F) -> NullLocalEndpoint
+    where
+        F: Fn(&[u8]) -> Result<Vec<u8>, Error> + Send + Sync + 'static,
+    {
+        NullLocalEndpoint {
+            responder: Some(Arc::new(responder)),
+        }
+    }
+}
 
 impl LocalEndpoint for NullLocalEndpoint {
     type SocketAddr = std::net::SocketAddr;
@@ -148,17 +321,32 @@ impl LocalEndpoint for NullLocalEndpoint {
 
     type RemoteEndpoint = NullRemoteEndpoint;
 
-    fn remote_endpoint<S, H, P>(&self, _addr: S, _host: Option<H>, _path: P) -> Self::RemoteEndpoint
+    fn remote_endpoint<S, H, P>(&self, addr: S, host: Option<H>, path: P) -> Self::RemoteEndpoint
     where
         S: ToSocketAddrs<SocketAddr = Self::SocketAddr, Error = Self::SocketError>,
         H: Into<String>,
         P: Into<RelRefBuf>,
     {
-        NullRemoteEndpoint
+        let socket_addr = addr
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut iter| iter.next())
+            .unwrap_or_else(null_socket_addr);
+
+        NullRemoteEndpoint {
+            responder: self.responder.clone(),
+            socket_addr,
+            host: host.map(Into::into),
+            path: path.into(),
+        }
     }
 
-    fn remote_endpoint_from_uri(&self, _uri: &Uri) -> Result<Self::RemoteEndpoint, Error> {
-        Ok(NullRemoteEndpoint)
+    fn remote_endpoint_from_uri(&self, uri: &Uri) -> Result<Self::RemoteEndpoint, Error> {
+        Ok(self.remote_endpoint(
+            null_socket_addr(),
+            None::<String>,
+            uri.trim_fragment().rel(),
+        ))
     }
 
     type LookupStream = futures::stream::Iter<std::vec::IntoIter<Self::SocketAddr>>;
@@ -170,13 +358,19 @@ impl LocalEndpoint for NullLocalEndpoint {
 
     type InboundContext = NullInboundContext;
 
-    fn send<'a, S, R, SD>(&'a self, _dest: S, _send_desc: SD) -> BoxFuture<'a, Result<R, Error>>
+    fn send<'a, S, R, SD>(&'a self, dest: S, send_desc: SD) -> BoxFuture<'a, Result<R, Error>>
     where
         S: ToSocketAddrs<SocketAddr = Self::SocketAddr, Error = Self::SocketError> + 'a,
         SD: SendDesc<Self::InboundContext, R> + 'a,
         R: Send + 'a,
     {
-        futures::future::ready(Err(Error::ResponseTimeout)).boxed()
+        let socket_addr = dest
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut iter| iter.next())
+            .unwrap_or_else(null_socket_addr);
+
+        send_scripted(self.responder.as_ref(), socket_addr, send_desc)
     }
 
     type RespondableInboundContext = NullRespondableInboundContext;
@@ -192,12 +386,12 @@ impl LocalEndpoint for NullLocalEndpoint {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::option::RequestOptions;
     use futures::executor::block_on;
-    use std::net::SocketAddr;
 
     #[test]
     fn ping() {
-        let local_endpoint = NullLocalEndpoint;
+        let local_endpoint = NullLocalEndpoint::new();
 
         let future = local_endpoint.send(
             SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 1234),
@@ -206,4 +400,45 @@ mod tests {
 
         assert_eq!(Err(Error::ResponseTimeout), block_on(future));
     }
+
+    #[test]
+    fn scripted_response() {
+        let local_endpoint = NullLocalEndpoint::with_responder(|request| {
+            let request = OwnedImmutableMessage::new(request.to_vec())?;
+            let options = RequestOptions::parse(request.options())?;
+
+            let mut response = VecMessageEncoder::new();
+            response.set_msg_code(MsgCode::SuccessContent);
+            response.append_payload_string(options.rel_ref().as_str())?;
+            Ok(response.into())
+        });
+
+        let remote_endpoint = local_endpoint
+            .remote_endpoint_from_uri(uri!("null:///a/b"))
+            .unwrap();
+
+        let response = block_on(remote_endpoint.send(CoapRequest::get().emit_any_response()))
+            .expect("Request failed");
+        assert_eq!(MsgCode::SuccessContent, response.msg_code());
+        assert_eq!(Some("a/b"), response.payload_as_str());
+
+        let response = block_on(
+            remote_endpoint.send_to(rel_ref!("c"), CoapRequest::get().emit_any_response()),
+        )
+        .expect("Request failed");
+        assert_eq!(Some("a/c"), response.payload_as_str());
+    }
+
+    #[test]
+    fn scripted_error() {
+        let local_endpoint =
+            NullLocalEndpoint::with_responder(|_request| Err(Error::HostUnreachable));
+
+        let future = local_endpoint.send(
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 1234),
+            CoapRequest::get().emit_any_response(),
+        );
+
+        assert_eq!(Err(Error::HostUnreachable), block_on(future));
+    }
 }
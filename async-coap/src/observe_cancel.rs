@@ -0,0 +1,407 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+
+use crate::send_desc::SendDesc;
+use futures::task::Context;
+use futures::task::Poll;
+use std::marker::PhantomData;
+use std::ops::Bound;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+/// A [`Stream`] that is created by [`RemoteEndpointExt::observe_cancelable`], which
+/// observes a resource until it is explicitly cancelled.
+///
+/// Simply dropping an observation only stops handling notifications; the server keeps
+/// sending them until it gets a reset in reply to one. [`CancelableObservation::cancel`]
+/// instead deregisters the observation as described in [IETF-RFC7641 Section 3.6], and
+/// waits for the server to confirm it.
+///
+/// [`Stream`]: futures::stream::Stream
+/// [`RemoteEndpointExt::observe_cancelable`]: crate::RemoteEndpointExt::observe_cancelable
+/// [IETF-RFC7641 Section 3.6]: https://tools.ietf.org/html/rfc7641#section-3.6
+pub struct CancelableObservation<'a, RE, SD, R: Send> {
+    remote_endpoint: &'a RE,
+    send_desc: SD,
+    stream: Option<SendAsStream<'a, R>>,
+    msg_token: Arc<Mutex<Option<MsgToken>>>,
+    cancellation: Option<Cancellation<'a, R>>,
+}
+
+/// The state of a [`CancelableObservation`] which was given a [`CancellationToken`].
+enum Cancellation<'a, R> {
+    /// Waiting for the token to be cancelled.
+    Armed(Box<dyn Future<Output = ()> + Send + Unpin>),
+
+    /// The token was cancelled, and the observation is being deregistered.
+    Deregistering(BoxFuture<'a, Result<R, Error>>),
+
+    /// The observation was deregistered and the stream has ended.
+    Done,
+}
+
+impl<'a, RE, SD, R: Send> core::fmt::Debug for CancelableObservation<'a, RE, SD, R> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        f.debug_struct("CancelableObservation")
+            .field("msg_token", &*self.msg_token.lock().expect("Lock failed"))
+            .finish()
+    }
+}
+
+// The send descriptor is never pinned, it is only ever cloned.
+impl<'a, RE, SD, R: Send> Unpin for CancelableObservation<'a, RE, SD, R> {}
+
+impl<'a, RE, SD, R> CancelableObservation<'a, RE, SD, R>
+where
+    RE: RemoteEndpoint,
+    SD: SendDesc<RE::InboundContext, R> + Clone + 'a,
+    R: Send + 'a,
+{
+    pub(crate) fn new(remote_endpoint: &'a RE, send_desc: SD) -> Self {
+        let msg_token: Arc<Mutex<Option<MsgToken>>> = Default::default();
+
+        let stream = remote_endpoint.send_as_stream(TrackObserveToken {
+            inner: send_desc.clone(),
+            msg_token: msg_token.clone(),
+            phantom: PhantomData,
+        });
+
+        CancelableObservation {
+            remote_endpoint,
+            send_desc,
+            stream: Some(stream),
+            msg_token,
+            cancellation: None,
+        }
+    }
+
+    /// Deregisters the observation once `token` is cancelled, like
+    /// [`cancel`](CancelableObservation::cancel) does, and then ends the stream.
+    ///
+    /// Once `token` is cancelled, no more notifications are yielded. The stream yields
+    /// [`Error::Cancelled`] after the deregistration has finished, whether or not it
+    /// succeeded, and ends right after that.
+    pub fn with_cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(Cancellation::Armed(Box::new(token.cancelled())));
+        self
+    }
+
+    fn deregister(&self) -> BoxFuture<'a, Result<R, Error>> {
+        let msg_token = *self.msg_token.lock().expect("Lock failed");
+
+        self.remote_endpoint.send(DeregisterObserve {
+            inner: self.send_desc.clone(),
+            msg_token,
+            phantom: PhantomData,
+        })
+    }
+
+    /// Deregisters the observation, returning the final representation of the resource.
+    ///
+    /// This sends the registration request again, but with the `Observe` option set to
+    /// [`OBSERVE_DEREGISTER`] and with the token of the notifications received so far.
+    /// The response to it is handled by the send descriptor like any notification, so the
+    /// send descriptor must emit a result for it, as
+    /// [`SendDescExt::emit_successful_response`] does.
+    ///
+    /// If no notification has been received yet, the token isn't known, so the server
+    /// treats the request as a plain `GET` and the registration (if any) lingers until
+    /// the server's next notification is rejected.
+    ///
+    /// [`SendDescExt::emit_successful_response`]: crate::send_desc::SendDescExt::emit_successful_response
+    ///
+    /// If the observation is already being deregistered because its cancellation token
+    /// was cancelled, the returned future resolves to the result of that instead.
+    pub fn cancel(self) -> BoxFuture<'a, Result<R, Error>> {
+        let CancelableObservation {
+            remote_endpoint,
+            send_desc,
+            stream,
+            msg_token,
+            cancellation,
+        } = self;
+
+        // Notifications arriving from now on are no longer of interest.
+        drop(stream);
+
+        match cancellation {
+            Some(Cancellation::Deregistering(future)) => return future,
+            Some(Cancellation::Done) => {
+                return futures::future::ready(Err(Error::Cancelled)).boxed();
+            }
+            _ => (),
+        }
+
+        let msg_token = *msg_token.lock().expect("Lock failed");
+
+        remote_endpoint.send(DeregisterObserve {
+            inner: send_desc,
+            msg_token,
+            phantom: PhantomData,
+        })
+    }
+}
+
+impl<'a, RE, SD, R> Stream for CancelableObservation<'a, RE, SD, R>
+where
+    RE: RemoteEndpoint,
+    SD: SendDesc<RE::InboundContext, R> + Clone + 'a,
+    R: Send + 'a,
+{
+    type Item = Result<R, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(Cancellation::Armed(cancelled)) = self.cancellation.as_mut() {
+            if cancelled.poll_unpin(cx).is_ready() {
+                // The notifications must no longer be handled once the deregistration,
+                // which has the same token, is sent.
+                self.stream = None;
+                let future = self.deregister();
+                self.cancellation = Some(Cancellation::Deregistering(future));
+            }
+        }
+
+        match self.cancellation.as_mut() {
+            Some(Cancellation::Deregistering(future)) => match future.poll_unpin(cx) {
+                Poll::Ready(_) => {
+                    self.cancellation = Some(Cancellation::Done);
+                    Poll::Ready(Some(Err(Error::Cancelled)))
+                }
+                Poll::Pending => Poll::Pending,
+            },
+            Some(Cancellation::Done) => Poll::Ready(None),
+            _ => match self.stream.as_mut() {
+                Some(stream) => stream.poll_next_unpin(cx),
+                None => Poll::Ready(None),
+            },
+        }
+    }
+}
+
+/// Records the token of the notifications, which is needed to deregister the observation.
+#[derive(Debug)]
+struct TrackObserveToken<SD, IC> {
+    inner: SD,
+    msg_token: Arc<Mutex<Option<MsgToken>>>,
+    phantom: PhantomData<IC>,
+}
+
+impl<SD, IC, R> SendDesc<IC, R> for TrackObserveToken<SD, IC>
+where
+    SD: SendDesc<IC, R>,
+    IC: InboundContext,
+    R: Send,
+{
+    send_desc_passthru_timing!(inner);
+    send_desc_passthru_options!(inner);
+    send_desc_passthru_payload!(inner);
+    send_desc_passthru_supports_option!(inner);
+
+    fn handler(&mut self, context: Result<&IC, Error>) -> Result<ResponseStatus<R>, Error> {
+        if let Ok(context) = context {
+            *self.msg_token.lock().expect("Lock failed") = Some(context.message().msg_token());
+        }
+
+        self.inner.handler(context)
+    }
+}
+
+/// Sends the registration request of `inner` as a deregistration: with `Observe` set to
+/// [`OBSERVE_DEREGISTER`], and with the token of the registration if it is known.
+#[derive(Debug)]
+struct DeregisterObserve<SD, IC> {
+    inner: SD,
+    msg_token: Option<MsgToken>,
+    phantom: PhantomData<IC>,
+}
+
+impl<SD, IC, R> SendDesc<IC, R> for DeregisterObserve<SD, IC>
+where
+    SD: SendDesc<IC, R>,
+    IC: InboundContext,
+    R: Send,
+{
+    send_desc_passthru_timing!(inner);
+    send_desc_passthru_handler!(inner, R);
+
+    fn write_options(
+        &self,
+        msg: &mut dyn OptionInsert,
+        socket_addr: &IC::SocketAddr,
+        start: Bound<OptionNumber>,
+        end: Bound<OptionNumber>,
+    ) -> Result<(), Error> {
+        let key = option::OBSERVE;
+
+        let in_range = match start {
+            Bound::Included(b) => b <= key.0,
+            Bound::Excluded(b) => b < key.0,
+            Bound::Unbounded => true,
+        } && match end {
+            Bound::Included(b) => key.0 <= b,
+            Bound::Excluded(b) => key.0 < b,
+            Bound::Unbounded => true,
+        };
+
+        if !in_range {
+            return self.inner.write_options(msg, socket_addr, start, end);
+        }
+
+        // The `Observe` option written by `inner` is replaced, not added to.
+        self.inner
+            .write_options(msg, socket_addr, start, Bound::Excluded(key.0))?;
+        msg.insert_option(key, OBSERVE_DEREGISTER)?;
+        self.inner
+            .write_options(msg, socket_addr, Bound::Excluded(key.0), end)
+    }
+
+    fn write_payload(
+        &self,
+        msg: &mut dyn MessageWrite,
+        socket_addr: &IC::SocketAddr,
+    ) -> Result<(), Error> {
+        self.inner.write_payload(msg, socket_addr)?;
+
+        if let Some(msg_token) = self.msg_token {
+            msg.set_msg_token(msg_token);
+        }
+
+        Ok(())
+    }
+
+    fn payload_size_hint(&self) -> usize {
+        self.inner.payload_size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datagram::{
+        DatagramLocalEndpoint, DatagramRespondableInboundContext, LoopbackSocket,
+        LoopbackSocketAddr,
+    };
+    use crate::option::OptionIteratorExt;
+    use futures::executor::block_on;
+    use futures::future::{select, Either};
+
+    type Requests = Mutex<Vec<(Option<u32>, MsgToken)>>;
+
+    /// Records the `Observe` option and token of `context`, and responds to it.
+    fn handle_observe(
+        requests: &Requests,
+        context: &DatagramRespondableInboundContext<LoopbackSocketAddr>,
+    ) -> Result<(), Error> {
+        let request = context.message();
+        let observe = request
+            .options()
+            .find_next_of(option::OBSERVE)
+            .transpose()?;
+        requests
+            .lock()
+            .unwrap()
+            .push((observe, request.msg_token()));
+
+        context.respond(|msg_out| {
+            msg_out.set_msg_code(MsgCode::SuccessContent);
+            if observe == Some(OBSERVE_REGISTER) {
+                msg_out.insert_option(option::OBSERVE, 1)?;
+                msg_out.append_payload_string("registered")
+            } else {
+                msg_out.append_payload_string("deregistered")
+            }
+        })
+    }
+
+    #[test]
+    fn cancel_observation() {
+        let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+        let remote_endpoint = local_endpoint.remote_endpoint(
+            LoopbackSocketAddr::Unicast,
+            None::<String>,
+            rel_ref!(""),
+        );
+        let requests = Requests::default();
+
+        let handler = |context: &_| handle_observe(&requests, context);
+
+        let future = async {
+            let mut observation = remote_endpoint
+                .observe_cancelable(CoapRequest::observe().emit_successful_response());
+
+            let first = observation.next().await.unwrap().unwrap();
+            assert_eq!(Some(1), first.observe());
+            assert_eq!(Some("registered"), first.payload_as_str());
+
+            observation.cancel().await.unwrap()
+        };
+        let future_receive = local_endpoint.receive_loop(handler);
+
+        let last = match block_on(select(future.boxed(), future_receive)) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((last, _)) => last,
+        };
+
+        assert_eq!(None, last.observe());
+        assert_eq!(Some("deregistered"), last.payload_as_str());
+
+        let requests = requests.into_inner().unwrap();
+        assert_eq!(2, requests.len());
+        assert_eq!(Some(OBSERVE_REGISTER), requests[0].0);
+        assert_eq!(Some(OBSERVE_DEREGISTER), requests[1].0);
+        assert_eq!(requests[0].1, requests[1].1);
+    }
+
+    #[test]
+    fn cancel_observation_with_token() {
+        let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+        let remote_endpoint = local_endpoint.remote_endpoint(
+            LoopbackSocketAddr::Unicast,
+            None::<String>,
+            rel_ref!(""),
+        );
+        let requests = Requests::default();
+        let token = CancellationToken::new();
+
+        let handler = |context: &_| handle_observe(&requests, context);
+
+        let future = async {
+            let mut observation = remote_endpoint
+                .observe_cancelable(CoapRequest::observe().emit_successful_response())
+                .with_cancellation_token(token.clone());
+
+            let first = observation.next().await.unwrap().unwrap();
+            assert_eq!(Some("registered"), first.payload_as_str());
+
+            token.cancel();
+            let results = observation.collect::<Vec<_>>().await;
+            assert_eq!(1, results.len());
+            assert_eq!(Some(&Error::Cancelled), results[0].as_ref().err());
+        };
+        let future_receive = local_endpoint.receive_loop(handler);
+
+        if let Either::Right(_) = block_on(select(future.boxed(), future_receive)) {
+            panic!("Receive future finished unexpectedly");
+        }
+
+        let requests = requests.into_inner().unwrap();
+        assert_eq!(2, requests.len());
+        assert_eq!(Some(OBSERVE_REGISTER), requests[0].0);
+        assert_eq!(Some(OBSERVE_DEREGISTER), requests[1].0);
+        assert_eq!(requests[0].1, requests[1].1);
+    }
+}
@@ -0,0 +1,389 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+
+use crate::send_desc::SendDesc;
+use futures::task::Context;
+use futures::task::Poll;
+use futures_timer::Delay;
+use std::marker::PhantomData;
+use std::ops::Bound;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Determines how a [`ReconnectingObservation`] re-establishes an observation after a
+/// transient failure.
+///
+/// The first attempt to re-register is made after `initial_backoff`. The wait doubles
+/// after each failed attempt, up to `max_backoff`. Once an attempt would start more than
+/// `max_gap` after the observation was lost, the stream gives up.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct ObserveReconnectPolicy {
+    /// How long to wait before the first attempt to re-register.
+    pub initial_backoff: Duration,
+
+    /// The longest to wait between two attempts to re-register.
+    pub max_backoff: Duration,
+
+    /// How long to keep trying to re-register, measured from when the observation was
+    /// lost.
+    pub max_gap: Duration,
+}
+
+impl Default for ObserveReconnectPolicy {
+    /// The default policy starts with a backoff of one second, doubling up to one minute,
+    /// and gives up after ten minutes.
+    fn default() -> Self {
+        ObserveReconnectPolicy {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            max_gap: Duration::from_secs(10 * 60),
+        }
+    }
+}
+
+/// Item yielded by a [`ReconnectingObservation`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ObserveEvent<R> {
+    /// A notification for the observed resource.
+    Notification(R),
+
+    /// The observation was lost and has been re-established. This is yielded right before
+    /// the first notification received after re-registering.
+    ///
+    /// `missed_possible` is false if the `Observe` sequence numbers show that no
+    /// notifications were sent while the observation was lost. Otherwise, the consumer
+    /// may have missed intermediate states of the resource.
+    Resumed {
+        /// True if notifications may have been missed while the observation was lost.
+        missed_possible: bool,
+    },
+}
+
+/// A [`Stream`] that is created by [`RemoteEndpointExt::observe_with_reconnect`], which
+/// observes a resource and re-registers the observation after transient failures.
+///
+/// The observation is considered lost when it fails with [`Error::IOError`],
+/// [`Error::HostUnreachable`], [`Error::ObserveRegistrationTimeout`], or
+/// [`Error::ObservationLapsed`], or when it ends (for example because it timed out).
+/// Instead of ending, the stream then re-sends the registration as described by the
+/// [`ObserveReconnectPolicy`] and yields [`ObserveEvent::Resumed`] once it has been
+/// re-established. Any other error is yielded and ends the stream, as does giving up.
+///
+/// [`Stream`]: futures::stream::Stream
+/// [`RemoteEndpointExt::observe_with_reconnect`]: crate::RemoteEndpointExt::observe_with_reconnect
+pub struct ReconnectingObservation<'a, RE, SD, R: Send> {
+    remote_endpoint: &'a RE,
+    send_desc: SD,
+    policy: ObserveReconnectPolicy,
+    stream: Option<SendAsStream<'a, R>>,
+    last_seq: Arc<Mutex<Option<ObserveSeq>>>,
+    seq_before_gap: Option<ObserveSeq>,
+    gap_start: Option<Instant>,
+    backoff: Duration,
+    delay: Option<Delay>,
+    pending: Option<R>,
+    finished: bool,
+}
+
+impl<'a, RE, SD, R: Send> core::fmt::Debug for ReconnectingObservation<'a, RE, SD, R> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        f.debug_struct("ReconnectingObservation")
+            .field("policy", &self.policy)
+            .field("is_registered", &self.stream.is_some())
+            .field("gap_start", &self.gap_start)
+            .field("backoff", &self.backoff)
+            .field("finished", &self.finished)
+            .finish()
+    }
+}
+
+// The send descriptor is never pinned, it is only ever cloned.
+impl<'a, RE, SD, R: Send> Unpin for ReconnectingObservation<'a, RE, SD, R> {}
+
+impl<'a, RE, SD, R: Send> ReconnectingObservation<'a, RE, SD, R> {
+    pub(crate) fn new(
+        remote_endpoint: &'a RE,
+        send_desc: SD,
+        policy: ObserveReconnectPolicy,
+    ) -> Self {
+        ReconnectingObservation {
+            remote_endpoint,
+            send_desc,
+            policy,
+            stream: None,
+            last_seq: Default::default(),
+            seq_before_gap: None,
+            gap_start: None,
+            backoff: policy.initial_backoff,
+            delay: None,
+            pending: None,
+            finished: false,
+        }
+    }
+
+    /// Schedules the next attempt to re-register, or returns `err` if the policy says to
+    /// give up.
+    fn reconnect_after(&mut self, err: Error) -> Option<Error> {
+        let now = Instant::now();
+
+        self.stream = None;
+
+        if self.gap_start.is_none() {
+            self.gap_start = Some(now);
+            self.seq_before_gap = *self.last_seq.lock().expect("Lock failed");
+        }
+
+        let gap_start = self.gap_start.unwrap();
+
+        if now + self.backoff > gap_start + self.policy.max_gap {
+            debug!("Giving up on observation after {:?}", now - gap_start);
+            self.finished = true;
+            return Some(err);
+        }
+
+        debug!(
+            "Observation lost ({}), re-registering in {:?}",
+            err, self.backoff
+        );
+        self.delay = Some(Delay::new(self.backoff));
+        self.backoff = std::cmp::min(self.backoff * 2, self.policy.max_backoff);
+
+        None
+    }
+
+    fn missed_possible(&self) -> bool {
+        match (
+            self.seq_before_gap,
+            *self.last_seq.lock().expect("Lock failed"),
+        ) {
+            // Nothing had been received yet, so there was nothing to fall behind on.
+            (None, _) => false,
+
+            // The server resends the current sequence number if nothing changed.
+            (Some(before), Some(after)) => after.steps_since(before) > 1,
+
+            (Some(_), None) => true,
+        }
+    }
+}
+
+impl<'a, RE, SD, R> ReconnectingObservation<'a, RE, SD, R>
+where
+    RE: RemoteEndpoint,
+    SD: SendDesc<RE::InboundContext, R> + Clone + 'a,
+    R: Send + 'a,
+{
+    fn register(&mut self) {
+        let send_desc = TrackObserveSeq {
+            inner: self.send_desc.clone(),
+            last_seq: self.last_seq.clone(),
+            phantom: PhantomData,
+        };
+
+        self.stream = Some(self.remote_endpoint.send_as_stream(send_desc));
+    }
+}
+
+impl<'a, RE, SD, R> Stream for ReconnectingObservation<'a, RE, SD, R>
+where
+    RE: RemoteEndpoint,
+    SD: SendDesc<RE::InboundContext, R> + Clone + 'a,
+    R: Send + 'a,
+{
+    type Item = Result<ObserveEvent<R>, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(x) = self.pending.take() {
+                return Poll::Ready(Some(Ok(ObserveEvent::Notification(x))));
+            }
+
+            if self.finished {
+                return Poll::Ready(None);
+            }
+
+            if let Some(delay) = self.delay.as_mut() {
+                match delay.poll_unpin(cx) {
+                    Poll::Ready(()) => self.delay = None,
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+
+            if self.stream.is_none() {
+                self.register();
+            }
+
+            let err = match self.stream.as_mut().unwrap().poll_next_unpin(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Some(Ok(x))) => {
+                    if self.gap_start.take().is_none() {
+                        return Poll::Ready(Some(Ok(ObserveEvent::Notification(x))));
+                    }
+
+                    let missed_possible = self.missed_possible();
+                    self.backoff = self.policy.initial_backoff;
+                    self.pending = Some(x);
+                    return Poll::Ready(Some(Ok(ObserveEvent::Resumed { missed_possible })));
+                }
+                Poll::Ready(Some(Err(err @ Error::IOError)))
+                | Poll::Ready(Some(Err(err @ Error::HostUnreachable)))
+                | Poll::Ready(Some(Err(err @ Error::ObserveRegistrationTimeout)))
+                | Poll::Ready(Some(Err(err @ Error::ObservationLapsed))) => err,
+                Poll::Ready(Some(Err(err))) => {
+                    self.finished = true;
+                    return Poll::Ready(Some(Err(err)));
+                }
+                Poll::Ready(None) => Error::ResponseTimeout,
+            };
+
+            if let Some(err) = self.reconnect_after(err) {
+                return Poll::Ready(Some(Err(err)));
+            }
+        }
+    }
+}
+
+/// Records the `Observe` option of each response, so that the sequence numbers from
+/// before and after re-registering can be compared.
+#[derive(Debug)]
+struct TrackObserveSeq<SD, IC> {
+    inner: SD,
+    last_seq: Arc<Mutex<Option<ObserveSeq>>>,
+    phantom: PhantomData<IC>,
+}
+
+impl<SD, IC, R> SendDesc<IC, R> for TrackObserveSeq<SD, IC>
+where
+    SD: SendDesc<IC, R>,
+    IC: InboundContext,
+    R: Send,
+{
+    send_desc_passthru_timing!(inner);
+    send_desc_passthru_options!(inner);
+    send_desc_passthru_payload!(inner);
+    send_desc_passthru_supports_option!(inner);
+
+    fn handler(&mut self, context: Result<&IC, Error>) -> Result<ResponseStatus<R>, Error> {
+        if let Ok(context) = context {
+            if !context.is_dupe() {
+                let seq = context
+                    .message()
+                    .options()
+                    .find_next_of(option::OBSERVE)
+                    .and_then(Result::ok)
+                    .map(ObserveSeq::new);
+                *self.last_seq.lock().expect("Lock failed") = seq;
+            }
+        }
+
+        self.inner.handler(context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datagram::{
+        DatagramLocalEndpoint, DatagramRespondableInboundContext, LoopbackSocket,
+        LoopbackSocketAddr,
+    };
+    use futures::executor::block_on;
+    use futures::future::{select, Either};
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn observe_with_reconnect(seq_step: u32) -> Vec<ObserveEvent<Option<u32>>> {
+        let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+        let remote_endpoint = local_endpoint.remote_endpoint(
+            LoopbackSocketAddr::Unicast,
+            None::<String>,
+            rel_ref!(""),
+        );
+        let seq = AtomicU32::new(1);
+
+        let handler = |context: &DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+            let seq = seq.fetch_add(seq_step, Ordering::Relaxed);
+            context.respond(|msg_out| {
+                msg_out.set_msg_code(MsgCode::SuccessContent);
+                msg_out.insert_option(option::OBSERVE, seq)
+            })
+        };
+
+        let policy = ObserveReconnectPolicy {
+            initial_backoff: Duration::from_millis(10),
+            ..Default::default()
+        };
+
+        let future = async {
+            let mut stream = remote_endpoint
+                .observe_with_reconnect(CoapRequest::observe().emit_successful_response(), policy)
+                .map(|event| match event.unwrap() {
+                    ObserveEvent::Notification(response) => {
+                        ObserveEvent::Notification(response.observe())
+                    }
+                    ObserveEvent::Resumed { missed_possible } => {
+                        ObserveEvent::Resumed { missed_possible }
+                    }
+                });
+            let mut events = vec![stream.next().await.unwrap()];
+
+            // Sending anything to an unreachable host fails the observation as well.
+            local_endpoint.socket().set_unreachable(true);
+            let _ = local_endpoint
+                .send(LoopbackSocketAddr::Unicast, Ping::new())
+                .await;
+            local_endpoint.socket().set_unreachable(false);
+
+            events.push(stream.next().await.unwrap());
+            events.push(stream.next().await.unwrap());
+            events
+        };
+        let future_receive = local_endpoint.receive_loop(handler);
+
+        let events = match block_on(select(future.boxed(), future_receive)) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((events, _)) => events,
+        };
+
+        events
+    }
+
+    #[test]
+    fn observe_with_reconnect_resumed() {
+        assert_eq!(
+            vec![
+                ObserveEvent::Notification(Some(1)),
+                ObserveEvent::Resumed {
+                    missed_possible: false
+                },
+                ObserveEvent::Notification(Some(2)),
+            ],
+            observe_with_reconnect(1)
+        );
+
+        assert_eq!(
+            vec![
+                ObserveEvent::Notification(Some(1)),
+                ObserveEvent::Resumed {
+                    missed_possible: true
+                },
+                ObserveEvent::Notification(Some(6)),
+            ],
+            observe_with_reconnect(5)
+        );
+    }
+}
@@ -0,0 +1,165 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+use std::time::Duration;
+
+/// Type for holding the 24-bit sequence number carried by the `Observe` option of a
+/// notification.
+///
+/// Sequence numbers wrap around, so they can't be ordered like integers. Instead,
+/// [`ObserveSeq::is_fresher_than`] implements the comparison described in
+/// [IETF-RFC7641 Section 3.4], which clients use to discard notifications that were
+/// reordered in transit. Because that comparison isn't transitive, this type
+/// deliberately doesn't implement [`PartialOrd`].
+///
+/// ```
+/// # use async_coap::ObserveSeq;
+/// # use std::time::{Duration, Instant};
+/// let t1 = Instant::now();
+/// let t2 = t1 + Duration::from_secs(1);
+///
+/// // 0 follows 0xFFFFFF, even though it is smaller.
+/// assert!(ObserveSeq::new(0).is_fresher_than(t2, ObserveSeq::new(0xFF_FFFF), t1));
+/// assert!(!ObserveSeq::new(0xFF_FFFF).is_fresher_than(t2, ObserveSeq::new(0), t1));
+///
+/// // Anything received long enough after the previous notification is fresher.
+/// let t3 = t1 + ObserveSeq::FRESHNESS_WINDOW + Duration::from_secs(1);
+/// assert!(ObserveSeq::new(3).is_fresher_than(t3, ObserveSeq::new(7), t1));
+/// ```
+///
+/// [IETF-RFC7641 Section 3.4]: https://tools.ietf.org/html/rfc7641#section-3.4
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub struct ObserveSeq(u32);
+
+impl ObserveSeq {
+    /// The largest sequence number, after which the sequence wraps around to zero.
+    pub const MAX: u32 = 0xFF_FFFF;
+
+    /// How long after a notification any other notification is considered fresher,
+    /// regardless of its sequence number.
+    pub const FRESHNESS_WINDOW: Duration = Duration::from_secs(128);
+
+    /// Creates a new `ObserveSeq` from the lower 24 bits of `value`.
+    pub const fn new(value: u32) -> ObserveSeq {
+        ObserveSeq(value & ObserveSeq::MAX)
+    }
+
+    /// Returns the sequence number as an integer.
+    pub const fn value(self) -> u32 {
+        self.0
+    }
+
+    /// Returns the sequence number which follows this one, wrapping around to zero after
+    /// [`ObserveSeq::MAX`].
+    pub const fn next(self) -> ObserveSeq {
+        ObserveSeq::new(self.0.wrapping_add(1))
+    }
+
+    /// Returns how many times [`ObserveSeq::next`] must be applied to `earlier` to reach
+    /// this sequence number.
+    pub const fn steps_since(self, earlier: ObserveSeq) -> u32 {
+        self.0.wrapping_sub(earlier.0) & ObserveSeq::MAX
+    }
+
+    /// Returns true if this sequence number is newer than `other`, considering only the
+    /// sequence numbers and not when they were received.
+    ///
+    /// A sequence number is newer if it is less than 2<sup>23</sup> steps after `other`.
+    pub const fn is_newer_than(self, other: ObserveSeq) -> bool {
+        let steps = self.steps_since(other);
+        steps != 0 && steps < (1 << 23)
+    }
+
+    /// Returns true if a notification with this sequence number, received at `received`,
+    /// is fresher than one with the sequence number `other`, received at `other_received`.
+    ///
+    /// This is the case if this sequence number [is newer](ObserveSeq::is_newer_than), or
+    /// if it was received more than [`ObserveSeq::FRESHNESS_WINDOW`] after the other one.
+    /// The latter covers servers which have restarted or whose sequence numbers have
+    /// otherwise jumped.
+    pub fn is_fresher_than<I: MonotonicInstant>(
+        self,
+        received: I,
+        other: ObserveSeq,
+        other_received: I,
+    ) -> bool {
+        self.is_newer_than(other)
+            || received.saturating_duration_since(other_received) > ObserveSeq::FRESHNESS_WINDOW
+    }
+}
+
+impl From<u32> for ObserveSeq {
+    fn from(value: u32) -> Self {
+        ObserveSeq::new(value)
+    }
+}
+
+impl From<ObserveSeq> for u32 {
+    fn from(seq: ObserveSeq) -> Self {
+        seq.value()
+    }
+}
+
+impl std::fmt::Display for ObserveSeq {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wraparound() {
+        assert_eq!(ObserveSeq::new(0), ObserveSeq::new(ObserveSeq::MAX).next());
+        assert_eq!(ObserveSeq::new(5), ObserveSeq::new(0x100_0005));
+        assert_eq!(
+            2,
+            ObserveSeq::new(1).steps_since(ObserveSeq::new(ObserveSeq::MAX))
+        );
+    }
+
+    #[test]
+    fn is_newer_than() {
+        let seq = ObserveSeq::new;
+
+        assert!(seq(2).is_newer_than(seq(1)));
+        assert!(!seq(1).is_newer_than(seq(2)));
+        assert!(!seq(1).is_newer_than(seq(1)));
+        assert!(seq(0).is_newer_than(seq(ObserveSeq::MAX)));
+        assert!(seq((1 << 23) - 1).is_newer_than(seq(0)));
+        assert!(!seq(1 << 23).is_newer_than(seq(0)));
+        assert!(!seq(0).is_newer_than(seq(1 << 23)));
+        assert!(seq(0).is_newer_than(seq((1 << 23) + 1)));
+    }
+
+    #[test]
+    fn is_fresher_than() {
+        let t1 = MillisInstant(1_000);
+        let t2 = MillisInstant(2_000);
+        let later = t1.saturating_add(ObserveSeq::FRESHNESS_WINDOW);
+
+        assert!(ObserveSeq::new(8).is_fresher_than(t2, ObserveSeq::new(7), t1));
+        assert!(!ObserveSeq::new(6).is_fresher_than(t2, ObserveSeq::new(7), t1));
+        assert!(!ObserveSeq::new(6).is_fresher_than(later, ObserveSeq::new(7), t1));
+        assert!(ObserveSeq::new(6).is_fresher_than(
+            later.saturating_add(Duration::from_millis(1)),
+            ObserveSeq::new(7),
+            t1
+        ));
+    }
+}
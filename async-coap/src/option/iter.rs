@@ -38,6 +38,15 @@ impl<'a> OptionIterator<'a> {
         }
     }
 
+    /// Creates an iterator over options in `buffer` which follow an option with the
+    /// number `last_option`.
+    pub(super) fn resume(buffer: &'a [u8], last_option: OptionNumber) -> OptionIterator<'a> {
+        OptionIterator {
+            iter: buffer.iter(),
+            last_option,
+        }
+    }
+
     /// Returns the unread remaining options as a byte slice.
     pub fn as_slice(&self) -> &'a [u8] {
         self.iter.as_slice()
@@ -133,37 +142,96 @@ pub trait OptionIteratorExt<'a>: Iterator<Item = Result<(OptionNumber, &'a [u8])
 
     /// Extracts a URI relative-reference from the remaining URI_PATH and URI_QUERY options,
     /// moving the iterator past them.
+    ///
+    /// The path segments and query items are percent-encoded as necessary, including
+    /// characters like `/` in segments and `&` in query items, so that the reference
+    /// decomposes into the same options again. If there are no URI_PATH options, the
+    /// path of the reference is empty.
+    ///
+    /// See [`OptionIteratorExt::extract_full_uri`] for a version which includes the
+    /// URI_HOST and URI_PORT options, and [`OptionIteratorExt::uri_path_segments`] for
+    /// accessing the segments without any allocation.
     fn extract_uri(&self) -> Result<RelRefBuf, Error>
+    where
+        Self: Sized + Clone,
+    {
+        rel_ref_from_parts(self.uri_path_segments(), self.uri_query_items())
+    }
+
+    /// Extracts an absolute URI from the remaining URI_HOST, URI_PORT, URI_PATH, and
+    /// URI_QUERY options, as described in [IETF-RFC7252 Section 6.5].
+    ///
+    /// `default_host` and `default_port` are used when there is no URI_HOST or URI_PORT
+    /// option, respectively, and would typically describe the local socket address that the
+    /// request was received on. IPv6 addresses are enclosed in brackets automatically. If
+    /// `default_port` is `None` and there is no URI_PORT option, the URI has no port.
+    ///
+    /// Returns [`Error::InvalidArgument`] if `scheme` isn't a valid URI scheme.
+    ///
+    /// [IETF-RFC7252 Section 6.5]: https://tools.ietf.org/html/rfc7252#section-6.5
+    fn extract_full_uri(
+        &self,
+        scheme: &str,
+        default_host: &str,
+        default_port: Option<u16>,
+    ) -> Result<UriBuf, Error>
     where
         Self: Sized + Clone,
     {
         let mut copy = self.clone();
+        let host = copy.find_next_of(option::URI_HOST).transpose()?;
+        let port = copy
+            .find_next_of(option::URI_PORT)
+            .transpose()?
+            .or(default_port);
+
         let mut buf = String::new();
+        buf.push_str(scheme);
+        buf.push_str("://");
 
-        while let Some(seg) = copy.find_next_of(option::URI_PATH).transpose()? {
-            if !buf.is_empty() {
-                buf.push('/');
-            }
-            buf.extend(seg.escape_uri());
+        let host = host.unwrap_or(default_host);
+        if host.parse::<std::net::Ipv6Addr>().is_ok() {
+            buf.push('[');
+            buf.push_str(host);
+            buf.push(']');
+        } else {
+            buf.extend(host.escape_uri().for_authority());
         }
 
-        let mut has_query = false;
+        if let Some(port) = port {
+            buf.push(':');
+            buf.push_str(&port.to_string());
+        }
 
-        while let Some(item) = copy.find_next_of(option::URI_QUERY).transpose()? {
-            if has_query {
-                buf.push('&');
-            } else {
-                buf.push('?');
-                has_query = true;
-            }
-            buf.extend(item.escape_uri().for_query());
+        let mut segments = copy.uri_path_segments().peekable();
+        if segments.peek().is_none() {
+            buf.push('/');
+        }
+        for segment in segments {
+            buf.push('/');
+            push_path_segment(&mut buf, segment?);
         }
+        push_query_items(&mut buf, copy.uri_query_items())?;
 
-        let mut ret = RelRefBuf::from_string(buf).expect("Constructed URI was malformed");
+        UriBuf::from_string(buf).map_err(|_| Error::InvalidArgument)
+    }
 
-        ret.disambiguate();
+    /// Returns an iterator over the values of the remaining URI_PATH options, which are
+    /// *not* percent-encoded. The values are borrowed from the underlying message.
+    fn uri_path_segments(&self) -> OptionStrIter<Self>
+    where
+        Self: Sized + Clone,
+    {
+        OptionStrIter::new(self.clone(), OptionNumber::URI_PATH)
+    }
 
-        Ok(ret)
+    /// Returns an iterator over the values of the remaining URI_QUERY options, which are
+    /// *not* percent-encoded. The values are borrowed from the underlying message.
+    fn uri_query_items(&self) -> OptionStrIter<Self>
+    where
+        Self: Sized + Clone,
+    {
+        OptionStrIter::new(self.clone(), OptionNumber::URI_QUERY)
     }
 
     /// Extracts a URI relative-reference from the remaining LOCATION_PATH and LOCATION_QUERY options,
@@ -172,33 +240,121 @@ pub trait OptionIteratorExt<'a>: Iterator<Item = Result<(OptionNumber, &'a [u8])
     where
         Self: Sized + Clone,
     {
-        let mut copy = self.clone();
-        let mut buf = String::new();
+        // TODO(#6): Check out those reserved Location-* options and fail if found.
+        //       See <https://tools.ietf.org/html/rfc7252#section-5.10.7> for more info.
 
-        while let Some(seg) = copy.find_next_of(option::LOCATION_PATH).transpose()? {
-            if !buf.is_empty() {
-                buf.push('/');
-            }
-            buf.extend(seg.escape_uri());
+        rel_ref_from_parts(
+            OptionStrIter::new(self.clone(), OptionNumber::LOCATION_PATH),
+            OptionStrIter::new(self.clone(), OptionNumber::LOCATION_QUERY),
+        )
+    }
+}
+
+/// Iterator over the string values of the remaining options with a specific number,
+/// returned by methods like [`OptionIteratorExt::uri_path_segments`].
+#[derive(Debug, Clone)]
+pub struct OptionStrIter<I> {
+    iter: I,
+    key: OptionNumber,
+    failed: bool,
+}
+
+impl<I> OptionStrIter<I> {
+    fn new(iter: I, key: OptionNumber) -> OptionStrIter<I> {
+        OptionStrIter {
+            iter,
+            key,
+            failed: false,
         }
+    }
+}
 
-        let mut has_query = false;
+impl<'a, I> Iterator for OptionStrIter<I>
+where
+    I: Iterator<Item = Result<(OptionNumber, &'a [u8]), Error>> + Clone,
+{
+    type Item = Result<&'a str, Error>;
 
-        while let Some(item) = copy.find_next_of(option::LOCATION_QUERY).transpose()? {
-            if has_query {
-                buf.push('&');
-            } else {
-                buf.push('?');
-                has_query = true;
-            }
-            buf.extend(item.escape_uri().for_query());
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
         }
 
-        // TODO(#6): Check out those reserved Location-* options and fail if found.
-        //       See <https://tools.ietf.org/html/rfc7252#section-5.10.7> for more info.
+        let ret = self.iter.find_next(self.key).map(|result| {
+            result
+                .and_then(|(_, value)| core::str::from_utf8(value).map_err(|_| Error::ParseFailure))
+        });
 
-        Ok(RelRefBuf::from_string(buf).expect("Constructed URI was malformed"))
+        // Errors aren't skipped by `find_next`, so we stop after the first one.
+        self.failed = matches!(ret, Some(Err(_)));
+
+        ret
+    }
+}
+
+/// Appends `segment` to `buf`, percent-encoded as a path segment. Dot-segments are
+/// encoded so that they aren't removed when the reference is resolved.
+pub(crate) fn push_path_segment(buf: &mut String, segment: &str) {
+    match segment {
+        "." => buf.push_str("%2E"),
+        ".." => buf.push_str("%2E%2E"),
+        segment => buf.extend(segment.escape_uri()),
+    }
+}
+
+/// Appends `items` to `buf` as a percent-encoded query. Characters which are used to
+/// separate query items are encoded too.
+fn push_query_items<'a, Q>(buf: &mut String, items: Q) -> Result<(), Error>
+where
+    Q: IntoIterator<Item = Result<&'a str, Error>>,
+{
+    for (i, item) in items.into_iter().enumerate() {
+        buf.push(if i == 0 { '?' } else { '&' });
+        push_query_item(buf, item?);
+    }
+    Ok(())
+}
+
+/// Appends `item` to `buf`, percent-encoded as a single query item.
+pub(crate) fn push_query_item(buf: &mut String, item: &str) {
+    for c in item.escape_uri() {
+        match c {
+            '&' => buf.push_str("%26"),
+            ';' => buf.push_str("%3B"),
+            '+' => buf.push_str("%2B"),
+            c => buf.push(c),
+        }
+    }
+}
+
+/// Constructs a relative reference from the given path segments and query items, which
+/// are percent-encoded as necessary.
+pub(crate) fn rel_ref_from_parts<'a, P, Q>(segments: P, items: Q) -> Result<RelRefBuf, Error>
+where
+    P: IntoIterator<Item = Result<&'a str, Error>>,
+    Q: IntoIterator<Item = Result<&'a str, Error>>,
+{
+    let mut buf = String::new();
+
+    for (i, segment) in segments.into_iter().enumerate() {
+        let segment = segment?;
+        if i != 0 {
+            buf.push('/');
+        } else if segment.is_empty() {
+            // A leading empty segment would otherwise be lost, since the path of the
+            // reference would start with a slash (or be empty).
+            buf.push_str("./");
+        }
+        push_path_segment(&mut buf, segment);
     }
+
+    push_query_items(&mut buf, items)?;
+
+    let mut ret = RelRefBuf::from_string(buf).expect("Constructed URI was malformed");
+
+    ret.disambiguate();
+
+    Ok(ret)
 }
 
 impl<'a, I> OptionIteratorExt<'a> for I
@@ -229,3 +385,146 @@ where
         Some(Ok(next_value))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::encoder::OptionEncoder;
+    use super::*;
+
+    fn with_options<F, T>(f: F, g: impl FnOnce(OptionIterator<'_>) -> T) -> T
+    where
+        F: FnOnce(&mut OptionEncoder<'_>) -> Result<(), Error>,
+    {
+        let mut buffer = [0u8; 256];
+        let mut encoder = OptionEncoder::new(&mut buffer);
+        f(&mut encoder).unwrap();
+        let (options, _) = encoder.finish();
+        g(OptionIterator::new(options))
+    }
+
+    #[test]
+    fn extract_uri() {
+        let uri = with_options(|_| Ok(()), |iter| iter.extract_uri());
+        assert_eq!("", uri.unwrap().as_str());
+
+        let uri = with_options(
+            |encoder| {
+                encoder.insert_option(URI_HOST, "example.com")?;
+                encoder.insert_option(URI_PATH, "a:b")?;
+                encoder.insert_option(URI_PATH, "c/d")?;
+                encoder.insert_option(URI_PATH, "..")?;
+                encoder.insert_option(URI_QUERY, "e=f&g")?;
+                encoder.insert_option(URI_QUERY, "h i+;")
+            },
+            |iter| iter.extract_uri(),
+        );
+        assert_eq!(
+            "a%3Ab/c%2Fd/%2E%2E?e=f%26g&h%20i%2B%3B",
+            uri.unwrap().as_str()
+        );
+
+        let uri = with_options(
+            |encoder| {
+                encoder.insert_option(URI_PATH, "")?;
+                encoder.insert_option(URI_PATH, "a")
+            },
+            |iter| iter.extract_uri(),
+        );
+        assert_eq!(".//a", uri.unwrap().as_str());
+
+        let uri = with_options(
+            |encoder| encoder.insert_option(URI_QUERY, "a"),
+            |iter| iter.extract_uri(),
+        );
+        assert_eq!("?a", uri.unwrap().as_str());
+    }
+
+    #[test]
+    fn registered_option_keys() {
+        with_options(
+            |encoder| {
+                encoder.insert_option(HOP_LIMIT, 16)?;
+                encoder.insert_option(EDHOC, ())?;
+                encoder.insert_option(ECHO, &[1u8, 2, 3][..])?;
+                encoder.insert_option(NO_RESPONSE, crate::NO_RESPONSE_ERROR)?;
+                encoder.insert_option(OCF_CONTENT_FORMAT_VERSION, 0x0800)
+            },
+            |mut iter| {
+                assert_eq!(Some(Ok(16)), iter.find_next_of(HOP_LIMIT));
+                assert_eq!(Some(Ok(())), iter.find_next_of(EDHOC));
+                assert_eq!(Some(Ok(&[1u8, 2, 3][..])), iter.find_next_of(ECHO));
+                assert_eq!(
+                    Some(Ok(crate::NO_RESPONSE_ERROR)),
+                    iter.find_next_of(NO_RESPONSE)
+                );
+                assert_eq!(
+                    Some(Ok(0x0800)),
+                    iter.find_next_of(OCF_CONTENT_FORMAT_VERSION)
+                );
+            },
+        );
+
+        assert_eq!("Hop-Limit", HOP_LIMIT.to_string());
+        assert_eq!("Echo", ECHO.to_string());
+        assert!(!ECHO.is_repeatable());
+        assert!(!HOP_LIMIT.is_ok_in_response());
+    }
+
+    #[test]
+    fn extract_full_uri() {
+        let uri = with_options(
+            |encoder| {
+                encoder.insert_option(URI_PATH, "a b")?;
+                encoder.insert_option(URI_QUERY, "c")
+            },
+            |iter| iter.extract_full_uri("coap", "::1", Some(5683)),
+        );
+        assert_eq!("coap://[::1]:5683/a%20b?c", uri.unwrap().as_str());
+
+        let uri = with_options(
+            |encoder| {
+                encoder.insert_option(URI_HOST, "example.com")?;
+                encoder.insert_option(URI_PORT, 1234)
+            },
+            |iter| iter.extract_full_uri("coap", "192.0.2.1", None),
+        );
+        assert_eq!("coap://example.com:1234/", uri.unwrap().as_str());
+
+        let uri = with_options(
+            |_| Ok(()),
+            |iter| iter.extract_full_uri("coap", "host", None),
+        );
+        assert_eq!("coap://host/", uri.unwrap().as_str());
+
+        let uri = with_options(|_| Ok(()), |iter| iter.extract_full_uri("?", "host", None));
+        assert_eq!(Err(Error::InvalidArgument), uri);
+    }
+
+    #[test]
+    fn uri_path_segments() {
+        with_options(
+            |encoder| {
+                encoder.insert_option(URI_HOST, "example.com")?;
+                encoder.insert_option(URI_PATH, "a%20b")?;
+                encoder.insert_option(URI_PATH, "c")?;
+                encoder.insert_option(URI_QUERY, "d")
+            },
+            |iter| {
+                let segments: Result<Vec<_>, _> = iter.uri_path_segments().collect();
+                assert_eq!(Ok(vec!["a%20b", "c"]), segments);
+
+                let items: Result<Vec<_>, _> = iter.uri_query_items().collect();
+                assert_eq!(Ok(vec!["d"]), items);
+            },
+        );
+
+        with_options(
+            |encoder| encoder.insert_option_with_bytes(OptionNumber::URI_PATH, &[0xFF]),
+            |iter| {
+                let mut segments = iter.uri_path_segments();
+                assert_eq!(Some(Err(Error::ParseFailure)), segments.next());
+                assert_eq!(None, segments.next());
+            },
+        );
+    }
+}
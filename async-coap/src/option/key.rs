@@ -86,12 +86,21 @@ pub const MAX_AGE: OptionKey<u32> = OptionKey::new(OptionNumber::MAX_AGE);
 /// Typed key for URI-Query option.
 pub const URI_QUERY: OptionKey<&str> = OptionKey::new(OptionNumber::URI_QUERY);
 
+/// Typed key for Hop-Limit option.
+pub const HOP_LIMIT: OptionKey<u8> = OptionKey::new(OptionNumber::HOP_LIMIT);
+
 /// Typed key for Accept option.
 pub const ACCEPT: OptionKey<ContentFormat> = OptionKey::new(OptionNumber::ACCEPT);
 
+/// Typed key for Q-Block1 option.
+pub const Q_BLOCK1: OptionKey<BlockInfo> = OptionKey::new(OptionNumber::Q_BLOCK1);
+
 /// Typed key for Location-Query option.
 pub const LOCATION_QUERY: OptionKey<&str> = OptionKey::new(OptionNumber::LOCATION_QUERY);
 
+/// Typed key for EDHOC option.
+pub const EDHOC: OptionKey<()> = OptionKey::new(OptionNumber::EDHOC);
+
 /// Typed key for Block2 option.
 pub const BLOCK2: OptionKey<BlockInfo> = OptionKey::new(OptionNumber::BLOCK2);
 
@@ -101,6 +110,9 @@ pub const BLOCK1: OptionKey<BlockInfo> = OptionKey::new(OptionNumber::BLOCK1);
 /// Typed key for Size2 option.
 pub const SIZE2: OptionKey<u32> = OptionKey::new(OptionNumber::SIZE2);
 
+/// Typed key for Q-Block2 option.
+pub const Q_BLOCK2: OptionKey<BlockInfo> = OptionKey::new(OptionNumber::Q_BLOCK2);
+
 /// Typed key for Proxy-URI option.
 pub const PROXY_URI: OptionKey<&str> = OptionKey::new(OptionNumber::PROXY_URI);
 
@@ -109,3 +121,22 @@ pub const PROXY_SCHEME: OptionKey<&str> = OptionKey::new(OptionNumber::PROXY_SCH
 
 /// Typed key for Size1 option.
 pub const SIZE1: OptionKey<u32> = OptionKey::new(OptionNumber::SIZE1);
+
+/// Typed key for Echo option.
+pub const ECHO: OptionKey<&[u8]> = OptionKey::new(OptionNumber::ECHO);
+
+/// Typed key for No-Response option. See [`NO_RESPONSE_ANY`] and related constants.
+///
+/// [`NO_RESPONSE_ANY`]: crate::NO_RESPONSE_ANY
+pub const NO_RESPONSE: OptionKey<u8> = OptionKey::new(OptionNumber::NO_RESPONSE);
+
+/// Typed key for Request-Tag option.
+pub const REQUEST_TAG: OptionKey<RequestTag> = OptionKey::new(OptionNumber::REQUEST_TAG);
+
+/// Typed key for OCF-Accept-Content-Format-Version option.
+pub const OCF_ACCEPT_CONTENT_FORMAT_VERSION: OptionKey<u16> =
+    OptionKey::new(OptionNumber::OCF_ACCEPT_CONTENT_FORMAT_VERSION);
+
+/// Typed key for OCF-Content-Format-Version option.
+pub const OCF_CONTENT_FORMAT_VERSION: OptionKey<u16> =
+    OptionKey::new(OptionNumber::OCF_CONTENT_FORMAT_VERSION);
@@ -0,0 +1,254 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+
+/// The location of an option in the buffer indexed by an [`OptionMap`].
+#[derive(Debug, Copy, Clone, Default)]
+struct Entry {
+    number: OptionNumber,
+    offset: u16,
+}
+
+/// Random-access view of the options of a message, for looking up options by number
+/// without decoding the whole option list each time.
+///
+/// The options are decoded and validated once, when the map is created, and the
+/// locations of the first [`OptionMap::CAPACITY`] options are recorded in a fixed-size
+/// index, so creating the map doesn't allocate. Lookups in the index take
+/// `O(log n)` time; options beyond its capacity are found by decoding the rest of the
+/// option list.
+///
+/// ```
+/// # use async_coap::prelude::*;
+/// # use async_coap::message::{BufferMessageEncoder, MessageRead, StandardMessageParser};
+/// # use async_coap::option::OptionMap;
+/// # use async_coap::Error;
+/// # let mut buffer = [0u8; 64];
+/// # let mut encoder = BufferMessageEncoder::new(&mut buffer);
+/// # encoder.insert_option(option::URI_PATH, "sensors").unwrap();
+/// # encoder.insert_option(option::URI_PATH, "temp").unwrap();
+/// # encoder.insert_option(option::ACCEPT, ContentFormat::TEXT_PLAIN_UTF8).unwrap();
+/// # let message = StandardMessageParser::new(encoder.as_bytes()).unwrap();
+/// let options = OptionMap::new(message.options())?;
+///
+/// assert_eq!(Some(Ok(ContentFormat::TEXT_PLAIN_UTF8)), options.get_of(option::ACCEPT));
+/// assert_eq!(None, options.get_of(option::CONTENT_FORMAT));
+/// assert_eq!(
+///     vec!["sensors", "temp"],
+///     options.get_all_of(option::URI_PATH).collect::<Result<Vec<_>, _>>()?
+/// );
+/// # Ok::<(), Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct OptionMap<'a> {
+    buffer: &'a [u8],
+    entries: [Entry; OptionMap::CAPACITY],
+    indexed: usize,
+    count: usize,
+    overflow: Option<OptionIterator<'a>>,
+}
+
+impl<'a> OptionMap<'a> {
+    /// The number of options that are indexed.
+    pub const CAPACITY: usize = 24;
+
+    /// Creates a new map of the options remaining in `iter`.
+    ///
+    /// Returns an error if any of the options are malformed.
+    pub fn new(mut iter: OptionIterator<'a>) -> Result<OptionMap<'a>, Error> {
+        let buffer = iter.as_slice();
+        let mut ret = OptionMap {
+            buffer,
+            entries: [Entry::default(); OptionMap::CAPACITY],
+            indexed: 0,
+            count: 0,
+            overflow: None,
+        };
+
+        loop {
+            let offset = buffer.len() - iter.as_slice().len();
+            let remaining = iter.clone();
+            let number = match iter.next() {
+                Some(result) => result?.0,
+                None => break,
+            };
+
+            if ret.overflow.is_none() && ret.indexed < OptionMap::CAPACITY && offset <= 0xFFFF {
+                ret.entries[ret.indexed] = Entry {
+                    number,
+                    offset: offset as u16,
+                };
+                ret.indexed += 1;
+            } else if ret.overflow.is_none() {
+                ret.overflow = Some(remaining);
+            }
+
+            ret.count += 1;
+        }
+
+        Ok(ret)
+    }
+
+    /// Returns the total number of options in the map.
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    /// Returns true if there are no options in the map.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Returns true if there is at least one option with the given number.
+    pub fn contains(&self, number: OptionNumber) -> bool {
+        self.get(number).is_some()
+    }
+
+    /// Returns the value of the first option with the given number.
+    pub fn get(&self, number: OptionNumber) -> Option<&'a [u8]> {
+        self.get_all(number).next()
+    }
+
+    /// Returns an iterator over the values of all of the options with the given number,
+    /// in order.
+    pub fn get_all(&self, number: OptionNumber) -> impl Iterator<Item = &'a [u8]> + 'a {
+        let index = self.entries[..self.indexed].partition_point(|entry| entry.number < number);
+
+        // The options were validated when the map was created.
+        self.iter_from(index)
+            .filter_map(Result::ok)
+            .skip_while(move |(n, _)| *n < number)
+            .take_while(move |(n, _)| *n == number)
+            .map(|(_, value)| value)
+    }
+
+    /// Typed version of [`OptionMap::get`].
+    ///
+    /// Returns the value of the first option with the given key, or
+    /// [`Error::ParseFailure`] if it can't be converted to `T`.
+    pub fn get_of<T>(&self, key: OptionKey<T>) -> Option<Result<T, Error>>
+    where
+        T: TryOptionValueFrom<'a> + Sized,
+    {
+        self.get(key.0)
+            .map(|value| T::try_option_value_from(value).ok_or(Error::ParseFailure))
+    }
+
+    /// Typed version of [`OptionMap::get_all`].
+    pub fn get_all_of<T>(&self, key: OptionKey<T>) -> impl Iterator<Item = Result<T, Error>> + 'a
+    where
+        T: TryOptionValueFrom<'a> + Sized + 'a,
+    {
+        self.get_all(key.0)
+            .map(|value| T::try_option_value_from(value).ok_or(Error::ParseFailure))
+    }
+
+    /// Returns an iterator over all of the options in the map, in order.
+    pub fn iter(&self) -> OptionIterator<'a> {
+        OptionIterator::new(self.buffer)
+    }
+
+    /// Returns an iterator starting at the option with the given position in the index,
+    /// or at the first option that isn't indexed.
+    fn iter_from(&self, index: usize) -> OptionIterator<'a> {
+        if index < self.indexed {
+            let last_option = match index {
+                0 => OptionNumber::default(),
+                i => self.entries[i - 1].number,
+            };
+            let offset = self.entries[index].offset as usize;
+            OptionIterator::resume(&self.buffer[offset..], last_option)
+        } else {
+            self.overflow.clone().unwrap_or_default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::encoder::OptionEncoder;
+    use super::*;
+
+    #[test]
+    fn lookup() {
+        let mut buffer = [0u8; 256];
+        let mut encoder = OptionEncoder::new(&mut buffer);
+        encoder.insert_option(URI_HOST, "example.com").unwrap();
+        encoder.insert_option(LOCATION_PATH, "x").unwrap();
+        encoder.insert_option(LOCATION_PATH, "y").unwrap();
+        encoder.insert_option(URI_PATH, "a").unwrap();
+        encoder.insert_option(URI_PATH, "b").unwrap();
+        encoder.insert_option(SIZE2, 0).unwrap();
+        let (options, _) = encoder.finish();
+
+        let map = OptionMap::new(OptionIterator::new(options)).unwrap();
+
+        assert_eq!(6, map.len());
+        assert_eq!(Some(Ok("example.com")), map.get_of(URI_HOST));
+        assert_eq!(Some(Ok(0)), map.get_of(SIZE2));
+        assert_eq!(None, map.get_of(URI_QUERY));
+        assert!(!map.contains(OptionNumber::OBSERVE));
+        assert_eq!(
+            vec![&b"x"[..], &b"y"[..]],
+            map.get_all(OptionNumber::LOCATION_PATH).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![Ok("a"), Ok("b")],
+            map.get_all_of(URI_PATH).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            OptionIterator::new(options).collect::<Vec<_>>(),
+            map.iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn lookup_beyond_capacity() {
+        let mut buffer = [0u8; 256];
+        let mut encoder = OptionEncoder::new(&mut buffer);
+        for _ in 0..OptionMap::CAPACITY {
+            encoder.insert_option(LOCATION_PATH, "x").unwrap();
+        }
+        encoder.insert_option(URI_PATH, "a").unwrap();
+        encoder.insert_option(URI_PATH, "b").unwrap();
+        encoder
+            .insert_option(ACCEPT, ContentFormat::TEXT_PLAIN_UTF8)
+            .unwrap();
+        let (options, _) = encoder.finish();
+
+        let map = OptionMap::new(OptionIterator::new(options)).unwrap();
+
+        assert_eq!(OptionMap::CAPACITY + 3, map.len());
+        assert_eq!(
+            OptionMap::CAPACITY,
+            map.get_all(OptionNumber::LOCATION_PATH).count()
+        );
+        assert_eq!(
+            vec![Ok("a"), Ok("b")],
+            map.get_all_of(URI_PATH).collect::<Vec<_>>()
+        );
+        assert_eq!(Some(Ok(ContentFormat::TEXT_PLAIN_UTF8)), map.get_of(ACCEPT));
+        assert_eq!(None, map.get_of(URI_QUERY));
+    }
+
+    #[test]
+    fn malformed() {
+        assert_eq!(
+            Err(Error::ParseFailure),
+            OptionMap::new(OptionIterator::new(&[0xD0])).map(|map| map.len())
+        );
+    }
+}
@@ -34,8 +34,25 @@ pub use iter::*;
 mod key;
 pub use key::*;
 
+mod map;
+pub use map::OptionMap;
+
+mod set;
+pub use set::OptionSet;
+
 mod value;
 pub use value::*;
 
+mod registry;
+pub(crate) use registry::registered_option;
+pub use registry::{with_option_registry, CustomOption, OptionRegistry};
+
+mod request_options;
+pub use request_options::RequestOptions;
+
+mod uri_options;
+pub use uri_options::UriOptions;
+pub(crate) use uri_options::{raw_uri_path_segments, raw_uri_query_items};
+
 #[cfg(test)]
 mod encoder;
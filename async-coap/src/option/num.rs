@@ -56,12 +56,21 @@ impl OptionNumber {
     /// URI_QUERY option.
     pub const URI_QUERY: OptionNumber = OptionNumber(15);
 
+    /// HOP_LIMIT option.
+    pub const HOP_LIMIT: OptionNumber = OptionNumber(16);
+
     /// ACCEPT option.
     pub const ACCEPT: OptionNumber = OptionNumber(17);
 
+    /// Q_BLOCK1 option.
+    pub const Q_BLOCK1: OptionNumber = OptionNumber(19);
+
     /// LOCATION_QUERY option.
     pub const LOCATION_QUERY: OptionNumber = OptionNumber(20);
 
+    /// EDHOC option.
+    pub const EDHOC: OptionNumber = OptionNumber(21);
+
     /// BLOCK2 option.
     pub const BLOCK2: OptionNumber = OptionNumber(23);
 
@@ -71,6 +80,9 @@ impl OptionNumber {
     /// SIZE2 option.
     pub const SIZE2: OptionNumber = OptionNumber(28);
 
+    /// Q_BLOCK2 option.
+    pub const Q_BLOCK2: OptionNumber = OptionNumber(31);
+
     /// PROXY_URI option.
     pub const PROXY_URI: OptionNumber = OptionNumber(35);
 
@@ -80,9 +92,21 @@ impl OptionNumber {
     /// SIZE1 option.
     pub const SIZE1: OptionNumber = OptionNumber(60);
 
+    /// ECHO option.
+    pub const ECHO: OptionNumber = OptionNumber(252);
+
     /// NO_RESPONSE option.
     pub const NO_RESPONSE: OptionNumber = OptionNumber(258);
 
+    /// REQUEST_TAG option.
+    pub const REQUEST_TAG: OptionNumber = OptionNumber(292);
+
+    /// OCF_ACCEPT_CONTENT_FORMAT_VERSION option.
+    pub const OCF_ACCEPT_CONTENT_FORMAT_VERSION: OptionNumber = OptionNumber(2049);
+
+    /// OCF_CONTENT_FORMAT_VERSION option.
+    pub const OCF_CONTENT_FORMAT_VERSION: OptionNumber = OptionNumber(2053);
+
     /// Returns true if this option number is critical, false if it is optional.
     pub fn is_critical(self) -> bool {
         const FLAG_CRITICAL: u16 = 1;
@@ -117,16 +141,26 @@ impl OptionNumber {
             OptionNumber::CONTENT_FORMAT => OptionValueType::ContentFormat,
             OptionNumber::MAX_AGE => OptionValueType::Integer,
             OptionNumber::URI_QUERY => OptionValueType::String,
+            OptionNumber::HOP_LIMIT => OptionValueType::Integer,
             OptionNumber::ACCEPT => OptionValueType::ContentFormat,
+            OptionNumber::Q_BLOCK1 => OptionValueType::Block,
             OptionNumber::LOCATION_QUERY => OptionValueType::String,
+            OptionNumber::EDHOC => OptionValueType::Flag,
             OptionNumber::BLOCK2 => OptionValueType::Block,
             OptionNumber::BLOCK1 => OptionValueType::Block,
             OptionNumber::SIZE2 => OptionValueType::Integer,
+            OptionNumber::Q_BLOCK2 => OptionValueType::Block,
             OptionNumber::PROXY_URI => OptionValueType::String,
             OptionNumber::PROXY_SCHEME => OptionValueType::String,
             OptionNumber::SIZE1 => OptionValueType::Integer,
+            OptionNumber::ECHO => OptionValueType::Opaque,
             OptionNumber::NO_RESPONSE => OptionValueType::Integer,
-            OptionNumber(_) => OptionValueType::Opaque,
+            OptionNumber::REQUEST_TAG => OptionValueType::Opaque,
+            OptionNumber::OCF_ACCEPT_CONTENT_FORMAT_VERSION => OptionValueType::Integer,
+            OptionNumber::OCF_CONTENT_FORMAT_VERSION => OptionValueType::Integer,
+            number => registered_option(number)
+                .map(|option| option.value_type)
+                .unwrap_or(OptionValueType::Opaque),
         }
     }
 
@@ -140,19 +174,28 @@ impl OptionNumber {
             OptionNumber::OBSERVE => true,
             OptionNumber::URI_PORT => true,
             OptionNumber::LOCATION_PATH => false,
+            OptionNumber::OSCORE => true,
             OptionNumber::URI_PATH => true,
             OptionNumber::CONTENT_FORMAT => true,
             OptionNumber::MAX_AGE => false,
             OptionNumber::URI_QUERY => true,
+            OptionNumber::HOP_LIMIT => true,
             OptionNumber::ACCEPT => true,
+            OptionNumber::Q_BLOCK1 => true,
             OptionNumber::LOCATION_QUERY => false,
+            OptionNumber::EDHOC => true,
             OptionNumber::BLOCK2 => true,
             OptionNumber::BLOCK1 => true,
             OptionNumber::SIZE2 => false,
+            OptionNumber::Q_BLOCK2 => true,
             OptionNumber::PROXY_URI => true,
             OptionNumber::PROXY_SCHEME => true,
             OptionNumber::SIZE1 => true,
+            OptionNumber::ECHO => true,
             OptionNumber::NO_RESPONSE => true,
+            OptionNumber::REQUEST_TAG => true,
+            OptionNumber::OCF_ACCEPT_CONTENT_FORMAT_VERSION => true,
+            OptionNumber::OCF_CONTENT_FORMAT_VERSION => true,
 
             // We default to true for unknown options.
             OptionNumber(_) => true,
@@ -169,19 +212,28 @@ impl OptionNumber {
             OptionNumber::OBSERVE => true,
             OptionNumber::URI_PORT => false,
             OptionNumber::LOCATION_PATH => true,
+            OptionNumber::OSCORE => true,
             OptionNumber::URI_PATH => false,
             OptionNumber::CONTENT_FORMAT => true,
             OptionNumber::MAX_AGE => true,
             OptionNumber::URI_QUERY => false,
+            OptionNumber::HOP_LIMIT => false,
             OptionNumber::ACCEPT => false,
+            OptionNumber::Q_BLOCK1 => true,
             OptionNumber::LOCATION_QUERY => true,
+            OptionNumber::EDHOC => false,
             OptionNumber::BLOCK2 => true,
             OptionNumber::BLOCK1 => true,
             OptionNumber::SIZE2 => true,
+            OptionNumber::Q_BLOCK2 => true,
             OptionNumber::PROXY_URI => false,
             OptionNumber::PROXY_SCHEME => false,
             OptionNumber::SIZE1 => false,
+            OptionNumber::ECHO => true,
             OptionNumber::NO_RESPONSE => false,
+            OptionNumber::REQUEST_TAG => false,
+            OptionNumber::OCF_ACCEPT_CONTENT_FORMAT_VERSION => false,
+            OptionNumber::OCF_CONTENT_FORMAT_VERSION => true,
 
             // We default to true for unknown options.
             OptionNumber(_) => true,
@@ -199,22 +251,33 @@ impl OptionNumber {
             OptionNumber::OBSERVE => false,
             OptionNumber::URI_PORT => false,
             OptionNumber::LOCATION_PATH => true,
+            OptionNumber::OSCORE => false,
             OptionNumber::URI_PATH => true,
             OptionNumber::CONTENT_FORMAT => false,
             OptionNumber::MAX_AGE => false,
             OptionNumber::URI_QUERY => true,
+            OptionNumber::HOP_LIMIT => false,
             OptionNumber::ACCEPT => false,
+            OptionNumber::Q_BLOCK1 => false,
             OptionNumber::LOCATION_QUERY => true,
+            OptionNumber::EDHOC => false,
             OptionNumber::BLOCK2 => false,
             OptionNumber::BLOCK1 => false,
             OptionNumber::SIZE2 => false,
+            OptionNumber::Q_BLOCK2 => false,
             OptionNumber::PROXY_URI => false,
             OptionNumber::PROXY_SCHEME => false,
             OptionNumber::SIZE1 => false,
+            OptionNumber::ECHO => false,
             OptionNumber::NO_RESPONSE => false,
+            OptionNumber::REQUEST_TAG => true,
+            OptionNumber::OCF_ACCEPT_CONTENT_FORMAT_VERSION => false,
+            OptionNumber::OCF_CONTENT_FORMAT_VERSION => false,
 
             // We default to true for unknown options.
-            OptionNumber(_) => true,
+            number => registered_option(number)
+                .map(|option| option.repeatable)
+                .unwrap_or(true),
         }
     }
 
@@ -235,15 +298,25 @@ impl OptionNumber {
             OptionNumber::CONTENT_FORMAT => Some("Content-Format"),
             OptionNumber::MAX_AGE => Some("Max-Age"),
             OptionNumber::URI_QUERY => Some("Uri-Query"),
+            OptionNumber::HOP_LIMIT => Some("Hop-Limit"),
             OptionNumber::ACCEPT => Some("Accept"),
+            OptionNumber::Q_BLOCK1 => Some("Q-Block1"),
             OptionNumber::LOCATION_QUERY => Some("Location-Query"),
+            OptionNumber::EDHOC => Some("EDHOC"),
             OptionNumber::BLOCK2 => Some("Block2"),
             OptionNumber::BLOCK1 => Some("Block1"),
             OptionNumber::SIZE2 => Some("Size2"),
+            OptionNumber::Q_BLOCK2 => Some("Q-Block2"),
             OptionNumber::PROXY_URI => Some("Proxy-Uri"),
             OptionNumber::PROXY_SCHEME => Some("Proxy-Scheme"),
             OptionNumber::SIZE1 => Some("Size1"),
+            OptionNumber::ECHO => Some("Echo"),
             OptionNumber::NO_RESPONSE => Some("No-Response"),
+            OptionNumber::REQUEST_TAG => Some("Request-Tag"),
+            OptionNumber::OCF_ACCEPT_CONTENT_FORMAT_VERSION => {
+                Some("OCF-Accept-Content-Format-Version")
+            }
+            OptionNumber::OCF_CONTENT_FORMAT_VERSION => Some("OCF-Content-Format-Version"),
             _ => None,
         }
     }
@@ -299,12 +372,14 @@ impl core::fmt::Display for OptionNumber {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         if let Some(name) = self.static_name() {
             f.write_str(name)
+        } else if let Some(option) = registered_option(*self) {
+            f.write_str(option.name)
         } else {
             // Write out a descriptive identifier.
             if self.is_critical() {
-                f.write_str("Opt-")?;
-            } else {
                 f.write_str("Crit-")?;
+            } else {
+                f.write_str("Opt-")?;
             }
 
             if self.is_un_safe() {
@@ -0,0 +1,246 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Description of a vendor-specific or experimental option, for registering it with an
+/// [`OptionRegistry`].
+///
+/// Custom options are usually defined as constants, along with a typed key for them:
+///
+/// ```
+/// # use async_coap::option::*;
+/// const VENDOR_TRACE: CustomOption = CustomOption {
+///     number: OptionNumber(65002),
+///     name: "Vendor-Trace",
+///     value_type: OptionValueType::Opaque,
+///     critical: false,
+///     repeatable: false,
+/// };
+///
+/// const VENDOR_TRACE_KEY: OptionKey<&[u8]> = VENDOR_TRACE.key();
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct CustomOption {
+    /// The option number. Numbers from 65000 to 65535 are reserved for experimental use
+    /// by [IETF-RFC7252 Section 12.2].
+    ///
+    /// [IETF-RFC7252 Section 12.2]: https://tools.ietf.org/html/rfc7252#section-12.2
+    pub number: OptionNumber,
+
+    /// The name of the option, used when displaying messages.
+    pub name: &'static str,
+
+    /// The type of the option's value, used when displaying messages.
+    pub value_type: OptionValueType,
+
+    /// Whether the option is critical. This must agree with [`OptionNumber::is_critical`].
+    pub critical: bool,
+
+    /// Whether multiple instances of the option are allowed.
+    pub repeatable: bool,
+}
+
+impl CustomOption {
+    /// Returns a typed key for this option.
+    pub const fn key<T>(&self) -> OptionKey<T> {
+        OptionKey::new(self.number)
+    }
+}
+
+/// A set of [`CustomOption`]s that a local endpoint understands, in addition to the
+/// options defined by the specifications.
+///
+/// While a local endpoint handles a message, the options in its registry are:
+///
+/// * Displayed using their registered names and value types.
+/// * Checked against their declared repeatability, by [`OptionNumber::is_repeatable`].
+/// * Treated as handled by [`RequestOptions::parse`] and by the default implementation of
+///   [`SendDesc::supports_option`], even if they are critical.
+///
+/// [`SendDesc::supports_option`]: crate::send_desc::SendDesc::supports_option
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct OptionRegistry {
+    options: BTreeMap<OptionNumber, CustomOption>,
+}
+
+impl OptionRegistry {
+    /// Creates a new, empty registry.
+    pub fn new() -> OptionRegistry {
+        Default::default()
+    }
+
+    /// Adds `option` to this registry.
+    ///
+    /// Returns [`Error::InvalidArgument`] if the option number is already defined by a
+    /// specification or registered, or if the declared criticality doesn't agree with the
+    /// option number.
+    pub fn register(&mut self, option: CustomOption) -> Result<(), Error> {
+        if option.number.static_name().is_some()
+            || option.critical != option.number.is_critical()
+            || self.options.contains_key(&option.number)
+        {
+            return Err(Error::InvalidArgument);
+        }
+
+        self.options.insert(option.number, option);
+        Ok(())
+    }
+
+    /// Returns the registered option with the given number, if there is one.
+    pub fn get(&self, number: OptionNumber) -> Option<&CustomOption> {
+        self.options.get(&number)
+    }
+
+    /// Returns an iterator over the registered options, in order of option number.
+    pub fn iter(&self) -> impl Iterator<Item = &CustomOption> {
+        self.options.values()
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<OptionRegistry>>> = const { RefCell::new(None) };
+}
+
+/// Restores the previous registry when dropped, even if `f` panics.
+struct RestoreOnDrop(Option<Arc<OptionRegistry>>);
+
+impl Drop for RestoreOnDrop {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        CURRENT.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+/// Calls `f`, with the options in `registry` being understood on this thread until it
+/// returns.
+///
+/// Local endpoints do this with their own registry while handling messages; this is only
+/// needed to display or validate messages outside of that.
+pub fn with_option_registry<F, R>(registry: &Arc<OptionRegistry>, f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let previous = CURRENT.with(|current| current.replace(Some(registry.clone())));
+    let _restore = RestoreOnDrop(previous);
+    f()
+}
+
+/// Returns the registered option with the given number from the registry that is
+/// currently in effect, if any.
+pub(crate) fn registered_option(number: OptionNumber) -> Option<CustomOption> {
+    CURRENT.with(|current| {
+        current
+            .borrow()
+            .as_ref()
+            .and_then(|registry| registry.get(number).copied())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::{BufferMessageEncoder, StandardMessageParser};
+
+    const VENDOR_TRACE: CustomOption = CustomOption {
+        number: OptionNumber(65002),
+        name: "Vendor-Trace",
+        value_type: OptionValueType::Integer,
+        critical: false,
+        repeatable: false,
+    };
+
+    const VENDOR_MODE: CustomOption = CustomOption {
+        number: OptionNumber(65001),
+        name: "Vendor-Mode",
+        value_type: OptionValueType::String,
+        critical: true,
+        repeatable: true,
+    };
+
+    #[test]
+    fn register() {
+        let mut registry = OptionRegistry::new();
+
+        assert_eq!(Ok(()), registry.register(VENDOR_TRACE));
+        assert_eq!(Ok(()), registry.register(VENDOR_MODE));
+        assert_eq!(Err(Error::InvalidArgument), registry.register(VENDOR_MODE));
+        assert_eq!(Some(&VENDOR_MODE), registry.get(OptionNumber(65001)));
+        assert_eq!(
+            vec![VENDOR_MODE, VENDOR_TRACE],
+            registry.iter().copied().collect::<Vec<_>>()
+        );
+
+        // Standard options can't be redefined.
+        assert_eq!(
+            Err(Error::InvalidArgument),
+            registry.register(CustomOption {
+                number: OptionNumber::ECHO,
+                ..VENDOR_TRACE
+            })
+        );
+
+        // The declared criticality must agree with the option number.
+        assert_eq!(
+            Err(Error::InvalidArgument),
+            registry.register(CustomOption {
+                number: OptionNumber(65004),
+                critical: true,
+                ..VENDOR_TRACE
+            })
+        );
+    }
+
+    #[test]
+    fn registered_options_in_effect() {
+        let mut registry = OptionRegistry::new();
+        registry.register(VENDOR_TRACE).unwrap();
+        registry.register(VENDOR_MODE).unwrap();
+        let registry = Arc::new(registry);
+
+        let mut buffer = [0u8; 32];
+        let mut encoder = BufferMessageEncoder::new(&mut buffer);
+        encoder.set_msg_code(MsgCode::MethodGet);
+        encoder
+            .insert_option(VENDOR_MODE.key::<&str>(), "x")
+            .unwrap();
+        encoder
+            .insert_option(VENDOR_MODE.key::<&str>(), "y")
+            .unwrap();
+        encoder.insert_option(VENDOR_TRACE.key::<u32>(), 7).unwrap();
+        let msg = StandardMessageParser::new(encoder.as_bytes()).unwrap();
+
+        assert_eq!(
+            Err(Error::UnhandledCriticalOption),
+            RequestOptions::parse(msg.options())
+        );
+        assert!(OptionNumber(65002).is_repeatable());
+        assert_ne!("Vendor-Trace", OptionNumber(65002).to_string());
+
+        with_option_registry(&registry, || {
+            assert!(RequestOptions::parse(msg.options()).is_ok());
+            assert!(!OptionNumber(65002).is_repeatable());
+            assert_eq!("Vendor-Trace", OptionNumber(65002).to_string());
+            assert!(msg
+                .to_string()
+                .contains("Vendor-Mode:\"x\" Vendor-Mode:\"y\" Vendor-Trace:7"));
+        });
+
+        assert_eq!(None, registered_option(OptionNumber(65002)));
+    }
+}
@@ -0,0 +1,245 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+
+/// The options of a CoAP request that are typically of interest to a server, extracted
+/// in a single pass over the options of the message.
+///
+/// Rather than repeatedly calling [`OptionIteratorExt::find_next_of`] on fresh iterators,
+/// a request handler can call [`RequestOptions::parse`] once and then inspect the fields.
+///
+/// ```
+/// # use async_coap::prelude::*;
+/// # use async_coap::option::RequestOptions;
+/// # use async_coap::message::{MessageRead, MessageWrite, VecMessageEncoder, StandardMessageParser};
+/// # let mut encoder = VecMessageEncoder::new();
+/// # encoder.set_msg_code(MsgCode::MethodGet);
+/// # encoder.insert_option(option::URI_PATH, "sensors").unwrap();
+/// # encoder.insert_option(option::URI_PATH, "temp").unwrap();
+/// # encoder.insert_option(option::URI_QUERY, "unit=C").unwrap();
+/// # encoder.insert_option(option::ACCEPT, ContentFormat::APPLICATION_JSON).unwrap();
+/// # let msg = StandardMessageParser::new(encoder.as_bytes()).unwrap();
+/// let options = RequestOptions::parse(msg.options())?;
+///
+/// assert_eq!(options.uri_path, vec!["sensors", "temp"]);
+/// assert_eq!(options.uri_query, vec!["unit=C"]);
+/// assert_eq!(options.accept, Some(ContentFormat::APPLICATION_JSON));
+/// assert_eq!(options.rel_ref(), rel_ref!("sensors/temp?unit=C"));
+/// # Ok::<(), async_coap::Error>(())
+/// ```
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct RequestOptions {
+    /// The value of the `Uri-Host` option.
+    pub uri_host: Option<String>,
+
+    /// The value of the `Uri-Port` option.
+    pub uri_port: Option<u16>,
+
+    /// The values of the `Uri-Path` options, in order. The segments are *not* percent-encoded.
+    pub uri_path: Vec<String>,
+
+    /// The values of the `Uri-Query` options, in order. The items are *not* percent-encoded.
+    pub uri_query: Vec<String>,
+
+    /// The value of the `Accept` option.
+    pub accept: Option<ContentFormat>,
+
+    /// The value of the `Content-Format` option.
+    pub content_format: Option<ContentFormat>,
+
+    /// The value of the `Block1` option.
+    pub block1: Option<BlockInfo>,
+
+    /// The value of the `Block2` option.
+    pub block2: Option<BlockInfo>,
+
+    /// The value of the `Observe` option.
+    pub observe: Option<u32>,
+
+    /// The values of the `ETag` options, in order.
+    pub etags: Vec<ETag>,
+
+    /// The values of the `If-Match` options, in order.
+    pub if_match: Vec<ETag>,
+
+    /// True if the `If-None-Match` option was present.
+    pub if_none_match: bool,
+}
+
+impl RequestOptions {
+    /// Extracts the request options from the given option iterator, which is usually
+    /// obtained from [`MessageRead::options`](crate::message::MessageRead::options).
+    ///
+    /// The options are validated as they are extracted. This method fails with:
+    ///
+    /// * [`Error::ParseFailure`] if the value of any option is malformed.
+    /// * [`Error::OptionNotRepeatable`] if a non-repeatable option appears more than once.
+    /// * [`Error::UnhandledCriticalOption`] if there is a critical option that isn't
+    ///   represented by this struct (like `Proxy-Uri`), or an option which isn't
+    ///   allowed in requests. Critical options registered in the [`OptionRegistry`] that
+    ///   is in effect are assumed to be handled by the caller.
+    ///
+    /// In all of these cases the request should be rejected with a
+    /// [`MsgCode::ClientErrorBadOption`] response.
+    pub fn parse<'a, I>(iter: I) -> Result<RequestOptions, Error>
+    where
+        I: IntoIterator<Item = Result<(OptionNumber, &'a [u8]), Error>>,
+    {
+        fn decode<'a, T: TryOptionValueFrom<'a>>(value: &'a [u8]) -> Result<T, Error> {
+            T::try_option_value_from(value).ok_or(Error::ParseFailure)
+        }
+
+        let mut ret = RequestOptions::default();
+        let mut last_number = None;
+
+        for result in iter {
+            let (number, value) = result?;
+
+            if last_number == Some(number) && !number.is_repeatable() {
+                return Err(Error::OptionNotRepeatable);
+            }
+            last_number = Some(number);
+
+            match number {
+                OptionNumber::URI_HOST => ret.uri_host = Some(decode::<&str>(value)?.to_string()),
+                OptionNumber::URI_PORT => ret.uri_port = Some(decode(value)?),
+                OptionNumber::URI_PATH => ret.uri_path.push(decode::<&str>(value)?.to_string()),
+                OptionNumber::URI_QUERY => ret.uri_query.push(decode::<&str>(value)?.to_string()),
+                OptionNumber::ACCEPT => ret.accept = Some(decode(value)?),
+                OptionNumber::CONTENT_FORMAT => ret.content_format = Some(decode(value)?),
+                OptionNumber::BLOCK1 => ret.block1 = Some(decode(value)?),
+                OptionNumber::BLOCK2 => ret.block2 = Some(decode(value)?),
+                OptionNumber::OBSERVE => ret.observe = Some(decode(value)?),
+                OptionNumber::ETAG => ret.etags.push(decode(value)?),
+                OptionNumber::IF_MATCH => ret.if_match.push(decode(value)?),
+                OptionNumber::IF_NONE_MATCH => ret.if_none_match = true,
+                number if !number.is_ok_in_request() => {
+                    return Err(Error::UnhandledCriticalOption);
+                }
+                number if number.is_critical() && registered_option(number).is_none() => {
+                    return Err(Error::UnhandledCriticalOption);
+                }
+                _ => (),
+            }
+        }
+
+        Ok(ret)
+    }
+
+    /// Returns a relative reference constructed from [`uri_path`](Self::uri_path) and
+    /// [`uri_query`](Self::uri_query), percent-encoding them as necessary.
+    pub fn rel_ref(&self) -> RelRefBuf {
+        iter::rel_ref_from_parts(
+            self.uri_path.iter().map(|segment| Ok(segment.as_str())),
+            self.uri_query.iter().map(|item| Ok(item.as_str())),
+        )
+        .expect("Infallible path segments and query items")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::encoder::OptionEncoder;
+    use super::*;
+
+    fn parse_with<F>(f: F) -> Result<RequestOptions, Error>
+    where
+        F: FnOnce(&mut OptionEncoder<'_>) -> Result<(), Error>,
+    {
+        let mut buffer = [0u8; 256];
+        let mut encoder = OptionEncoder::new(&mut buffer);
+        f(&mut encoder)?;
+        let (options, _) = encoder.finish();
+        RequestOptions::parse(OptionIterator::new(options))
+    }
+
+    #[test]
+    fn parse() {
+        let options = parse_with(|encoder| {
+            encoder.insert_option(IF_MATCH, ETag::new(&[1, 2]))?;
+            encoder.insert_option(URI_HOST, "example.com")?;
+            encoder.insert_option(ETAG, ETag::new(&[3]))?;
+            encoder.insert_option(ETAG, ETag::new(&[4]))?;
+            encoder.insert_option(IF_NONE_MATCH, ())?;
+            encoder.insert_option(OBSERVE, 0)?;
+            encoder.insert_option(URI_PORT, 5683)?;
+            encoder.insert_option(URI_PATH, "a b")?;
+            encoder.insert_option(URI_PATH, "c")?;
+            encoder.insert_option(CONTENT_FORMAT, ContentFormat::APPLICATION_CBOR)?;
+            encoder.insert_option(URI_QUERY, "d=e")?;
+            encoder.insert_option(URI_QUERY, "f")?;
+            encoder.insert_option(ACCEPT, ContentFormat::APPLICATION_JSON)?;
+            encoder.insert_option(BLOCK2, BlockInfo::new(1, false, 6).unwrap())?;
+            encoder.insert_option(BLOCK1, BlockInfo::new(2, true, 6).unwrap())
+        })
+        .unwrap();
+
+        assert_eq!(Some("example.com".to_string()), options.uri_host);
+        assert_eq!(Some(5683), options.uri_port);
+        assert_eq!(vec!["a b", "c"], options.uri_path);
+        assert_eq!(vec!["d=e", "f"], options.uri_query);
+        assert_eq!(Some(ContentFormat::APPLICATION_JSON), options.accept);
+        assert_eq!(
+            Some(ContentFormat::APPLICATION_CBOR),
+            options.content_format
+        );
+        assert_eq!(BlockInfo::new(2, true, 6), options.block1);
+        assert_eq!(BlockInfo::new(1, false, 6), options.block2);
+        assert_eq!(Some(0), options.observe);
+        assert_eq!(vec![ETag::new(&[3]), ETag::new(&[4])], options.etags);
+        assert_eq!(vec![ETag::new(&[1, 2])], options.if_match);
+        assert!(options.if_none_match);
+        assert_eq!("a%20b/c?d=e&f", options.rel_ref().as_str());
+
+        assert_eq!(Ok(RequestOptions::default()), parse_with(|_| Ok(())));
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert_eq!(
+            Err(Error::OptionNotRepeatable),
+            parse_with(|encoder| {
+                encoder.insert_option(ACCEPT, ContentFormat::APPLICATION_JSON)?;
+                encoder.insert_option(ACCEPT, ContentFormat::APPLICATION_CBOR)
+            })
+        );
+
+        assert_eq!(
+            Err(Error::UnhandledCriticalOption),
+            parse_with(|encoder| encoder.insert_option(PROXY_URI, "coap://example.com/"))
+        );
+
+        assert_eq!(
+            Err(Error::UnhandledCriticalOption),
+            parse_with(|encoder| encoder.insert_option(LOCATION_PATH, "a"))
+        );
+
+        assert_eq!(
+            Err(Error::ParseFailure),
+            parse_with(|encoder| encoder.insert_option_with_bytes(OptionNumber::URI_PATH, &[0xff]))
+        );
+
+        assert_eq!(
+            Err(Error::ParseFailure),
+            parse_with(
+                |encoder| encoder.insert_option_with_bytes(OptionNumber::URI_PORT, &[1, 2, 3])
+            )
+        );
+
+        // Elective options that aren't represented are ignored.
+        assert!(parse_with(|encoder| encoder.insert_option(SIZE1, 1024)).is_ok());
+    }
+}
@@ -0,0 +1,295 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+
+/// Owned, ordered collection of CoAP options, for copying options from one message to
+/// another.
+///
+/// The options are kept sorted by option number, with options of the same number in the
+/// order in which they were inserted, which is the order in which they are written to a
+/// message. This makes it easy for proxies and gateways to take the options of an inbound
+/// message, filter or change them, and write them to an outbound message:
+///
+/// ```
+/// # use async_coap::prelude::*;
+/// # use async_coap::message::{MessageRead, VecMessageEncoder, StandardMessageParser};
+/// # use async_coap::option::OptionSet;
+/// # use async_coap::Error;
+/// # let mut encoder = VecMessageEncoder::new();
+/// # encoder.insert_option(option::URI_HOST, "example.com").unwrap();
+/// # encoder.insert_option(option::URI_PATH, "sensors").unwrap();
+/// # encoder.insert_option(option::ACCEPT, ContentFormat::APPLICATION_JSON).unwrap();
+/// # let inbound = StandardMessageParser::new(encoder.as_bytes()).unwrap();
+/// let mut options = OptionSet::parse(inbound.options())?;
+///
+/// options.remove(OptionNumber::URI_HOST);
+/// options.insert_option(option::URI_QUERY, "unit=C")?;
+///
+/// let mut outbound = VecMessageEncoder::new();
+/// options.write_to(&mut outbound)?;
+///
+/// # let outbound = StandardMessageParser::new(outbound.as_bytes()).unwrap();
+/// assert_eq!(
+///     Some(Ok(ContentFormat::APPLICATION_JSON)),
+///     outbound.options().find_next_of(option::ACCEPT)
+/// );
+/// assert_eq!(None, outbound.options().find_next_of(option::URI_HOST));
+/// # Ok::<(), Error>(())
+/// ```
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct OptionSet {
+    options: Vec<(OptionNumber, Vec<u8>)>,
+}
+
+impl OptionSet {
+    /// Creates a new, empty set of options.
+    pub fn new() -> OptionSet {
+        Default::default()
+    }
+
+    /// Creates a set of the options from `iter`, which is usually an [`OptionIterator`]
+    /// (possibly filtered).
+    ///
+    /// Returns an error if any of the options are malformed.
+    pub fn parse<'a, I>(iter: I) -> Result<OptionSet, Error>
+    where
+        I: IntoIterator<Item = Result<(OptionNumber, &'a [u8]), Error>>,
+    {
+        let options = iter
+            .into_iter()
+            .map(|result| result.map(|(number, value)| (number, value.to_vec())))
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        // Options are sorted in a valid message, but keep the invariant regardless.
+        let mut ret = OptionSet { options };
+        ret.options.sort_by_key(|(number, _)| *number);
+        Ok(ret)
+    }
+
+    /// Returns the number of options in the set.
+    pub fn len(&self) -> usize {
+        self.options.len()
+    }
+
+    /// Returns true if there are no options in the set.
+    pub fn is_empty(&self) -> bool {
+        self.options.is_empty()
+    }
+
+    /// Returns true if there is at least one option with the given number.
+    pub fn contains(&self, number: OptionNumber) -> bool {
+        self.get(number).is_some()
+    }
+
+    /// Returns the value of the first option with the given number.
+    pub fn get(&self, number: OptionNumber) -> Option<&[u8]> {
+        self.get_all(number).next()
+    }
+
+    /// Returns an iterator over the values of all of the options with the given number,
+    /// in order.
+    pub fn get_all(&self, number: OptionNumber) -> impl Iterator<Item = &[u8]> {
+        let index = self.options.partition_point(|(n, _)| *n < number);
+
+        self.options[index..]
+            .iter()
+            .take_while(move |(n, _)| *n == number)
+            .map(|(_, value)| value.as_slice())
+    }
+
+    /// Typed version of [`OptionSet::get`].
+    ///
+    /// Returns the value of the first option with the given key, or
+    /// [`Error::ParseFailure`] if it can't be converted to `T`.
+    pub fn get_of<'a, T>(&'a self, key: OptionKey<T>) -> Option<Result<T, Error>>
+    where
+        T: TryOptionValueFrom<'a> + Sized,
+    {
+        self.get(key.0)
+            .map(|value| T::try_option_value_from(value).ok_or(Error::ParseFailure))
+    }
+
+    /// Typed version of [`OptionSet::get_all`].
+    pub fn get_all_of<'a, T>(
+        &'a self,
+        key: OptionKey<T>,
+    ) -> impl Iterator<Item = Result<T, Error>> + 'a
+    where
+        T: TryOptionValueFrom<'a> + Sized + 'a,
+    {
+        self.get_all(key.0)
+            .map(|value| T::try_option_value_from(value).ok_or(Error::ParseFailure))
+    }
+
+    /// Returns an iterator over all of the options in the set, in order.
+    pub fn iter(&self) -> impl Iterator<Item = (OptionNumber, &[u8])> {
+        self.options
+            .iter()
+            .map(|(number, value)| (*number, value.as_slice()))
+    }
+
+    /// Removes all of the options with the given number, returning how many were removed.
+    pub fn remove(&mut self, number: OptionNumber) -> usize {
+        let len = self.options.len();
+        self.retain(|n, _| n != number);
+        len - self.options.len()
+    }
+
+    /// Keeps only the options for which `f` returns true.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(OptionNumber, &[u8]) -> bool,
+    {
+        self.options.retain(|(number, value)| f(*number, value));
+    }
+
+    /// Returns the options in this set which aren't in `other`, counting repeated options
+    /// with the same value separately.
+    ///
+    /// Comparing the options of a message before and after it was changed this way, in
+    /// both directions, yields the options which were removed and added.
+    pub fn difference(&self, other: &OptionSet) -> OptionSet {
+        let mut remaining = other.options.iter().collect::<Vec<_>>();
+        let mut ret = OptionSet::new();
+
+        for option in self.options.iter() {
+            match remaining.iter().position(|x| *x == option) {
+                Some(index) => {
+                    remaining.remove(index);
+                }
+                None => ret.options.push(option.clone()),
+            }
+        }
+
+        ret
+    }
+
+    /// Writes all of the options in the set to `msg`, in order.
+    pub fn write_to(&self, msg: &mut dyn OptionInsert) -> Result<(), Error> {
+        for (number, value) in self.iter() {
+            msg.insert_option_with_bytes(number, value)?;
+        }
+        Ok(())
+    }
+}
+
+impl OptionInsert for OptionSet {
+    /// Inserts an option into the set, after any other options with the same number.
+    ///
+    /// Fails with [`Error::OptionNotRepeatable`] if the option isn't repeatable and the
+    /// set already has an option with the same number.
+    fn insert_option_with_bytes(&mut self, key: OptionNumber, value: &[u8]) -> Result<(), Error> {
+        if !key.is_repeatable() && self.contains(key) {
+            return Err(Error::OptionNotRepeatable);
+        }
+
+        let index = self.options.partition_point(|(n, _)| *n <= key);
+        self.options.insert(index, (key, value.to_vec()));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::encoder::OptionEncoder;
+    use super::*;
+
+    #[test]
+    fn parse_and_write() {
+        let mut buffer = [0u8; 256];
+        let mut encoder = OptionEncoder::new(&mut buffer);
+        encoder.insert_option(URI_HOST, "example.com").unwrap();
+        encoder.insert_option(URI_PATH, "a").unwrap();
+        encoder.insert_option(URI_PATH, "b").unwrap();
+        encoder.insert_option(SIZE2, 0).unwrap();
+        let (options, _) = encoder.finish();
+
+        let set = OptionSet::parse(OptionIterator::new(options)).unwrap();
+
+        assert_eq!(4, set.len());
+        assert_eq!(Some(Ok("example.com")), set.get_of(URI_HOST));
+        assert_eq!(Some(Ok(0)), set.get_of(SIZE2));
+        assert_eq!(None, set.get_of(URI_QUERY));
+        assert_eq!(
+            vec![Ok("a"), Ok("b")],
+            set.get_all_of(URI_PATH).collect::<Vec<_>>()
+        );
+
+        let mut buffer = [0u8; 256];
+        let mut encoder = OptionEncoder::new(&mut buffer);
+        set.write_to(&mut encoder).unwrap();
+        assert_eq!(options, encoder.finish().0);
+    }
+
+    #[test]
+    fn insert_and_remove() {
+        let mut set = OptionSet::new();
+        set.insert_option(URI_PATH, "b").unwrap();
+        set.insert_option(URI_HOST, "example.com").unwrap();
+        set.insert_option(URI_QUERY, "x=1").unwrap();
+        set.insert_option(URI_PATH, "c").unwrap();
+
+        assert_eq!(
+            vec![
+                (OptionNumber::URI_HOST, &b"example.com"[..]),
+                (OptionNumber::URI_PATH, &b"b"[..]),
+                (OptionNumber::URI_PATH, &b"c"[..]),
+                (OptionNumber::URI_QUERY, &b"x=1"[..]),
+            ],
+            set.iter().collect::<Vec<_>>()
+        );
+
+        assert_eq!(
+            Err(Error::OptionNotRepeatable),
+            set.insert_option(URI_HOST, "example.org")
+        );
+
+        assert_eq!(2, set.remove(OptionNumber::URI_PATH));
+        assert_eq!(0, set.remove(OptionNumber::URI_PATH));
+        assert_eq!(2, set.len());
+
+        set.retain(|_, value| value.len() < 5);
+        assert_eq!(
+            vec![(OptionNumber::URI_QUERY, &b"x=1"[..])],
+            set.iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn difference() {
+        let mut before = OptionSet::new();
+        before.insert_option(URI_PATH, "a").unwrap();
+        before.insert_option(URI_PATH, "a").unwrap();
+        before
+            .insert_option(ACCEPT, ContentFormat::TEXT_PLAIN_UTF8)
+            .unwrap();
+
+        let mut after = before.clone();
+        after.remove(OptionNumber::ACCEPT);
+        after.insert_option(URI_PATH, "b").unwrap();
+
+        let mut removed = OptionSet::new();
+        removed
+            .insert_option(ACCEPT, ContentFormat::TEXT_PLAIN_UTF8)
+            .unwrap();
+        let mut added = OptionSet::new();
+        added.insert_option(URI_PATH, "b").unwrap();
+
+        assert_eq!(removed, before.difference(&after));
+        assert_eq!(added, after.difference(&before));
+        assert!(before.difference(&before).is_empty());
+    }
+}
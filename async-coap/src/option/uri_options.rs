@@ -0,0 +1,179 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+use crate::uri::UriHost;
+
+/// The `Uri-Host`, `Uri-Port`, `Uri-Path`, and `Uri-Query` options which identify the
+/// target resource of a request, as decomposed from a URI reference by the algorithm in
+/// [IETF-RFC7252 Section 6.4].
+///
+/// This is useful for implementing [`SendDesc::write_options`] when the target resource
+/// is only known as a URI. The fields can be passed individually to [`write_options!`],
+/// or all of them can be written at once using [`UriOptions::insert_into`].
+///
+/// ```
+/// # use async_coap::prelude::*;
+/// # use async_coap::option::UriOptions;
+/// let options = UriOptions::from_uri_ref(uri!("coap://Example.com:1234/a%20b/c?x=1&y"), 5683)?;
+///
+/// assert_eq!(options.uri_host.as_ref().map(String::as_str), Some("example.com"));
+/// assert_eq!(options.uri_port, Some(1234));
+/// assert_eq!(options.uri_path, vec!["a b", "c"]);
+/// assert_eq!(options.uri_query, vec!["x=1", "y"]);
+/// # Ok::<(), async_coap::Error>(())
+/// ```
+///
+/// [IETF-RFC7252 Section 6.4]: https://tools.ietf.org/html/rfc7252#section-6.4
+/// [`SendDesc::write_options`]: crate::send_desc::SendDesc::write_options
+/// [`write_options!`]: crate::write_options
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct UriOptions {
+    /// The value of the `Uri-Host` option. This is the lowercased registered name of the
+    /// host, and is `None` if the host is missing or an IP address literal.
+    pub uri_host: Option<String>,
+
+    /// The value of the `Uri-Port` option, if the port differs from the destination port.
+    pub uri_port: Option<u16>,
+
+    /// The values of the `Uri-Path` options, in order. The segments are *not* percent-encoded.
+    pub uri_path: Vec<String>,
+
+    /// The values of the `Uri-Query` options, in order. The items are *not* percent-encoded.
+    pub uri_query: Vec<String>,
+}
+
+impl UriOptions {
+    /// Decomposes `uri` into its URI options.
+    ///
+    /// `dest_port` is the UDP port that the request will be sent to: a `Uri-Port` option is
+    /// only needed if the port of `uri` differs from it. Relative references are allowed,
+    /// in which case only the `Uri-Path` and `Uri-Query` options are determined.
+    ///
+    /// Returns [`Error::InvalidArgument`] if `uri` has a fragment, since fragments are
+    /// never sent to the server.
+    pub fn from_uri_ref<U: AnyUriRef + ?Sized>(
+        uri: &U,
+        dest_port: u16,
+    ) -> Result<UriOptions, Error> {
+        let components = uri.components();
+
+        if components.raw_fragment().is_some() {
+            return Err(Error::InvalidArgument);
+        }
+
+        let uri_host = match components.host() {
+            Some(UriHost::RegName(name)) if !name.is_empty() => Some(name.to_ascii_lowercase()),
+            _ => None,
+        };
+
+        let uri_port = components.port().filter(|port| *port != dest_port);
+
+        let uri_path = raw_uri_path_segments(components.raw_path())
+            .map(|segment| segment.unescape_uri().to_string())
+            .collect();
+
+        let uri_query = raw_uri_query_items(components.raw_query())
+            .map(|item| item.unescape_uri().to_string())
+            .collect();
+
+        Ok(UriOptions {
+            uri_host,
+            uri_port,
+            uri_path,
+            uri_query,
+        })
+    }
+
+    /// Inserts all of the options into `msg`, in order.
+    pub fn insert_into(&self, msg: &mut dyn OptionInsert) -> Result<(), Error> {
+        if let Some(host) = &self.uri_host {
+            msg.insert_option(URI_HOST, host.as_str())?;
+        }
+
+        if let Some(port) = self.uri_port {
+            msg.insert_option(URI_PORT, port)?;
+        }
+
+        for segment in self.uri_path.iter() {
+            msg.insert_option(URI_PATH, segment.as_str())?;
+        }
+
+        for item in self.uri_query.iter() {
+            msg.insert_option(URI_QUERY, item.as_str())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Splits the escaped path of a URI into the still-escaped values of its `Uri-Path`
+/// options.
+pub(crate) fn raw_uri_path_segments(raw_path: &str) -> impl Iterator<Item = &str> {
+    let path = raw_path.strip_prefix('/').unwrap_or(raw_path);
+
+    path.split('/')
+        .filter(move |segment| !path.is_empty() && *segment != ".")
+}
+
+/// Splits the escaped query of a URI into the still-escaped values of its `Uri-Query`
+/// options.
+pub(crate) fn raw_uri_query_items(raw_query: Option<&str>) -> impl Iterator<Item = &str> {
+    raw_query
+        .filter(|query| !query.is_empty())
+        .into_iter()
+        .flat_map(|query| query.split(['&', ';']))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::{MessageRead, StandardMessageParser, VecMessageEncoder};
+
+    #[test]
+    fn from_uri_ref() {
+        let options = UriOptions::from_uri_ref(uri!("coap://[::1]:5683/"), 5683).unwrap();
+        assert_eq!(UriOptions::default(), options);
+
+        let options = UriOptions::from_uri_ref(uri!("coap://192.168.1.1:1234"), 5683).unwrap();
+        assert_eq!(None, options.uri_host);
+        assert_eq!(Some(1234), options.uri_port);
+
+        let options = UriOptions::from_uri_ref(rel_ref!("./a/./b%2Fc/?q%26=1;r"), 5683).unwrap();
+        assert_eq!(None, options.uri_host);
+        assert_eq!(vec!["a", "b/c", ""], options.uri_path);
+        assert_eq!(vec!["q&=1", "r"], options.uri_query);
+
+        assert_eq!(
+            Err(Error::InvalidArgument),
+            UriOptions::from_uri_ref(uri!("coap://example.com/a#b"), 5683)
+        );
+    }
+
+    #[test]
+    fn insert_into() {
+        let options = UriOptions::from_uri_ref(uri!("coap://example.com:1234/a/b?c"), 0).unwrap();
+
+        let mut encoder = VecMessageEncoder::new();
+        options.insert_into(&mut encoder).unwrap();
+
+        let msg = StandardMessageParser::new(encoder.as_bytes()).unwrap();
+        let parsed = RequestOptions::parse(msg.options()).unwrap();
+        assert_eq!(Some("example.com".to_string()), parsed.uri_host);
+        assert_eq!(Some(1234), parsed.uri_port);
+        assert_eq!(vec!["a", "b"], parsed.uri_path);
+        assert_eq!(vec!["c"], parsed.uri_query);
+    }
+}
@@ -81,6 +81,13 @@ impl<'a> From<ETag> for OptionValue<'a> {
     }
 }
 
+impl<'a> From<RequestTag> for OptionValue<'a> {
+    fn from(value: RequestTag) -> Self {
+        // Request-Tags have the same length limit as ETags.
+        OptionValue::ETag(ETag::new(value.as_bytes()))
+    }
+}
+
 impl<'a> From<&'a [u8]> for OptionValue<'a> {
     fn from(value: &'a [u8]) -> Self {
         OptionValue::Bytes(value)
@@ -120,6 +127,12 @@ impl<'a> TryOptionValueFrom<'a> for ETag {
     }
 }
 
+impl<'a> TryOptionValueFrom<'a> for RequestTag {
+    fn try_option_value_from(buffer: &'a [u8]) -> Option<Self> {
+        RequestTag::new(buffer)
+    }
+}
+
 impl<'a> TryOptionValueFrom<'a> for &'a [u8] {
     fn try_option_value_from(buffer: &'a [u8]) -> Option<Self> {
         Some(buffer)
@@ -150,6 +163,15 @@ impl<'a> TryOptionValueFrom<'a> for u16 {
     }
 }
 
+impl<'a> TryOptionValueFrom<'a> for u8 {
+    fn try_option_value_from(buffer: &'a [u8]) -> Option<Self> {
+        match try_decode_u16(buffer)? {
+            value if value <= u16::from(u8::MAX) => Some(value as u8),
+            _ => None,
+        }
+    }
+}
+
 impl<'a> TryOptionValueFrom<'a> for () {
     fn try_option_value_from(_: &'a [u8]) -> Option<Self> {
         Some(())
@@ -0,0 +1,201 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+
+use crate::send_desc::SendDesc;
+use futures::task::Context;
+use futures::task::Poll;
+use futures_timer::Delay;
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+
+/// A [`Stream`] that is created by [`RemoteEndpointExt::poll_as_stream`], which sends a
+/// request every time the polling interval elapses and yields the result of each request.
+///
+/// The first request is sent as soon as the stream is first polled. The stream never
+/// ends on its own: errors (like [`Error::ResponseTimeout`]) are yielded like any other
+/// result, and polling continues until the stream is dropped.
+///
+/// Only one request is outstanding at a time. By default, if a request is still pending
+/// when the interval elapses, no additional request is sent for that interval. See
+/// [`PollAsStream::skip_if_pending`] for the alternative.
+///
+/// [`Stream`]: futures::stream::Stream
+/// [`RemoteEndpointExt::poll_as_stream`]: crate::RemoteEndpointExt::poll_as_stream
+pub struct PollAsStream<'a, RE, SD, R> {
+    remote_endpoint: &'a RE,
+    send_desc: SD,
+    interval: Duration,
+    jitter: Duration,
+    skip_if_pending: bool,
+    delay: Delay,
+    pending: Option<BoxFuture<'a, Result<R, Error>>>,
+    missed: bool,
+}
+
+impl<'a, RE, SD, R> core::fmt::Debug for PollAsStream<'a, RE, SD, R> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        f.debug_struct("PollAsStream")
+            .field("interval", &self.interval)
+            .field("jitter", &self.jitter)
+            .field("skip_if_pending", &self.skip_if_pending)
+            .field("is_pending", &self.pending.is_some())
+            .finish()
+    }
+}
+
+// The send descriptor is never pinned, it is only ever cloned.
+impl<'a, RE, SD, R> Unpin for PollAsStream<'a, RE, SD, R> {}
+
+impl<'a, RE, SD, R> PollAsStream<'a, RE, SD, R> {
+    pub(crate) fn new(remote_endpoint: &'a RE, interval: Duration, send_desc: SD) -> Self {
+        PollAsStream {
+            remote_endpoint,
+            send_desc,
+            interval,
+            jitter: Duration::from_secs(0),
+            skip_if_pending: true,
+            delay: Delay::new(Duration::from_secs(0)),
+            pending: None,
+            missed: false,
+        }
+    }
+
+    /// Adds a random delay of up to `jitter` to each interval, so that many devices which
+    /// were started at the same time don't keep polling the server at the same moment.
+    pub fn with_jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Determines what happens when the interval elapses while the previous request is
+    /// still pending.
+    ///
+    /// If `skip` is true (the default), no request is sent for that interval. Otherwise,
+    /// the next request is sent as soon as the previous one has finished.
+    pub fn skip_if_pending(mut self, skip: bool) -> Self {
+        self.skip_if_pending = skip;
+        self
+    }
+
+    fn next_interval(&self) -> Duration {
+        if self.jitter == Duration::from_secs(0) {
+            self.interval
+        } else {
+            self.interval + self.jitter.mul_f64(rand::random::<f64>())
+        }
+    }
+}
+
+impl<'a, RE, SD, R> PollAsStream<'a, RE, SD, R>
+where
+    RE: RemoteEndpoint,
+    SD: SendDesc<RE::InboundContext, R> + Clone + 'a,
+    R: Send + 'a,
+{
+    fn send_next(&mut self) {
+        self.pending = Some(self.remote_endpoint.send(self.send_desc.clone()));
+    }
+}
+
+impl<'a, RE, SD, R> Stream for PollAsStream<'a, RE, SD, R>
+where
+    RE: RemoteEndpoint,
+    SD: SendDesc<RE::InboundContext, R> + Clone + 'a,
+    R: Send + 'a,
+{
+    type Item = Result<R, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(pending) = self.pending.as_mut() {
+                if let Poll::Ready(result) = pending.poll_unpin(cx) {
+                    self.pending = None;
+
+                    if self.missed {
+                        self.missed = false;
+                        self.send_next();
+                    }
+
+                    return Poll::Ready(Some(result));
+                }
+            }
+
+            match self.delay.poll_unpin(cx) {
+                Poll::Ready(()) => {
+                    let next_interval = self.next_interval();
+                    self.delay.reset(Instant::now() + next_interval);
+
+                    if self.pending.is_none() {
+                        self.send_next();
+                    } else if !self.skip_if_pending {
+                        self.missed = true;
+                    }
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datagram::{
+        DatagramLocalEndpoint, DatagramRespondableInboundContext, LoopbackSocket,
+        LoopbackSocketAddr,
+    };
+    use futures::executor::block_on;
+    use futures::future::{select, Either};
+
+    #[test]
+    fn poll_as_stream() {
+        let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+        let remote_endpoint = local_endpoint.remote_endpoint(
+            LoopbackSocketAddr::Unicast,
+            None::<String>,
+            rel_ref!(""),
+        );
+
+        let handler = |context: &DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+            context.respond(|msg_out| {
+                msg_out.set_msg_code(MsgCode::SuccessContent);
+                Ok(())
+            })
+        };
+
+        let start = Instant::now();
+        let future = remote_endpoint
+            .poll_as_stream(
+                Duration::from_millis(20),
+                CoapRequest::get().emit_msg_code(),
+            )
+            .with_jitter(Duration::from_millis(5))
+            .take(3)
+            .collect::<Vec<_>>();
+        let future_receive = local_endpoint.receive_loop(handler);
+
+        let results = match block_on(select(future, future_receive)) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((results, _)) => results,
+        };
+
+        assert_eq!(vec![Ok(MsgCode::SuccessContent); 3], results);
+
+        // The first request is sent immediately, the others after an interval each.
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+}
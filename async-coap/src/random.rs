@@ -0,0 +1,196 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// A source of random numbers, used to jitter retransmission timeouts.
+///
+/// Local endpoints which support it (like
+/// [`DatagramLocalEndpoint::set_random_source`](crate::datagram::DatagramLocalEndpoint::set_random_source))
+/// make their random source available while calling [`SendDesc::delay_to_retransmit`],
+/// so that the default implementation of that method (and
+/// [`TransParams::calc_retransmit_duration`]) uses it instead of [`ThreadRandom`].
+/// This allows constrained ports to use a hardware random number generator, and tests
+/// to run deterministically.
+///
+/// [`SendDesc::delay_to_retransmit`]: crate::send_desc::SendDesc::delay_to_retransmit
+/// [`TransParams::calc_retransmit_duration`]: crate::TransParams::calc_retransmit_duration
+pub trait RandomSource: Send + Sync {
+    /// Returns the next random number.
+    fn next_u64(&self) -> u64;
+}
+
+impl std::fmt::Debug for dyn RandomSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("RandomSource")
+    }
+}
+
+/// [`RandomSource`] which uses the thread-local generator from the `rand` crate.
+///
+/// This is the default random source.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ThreadRandom;
+
+impl RandomSource for ThreadRandom {
+    fn next_u64(&self) -> u64 {
+        rand::random()
+    }
+}
+
+/// [`RandomSource`] which always returns zero, which disables retransmission jitter.
+///
+/// With this source, the first retransmission timeout is always exactly `ACK_TIMEOUT`.
+/// This is useful for latency-sensitive benchmarks.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct NoJitter;
+
+impl RandomSource for NoJitter {
+    fn next_u64(&self) -> u64 {
+        0
+    }
+}
+
+/// Deterministic [`RandomSource`] which returns the same sequence of numbers for a
+/// given seed.
+///
+/// The numbers are generated using [SplitMix64], which is fast but not cryptographically
+/// secure. It is intended for tests and simulations.
+///
+/// [SplitMix64]: http://xoshiro.di.unimi.it/splitmix64.c
+#[derive(Debug)]
+pub struct SeededRandom {
+    state: AtomicU64,
+}
+
+impl SeededRandom {
+    /// Creates a new [`SeededRandom`] instance, starting from `seed`.
+    pub fn new(seed: u64) -> SeededRandom {
+        SeededRandom {
+            state: AtomicU64::new(seed),
+        }
+    }
+}
+
+impl RandomSource for SeededRandom {
+    fn next_u64(&self) -> u64 {
+        const GAMMA: u64 = 0x9E37_79B9_7F4A_7C15;
+
+        let mut z = self
+            .state
+            .fetch_add(GAMMA, Ordering::Relaxed)
+            .wrapping_add(GAMMA);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<dyn RandomSource>>> = RefCell::new(None);
+}
+
+/// Restores the previous random source when dropped, even if `f` panics.
+struct RestoreOnDrop(Option<Arc<dyn RandomSource>>);
+
+impl Drop for RestoreOnDrop {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        CURRENT.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+/// Calls `f`, with `source` being used by [`random_u64`] on this thread until it returns.
+pub(crate) fn with_random_source<F, R>(source: &Arc<dyn RandomSource>, f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let previous = CURRENT.with(|current| current.replace(Some(source.clone())));
+    let _restore = RestoreOnDrop(previous);
+    f()
+}
+
+/// Returns a random number from the random source of the local endpoint that is currently
+/// calculating a retransmission timeout, or from [`ThreadRandom`] if there isn't one.
+pub(crate) fn random_u64() -> u64 {
+    CURRENT
+        .with(|current| current.borrow().as_ref().map(|source| source.next_u64()))
+        .unwrap_or_else(|| ThreadRandom.next_u64())
+}
+
+/// Scales `millis` by a random factor between 1.0 and `random_factor`, as described for
+/// `ACK_RANDOM_FACTOR` in [IETF-RFC7252 Section 4.2].
+///
+/// [IETF-RFC7252 Section 4.2]: https://tools.ietf.org/html/rfc7252#section-4.2
+pub(crate) fn jitter_millis(millis: u64, random_factor: f32) -> u64 {
+    const JDIV: u64 = 512u64;
+    let rmod: u64 = (JDIV as f32 * (random_factor - 1.0)) as u64;
+
+    // A random factor of 1.0 (or less) means no jitter at all.
+    let jmul = match rmod {
+        0 => JDIV,
+        rmod => JDIV + random_u64() % rmod,
+    };
+
+    millis.saturating_mul(jmul) / JDIV
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_random_is_deterministic() {
+        let a = SeededRandom::new(1234);
+        let b = SeededRandom::new(1234);
+
+        let a: Vec<u64> = (0..8).map(|_| a.next_u64()).collect();
+        let b: Vec<u64> = (0..8).map(|_| b.next_u64()).collect();
+
+        assert_eq!(a, b);
+        assert_ne!(a[0], a[1]);
+    }
+
+    #[test]
+    fn with_random_source_nests() {
+        let outer: Arc<dyn RandomSource> = Arc::new(SeededRandom::new(0));
+        let inner: Arc<dyn RandomSource> = Arc::new(NoJitter);
+        let expected = SeededRandom::new(0);
+
+        with_random_source(&outer, || {
+            assert_eq!(expected.next_u64(), random_u64());
+            with_random_source(&inner, || assert_eq!(0, random_u64()));
+            assert_eq!(expected.next_u64(), random_u64());
+        });
+    }
+
+    #[test]
+    fn jitter() {
+        let no_jitter: Arc<dyn RandomSource> = Arc::new(NoJitter);
+
+        with_random_source(&no_jitter, || {
+            assert_eq!(2000, jitter_millis(2000, 1.5));
+        });
+
+        for _ in 0..100 {
+            let millis = jitter_millis(2000, 1.5);
+            assert!((2000..3000).contains(&millis), "{}", millis);
+        }
+
+        assert_eq!(2000, jitter_millis(2000, 1.0));
+    }
+}
@@ -16,26 +16,110 @@
 use super::*;
 use futures::task::Context;
 use futures::task::Poll;
+use futures_timer::Delay;
 use std::pin::Pin;
+use std::time::Duration;
+
+/// What a [`ReceiveAsStream`] does after the underlying call to [`LocalEndpoint::receive`]
+/// returns an error, as decided by its [`ReceiveErrorPolicy`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ReceiveErrorAction {
+    /// The error is emitted and the stream terminates.
+    Terminate,
+
+    /// The error is ignored and the next message is received immediately.
+    Continue,
+
+    /// The error is ignored and the next message is received after waiting for the
+    /// given duration. Useful for transient socket errors (like running out of file
+    /// descriptors or buffer space), which would otherwise be retried in a tight loop.
+    Backoff(Duration),
+}
+
+/// Decides which errors from [`LocalEndpoint::receive`] are fatal to a [`ReceiveAsStream`]
+/// and which are transient. Set with [`ReceiveAsStream::with_error_policy`].
+///
+/// This trait is implemented for all closures of the form
+/// `FnMut(Error) -> ReceiveErrorAction`:
+///
+/// ```
+/// # use async_coap::prelude::*;
+/// # use async_coap::null::NullLocalEndpoint;
+/// # use async_coap::{Error, ReceiveErrorAction};
+/// # use futures::prelude::*;
+/// # use std::time::Duration;
+/// # let local_endpoint = NullLocalEndpoint::new();
+/// let receive_loop = local_endpoint
+///     .receive_as_stream(null_receiver!())
+///     .with_error_policy(|err| match err {
+///         Error::Cancelled => ReceiveErrorAction::Terminate,
+///         Error::IOError => ReceiveErrorAction::Backoff(Duration::from_millis(100)),
+///         _ => ReceiveErrorAction::Continue,
+///     })
+///     .collect::<Error>();
+/// ```
+pub trait ReceiveErrorPolicy {
+    /// Classifies `err`, which was returned by [`LocalEndpoint::receive`].
+    fn classify(&mut self, err: Error) -> ReceiveErrorAction;
+}
+
+impl<F: FnMut(Error) -> ReceiveErrorAction> ReceiveErrorPolicy for F {
+    fn classify(&mut self, err: Error) -> ReceiveErrorAction {
+        self(err)
+    }
+}
+
+/// The [`ReceiveErrorPolicy`] used by [`ReceiveAsStream`] unless another one is set.
+///
+/// [`Error::Cancelled`] is always fatal. [`Error::IOError`] is fatal as well, unless a
+/// backoff was set using [`DefaultReceiveErrorPolicy::with_io_error_backoff`]. All other
+/// errors are ignored.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub struct DefaultReceiveErrorPolicy {
+    io_error_backoff: Option<Duration>,
+}
+
+impl DefaultReceiveErrorPolicy {
+    /// Returns a policy which treats [`Error::IOError`] as transient, waiting for `backoff`
+    /// before receiving again.
+    pub fn with_io_error_backoff(backoff: Duration) -> DefaultReceiveErrorPolicy {
+        DefaultReceiveErrorPolicy {
+            io_error_backoff: Some(backoff),
+        }
+    }
+}
+
+impl ReceiveErrorPolicy for DefaultReceiveErrorPolicy {
+    fn classify(&mut self, err: Error) -> ReceiveErrorAction {
+        match (err, self.io_error_backoff) {
+            (Error::Cancelled, _) | (Error::IOError, None) => ReceiveErrorAction::Terminate,
+            (Error::IOError, Some(backoff)) => ReceiveErrorAction::Backoff(backoff),
+            _ => ReceiveErrorAction::Continue,
+        }
+    }
+}
 
 /// A [`Stream`] that is created by [`LocalEndpointExt::receive_as_stream`].
 ///
 /// [`Stream`]: futures::stream::Stream
 /// [`LocalEndpointExt::receive_as_stream`]: crate::LocalEndpointExt::receive_as_stream
-pub struct ReceiveAsStream<'a, LE, F> {
+pub struct ReceiveAsStream<'a, LE, F, P = DefaultReceiveErrorPolicy> {
     local_endpoint: &'a LE,
     handler: F,
+    error_policy: P,
     recv_future: Option<BoxFuture<'a, Result<(), Error>>>,
+    backoff: Option<Delay>,
 }
 
-impl<'a, LE: core::fmt::Debug, F: core::fmt::Debug> core::fmt::Debug
-    for ReceiveAsStream<'a, LE, F>
+impl<'a, LE: core::fmt::Debug, F: core::fmt::Debug, P> core::fmt::Debug
+    for ReceiveAsStream<'a, LE, F, P>
 {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
         f.debug_struct("ReceiveAsStream")
             .field("local_endpoint", self.local_endpoint)
             .field("handler", &self.handler)
             .field("recv_future", &self.recv_future.as_ref().map(|_| ""))
+            .field("is_backing_off", &self.backoff.is_some())
             .finish()
     }
 }
@@ -50,27 +134,66 @@ where
             local_endpoint,
             recv_future: None,
             handler,
+            error_policy: DefaultReceiveErrorPolicy::default(),
+            backoff: None,
         };
         ret.update_recv_future();
         return ret;
     }
+}
+
+impl<'a, LE, F, P> ReceiveAsStream<'a, LE, F, P>
+where
+    LE: LocalEndpoint,
+    F: FnMut(&LE::RespondableInboundContext) -> Result<(), Error> + 'a + Clone + Unpin + Send,
+    P: ReceiveErrorPolicy + Unpin,
+{
+    /// Replaces the policy which decides which errors terminate this stream.
+    /// See [`ReceiveErrorPolicy`] for an example.
+    pub fn with_error_policy<P2>(self, error_policy: P2) -> ReceiveAsStream<'a, LE, F, P2>
+    where
+        P2: ReceiveErrorPolicy + Unpin,
+    {
+        ReceiveAsStream {
+            local_endpoint: self.local_endpoint,
+            handler: self.handler,
+            error_policy,
+            recv_future: self.recv_future,
+            backoff: self.backoff,
+        }
+    }
 
     fn update_recv_future(&mut self) {
         self.recv_future = Some(self.local_endpoint.receive(self.handler.clone()));
     }
 
     fn _poll_next_unpin(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<(), Error>>> {
+        if let Some(backoff) = self.backoff.as_mut() {
+            match backoff.poll_unpin(cx) {
+                Poll::Ready(()) => self.backoff = None,
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+
         if let Some(recv_future) = self.recv_future.as_mut() {
             match recv_future.poll_unpin(cx) {
-                Poll::Ready(Err(Error::IOError)) => {
-                    self.recv_future = None;
-                    Poll::Ready(Some(Err(Error::IOError)))
-                }
-                Poll::Ready(Err(Error::Cancelled)) => {
-                    self.recv_future = None;
-                    Poll::Ready(Some(Err(Error::Cancelled)))
-                }
-                Poll::Ready(_) => {
+                Poll::Ready(Err(err)) => match self.error_policy.classify(err) {
+                    ReceiveErrorAction::Terminate => {
+                        self.recv_future = None;
+                        Poll::Ready(Some(Err(err)))
+                    }
+                    ReceiveErrorAction::Continue => {
+                        self.update_recv_future();
+                        Poll::Ready(Some(Ok(())))
+                    }
+                    ReceiveErrorAction::Backoff(duration) => {
+                        debug!("Receive failed with {:?}, retrying in {:?}", err, duration);
+                        self.update_recv_future();
+                        self.backoff = Some(Delay::new(duration));
+                        Poll::Ready(Some(Ok(())))
+                    }
+                },
+                Poll::Ready(Ok(())) => {
                     self.update_recv_future();
                     Poll::Ready(Some(Ok(())))
                 }
@@ -82,10 +205,11 @@ where
     }
 }
 
-impl<'a, LE, F> Stream for ReceiveAsStream<'a, LE, F>
+impl<'a, LE, F, P> Stream for ReceiveAsStream<'a, LE, F, P>
 where
     LE: LocalEndpoint,
     F: FnMut(&LE::RespondableInboundContext) -> Result<(), Error> + 'a + Clone + Unpin + Send,
+    P: ReceiveErrorPolicy + Unpin,
 {
     type Item = Result<(), Error>;
 
@@ -93,3 +217,73 @@ where
         self.get_mut()._poll_next_unpin(cx)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datagram::{AsyncSendTo, DatagramLocalEndpoint, LoopbackSocket, LoopbackSocketAddr};
+    use futures::executor::block_on;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    fn endpoint_with_two_requests() -> DatagramLocalEndpoint<LoopbackSocket> {
+        let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+
+        // Two non-confirmable GET requests.
+        for msg in [[0x50, 0x01, 0x00, 0x01], [0x50, 0x01, 0x00, 0x02]].iter() {
+            block_on(
+                local_endpoint
+                    .socket()
+                    .send_to(msg, LoopbackSocketAddr::Unicast),
+            )
+            .unwrap();
+        }
+
+        local_endpoint
+    }
+
+    #[test]
+    fn default_policy_terminates_on_io_error() {
+        let local_endpoint = endpoint_with_two_requests();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let calls_clone = calls.clone();
+
+        let results = block_on(
+            local_endpoint
+                .receive_as_stream(move |_: &_| {
+                    calls_clone.fetch_add(1, Ordering::SeqCst);
+                    Err(Error::IOError)
+                })
+                .collect::<Vec<_>>(),
+        );
+
+        assert_eq!(vec![Err(Error::IOError)], results);
+        assert_eq!(1, calls.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn backoff_on_io_error() {
+        let local_endpoint = endpoint_with_two_requests();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let calls_clone = calls.clone();
+
+        let start = Instant::now();
+        let results = block_on(
+            local_endpoint
+                .receive_as_stream(move |_: &_| {
+                    calls_clone.fetch_add(1, Ordering::SeqCst);
+                    Err(Error::IOError)
+                })
+                .with_error_policy(DefaultReceiveErrorPolicy::with_io_error_backoff(
+                    Duration::from_millis(20),
+                ))
+                .take(2)
+                .collect::<Vec<_>>(),
+        );
+
+        assert_eq!(vec![Ok(()), Ok(())], results);
+        assert_eq!(2, calls.load(Ordering::SeqCst));
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+}
@@ -15,6 +15,7 @@
 
 use super::*;
 use crate::UriBuf;
+use core::time::Duration;
 
 /// An object that represents a remote CoAP endpoint with a default, overridable path.
 ///
@@ -56,7 +57,7 @@ use crate::UriBuf;
 ///
 /// // Create a future that sends a request to a specific path
 /// // on the remote endpoint, collecting any blocks in the response
-/// // and returning `Ok(OwnedImmutableMessage)` upon success.
+/// // and returning `Ok(Response)` upon success.
 /// let future = remote_endpoint.send_to(
 ///     rel_ref!("large"),
 ///     CoapRequest::get()       // This is a CoAP GET request
@@ -83,13 +84,39 @@ pub trait RemoteEndpoint {
     type InboundContext: InboundContext<SocketAddr = Self::SocketAddr>;
 
     /// Returns a [`UriBuf`] describing the underlying destination of this remote endpoint.
+    ///
+    /// The host is taken from the `Uri-Host` option (see [`RemoteEndpoint::host_option`]) if
+    /// there is one, otherwise it is rendered from the socket address. The port is omitted
+    /// if it is the default port for the scheme. The path and query are those of
+    /// [`RemoteEndpoint::default_path`].
     fn uri(&self) -> UriBuf;
 
     /// Returns a string slice containing the scheme for this `RemoteEndpoint`.
     fn scheme(&self) -> &'static str;
 
+    /// Returns the value of the `Uri-Host` option that is included in requests sent to this
+    /// remote endpoint, if any.
+    fn host_option(&self) -> Option<&str>;
+
+    /// Returns the path (and query) that requests sent using [`RemoteEndpoint::send`] are
+    /// directed to, and that the relative references passed to [`RemoteEndpoint::send_to`]
+    /// are resolved against.
+    fn default_path(&self) -> &RelRef;
+
+    /// Sets the value of the `Uri-Host` option to include in requests sent to this remote
+    /// endpoint, which is useful when the server is virtual-hosted. Passing `None` prevents
+    /// this remote endpoint from including a `Uri-Host` option.
+    ///
+    /// The host can also be overridden for individual requests using
+    /// [`SendDescExt::uri_host`](crate::send_desc::SendDescExt::uri_host).
+    fn set_host_option(&mut self, host: Option<&str>);
+
     /// Prevents this remote endpoint from including a `Uri-Host` option.
-    fn remove_host_option(&mut self);
+    ///
+    /// Equivalent to calling [`RemoteEndpoint::set_host_option`] with `None`.
+    fn remove_host_option(&mut self) {
+        self.set_host_option(None)
+    }
 
     /// Creates a clone of this `RemoteEndpoint` with a different relative path.
     fn clone_using_rel_ref(&self, uri: &RelRef) -> Self;
@@ -118,6 +145,20 @@ pub trait RemoteEndpointExt: RemoteEndpoint {
         self.send(Ping::new())
     }
 
+    /// Analogous to [`LocalEndpointExt::send_with_cancel`], except using this
+    /// `RemoteEndpoint` for the destination SocketAddr and path.
+    fn send_with_cancel<'a, R, SD>(
+        &'a self,
+        send_desc: SD,
+        token: CancellationToken,
+    ) -> BoxFuture<'a, Result<R, Error>>
+    where
+        SD: SendDesc<Self::InboundContext, R> + 'a,
+        R: Send + 'a,
+    {
+        token.guard(self.send(send_desc))
+    }
+
     /// Analogous to [`LocalEndpointExt::send_as_stream`], except using this `RemoteEndpoint` for
     /// the destination SocketAddr and path.
     fn send_as_stream<'a, R, SD>(&'a self, send_desc: SD) -> SendAsStream<'a, R>
@@ -125,12 +166,21 @@ pub trait RemoteEndpointExt: RemoteEndpoint {
         SD: SendDesc<Self::InboundContext, R> + 'a,
         R: Send + 'a,
     {
-        let (sender, receiver) = futures::channel::mpsc::channel::<Result<R, Error>>(10);
+        self.send_as_stream_with_buffer(send_desc, StreamBuffer::default())
+    }
 
-        SendAsStream {
-            receiver,
-            send_future: self.send(SendAsStreamDesc::new(send_desc, sender)),
-        }
+    /// Analogous to [`LocalEndpointExt::send_as_stream_with_buffer`], except using this
+    /// `RemoteEndpoint` for the destination SocketAddr and path.
+    fn send_as_stream_with_buffer<'a, R, SD>(
+        &'a self,
+        send_desc: SD,
+        buffer: StreamBuffer,
+    ) -> SendAsStream<'a, R>
+    where
+        SD: SendDesc<Self::InboundContext, R> + 'a,
+        R: Send + 'a,
+    {
+        SendAsStream::new(send_desc, buffer, |send_desc| self.send(send_desc))
     }
 
     /// Analogous to [`LocalEndpointExt::send_as_stream`], except using this `RemoteEndpoint` for
@@ -141,12 +191,127 @@ pub trait RemoteEndpointExt: RemoteEndpoint {
         R: Send + 'a,
         UF: AsRef<RelRef>,
     {
-        let (sender, receiver) = futures::channel::mpsc::channel::<Result<R, Error>>(10);
+        SendAsStream::new(send_desc, StreamBuffer::default(), |send_desc| {
+            self.send_to(path, send_desc)
+        })
+    }
+
+    /// Sends a clone of `send_desc` every time `interval` elapses, returning a stream of
+    /// the results. The first request is sent right away.
+    ///
+    /// Only one request is outstanding at a time, and the stream never ends on its own.
+    /// The returned [`PollAsStream`] can be configured to add jitter to the interval and to
+    /// change what happens when a request is still pending at the end of an interval.
+    ///
+    /// Combine with [`SendDescExt::prepare`] to avoid serializing the request each time.
+    ///
+    /// ```
+    /// # use async_coap::prelude::*;
+    /// # use async_coap::{RemoteEndpoint, RemoteEndpointExt, Error};
+    /// # use futures::prelude::*;
+    /// # use std::time::Duration;
+    /// # async fn poll<RE: RemoteEndpoint>(remote_endpoint: RE) {
+    /// let mut stream = remote_endpoint
+    ///     .poll_as_stream(
+    ///         Duration::from_secs(60),
+    ///         CoapRequest::get().prepare().emit_successful_response(),
+    ///     )
+    ///     .with_jitter(Duration::from_secs(5));
+    ///
+    /// while let Some(result) = stream.next().await {
+    ///     match result {
+    ///         Ok(response) => println!("temp: {:?}", response.payload_as_str()),
+    ///         Err(e) => println!("poll failed: {}", e),
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    fn poll_as_stream<'a, R, SD>(
+        &'a self,
+        interval: Duration,
+        send_desc: SD,
+    ) -> PollAsStream<'a, Self, SD, R>
+    where
+        SD: SendDesc<Self::InboundContext, R> + Clone + 'a,
+        R: Send + 'a,
+        Self: Sized,
+    {
+        PollAsStream::new(self, interval, send_desc)
+    }
+
+    /// Observes a resource using a clone of `send_desc`, re-registering the observation
+    /// whenever it is lost due to a transient failure, as described by `policy`.
+    ///
+    /// Notifications are yielded as [`ObserveEvent::Notification`]. After the observation
+    /// has been re-established, [`ObserveEvent::Resumed`] is yielded right before the next
+    /// notification, indicating whether notifications might have been missed in between.
+    ///
+    /// ```
+    /// # use async_coap::prelude::*;
+    /// # use async_coap::{RemoteEndpoint, RemoteEndpointExt, ObserveEvent};
+    /// # use futures::prelude::*;
+    /// # async fn observe<RE: RemoteEndpoint>(remote_endpoint: RE) {
+    /// let mut stream = remote_endpoint.observe_with_reconnect(
+    ///     CoapRequest::observe().emit_successful_response(),
+    ///     Default::default(),
+    /// );
+    ///
+    /// while let Some(result) = stream.next().await {
+    ///     match result {
+    ///         Ok(ObserveEvent::Notification(response)) => {
+    ///             println!("temp: {:?}", response.payload_as_str())
+    ///         }
+    ///         Ok(ObserveEvent::Resumed { missed_possible }) => {
+    ///             println!("observation resumed, missed_possible: {}", missed_possible)
+    ///         }
+    ///         Err(e) => println!("observation failed: {}", e),
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    fn observe_with_reconnect<'a, R, SD>(
+        &'a self,
+        send_desc: SD,
+        policy: ObserveReconnectPolicy,
+    ) -> ReconnectingObservation<'a, Self, SD, R>
+    where
+        SD: SendDesc<Self::InboundContext, R> + Clone + 'a,
+        R: Send + 'a,
+        Self: Sized,
+    {
+        ReconnectingObservation::new(self, send_desc, policy)
+    }
 
-        SendAsStream {
-            receiver,
-            send_future: self.send_to(path, SendAsStreamDesc::new(send_desc, sender)),
-        }
+    /// Observes a resource using `send_desc`, returning a stream of notifications that can
+    /// be explicitly deregistered using [`CancelableObservation::cancel`].
+    ///
+    /// ```
+    /// # use async_coap::prelude::*;
+    /// # use async_coap::{Error, RemoteEndpoint, RemoteEndpointExt};
+    /// # use futures::prelude::*;
+    /// # async fn observe<RE: RemoteEndpoint>(remote_endpoint: RE) -> Result<(), Error> {
+    /// let mut observation =
+    ///     remote_endpoint.observe_cancelable(CoapRequest::observe().emit_successful_response());
+    ///
+    /// if let Some(response) = observation.next().await {
+    ///     println!("temp: {:?}", response?.payload_as_str());
+    /// }
+    ///
+    /// let response = observation.cancel().await?;
+    /// println!("final temp: {:?}", response.payload_as_str());
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn observe_cancelable<'a, R, SD>(
+        &'a self,
+        send_desc: SD,
+    ) -> CancelableObservation<'a, Self, SD, R>
+    where
+        SD: SendDesc<Self::InboundContext, R> + Clone + 'a,
+        R: Send + 'a,
+        Self: Sized,
+    {
+        CancelableObservation::new(self, send_desc)
     }
 }
 
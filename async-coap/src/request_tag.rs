@@ -0,0 +1,110 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use core::ops::Deref;
+
+/// Type for holding the value of a Request-Tag option, as described in [IETF-RFC9175].
+///
+/// A Request-Tag allows a server to recognize that two requests belong to the same
+/// operation even if they were sent in different CoAP messages. To make a retried
+/// non-idempotent request (like a `POST`) recognizable, create a tag once with
+/// [`RequestTag::random`] and attach that *same* tag to every attempt using
+/// [`SendDescExt::request_tag`](crate::send_desc::SendDescExt::request_tag).
+///
+/// [IETF-RFC9175]: https://tools.ietf.org/html/rfc9175
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone, Ord, PartialOrd)]
+pub struct RequestTag {
+    len: u8,
+    bytes: [u8; 8],
+}
+
+impl RequestTag {
+    /// Describes the maximum length of a Request-Tag (8 bytes).
+    pub const MAX_LEN: usize = 8;
+
+    /// Constant representing an empty Request-Tag.
+    pub const EMPTY: RequestTag = RequestTag {
+        len: 0u8,
+        bytes: [0; 8],
+    };
+
+    /// Creates a new Request-Tag from the given byte slice, returning `None` if
+    /// it is longer than [`RequestTag::MAX_LEN`].
+    pub fn new(x: &[u8]) -> Option<RequestTag> {
+        if x.len() > RequestTag::MAX_LEN {
+            return None;
+        }
+
+        let mut bytes = [0u8; 8];
+        bytes[..x.len()].copy_from_slice(x);
+
+        Some(RequestTag {
+            len: x.len() as u8,
+            bytes,
+        })
+    }
+
+    /// Creates a new, randomly generated Request-Tag of the maximum length.
+    pub fn random() -> RequestTag {
+        RequestTag {
+            len: RequestTag::MAX_LEN as u8,
+            bytes: rand::random(),
+        }
+    }
+
+    /// Returns the length of this Request-Tag in bytes.
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    /// Returns true if the length of this Request-Tag is zero.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the value of this Request-Tag as a byte slice.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len as usize]
+    }
+}
+
+impl std::fmt::Display for RequestTag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for b in self.as_bytes() {
+            write!(f, "{:02X}", b)?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for RequestTag {
+    fn default() -> Self {
+        RequestTag::EMPTY
+    }
+}
+
+impl Deref for RequestTag {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        self.as_bytes()
+    }
+}
+
+impl core::cmp::PartialEq<[u8]> for RequestTag {
+    fn eq(&self, other: &[u8]) -> bool {
+        self.as_bytes() == other
+    }
+}
@@ -0,0 +1,518 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Dispatching of inbound requests to handlers by path and method.
+//!
+//! Instead of writing one handler which decodes the path of every request and matches on
+//! it, handlers for individual resources are registered with a [`Router`] using path
+//! templates like `/sensors/{id}/value`. The values of the parameters in the template are
+//! passed to the handler as [`PathParams`].
+//!
+//! This module is only available when the `server` feature is enabled.
+
+use super::*;
+use crate::option::OptionIteratorExt;
+use std::str::FromStr;
+
+type RouteHandler<T> = Box<dyn Fn(&T, &PathParams) -> Result<(), Error> + Send + Sync>;
+type DefaultHandler<T> = Box<dyn Fn(&T) -> Result<(), Error> + Send + Sync>;
+
+/// The values of the parameters of a path template, as extracted from the path of a
+/// request by [`Router::handle`].
+///
+/// The values are the decoded path segments, so they are *not* percent-encoded.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct PathParams {
+    params: Vec<(String, String)>,
+}
+
+impl PathParams {
+    /// Returns the value of the parameter called `name`, or `None` if the template
+    /// doesn't have such a parameter.
+    ///
+    /// The value of a trailing `{name*}` parameter is the remaining path segments joined
+    /// by `/`, which is empty if there are none.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Parses the value of the parameter called `name` as a `V`, returning `None` if there
+    /// is no such parameter or if its value couldn't be parsed.
+    ///
+    /// ```
+    /// # use async_coap::resource::PathParams;
+    /// # fn handle(params: &PathParams) -> Option<()> {
+    /// let id: u32 = params.parse("id")?;
+    /// # Some(())
+    /// # }
+    /// ```
+    pub fn parse<V: FromStr>(&self, name: &str) -> Option<V> {
+        self.get(name)?.parse().ok()
+    }
+
+    /// Returns an iterator over the names and values of the parameters, in the order in
+    /// which they appear in the template.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.params
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    /// Returns the number of parameters.
+    pub fn len(&self) -> usize {
+        self.params.len()
+    }
+
+    /// Returns true if the template doesn't have any parameters.
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Segment {
+    /// Matches a path segment equal to the string.
+    Literal(String),
+
+    /// Matches any single path segment, written as `{name}`.
+    Param(String),
+
+    /// Matches all of the remaining path segments, written as `{name*}`.
+    Rest(String),
+}
+
+/// A parsed path template, like `/sensors/{id}/value`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct PathTemplate {
+    source: String,
+    segments: Vec<Segment>,
+}
+
+impl PathTemplate {
+    /// Parses `template`, panicking if it is malformed.
+    fn parse(template: &str) -> PathTemplate {
+        let path = template.strip_prefix('/').unwrap_or(template);
+        let mut segments = Vec::new();
+
+        if !path.is_empty() {
+            for segment in path.split('/') {
+                if let Some(Segment::Rest(_)) = segments.last() {
+                    panic!("{:?}: {{name*}} must be the last segment", template);
+                }
+
+                let name = match segment.strip_prefix('{') {
+                    Some(name) => name.strip_suffix('}').unwrap_or_else(|| {
+                        panic!("{:?}: unterminated parameter {:?}", template, segment)
+                    }),
+                    None => {
+                        segments.push(Segment::Literal(segment.to_string()));
+                        continue;
+                    }
+                };
+
+                segments.push(match name.strip_suffix('*') {
+                    Some(name) => Segment::Rest(name.to_string()),
+                    None => Segment::Param(name.to_string()),
+                });
+            }
+        }
+
+        PathTemplate {
+            source: template.to_string(),
+            segments,
+        }
+    }
+
+    /// Matches `path` against this template, returning the values of the parameters if
+    /// it matches.
+    fn matches(&self, path: &[&str]) -> Option<PathParams> {
+        let mut params = PathParams::default();
+        let mut path = path.iter();
+
+        for segment in self.segments.iter() {
+            match segment {
+                Segment::Literal(literal) => {
+                    if path.next()? != literal {
+                        return None;
+                    }
+                }
+                Segment::Param(name) => {
+                    let value = path.next()?;
+                    params.params.push((name.clone(), value.to_string()));
+                }
+                Segment::Rest(name) => {
+                    let value = path.by_ref().copied().collect::<Vec<_>>().join("/");
+                    params.params.push((name.clone(), value));
+                }
+            }
+        }
+
+        if path.next().is_some() {
+            return None;
+        }
+
+        Some(params)
+    }
+}
+
+struct Route<T> {
+    method: MsgCode,
+    template: PathTemplate,
+    handler: RouteHandler<T>,
+}
+
+/// Dispatches inbound requests to handlers registered for their path and method.
+///
+/// Handlers are registered for a method and a path template, like `/sensors/{id}/value`.
+/// Each segment of a template either has to match a path segment of the request exactly,
+/// or is a parameter: `{name}` matches any single path segment, and `{name*}` matches
+/// all of the remaining path segments (if any), so it may only appear at the end. The
+/// values of the parameters are passed to the handler as [`PathParams`].
+///
+/// Routes are tried in the order in which they were added, and the request is passed to
+/// the handler of the first one whose template and method both match. If the template of
+/// at least one route matches but none of those have a handler for the method of the
+/// request, the request is answered with `4.05 Method Not Allowed`. If no template
+/// matches, the request is passed to the default handler, or answered with
+/// `4.04 Not Found` if there isn't one.
+///
+/// Inbound messages which aren't requests are ignored, so that the local endpoint can
+/// handle them itself.
+///
+/// ```
+/// # use async_coap::prelude::*;
+/// # use async_coap::datagram::*;
+/// # use async_coap::{Error, RespondableInboundContext};
+/// # use async_coap::resource::Router;
+/// # let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+/// let router = Router::new()
+///     .get("/sensors", |context: &DatagramRespondableInboundContext<_>, _| {
+///         context.respond(|msg_out| {
+///             msg_out.set_msg_code(MsgCode::SuccessContent);
+///             msg_out.append_payload_string("1,2")
+///         })
+///     })
+///     .get("/sensors/{id}/value", |context, params| {
+///         let id: u32 = match params.parse("id") {
+///             Some(id) => id,
+///             None => return context.respond_not_found(),
+///         };
+///
+///         context.respond(|msg_out| {
+///             msg_out.set_msg_code(MsgCode::SuccessContent);
+///             msg_out.append_payload_string(&format!("value of sensor {}", id))
+///         })
+///     });
+///
+/// # let _ =
+/// local_endpoint.receive_loop(|context| router.handle(context));
+/// ```
+pub struct Router<T> {
+    routes: Vec<Route<T>>,
+    default: Option<DefaultHandler<T>>,
+}
+
+impl<T> std::fmt::Debug for Router<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let routes = self
+            .routes
+            .iter()
+            .map(|route| format!("{:?} {}", route.method, route.template.source))
+            .collect::<Vec<_>>();
+
+        f.debug_struct("Router")
+            .field("routes", &routes)
+            .field("default", &self.default.is_some())
+            .finish()
+    }
+}
+
+impl<T> Default for Router<T> {
+    fn default() -> Self {
+        Router {
+            routes: Vec::new(),
+            default: None,
+        }
+    }
+}
+
+impl<T: RespondableInboundContext> Router<T> {
+    /// Creates a new `Router` instance without any routes or default handler.
+    pub fn new() -> Router<T> {
+        Default::default()
+    }
+
+    /// Adds a route which passes requests with the method `method` and a path matching
+    /// `template` to `handler`.
+    ///
+    /// Panics if `method` isn't a method, or if `template` is malformed: if a parameter
+    /// is missing its closing brace, or if a `{name*}` parameter isn't the last segment.
+    pub fn with_route<F>(mut self, method: MsgCode, template: &str, handler: F) -> Router<T>
+    where
+        F: Fn(&T, &PathParams) -> Result<(), Error> + Send + Sync + 'static,
+    {
+        assert!(method.is_method(), "{:?} is not a method", method);
+
+        self.routes.push(Route {
+            method,
+            template: PathTemplate::parse(template),
+            handler: Box::new(handler),
+        });
+        self
+    }
+
+    /// Adds a route for `GET` requests. See [`Router::with_route`].
+    pub fn get<F>(self, template: &str, handler: F) -> Router<T>
+    where
+        F: Fn(&T, &PathParams) -> Result<(), Error> + Send + Sync + 'static,
+    {
+        self.with_route(MsgCode::MethodGet, template, handler)
+    }
+
+    /// Adds a route for `POST` requests. See [`Router::with_route`].
+    pub fn post<F>(self, template: &str, handler: F) -> Router<T>
+    where
+        F: Fn(&T, &PathParams) -> Result<(), Error> + Send + Sync + 'static,
+    {
+        self.with_route(MsgCode::MethodPost, template, handler)
+    }
+
+    /// Adds a route for `PUT` requests. See [`Router::with_route`].
+    pub fn put<F>(self, template: &str, handler: F) -> Router<T>
+    where
+        F: Fn(&T, &PathParams) -> Result<(), Error> + Send + Sync + 'static,
+    {
+        self.with_route(MsgCode::MethodPut, template, handler)
+    }
+
+    /// Adds a route for `DELETE` requests. See [`Router::with_route`].
+    pub fn delete<F>(self, template: &str, handler: F) -> Router<T>
+    where
+        F: Fn(&T, &PathParams) -> Result<(), Error> + Send + Sync + 'static,
+    {
+        self.with_route(MsgCode::MethodDelete, template, handler)
+    }
+
+    /// Sets the handler for requests whose path doesn't match the template of any route.
+    pub fn with_default<F>(mut self, handler: F) -> Router<T>
+    where
+        F: Fn(&T) -> Result<(), Error> + Send + Sync + 'static,
+    {
+        self.default = Some(Box::new(handler));
+        self
+    }
+
+    /// Passes the inbound request to the handler of the route matching its path and
+    /// method, or responds with an error if there is none.
+    ///
+    /// This method is intended to be called from the handler passed to
+    /// [`LocalEndpoint::receive`].
+    pub fn handle(&self, context: &T) -> Result<(), Error> {
+        let msg = context.message();
+        let method = msg.msg_code();
+
+        if !method.is_method() {
+            return Ok(());
+        }
+
+        let path = msg
+            .options()
+            .uri_path_segments()
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut allowed_methods = Vec::new();
+
+        for route in self.routes.iter() {
+            if let Some(params) = route.template.matches(&path) {
+                if route.method == method {
+                    return (route.handler)(context, &params);
+                }
+
+                if !allowed_methods.contains(&route.method) {
+                    allowed_methods.push(route.method);
+                }
+            }
+        }
+
+        if !allowed_methods.is_empty() {
+            context.respond_method_not_allowed(&allowed_methods)
+        } else if let Some(default) = self.default.as_ref() {
+            default(context)
+        } else {
+            context.respond_not_found()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datagram::{
+        DatagramLocalEndpoint, DatagramRespondableInboundContext, LoopbackSocket,
+        LoopbackSocketAddr,
+    };
+    use futures::executor::block_on;
+    use futures::future::{select, Either};
+
+    type Context = DatagramRespondableInboundContext<LoopbackSocketAddr>;
+
+    fn respond_with_params(context: &Context, params: &PathParams) -> Result<(), Error> {
+        let params = params
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<_>>();
+
+        context.respond(|msg_out| {
+            msg_out.set_msg_code(MsgCode::SuccessContent);
+            msg_out.append_payload_string(&params.join(","))
+        })
+    }
+
+    fn request(router: &Router<Context>, method: MsgCode, path: &str) -> (MsgCode, String) {
+        let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+        let future = local_endpoint.send(
+            LoopbackSocketAddr::Unicast,
+            CoapRequest::method(method)
+                .uri_host_path(None, RelRefBuf::from_str(path).unwrap())
+                .emit_any_response(),
+        );
+        let future_receive = local_endpoint.receive_loop(|context| router.handle(context));
+
+        let ret = match block_on(select(future, future_receive)) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((ret, _)) => ret.unwrap(),
+        };
+
+        (ret.msg_code(), ret.payload_as_str().unwrap().to_string())
+    }
+
+    #[test]
+    fn path_template() {
+        let matches = |template: &str, path: &[&str]| {
+            PathTemplate::parse(template)
+                .matches(path)
+                .map(|params| params.iter().map(|(k, v)| format!("{}={}", k, v)).collect())
+        };
+        let params = |x: &[&str]| Some(x.iter().map(|x| x.to_string()).collect::<Vec<_>>());
+
+        assert_eq!(matches("/", &[]), params(&[]));
+        assert_eq!(matches("", &[]), params(&[]));
+        assert_eq!(matches("/", &["a"]), None);
+        assert_eq!(matches("/a/b", &["a", "b"]), params(&[]));
+        assert_eq!(matches("a/b", &["a", "b"]), params(&[]));
+        assert_eq!(matches("/a/b", &["a"]), None);
+        assert_eq!(matches("/a/b", &["a", "b", "c"]), None);
+        assert_eq!(matches("/a/", &["a", ""]), params(&[]));
+        assert_eq!(matches("/a/{x}/c", &["a", "b/b", "c"]), params(&["x=b/b"]));
+        assert_eq!(matches("/a/{x}/c", &["a", "b", "d"]), None);
+        assert_eq!(matches("/a/{x*}", &["a"]), params(&["x="]));
+        assert_eq!(
+            matches("/{x}/{y*}", &["a", "b", "c"]),
+            params(&["x=a", "y=b/c"])
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn path_template_rest_not_last() {
+        PathTemplate::parse("/{x*}/a");
+    }
+
+    #[test]
+    #[should_panic]
+    fn path_template_unterminated() {
+        PathTemplate::parse("/{x");
+    }
+
+    #[test]
+    fn path_params() {
+        let params = PathTemplate::parse("/sensors/{id}/{name}")
+            .matches(&["sensors", "42", "temp"])
+            .unwrap();
+
+        assert_eq!(2, params.len());
+        assert_eq!(Some("42"), params.get("id"));
+        assert_eq!(Some(42u32), params.parse("id"));
+        assert_eq!(None, params.parse::<u32>("name"));
+        assert_eq!(None, params.get("value"));
+    }
+
+    #[test]
+    fn router() {
+        let router = Router::new()
+            .get("/sensors/{id}/value", respond_with_params)
+            .put("/sensors/{id}/value", |context: &Context, _: &_| {
+                context.respond(|msg_out| {
+                    msg_out.set_msg_code(MsgCode::SuccessChanged);
+                    Ok(())
+                })
+            })
+            .post("/sensors/{id}/{action}", respond_with_params)
+            .get("/files/{path*}", respond_with_params);
+
+        let ok = |payload: &str| (MsgCode::SuccessContent, payload.to_string());
+
+        assert_eq!(
+            request(&router, MsgCode::MethodGet, "sensors/3/value"),
+            ok("id=3")
+        );
+        assert_eq!(
+            request(&router, MsgCode::MethodPut, "sensors/3/value"),
+            (MsgCode::SuccessChanged, String::new())
+        );
+        assert_eq!(
+            request(&router, MsgCode::MethodPost, "sensors/3/value"),
+            ok("id=3,action=value")
+        );
+        assert_eq!(
+            request(&router, MsgCode::MethodPost, "sensors/3/reset"),
+            ok("id=3,action=reset")
+        );
+        assert_eq!(
+            request(&router, MsgCode::MethodGet, "files/a/b%2Fc"),
+            ok("path=a/b/c")
+        );
+        assert_eq!(
+            request(&router, MsgCode::MethodDelete, "sensors/3/value"),
+            (
+                MsgCode::ClientErrorMethodNotAllowed,
+                "Allow: GET, PUT, POST".to_string()
+            )
+        );
+        assert_eq!(
+            request(&router, MsgCode::MethodGet, "sensors/3"),
+            (MsgCode::ClientErrorNotFound, String::new())
+        );
+
+        let router = router.with_default(|context| {
+            context.respond(|msg_out| {
+                msg_out.set_msg_code(MsgCode::SuccessContent);
+                msg_out.append_payload_string("default")
+            })
+        });
+
+        assert_eq!(
+            request(&router, MsgCode::MethodGet, "sensors/3"),
+            ok("default")
+        );
+        assert_eq!(
+            request(&router, MsgCode::MethodGet, "sensors/3/value"),
+            ok("id=3")
+        );
+    }
+}
@@ -0,0 +1,240 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+use crate::message::{MsgId, MsgToken, MsgType, OwnedImmutableMessage};
+use crate::option::{OptionIterator, OptionIteratorExt, OptionKey, TryOptionValueFrom};
+use core::time::Duration;
+
+/// A response to an outbound request, as emitted by combinators like
+/// [`emit_any_response`][SendDescExt::emit_any_response],
+/// [`emit_successful_response`][SendDescExt::emit_successful_response], and
+/// [`emit_successful_collected_response`][UnicastBlock2::emit_successful_collected_response].
+///
+/// In addition to the message itself, a `Response` records which remote endpoint sent it
+/// (which matters for multicast requests) and how long it took to arrive. The most commonly
+/// used parts of the message have accessors of their own, the rest can be read via
+/// [`MessageRead`], and the message can be taken out using [`Response::into_message`].
+///
+/// ```
+/// # use async_coap::prelude::*;
+/// # use async_coap::datagram::{DatagramLocalEndpoint, LoopbackSocket, LoopbackSocketAddr};
+/// # use futures::prelude::*;
+/// # use futures::executor::block_on;
+/// # let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+/// let future = local_endpoint.send(
+///     LoopbackSocketAddr::Unicast,
+///     CoapRequest::get().emit_any_response(),
+/// );
+/// # let future = future::select(future, local_endpoint.receive_loop(null_receiver!()));
+/// # let response = match block_on(future) {
+/// #     future::Either::Left((response, _)) => response.unwrap(),
+/// #     future::Either::Right(_) => unreachable!(),
+/// # };
+///
+/// // let response = future.await?;
+///
+/// assert_eq!(LoopbackSocketAddr::Unicast, response.remote_socket_addr());
+/// println!("Got {} after {:?}", response, response.elapsed());
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Response<SA> {
+    message: OwnedImmutableMessage,
+    remote_socket_addr: SA,
+    elapsed: Duration,
+}
+
+impl<SA: SocketAddrExt> Response<SA> {
+    /// Creates a new `Response` from its parts.
+    pub fn new(message: OwnedImmutableMessage, remote_socket_addr: SA, elapsed: Duration) -> Self {
+        Response {
+            message,
+            remote_socket_addr,
+            elapsed,
+        }
+    }
+
+    /// Creates a new `Response` from the message of `context`.
+    pub(crate) fn from_context<IC>(context: &IC, elapsed: Duration) -> Self
+    where
+        IC: InboundContext<SocketAddr = SA>,
+    {
+        Response::new(
+            context.message().to_owned(),
+            context.remote_socket_addr(),
+            elapsed,
+        )
+    }
+
+    /// The socket address of the remote endpoint that sent this response.
+    pub fn remote_socket_addr(&self) -> SA {
+        self.remote_socket_addr
+    }
+
+    /// The amount of time between the first transmission of the request and the arrival
+    /// of this response.
+    ///
+    /// For observations and Block2 transfers, this is measured from the very first
+    /// request of the exchange.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// The message code of this response.
+    pub fn msg_code(&self) -> MsgCode {
+        self.message.msg_code()
+    }
+
+    /// Returns true if the message code of this response is in the success class (2.xx).
+    pub fn is_success(&self) -> bool {
+        self.message.msg_code().is_success()
+    }
+
+    /// Returns the value of the first option for `key`, or `None` if the response doesn't
+    /// contain such an option.
+    pub fn option<'a, T>(&'a self, key: OptionKey<T>) -> Result<Option<T>, Error>
+    where
+        T: TryOptionValueFrom<'a> + Sized,
+    {
+        self.message.options().find_next_of(key).transpose()
+    }
+
+    /// The value of the `ETag` option, if present.
+    pub fn etag(&self) -> Option<ETag> {
+        self.option(option::ETAG).ok().flatten()
+    }
+
+    /// The value of the `Max-Age` option, if present.
+    pub fn max_age(&self) -> Option<u32> {
+        self.option(option::MAX_AGE).ok().flatten()
+    }
+
+    /// The value of the `Observe` option, if present.
+    pub fn observe(&self) -> Option<u32> {
+        self.option(option::OBSERVE).ok().flatten()
+    }
+
+    /// The value of the `Content-Format` option, if present.
+    pub fn content_format(&self) -> Option<ContentFormat> {
+        self.message.content_format()
+    }
+
+    /// The payload of this response.
+    pub fn payload(&self) -> &[u8] {
+        self.message.payload()
+    }
+
+    /// The payload of this response as a string slice, or `None` if it isn't valid UTF-8.
+    pub fn payload_as_str(&self) -> Option<&str> {
+        self.message.payload_as_str()
+    }
+
+    /// Borrows the message of this response.
+    pub fn message(&self) -> &OwnedImmutableMessage {
+        &self.message
+    }
+
+    /// Consumes this response, returning the raw message.
+    pub fn into_message(self) -> OwnedImmutableMessage {
+        self.message
+    }
+}
+
+impl<SA> MessageRead for Response<SA> {
+    fn msg_code(&self) -> MsgCode {
+        self.message.msg_code()
+    }
+
+    fn msg_type(&self) -> MsgType {
+        self.message.msg_type()
+    }
+
+    fn msg_id(&self) -> MsgId {
+        self.message.msg_id()
+    }
+
+    fn msg_token(&self) -> MsgToken {
+        self.message.msg_token()
+    }
+
+    fn payload(&self) -> &[u8] {
+        self.message.payload()
+    }
+
+    fn options(&self) -> OptionIterator<'_> {
+        self.message.options()
+    }
+
+    fn content_format(&self) -> Option<ContentFormat> {
+        self.message.content_format()
+    }
+
+    fn accept(&self) -> Option<ContentFormat> {
+        self.message.accept()
+    }
+
+    fn block2(&self) -> Option<BlockInfo> {
+        self.message.block2()
+    }
+
+    fn block1(&self) -> Option<BlockInfo> {
+        self.message.block1()
+    }
+}
+
+impl<SA> From<Response<SA>> for OwnedImmutableMessage {
+    fn from(response: Response<SA>) -> Self {
+        response.message
+    }
+}
+
+impl<SA> core::fmt::Display for Response<SA> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Display::fmt(&self.message, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::VecMessageEncoder;
+
+    #[test]
+    fn typed_accessors() {
+        let mut encoder = VecMessageEncoder::new();
+        encoder.set_msg_code(MsgCode::SuccessContent);
+        encoder
+            .insert_option(option::ETAG, ETag::new(&[1, 2, 3]))
+            .unwrap();
+        encoder.insert_option(option::OBSERVE, 7).unwrap();
+        encoder.insert_option(option::MAX_AGE, 30).unwrap();
+        encoder.append_payload_string("hello").unwrap();
+
+        let addr: std::net::SocketAddr = "127.0.0.1:5683".parse().unwrap();
+        let response = Response::new(encoder.into(), addr, Duration::from_millis(5));
+
+        assert!(response.is_success());
+        assert_eq!(addr, response.remote_socket_addr());
+        assert_eq!(Duration::from_millis(5), response.elapsed());
+        assert_eq!(Some(ETag::new(&[1, 2, 3])), response.etag());
+        assert_eq!(Some(7), response.observe());
+        assert_eq!(Some(30), response.max_age());
+        assert_eq!(Ok(None), response.option(option::CONTENT_FORMAT));
+        assert_eq!(Some("hello"), response.payload_as_str());
+
+        let message = response.into_message();
+        assert_eq!(MsgCode::SuccessContent, message.msg_code());
+    }
+}
@@ -13,9 +13,12 @@
 // limitations under the License.
 //
 
+use super::*;
+use std::sync::Arc;
+
 /// Successful return type from [send descriptor handler method](send_desc/trait.SendDesc.html#tymethod.handler)
 /// that indicates what should happen next.
-#[derive(Debug, Copy, Eq, PartialEq, Clone)]
+#[derive(Debug, Eq, PartialEq, Clone)]
 pub enum ResponseStatus<T = ()> {
     /// Emit the given value.
     Done(T),
@@ -25,10 +28,25 @@ pub enum ResponseStatus<T = ()> {
     /// This is used when handling block requests to fetch additional blocks, among other cases.
     SendNext,
 
+    /// Like [`SendNext`](Self::SendNext), except that the next request is changed by the
+    /// given [`ModifyRequest`] after it has been written by the send descriptor.
+    ///
+    /// This allows multi-step exchanges where the next request depends on the previous
+    /// response, like adjusting an option, without writing a custom send descriptor.
+    SendNextWith(ModifyRequest),
+
     /// Wait for additional responses to the original request without sending new requests.
     ///
     /// This is used when handling multicast requests and observing.
     Continue,
+
+    /// Like [`Continue`](Self::Continue), except that the response isn't accepted: if it
+    /// was confirmable, it isn't acknowledged, so that the remote endpoint retransmits it
+    /// later.
+    ///
+    /// This is used to exert backpressure when responses arrive faster than they can be
+    /// consumed. Transports which don't acknowledge responses treat this like `Continue`.
+    Defer,
 }
 
 impl<T> ResponseStatus<T> {
@@ -48,12 +66,13 @@ impl<T> ResponseStatus<T> {
         }
     }
 
-    /// Returns true if the response status is `SendNext`, false otherwise.
+    /// Returns true if the response status is `SendNext` or `SendNextWith(...)`, false
+    /// otherwise.
     pub fn is_send_next(&self) -> bool {
-        match *self {
-            ResponseStatus::SendNext => true,
-            _ => false,
-        }
+        matches!(
+            *self,
+            ResponseStatus::SendNext | ResponseStatus::SendNextWith(_)
+        )
     }
 
     /// Returns true if the response status is `Continue`, false otherwise.
@@ -64,12 +83,21 @@ impl<T> ResponseStatus<T> {
         }
     }
 
+    /// Returns true if the response status is `Defer`, false otherwise.
+    pub fn is_defer(&self) -> bool {
+        matches!(*self, ResponseStatus::Defer)
+    }
+
     /// Converts the contained type to be a reference, so that `Done(T)` becomes `Done(&T)`.
     pub fn as_ref(&self) -> ResponseStatus<&T> {
         match *self {
             ResponseStatus::Done(ref x) => ResponseStatus::Done(x),
             ResponseStatus::SendNext => ResponseStatus::SendNext,
+            ResponseStatus::SendNextWith(ref modify) => {
+                ResponseStatus::SendNextWith(modify.clone())
+            }
             ResponseStatus::Continue => ResponseStatus::Continue,
+            ResponseStatus::Defer => ResponseStatus::Defer,
         }
     }
 
@@ -79,7 +107,60 @@ impl<T> ResponseStatus<T> {
         match *self {
             ResponseStatus::Done(ref mut x) => ResponseStatus::Done(x),
             ResponseStatus::SendNext => ResponseStatus::SendNext,
+            ResponseStatus::SendNextWith(ref modify) => {
+                ResponseStatus::SendNextWith(modify.clone())
+            }
             ResponseStatus::Continue => ResponseStatus::Continue,
+            ResponseStatus::Defer => ResponseStatus::Defer,
         }
     }
 }
+
+/// A change to the next request of an exchange, as returned from a send descriptor
+/// handler with [`ResponseStatus::SendNextWith`].
+///
+/// The change is applied to the next request and its retransmissions, but not to any
+/// requests after it.
+///
+/// ```
+/// # use async_coap::prelude::*;
+/// # use async_coap::ModifyRequest;
+/// // Asks for the next page of a paginated resource.
+/// let next_page = ModifyRequest::new(|msg_out| msg_out.insert_option(option::URI_QUERY, "page=2"));
+/// let status: ResponseStatus<()> = ResponseStatus::SendNextWith(next_page);
+/// assert!(status.is_send_next());
+/// ```
+#[derive(Clone)]
+pub struct ModifyRequest(Arc<ModifyFn>);
+
+type ModifyFn = dyn Fn(&mut dyn MessageWrite) -> Result<(), Error> + Send + Sync;
+
+impl ModifyRequest {
+    /// Creates a change to the next request which is made by calling `modify` with it.
+    pub fn new<F>(modify: F) -> ModifyRequest
+    where
+        F: Fn(&mut dyn MessageWrite) -> Result<(), Error> + Send + Sync + 'static,
+    {
+        ModifyRequest(Arc::new(modify))
+    }
+
+    /// Applies this change to `msg`, a request which has been written by a send descriptor.
+    pub fn apply(&self, msg: &mut dyn MessageWrite) -> Result<(), Error> {
+        (self.0)(msg)
+    }
+}
+
+impl core::fmt::Debug for ModifyRequest {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("ModifyRequest")
+    }
+}
+
+/// Two changes are equal if they are clones of each other.
+impl PartialEq for ModifyRequest {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for ModifyRequest {}
@@ -16,55 +16,269 @@
 use super::*;
 
 use crate::send_desc::SendDesc;
-use futures::channel::mpsc::{Receiver, Sender};
 use futures::task::Context;
 use futures::task::Poll;
-use pin_utils::unsafe_pinned;
+use futures::task::Waker;
+use futures_timer::Delay;
+use std::collections::{HashSet, VecDeque};
 use std::marker::PhantomData;
 use std::ops::Bound;
 use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// What a [`SendAsStream`] does with a response which arrives while its buffer is full,
+/// because the stream isn't being polled as quickly as responses are arriving.
+///
+/// This matters mostly for observing resources which change at a high rate.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub enum StreamOverflow {
+    /// Fails the stream with [`Error::OutOfSpace`], ending it. This is the default.
+    #[default]
+    Fail,
+
+    /// Discards the oldest buffered response to make room for the new one.
+    DropOldest,
+
+    /// Discards all of the buffered responses, so that the stream continues with the
+    /// latest one. This is useful for observing a state where only the current value
+    /// is interesting.
+    KeepLatest,
+
+    /// Defers the new response with [`ResponseStatus::Defer`], leaving confirmable
+    /// notifications unacknowledged until there is room for them again. The remote
+    /// endpoint then retransmits them, slowing down its notifications.
+    ///
+    /// Non-confirmable responses can't be retransmitted, so they are lost.
+    Backpressure,
+}
+
+/// The buffering of the responses of a [`SendAsStream`] which haven't been taken from the
+/// stream yet, as given to [`LocalEndpointExt::send_as_stream_with_buffer`] or
+/// [`RemoteEndpointExt::send_as_stream_with_buffer`].
+///
+/// The default buffer holds [`StreamBuffer::DEFAULT_DEPTH`] responses, and fails the
+/// stream when it overflows.
+///
+/// [`LocalEndpointExt::send_as_stream_with_buffer`]: crate::LocalEndpointExt::send_as_stream_with_buffer
+/// [`RemoteEndpointExt::send_as_stream_with_buffer`]: crate::RemoteEndpointExt::send_as_stream_with_buffer
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct StreamBuffer {
+    depth: usize,
+    overflow: StreamOverflow,
+}
+
+impl StreamBuffer {
+    /// The number of responses held by the default buffer.
+    pub const DEFAULT_DEPTH: usize = 10;
+
+    /// Creates a buffer for `depth` responses, which handles overflowing with `overflow`.
+    /// A `depth` of zero is treated as one.
+    pub fn new(depth: usize, overflow: StreamOverflow) -> StreamBuffer {
+        StreamBuffer {
+            depth: depth.max(1),
+            overflow,
+        }
+    }
+
+    /// The number of responses this buffer holds.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// What happens when a response arrives while this buffer is full.
+    pub fn overflow(&self) -> StreamOverflow {
+        self.overflow
+    }
+}
+
+impl Default for StreamBuffer {
+    fn default() -> Self {
+        StreamBuffer::new(StreamBuffer::DEFAULT_DEPTH, StreamOverflow::default())
+    }
+}
+
+/// The responses shared between a [`SendAsStreamDesc`] and its [`SendAsStream`].
+#[derive(Debug)]
+pub(crate) struct StreamQueue<R> {
+    responses: VecDeque<R>,
+    waker: Option<Waker>,
+    sender_dropped: bool,
+    receiver_dropped: bool,
+}
+
+impl<R> StreamQueue<R> {
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
 
 /// A [`Stream`] that is created by [`LocalEndpointExt::send_as_stream`],
 /// [`RemoteEndpointExt::send_as_stream`], and [`RemoteEndpointExt::send_to_as_stream`].
 ///
+/// Responses which haven't been taken from the stream yet are buffered as described by
+/// the [`StreamBuffer`] it was created with.
+///
 /// [`Stream`]: futures::stream::Stream
 /// [`LocalEndpointExt::send_as_stream`]: crate::LocalEndpointExt::send_as_stream
 /// [`RemoteEndpointExt::send_as_stream`]: crate::RemoteEndpointExt::send_as_stream
 /// [`RemoteEndpointExt::send_to_as_stream`]: crate::RemoteEndpointExt::send_to_as_stream
 pub struct SendAsStream<'a, R: Send> {
-    pub(crate) receiver: Receiver<Result<R, Error>>,
-    pub(crate) send_future: BoxFuture<'a, Result<R, Error>>,
+    queue: Arc<Mutex<StreamQueue<R>>>,
+    send_future: Option<BoxFuture<'a, Result<R, Error>>>,
+    error: Option<Error>,
+}
+
+impl<'a, R: Send> SendAsStream<'a, R> {
+    /// Creates a stream of the responses to `send_desc`, which is sent by passing the
+    /// wrapped send descriptor to `send`.
+    pub(crate) fn new<SD, IC, F>(send_desc: SD, buffer: StreamBuffer, send: F) -> Self
+    where
+        SD: SendDesc<IC, R>,
+        IC: InboundContext,
+        F: FnOnce(SendAsStreamDesc<SD, IC, R>) -> BoxFuture<'a, Result<R, Error>>,
+    {
+        let queue = Arc::new(Mutex::new(StreamQueue {
+            responses: VecDeque::with_capacity(buffer.depth),
+            waker: None,
+            sender_dropped: false,
+            receiver_dropped: false,
+        }));
+
+        SendAsStream {
+            send_future: Some(send(SendAsStreamDesc {
+                inner: send_desc,
+                queue: queue.clone(),
+                buffer,
+                phantom: PhantomData,
+            })),
+            queue,
+            error: None,
+        }
+    }
+}
+
+impl<'a, T, SA> SendAsStream<'a, (T, SA)>
+where
+    T: Send + 'a,
+    SA: SocketAddrExt + 'a,
+{
+    /// Collects the results of this stream for at most `duration`, keeping only the first
+    /// result from each remote address.
+    ///
+    /// This is meant for discovering endpoints with multicast requests which use
+    /// [`include_socket_addr`](crate::send_desc::SendDescExt::include_socket_addr), where
+    /// every endpoint that responds should be listed once. The returned future finishes
+    /// once `duration` has elapsed or the stream has ended, with the results in the order
+    /// in which they were received.
+    ///
+    /// An error from the stream (for example, because one of the endpoints responded
+    /// with something that couldn't be handled) doesn't discard the results which were
+    /// already collected. The future only fails with the error if there are no results.
+    ///
+    /// ```no_run
+    /// # use async_coap::prelude::*;
+    /// # use async_coap::Error;
+    /// # use std::time::Duration;
+    /// # async fn discover<RE: RemoteEndpoint>(remote_endpoint: RE) -> Result<(), Error> {
+    /// let endpoints = remote_endpoint
+    ///     .send_as_stream(
+    ///         CoapRequest::get()
+    ///             .multicast()
+    ///             .emit_successful_response()
+    ///             .include_socket_addr(),
+    ///     )
+    ///     .collect_for(Duration::from_secs(2))
+    ///     .await?;
+    ///
+    /// for (_, socket_addr) in endpoints {
+    ///     println!("Found {}", socket_addr);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn collect_for(self, duration: Duration) -> BoxFuture<'a, Result<Vec<(T, SA)>, Error>> {
+        let mut stream = self.take_until(Delay::new(duration));
+
+        async move {
+            let mut socket_addrs = HashSet::new();
+            let mut results = Vec::new();
+            let mut error = None;
+
+            while let Some(result) = stream.next().await {
+                match result {
+                    Ok((value, socket_addr)) => {
+                        if socket_addrs.insert(socket_addr) {
+                            results.push((value, socket_addr));
+                        }
+                    }
+                    Err(e) => {
+                        debug!("collect_for: Ignoring error: {:?}", e);
+                        error.get_or_insert(e);
+                    }
+                }
+            }
+
+            match error {
+                Some(e) if results.is_empty() => Err(e),
+                _ => Ok(results),
+            }
+        }
+        .boxed()
+    }
+}
+
+impl<'a, R: Send> Drop for SendAsStream<'a, R> {
+    fn drop(&mut self) {
+        self.queue.lock().expect("Lock failed").receiver_dropped = true;
+    }
 }
 
 impl<'a, R: Send + core::fmt::Debug> core::fmt::Debug for SendAsStream<'a, R> {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
         f.debug_struct("SendAsStream")
-            .field("receiver", &self.receiver)
+            .field("queue", &self.queue)
             .field("send_future", &"")
+            .field("error", &self.error)
             .finish()
     }
 }
 
-impl<'a, R: Send> SendAsStream<'a, R> {
-    unsafe_pinned!(send_future: BoxFuture<'a, Result<R, Error>>);
-    unsafe_pinned!(receiver: futures::channel::mpsc::Receiver<Result<R, Error>>);
-}
-
 impl<'a, R: Send> Stream for SendAsStream<'a, R> {
     type Item = Result<R, Error>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        match self.as_mut().receiver().poll_next(cx) {
-            Poll::Ready(None) => Poll::Ready(None),
-            from_receiver => match self.as_mut().send_future().poll(cx) {
-                Poll::Ready(Ok(_)) => Poll::Ready(None),
-                Poll::Ready(Err(Error::ResponseTimeout)) | Poll::Ready(Err(Error::Cancelled)) => {
-                    Poll::Ready(None)
-                }
-                Poll::Ready(Err(x)) => Poll::Ready(Some(Err(x))),
-                Poll::Pending => from_receiver,
-            },
+        let this = &mut *self;
+
+        if let Some(send_future) = this.send_future.as_mut() {
+            if let Poll::Ready(result) = send_future.poll_unpin(cx) {
+                // The send future must not be polled again once it has finished, so that
+                // an error (like `Error::Cancelled` after the local endpoint was shut down)
+                // is followed by the end of the stream.
+                this.send_future = None;
+                this.error = match result {
+                    Ok(_) | Err(Error::ResponseTimeout) => None,
+                    Err(x) => Some(x),
+                };
+            }
+        }
+
+        let mut queue = this.queue.lock().expect("Lock failed");
+
+        // Responses which arrived before the send future finished are still emitted.
+        if let Some(response) = queue.responses.pop_front() {
+            return Poll::Ready(Some(Ok(response)));
+        }
+
+        if this.send_future.is_some() && !queue.sender_dropped {
+            queue.waker = Some(cx.waker().clone());
+            return Poll::Pending;
         }
+
+        this.send_future = None;
+        Poll::Ready(this.error.take().map(Err))
     }
 }
 
@@ -76,22 +290,21 @@ where
     R: Send,
 {
     inner: SD,
-    sender: Sender<Result<R, Error>>,
+    queue: Arc<Mutex<StreamQueue<R>>>,
+    buffer: StreamBuffer,
     phantom: PhantomData<IC>,
 }
 
-impl<SD, IC, R> SendAsStreamDesc<SD, IC, R>
+impl<SD, IC, R> Drop for SendAsStreamDesc<SD, IC, R>
 where
     SD: SendDesc<IC, R>,
     IC: InboundContext,
     R: Send,
 {
-    pub(crate) fn new(inner: SD, sender: Sender<Result<R, Error>>) -> SendAsStreamDesc<SD, IC, R> {
-        SendAsStreamDesc {
-            inner,
-            sender,
-            phantom: PhantomData,
-        }
+    fn drop(&mut self) {
+        let mut queue = self.queue.lock().expect("Lock failed");
+        queue.sender_dropped = true;
+        queue.wake();
     }
 }
 
@@ -109,17 +322,244 @@ where
     fn handler(&mut self, context: Result<&IC, Error>) -> Result<ResponseStatus<R>, Error> {
         match self.inner.handler(context)? {
             ResponseStatus::Done(x) => {
-                if let Some(err) = self.sender.start_send(Ok(x)).err() {
-                    if err.is_full() {
-                        Err(Error::OutOfSpace)
-                    } else {
-                        Err(Error::Cancelled)
+                let mut queue = self.queue.lock().expect("Lock failed");
+
+                if queue.receiver_dropped {
+                    return Err(Error::Cancelled);
+                }
+
+                if queue.responses.len() >= self.buffer.depth {
+                    match self.buffer.overflow {
+                        StreamOverflow::Fail => return Err(Error::OutOfSpace),
+                        StreamOverflow::DropOldest => {
+                            debug!("Stream buffer full, dropping oldest response");
+                            queue.responses.pop_front();
+                        }
+                        StreamOverflow::KeepLatest => {
+                            debug!("Stream buffer full, keeping latest response");
+                            queue.responses.clear();
+                        }
+                        StreamOverflow::Backpressure => {
+                            debug!("Stream buffer full, deferring response");
+                            return Ok(ResponseStatus::Defer);
+                        }
                     }
-                } else {
-                    Ok(ResponseStatus::Continue)
                 }
+
+                queue.responses.push_back(x);
+                queue.wake();
+                Ok(ResponseStatus::Continue)
             }
             response_status => Ok(response_status),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datagram::{
+        AsyncSendTo, DatagramLocalEndpoint, DatagramRespondableInboundContext, LoopbackSocket,
+        LoopbackSocketAddr,
+    };
+    use crate::message::BufferMessageEncoder;
+    use futures::executor::block_on;
+    use futures::future::{select, Either};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Observes the loopback endpoint using `buffer`, and receives `count` confirmable
+    /// notifications before taking anything from the stream. Returns what is then taken
+    /// from the stream, and the number of messages sent in reply to the notifications.
+    fn observe(buffer: StreamBuffer, count: u8) -> (Vec<Result<u8, Error>>, u64) {
+        let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+        let mut stream = local_endpoint.send_as_stream_with_buffer(
+            LoopbackSocketAddr::Unicast,
+            CoapRequest::observe()
+                .use_handler(|context| Ok(ResponseStatus::Done(context?.message().payload()[0]))),
+            buffer,
+        );
+        let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+        assert!(stream.poll_next_unpin(&mut cx).is_pending());
+
+        // Failing the handler keeps the local endpoint from responding by itself.
+        let handler = |context: &DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+            for i in 0..count {
+                let mut buffer = [0u8; 32];
+                let mut builder = BufferMessageEncoder::new(&mut buffer);
+                builder.set_msg_type(MsgType::Con);
+                builder.set_msg_code(MsgCode::SuccessContent);
+                builder.set_msg_id(0x1000 + MsgId::from(i));
+                builder.set_msg_token(context.message().msg_token());
+                builder.append_payload_bytes(&[i])?;
+                local_endpoint
+                    .socket()
+                    .send_to(&builder, LoopbackSocketAddr::Unicast)
+                    .now_or_never()
+                    .unwrap()?;
+            }
+            Err(Error::Unspecified)
+        };
+        assert_eq!(
+            Err(Error::Unspecified),
+            block_on(local_endpoint.receive(handler))
+        );
+
+        let messages_out = local_endpoint.stats().messages_out;
+        for _ in 0..count {
+            assert_eq!(
+                Ok(()),
+                block_on(local_endpoint.receive(|_| panic!("Unexpected request")))
+            );
+        }
+        let replies = local_endpoint.stats().messages_out - messages_out;
+
+        let mut results = vec![];
+        while let Poll::Ready(Some(result)) = stream.poll_next_unpin(&mut cx) {
+            results.push(result);
+        }
+
+        (results, replies)
+    }
+
+    #[test]
+    fn overflow_fail() {
+        let (results, _) = observe(StreamBuffer::new(2, StreamOverflow::Fail), 5);
+        assert_eq!(vec![Ok(0), Ok(1), Err(Error::OutOfSpace)], results);
+    }
+
+    #[test]
+    fn overflow_drop_oldest() {
+        let (results, replies) = observe(StreamBuffer::new(2, StreamOverflow::DropOldest), 5);
+        assert_eq!(vec![Ok(3), Ok(4)], results);
+        assert_eq!(5, replies);
+    }
+
+    #[test]
+    fn overflow_keep_latest() {
+        let (results, replies) = observe(StreamBuffer::new(2, StreamOverflow::KeepLatest), 5);
+        assert_eq!(vec![Ok(4)], results);
+        assert_eq!(5, replies);
+    }
+
+    #[test]
+    fn overflow_backpressure() {
+        let (results, replies) = observe(StreamBuffer::new(2, StreamOverflow::Backpressure), 5);
+        assert_eq!(vec![Ok(0), Ok(1)], results);
+
+        // Only the notifications which were accepted are acknowledged.
+        assert_eq!(2, replies);
+    }
+
+    #[test]
+    fn collect_for() {
+        // Every request and response is delivered twice.
+        let local_endpoint =
+            DatagramLocalEndpoint::new(LoopbackSocket::new().with_duplication(1.0));
+        let responses = AtomicUsize::new(0);
+
+        let handler = |context: &DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+            // Responses to multicast requests are non-confirmable.
+            context.respond(|msg_out| {
+                msg_out.set_msg_type(MsgType::Non);
+                msg_out.set_msg_code(MsgCode::SuccessContent);
+                Ok(())
+            })
+        };
+
+        let future = local_endpoint
+            .send_as_stream(
+                LoopbackSocketAddr::Multicast,
+                CoapRequest::get()
+                    .multicast()
+                    .inspect(|_| {
+                        responses.fetch_add(1, Ordering::Relaxed);
+                    })
+                    .emit_msg_code()
+                    .include_socket_addr(),
+            )
+            .collect_for(Duration::from_millis(100));
+
+        let results = match block_on(select(future, local_endpoint.receive_loop(handler))) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((results, _)) => results,
+        };
+
+        assert_eq!(
+            Ok(vec![(MsgCode::SuccessContent, LoopbackSocketAddr::Unicast)]),
+            results
+        );
+        assert!(responses.load(Ordering::Relaxed) > 1);
+    }
+
+    /// Sends a multicast request to the loopback endpoint, which is answered with
+    /// responses with the message codes `msg_codes`, and collects the results. Responses
+    /// which aren't successful fail the send descriptor's handler.
+    fn collect_for_responses(
+        msg_codes: &[MsgCode],
+    ) -> Result<Vec<(MsgCode, LoopbackSocketAddr)>, Error> {
+        let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+
+        let mut future = local_endpoint
+            .send_as_stream(
+                LoopbackSocketAddr::Multicast,
+                CoapRequest::get()
+                    .multicast()
+                    .use_handler(|context| {
+                        let msg_code = context?.message().msg_code();
+                        if msg_code.is_success() {
+                            Ok(ResponseStatus::Done(msg_code))
+                        } else {
+                            Err(Error::ResourceNotFound)
+                        }
+                    })
+                    .include_socket_addr(),
+            )
+            .collect_for(Duration::from_secs(5));
+        let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+        assert!(future.poll_unpin(&mut cx).is_pending());
+
+        // Failing the handler keeps the local endpoint from responding by itself.
+        let handler = |context: &DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+            for (i, msg_code) in msg_codes.iter().enumerate() {
+                let mut buffer = [0u8; 32];
+                let mut builder = BufferMessageEncoder::new(&mut buffer);
+                builder.set_msg_type(MsgType::Non);
+                builder.set_msg_code(*msg_code);
+                builder.set_msg_id(0x1000 + i as MsgId);
+                builder.set_msg_token(context.message().msg_token());
+                local_endpoint
+                    .socket()
+                    .send_to(&builder, LoopbackSocketAddr::Unicast)
+                    .now_or_never()
+                    .unwrap()?;
+            }
+            Err(Error::Unspecified)
+        };
+        assert_eq!(
+            Err(Error::Unspecified),
+            block_on(local_endpoint.receive(handler))
+        );
+
+        for _ in msg_codes {
+            assert_eq!(
+                Ok(()),
+                block_on(local_endpoint.receive(|_| panic!("Unexpected request")))
+            );
+        }
+
+        block_on(future)
+    }
+
+    #[test]
+    fn collect_for_keeps_results_after_error() {
+        assert_eq!(
+            Ok(vec![(MsgCode::SuccessContent, LoopbackSocketAddr::Unicast)]),
+            collect_for_responses(&[MsgCode::SuccessContent, MsgCode::ClientErrorNotFound])
+        );
+
+        assert_eq!(
+            Err(Error::ResourceNotFound),
+            collect_for_responses(&[MsgCode::ClientErrorNotFound])
+        );
+    }
+}
@@ -0,0 +1,114 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+
+impl<SD: SendDescUnicast, IC> SendDescUnicast for AcceptAnyOf<SD, IC> {}
+
+/// Accept negotiation combinator, created by [`SendDescUnicast::accept_any_of`].
+///
+/// Sends the request with an `Accept` option for the first candidate content format. Each
+/// time the server responds with `4.06 Not Acceptable`, the request is sent again with
+/// the next candidate. The result of the wrapped send descriptor is emitted along with
+/// the content format that was accepted.
+#[derive(Debug)]
+pub struct AcceptAnyOf<SD, IC> {
+    inner: SD,
+    formats: Vec<ContentFormat>,
+    index: usize,
+    phantom: PhantomData<IC>,
+}
+
+impl<SD: Clone, IC> Clone for AcceptAnyOf<SD, IC> {
+    fn clone(&self) -> Self {
+        // The clone starts over with the first candidate.
+        AcceptAnyOf {
+            inner: self.inner.clone(),
+            formats: self.formats.clone(),
+            index: 0,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<SD, IC> AcceptAnyOf<SD, IC> {
+    pub(super) fn new(inner: SD, formats: Vec<ContentFormat>) -> Self {
+        assert!(
+            !formats.is_empty(),
+            "accept_any_of requires at least one content format"
+        );
+
+        AcceptAnyOf {
+            inner,
+            formats,
+            index: 0,
+            phantom: PhantomData,
+        }
+    }
+
+    /// The content format that is currently being requested.
+    pub fn current_accept(&self) -> ContentFormat {
+        self.formats[self.index]
+    }
+}
+
+impl<SD, IC, R> SendDesc<IC, (R, ContentFormat)> for AcceptAnyOf<SD, IC>
+where
+    SD: SendDesc<IC, R> + Send + SendDescUnicast,
+    IC: InboundContext,
+    R: Send,
+{
+    send_desc_passthru_timing!(inner);
+    send_desc_passthru_payload!(inner);
+    send_desc_passthru_supports_option!(inner);
+
+    fn write_options(
+        &self,
+        msg: &mut dyn OptionInsert,
+        socket_addr: &IC::SocketAddr,
+        start: Bound<OptionNumber>,
+        end: Bound<OptionNumber>,
+    ) -> Result<(), Error> {
+        write_options!((msg, socket_addr, start, end, self.inner) {
+            ACCEPT => once(self.current_accept()),
+        })
+    }
+
+    fn handler(
+        &mut self,
+        context: Result<&IC, Error>,
+    ) -> Result<ResponseStatus<(R, ContentFormat)>, Error> {
+        let accept = self.current_accept();
+
+        if let Ok(context) = context {
+            if !context.is_dupe()
+                && context.message().msg_code() == MsgCode::ClientErrorNotAcceptable
+                && self.index + 1 < self.formats.len()
+            {
+                debug!("{} not acceptable, trying next format", accept);
+                self.index += 1;
+                return Ok(ResponseStatus::SendNext);
+            }
+        }
+
+        self.inner.handler(context).map(|x| match x {
+            ResponseStatus::Done(x) => ResponseStatus::Done((x, accept)),
+            ResponseStatus::SendNext => ResponseStatus::SendNext,
+            ResponseStatus::SendNextWith(modify) => ResponseStatus::SendNextWith(modify),
+            ResponseStatus::Continue => ResponseStatus::Continue,
+            ResponseStatus::Defer => ResponseStatus::Defer,
+        })
+    }
+}
@@ -28,6 +28,17 @@ pub struct AddOption<SD, K, I: Send, IC> {
     pub(super) phantom: PhantomData<IC>,
 }
 
+impl<SD: Clone, K, I: Send + Clone, IC> Clone for AddOption<SD, K, I, IC> {
+    fn clone(&self) -> Self {
+        AddOption {
+            inner: self.inner.clone(),
+            key: self.key,
+            viter: self.viter.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
 impl<'a, SD, IC, R, K, I> SendDesc<IC, R> for AddOption<SD, K, I, IC>
 where
     SD: SendDesc<IC, R> + Send,
@@ -0,0 +1,63 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+
+impl<SD: SendDescUnicast> SendDescUnicast for Dscp<SD> {}
+impl<SD: SendDescMulticast> SendDescMulticast for Dscp<SD> {}
+
+/// Combinator for Send Descriptors created by [`SendDescExt::dscp`].
+#[derive(Debug, Clone)]
+pub struct Dscp<SD> {
+    pub(super) inner: SD,
+    pub(super) dscp: u8,
+}
+
+impl<SD> Dscp<SD> {
+    pub(super) fn new(inner: SD, dscp: u8) -> Dscp<SD> {
+        Dscp { inner, dscp }
+    }
+}
+
+impl<SD, IC, R> SendDesc<IC, R> for Dscp<SD>
+where
+    SD: SendDesc<IC, R> + Send,
+    IC: InboundContext,
+    R: Send,
+{
+    send_desc_passthru_options!(inner);
+    send_desc_passthru_payload!(inner);
+    send_desc_passthru_handler!(inner, R);
+
+    fn delay_to_retransmit(&self, retransmits_sent: u32) -> Option<Duration> {
+        self.inner.delay_to_retransmit(retransmits_sent)
+    }
+
+    fn delay_to_restart(&self) -> Option<Duration> {
+        self.inner.delay_to_restart()
+    }
+
+    fn max_rtt(&self) -> Duration {
+        self.inner.max_rtt()
+    }
+
+    fn transmit_wait_duration(&self) -> Duration {
+        self.inner.transmit_wait_duration()
+    }
+
+    fn traffic_class(&self) -> TrafficClass {
+        self.inner.traffic_class().with_dscp(self.dscp)
+    }
+}
@@ -14,7 +14,8 @@
 //
 
 use super::*;
-use crate::message::OwnedImmutableMessage;
+use std::cell::Cell;
+use std::time::Instant;
 
 impl<SD: SendDescUnicast> SendDescUnicast for EmitAnyResponse<SD> {}
 impl<SD: SendDescMulticast> SendDescMulticast for EmitAnyResponse<SD> {}
@@ -23,83 +24,159 @@ impl<SD: SendDescMulticast> SendDescMulticast for EmitAnyResponse<SD> {}
 #[derive(Debug)]
 pub struct EmitAnyResponse<SD> {
     pub(super) inner: SD,
+    sent_at: Cell<Option<Instant>>,
+}
+
+impl<SD: Clone> Clone for EmitAnyResponse<SD> {
+    fn clone(&self) -> Self {
+        // The clone hasn't been sent yet.
+        EmitAnyResponse::new(self.inner.clone())
+    }
 }
 
 impl<SD> EmitAnyResponse<SD> {
     pub(super) fn new(inner: SD) -> EmitAnyResponse<SD> {
-        EmitAnyResponse { inner }
+        EmitAnyResponse {
+            inner,
+            sent_at: Cell::new(None),
+        }
     }
 }
 
-impl<SD, IC> SendDesc<IC, OwnedImmutableMessage> for EmitAnyResponse<SD>
+impl<SD, IC> SendDesc<IC, Response<IC::SocketAddr>> for EmitAnyResponse<SD>
 where
     SD: SendDesc<IC, ()> + Send,
     IC: InboundContext,
 {
     send_desc_passthru_timing!(inner);
     send_desc_passthru_options!(inner);
-    send_desc_passthru_payload!(inner);
     send_desc_passthru_supports_option!(inner);
 
+    fn write_payload(
+        &self,
+        msg: &mut dyn MessageWrite,
+        socket_addr: &IC::SocketAddr,
+    ) -> Result<(), Error> {
+        mark_sent(&self.sent_at);
+        self.inner.write_payload(msg, socket_addr)
+    }
+
+    fn payload_size_hint(&self) -> usize {
+        self.inner.payload_size_hint()
+    }
+
     fn handler(
         &mut self,
         context: Result<&IC, Error>,
-    ) -> Result<ResponseStatus<OwnedImmutableMessage>, Error> {
-        let msg = context.ok().map(|x| x.message());
+    ) -> Result<ResponseStatus<Response<IC::SocketAddr>>, Error> {
+        let msg = context
+            .ok()
+            .map(|x| Response::from_context(x, elapsed_since(&self.sent_at)));
 
         match (self.inner.handler(context), msg) {
-            (_, Some(msg)) => Ok(ResponseStatus::Done(msg.to_owned())),
+            (_, Some(msg)) => Ok(ResponseStatus::Done(msg)),
             (Ok(ResponseStatus::SendNext), None) => Ok(ResponseStatus::SendNext),
+            (Ok(ResponseStatus::SendNextWith(modify)), None) => {
+                Ok(ResponseStatus::SendNextWith(modify))
+            }
             (Ok(ResponseStatus::Continue), None) => Ok(ResponseStatus::Continue),
+            (Ok(ResponseStatus::Defer), None) => Ok(ResponseStatus::Defer),
             (Ok(ResponseStatus::Done(())), None) => unreachable!(),
             (Err(e), None) => Err(e),
         }
     }
 }
 
+impl<SD: SendDescUnicast> SendDescUnicast for EmitSuccessfulResponse<SD> {}
+impl<SD: SendDescMulticast> SendDescMulticast for EmitSuccessfulResponse<SD> {}
+
 /// Combinator for Send Descriptors created by [`SendDescExt::emit_successful_response`].
 #[derive(Debug)]
 pub struct EmitSuccessfulResponse<SD> {
     pub(super) inner: SD,
+    sent_at: Cell<Option<Instant>>,
+}
+
+impl<SD: Clone> Clone for EmitSuccessfulResponse<SD> {
+    fn clone(&self) -> Self {
+        // The clone hasn't been sent yet.
+        EmitSuccessfulResponse::new(self.inner.clone())
+    }
 }
 
 impl<SD> EmitSuccessfulResponse<SD> {
     pub(super) fn new(inner: SD) -> EmitSuccessfulResponse<SD> {
-        EmitSuccessfulResponse { inner }
+        EmitSuccessfulResponse {
+            inner,
+            sent_at: Cell::new(None),
+        }
     }
 }
 
-impl<SD, IC> SendDesc<IC, OwnedImmutableMessage> for EmitSuccessfulResponse<SD>
+impl<SD, IC> SendDesc<IC, Response<IC::SocketAddr>> for EmitSuccessfulResponse<SD>
 where
     SD: SendDesc<IC, ()> + Send,
     IC: InboundContext,
 {
     send_desc_passthru_timing!(inner);
     send_desc_passthru_options!(inner);
-    send_desc_passthru_payload!(inner);
     send_desc_passthru_supports_option!(inner);
 
+    fn write_payload(
+        &self,
+        msg: &mut dyn MessageWrite,
+        socket_addr: &IC::SocketAddr,
+    ) -> Result<(), Error> {
+        mark_sent(&self.sent_at);
+        self.inner.write_payload(msg, socket_addr)
+    }
+
+    fn payload_size_hint(&self) -> usize {
+        self.inner.payload_size_hint()
+    }
+
     fn handler(
         &mut self,
         context: Result<&IC, Error>,
-    ) -> Result<ResponseStatus<OwnedImmutableMessage>, Error> {
-        let msg = context.ok().map(|x| x.message());
+    ) -> Result<ResponseStatus<Response<IC::SocketAddr>>, Error> {
+        let msg = context
+            .ok()
+            .map(|x| Response::from_context(x, elapsed_since(&self.sent_at)));
 
         match (self.inner.handler(context), msg) {
             (Err(e), _) => Err(e),
-            (_, Some(msg)) => Ok(ResponseStatus::Done(msg.to_owned())),
+            (_, Some(msg)) => Ok(ResponseStatus::Done(msg)),
             (Ok(ResponseStatus::SendNext), None) => Ok(ResponseStatus::SendNext),
+            (Ok(ResponseStatus::SendNextWith(modify)), None) => {
+                Ok(ResponseStatus::SendNextWith(modify))
+            }
             (Ok(ResponseStatus::Continue), None) => Ok(ResponseStatus::Continue),
+            (Ok(ResponseStatus::Defer), None) => Ok(ResponseStatus::Defer),
             (Ok(ResponseStatus::Done(())), None) => unreachable!(),
         }
     }
 }
 
+/// Records the time of the first transmission of a request in `sent_at`.
+pub(super) fn mark_sent(sent_at: &Cell<Option<Instant>>) {
+    if sent_at.get().is_none() {
+        sent_at.set(Some(StdClock.now()));
+    }
+}
+
+/// Returns the amount of time that has passed since the time recorded by [`mark_sent`].
+pub(super) fn elapsed_since(sent_at: &Cell<Option<Instant>>) -> Duration {
+    sent_at
+        .get()
+        .map(|sent_at| StdClock.now().saturating_duration_since(sent_at))
+        .unwrap_or_default()
+}
+
 impl<SD: SendDescUnicast> SendDescUnicast for EmitMsgCode<SD> {}
 impl<SD: SendDescMulticast> SendDescMulticast for EmitMsgCode<SD> {}
 
 /// Combinator for Send Descriptors created by [`SendDescExt::emit_msg_code`].
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct EmitMsgCode<SD> {
     pub(super) inner: SD,
 }
@@ -125,6 +202,7 @@ where
 
         self.inner.handler(context).map(|x| match (x, msg_code) {
             (ResponseStatus::SendNext, _) => ResponseStatus::SendNext,
+            (ResponseStatus::SendNextWith(modify), _) => ResponseStatus::SendNextWith(modify),
             (_, Some(msg)) => ResponseStatus::Done(msg.to_owned()),
             (_, _) => unreachable!(),
         })
@@ -19,7 +19,7 @@ impl<SD: SendDescUnicast, IC> SendDescUnicast for Handler<SD, IC> {}
 impl<SD: SendDescMulticast, IC> SendDescMulticast for Handler<SD, IC> {}
 
 /// Combinator for Send Descriptors created by [`SendDescExt::use_handler`].
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Handler<SD, F> {
     pub(super) inner: SD,
     pub(super) handler: F,
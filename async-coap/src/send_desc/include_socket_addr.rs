@@ -19,7 +19,7 @@ impl<SD: SendDescUnicast> SendDescUnicast for IncludeSocketAddr<SD> {}
 impl<SD: SendDescMulticast> SendDescMulticast for IncludeSocketAddr<SD> {}
 
 /// Combinator for Send Descriptors created by [`SendDescExt::include_socket_addr`].
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct IncludeSocketAddr<SD> {
     pub(super) inner: SD,
 }
@@ -51,7 +51,9 @@ where
             (ResponseStatus::Done(x), Some(socket_addr)) => ResponseStatus::Done((x, socket_addr)),
             (ResponseStatus::Done(_), None) => unreachable!(),
             (ResponseStatus::SendNext, _) => ResponseStatus::SendNext,
+            (ResponseStatus::SendNextWith(modify), _) => ResponseStatus::SendNextWith(modify),
             (ResponseStatus::Continue, _) => ResponseStatus::Continue,
+            (ResponseStatus::Defer, _) => ResponseStatus::Defer,
         })
     }
 }
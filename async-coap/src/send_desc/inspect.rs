@@ -19,7 +19,7 @@ impl<SD: SendDescUnicast, IC> SendDescUnicast for Inspect<SD, IC> {}
 impl<SD: SendDescMulticast, IC> SendDescMulticast for Inspect<SD, IC> {}
 
 /// Combinator for Send Descriptors created by [`SendDescExt::inspect`].
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Inspect<SD, F> {
     pub(super) inner: SD,
     pub(super) inspect: F,
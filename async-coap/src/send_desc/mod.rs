@@ -56,7 +56,8 @@
 //! returning `Ok(())` for any message responding with a `2.05 Content` message!
 //!
 //! By using the combinator `.emit_successful_response()`, we can have our `SendDesc` return
-//! an owned copy of the message it received ([`OwnedImmutableMessage`](crate::message::OwnedImmutableMessage)):
+//! the [`Response`](crate::Response) it received, which contains an owned copy of the message
+//! along with the address it came from and how long it took to arrive:
 //!
 //! ```
 //! # use std::sync::Arc;
@@ -68,7 +69,6 @@
 //! # let mut pool = LocalPool::new();
 //! # pool.spawner().spawn_local(local_endpoint.clone().receive_loop_arc(null_receiver!()).map(|_|unreachable!()));
 //! # let future = async move {
-//! #    use async_coap::message::OwnedImmutableMessage;
 //! #    let mut remote_endpoint = local_endpoint
 //! #        .remote_endpoint_from_uri(uri!("coap://coap.me:5683/test"))
 //! #        .expect("Remote endpoint lookup failed");
@@ -78,9 +78,9 @@
 //!
 //! let future = remote_endpoint.send(send_desc);
 //!
-//! let message = future.await.expect("Request failed");
+//! let response = future.await.expect("Request failed");
 //!
-//! println!("Got reply: {:?}", message);
+//! println!("Got reply: {} in {:?}", response, response.elapsed());
 //! #
 //! #
 //! # };
@@ -133,13 +133,24 @@
 
 use super::*;
 
+#[cfg(feature = "client")]
 mod request;
+#[cfg(feature = "client")]
 pub use request::*;
 
+#[cfg(all(feature = "client", feature = "observe"))]
 mod observe;
+#[cfg(all(feature = "client", feature = "observe"))]
 pub use observe::*;
 
+#[cfg(all(feature = "client", feature = "block"))]
+mod unicast_block1;
+#[cfg(all(feature = "client", feature = "block"))]
+pub use unicast_block1::*;
+
+#[cfg(all(feature = "client", feature = "block"))]
 mod unicast_block2;
+#[cfg(all(feature = "client", feature = "block"))]
 pub use unicast_block2::*;
 
 mod handler;
@@ -170,7 +181,19 @@ mod include_socket_addr;
 pub use include_socket_addr::*;
 
 mod uri_host_path;
-pub use uri_host_path::UriHostPath;
+pub use uri_host_path::{UriHost, UriHostPath};
+
+mod prepared;
+pub use prepared::Prepared;
+
+mod accept_any_of;
+pub use accept_any_of::AcceptAnyOf;
+
+mod validate;
+pub use validate::{ExpectSize2Matches, ValidatePayload};
+
+mod dscp;
+pub use dscp::Dscp;
 
 use std::iter::{once, Once};
 use std::marker::PhantomData;
@@ -213,25 +236,32 @@ where
     /// Response messages with any options that cause this
     /// method to return false will be rejected.
     ///
+    /// The default implementation supports all elective options, as well as critical
+    /// options registered in the [`OptionRegistry`] of the local endpoint.
+    ///
+    /// [`OptionRegistry`]: crate::option::OptionRegistry
     fn supports_option(&self, option: OptionNumber) -> bool {
-        !option.is_critical()
+        !option.is_critical() || crate::option::registered_option(option).is_some()
     }
 
     /// Calculates the duration of the delay to wait before sending the next retransmission.
     ///
     /// If `None` is returned, then no further retransmissions will be attempted.
+    ///
+    /// The default implementation jitters the delay using the [`RandomSource`] of the
+    /// local endpoint, if it has one.
     fn delay_to_retransmit(&self, retransmits_sent: u32) -> Option<Duration> {
         if retransmits_sent > TP::COAP_MAX_RETRANSMIT {
             return None;
         }
 
-        let ret = (TP::COAP_ACK_TIMEOUT.as_millis() as u64) << retransmits_sent as u64;
-
-        const JDIV: u64 = 512u64;
-        let rmod: u64 = (JDIV as f32 * (TP::COAP_ACK_RANDOM_FACTOR - 1.0)) as u64;
-        let jmul = JDIV + rand::random::<u64>() % rmod;
+        let ret = (TP::COAP_ACK_TIMEOUT.as_millis() as u64)
+            .saturating_mul(1u64.checked_shl(retransmits_sent).unwrap_or(u64::MAX));
 
-        Some(Duration::from_millis(ret * jmul / JDIV))
+        Some(Duration::from_millis(crate::random::jitter_millis(
+            ret,
+            TP::COAP_ACK_RANDOM_FACTOR,
+        )))
     }
 
     /// The delay to wait between when we have received a successful response and when
@@ -255,10 +285,22 @@ where
 
     /// the maximum time from the first transmission of a Confirmable message to the time when
     /// the sender gives up on receiving an acknowledgement or reset.
+    ///
+    /// The deadline is calculated using [`MonotonicInstant::saturating_add`], so durations
+    /// which are too long to be represented (like [`Duration::MAX`]) never time out.
     fn transmit_wait_duration(&self) -> Duration {
         TP::COAP_MAX_TRANSMIT_WAIT
     }
 
+    /// The [`TrafficClass`] to mark outbound messages with, so that the network can
+    /// prioritize them.
+    ///
+    /// Marking is only applied by sockets which support it. The default implementation
+    /// returns [`TrafficClass::DEFAULT`].
+    fn traffic_class(&self) -> TrafficClass {
+        TrafficClass::DEFAULT
+    }
+
     /// Defines which options are going to be included in the outbound message.
     ///
     /// Writes all options in the given range to `msg`.
@@ -277,6 +319,15 @@ where
         socket_addr: &IC::SocketAddr,
     ) -> Result<(), Error>;
 
+    /// Returns an estimate of the number of payload bytes that
+    /// [`write_payload`](SendDesc::write_payload) will write, which is used to size
+    /// message buffers ahead of time.
+    ///
+    /// The default implementation returns zero.
+    fn payload_size_hint(&self) -> usize {
+        0
+    }
+
     /// Handles the response to the outbound message.
     fn handler(&mut self, context: Result<&IC, Error>) -> Result<ResponseStatus<R>, Error>;
 }
@@ -303,6 +354,7 @@ pub trait SendDescUnicast {
     ///
     /// There may be other valid combinations of combinators, depending on what you are trying
     /// to do.
+    #[cfg(all(feature = "client", feature = "block"))]
     fn block2<IC, R, TP>(self, block2: Option<BlockInfo>) -> UnicastBlock2<Self, IC>
     where
         IC: InboundContext,
@@ -312,6 +364,107 @@ pub trait SendDescUnicast {
     {
         UnicastBlock2::new(self, block2)
     }
+
+    /// Returns a send descriptor that will upload `payload` using Block1 transfers,
+    /// as described in [IETF-RFC7959].
+    ///
+    /// `block1` determines the initial block size; if `None`, the default block size
+    /// of 1024 bytes is used. The payload is sent even if it fits into a single block.
+    /// Intermediate `2.31 Continue` responses are handled internally, and the final
+    /// response is passed along to the rest of the chain. Upload progress can be
+    /// observed by following this with a call to
+    /// [`inspect_upload`][UnicastBlock1::inspect_upload].
+    ///
+    /// If the wrapped send descriptor writes a payload, `payload` is appended to it and
+    /// the whole is uploaded. Use [`block1_from_payload`][SendDescUnicast::block1_from_payload]
+    /// to only upload the payload of the wrapped send descriptor.
+    ///
+    /// [IETF-RFC7959]: https://tools.ietf.org/html/rfc7959
+    #[cfg(all(feature = "client", feature = "block"))]
+    fn block1<IC, R, TP, P>(self, payload: P, block1: Option<BlockInfo>) -> UnicastBlock1<Self, IC>
+    where
+        IC: InboundContext,
+        R: Send,
+        TP: TransParams,
+        P: Into<Vec<u8>>,
+        Self: SendDesc<IC, R, TP> + Sized,
+    {
+        UnicastBlock1::new(self, payload.into(), block1)
+    }
+
+    /// Returns a send descriptor that will upload the payload written by this send
+    /// descriptor, for example with [`payload_writer`][SendDescExt::payload_writer], using
+    /// Block1 transfers as described in [IETF-RFC7959].
+    ///
+    /// This behaves like [`block1`][SendDescUnicast::block1] with an empty `payload`. The
+    /// payload is written once, when the first block is sent, and is then split into
+    /// blocks. Each block is sent as its own request, which is retransmitted if it is
+    /// confirmable and isn't acknowledged.
+    ///
+    /// ```
+    /// # use async_coap::prelude::*;
+    /// # use async_coap::{RemoteEndpoint, Error};
+    /// # async fn put<RE: RemoteEndpoint>(remote_endpoint: RE) -> Result<MsgCode, Error> {
+    /// let send_desc = CoapRequest::put()
+    ///     .payload_writer(|msg_out| {
+    ///         msg_out.set_msg_code(MsgCode::MethodPut);
+    ///         msg_out.append_payload_bytes(&[0u8; 5000])
+    ///     })
+    ///     .block1_from_payload(None)
+    ///     .inspect_upload(|sent, total| println!("{}/{} bytes uploaded", sent, total))
+    ///     .emit_msg_code();
+    ///
+    /// remote_endpoint.send(send_desc).await
+    /// # }
+    /// ```
+    ///
+    /// [IETF-RFC7959]: https://tools.ietf.org/html/rfc7959
+    #[cfg(all(feature = "client", feature = "block"))]
+    fn block1_from_payload<IC, R, TP>(self, block1: Option<BlockInfo>) -> UnicastBlock1<Self, IC>
+    where
+        IC: InboundContext,
+        R: Send,
+        TP: TransParams,
+        Self: SendDesc<IC, R, TP> + Sized,
+    {
+        UnicastBlock1::new(self, Vec::new(), block1)
+    }
+
+    /// Returns a send descriptor that negotiates the content format of the response.
+    ///
+    /// The request is first sent with an `Accept` option for the first format in `formats`.
+    /// Each time the server responds with `4.06 Not Acceptable`, the request is transparently
+    /// sent again with the next format. The result of the send descriptor chain is emitted
+    /// along with the format that was accepted; if the server rejects all of them, the final
+    /// `4.06 Not Acceptable` response is passed along to the rest of the chain.
+    ///
+    /// Since the result type changes, this should usually follow the `emit_*` combinator:
+    ///
+    /// ```
+    /// # use async_coap::prelude::*;
+    /// # use async_coap::{RemoteEndpoint, Error};
+    /// # async fn get<RE: RemoteEndpoint>(remote_endpoint: RE) -> Result<(), Error> {
+    /// let request = CoapRequest::get()
+    ///     .emit_successful_response()
+    ///     .accept_any_of(&[ContentFormat::APPLICATION_CBOR, ContentFormat::APPLICATION_JSON]);
+    ///
+    /// let (response, format) = remote_endpoint.send_to(rel_ref!("temp"), request).await?;
+    ///
+    /// println!("Got {} as {}", response, format);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Panics if `formats` is empty.
+    fn accept_any_of<IC, R, TP>(self, formats: &[ContentFormat]) -> AcceptAnyOf<Self, IC>
+    where
+        IC: InboundContext,
+        R: Send,
+        TP: TransParams,
+        Self: SendDesc<IC, R, TP> + Sized,
+    {
+        AcceptAnyOf::new(self, formats.to_vec())
+    }
 }
 
 /// Marker trait for identifying that this `SendDesc` is for *multicast* requests.
@@ -367,6 +520,46 @@ where
         self.add_option(option::CONTENT_FORMAT, content_format)
     }
 
+    /// Adds a Request-Tag option with the given value, as described in [IETF-RFC9175].
+    ///
+    /// The send future retransmits a confirmable request using the same message id, which
+    /// is enough for the server to deduplicate it. But if the request times out without a
+    /// response and the caller sends it again, the new attempt is a different message and
+    /// the server has no way of knowing that it may have already performed it. Attaching the
+    /// same Request-Tag to every attempt allows a compliant server to recognize the retry and
+    /// answer it without performing a non-idempotent operation (like a `POST`) twice.
+    ///
+    /// The tag is written identically into every retransmission and every follow-up
+    /// message sent due to [`ResponseStatus::SendNext`], so it is also suitable for
+    /// tagging all of the blocks of a [Block1 upload][SendDescUnicast::block1].
+    ///
+    /// ```
+    /// # use async_coap::prelude::*;
+    /// # use async_coap::{RemoteEndpoint, RequestTag, Error};
+    /// # async fn post<RE: RemoteEndpoint>(remote_endpoint: RE) -> Result<MsgCode, Error> {
+    /// // Create the tag once and reuse it for every attempt.
+    /// let tag = RequestTag::random();
+    ///
+    /// for _ in 0..3 {
+    ///     let request = CoapRequest::post()
+    ///         .request_tag(tag)
+    ///         .payload_writer(|msg| msg.append_payload_string("launch"))
+    ///         .emit_msg_code();
+    ///
+    ///     match remote_endpoint.send(request).await {
+    ///         Err(Error::ResponseTimeout) => continue,
+    ///         ret => return ret,
+    ///     }
+    /// }
+    /// # Err(Error::ResponseTimeout)
+    /// # }
+    /// ```
+    ///
+    /// [IETF-RFC9175]: https://tools.ietf.org/html/rfc9175
+    fn request_tag(self, tag: RequestTag) -> AddOption<Self, RequestTag, Once<RequestTag>, IC> {
+        self.add_option(option::REQUEST_TAG, tag)
+    }
+
     /// Adds a handler function to be called when a response message has been received (or when
     /// an error has occurred).
     fn use_handler<F, FR>(self, handler: F) -> Handler<Self, F>
@@ -383,14 +576,14 @@ where
         }
     }
 
-    /// Updates the send descriptor chain to emit any received message as a result, even
-    /// if that message has a message code that indicates an error.
+    /// Updates the send descriptor chain to emit any received message as a [`Response`],
+    /// even if that message has a message code that indicates an error.
     fn emit_any_response(self) -> EmitAnyResponse<Self> {
         EmitAnyResponse::new(self)
     }
 
-    /// Updates the send descriptor chain to emit received message as a result, but only
-    /// if that message has a message code that indicates success.
+    /// Updates the send descriptor chain to emit received message as a [`Response`], but
+    /// only if that message has a message code that indicates success.
     fn emit_successful_response(self) -> EmitSuccessfulResponse<Self> {
         EmitSuccessfulResponse::new(self)
     }
@@ -409,6 +602,33 @@ where
         IncludeSocketAddr::new(self)
     }
 
+    /// Marks the messages sent by this send descriptor chain with the DSCP value `dscp`,
+    /// so that they can be prioritized by the network.
+    ///
+    /// The ECN codepoint of the inner send descriptor is kept. The marking is only applied
+    /// by sockets that implement
+    /// [`AsyncSendTo::poll_send_to_with_traffic_class`](crate::datagram::AsyncSendTo::poll_send_to_with_traffic_class);
+    /// other sockets send the messages unmarked.
+    ///
+    /// ```
+    /// # use async_coap::prelude::*;
+    /// # use async_coap::{RemoteEndpoint, TrafficClass, Error};
+    /// # async fn alarm<RE: RemoteEndpoint>(remote_endpoint: RE) -> Result<MsgCode, Error> {
+    /// let request = CoapRequest::post()
+    ///     .dscp(TrafficClass::DSCP_EF)
+    ///     .payload_writer(|msg| {
+    ///         msg.set_msg_code(MsgCode::MethodPost);
+    ///         msg.append_payload_string("overpressure")
+    ///     })
+    ///     .emit_msg_code();
+    ///
+    /// remote_endpoint.send_to(rel_ref!("alarms"), request).await
+    /// # }
+    /// ```
+    fn dscp(self, dscp: u8) -> Dscp<Self> {
+        Dscp::new(self, dscp)
+    }
+
     /// Adds an inspection closure that will be called for each received response message.
     ///
     /// The inspector closure will not be called if no responses are received, and it cannot
@@ -424,6 +644,56 @@ where
         }
     }
 
+    /// Adds a closure that checks the payload of the response before it is emitted, for
+    /// example against a known checksum.
+    ///
+    /// This may only follow a send descriptor which emits a [`Response`], like one created
+    /// by [`emit_successful_collected_response`][UnicastBlock2::emit_successful_collected_response].
+    /// If the closure returns `false`, the future resolves to [`Error::InvalidPayload`]
+    /// instead of the response.
+    ///
+    /// ```
+    /// # use async_coap::prelude::*;
+    /// # use async_coap::{RemoteEndpoint, Error};
+    /// # fn checksum(bytes: &[u8]) -> u32 { bytes.iter().map(|&b| b as u32).sum() }
+    /// # async fn download<RE: RemoteEndpoint>(remote_endpoint: RE) -> Result<(), Error> {
+    /// # let expected_checksum = 0;
+    /// let send_desc = CoapRequest::get()
+    ///     .block2(None)
+    ///     .emit_successful_collected_response()
+    ///     .expect_size2_matches()
+    ///     .validate_payload(move |bytes| checksum(bytes) == expected_checksum);
+    ///
+    /// let firmware = remote_endpoint.send_to(rel_ref!("firmware"), send_desc).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn validate_payload<F>(self, validate: F) -> ValidatePayload<Self, F>
+    where
+        F: FnMut(&[u8]) -> bool + Send,
+        Self: SendDesc<IC, Response<IC::SocketAddr>, TP>,
+    {
+        ValidatePayload {
+            inner: self,
+            validate,
+        }
+    }
+
+    /// Checks that the length of the payload of the response matches the value of its
+    /// `Size2` option, if it has one, before the response is emitted.
+    ///
+    /// This may only follow a send descriptor which emits a [`Response`]. When following
+    /// [`emit_successful_collected_response`][UnicastBlock2::emit_successful_collected_response],
+    /// this catches transfers that were cut short because the server changed the size of
+    /// the resource between blocks, or didn't send all of it. On a mismatch, the future
+    /// resolves to [`Error::InvalidPayload`] instead of the response.
+    fn expect_size2_matches(self) -> ExpectSize2Matches<Self>
+    where
+        Self: SendDesc<IC, Response<IC::SocketAddr>, TP>,
+    {
+        ExpectSize2Matches { inner: self }
+    }
+
     /// Adds a closure that writes to the payload of the outbound message.
     fn payload_writer<F>(self, writer: F) -> PayloadWriter<Self, F>
     where
@@ -435,6 +705,32 @@ where
         }
     }
 
+    /// Sets the `Uri-Host` option of the request, overriding the default host (if any) of the
+    /// [`RemoteEndpoint`] that it is sent with.
+    ///
+    /// This is useful for virtual-hosted servers, where the `Uri-Host` that identifies the
+    /// resource differs from the host that the request is sent to. To change the default
+    /// host for every request, see [`RemoteEndpoint::set_host_option`].
+    ///
+    /// ```
+    /// # use async_coap::prelude::*;
+    /// # use async_coap::{RemoteEndpoint, Error};
+    /// # async fn get<RE: RemoteEndpoint>(remote_endpoint: RE) -> Result<MsgCode, Error> {
+    /// let request = CoapRequest::get()
+    ///     .uri_host("device-1234.example.com")
+    ///     .emit_msg_code();
+    ///
+    /// remote_endpoint.send_to(rel_ref!("temp"), request).await
+    /// # }
+    /// ```
+    fn uri_host<S: Into<String>>(self, host: S) -> UriHost<Self, IC> {
+        UriHost {
+            inner: self,
+            host: host.into(),
+            phantom: PhantomData,
+        }
+    }
+
     /// Allows you to specify the URI_HOST, URI_PATH, and URI_QUERY option values
     /// in a more convenient way than using `add_option_iter` manually.
     fn uri_host_path<T: Into<RelRefBuf>>(
@@ -449,6 +745,38 @@ where
             phantom: PhantomData,
         }
     }
+
+    /// Records the options and payload written by this send descriptor chain the first
+    /// time it is sent, so that subsequent transmissions don't need to serialize them again.
+    ///
+    /// This is intended for requests that are sent repeatedly, like in a poll loop: the
+    /// returned send descriptor can be cloned for every request, with all of the clones
+    /// sharing the recorded options and payload. Since they are only recorded once per
+    /// destination, the chain must always write the same options and payload. Combinators
+    /// which write options that change from one message to the next, like
+    /// [`SendDescUnicast::block2`], should be added after calling this method.
+    ///
+    /// ```
+    /// # use async_coap::prelude::*;
+    /// # use async_coap::{RemoteEndpoint, Error};
+    /// # async fn poll<RE: RemoteEndpoint>(remote_endpoint: RE) -> Result<(), Error> {
+    /// let request = CoapRequest::get()
+    ///     .accept(ContentFormat::TEXT_PLAIN_UTF8)
+    ///     .prepare();
+    ///
+    /// loop {
+    ///     let response = remote_endpoint
+    ///         .send_to(rel_ref!("temp"), request.clone().emit_successful_response())
+    ///         .await?;
+    ///
+    ///     println!("temp: {:?}", response.payload_as_str());
+    /// #   break Ok(());
+    /// }
+    /// # }
+    /// ```
+    fn prepare(self) -> Prepared<Self, IC::SocketAddr> {
+        Prepared::new(self)
+    }
 }
 
 /// Blanket implementation of `SendDescExt` for all types implementing `SendDesc`.
@@ -594,7 +922,7 @@ macro_rules! write_options {
 }
 
 /// Helper macro that provides pass-thru implementations of the timing-related methods
-/// of a [`SendDesc`].
+/// of a [`SendDesc`], along with [`SendDesc::traffic_class`].
 ///
 /// This macro takes a single argument: the name of the member variable to pass along
 /// the call to.
@@ -614,6 +942,9 @@ macro_rules! send_desc_passthru_timing {
         fn transmit_wait_duration(&self) -> ::core::time::Duration {
             self.$inner.transmit_wait_duration()
         }
+        fn traffic_class(&self) -> $crate::TrafficClass {
+            self.$inner.traffic_class()
+        }
     }
 }
 
@@ -688,5 +1019,9 @@ macro_rules! send_desc_passthru_payload {
         ) -> Result<(), Error> {
             self.$inner.write_payload(msg, socket_addr)
         }
+
+        fn payload_size_hint(&self) -> usize {
+            self.$inner.payload_size_hint()
+        }
     }
 }
@@ -22,7 +22,7 @@ use super::*;
 /// This send descriptor can yield multiple results, so it should be used with
 /// [`LocalEndpointExt::send_as_stream`], [`RemoteEndpointExt::send_as_stream`],
 /// and/or [`RemoteEndpointExt::send_to_as_stream`].
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Multicast<SD>(pub(crate) SD);
 
 impl<SD> SendDescMulticast for Multicast<SD> {}
@@ -53,6 +53,9 @@ where
     fn transmit_wait_duration(&self) -> Duration {
         Duration::from_secs(8)
     }
+    fn traffic_class(&self) -> TrafficClass {
+        self.0.traffic_class()
+    }
 
     fn write_payload(
         &self,
@@ -64,6 +67,10 @@ where
         Ok(())
     }
 
+    fn payload_size_hint(&self) -> usize {
+        self.0.payload_size_hint()
+    }
+
     fn handler(&mut self, context: Result<&IC, Error>) -> Result<ResponseStatus<()>, Error> {
         context?;
         Ok(ResponseStatus::Continue)
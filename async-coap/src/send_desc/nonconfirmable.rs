@@ -20,7 +20,7 @@ use crate::send_desc_passthru_timing;
 
 /// Nonconfirmable send descriptor combinator created by the `nonconfirmable()` method on
 /// [`SendGet`], [`SendPut`], [`SendPost`], [`SendDelete`], and [`SendObserve`].
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Nonconfirmable<SD>(pub(crate) SD);
 
 impl<SD: SendDescUnicast> SendDescUnicast for Nonconfirmable<SD> {}
@@ -49,4 +49,8 @@ where
         msg.set_msg_type(MsgType::Non);
         Ok(())
     }
+
+    fn payload_size_hint(&self) -> usize {
+        self.0.payload_size_hint()
+    }
 }
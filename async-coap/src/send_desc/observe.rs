@@ -21,13 +21,39 @@ use super::*;
 /// This send descriptor can yield multiple results, so it should be used with
 /// [`LocalEndpointExt::send_as_stream`], [`RemoteEndpointExt::send_as_stream`],
 /// and/or [`RemoteEndpointExt::send_to_as_stream`].
+///
+/// Until the first response is received, the observation isn't registered, and the
+/// request fails with [`Error::ObserveRegistrationTimeout`] if no response arrives within
+/// the [initial timeout][SendObserve::initial_timeout]. After that, it fails with
+/// [`Error::ObservationLapsed`] if the time between two notifications exceeds the
+/// [notification timeout][SendObserve::notification_timeout].
+///
+/// Notifications which arrive over datagram transports after a fresher one (because
+/// they were reordered or duplicated in transit) are dropped, as determined by
+/// [`ObserveSeq::is_fresher_than`](crate::ObserveSeq::is_fresher_than).
 #[derive(Debug)]
 pub struct SendObserve<IC> {
+    initial_timeout: Option<Duration>,
+    notification_timeout: Option<Duration>,
+    responded: bool,
     phantom: PhantomData<IC>,
 }
 
 impl<IC> SendDescUnicast for SendObserve<IC> {}
 
+impl<IC> Clone for SendObserve<IC> {
+    /// Clones the configuration of this send descriptor. The clone starts out without a
+    /// registered observation.
+    fn clone(&self) -> Self {
+        Self {
+            initial_timeout: self.initial_timeout,
+            notification_timeout: self.notification_timeout,
+            responded: false,
+            phantom: PhantomData,
+        }
+    }
+}
+
 impl<IC> Default for SendObserve<IC> {
     fn default() -> Self {
         Self::new()
@@ -37,20 +63,47 @@ impl<IC> Default for SendObserve<IC> {
 impl<IC> SendObserve<IC> {
     pub(crate) fn new() -> Self {
         Self {
+            initial_timeout: None,
+            notification_timeout: None,
+            responded: false,
             phantom: PhantomData,
         }
     }
 
+    /// Sets how long to wait for the response to the registration, including any
+    /// retransmissions of the request.
+    ///
+    /// If no response is received in time, the request fails with
+    /// [`Error::ObserveRegistrationTimeout`]. This is typically shorter than the
+    /// [notification timeout][SendObserve::notification_timeout], so that an unreachable
+    /// server is noticed quickly. By default, the standard transmission parameters are
+    /// used.
+    pub fn initial_timeout(mut self, timeout: Duration) -> Self {
+        self.initial_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the longest time to wait for a notification after the previous one, once the
+    /// observation has been registered.
+    ///
+    /// If no notification is received in time, the request fails with
+    /// [`Error::ObservationLapsed`]. By default, this is the maximum round-trip time of the
+    /// standard transmission parameters.
+    pub fn notification_timeout(mut self, timeout: Duration) -> Self {
+        self.notification_timeout = Some(timeout);
+        self
+    }
+
     /// Returns a nonconfirmable version of this send descriptor.
     #[inline(always)]
     pub fn nonconfirmable(self) -> Nonconfirmable<SendObserve<IC>> {
-        Default::default()
+        Nonconfirmable(self)
     }
 
     /// Returns a multicast version of this send descriptor.
     #[inline(always)]
     pub fn multicast(self) -> Multicast<SendObserve<IC>> {
-        Default::default()
+        Multicast(self)
     }
 }
 
@@ -60,6 +113,21 @@ impl<IC: InboundContext> SendDesc<IC, ()> for SendObserve<IC> {
         Some(Duration::from_secs(60))
     }
 
+    fn max_rtt(&self) -> Duration {
+        if self.responded {
+            self.notification_timeout
+                .unwrap_or(StandardCoapConstants::COAP_MAX_RTT)
+        } else {
+            self.initial_timeout
+                .unwrap_or(StandardCoapConstants::COAP_MAX_RTT)
+        }
+    }
+
+    fn transmit_wait_duration(&self) -> Duration {
+        self.initial_timeout
+            .unwrap_or(StandardCoapConstants::COAP_MAX_TRANSMIT_WAIT)
+    }
+
     fn write_options(
         &self,
         msg: &mut dyn OptionInsert,
@@ -82,7 +150,12 @@ impl<IC: InboundContext> SendDesc<IC, ()> for SendObserve<IC> {
     }
 
     fn handler(&mut self, context: Result<&IC, Error>) -> Result<ResponseStatus<()>, Error> {
-        context?;
+        match context {
+            Ok(_) => self.responded = true,
+            Err(Error::ResponseTimeout) if self.responded => return Err(Error::ObservationLapsed),
+            Err(Error::ResponseTimeout) => return Err(Error::ObserveRegistrationTimeout),
+            Err(e) => return Err(e),
+        }
         Ok(ResponseStatus::Continue)
     }
 }
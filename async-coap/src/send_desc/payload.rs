@@ -19,7 +19,7 @@ impl<SD: SendDescUnicast, IC> SendDescUnicast for PayloadWriter<SD, IC> {}
 impl<SD: SendDescMulticast, IC> SendDescMulticast for PayloadWriter<SD, IC> {}
 
 /// Combinator for Send Descriptors created by [`SendDescExt::payload_writer`].
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct PayloadWriter<SD, F> {
     pub(super) inner: SD,
     pub(super) writer: F,
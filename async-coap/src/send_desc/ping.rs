@@ -16,7 +16,7 @@
 use super::*;
 
 /// Send descriptor for sending a CoAP ping.
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone)]
 pub struct Ping;
 
 impl Ping {
@@ -0,0 +1,219 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+use std::ops::RangeBounds;
+use std::sync::{Arc, Mutex};
+
+/// Combinator for Send Descriptors created by [`SendDescExt::prepare`].
+///
+/// The options and payload written by the wrapped send descriptor are recorded the first
+/// time they are needed and are replayed from then on, until the request is sent to a
+/// different socket address. Clones share the recorded request, so cloning a `Prepared`
+/// send descriptor for each request of a poll loop only serializes the request once.
+#[derive(Debug)]
+pub struct Prepared<SD, SA> {
+    pub(super) inner: SD,
+    pub(super) cache: Arc<Mutex<Option<PreparedRequest<SA>>>>,
+}
+
+impl<SD: SendDescUnicast, SA> SendDescUnicast for Prepared<SD, SA> {}
+impl<SD: SendDescMulticast, SA> SendDescMulticast for Prepared<SD, SA> {}
+
+impl<SD: Clone, SA> Clone for Prepared<SD, SA> {
+    fn clone(&self) -> Self {
+        Prepared {
+            inner: self.inner.clone(),
+            cache: self.cache.clone(),
+        }
+    }
+}
+
+impl<SD, SA> Prepared<SD, SA> {
+    pub(super) fn new(inner: SD) -> Prepared<SD, SA> {
+        Prepared {
+            inner,
+            cache: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Returns true if the request has been recorded.
+    pub fn is_prepared(&self) -> bool {
+        self.cache.lock().expect("Lock failed").is_some()
+    }
+}
+
+impl<SD, SA> Prepared<SD, SA>
+where
+    SA: SocketAddrExt,
+{
+    /// Calls `f` with the recorded request for `socket_addr`, recording it first if
+    /// necessary.
+    fn with_request<IC, R, F>(&self, socket_addr: &SA, f: F) -> Result<(), Error>
+    where
+        SD: SendDesc<IC, R>,
+        IC: InboundContext<SocketAddr = SA>,
+        R: Send,
+        F: FnOnce(&PreparedRequest<SA>) -> Result<(), Error>,
+    {
+        let mut cache = self.cache.lock().expect("Lock failed");
+
+        match cache.as_ref() {
+            Some(request) if request.socket_addr == *socket_addr => (),
+            _ => {
+                let mut options = RecordedMessage::default();
+                self.inner.write_options(
+                    &mut options,
+                    socket_addr,
+                    Bound::Unbounded,
+                    Bound::Unbounded,
+                )?;
+
+                let mut payload = RecordedMessage::default();
+                self.inner.write_payload(&mut payload, socket_addr)?;
+
+                *cache = Some(PreparedRequest {
+                    socket_addr: *socket_addr,
+                    options,
+                    payload,
+                });
+            }
+        }
+
+        f(cache.as_ref().unwrap())
+    }
+}
+
+impl<SD, IC, R> SendDesc<IC, R> for Prepared<SD, IC::SocketAddr>
+where
+    SD: SendDesc<IC, R>,
+    IC: InboundContext,
+    R: Send,
+{
+    send_desc_passthru_timing!(inner);
+    send_desc_passthru_handler!(inner, R);
+
+    fn write_options(
+        &self,
+        msg: &mut dyn OptionInsert,
+        socket_addr: &IC::SocketAddr,
+        start: Bound<OptionNumber>,
+        end: Bound<OptionNumber>,
+    ) -> Result<(), Error> {
+        self.with_request(socket_addr, |request| {
+            for (key, value) in request.options.options.iter() {
+                if (start, end).contains(key) {
+                    msg.insert_option_with_bytes(*key, value)?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn write_payload(
+        &self,
+        msg: &mut dyn MessageWrite,
+        socket_addr: &IC::SocketAddr,
+    ) -> Result<(), Error> {
+        self.with_request(socket_addr, |request| request.payload.replay(msg))
+    }
+}
+
+/// The options and payload of a request recorded by [`Prepared`].
+#[derive(Debug)]
+pub(super) struct PreparedRequest<SA> {
+    socket_addr: SA,
+    options: RecordedMessage,
+    payload: RecordedMessage,
+}
+
+/// A [`MessageWrite`] which records what is written to it, so that it can be replayed
+/// into another message later.
+///
+/// The message ID and token are ignored, since those are determined by the local endpoint
+/// for every transmission.
+#[derive(Debug, Default)]
+pub(super) struct RecordedMessage {
+    msg_type: Option<MsgType>,
+    msg_code: Option<MsgCode>,
+    options: Vec<(OptionNumber, Vec<u8>)>,
+    payload: Vec<u8>,
+}
+
+impl RecordedMessage {
+    fn replay(&self, msg: &mut dyn MessageWrite) -> Result<(), Error> {
+        self.replay_without_payload(msg)?;
+
+        if !self.payload.is_empty() {
+            msg.append_payload_bytes(&self.payload)?;
+        }
+
+        Ok(())
+    }
+
+    /// Replays everything except for the payload into `msg`.
+    pub(super) fn replay_without_payload(&self, msg: &mut dyn MessageWrite) -> Result<(), Error> {
+        if let Some(msg_type) = self.msg_type {
+            msg.set_msg_type(msg_type);
+        }
+
+        if let Some(msg_code) = self.msg_code {
+            msg.set_msg_code(msg_code);
+        }
+
+        for (key, value) in self.options.iter() {
+            msg.insert_option_with_bytes(*key, value)?;
+        }
+
+        Ok(())
+    }
+
+    /// The recorded payload.
+    #[cfg(all(feature = "client", feature = "block"))]
+    pub(super) fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+}
+
+impl OptionInsert for RecordedMessage {
+    fn insert_option_with_bytes(&mut self, key: OptionNumber, value: &[u8]) -> Result<(), Error> {
+        self.options.push((key, value.to_vec()));
+        Ok(())
+    }
+}
+
+impl MessageWrite for RecordedMessage {
+    fn set_msg_type(&mut self, tt: MsgType) {
+        self.msg_type = Some(tt);
+    }
+
+    fn set_msg_id(&mut self, _msg_id: MsgId) {}
+
+    fn set_msg_code(&mut self, code: MsgCode) {
+        self.msg_code = Some(code);
+    }
+
+    fn set_msg_token(&mut self, _token: MsgToken) {}
+
+    fn append_payload_bytes(&mut self, body: &[u8]) -> Result<(), Error> {
+        self.payload.extend_from_slice(body);
+        Ok(())
+    }
+
+    fn clear(&mut self) {
+        self.options.clear();
+        self.payload.clear();
+    }
+}
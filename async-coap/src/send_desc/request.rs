@@ -36,6 +36,7 @@ impl CoapRequest {
     /// inferred when the send descriptor is passed to [`LocalEndpointExt::send_as_stream`] (or
     /// one of its [many][RemoteEndpointExt::send_to_as_stream]
     /// [variants][RemoteEndpointExt::send_as_stream]).
+    #[cfg(feature = "observe")]
     #[inline(always)]
     pub fn observe<IC>() -> SendObserve<IC> {
         Default::default()
@@ -103,6 +104,13 @@ macro_rules! send_desc_def_method {
     (@rest ($name:ident, $code:expr, $handler:expr)) => {
         impl<IC> SendDescUnicast for $name<IC> {}
 
+        impl<IC> Clone for $name<IC> {
+            #[inline(always)]
+            fn clone(&self) -> Self {
+                Self(PhantomData)
+            }
+        }
+
         impl<IC> Default for $name<IC> {
             #[inline(always)]
             fn default() -> Self {
@@ -236,6 +244,15 @@ pub struct CoapRequestMethod<IC> {
     phantom: PhantomData<IC>,
 }
 
+impl<IC> Clone for CoapRequestMethod<IC> {
+    fn clone(&self) -> Self {
+        CoapRequestMethod {
+            msg_code: self.msg_code,
+            phantom: PhantomData,
+        }
+    }
+}
+
 impl<IC> SendDescUnicast for CoapRequestMethod<IC> {}
 
 impl<IC> CoapRequestMethod<IC> {
@@ -0,0 +1,296 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+use crate::send_desc::prepared::RecordedMessage;
+use std::marker::PhantomData;
+use std::sync::Mutex;
+
+impl<SD: SendDescUnicast, IC> SendDescUnicast for UnicastBlock1<SD, IC> {}
+impl<SD: SendDescUnicast, IC, F> SendDescUnicast for InspectUpload<SD, IC, F> {}
+
+/// Unicast Block1 upload combinator, created by [`SendDescUnicast::block1`] or
+/// [`SendDescUnicast::block1_from_payload`].
+///
+/// Splits the request payload into blocks, sending the next block each time the server
+/// responds with `2.31 Continue`. If the server asks for a smaller block size, subsequent
+/// blocks are sent using the smaller size. The final response from the server (usually
+/// `2.04 Changed` or `2.01 Created`) is passed along to the rest of the chain; any
+/// intermediate `2.31 Continue` responses are not.
+///
+/// The payload being uploaded is the payload written by the wrapped send descriptor (if
+/// any), followed by the payload given to [`SendDescUnicast::block1`]. The wrapped send
+/// descriptor only writes its payload once, the first time it is needed. What it wrote is
+/// recorded, and everything except for the payload is replayed into every block.
+#[derive(Debug)]
+pub struct UnicastBlock1<SD, IC> {
+    inner: SD,
+    payload: Vec<u8>,
+    captured: Mutex<Option<RecordedMessage>>,
+    offset: usize,
+    szx: u8,
+    acked: usize,
+    phantom: PhantomData<IC>,
+}
+
+impl<SD: Clone, IC> Clone for UnicastBlock1<SD, IC> {
+    fn clone(&self) -> Self {
+        // The clone starts uploading from the beginning.
+        UnicastBlock1 {
+            inner: self.inner.clone(),
+            payload: self.payload.clone(),
+            captured: Mutex::new(None),
+            offset: 0,
+            szx: self.szx,
+            acked: 0,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<SD, IC> UnicastBlock1<SD, IC> {
+    pub(super) fn new(inner: SD, payload: Vec<u8>, block1: Option<BlockInfo>) -> Self {
+        UnicastBlock1 {
+            inner,
+            payload,
+            captured: Mutex::new(None),
+            offset: 0,
+            // Without a block size, the default block size is used.
+            szx: block1.map_or(BlockInfo::default().szx(), |block1| block1.szx()),
+            acked: 0,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Adds a closure that is called with `(bytes_sent, total)` each time the server
+    /// acknowledges a block, including once the final block has been acknowledged.
+    ///
+    /// This may only follow a [`UnicastBlock1`].
+    pub fn inspect_upload<F>(self, inspect: F) -> InspectUpload<SD, IC, F>
+    where
+        F: FnMut(usize, usize) + Send,
+    {
+        InspectUpload {
+            inner: self,
+            inspect,
+        }
+    }
+
+    /// The number of payload bytes that have been acknowledged by the server so far.
+    pub fn bytes_sent(&self) -> usize {
+        self.acked
+    }
+
+    /// The total size of the payload, in bytes.
+    ///
+    /// Until the first block has been sent, this doesn't include the size of the payload
+    /// of the wrapped send descriptor.
+    pub fn total(&self) -> usize {
+        self.captured
+            .lock()
+            .expect("Lock failed")
+            .as_ref()
+            .map_or(0, |captured| captured.payload().len())
+            + self.payload.len()
+    }
+
+    /// The block that will be sent next.
+    ///
+    /// Fails with [`Error::InvalidArgument`] if the payload has more blocks than can be
+    /// numbered using the current block size.
+    fn current_block(&self) -> Result<BlockInfo, Error> {
+        let len = 1 << (self.szx as usize + 4);
+        let total = self.total();
+
+        if total > 0 && (total - 1) / len > BlockInfo::NUM_MAX as usize {
+            return Err(Error::InvalidArgument);
+        }
+
+        let more = self.offset + len < total;
+        BlockInfo::new((self.offset / len) as u32, more, self.szx).ok_or(Error::InvalidArgument)
+    }
+
+    /// Records what the wrapped send descriptor writes as its payload into `captured`, if
+    /// that hasn't happened yet.
+    fn capture_payload<R>(&self, socket_addr: &IC::SocketAddr) -> Result<(), Error>
+    where
+        SD: SendDesc<IC, R>,
+        IC: InboundContext,
+        R: Send,
+    {
+        let mut captured = self.captured.lock().expect("Lock failed");
+
+        if captured.is_none() {
+            let mut recorded = RecordedMessage::default();
+            self.inner.write_payload(&mut recorded, socket_addr)?;
+            *captured = Some(recorded);
+        }
+
+        Ok(())
+    }
+}
+
+impl<SD, IC, R> SendDesc<IC, R> for UnicastBlock1<SD, IC>
+where
+    SD: SendDesc<IC, R> + Send + SendDescUnicast,
+    IC: InboundContext,
+    R: Send,
+{
+    send_desc_passthru_timing!(inner);
+
+    fn supports_option(&self, option: OptionNumber) -> bool {
+        self.inner.supports_option(option)
+            || option == OptionNumber::BLOCK1
+            || option == OptionNumber::SIZE1
+    }
+
+    fn write_options(
+        &self,
+        msg: &mut dyn OptionInsert,
+        socket_addr: &IC::SocketAddr,
+        start: Bound<OptionNumber>,
+        end: Bound<OptionNumber>,
+    ) -> Result<(), Error> {
+        // The size of the payload determines whether there are more blocks.
+        self.capture_payload(socket_addr)?;
+
+        let block1 = self.current_block()?;
+
+        // Only the first block needs to indicate the total size.
+        let size1 = if block1.num() == 0 && block1.more_flag() {
+            Some(self.total() as u32)
+        } else {
+            None
+        };
+
+        write_options!((msg, socket_addr, start, end, self.inner) {
+            BLOCK1 => Some(block1).into_iter(),
+            SIZE1 => size1.into_iter(),
+        })
+    }
+
+    fn write_payload(
+        &self,
+        msg: &mut dyn MessageWrite,
+        socket_addr: &IC::SocketAddr,
+    ) -> Result<(), Error> {
+        self.capture_payload(socket_addr)?;
+
+        let block1 = self.current_block()?;
+        let total = self.total();
+        let start = block1.offset().min(total);
+        let end = (start + block1.len()).min(total);
+
+        let captured = self.captured.lock().expect("Lock failed");
+        let captured = match captured.as_ref() {
+            Some(captured) => {
+                captured.replay_without_payload(msg)?;
+                captured.payload()
+            }
+            None => &[],
+        };
+
+        if start < captured.len() {
+            msg.append_payload_bytes(&captured[start..end.min(captured.len())])?;
+        }
+
+        if end > captured.len() {
+            let start = start.max(captured.len()) - captured.len();
+            msg.append_payload_bytes(&self.payload[start..end - captured.len()])?;
+        }
+
+        Ok(())
+    }
+
+    fn payload_size_hint(&self) -> usize {
+        self.current_block().map_or(0, |block1| block1.len())
+    }
+
+    fn handler(&mut self, context: Result<&IC, Error>) -> Result<ResponseStatus<R>, Error> {
+        if let Ok(context) = context {
+            if context.is_dupe() {
+                // Ignore dupes.
+                return Ok(ResponseStatus::Continue);
+            }
+
+            let msg = context.message();
+            let current = match self.current_block() {
+                Ok(current) => current,
+                Err(e) => return self.inner.handler(Err(e)),
+            };
+
+            if current.more_flag() && msg.msg_code() == MsgCode::SuccessContinue {
+                match msg.block1() {
+                    // The server may ask us to use a smaller block size, in which
+                    // case it has only kept the first part of the block we sent.
+                    Some(ack) if ack.szx() <= current.szx() && ack.offset() == current.offset() => {
+                        self.szx = ack.szx();
+                        self.offset = ack.offset() + ack.len();
+                        self.acked = self.offset;
+                        return Ok(ResponseStatus::SendNext);
+                    }
+                    _ => return self.inner.handler(Err(Error::BadResponse)),
+                }
+            }
+
+            if !current.more_flag() && msg.msg_code().is_success() {
+                self.acked = self.total();
+            }
+        }
+
+        self.inner.handler(context)
+    }
+}
+
+/// Upload progress inspection combinator, created by [`UnicastBlock1::inspect_upload`].
+#[derive(Debug)]
+pub struct InspectUpload<SD, IC, F> {
+    inner: UnicastBlock1<SD, IC>,
+    inspect: F,
+}
+
+impl<SD: Clone, IC, F: Clone> Clone for InspectUpload<SD, IC, F> {
+    fn clone(&self) -> Self {
+        InspectUpload {
+            inner: self.inner.clone(),
+            inspect: self.inspect.clone(),
+        }
+    }
+}
+
+impl<SD, IC, R, F> SendDesc<IC, R> for InspectUpload<SD, IC, F>
+where
+    SD: SendDesc<IC, R> + Send + SendDescUnicast,
+    IC: InboundContext,
+    R: Send,
+    F: FnMut(usize, usize) + Send,
+{
+    send_desc_passthru_timing!(inner);
+    send_desc_passthru_options!(inner);
+    send_desc_passthru_payload!(inner);
+    send_desc_passthru_supports_option!(inner);
+
+    fn handler(&mut self, context: Result<&IC, Error>) -> Result<ResponseStatus<R>, Error> {
+        let before = self.inner.bytes_sent();
+        let ret = self.inner.handler(context);
+        let after = self.inner.bytes_sent();
+
+        if after != before {
+            (self.inspect)(after, self.inner.total());
+        }
+
+        ret
+    }
+}
@@ -13,12 +13,16 @@
 // limitations under the License.
 //
 
+use super::emit::{elapsed_since, mark_sent};
 use super::*;
 use crate::message::{OwnedImmutableMessage, VecMessageEncoder};
+use std::cell::Cell;
 use std::marker::PhantomData;
+use std::time::Instant;
 
 impl<SD: SendDescUnicast, IC> SendDescUnicast for UnicastBlock2<SD, IC> {}
 impl<SD: SendDescUnicast, IC> SendDescUnicast for UnicastBlock2Collect<SD, IC> {}
+impl<SD: SendDescUnicast, IC, F> SendDescUnicast for InspectIncomplete<SD, IC, F> {}
 
 /// Unicast Block2 Tracking combinator, created by [`SendDescUnicast::block2`].
 ///
@@ -28,9 +32,21 @@ pub struct UnicastBlock2<SD, IC> {
     pub(super) block2_default: Option<BlockInfo>,
     pub(super) reconstructor: Option<BlockReconstructor<VecMessageEncoder>>,
     pub(super) etag: Option<ETag>,
+    pub(super) is_strict: bool,
+    pub(super) failed_block_map: Option<BlockMap>,
     pub(super) phantom: PhantomData<IC>,
 }
 
+impl<SD: Clone, IC> Clone for UnicastBlock2<SD, IC> {
+    fn clone(&self) -> Self {
+        // The clone starts a new transfer.
+        UnicastBlock2 {
+            is_strict: self.is_strict,
+            ..UnicastBlock2::new(self.inner.clone(), self.block2_default)
+        }
+    }
+}
+
 impl<SD, IC> UnicastBlock2<SD, IC> {
     pub(super) fn new(inner: SD, block2: Option<BlockInfo>) -> UnicastBlock2<SD, IC> {
         UnicastBlock2 {
@@ -38,16 +54,42 @@ impl<SD, IC> UnicastBlock2<SD, IC> {
             block2_default: block2,
             reconstructor: None,
             etag: None,
+            is_strict: false,
+            failed_block_map: None,
             phantom: PhantomData,
         }
     }
 
+    /// Rejects blocks which aren't the next block expected, including duplicates of blocks
+    /// that have already been received.
+    ///
+    /// By default, blocks which have already been received are ignored. Either way, a
+    /// block that skips ahead of the next block causes the transfer to fail with
+    /// [`Error::IncompleteBlockTransfer`].
+    pub fn strict_block_sequencing(mut self) -> UnicastBlock2<SD, IC> {
+        self.is_strict = true;
+        self
+    }
+
+    /// The parts of the transfer that have been received, either for the transfer that is
+    /// in progress or for the last transfer which failed with
+    /// [`Error::IncompleteBlockTransfer`].
+    pub fn block_map(&self) -> Option<&BlockMap> {
+        self.reconstructor
+            .as_ref()
+            .map(BlockReconstructor::block_map)
+            .or(self.failed_block_map.as_ref())
+    }
+
     /// Adds Block2 collection support to this [`SendDesc`] chain.
     ///
     /// This may only follow a [`UnicastBlock2`], and the prior return type
     /// must be `()` (the default).
     pub fn emit_successful_collected_response(self) -> UnicastBlock2Collect<SD, IC> {
-        UnicastBlock2Collect { inner: self }
+        UnicastBlock2Collect {
+            inner: self,
+            sent_at: Cell::new(None),
+        }
     }
 }
 
@@ -118,14 +160,17 @@ where
 
                     let next_block = block2.next().unwrap();
                     self.reconstructor = Some(BlockReconstructor::new(encoder, next_block));
+                    self.failed_block_map = None;
                 }
 
-                match self
-                    .reconstructor
-                    .as_mut()
-                    .unwrap()
-                    .feed(block2, msg.payload())
-                {
+                let reconstructor = self.reconstructor.as_mut().unwrap();
+                let fed = reconstructor.feed(block2, msg.payload());
+
+                // The first block has already been written along with the rest of the
+                // message, so strict sequencing only applies to the blocks after it.
+                reconstructor.set_strict_sequencing(self.is_strict);
+
+                match fed {
                     Ok(false) => {
                         return self
                             .inner
@@ -134,9 +179,10 @@ where
                     }
                     Ok(true) => return self.inner.handler(Ok(context)),
                     Err(_) => {
-                        self.reconstructor = None;
+                        self.failed_block_map =
+                            self.reconstructor.take().map(|r| r.block_map().clone());
                         self.etag = None;
-                        return self.inner.handler(Err(Error::Reset));
+                        return self.inner.handler(Err(Error::IncompleteBlockTransfer));
                     }
                 };
             } else {
@@ -151,49 +197,98 @@ where
 
 /// Unicast Block2 Collecting combinator, created by [`UnicastBlock2::emit_successful_collected_response`].
 ///
-/// This `SendDesc` will collect all of the various pieces and emit a single allocated
-/// [`MessageRead`] instance that contains the entire payload.
+/// This `SendDesc` will collect all of the various pieces and emit a single [`Response`]
+/// that contains the entire payload.
 #[derive(Debug)]
 pub struct UnicastBlock2Collect<SD, SA> {
     inner: UnicastBlock2<SD, SA>,
+    sent_at: Cell<Option<Instant>>,
+}
+
+impl<SD: Clone, IC> Clone for UnicastBlock2Collect<SD, IC> {
+    fn clone(&self) -> Self {
+        self.inner.clone().emit_successful_collected_response()
+    }
 }
 
-impl<SD, IC> SendDesc<IC, OwnedImmutableMessage> for UnicastBlock2Collect<SD, IC>
+impl<SD, IC> UnicastBlock2Collect<SD, IC> {
+    /// Adds a closure that is called with the [`BlockMap`] of the transfer if the collection
+    /// fails with [`Error::IncompleteBlockTransfer`], describing which parts of the
+    /// response were received and where the gaps are.
+    ///
+    /// This may only follow a [`UnicastBlock2Collect`].
+    pub fn inspect_incomplete<F>(self, inspect: F) -> InspectIncomplete<SD, IC, F>
+    where
+        F: FnMut(&BlockMap) + Send,
+    {
+        InspectIncomplete {
+            inner: self,
+            inspect,
+        }
+    }
+}
+
+impl<SD, IC> SendDesc<IC, Response<IC::SocketAddr>> for UnicastBlock2Collect<SD, IC>
 where
     SD: SendDesc<IC, ()> + Send + SendDescUnicast,
     IC: InboundContext,
 {
     send_desc_passthru_timing!(inner);
-    send_desc_passthru_payload!(inner);
     send_desc_passthru_options!(inner);
     send_desc_passthru_supports_option!(inner);
 
+    fn write_payload(
+        &self,
+        msg: &mut dyn MessageWrite,
+        socket_addr: &IC::SocketAddr,
+    ) -> Result<(), Error> {
+        mark_sent(&self.sent_at);
+        self.inner.write_payload(msg, socket_addr)
+    }
+
+    fn payload_size_hint(&self) -> usize {
+        self.inner.payload_size_hint()
+    }
+
     fn handler(
         &mut self,
         context: Result<&IC, Error>,
-    ) -> Result<ResponseStatus<OwnedImmutableMessage>, Error> {
+    ) -> Result<ResponseStatus<Response<IC::SocketAddr>>, Error> {
+        let elapsed = elapsed_since(&self.sent_at);
         let ret = match self.inner.handler(context) {
             Ok(rs) => {
                 if let Some(recons) = self.inner.reconstructor.as_ref() {
                     if recons.is_finished() {
-                        self.inner.reconstructor.take().unwrap().into_inner().into()
+                        // UNWRAP-SAFETY: The reconstructor only finishes after being fed
+                        //                a response, so `context` can't be an error here.
+                        let remote_socket_addr = context.unwrap().remote_socket_addr();
+                        let message: OwnedImmutableMessage =
+                            self.inner.reconstructor.take().unwrap().into_inner().into();
+                        Response::new(message, remote_socket_addr, elapsed)
                     } else {
                         return Ok(match rs {
                             ResponseStatus::SendNext => ResponseStatus::SendNext,
+                            ResponseStatus::SendNextWith(modify) => {
+                                ResponseStatus::SendNextWith(modify)
+                            }
                             _ => ResponseStatus::Continue,
                         });
                     }
-                } else if let Some(context) = context.ok() {
-                    context.message().to_owned()
                 } else {
-                    return Ok(match rs {
-                        ResponseStatus::SendNext => ResponseStatus::SendNext,
-                        _ => ResponseStatus::Continue,
-                    });
+                    match (rs, context.ok()) {
+                        // Intermediate responses, like the `2.31 Continue` responses
+                        // of Block1 uploads, aren't collected.
+                        (ResponseStatus::SendNext, _) => return Ok(ResponseStatus::SendNext),
+                        (ResponseStatus::SendNextWith(modify), _) => {
+                            return Ok(ResponseStatus::SendNextWith(modify))
+                        }
+                        (_, Some(context)) => Response::from_context(context, elapsed),
+                        (_, None) => return Ok(ResponseStatus::Continue),
+                    }
                 }
             }
             Err(Error::ClientRequestError) if context.is_ok() => {
-                context.unwrap().message().to_owned()
+                Response::from_context(context.unwrap(), elapsed)
             }
             Err(e) => return Err(e),
         };
@@ -201,3 +296,47 @@ where
         return Ok(ResponseStatus::Done(ret));
     }
 }
+
+/// Incomplete Block2 collection inspection combinator, created by
+/// [`UnicastBlock2Collect::inspect_incomplete`].
+#[derive(Debug)]
+pub struct InspectIncomplete<SD, IC, F> {
+    inner: UnicastBlock2Collect<SD, IC>,
+    inspect: F,
+}
+
+impl<SD: Clone, IC, F: Clone> Clone for InspectIncomplete<SD, IC, F> {
+    fn clone(&self) -> Self {
+        InspectIncomplete {
+            inner: self.inner.clone(),
+            inspect: self.inspect.clone(),
+        }
+    }
+}
+
+impl<SD, IC, F> SendDesc<IC, Response<IC::SocketAddr>> for InspectIncomplete<SD, IC, F>
+where
+    SD: SendDesc<IC, ()> + Send + SendDescUnicast,
+    IC: InboundContext,
+    F: FnMut(&BlockMap) + Send,
+{
+    send_desc_passthru_timing!(inner);
+    send_desc_passthru_payload!(inner);
+    send_desc_passthru_options!(inner);
+    send_desc_passthru_supports_option!(inner);
+
+    fn handler(
+        &mut self,
+        context: Result<&IC, Error>,
+    ) -> Result<ResponseStatus<Response<IC::SocketAddr>>, Error> {
+        let ret = self.inner.handler(context);
+
+        if let Err(Error::IncompleteBlockTransfer) = ret {
+            if let Some(block_map) = self.inner.inner.failed_block_map.as_ref() {
+                (self.inspect)(block_map);
+            }
+        }
+
+        ret
+    }
+}
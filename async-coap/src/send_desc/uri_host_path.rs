@@ -14,10 +14,13 @@
 //
 
 use super::*;
+use crate::option::UriOptions;
 use std::marker::PhantomData;
 
 impl<SD: SendDescUnicast, IC> SendDescUnicast for UriHostPath<SD, IC> {}
 impl<SD: SendDescMulticast, IC> SendDescMulticast for UriHostPath<SD, IC> {}
+impl<SD: SendDescUnicast, IC> SendDescUnicast for UriHost<SD, IC> {}
+impl<SD: SendDescMulticast, IC> SendDescMulticast for UriHost<SD, IC> {}
 
 /// Combinator for Send Descriptors created by [`SendDescExt::uri_host_path`].
 #[derive(Debug)]
@@ -28,6 +31,17 @@ pub struct UriHostPath<SD, IC> {
     pub(super) phantom: PhantomData<IC>,
 }
 
+impl<SD: Clone, IC> Clone for UriHostPath<SD, IC> {
+    fn clone(&self) -> Self {
+        UriHostPath {
+            inner: self.inner.clone(),
+            host: self.host.clone(),
+            path_and_query: self.path_and_query.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
 impl<SD, IC, R> SendDesc<IC, R> for UriHostPath<SD, IC>
 where
     SD: SendDesc<IC, R>,
@@ -45,20 +59,103 @@ where
         start: Bound<OptionNumber>,
         end: Bound<OptionNumber>,
     ) -> Result<(), Error> {
-        // We define this up here at the top of the method so that it doesn't
-        // go out of scope.
-        let mut unescape_buf;
+        // A `Uri-Host` option written by the inner send descriptor (like one added
+        // using `SendDescExt::uri_host`) takes precedence over ours.
+        let host = match &self.host {
+            Some(_) if writes_uri_host(&self.inner, socket_addr)? => None,
+            host => host.as_ref(),
+        };
+
+        if self.path_and_query.as_str().contains('%') {
+            // Percent-encoded components have to be unescaped into new strings.
+            let uri_options = UriOptions::from_uri_ref(&self.path_and_query, 0)?;
+
+            return write_options!((msg, socket_addr, start, end, self.inner) {
+                URI_HOST => host,
+                URI_PATH => uri_options.uri_path.iter(),
+                URI_QUERY => uri_options.uri_query.iter(),
+            });
+        }
+
+        // Otherwise the options can be written straight from the reference.
+        let components = self.path_and_query.components();
+
+        if components.raw_fragment().is_some() {
+            return Err(Error::InvalidArgument);
+        }
+
+        write_options!((msg, socket_addr, start, end, self.inner) {
+            URI_HOST => host,
+            URI_PATH => raw_uri_path_segments(components.raw_path()),
+            URI_QUERY => raw_uri_query_items(components.raw_query()),
+        })
+    }
+}
+
+/// Determines if `send_desc` writes a `Uri-Host` option.
+fn writes_uri_host<SD, IC, R>(send_desc: &SD, socket_addr: &IC::SocketAddr) -> Result<bool, Error>
+where
+    SD: SendDesc<IC, R>,
+    IC: InboundContext,
+    R: Send,
+{
+    /// An [`OptionInsert`] that only keeps track of whether a `Uri-Host` option was inserted.
+    struct UriHostProbe(bool);
+
+    impl OptionInsert for UriHostProbe {
+        fn insert_option_with_bytes(&mut self, key: OptionNumber, _: &[u8]) -> Result<(), Error> {
+            self.0 |= key == option::URI_HOST.0;
+            Ok(())
+        }
+    }
+
+    let mut probe = UriHostProbe(false);
+    send_desc.write_options(
+        &mut probe,
+        socket_addr,
+        Bound::Included(option::URI_HOST.0),
+        Bound::Included(option::URI_HOST.0),
+    )?;
+    Ok(probe.0)
+}
+
+/// Combinator for Send Descriptors created by [`SendDescExt::uri_host`].
+#[derive(Debug)]
+pub struct UriHost<SD, IC> {
+    pub(super) inner: SD,
+    pub(super) host: String,
+    pub(super) phantom: PhantomData<IC>,
+}
+
+impl<SD: Clone, IC> Clone for UriHost<SD, IC> {
+    fn clone(&self) -> Self {
+        UriHost {
+            inner: self.inner.clone(),
+            host: self.host.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
 
+impl<SD, IC, R> SendDesc<IC, R> for UriHost<SD, IC>
+where
+    SD: SendDesc<IC, R>,
+    IC: InboundContext,
+    R: Send,
+{
+    send_desc_passthru_timing!(inner);
+    send_desc_passthru_handler!(inner, R);
+    send_desc_passthru_payload!(inner);
+
+    fn write_options(
+        &self,
+        msg: &mut dyn OptionInsert,
+        socket_addr: &IC::SocketAddr,
+        start: Bound<OptionNumber>,
+        end: Bound<OptionNumber>,
+    ) -> Result<(), Error> {
         write_options!((msg, socket_addr, start, end, self.inner) {
-            URI_HOST => self.host.iter(),
-            URI_PATH => {
-                unescape_buf = self.path_and_query.clone().into_unescape_buf();
-                unescape_buf.path_segments()
-            },
-            URI_QUERY => {
-                unescape_buf = self.path_and_query.clone().into_unescape_buf();
-                unescape_buf.query_items()
-            },
+            URI_HOST => once(self.host.as_str()),
         })
     }
 }
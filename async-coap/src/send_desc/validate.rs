@@ -0,0 +1,87 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+
+impl<SD: SendDescUnicast, F> SendDescUnicast for ValidatePayload<SD, F> {}
+impl<SD: SendDescMulticast, F> SendDescMulticast for ValidatePayload<SD, F> {}
+
+/// Combinator for Send Descriptors created by [`SendDescExt::validate_payload`].
+#[derive(Debug, Clone)]
+pub struct ValidatePayload<SD, F> {
+    pub(super) inner: SD,
+    pub(super) validate: F,
+}
+
+impl<SD, F, IC> SendDesc<IC, Response<IC::SocketAddr>> for ValidatePayload<SD, F>
+where
+    SD: SendDesc<IC, Response<IC::SocketAddr>> + Send,
+    IC: InboundContext,
+    F: FnMut(&[u8]) -> bool + Send,
+{
+    send_desc_passthru_timing!(inner);
+    send_desc_passthru_options!(inner);
+    send_desc_passthru_payload!(inner);
+    send_desc_passthru_supports_option!(inner);
+
+    fn handler(
+        &mut self,
+        context: Result<&IC, Error>,
+    ) -> Result<ResponseStatus<Response<IC::SocketAddr>>, Error> {
+        match self.inner.handler(context)? {
+            ResponseStatus::Done(response) if !(self.validate)(response.payload()) => {
+                Err(Error::InvalidPayload)
+            }
+            status => Ok(status),
+        }
+    }
+}
+
+impl<SD: SendDescUnicast> SendDescUnicast for ExpectSize2Matches<SD> {}
+impl<SD: SendDescMulticast> SendDescMulticast for ExpectSize2Matches<SD> {}
+
+/// Combinator for Send Descriptors created by [`SendDescExt::expect_size2_matches`].
+#[derive(Debug, Clone)]
+pub struct ExpectSize2Matches<SD> {
+    pub(super) inner: SD,
+}
+
+impl<SD, IC> SendDesc<IC, Response<IC::SocketAddr>> for ExpectSize2Matches<SD>
+where
+    SD: SendDesc<IC, Response<IC::SocketAddr>> + Send,
+    IC: InboundContext,
+{
+    send_desc_passthru_timing!(inner);
+    send_desc_passthru_options!(inner);
+    send_desc_passthru_payload!(inner);
+    send_desc_passthru_supports_option!(inner);
+
+    fn handler(
+        &mut self,
+        context: Result<&IC, Error>,
+    ) -> Result<ResponseStatus<Response<IC::SocketAddr>>, Error> {
+        let status = self.inner.handler(context)?;
+
+        if let ResponseStatus::Done(response) = &status {
+            if let Some(size2) = response.option(option::SIZE2)? {
+                if size2 as usize != response.payload().len() {
+                    return Err(Error::InvalidPayload);
+                }
+            }
+        }
+
+        Ok(status)
+    }
+}
@@ -0,0 +1,256 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Simplified one-shot API, for scripts and quick tools.
+//!
+//! Each of the functions in this module sends a single request to the resource at a
+//! `coap:` URI and returns the payload of the response. They take care of everything
+//! else: a temporary [`DatagramLocalEndpoint`] is created for the request, bound to an
+//! ephemeral UDP port, and its receive loop is driven until the response has been
+//! received. Block2 transfers are used to retrieve large responses, and Block1 transfers
+//! to upload large payloads.
+//!
+//! ```no_run
+//! # use async_coap::prelude::*;
+//! # use async_coap::{simple, ContentFormat, Error};
+//! # async fn run() -> Result<(), Error> {
+//! let payload = simple::get(uri!("coap://coap.me/test")).await?;
+//! println!("{}", String::from_utf8_lossy(&payload));
+//!
+//! simple::put(uri!("coap://coap.me/sink"), b"hello", ContentFormat::TEXT_PLAIN_UTF8).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Since a new endpoint is created for every request, this is less efficient than
+//! keeping a [`LocalEndpoint`] around, and there is no way to adjust the requests that
+//! are sent. Anything beyond one-off requests should use the full API instead.
+//!
+//! [`DatagramLocalEndpoint`]: crate::datagram::DatagramLocalEndpoint
+
+use super::*;
+use crate::datagram::{
+    AllowStdUdpSocket, DatagramInboundContext, DatagramLocalEndpoint, DatagramSocketTypes,
+};
+use futures::future::{select, Either};
+
+type UdpInboundContext = DatagramInboundContext<std::net::SocketAddr>;
+
+/// Fetches the resource at `uri` with a `GET` request, returning the payload of the
+/// response.
+///
+/// Responses which aren't successful are returned as the corresponding [`Error`], like
+/// [`Error::ResourceNotFound`].
+pub async fn get(uri: &Uri) -> Result<Vec<u8>, Error> {
+    let send_desc = CoapRequest::get()
+        .block2(None)
+        .emit_successful_collected_response();
+
+    one_shot(uri, send_desc).await
+}
+
+/// Stores `payload` in the resource at `uri` with a `PUT` request, returning the payload
+/// of the response.
+///
+/// The request is sent with a `Content-Format` option for `content_format`. Payloads which
+/// don't fit into a single block of the default size are uploaded using Block1 transfers.
+/// Responses which aren't successful are returned as the corresponding [`Error`].
+pub async fn put(
+    uri: &Uri,
+    payload: &[u8],
+    content_format: ContentFormat,
+) -> Result<Vec<u8>, Error> {
+    let request = CoapRequest::put().content_format(content_format);
+
+    if payload.len() <= BlockInfo::default().len() {
+        let send_desc = request
+            .payload_writer(move |msg_out| {
+                msg_out.set_msg_code(MsgCode::MethodPut);
+                msg_out.append_payload_bytes(payload)
+            })
+            .block2(None)
+            .emit_successful_collected_response();
+
+        one_shot(uri, send_desc).await
+    } else {
+        let send_desc = request
+            .block1(payload, None)
+            .block2(None)
+            .emit_successful_collected_response();
+
+        one_shot(uri, send_desc).await
+    }
+}
+
+/// Creates a temporary local endpoint suitable for `uri`, and drives its receive loop
+/// while `send_desc` is used to send a request to `uri`.
+async fn one_shot<SD>(uri: &Uri, send_desc: SD) -> Result<Vec<u8>, Error>
+where
+    SD: SendDesc<UdpInboundContext, Response<std::net::SocketAddr>> + Send,
+{
+    if let Some(scheme) = uri.scheme() {
+        if scheme != URI_SCHEME_COAP {
+            return Err(Error::UnsupportedUriScheme);
+        }
+    }
+
+    let components = uri.components();
+    let host = components.host().ok_or(Error::HostNotFound)?;
+    let port = components.port().unwrap_or(DEFAULT_PORT_COAP_UDP);
+
+    // The host is resolved first, so that the endpoint can be bound to the unspecified
+    // address of the same family. Only binding to the unspecified IPv6 address when it
+    // is needed avoids relying on IPv6 being available.
+    let socket_addr = AllowStdUdpSocket::lookup_host(&host.to_string(), port)
+        .map_err(|_| Error::HostLookupFailure)?
+        .next()
+        .ok_or(Error::HostNotFound)?;
+
+    let bind_addr = if socket_addr.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+
+    let local_endpoint = DatagramLocalEndpoint::new(AllowStdUdpSocket::bind(bind_addr)?);
+
+    // Like `LocalEndpoint::remote_endpoint_from_uri`, only host names are sent along
+    // with the request.
+    let host = match host.ip_addr() {
+        Some(_) => None,
+        None => Some(host.to_string()),
+    };
+    let remote_endpoint =
+        local_endpoint.remote_endpoint(socket_addr, host, uri.trim_fragment().rel());
+
+    let future_send = request(&remote_endpoint, send_desc).boxed();
+    let future_receive = local_endpoint.receive_loop(null_receiver!());
+
+    // The result is bound before returning it so that both futures, which borrow
+    // `local_endpoint`, are dropped first.
+    let ret = match select(future_send, future_receive).await {
+        Either::Left((ret, _)) => ret,
+        Either::Right((err, _)) => Err(err),
+    };
+
+    ret
+}
+
+/// Uses `send_desc` to send a request to `remote_endpoint`, returning the payload of the
+/// response if it was successful.
+async fn request<RE, SD>(remote_endpoint: &RE, send_desc: SD) -> Result<Vec<u8>, Error>
+where
+    RE: RemoteEndpoint,
+    SD: SendDesc<RE::InboundContext, Response<RE::SocketAddr>> + Send,
+{
+    let response = remote_endpoint.send(send_desc).await?;
+
+    match response.msg_code() {
+        code if code.is_success() => Ok(response.payload().to_vec()),
+        code if code.is_client_error() => Err(Error::ClientRequestError),
+        _ => Err(Error::ServerError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datagram::DatagramRespondableInboundContext;
+    use futures::executor::block_on;
+    use std::net::SocketAddr;
+
+    fn handle(context: &DatagramRespondableInboundContext<SocketAddr>) -> Result<(), Error> {
+        let msg = context.message();
+        let path = msg.options().extract_uri()?;
+
+        match (msg.msg_code(), path.as_str()) {
+            (MsgCode::MethodGet, "test") => context.respond(|msg_out| {
+                msg_out.set_msg_code(MsgCode::SuccessContent);
+                msg_out.append_payload_string("Hello, world!")
+            }),
+            (MsgCode::MethodPut, "sink") => {
+                // Blocks are assumed to arrive in order, so the final block reveals the
+                // length of the whole payload.
+                let block1 = msg.block1();
+                let len = block1.map_or(0, |block1| block1.offset()) + msg.payload().len();
+                let content_format = msg.content_format();
+                context.respond(|msg_out| {
+                    if let Some(block1) = block1 {
+                        msg_out.insert_option(option::BLOCK1, block1)?;
+                        if block1.more_flag() {
+                            msg_out.set_msg_code(MsgCode::SuccessContinue);
+                            return Ok(());
+                        }
+                    }
+                    msg_out.set_msg_code(MsgCode::SuccessChanged);
+                    if let Some(content_format) = content_format {
+                        msg_out.append_payload_string(&format!("{} {}", len, content_format))?;
+                    }
+                    Ok(())
+                })
+            }
+            _ => context.respond(|msg_out| {
+                msg_out.set_msg_code(MsgCode::ClientErrorNotFound);
+                Ok(())
+            }),
+        }
+    }
+
+    /// Runs the future returned by `f` for the URI of `path` on a server on the local host.
+    fn with_server<F, Fut>(path: &str, f: F) -> Fut::Output
+    where
+        F: FnOnce(UriBuf) -> Fut,
+        Fut: Future + Unpin,
+    {
+        let socket = AllowStdUdpSocket::bind("127.0.0.1:0").expect("UDP bind failed");
+        let uri = format!("coap://{}/{}", socket.local_addr().unwrap(), path);
+        let server = DatagramLocalEndpoint::new(socket);
+
+        let future = f(UriBuf::from_string(uri).unwrap());
+
+        let ret = match block_on(select(future, server.receive_loop(handle))) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((ret, _)) => ret,
+        };
+
+        ret
+    }
+
+    #[test]
+    fn get() {
+        let ret = with_server("test", |uri| async move { super::get(&uri).await }.boxed());
+        assert_eq!(Ok(b"Hello, world!".to_vec()), ret);
+
+        let ret = with_server("missing", |uri| {
+            async move { super::get(&uri).await }.boxed()
+        });
+        assert_eq!(Err(Error::ResourceNotFound), ret);
+    }
+
+    #[test]
+    fn put() {
+        let ret = with_server("sink", |uri| {
+            async move { super::put(&uri, b"hello", ContentFormat::TEXT_PLAIN_UTF8).await }.boxed()
+        });
+        assert_eq!(Ok(b"5 text/plain;charset=utf-8".to_vec()), ret);
+
+        // Large enough to be uploaded in several blocks.
+        let payload = vec![b'x'; BlockInfo::default().len() * 2 + 1];
+        let ret = with_server("sink", |uri| {
+            async move { super::put(&uri, &payload, ContentFormat::TEXT_PLAIN_UTF8).await }.boxed()
+        });
+        assert_eq!(Ok(b"2049 text/plain;charset=utf-8".to_vec()), ret);
+    }
+}
@@ -73,12 +73,26 @@ pub trait SocketAddrExt:
         Some(*self)
     }
 
-    /// Renders the address portion to a string.
+    /// Creates a socket address from an IP address and port, or `None` if this
+    /// address type cannot represent IP addresses.
+    ///
+    /// Used to skip name resolution when a URI already contains an IP address literal.
+    #[cfg(feature = "std")]
+    #[allow(unused_variables)]
+    fn from_ip_addr(addr: std::net::IpAddr, port: u16) -> Option<Self> {
+        None
+    }
+
+    /// Renders the address portion to a string, as it would appear (unescaped) in the
+    /// host component of a URI.
     fn addr_to_string(&self) -> String;
 
     /// Creates a URI from this `SocketAddr` using the given scheme.
+    ///
+    /// The port is omitted if it is zero.
     fn as_uri_buf(&self, scheme: &str) -> UriBuf {
-        UriBuf::from_scheme_host_port(scheme, self.addr_to_string(), Some(self.port()))
+        let port = Some(self.port()).filter(|&port| port != 0);
+        UriBuf::from_scheme_host_port(scheme, self.addr_to_string(), port)
     }
 }
 
@@ -107,11 +121,22 @@ impl SocketAddrExt for std::net::SocketAddr {
         }
     }
 
-    fn addr_to_string(&self) -> String {
-        self.ip().to_string()
+    fn from_ip_addr(addr: std::net::IpAddr, port: u16) -> Option<Self> {
+        Some((addr, port).into())
     }
 
-    fn as_uri_buf(&self, scheme: &str) -> UriBuf {
-        UriBuf::from_scheme_host_port(scheme, self.ip().to_string(), Some(self.port()))
+    fn addr_to_string(&self) -> String {
+        match self {
+            std::net::SocketAddr::V6(v6) if v6.scope_id() != 0 => {
+                format!("{}%{}", v6.ip(), v6.scope_id())
+            }
+            // IPv4 addresses are mapped into IPv6 when talking to them from
+            // an IPv6 socket, but they should still look like IPv4 addresses.
+            std::net::SocketAddr::V6(v6) => match v6.ip().to_ipv4_mapped() {
+                Some(v4) => v4.to_string(),
+                None => v6.ip().to_string(),
+            },
+            std::net::SocketAddr::V4(v4) => v4.ip().to_string(),
+        }
     }
 }
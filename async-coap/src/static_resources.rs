@@ -0,0 +1,575 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+use crate::option::RequestOptions;
+use std::collections::BTreeMap;
+
+#[cfg(feature = "std")]
+use std::path::{Path, PathBuf};
+
+/// The path of the resource listing the static resources, relative to the root.
+const WELL_KNOWN_CORE: &str = ".well-known/core";
+
+/// The number of bytes to leave free for the `Block2` and `Size2` options when fitting a
+/// block into the capacity of a response.
+const BLOCK_OPTIONS_RESERVE: usize = 4 + 5;
+
+/// A resource served by [`StaticResources`].
+#[derive(Debug, Clone)]
+struct StaticResource {
+    content: Vec<u8>,
+    content_format: ContentFormat,
+    etag: ETag,
+}
+
+impl StaticResource {
+    fn new(content: Vec<u8>, content_format: ContentFormat) -> StaticResource {
+        let etag = ETag::from_hash(&content);
+        StaticResource {
+            content,
+            content_format,
+            etag,
+        }
+    }
+}
+
+/// Serves a static tree of resources, like firmware manifests and configuration
+/// documents, from memory or (with the `std` feature) from a directory.
+///
+/// Only `GET` requests are allowed. Each resource has a content format and an ETag, so:
+///
+/// * Requests with an `ETag` option matching the resource are answered with `2.03 Valid`.
+/// * Requests with an `Accept` option for another content format are answered with
+///   `4.06 Not Acceptable`.
+/// * Resources which don't fit into a single response are served using
+///   [Block2 transfers][IETF-RFC7959], without keeping any state between requests.
+///
+/// Unless disabled with [`StaticResources::with_well_known_core`], the resources in
+/// memory are listed at `/.well-known/core` ([IETF-RFC6690]).
+///
+/// ```
+/// # use async_coap::prelude::*;
+/// # use async_coap::datagram::*;
+/// # use async_coap::{Error, RespondableInboundContext, StaticResources};
+/// # let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+/// let resources = StaticResources::new()
+///     .with_resource("fw/manifest.json", r#"{"version":"1.2.0"}"#, ContentFormat::APPLICATION_JSON)
+///     .with_resource("config", vec![0xA0], ContentFormat::APPLICATION_CBOR);
+///
+/// let handler = |context: &DatagramRespondableInboundContext<_>| -> Result<(), Error> {
+///     if resources.handle_request(context)? {
+///         return Ok(());
+///     }
+///
+///     // Handle other requests...
+///     context.respond_not_found()
+/// };
+/// # let _ = local_endpoint.receive_loop(handler);
+/// ```
+///
+/// [IETF-RFC7959]: https://tools.ietf.org/html/rfc7959
+/// [IETF-RFC6690]: https://tools.ietf.org/html/rfc6690
+#[derive(Debug, Clone)]
+pub struct StaticResources {
+    resources: BTreeMap<Vec<String>, StaticResource>,
+    well_known_core: bool,
+
+    #[cfg(feature = "std")]
+    directory: Option<PathBuf>,
+}
+
+impl Default for StaticResources {
+    fn default() -> Self {
+        StaticResources {
+            resources: BTreeMap::new(),
+            well_known_core: true,
+
+            #[cfg(feature = "std")]
+            directory: None,
+        }
+    }
+}
+
+impl StaticResources {
+    /// Creates a new, empty set of static resources.
+    pub fn new() -> StaticResources {
+        Default::default()
+    }
+
+    /// Adds a resource at `path` with the given content, replacing any resource
+    /// previously added at the same path.
+    ///
+    /// The path is relative to the root of the endpoint, and consists of unescaped path
+    /// segments separated by slashes. The ETag of the resource is derived from `content`.
+    pub fn with_resource<S, C>(
+        self,
+        path: S,
+        content: C,
+        content_format: ContentFormat,
+    ) -> StaticResources
+    where
+        S: AsRef<str>,
+        C: Into<Vec<u8>>,
+    {
+        let resource = StaticResource::new(content.into(), content_format);
+        self.insert(path.as_ref(), resource)
+    }
+
+    /// Like [`StaticResources::with_resource`], but uses `etag` as the ETag of the
+    /// resource rather than deriving it from `content`.
+    pub fn with_resource_etag<S, C>(
+        self,
+        path: S,
+        content: C,
+        content_format: ContentFormat,
+        etag: ETag,
+    ) -> StaticResources
+    where
+        S: AsRef<str>,
+        C: Into<Vec<u8>>,
+    {
+        let resource = StaticResource {
+            content: content.into(),
+            content_format,
+            etag,
+        };
+        self.insert(path.as_ref(), resource)
+    }
+
+    /// Sets whether the resources in memory are listed at `/.well-known/core`. This is
+    /// enabled by default.
+    pub fn with_well_known_core(mut self, enabled: bool) -> StaticResources {
+        self.well_known_core = enabled;
+        self
+    }
+
+    /// Serves the files in `directory` (and its subdirectories) for requests which don't
+    /// match a resource in memory.
+    ///
+    /// The request path is resolved using [`RelRef::resolved_within_root`], so requests
+    /// can't refer to files outside of `directory`. Files are read for every request, and
+    /// their content format is guessed from their extension. They aren't listed at
+    /// `/.well-known/core`.
+    #[cfg(feature = "std")]
+    pub fn with_directory<P: Into<PathBuf>>(mut self, directory: P) -> StaticResources {
+        self.directory = Some(directory.into());
+        self
+    }
+
+    /// Returns true if there is a resource in memory at `path`.
+    pub fn contains(&self, path: &str) -> bool {
+        self.resources.contains_key(&split_path(path))
+    }
+
+    /// Returns the number of resources in memory.
+    pub fn len(&self) -> usize {
+        self.resources.len()
+    }
+
+    /// Returns true if there are no resources in memory.
+    pub fn is_empty(&self) -> bool {
+        self.resources.is_empty()
+    }
+
+    fn insert(mut self, path: &str, resource: StaticResource) -> StaticResources {
+        self.resources.insert(split_path(path), resource);
+        self
+    }
+
+    /// Writes links to the resources in memory to `write`, for including them in a
+    /// `/.well-known/core` resource that is served by another handler.
+    pub fn write_links<T: core::fmt::Write + ?Sized>(
+        &self,
+        write: &mut LinkFormatWrite<'_, T>,
+    ) -> Result<(), Error> {
+        for (path, resource) in self.resources.iter() {
+            let rel_ref = RequestOptions {
+                uri_path: path.clone(),
+                ..Default::default()
+            }
+            .rel_ref();
+            let link = RelRefBuf::from_string(format!("/{}", rel_ref))
+                .map_err(|_| Error::InvalidArgument)?;
+
+            write
+                .link(&link)
+                .attr_u16(LINK_ATTR_CONTENT_FORMAT, resource.content_format.0)
+                .attr_u32(
+                    LINK_ATTR_MAXIMUM_SIZE_ESTIMATE,
+                    resource.content.len() as u32,
+                )
+                .finish()?;
+        }
+
+        Ok(())
+    }
+
+    /// Responds to the inbound request if it is for one of these resources, returning
+    /// true if the request was handled.
+    ///
+    /// This method is intended to be called from the handler passed to
+    /// [`LocalEndpoint::receive`]. Requests for other paths are left for the caller to
+    /// handle.
+    pub fn handle_request<T: RespondableInboundContext>(&self, context: &T) -> Result<bool, Error> {
+        let msg = context.message();
+
+        let options = match RequestOptions::parse(msg.options()) {
+            Ok(options) => options,
+            Err(_) => return Ok(false),
+        };
+
+        let listing;
+        let resource = if let Some(resource) = self.resources.get(&options.uri_path) {
+            resource
+        } else if self.well_known_core && options.uri_path == split_path(WELL_KNOWN_CORE) {
+            let mut content = String::new();
+            self.write_links(&mut LinkFormatWrite::new(&mut content))?;
+            listing = StaticResource::new(content.into(), ContentFormat::APPLICATION_LINK_FORMAT);
+            &listing
+        } else {
+            #[cfg(feature = "std")]
+            {
+                match self.read_file(&options) {
+                    Some(file) => {
+                        listing = file;
+                        &listing
+                    }
+                    None => return Ok(false),
+                }
+            }
+
+            #[cfg(not(feature = "std"))]
+            return Ok(false);
+        };
+
+        if msg.msg_code() != MsgCode::MethodGet {
+            context.respond_method_not_allowed(&[MsgCode::MethodGet])?;
+        } else if matches!(options.accept, Some(accept) if accept != resource.content_format) {
+            context.respond_error(MsgCode::ClientErrorNotAcceptable, "")?;
+        } else {
+            respond_with_resource(context, resource, options.block2)?;
+        }
+
+        Ok(true)
+    }
+
+    /// Reads the file that the request refers to from the directory, if there is one.
+    #[cfg(feature = "std")]
+    fn read_file(&self, options: &RequestOptions) -> Option<StaticResource> {
+        let directory = self.directory.as_ref()?;
+        let rel_ref = rel_ref!("")
+            .resolved_within_root(options.rel_ref().path_as_rel_ref())
+            .ok()?;
+
+        if rel_ref.is_empty() || rel_ref.has_trailing_slash() {
+            return None;
+        }
+
+        let mut path = directory.clone();
+
+        for segment in rel_ref.path_segments() {
+            // Segments must not be interpreted as more than one path component.
+            if segment.contains(&['/', '\\', '\0'][..]) {
+                return None;
+            }
+            path.push(segment.as_ref());
+        }
+
+        if !path.is_file() {
+            return None;
+        }
+
+        let content = std::fs::read(&path).ok()?;
+
+        Some(StaticResource::new(content, content_format_for_path(&path)))
+    }
+}
+
+/// Splits `path` into its segments, ignoring any leading slash.
+fn split_path(path: &str) -> Vec<String> {
+    let path = path.strip_prefix('/').unwrap_or(path);
+
+    if path.is_empty() {
+        return Vec::new();
+    }
+
+    path.split('/').map(str::to_string).collect()
+}
+
+/// Guesses the content format of a file from its extension.
+#[cfg(feature = "std")]
+fn content_format_for_path(path: &Path) -> ContentFormat {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("txt") => ContentFormat::TEXT_PLAIN_UTF8,
+        Some("json") => ContentFormat::APPLICATION_JSON,
+        Some("cbor") => ContentFormat::APPLICATION_CBOR,
+        Some("xml") => ContentFormat::APPLICATION_XML,
+        Some("wlnk") => ContentFormat::APPLICATION_LINK_FORMAT,
+        _ => ContentFormat::APPLICATION_OCTET_STREAM,
+    }
+}
+
+/// Responds to `context` with `resource`, or with the block of it that is asked for by
+/// `block2`.
+fn respond_with_resource<T: RespondableInboundContext>(
+    context: &T,
+    resource: &StaticResource,
+    block2: Option<BlockInfo>,
+) -> Result<(), Error> {
+    let content = &resource.content[..];
+    let offset = block2.map(|block| block.offset()).unwrap_or(0);
+
+    if offset != 0 && offset >= content.len() {
+        return context.respond_error(MsgCode::ClientErrorBadOption, "");
+    }
+
+    context.respond_with_etag(
+        |msg_out| {
+            msg_out.set_msg_code(MsgCode::SuccessContent);
+            msg_out.insert_option(option::CONTENT_FORMAT, resource.content_format)?;
+
+            let max_len = 1 << (BlockInfo::SZX_MAX as usize + 4);
+            if block2.is_none() && content.len() <= max_len.min(msg_out.max_payload_len()) {
+                return msg_out.append_payload_bytes(content);
+            }
+
+            let capacity = msg_out
+                .max_payload_len()
+                .saturating_sub(BLOCK_OPTIONS_RESERVE);
+            let mut szx = block2
+                .map(|block| block.szx())
+                .unwrap_or(BlockInfo::SZX_MAX);
+            while szx > 0 && 1 << (szx as usize + 4) > capacity {
+                szx -= 1;
+            }
+
+            let len = (content.len() - offset).min(1 << (szx as usize + 4));
+            let more = offset + len < content.len();
+            let block = BlockInfo::new((offset >> (szx as usize + 4)) as u32, more, szx)
+                .ok_or(Error::OutOfSpace)?;
+
+            msg_out.insert_option(option::BLOCK2, block)?;
+            if offset == 0 {
+                msg_out.insert_option(option::SIZE2, content.len() as u32)?;
+            }
+            msg_out.append_payload_bytes(&content[offset..offset + len])
+        },
+        resource.etag,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datagram::{
+        DatagramInboundContext, DatagramLocalEndpoint, DatagramRespondableInboundContext,
+        LoopbackSocket, LoopbackSocketAddr,
+    };
+    use futures::executor::block_on;
+    use futures::future::{select, Either};
+
+    type Context = DatagramRespondableInboundContext<LoopbackSocketAddr>;
+
+    fn send<SD, R>(resources: &StaticResources, send_desc: SD) -> R
+    where
+        SD: SendDesc<DatagramInboundContext<LoopbackSocketAddr>, R> + Send,
+        R: Send,
+    {
+        let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+        let future = local_endpoint.send(LoopbackSocketAddr::Unicast, send_desc);
+        let future_receive = local_endpoint.receive_loop(|context: &Context| {
+            if !resources.handle_request(context)? {
+                context.respond_not_found()?;
+            }
+            Ok(())
+        });
+
+        let (ret, _) = match block_on(select(future, future_receive)) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left(ret) => ret,
+        };
+
+        ret.unwrap()
+    }
+
+    fn resources() -> StaticResources {
+        StaticResources::new()
+            .with_resource("config", "a=1", ContentFormat::TEXT_PLAIN_UTF8)
+            .with_resource(
+                "/fw/image",
+                vec![0x5Au8; 3000],
+                ContentFormat::APPLICATION_OCTET_STREAM,
+            )
+    }
+
+    #[test]
+    fn get() {
+        let resources = resources();
+        assert_eq!(2, resources.len());
+        assert!(resources.contains("fw/image"));
+
+        let response = send(
+            &resources,
+            CoapRequest::get()
+                .uri_host_path(None, rel_ref!("config"))
+                .emit_any_response(),
+        );
+        assert_eq!(MsgCode::SuccessContent, response.msg_code());
+        assert_eq!(Some("a=1"), response.payload_as_str());
+        assert_eq!(None, response.block2());
+
+        let etag = ETag::from_hash(b"a=1");
+        assert_eq!(
+            Some(Ok(etag)),
+            response.options().find_next_of(option::ETAG)
+        );
+
+        // A matching ETag is validated.
+        let response = send(
+            &resources,
+            CoapRequest::get()
+                .uri_host_path(None, rel_ref!("config"))
+                .add_option(option::ETAG, etag)
+                .emit_any_response(),
+        );
+        assert_eq!(MsgCode::SuccessValid, response.msg_code());
+        assert!(response.payload().is_empty());
+
+        let response = send(
+            &resources,
+            CoapRequest::get()
+                .uri_host_path(None, rel_ref!("config"))
+                .accept(ContentFormat::APPLICATION_JSON)
+                .emit_any_response(),
+        );
+        assert_eq!(MsgCode::ClientErrorNotAcceptable, response.msg_code());
+
+        let response = send(
+            &resources,
+            CoapRequest::post()
+                .uri_host_path(None, rel_ref!("config"))
+                .emit_any_response(),
+        );
+        assert_eq!(MsgCode::ClientErrorMethodNotAllowed, response.msg_code());
+
+        let response = send(
+            &resources,
+            CoapRequest::get()
+                .uri_host_path(None, rel_ref!("missing"))
+                .emit_any_response(),
+        );
+        assert_eq!(MsgCode::ClientErrorNotFound, response.msg_code());
+    }
+
+    #[test]
+    fn get_block2() {
+        let resources = resources();
+
+        let response = send(
+            &resources,
+            CoapRequest::get()
+                .uri_host_path(None, rel_ref!("fw/image"))
+                .emit_any_response(),
+        );
+        assert_eq!(BlockInfo::new(0, true, 6), response.block2());
+        assert_eq!(
+            Some(Ok(3000)),
+            response.options().find_next_of(option::SIZE2)
+        );
+
+        let response = send(
+            &resources,
+            CoapRequest::get()
+                .uri_host_path(None, rel_ref!("fw/image"))
+                .block2(None)
+                .emit_successful_collected_response(),
+        );
+        assert_eq!(&[0x5Au8; 3000][..], response.payload());
+
+        let response = send(
+            &resources,
+            CoapRequest::get()
+                .uri_host_path(None, rel_ref!("fw/image"))
+                .add_option(option::BLOCK2, BlockInfo::new(3, false, 6).unwrap())
+                .emit_any_response(),
+        );
+        assert_eq!(MsgCode::ClientErrorBadOption, response.msg_code());
+    }
+
+    #[test]
+    fn well_known_core() {
+        let response = send(
+            &resources(),
+            CoapRequest::get()
+                .uri_host_path(None, rel_ref!(".well-known/core"))
+                .emit_any_response(),
+        );
+        assert_eq!(
+            Some("</config>;ct=0;sz=3,</fw/image>;ct=42;sz=3000"),
+            response.payload_as_str()
+        );
+
+        let response = send(
+            &resources().with_well_known_core(false),
+            CoapRequest::get()
+                .uri_host_path(None, rel_ref!(".well-known/core"))
+                .emit_any_response(),
+        );
+        assert_eq!(MsgCode::ClientErrorNotFound, response.msg_code());
+    }
+
+    #[test]
+    fn directory() {
+        let directory = std::env::temp_dir().join(format!(
+            "async-coap-static-resources-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(directory.join("docs")).unwrap();
+        std::fs::write(directory.join("docs/readme.txt"), "hello").unwrap();
+
+        let resources = StaticResources::new().with_directory(&directory);
+        let get = |path: &'static RelRef| {
+            send(
+                &resources,
+                CoapRequest::get()
+                    .uri_host_path(None, path)
+                    .emit_any_response(),
+            )
+        };
+
+        let response = get(rel_ref!("docs/readme.txt"));
+        assert_eq!(Some("hello"), response.payload_as_str());
+        assert_eq!(
+            Some(Ok(ContentFormat::TEXT_PLAIN_UTF8)),
+            response.options().find_next_of(option::CONTENT_FORMAT)
+        );
+
+        assert_eq!(
+            MsgCode::ClientErrorNotFound,
+            get(rel_ref!("docs")).msg_code()
+        );
+        assert_eq!(
+            MsgCode::ClientErrorNotFound,
+            get(rel_ref!("docs/%2E%2E/%2E%2E/etc/passwd")).msg_code()
+        );
+        assert_eq!(
+            MsgCode::ClientErrorNotFound,
+            get(rel_ref!("docs%2Freadme.txt")).msg_code()
+        );
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,143 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+use futures::task::{Context, Poll};
+use futures_timer::Delay;
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::ops::Deref;
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+
+/// A naive wrapper around [`std::net::TcpStream`] that implements [`AsyncRead`] and
+/// [`AsyncWrite`], for use with [`StreamLocalEndpoint`].
+///
+/// This is the stream counterpart of [`AllowStdUdpSocket`], and has the same drawbacks:
+/// the underlying stream is put into non-blocking mode, and whenever it isn't ready, a
+/// `futures_timer::Delay` is used to try again after a short interval. A stream type
+/// backed by a real event loop should be used instead where possible.
+///
+/// [`AllowStdUdpSocket`]: crate::datagram::AllowStdUdpSocket
+#[derive(Debug)]
+pub struct AllowStdTcpStream {
+    stream: TcpStream,
+    delay: Option<Delay>,
+}
+
+impl AllowStdTcpStream {
+    /// The interval between polling attempts.
+    const ASYNC_POLL_INTERVAL: Duration = Duration::from_millis(30);
+
+    /// Upgrades the given [`std::net::TcpStream`] to an instance of [`AllowStdTcpStream`],
+    /// putting it into non-blocking mode.
+    pub fn from_std(stream: TcpStream) -> std::io::Result<AllowStdTcpStream> {
+        stream.set_nonblocking(true)?;
+        Ok(AllowStdTcpStream {
+            stream,
+            delay: None,
+        })
+    }
+
+    /// Analog of [`std::net::TcpStream::connect`] for [`AllowStdTcpStream`].
+    ///
+    /// Note that this blocks until the connection has been established.
+    pub fn connect<A>(addr: A) -> std::io::Result<AllowStdTcpStream>
+    where
+        A: std::net::ToSocketAddrs,
+    {
+        AllowStdTcpStream::from_std(TcpStream::connect(addr)?)
+    }
+
+    /// Converts the outcome of a non-blocking operation into a `Poll`, scheduling
+    /// another attempt if the operation would have blocked.
+    fn poll_io<T>(
+        &mut self,
+        cx: &mut Context<'_>,
+        result: std::io::Result<T>,
+    ) -> Poll<std::io::Result<T>> {
+        match result {
+            Err(ref e)
+                if e.kind() == std::io::ErrorKind::WouldBlock
+                    || e.kind() == std::io::ErrorKind::Interrupted =>
+            {
+                let delay = self
+                    .delay
+                    .get_or_insert_with(|| Delay::new(Self::ASYNC_POLL_INTERVAL));
+                delay.reset(Instant::now() + Self::ASYNC_POLL_INTERVAL);
+                let _ = Pin::new(delay).poll(cx);
+                Poll::Pending
+            }
+            result => Poll::Ready(result),
+        }
+    }
+}
+
+impl AsyncRead for AllowStdTcpStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let this = self.get_mut();
+        let result = this.stream.read(buf);
+        this.poll_io(cx, result)
+    }
+}
+
+impl AsyncWrite for AllowStdTcpStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let this = self.get_mut();
+        let result = this.stream.write(buf);
+        this.poll_io(cx, result)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        let result = this.stream.flush();
+        this.poll_io(cx, result)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(self.stream.shutdown(Shutdown::Write))
+    }
+}
+
+impl Deref for AllowStdTcpStream {
+    type Target = TcpStream;
+
+    fn deref(&self) -> &Self::Target {
+        &self.stream
+    }
+}
+
+/// A [`StreamConnector`] which establishes TCP connections using [`AllowStdTcpStream`].
+///
+/// Since [`AllowStdTcpStream::connect`] blocks, so does polling the futures returned by
+/// this connector.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct AllowStdTcpConnector;
+
+impl StreamConnector for AllowStdTcpConnector {
+    type Stream = AllowStdTcpStream;
+
+    fn connect(&self, addr: SocketAddr) -> BoxFuture<'static, std::io::Result<Self::Stream>> {
+        futures::future::ready(AllowStdTcpStream::connect(addr)).boxed()
+    }
+}
@@ -0,0 +1,319 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+#[cfg(feature = "websocket")]
+use crate::message::WebSocketFraming;
+use crate::message::{OwnedImmutableMessage, StreamFraming, VecMessageEncoder};
+use futures::channel::mpsc::UnboundedSender;
+use futures::io::{AsyncReadExt, AsyncWriteExt, ReadHalf, WriteHalf};
+use futures::task::{Context, Poll};
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// The Max-Message-Size option of CSMs, as described in [IETF-RFC8323 Section 5.3.1].
+///
+/// [IETF-RFC8323 Section 5.3.1]: https://tools.ietf.org/html/rfc8323#section-5.3.1
+pub(super) const OPTION_MAX_MESSAGE_SIZE: OptionNumber = OptionNumber(2);
+
+/// The size of messages which peers are assumed to accept until their CSM says otherwise.
+const DEFAULT_MAX_MESSAGE_SIZE: usize = 1152;
+
+/// Something which was received on a [`Connection`].
+#[derive(Debug)]
+pub(super) enum Inbound {
+    /// A CoAP message.
+    Message(OwnedImmutableMessage),
+
+    /// A WebSocket ping frame with the given payload, which must be answered with a pong.
+    #[cfg(feature = "websocket")]
+    WebSocketPing(Vec<u8>),
+
+    /// The connection was closed, either by the peer or because it failed.
+    Closed,
+}
+
+struct Reader<S> {
+    stream: ReadHalf<S>,
+    buffer: Vec<u8>,
+}
+
+/// A connection to a peer, which splits the received bytes into messages and writes the
+/// messages being sent.
+///
+/// Responses are routed to the exchanges which are waiting for them using their tokens.
+pub(super) struct Connection<S> {
+    peer: SocketAddr,
+    transport: StreamTransport,
+    is_client: bool,
+    reader: Mutex<Reader<S>>,
+    writer: futures::lock::Mutex<WriteHalf<S>>,
+    exchanges: Mutex<HashMap<MsgToken, UnboundedSender<OwnedImmutableMessage>>>,
+    next_token: AtomicU32,
+    peer_max_message_size: AtomicUsize,
+    closed: AtomicBool,
+}
+
+impl<S> core::fmt::Debug for Connection<S> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Connection")
+            .field("peer", &self.peer)
+            .field("transport", &self.transport)
+            .field("is_client", &self.is_client)
+            .field("closed", &self.closed)
+            .finish()
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Connection<S> {
+    /// Creates a connection for `stream`, which has already been upgraded to a WebSocket
+    /// if `transport` is `StreamTransport::WebSocket`.
+    pub(super) fn new(
+        stream: S,
+        peer: SocketAddr,
+        transport: StreamTransport,
+        is_client: bool,
+    ) -> Connection<S> {
+        let (reader, writer) = stream.split();
+
+        Connection {
+            peer,
+            transport,
+            is_client,
+            reader: Mutex::new(Reader {
+                stream: reader,
+                buffer: Vec::new(),
+            }),
+            writer: futures::lock::Mutex::new(writer),
+            exchanges: Mutex::new(HashMap::new()),
+            next_token: AtomicU32::new(rand::random()),
+            peer_max_message_size: AtomicUsize::new(DEFAULT_MAX_MESSAGE_SIZE),
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    pub(super) fn peer(&self) -> SocketAddr {
+        self.peer
+    }
+
+    pub(super) fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    /// Marks this connection as closed, ending all exchanges which are waiting for
+    /// responses on it.
+    pub(super) fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+        self.exchanges.lock().expect("Lock failed").clear();
+    }
+
+    pub(super) fn peer_max_message_size(&self) -> usize {
+        self.peer_max_message_size.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn set_peer_max_message_size(&self, size: usize) {
+        self.peer_max_message_size.store(size, Ordering::Relaxed);
+    }
+
+    /// Returns a token which isn't used by any other exchange on this connection.
+    pub(super) fn next_token(&self) -> MsgToken {
+        MsgToken::from(self.next_token.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Registers an exchange, so that messages with the token `msg_token` are sent to
+    /// `sender`.
+    pub(super) fn register(
+        &self,
+        msg_token: MsgToken,
+        sender: UnboundedSender<OwnedImmutableMessage>,
+    ) {
+        self.exchanges
+            .lock()
+            .expect("Lock failed")
+            .insert(msg_token, sender);
+    }
+
+    pub(super) fn unregister(&self, msg_token: MsgToken) {
+        self.exchanges
+            .lock()
+            .expect("Lock failed")
+            .remove(&msg_token);
+    }
+
+    /// Hands `message` to the exchange it belongs to, if there is one.
+    pub(super) fn dispatch(&self, message: OwnedImmutableMessage) {
+        let msg_token = message.msg_token();
+
+        match self.exchanges.lock().expect("Lock failed").get(&msg_token) {
+            Some(sender) => {
+                let _ = sender.unbounded_send(message);
+            }
+            None => debug!(
+                "{}: Ignoring message with unknown token {}",
+                self.peer, msg_token
+            ),
+        }
+    }
+
+    /// Sends our CSM, which must be the first message sent on every connection.
+    pub(super) async fn send_csm(&self) -> Result<(), Error> {
+        let mut msg = VecMessageEncoder::new();
+        msg.set_msg_code(MsgCode::SignalCsm);
+        msg.insert_option_with_u32(OPTION_MAX_MESSAGE_SIZE, STREAM_MAX_MESSAGE_SIZE as u32)?;
+        self.send_message(&msg).await
+    }
+
+    /// Sends `msg`, which is encoded for datagrams, using the framing of this connection.
+    ///
+    /// Fails with [`Error::MessageTooLarge`] if the peer doesn't accept messages of that
+    /// size.
+    pub(super) async fn send_message(&self, msg: &VecMessageEncoder) -> Result<(), Error> {
+        let framed = match self.transport {
+            StreamTransport::Tcp => msg.to_framed_vec(&StreamFraming)?,
+            #[cfg(feature = "websocket")]
+            StreamTransport::WebSocket => msg.to_framed_vec(&WebSocketFraming)?,
+        };
+
+        if framed.len() > self.peer_max_message_size() {
+            return Err(Error::MessageTooLarge);
+        }
+
+        match self.transport {
+            StreamTransport::Tcp => self.write(&framed).await,
+            #[cfg(feature = "websocket")]
+            StreamTransport::WebSocket => {
+                self.write_websocket_frame(websocket::OPCODE_BINARY, &framed)
+                    .await
+            }
+        }
+    }
+
+    /// Sends a WebSocket frame. Frames sent by clients are masked.
+    #[cfg(feature = "websocket")]
+    pub(super) async fn write_websocket_frame(
+        &self,
+        opcode: u8,
+        payload: &[u8],
+    ) -> Result<(), Error> {
+        self.write(&websocket::encode_frame(opcode, payload, self.is_client))
+            .await
+    }
+
+    async fn write(&self, bytes: &[u8]) -> Result<(), Error> {
+        if self.is_closed() {
+            return Err(Error::IOError);
+        }
+
+        let mut writer = self.writer.lock().await;
+
+        let ret = match writer.write_all(bytes).await {
+            Ok(()) => writer.flush().await,
+            Err(e) => Err(e),
+        };
+
+        ret.map_err(|e| {
+            debug!("{}: Write failed: {:?}", self.peer, e);
+            self.close();
+            Error::IOError
+        })
+    }
+
+    /// Polls for the next message or event received on this connection.
+    pub(super) fn poll_inbound(&self, cx: &mut Context<'_>) -> Poll<Inbound> {
+        if self.is_closed() {
+            return Poll::Ready(Inbound::Closed);
+        }
+
+        let mut reader = self.reader.lock().expect("Lock failed");
+        let Reader { stream, buffer } = &mut *reader;
+
+        loop {
+            match self.take_inbound(buffer) {
+                Ok(Some(Inbound::Closed)) => break,
+                Ok(Some(inbound)) => return Poll::Ready(inbound),
+                Ok(None) => (),
+                Err(e) => {
+                    debug!("{}: Closing connection: {:?}", self.peer, e);
+                    break;
+                }
+            }
+
+            let mut chunk = [0u8; 4096];
+            match Pin::new(&mut *stream).poll_read(cx, &mut chunk) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Ok(0)) => break,
+                Poll::Ready(Ok(len)) => buffer.extend_from_slice(&chunk[..len]),
+                Poll::Ready(Err(e)) => {
+                    debug!("{}: Read failed: {:?}", self.peer, e);
+                    break;
+                }
+            }
+        }
+
+        self.close();
+        Poll::Ready(Inbound::Closed)
+    }
+
+    /// Removes the next complete message or event from the start of `buffer`. Messages
+    /// which can't be parsed are skipped.
+    fn take_inbound(&self, buffer: &mut Vec<u8>) -> Result<Option<Inbound>, Error> {
+        loop {
+            let message = match self.transport {
+                StreamTransport::Tcp => {
+                    let len = match StreamFraming::frame_len(buffer) {
+                        Some(len) if len > STREAM_MAX_MESSAGE_SIZE => {
+                            return Err(Error::MessageTooLarge)
+                        }
+                        Some(len) if len <= buffer.len() => len,
+                        _ => return Ok(None),
+                    };
+
+                    let frame = buffer.drain(..len).collect();
+                    OwnedImmutableMessage::with_framing(frame, &StreamFraming)
+                }
+
+                // Only frames sent by clients are masked.
+                #[cfg(feature = "websocket")]
+                StreamTransport::WebSocket => {
+                    let max_len = STREAM_MAX_MESSAGE_SIZE;
+                    let (frame, len) =
+                        match websocket::decode_frame(buffer, max_len, !self.is_client)? {
+                            Some(x) => x,
+                            None => return Ok(None),
+                        };
+                    buffer.drain(..len);
+
+                    match frame.opcode {
+                        websocket::OPCODE_BINARY => {
+                            OwnedImmutableMessage::with_framing(frame.payload, &WebSocketFraming)
+                        }
+                        websocket::OPCODE_PING => {
+                            return Ok(Some(Inbound::WebSocketPing(frame.payload)))
+                        }
+                        websocket::OPCODE_CLOSE => return Ok(Some(Inbound::Closed)),
+                        _ => continue,
+                    }
+                }
+            };
+
+            match message {
+                Ok(message) => return Ok(Some(Inbound::Message(message))),
+                Err(e) => debug!("{}: Skipping unparsable message: {:?}", self.peer, e),
+            }
+        }
+    }
+}
@@ -0,0 +1,136 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+use crate::message::{OwnedImmutableMessage, VecMessageEncoder};
+use std::cell::Cell;
+
+/// Concrete instance of [`LocalEndpoint::InboundContext`] for [`StreamLocalEndpoint`].
+#[derive(Debug)]
+pub struct StreamInboundContext {
+    message: OwnedImmutableMessage,
+    remote: SocketAddr,
+}
+
+impl StreamInboundContext {
+    pub(super) fn new(message: OwnedImmutableMessage, remote: SocketAddr) -> StreamInboundContext {
+        StreamInboundContext { message, remote }
+    }
+}
+
+impl InboundContext for StreamInboundContext {
+    type SocketAddr = SocketAddr;
+
+    fn remote_socket_addr(&self) -> Self::SocketAddr {
+        self.remote
+    }
+
+    /// Always false, since messages are never retransmitted over reliable transports.
+    fn is_dupe(&self) -> bool {
+        false
+    }
+
+    fn message(&self) -> &dyn MessageRead {
+        &self.message
+    }
+}
+
+/// Concrete instance of [`LocalEndpoint::RespondableInboundContext`] for
+/// [`StreamLocalEndpoint`].
+pub struct StreamRespondableInboundContext
+where
+    Self: Send,
+{
+    message: OwnedImmutableMessage,
+    message_out: Cell<Option<VecMessageEncoder>>,
+    remote: SocketAddr,
+    max_response_len: usize,
+}
+
+impl core::fmt::Debug for StreamRespondableInboundContext {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        f.debug_struct("StreamRespondableInboundContext")
+            .field("message", &self.message)
+            .field("message_out", &"")
+            .field("remote", &self.remote)
+            .field("max_response_len", &self.max_response_len)
+            .finish()
+    }
+}
+
+impl StreamRespondableInboundContext {
+    pub(super) fn new(
+        message: OwnedImmutableMessage,
+        remote: SocketAddr,
+        max_response_len: usize,
+    ) -> StreamRespondableInboundContext {
+        StreamRespondableInboundContext {
+            message,
+            message_out: Cell::new(None),
+            remote,
+            max_response_len,
+        }
+    }
+
+    pub(super) fn into_message_out(self) -> Option<VecMessageEncoder> {
+        self.message_out.take()
+    }
+}
+
+impl RespondableInboundContext for StreamRespondableInboundContext {
+    fn is_multicast(&self) -> bool {
+        false
+    }
+
+    fn is_fake(&self) -> bool {
+        false
+    }
+
+    /// Responds to this inbound request. Since messages have no type over reliable
+    /// transports, responses with the code `0.00 Empty` (like resets and empty
+    /// acknowledgements) aren't sent at all.
+    fn respond<F>(&self, msg_gen: F) -> Result<(), Error>
+    where
+        F: Fn(&mut dyn MessageWrite) -> Result<(), Error>,
+    {
+        let mut builder = VecMessageEncoder::new();
+
+        builder.set_max_len(self.max_response_len);
+        builder.set_msg_token(self.message().msg_token());
+
+        msg_gen(&mut builder)?;
+
+        self.message_out.replace(Some(builder));
+
+        Ok(())
+    }
+}
+
+impl InboundContext for StreamRespondableInboundContext {
+    type SocketAddr = SocketAddr;
+
+    fn remote_socket_addr(&self) -> Self::SocketAddr {
+        self.remote
+    }
+
+    /// Always false, since messages are never retransmitted over reliable transports.
+    fn is_dupe(&self) -> bool {
+        false
+    }
+
+    fn message(&self) -> &dyn MessageRead {
+        &self.message
+    }
+}
@@ -0,0 +1,685 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+use crate::message::{OwnedImmutableMessage, StandardMessageParser, VecMessageEncoder};
+use connection::OPTION_MAX_MESSAGE_SIZE;
+use futures::channel::mpsc::unbounded;
+use futures::future::{select, Either};
+use futures::prelude::*;
+use futures::task::{AtomicWaker, Context, Poll};
+use futures_timer::Delay;
+use std::collections::HashMap;
+use std::ops::Bound;
+use std::sync::{Arc, Mutex};
+
+/// A [`LocalEndpoint`] implementation for CoAP over TCP or WebSockets, as described in
+/// [IETF-RFC8323].
+///
+/// Connections to remote endpoints are established by a [`StreamConnector`] when the
+/// first request is sent to them, and are reused for all subsequent requests. To serve
+/// requests, connections accepted from a listening socket are handed to
+/// [`accept`](StreamLocalEndpoint::accept).
+///
+/// [IETF-RFC8323]: https://tools.ietf.org/html/rfc8323
+pub struct StreamLocalEndpoint<C: StreamConnector> {
+    inner: Arc<StreamLocalEndpointInner<C>>,
+}
+
+impl<C: StreamConnector> core::fmt::Debug for StreamLocalEndpoint<C> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("StreamLocalEndpoint")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+pub(crate) struct StreamLocalEndpointInner<C: StreamConnector> {
+    connector: C,
+    transport: StreamTransport,
+    connections: Mutex<HashMap<SocketAddr, Arc<Connection<C::Stream>>>>,
+    connecting: Mutex<HashMap<SocketAddr, Arc<futures::lock::Mutex<()>>>>,
+    new_connection: AtomicWaker,
+}
+
+impl<C: StreamConnector> core::fmt::Debug for StreamLocalEndpointInner<C> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("StreamLocalEndpointInner")
+            .field("transport", &self.transport)
+            .field("connections", &self.connections)
+            .finish()
+    }
+}
+
+/// Unregisters an exchange when the future sending its requests finishes or is dropped.
+struct Registration<S: AsyncRead + AsyncWrite + Unpin> {
+    connection: Arc<Connection<S>>,
+    msg_token: MsgToken,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Drop for Registration<S> {
+    fn drop(&mut self) {
+        self.connection.unregister(self.msg_token);
+    }
+}
+
+impl<C: StreamConnector> StreamLocalEndpointInner<C> {
+    pub(crate) fn scheme(&self) -> &'static str {
+        self.transport.scheme()
+    }
+
+    pub(crate) fn default_port(&self) -> u16 {
+        self.transport.default_port()
+    }
+
+    fn add_connection(&self, connection: Arc<Connection<C::Stream>>) {
+        self.connections
+            .lock()
+            .expect("Lock failed")
+            .insert(connection.peer(), connection);
+
+        // Make sure the receive future starts reading from the new connection.
+        self.new_connection.wake();
+    }
+
+    fn open_connection(&self, addr: SocketAddr) -> Option<Arc<Connection<C::Stream>>> {
+        self.connections
+            .lock()
+            .expect("Lock failed")
+            .get(&addr)
+            .filter(|connection| !connection.is_closed())
+            .cloned()
+    }
+
+    /// Returns the connection to `addr`, establishing it if there isn't one yet.
+    ///
+    /// `host` is used for the `Host` header of the WebSocket handshake.
+    async fn connection(
+        &self,
+        addr: SocketAddr,
+        host: Option<&str>,
+    ) -> Result<Arc<Connection<C::Stream>>, Error> {
+        if let Some(connection) = self.open_connection(addr) {
+            return Ok(connection);
+        }
+
+        // Only one connection to each peer is established at a time, so that concurrent
+        // requests to the same peer share it.
+        let pending = self
+            .connecting
+            .lock()
+            .expect("Lock failed")
+            .entry(addr)
+            .or_default()
+            .clone();
+        let _guard = pending.lock().await;
+
+        let ret = match self.open_connection(addr) {
+            Some(connection) => Ok(connection),
+            None => self.connect(addr, host).await,
+        };
+
+        let mut connecting = self.connecting.lock().expect("Lock failed");
+        if connecting.get(&addr).map(|x| Arc::ptr_eq(x, &pending)) == Some(true) {
+            connecting.remove(&addr);
+        }
+
+        ret
+    }
+
+    /// Establishes a new connection to `addr`.
+    #[cfg_attr(not(feature = "websocket"), allow(unused_mut, unused_variables))]
+    async fn connect(
+        &self,
+        addr: SocketAddr,
+        host: Option<&str>,
+    ) -> Result<Arc<Connection<C::Stream>>, Error> {
+        let mut stream = self.connector.connect(addr).await.map_err(|e| {
+            debug!("Unable to connect to {}: {:?}", addr, e);
+            Error::IOError
+        })?;
+
+        #[cfg(feature = "websocket")]
+        if self.transport == StreamTransport::WebSocket {
+            let host = match host {
+                Some(host) if addr.port() == self.default_port() => host.to_string(),
+                Some(host) => format!("{}:{}", host, addr.port()),
+                None => addr.to_string(),
+            };
+
+            websocket::client_handshake(&mut stream, &host).await?;
+        }
+
+        let connection = Arc::new(Connection::new(stream, addr, self.transport, true));
+        connection.send_csm().await?;
+        self.add_connection(connection.clone());
+
+        Ok(connection)
+    }
+
+    /// Sends the requests described by `send_desc` to `addr`, feeding the responses to
+    /// the handler of `send_desc` until it is done.
+    pub(crate) async fn send<R, SD>(
+        self: Arc<Self>,
+        addr: SocketAddr,
+        host: Option<String>,
+        mut send_desc: SD,
+    ) -> Result<R, Error>
+    where
+        SD: SendDesc<StreamInboundContext, R>,
+        R: Send,
+    {
+        let connection = self.connection(addr, host.as_deref()).await?;
+        let msg_token = connection.next_token();
+        let (sender, mut receiver) = unbounded();
+
+        connection.register(msg_token, sender);
+        let _registration = Registration {
+            connection: connection.clone(),
+            msg_token,
+        };
+
+        let mut modify: Option<ModifyRequest> = None;
+
+        loop {
+            let mut builder = VecMessageEncoder::new();
+            builder.set_msg_token(msg_token);
+            send_desc.write_options(&mut builder, &addr, Bound::Unbounded, Bound::Unbounded)?;
+            send_desc.write_payload(&mut builder, &addr)?;
+            if let Some(modify) = modify.as_ref() {
+                modify.apply(&mut builder)?;
+            }
+
+            connection.send_message(&builder).await?;
+
+            // Since the transport is reliable, there is nothing to retransmit: we only
+            // wait for responses until the send descriptor is done with them.
+            loop {
+                let timeout = Delay::new(send_desc.max_rtt());
+
+                let result = match select(receiver.next(), timeout).await {
+                    Either::Left((Some(message), _)) => {
+                        Ok(StreamInboundContext::new(message, addr))
+                    }
+                    Either::Left((None, _)) => Err(Error::IOError),
+                    Either::Right(_) => Err(Error::ResponseTimeout),
+                };
+
+                match send_desc.handler(result.as_ref().map_err(|e| *e))? {
+                    ResponseStatus::Done(x) => return Ok(x),
+                    ResponseStatus::SendNext => {
+                        modify = None;
+                        break;
+                    }
+                    ResponseStatus::SendNextWith(next) => {
+                        modify = Some(next);
+                        break;
+                    }
+                    ResponseStatus::Continue | ResponseStatus::Defer => {
+                        // No more responses will arrive once the connection is closed.
+                        if let Err(Error::IOError) = result {
+                            return Err(Error::IOError);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Polls every connection for the next message or event received on it.
+    fn poll_inbound(&self, cx: &mut Context<'_>) -> Poll<(Arc<Connection<C::Stream>>, Inbound)> {
+        self.new_connection.register(cx.waker());
+
+        let connections = self
+            .connections
+            .lock()
+            .expect("Lock failed")
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for connection in connections {
+            if let Poll::Ready(inbound) = connection.poll_inbound(cx) {
+                return Poll::Ready((connection, inbound));
+            }
+        }
+
+        Poll::Pending
+    }
+
+    fn remove_connection(&self, connection: &Arc<Connection<C::Stream>>) {
+        let mut connections = self.connections.lock().expect("Lock failed");
+
+        if connections
+            .get(&connection.peer())
+            .map(|x| Arc::ptr_eq(x, connection))
+            == Some(true)
+        {
+            connections.remove(&connection.peer());
+        }
+    }
+}
+
+impl<C: StreamConnector> StreamLocalEndpoint<C> {
+    /// Creates a new local endpoint which uses `connector` to establish connections for
+    /// `transport`.
+    pub fn new(connector: C, transport: StreamTransport) -> StreamLocalEndpoint<C> {
+        StreamLocalEndpoint {
+            inner: Arc::new(StreamLocalEndpointInner {
+                connector,
+                transport,
+                connections: Mutex::new(HashMap::new()),
+                connecting: Mutex::new(HashMap::new()),
+                new_connection: AtomicWaker::new(),
+            }),
+        }
+    }
+
+    /// Returns the transport used by this local endpoint.
+    pub fn transport(&self) -> StreamTransport {
+        self.inner.transport
+    }
+
+    /// Takes over a connection from `peer` which was accepted from a listening socket,
+    /// so that the requests received on it are passed to the receive handler.
+    ///
+    /// For WebSocket transports, this performs the server side of the WebSocket
+    /// handshake first, which fails with [`Error::ParseFailure`] if `stream` doesn't
+    /// start with a valid upgrade request for `/.well-known/coap`.
+    #[cfg_attr(not(feature = "websocket"), allow(unused_mut))]
+    pub async fn accept(&self, peer: SocketAddr, mut stream: C::Stream) -> Result<(), Error> {
+        #[cfg(feature = "websocket")]
+        if self.inner.transport == StreamTransport::WebSocket {
+            websocket::server_handshake(&mut stream).await?;
+        }
+
+        let connection = Arc::new(Connection::new(stream, peer, self.inner.transport, false));
+        connection.send_csm().await?;
+        self.inner.add_connection(connection);
+
+        Ok(())
+    }
+
+    /// Closes the connection to `peer`, if there is one, by sending a Release message.
+    ///
+    /// Requests which are still waiting for responses on the connection fail with
+    /// [`Error::IOError`]. A new connection is established the next time a request is
+    /// sent to `peer`.
+    pub async fn close(&self, peer: SocketAddr) {
+        let connection = self
+            .inner
+            .connections
+            .lock()
+            .expect("Lock failed")
+            .remove(&peer);
+
+        if let Some(connection) = connection {
+            let mut msg = VecMessageEncoder::new();
+            msg.set_msg_code(MsgCode::SignalRelease);
+            let _ = connection.send_message(&msg).await;
+            connection.close();
+        }
+    }
+
+    /// Handles a message which isn't a request. Returns false if the connection should be
+    /// closed.
+    async fn handle_message(
+        &self,
+        connection: &Connection<C::Stream>,
+        message: OwnedImmutableMessage,
+    ) -> bool {
+        match message.msg_code() {
+            MsgCode::SignalCsm => {
+                let max_message_size = message
+                    .options()
+                    .filter_map(Result::ok)
+                    .find(|(number, _)| *number == OPTION_MAX_MESSAGE_SIZE)
+                    .and_then(|(_, value)| try_decode_u32(value));
+
+                if let Some(size) = max_message_size {
+                    connection.set_peer_max_message_size(size as usize);
+                }
+            }
+            MsgCode::SignalPing => {
+                let mut pong = VecMessageEncoder::new();
+                pong.set_msg_code(MsgCode::SignalPong);
+                pong.set_msg_token(message.msg_token());
+
+                if connection.send_message(&pong).await.is_err() {
+                    return false;
+                }
+            }
+            MsgCode::SignalRelease | MsgCode::SignalAbort => return false,
+            MsgCode::Empty => (),
+            _ => connection.dispatch(message),
+        }
+
+        true
+    }
+}
+
+impl<C: StreamConnector> LocalEndpoint for StreamLocalEndpoint<C> {
+    type SocketAddr = SocketAddr;
+    type SocketError = std::io::Error;
+    type DefaultTransParams = StandardCoapConstants;
+    type LookupStream = futures::stream::Iter<std::vec::IntoIter<Self::SocketAddr>>;
+    type RespondableInboundContext = StreamRespondableInboundContext;
+    type InboundContext = StreamInboundContext;
+
+    type RemoteEndpoint = StreamRemoteEndpoint<C>;
+
+    fn scheme(&self) -> &'static str {
+        self.inner.scheme()
+    }
+
+    fn default_port(&self) -> u16 {
+        self.inner.default_port()
+    }
+
+    fn lookup(&self, hostname: &str, mut port: u16) -> Result<Self::LookupStream, Error> {
+        if port == 0 {
+            port = self.default_port();
+        }
+
+        match std::net::ToSocketAddrs::to_socket_addrs(&(hostname, port)) {
+            Ok(iter) => Ok(futures::stream::iter(iter.collect::<Vec<_>>())),
+            Err(_) => Err(Error::HostLookupFailure),
+        }
+    }
+
+    fn remote_endpoint<S, H, P>(&self, addr: S, host: Option<H>, path: P) -> Self::RemoteEndpoint
+    where
+        S: ToSocketAddrs<SocketAddr = Self::SocketAddr, Error = Self::SocketError>,
+        H: Into<String>,
+        P: Into<RelRefBuf>,
+    {
+        let addr = addr.to_socket_addrs().unwrap().next().unwrap();
+        StreamRemoteEndpoint::new(&self.inner, addr, host.map(|h| h.into()), path.into())
+    }
+
+    fn remote_endpoint_from_uri(&self, uri: &Uri) -> Result<Self::RemoteEndpoint, Error> {
+        if let Some(scheme) = uri.scheme() {
+            if scheme != self.scheme() {
+                return Err(Error::UnsupportedUriScheme);
+            }
+        }
+
+        let components = uri.components();
+        let host = components.host().ok_or(Error::HostNotFound)?;
+        let port = components.port().unwrap_or(0);
+
+        // IP address literals were already parsed along with the URI,
+        // so we can skip the lookup entirely. Zone identifiers still
+        // require a lookup.
+        let has_zone = match &host {
+            uri::UriHost::Ipv6 { zone, .. } => zone.is_some(),
+            _ => false,
+        };
+
+        if let Some(socket_addr) = host.ip_addr().filter(|_| !has_zone).and_then(|addr| {
+            let port = if port == 0 { self.default_port() } else { port };
+            Self::SocketAddr::from_ip_addr(addr, port)
+        }) {
+            return Ok(self.remote_endpoint(
+                socket_addr,
+                None::<String>,
+                uri.trim_fragment().rel(),
+            ));
+        }
+
+        let host = host.to_string();
+        let mut lookup_stream = self.lookup(&host, port)?;
+
+        if let Some(socket_addr) = lookup_stream
+            .next()
+            .now_or_never()
+            .expect("Lookup stream not ready")
+        {
+            Ok(self.remote_endpoint(socket_addr, Some(host), uri.trim_fragment().rel()))
+        } else {
+            Err(Error::HostNotFound)
+        }
+    }
+
+    fn send<'a, S, R, SD>(&'a self, dest: S, send_desc: SD) -> BoxFuture<'a, Result<R, Error>>
+    where
+        S: ToSocketAddrs<SocketAddr = Self::SocketAddr, Error = Self::SocketError> + 'a,
+        SD: SendDesc<Self::InboundContext, R> + 'a,
+        R: Send + 'a,
+    {
+        match dest.to_socket_addrs() {
+            Ok(mut iter) => match iter.next() {
+                Some(socket_addr) => self
+                    .inner
+                    .clone()
+                    .send(socket_addr, None, send_desc)
+                    .boxed(),
+                None => futures::future::ready(Err(Error::HostNotFound)).boxed(),
+            },
+            Err(_) => futures::future::ready(Err(Error::HostLookupFailure)).boxed(),
+        }
+    }
+
+    fn receive<'a, F>(&'a self, mut handler: F) -> BoxFuture<'a, Result<(), Error>>
+    where
+        F: FnMut(&Self::RespondableInboundContext) -> Result<(), Error> + 'a + Send,
+    {
+        async move {
+            loop {
+                let (connection, inbound) = future::poll_fn(|cx| self.inner.poll_inbound(cx)).await;
+
+                let message = match inbound {
+                    Inbound::Message(message) => message,
+                    #[cfg(feature = "websocket")]
+                    Inbound::WebSocketPing(payload) => {
+                        let _ = connection
+                            .write_websocket_frame(websocket::OPCODE_PONG, &payload)
+                            .await;
+                        continue;
+                    }
+                    Inbound::Closed => {
+                        debug!("{}: Connection closed", connection.peer());
+                        self.inner.remove_connection(&connection);
+                        return Ok(());
+                    }
+                };
+
+                if !message.msg_code().is_method() {
+                    if !self.handle_message(&connection, message).await {
+                        connection.close();
+                    }
+                    continue;
+                }
+
+                let context = StreamRespondableInboundContext::new(
+                    message,
+                    connection.peer(),
+                    connection.peer_max_message_size(),
+                );
+
+                handler(&context)?;
+
+                if let Some(msg_out) = context.into_message_out() {
+                    if StandardMessageParser::new(msg_out.as_bytes())?.msg_code() != MsgCode::Empty
+                    {
+                        if let Err(e) = connection.send_message(&msg_out).await {
+                            debug!("{}: Unable to send response: {:?}", connection.peer(), e);
+                        }
+                    }
+                }
+
+                return Ok(());
+            }
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use std::net::TcpListener;
+
+    /// Responds to `GET /test` with `len` bytes of payload, where `len` is given by the
+    /// query of the request.
+    fn handler(context: &StreamRespondableInboundContext) -> Result<(), Error> {
+        let options = crate::option::RequestOptions::parse(context.message().options())?;
+        let len = options
+            .rel_ref()
+            .raw_query()
+            .and_then(|query| query.parse().ok())
+            .unwrap_or(5);
+
+        context.respond(|msg_out| {
+            msg_out.set_msg_code(MsgCode::SuccessContent);
+            msg_out.append_payload_bytes(&vec![b'x'; len])
+        })
+    }
+
+    /// Starts a server on a new thread, which serves the first connection it accepts.
+    fn start_server(transport: StreamTransport) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("TCP bind failed");
+        let port = listener.local_addr().unwrap().port();
+
+        std::thread::spawn(move || {
+            let server = StreamLocalEndpoint::new(AllowStdTcpConnector, transport);
+            let (stream, peer) = listener.accept().expect("TCP accept failed");
+            let stream = AllowStdTcpStream::from_std(stream).unwrap();
+
+            block_on(server.accept(peer, stream)).expect("Accept failed");
+            let _ = block_on(server.receive_loop(handler));
+        });
+
+        port
+    }
+
+    fn check_transport(transport: StreamTransport) {
+        let port = start_server(transport);
+        let local_endpoint = StreamLocalEndpoint::new(AllowStdTcpConnector, transport);
+
+        let uri = UriBuf::from_scheme_host_port(transport.scheme(), "127.0.0.1", Some(port));
+        let remote_endpoint = local_endpoint
+            .remote_endpoint_from_uri(&uri)
+            .expect("Remote endpoint lookup failed");
+        assert_eq!(transport.scheme(), remote_endpoint.scheme());
+
+        let get = |path: &RelRef| {
+            let future =
+                remote_endpoint.send_to(path, CoapRequest::get().emit_successful_response());
+
+            match block_on(select(
+                future,
+                local_endpoint.receive_loop(null_receiver!()),
+            )) {
+                Either::Left((ret, _)) => ret.map(|msg| msg.payload().len()),
+                Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            }
+        };
+
+        assert_eq!(Ok(5), get(rel_ref!("test")));
+
+        // Requires the extended length field, and the server to know our
+        // Max-Message-Size.
+        assert_eq!(Ok(2000), get(rel_ref!("test?2000")));
+
+        // Larger than the server accepts.
+        let future = remote_endpoint.send_to(
+            rel_ref!("test"),
+            CoapRequest::post()
+                .payload_writer(|msg_out| msg_out.append_payload_bytes(&[0; 70000]))
+                .emit_successful_response(),
+        );
+        assert_eq!(Err(Error::MessageTooLarge), block_on(future).map(|_| ()));
+
+        // The connection was reused, and the CSM of the server was received.
+        let connections = local_endpoint.inner.connections.lock().unwrap();
+        assert_eq!(1, connections.len());
+        assert_eq!(
+            STREAM_MAX_MESSAGE_SIZE,
+            connections.values().next().unwrap().peer_max_message_size()
+        );
+    }
+
+    #[test]
+    fn tcp() {
+        check_transport(StreamTransport::Tcp);
+    }
+
+    #[test]
+    #[cfg(feature = "websocket")]
+    fn websocket() {
+        check_transport(StreamTransport::WebSocket);
+    }
+
+    /// Connects like [`AllowStdTcpConnector`], except that connecting to `.0` never
+    /// finishes.
+    struct StallingConnector(SocketAddr);
+
+    impl StreamConnector for StallingConnector {
+        type Stream = AllowStdTcpStream;
+
+        fn connect(&self, addr: SocketAddr) -> BoxFuture<'static, std::io::Result<Self::Stream>> {
+            if addr == self.0 {
+                futures::future::pending().boxed()
+            } else {
+                AllowStdTcpConnector.connect(addr)
+            }
+        }
+    }
+
+    #[test]
+    fn concurrent_connections() {
+        let port = start_server(StreamTransport::Tcp);
+        let stalled: SocketAddr = "192.0.2.1:5683".parse().unwrap();
+        let local_endpoint =
+            StreamLocalEndpoint::new(StallingConnector(stalled), StreamTransport::Tcp);
+
+        // Connecting to one peer doesn't hold up connecting to another.
+        let requests = select(
+            local_endpoint.send(stalled, CoapRequest::get().emit_successful_response()),
+            local_endpoint.send(
+                SocketAddr::from(([127, 0, 0, 1], port)),
+                CoapRequest::get().emit_successful_response(),
+            ),
+        );
+
+        let ret = match block_on(select(
+            requests,
+            local_endpoint.receive_loop(null_receiver!()),
+        )) {
+            Either::Left((Either::Right((ret, _)), _)) => ret.map(|msg| msg.payload().len()),
+            _ => panic!("Request to the stalled peer finished unexpectedly"),
+        };
+        assert_eq!(Ok(5), ret);
+    }
+
+    #[test]
+    fn uri_scheme() {
+        let local_endpoint = StreamLocalEndpoint::new(AllowStdTcpConnector, StreamTransport::Tcp);
+
+        let remote_endpoint = local_endpoint
+            .remote_endpoint_from_uri(uri!("coap+tcp://[::1]/a/b"))
+            .expect("Remote endpoint lookup failed");
+        assert_eq!(remote_endpoint.uri(), uri!("coap+tcp://[::1]/a/b"));
+
+        assert_eq!(
+            Err(Error::UnsupportedUriScheme),
+            local_endpoint
+                .remote_endpoint_from_uri(uri!("coap://[::1]/"))
+                .map(|_| ())
+        );
+    }
+}
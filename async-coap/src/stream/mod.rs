@@ -0,0 +1,138 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Stream-based CoAP backend
+//!
+//! This module contains a [`LocalEndpoint`] implementation for CoAP over reliable,
+//! connection-oriented transports, as described in [IETF-RFC8323]: either directly over
+//! TCP (`coap+tcp:` URIs), or over WebSockets (`coap+ws:` URIs). WebSockets are only
+//! supported when the `websocket` feature is enabled.
+//!
+//! Since the transport is reliable, messages are never retransmitted or acknowledged, and
+//! have neither a type nor a message id. Responses are matched to requests using only
+//! their tokens. Connections are established on demand by a [`StreamConnector`], and
+//! each connection starts with the exchange of Capabilities and Settings Messages (CSMs).
+//! Ping and Pong signaling messages are answered automatically.
+//!
+//! Like with the datagram backend, the receive loop of a [`StreamLocalEndpoint`] needs
+//! to be running for responses to be received:
+//!
+//! ```no_run
+//! # use async_coap::prelude::*;
+//! # use async_coap::stream::{AllowStdTcpConnector, StreamLocalEndpoint, StreamTransport};
+//! # use futures::prelude::*;
+//! # use futures::executor::block_on;
+//! let local_endpoint = StreamLocalEndpoint::new(AllowStdTcpConnector, StreamTransport::Tcp);
+//!
+//! let remote_endpoint = local_endpoint
+//!     .remote_endpoint_from_uri(uri!("coap+tcp://coap.me/test"))
+//!     .unwrap();
+//!
+//! let future = remote_endpoint.send(CoapRequest::get().emit_successful_response());
+//! let receive_loop = local_endpoint.receive_loop(null_receiver!());
+//!
+//! let response = match block_on(future::select(future, receive_loop)) {
+//!     future::Either::Left((response, _)) => response,
+//!     future::Either::Right(_) => panic!("Receive loop finished unexpectedly"),
+//! };
+//!
+//! println!("{:?}", response);
+//! ```
+//!
+//! Connections which were accepted from a listening socket can be handed to a local
+//! endpoint with [`StreamLocalEndpoint::accept`], so that the requests received on them
+//! are passed to the receive handler.
+//!
+//! [IETF-RFC8323]: https://tools.ietf.org/html/rfc8323
+
+use super::*;
+use futures::future::BoxFuture;
+use futures::io::{AsyncRead, AsyncWrite};
+use std::net::SocketAddr;
+
+mod allow_tcp_stream;
+pub use allow_tcp_stream::{AllowStdTcpConnector, AllowStdTcpStream};
+
+mod connection;
+use connection::{Connection, Inbound};
+
+mod inbound_context;
+pub use inbound_context::{StreamInboundContext, StreamRespondableInboundContext};
+
+mod local_endpoint;
+pub use local_endpoint::StreamLocalEndpoint;
+use local_endpoint::StreamLocalEndpointInner;
+
+mod remote_endpoint;
+pub use remote_endpoint::StreamRemoteEndpoint;
+
+#[cfg(feature = "websocket")]
+mod websocket;
+
+/// The largest message which is accepted from peers, and which is advertised to them in
+/// the Max-Message-Size option of the CSM sent on every connection.
+pub const STREAM_MAX_MESSAGE_SIZE: usize = 65536;
+
+/// The transports supported by [`StreamLocalEndpoint`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum StreamTransport {
+    /// CoAP messages are sent directly over the connection, as described in
+    /// [IETF-RFC8323 Section 3].
+    ///
+    /// [IETF-RFC8323 Section 3]: https://tools.ietf.org/html/rfc8323#section-3
+    Tcp,
+
+    /// Each CoAP message is sent in a binary WebSocket frame, as described in
+    /// [IETF-RFC8323 Section 4]. Connections are upgraded to WebSockets with a handshake
+    /// for the `/.well-known/coap` resource of the peer.
+    ///
+    /// This variant is only available when the `websocket` feature is enabled.
+    ///
+    /// [IETF-RFC8323 Section 4]: https://tools.ietf.org/html/rfc8323#section-4
+    #[cfg(feature = "websocket")]
+    WebSocket,
+}
+
+impl StreamTransport {
+    /// Returns the URI scheme for this transport.
+    pub fn scheme(self) -> &'static str {
+        match self {
+            StreamTransport::Tcp => URI_SCHEME_COAP_TCP,
+            #[cfg(feature = "websocket")]
+            StreamTransport::WebSocket => URI_SCHEME_COAP_WS,
+        }
+    }
+
+    /// Returns the default port for this transport.
+    pub fn default_port(self) -> u16 {
+        match self {
+            StreamTransport::Tcp => DEFAULT_PORT_COAP_TCP,
+            #[cfg(feature = "websocket")]
+            StreamTransport::WebSocket => DEFAULT_PORT_COAP_WS,
+        }
+    }
+}
+
+/// Trait for establishing the connections used by [`StreamLocalEndpoint`].
+///
+/// Implementations only need to establish a connected byte stream, like a TCP connection;
+/// the WebSocket handshake and the exchange of CSMs are performed by the local endpoint.
+pub trait StreamConnector: Send + Sync + 'static {
+    /// The type of the connected streams.
+    type Stream: AsyncRead + AsyncWrite + Send + Unpin + 'static;
+
+    /// Connects to `addr`.
+    fn connect(&self, addr: SocketAddr) -> BoxFuture<'static, std::io::Result<Self::Stream>>;
+}
@@ -0,0 +1,156 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+use futures::prelude::*;
+use std::sync::{Arc, Weak};
+
+/// [`RemoteEndpoint`] implementation for [`StreamLocalEndpoint`].
+pub struct StreamRemoteEndpoint<C: StreamConnector> {
+    local_endpoint: Weak<StreamLocalEndpointInner<C>>,
+    socket_addr: SocketAddr,
+    host: Option<String>,
+    path: RelRefBuf,
+}
+
+impl<C: StreamConnector> core::fmt::Debug for StreamRemoteEndpoint<C> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("StreamRemoteEndpoint")
+            .field("socket_addr", &self.socket_addr)
+            .field("host", &self.host)
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+impl<C: StreamConnector> Clone for StreamRemoteEndpoint<C> {
+    fn clone(&self) -> Self {
+        StreamRemoteEndpoint {
+            local_endpoint: self.local_endpoint.clone(),
+            socket_addr: self.socket_addr,
+            host: self.host.clone(),
+            path: self.path.clone(),
+        }
+    }
+}
+
+impl<C: StreamConnector> StreamRemoteEndpoint<C> {
+    pub(crate) fn new(
+        local_endpoint: &Arc<StreamLocalEndpointInner<C>>,
+        socket_addr: SocketAddr,
+        host: Option<String>,
+        path: RelRefBuf,
+    ) -> StreamRemoteEndpoint<C> {
+        StreamRemoteEndpoint {
+            local_endpoint: Arc::downgrade(local_endpoint),
+            socket_addr,
+            host,
+            path,
+        }
+    }
+}
+
+impl<C: StreamConnector> RemoteEndpoint for StreamRemoteEndpoint<C> {
+    type SocketAddr = SocketAddr;
+    type InboundContext = StreamInboundContext;
+
+    fn uri(&self) -> UriBuf {
+        let local_endpoint = match self.local_endpoint.upgrade() {
+            Some(local_endpoint) => local_endpoint,
+            None => return uri!("null:///").to_owned(),
+        };
+
+        let scheme = local_endpoint.scheme();
+
+        let port = match self.socket_addr.port() {
+            0 => None,
+            port if port == local_endpoint.default_port() => None,
+            port => Some(port),
+        };
+
+        let mut uri_abs = match self.host_option() {
+            Some(host) if !host.is_empty() => UriBuf::from_scheme_host_port(scheme, host, port),
+            _ => UriBuf::from_scheme_host_port(scheme, self.socket_addr.addr_to_string(), port),
+        };
+
+        uri_abs.replace_path(&self.path);
+
+        uri_abs
+    }
+
+    fn scheme(&self) -> &'static str {
+        match self.local_endpoint.upgrade() {
+            Some(local_endpoint) => local_endpoint.scheme(),
+            None => "null",
+        }
+    }
+
+    fn host_option(&self) -> Option<&str> {
+        self.host.as_deref()
+    }
+
+    fn default_path(&self) -> &RelRef {
+        &self.path
+    }
+
+    fn set_host_option(&mut self, host: Option<&str>) {
+        self.host = host.map(String::from);
+    }
+
+    fn clone_using_rel_ref(&self, uri: &RelRef) -> Self {
+        StreamRemoteEndpoint {
+            local_endpoint: self.local_endpoint.clone(),
+            socket_addr: self.socket_addr,
+            host: self.host.clone(),
+            path: self.path.resolved_rel_ref(uri),
+        }
+    }
+
+    fn send<'a, R, SD>(&'a self, send_desc: SD) -> BoxFuture<'a, Result<R, Error>>
+    where
+        SD: SendDesc<Self::InboundContext, R> + 'a,
+        R: Send + 'a,
+    {
+        let local_endpoint = match self.local_endpoint.upgrade() {
+            Some(local_endpoint) => local_endpoint,
+            None => return futures::future::ready(Err(Error::Cancelled)).boxed(),
+        };
+
+        let send_desc = send_desc.uri_host_path(self.host.clone(), &self.path);
+
+        local_endpoint
+            .send(self.socket_addr, self.host.clone(), send_desc)
+            .boxed()
+    }
+
+    fn send_to<'a, R, SD, UF>(&'a self, path: UF, send_desc: SD) -> BoxFuture<'a, Result<R, Error>>
+    where
+        SD: SendDesc<Self::InboundContext, R> + 'a,
+        R: Send + 'a,
+        UF: AsRef<RelRef>,
+    {
+        let local_endpoint = match self.local_endpoint.upgrade() {
+            Some(local_endpoint) => local_endpoint,
+            None => return futures::future::ready(Err(Error::Cancelled)).boxed(),
+        };
+
+        let send_desc =
+            send_desc.uri_host_path(self.host.clone(), self.path.resolved_rel_ref(path));
+
+        local_endpoint
+            .send(self.socket_addr, self.host.clone(), send_desc)
+            .boxed()
+    }
+}
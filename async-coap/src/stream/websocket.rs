@@ -0,0 +1,357 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! The parts of the WebSocket protocol ([IETF-RFC6455]) needed to carry CoAP messages,
+//! as described in [IETF-RFC8323 Section 4].
+//!
+//! [IETF-RFC6455]: https://tools.ietf.org/html/rfc6455
+//! [IETF-RFC8323 Section 4]: https://tools.ietf.org/html/rfc8323#section-4
+
+use super::*;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use futures::io::{AsyncReadExt, AsyncWriteExt};
+use sha1::{Digest, Sha1};
+
+/// Opcode of frames which carry a CoAP message.
+pub(super) const OPCODE_BINARY: u8 = 0x2;
+
+/// Opcode of frames which close the connection.
+pub(super) const OPCODE_CLOSE: u8 = 0x8;
+
+/// Opcode of frames which must be answered with a frame with [`OPCODE_PONG`].
+pub(super) const OPCODE_PING: u8 = 0x9;
+
+/// Opcode of frames which answer a frame with [`OPCODE_PING`].
+pub(super) const OPCODE_PONG: u8 = 0xA;
+
+/// The path of the resource which is upgraded to a WebSocket.
+const WELL_KNOWN_PATH: &str = "/.well-known/coap";
+
+/// The name of the WebSocket subprotocol for CoAP.
+const SUBPROTOCOL: &str = "coap";
+
+/// The upper limit for the length of the HTTP messages of a handshake.
+const MAX_HEAD_LEN: usize = 4096;
+
+/// The upper limit for the number of header fields of the HTTP messages of a handshake.
+const MAX_HEADERS: usize = 32;
+
+/// A WebSocket frame, with its payload already unmasked.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(super) struct Frame {
+    pub opcode: u8,
+    pub payload: Vec<u8>,
+}
+
+/// Performs the opening handshake for the client side of a connection to `host`.
+pub(super) async fn client_handshake<S>(stream: &mut S, host: &str) -> Result<(), Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let key = BASE64.encode(rand::random::<[u8; 16]>());
+
+    let request = format!(
+        "GET {} HTTP/1.1\r\n\
+         Host: {}\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Key: {}\r\n\
+         Sec-WebSocket-Protocol: {}\r\n\
+         Sec-WebSocket-Version: 13\r\n\r\n",
+        WELL_KNOWN_PATH, host, key, SUBPROTOCOL
+    );
+    write_all(stream, request.as_bytes()).await?;
+
+    let head = read_head(stream).await?;
+    let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+    let mut response = httparse::Response::new(&mut headers);
+
+    match response.parse(&head) {
+        Ok(httparse::Status::Complete(_)) => (),
+        _ => return Err(Error::BadResponse),
+    }
+
+    if response.code != Some(101)
+        || header(response.headers, "sec-websocket-accept") != Some(accept_key(&key).as_str())
+        || header(response.headers, "sec-websocket-protocol") != Some(SUBPROTOCOL)
+    {
+        return Err(Error::BadResponse);
+    }
+
+    Ok(())
+}
+
+/// Performs the opening handshake for the server side of a connection, rejecting
+/// requests for other resources or subprotocols.
+pub(super) async fn server_handshake<S>(stream: &mut S) -> Result<(), Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let head = read_head(stream).await?;
+    let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+    let mut request = httparse::Request::new(&mut headers);
+
+    match request.parse(&head) {
+        Ok(httparse::Status::Complete(_)) => (),
+        _ => return Err(Error::ParseFailure),
+    }
+
+    let headers = request.headers;
+    let is_upgrade = request.method == Some("GET")
+        && request.path == Some(WELL_KNOWN_PATH)
+        && request.version == Some(1)
+        && header(headers, "upgrade").map(|x| x.eq_ignore_ascii_case("websocket")) == Some(true)
+        && header(headers, "sec-websocket-version") == Some("13")
+        && header(headers, "sec-websocket-protocol")
+            .map(|protocols| protocols.split(',').any(|x| x.trim() == SUBPROTOCOL))
+            == Some(true);
+
+    let key = match header(headers, "sec-websocket-key") {
+        Some(key) if is_upgrade => key,
+        _ => {
+            let _ = write_all(
+                stream,
+                b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n",
+            )
+            .await;
+            return Err(Error::ParseFailure);
+        }
+    };
+
+    let response = format!(
+        "HTTP/1.1 101 Switching Protocols\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\n\
+         Sec-WebSocket-Protocol: {}\r\n\r\n",
+        accept_key(key),
+        SUBPROTOCOL
+    );
+
+    write_all(stream, response.as_bytes()).await
+}
+
+/// Encodes a frame containing all of `payload`. Frames sent by clients must be masked.
+pub(super) fn encode_frame(opcode: u8, payload: &[u8], masked: bool) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(payload.len() + 14);
+    let mask_bit = if masked { 0x80 } else { 0x00 };
+
+    frame.push(0x80 | opcode);
+
+    match payload.len() {
+        len @ 0..=125 => frame.push(mask_bit | len as u8),
+        len @ 126..=0xFFFF => {
+            frame.push(mask_bit | 126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(mask_bit | 127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+
+    if masked {
+        let mask = rand::random::<[u8; 4]>();
+        frame.extend_from_slice(&mask);
+        frame.extend(payload.iter().zip(mask.iter().cycle()).map(|(b, m)| b ^ m));
+    } else {
+        frame.extend_from_slice(payload);
+    }
+
+    frame
+}
+
+/// Decodes the frame at the start of `buffer`, returning it along with its length, or
+/// `None` if `buffer` doesn't contain all of it yet.
+///
+/// Fragmented frames aren't supported, since every CoAP message is sent in a single
+/// frame in practice. Frames with payloads longer than `max_len` are rejected with
+/// [`Error::MessageTooLarge`]. Since frames sent by clients must be masked and frames
+/// sent by servers must not be, as described in [IETF-RFC6455 Section 5.1], frames
+/// which aren't masked as given by `masked` are rejected with [`Error::ParseFailure`].
+///
+/// [IETF-RFC6455 Section 5.1]: https://tools.ietf.org/html/rfc6455#section-5.1
+pub(super) fn decode_frame(
+    buffer: &[u8],
+    max_len: usize,
+    masked: bool,
+) -> Result<Option<(Frame, usize)>, Error> {
+    if buffer.len() < 2 {
+        return Ok(None);
+    }
+
+    // Fragmented frames and ones using extensions are rejected.
+    if buffer[0] & 0xF0 != 0x80 || buffer[0] & 0x0F == 0 {
+        return Err(Error::ParseFailure);
+    }
+
+    if (buffer[1] & 0x80 != 0) != masked {
+        return Err(Error::ParseFailure);
+    }
+
+    let opcode = buffer[0] & 0x0F;
+
+    let (len, mut offset) = match buffer[1] & 0x7F {
+        126 if buffer.len() < 4 => return Ok(None),
+        126 => (u16::from_be_bytes([buffer[2], buffer[3]]) as u64, 4),
+        127 if buffer.len() < 10 => return Ok(None),
+        127 => {
+            let mut len = [0u8; 8];
+            len.copy_from_slice(&buffer[2..10]);
+            (u64::from_be_bytes(len), 10)
+        }
+        len => (len as u64, 2),
+    };
+
+    if len > max_len as u64 {
+        return Err(Error::MessageTooLarge);
+    }
+    let len = len as usize;
+
+    let mask = if masked {
+        if buffer.len() < offset + 4 {
+            return Ok(None);
+        }
+        offset += 4;
+        [
+            buffer[offset - 4],
+            buffer[offset - 3],
+            buffer[offset - 2],
+            buffer[offset - 1],
+        ]
+    } else {
+        [0; 4]
+    };
+
+    if buffer.len() < offset + len {
+        return Ok(None);
+    }
+
+    let payload = buffer[offset..offset + len]
+        .iter()
+        .zip(mask.iter().cycle())
+        .map(|(b, m)| b ^ m)
+        .collect();
+
+    Ok(Some((Frame { opcode, payload }, offset + len)))
+}
+
+async fn write_all<S: AsyncWrite + Unpin>(stream: &mut S, bytes: &[u8]) -> Result<(), Error> {
+    stream.write_all(bytes).await.map_err(|_| Error::IOError)?;
+    stream.flush().await.map_err(|_| Error::IOError)
+}
+
+/// Reads the head of an HTTP message, up to and including the empty line which ends it.
+///
+/// This reads a byte at a time, so that nothing after the head is consumed.
+async fn read_head<S: AsyncRead + Unpin>(stream: &mut S) -> Result<Vec<u8>, Error> {
+    let mut head = Vec::new();
+
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() >= MAX_HEAD_LEN {
+            return Err(Error::MessageTooLarge);
+        }
+
+        let mut byte = [0u8];
+        match stream.read(&mut byte).await {
+            Ok(1) => head.push(byte[0]),
+            _ => return Err(Error::IOError),
+        }
+    }
+
+    Ok(head)
+}
+
+fn header<'a>(headers: &[httparse::Header<'a>], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|header| header.name.eq_ignore_ascii_case(name))
+        .and_then(|header| std::str::from_utf8(header.value).ok())
+}
+
+/// Calculates the value of the `Sec-WebSocket-Accept` header field for `key`.
+fn accept_key(key: &str) -> String {
+    const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+    BASE64.encode(Sha1::digest(format!("{}{}", key, GUID)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accept_key() {
+        // Example from IETF-RFC6455 Section 1.3.
+        assert_eq!(
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=",
+            super::accept_key("dGhlIHNhbXBsZSBub25jZQ==")
+        );
+    }
+
+    #[test]
+    fn frames() {
+        for &len in &[0, 5, 125, 126, 70_000] {
+            let payload = vec![0x5A; len];
+
+            for &masked in &[false, true] {
+                let mut frame = encode_frame(OPCODE_BINARY, &payload, masked);
+                let frame_len = frame.len();
+
+                for partial in &[0, 1, frame_len - 1] {
+                    assert_eq!(Ok(None), decode_frame(&frame[..*partial], 100_000, masked));
+                }
+
+                // Frames which aren't masked as expected are rejected.
+                assert_eq!(
+                    Err(Error::ParseFailure),
+                    decode_frame(&frame, 100_000, !masked)
+                );
+
+                frame.extend_from_slice(b"next frame");
+                let decoded = decode_frame(&frame, 100_000, masked).unwrap();
+                assert_eq!(
+                    Some((
+                        Frame {
+                            opcode: OPCODE_BINARY,
+                            payload: payload.clone()
+                        },
+                        frame_len
+                    )),
+                    decoded
+                );
+            }
+        }
+
+        // Masked "Hello" text frame from IETF-RFC6455 Section 5.7.
+        let frame = [
+            0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58,
+        ];
+        let (frame, _) = decode_frame(&frame, 125, true).unwrap().unwrap();
+        assert_eq!(b"Hello", &frame.payload[..]);
+
+        assert_eq!(
+            Err(Error::MessageTooLarge),
+            decode_frame(&encode_frame(OPCODE_BINARY, &[0; 126], false), 125, false)
+        );
+
+        // Fragmented frames aren't supported.
+        assert_eq!(
+            Err(Error::ParseFailure),
+            decode_frame(&[0x02, 0x00], 125, false)
+        );
+    }
+}
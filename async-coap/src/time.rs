@@ -0,0 +1,160 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use core::fmt::Debug;
+use core::time::Duration;
+
+/// Trait for points in time taken from a monotonic clock, used for scheduling
+/// retransmissions and for determining when an exchange has timed out.
+///
+/// All of the arithmetic saturates instead of overflowing or panicking, so that a
+/// very long duration (like the [`transmit_wait_duration`] of an observation) simply
+/// results in a deadline that is never reached.
+///
+/// This is implemented for [`std::time::Instant`] and for [`MillisInstant`], which is
+/// intended for targets that only have a tick counter.
+///
+/// [`transmit_wait_duration`]: crate::send_desc::SendDesc::transmit_wait_duration
+pub trait MonotonicInstant: Copy + Ord + Debug + Send + Sync {
+    /// Returns the instant which is `duration` after this one, or the latest instant
+    /// that can be represented if that would overflow.
+    fn saturating_add(self, duration: Duration) -> Self;
+
+    /// Returns the amount of time elapsed from `earlier` to this instant, or zero if
+    /// `earlier` is later than this instant.
+    fn saturating_duration_since(self, earlier: Self) -> Duration;
+
+    /// Returns the amount of time from `now` until this instant, or `None` if this
+    /// instant has been reached.
+    fn remaining(self, now: Self) -> Option<Duration> {
+        if now >= self {
+            None
+        } else {
+            Some(self.saturating_duration_since(now))
+        }
+    }
+}
+
+/// Trait for monotonic clocks, which provide the current [`MonotonicInstant`].
+pub trait Clock: Send + Sync {
+    /// The type of instant returned by this clock.
+    type Instant: MonotonicInstant;
+
+    /// Returns the current instant.
+    fn now(&self) -> Self::Instant;
+}
+
+/// The monotonic clock provided by the standard library.
+#[cfg(feature = "std")]
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct StdClock;
+
+#[cfg(feature = "std")]
+impl Clock for StdClock {
+    type Instant = std::time::Instant;
+
+    fn now(&self) -> Self::Instant {
+        std::time::Instant::now()
+    }
+}
+
+#[cfg(feature = "std")]
+impl MonotonicInstant for std::time::Instant {
+    fn saturating_add(self, mut duration: Duration) -> Self {
+        // The latest representable `Instant` is platform-specific, so we get as
+        // close to it as we reasonably can.
+        loop {
+            if let Some(instant) = self.checked_add(duration) {
+                return instant;
+            }
+            duration /= 2;
+        }
+    }
+
+    fn saturating_duration_since(self, earlier: Self) -> Duration {
+        std::time::Instant::saturating_duration_since(&self, earlier)
+    }
+}
+
+/// A [`MonotonicInstant`] measured in milliseconds since an arbitrary epoch, such as
+/// when the device booted.
+///
+/// This is intended for targets without [`std::time::Instant`] which have a tick
+/// counter that can be converted to milliseconds.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct MillisInstant(pub u64);
+
+impl MonotonicInstant for MillisInstant {
+    fn saturating_add(self, duration: Duration) -> Self {
+        let millis = duration.as_millis();
+        if millis > u64::MAX as u128 {
+            MillisInstant(u64::MAX)
+        } else {
+            MillisInstant(self.0.saturating_add(millis as u64))
+        }
+    }
+
+    fn saturating_duration_since(self, earlier: Self) -> Duration {
+        Duration::from_millis(self.0.saturating_sub(earlier.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn millis_instant() {
+        let start = MillisInstant(1000);
+        let later = start.saturating_add(Duration::from_millis(2500));
+        assert_eq!(MillisInstant(3500), later);
+        assert_eq!(
+            Duration::from_millis(2500),
+            later.saturating_duration_since(start)
+        );
+        assert_eq!(
+            Duration::from_millis(0),
+            start.saturating_duration_since(later)
+        );
+        assert_eq!(Some(Duration::from_millis(2500)), later.remaining(start));
+        assert_eq!(None, start.remaining(later));
+        assert_eq!(None, later.remaining(later));
+
+        assert_eq!(
+            MillisInstant(u64::MAX),
+            start.saturating_add(Duration::from_secs(u64::MAX))
+        );
+    }
+
+    #[test]
+    fn std_instant() {
+        let start = StdClock.now();
+        let later = start.saturating_add(Duration::from_secs(60));
+        assert_eq!(
+            Duration::from_secs(60),
+            later.saturating_duration_since(start)
+        );
+        assert_eq!(Some(Duration::from_secs(60)), later.remaining(start));
+        assert_eq!(None, start.remaining(later));
+
+        // Saturates instead of panicking.
+        let forever = start.saturating_add(Duration::from_secs(u64::MAX));
+        assert!(forever > later);
+        assert_eq!(
+            Duration::from_secs(0),
+            start.saturating_duration_since(forever)
+        );
+    }
+}
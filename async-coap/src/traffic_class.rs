@@ -0,0 +1,100 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+/// Marking for outbound datagrams, made up of a [DSCP][IETF-RFC2474] value used by the
+/// network to prioritize the datagram and an [ECN][IETF-RFC3168] codepoint.
+///
+/// This is the octet carried in the Traffic Class field of IPv6 headers and in the
+/// former Type of Service field of IPv4 headers: the DSCP occupies the upper six bits,
+/// and the ECN codepoint the lower two.
+///
+/// Requests are marked using [`SendDescExt::dscp`], and the marking is applied by
+/// sockets which implement
+/// [`AsyncSendTo::poll_send_to_with_traffic_class`](crate::datagram::AsyncSendTo::poll_send_to_with_traffic_class).
+///
+/// [IETF-RFC2474]: https://tools.ietf.org/html/rfc2474
+/// [IETF-RFC3168]: https://tools.ietf.org/html/rfc3168
+/// [`SendDescExt::dscp`]: crate::send_desc::SendDescExt::dscp
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone, Default)]
+pub struct TrafficClass(u8);
+
+impl TrafficClass {
+    /// Unmarked, best-effort traffic. This is what datagrams are sent with by default.
+    pub const DEFAULT: TrafficClass = TrafficClass(0);
+
+    /// The DSCP for Expedited Forwarding ([IETF-RFC3246]), for low-latency traffic like
+    /// alarms.
+    ///
+    /// [IETF-RFC3246]: https://tools.ietf.org/html/rfc3246
+    pub const DSCP_EF: u8 = 46;
+
+    /// The DSCP for Lower-Effort traffic ([IETF-RFC8622]), like bulk telemetry which
+    /// should yield to everything else on congested links.
+    ///
+    /// [IETF-RFC8622]: https://tools.ietf.org/html/rfc8622
+    pub const DSCP_LE: u8 = 1;
+
+    /// Creates a traffic class from a DSCP value and an ECN codepoint. Bits which don't
+    /// fit in either field are ignored.
+    pub const fn new(dscp: u8, ecn: u8) -> TrafficClass {
+        TrafficClass(((dscp & 0x3F) << 2) | (ecn & 0x03))
+    }
+
+    /// Creates a traffic class from the octet as it appears in IP headers.
+    pub const fn from_octet(octet: u8) -> TrafficClass {
+        TrafficClass(octet)
+    }
+
+    /// Returns the octet as it appears in IP headers.
+    pub const fn octet(self) -> u8 {
+        self.0
+    }
+
+    /// Returns the DSCP value.
+    pub const fn dscp(self) -> u8 {
+        self.0 >> 2
+    }
+
+    /// Returns the ECN codepoint.
+    pub const fn ecn(self) -> u8 {
+        self.0 & 0x03
+    }
+
+    /// Returns a copy of this traffic class with the DSCP value replaced by `dscp`.
+    pub const fn with_dscp(self, dscp: u8) -> TrafficClass {
+        TrafficClass::new(dscp, self.ecn())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields() {
+        let tc = TrafficClass::new(TrafficClass::DSCP_EF, 0b01);
+        assert_eq!(0xB9, tc.octet());
+        assert_eq!(TrafficClass::DSCP_EF, tc.dscp());
+        assert_eq!(0b01, tc.ecn());
+        assert_eq!(tc, TrafficClass::from_octet(0xB9));
+
+        let tc = tc.with_dscp(TrafficClass::DSCP_LE);
+        assert_eq!(0x05, tc.octet());
+        assert_eq!(0b01, tc.ecn());
+
+        assert_eq!(0xFF, TrafficClass::new(0xFF, 0xFF).octet());
+        assert_eq!(TrafficClass::DEFAULT, TrafficClass::default());
+    }
+}
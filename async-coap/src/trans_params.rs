@@ -226,11 +226,10 @@ pub trait TransParams: Default + Copy + Sync + Send + Unpin {
 
         let ret = (self.coap_ack_timeout().as_millis() as u64) << attempt;
 
-        const JDIV: u64 = 512u64;
-        let rmod: u64 = (JDIV as f32 * (Self::COAP_ACK_RANDOM_FACTOR - 1.0)) as u64;
-        let jmul = JDIV + rand::random::<u64>() % rmod;
-
-        Duration::from_millis(ret * jmul / JDIV)
+        Duration::from_millis(crate::random::jitter_millis(
+            ret,
+            Self::COAP_ACK_RANDOM_FACTOR,
+        ))
     }
 }
 
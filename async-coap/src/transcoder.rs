@@ -0,0 +1,222 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+use std::collections::HashMap;
+
+type CodecFn = Box<dyn Fn(&[u8]) -> Result<Vec<u8>, Error> + Send + Sync>;
+
+/// Serves a representation in whichever content format is requested by the `Accept` option
+/// of an inbound request, using registered codecs to convert between content formats.
+///
+/// This allows a resource to be written once, producing a single representation (say,
+/// CBOR), while still being served to clients which ask for other formats (say, JSON).
+/// Codecs are registered for each pair of content formats using
+/// [`Transcoder::with_codec`]; no codecs are built in.
+///
+/// ```
+/// # use async_coap::prelude::*;
+/// # use async_coap::datagram::*;
+/// # use async_coap::{Error, RespondableInboundContext, Transcoder};
+/// # let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+/// let transcoder = Transcoder::new().with_codec(
+///     ContentFormat::TEXT_PLAIN_UTF8,
+///     ContentFormat::APPLICATION_JSON,
+///     |payload| {
+///         let text = std::str::from_utf8(payload).map_err(|_| Error::ParseFailure)?;
+///         Ok(format!("{:?}", text).into_bytes())
+///     },
+/// );
+///
+/// # let _ =
+/// local_endpoint.receive_loop(|context: &DatagramRespondableInboundContext<_>| {
+///     transcoder.respond(context, ContentFormat::TEXT_PLAIN_UTF8, b"Hello, world!")
+/// });
+/// ```
+#[derive(Default)]
+pub struct Transcoder {
+    codecs: HashMap<(ContentFormat, ContentFormat), CodecFn>,
+}
+
+impl std::fmt::Debug for Transcoder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Transcoder")
+            .field("codecs", &self.codecs.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Transcoder {
+    /// Creates a new `Transcoder` instance without any codecs.
+    pub fn new() -> Transcoder {
+        Default::default()
+    }
+
+    /// Registers a codec which converts payloads in the content format `from` into the
+    /// content format `to`, replacing any codec previously registered for the same pair.
+    ///
+    /// Codecs only work in one direction: converting back requires a separate codec.
+    pub fn with_codec<F>(mut self, from: ContentFormat, to: ContentFormat, codec: F) -> Transcoder
+    where
+        F: Fn(&[u8]) -> Result<Vec<u8>, Error> + Send + Sync + 'static,
+    {
+        self.codecs.insert((from, to), Box::new(codec));
+        self
+    }
+
+    /// Returns true if a payload in the content format `from` can be served as `to`.
+    pub fn can_transcode(&self, from: ContentFormat, to: ContentFormat) -> bool {
+        from == to || self.codecs.contains_key(&(from, to))
+    }
+
+    /// Converts `payload` from the content format `from` into the content format `to`.
+    ///
+    /// Fails with [`Error::InvalidArgument`] if no codec was registered for the pair.
+    /// Also useful for converting the payloads of inbound requests into the content
+    /// format that a resource is stored in.
+    pub fn transcode(
+        &self,
+        from: ContentFormat,
+        to: ContentFormat,
+        payload: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        if from == to {
+            return Ok(payload.to_vec());
+        }
+
+        match self.codecs.get(&(from, to)) {
+            Some(codec) => codec(payload),
+            None => Err(Error::InvalidArgument),
+        }
+    }
+
+    /// Responds to the inbound request with `2.05 Content` and `payload`, which is in the
+    /// content format `content_format`, transcoding it if the request asks for another
+    /// content format using the `Accept` option.
+    ///
+    /// If the request doesn't include an `Accept` option, `payload` is served as-is. If
+    /// no codec was registered for the requested content format, the request is answered
+    /// with `4.06 Not Acceptable`. If the codec fails, the request is answered with
+    /// `5.00 Internal Server Error`.
+    ///
+    /// This method is intended to be called from the handler passed to
+    /// [`LocalEndpoint::receive`].
+    pub fn respond<T>(
+        &self,
+        context: &T,
+        content_format: ContentFormat,
+        payload: &[u8],
+    ) -> Result<(), Error>
+    where
+        T: RespondableInboundContext,
+    {
+        let accept = context.message().accept().unwrap_or(content_format);
+
+        if !self.can_transcode(content_format, accept) {
+            return context.respond_error(MsgCode::ClientErrorNotAcceptable, "");
+        }
+
+        let payload = match self.transcode(content_format, accept, payload) {
+            Ok(payload) => payload,
+            Err(e) => {
+                debug!(
+                    "Unable to transcode {} to {}: {:?}",
+                    content_format, accept, e
+                );
+                return context.respond_error(
+                    MsgCode::ServerErrorInternalServerError,
+                    "Transcoding failed",
+                );
+            }
+        };
+
+        context.respond(|msg_out| {
+            msg_out.set_msg_code(MsgCode::SuccessContent);
+            msg_out.insert_option(option::CONTENT_FORMAT, accept)?;
+            msg_out.append_payload_bytes(&payload)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datagram::{
+        DatagramLocalEndpoint, DatagramRespondableInboundContext, LoopbackSocket,
+        LoopbackSocketAddr,
+    };
+    use futures::executor::block_on;
+    use futures::future::{select, Either};
+
+    fn get(
+        transcoder: &Transcoder,
+        accept: Option<ContentFormat>,
+    ) -> (MsgCode, Option<ContentFormat>, Vec<u8>) {
+        let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+        let request = CoapRequest::get().add_option_iter(option::ACCEPT, accept);
+        let future = local_endpoint.send(LoopbackSocketAddr::Unicast, request.emit_any_response());
+        let future_receive = local_endpoint.receive_loop(
+            |context: &DatagramRespondableInboundContext<LoopbackSocketAddr>| {
+                transcoder.respond(context, ContentFormat::TEXT_PLAIN_UTF8, b"abc")
+            },
+        );
+
+        let ret = match block_on(select(future, future_receive)) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((ret, _)) => ret.unwrap(),
+        };
+
+        (ret.msg_code(), ret.content_format(), ret.payload().to_vec())
+    }
+
+    #[test]
+    fn transcoder() {
+        let transcoder = Transcoder::new()
+            .with_codec(
+                ContentFormat::TEXT_PLAIN_UTF8,
+                ContentFormat::APPLICATION_OCTET_STREAM,
+                |payload| Ok(payload.iter().rev().cloned().collect()),
+            )
+            .with_codec(
+                ContentFormat::TEXT_PLAIN_UTF8,
+                ContentFormat::APPLICATION_CBOR,
+                |_| Err(Error::ParseFailure),
+            );
+
+        assert_eq!(
+            (
+                MsgCode::SuccessContent,
+                Some(ContentFormat::TEXT_PLAIN_UTF8),
+                b"abc".to_vec()
+            ),
+            get(&transcoder, None)
+        );
+
+        assert_eq!(
+            (
+                MsgCode::SuccessContent,
+                Some(ContentFormat::APPLICATION_OCTET_STREAM),
+                b"cba".to_vec()
+            ),
+            get(&transcoder, Some(ContentFormat::APPLICATION_OCTET_STREAM))
+        );
+
+        let (msg_code, _, _) = get(&transcoder, Some(ContentFormat::APPLICATION_JSON));
+        assert_eq!(MsgCode::ClientErrorNotAcceptable, msg_code);
+
+        let (msg_code, _, _) = get(&transcoder, Some(ContentFormat::APPLICATION_CBOR));
+        assert_eq!(MsgCode::ServerErrorInternalServerError, msg_code);
+    }
+}
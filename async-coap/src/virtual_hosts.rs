@@ -0,0 +1,198 @@
+// Copyright 2019 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use super::*;
+use std::collections::HashMap;
+
+type VirtualHostHandler<T> = Box<dyn Fn(&T) -> Result<(), Error> + Send + Sync>;
+
+/// Dispatches inbound requests to different handlers depending on the `Uri-Host` option
+/// of the request, allowing a single local endpoint to serve several logical hosts which
+/// each have their own resources.
+///
+/// Host names are compared case-insensitively. Requests without a `Uri-Host` option are
+/// directed at the host identified by the destination IP address ([IETF-RFC7252 Section
+/// 5.10.1]), so they are passed to the default handler along with requests for any host
+/// which hasn't been added. If there is no default handler, those requests are answered
+/// with `4.04 Not Found`.
+///
+/// ```
+/// # use async_coap::prelude::*;
+/// # use async_coap::datagram::*;
+/// # use async_coap::{Error, RespondableInboundContext, VirtualHosts};
+/// # let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+/// let virtual_hosts = VirtualHosts::new()
+///     .with_host("device-1.example.com", |context: &DatagramRespondableInboundContext<_>| {
+///         context.respond(|msg_out| {
+///             msg_out.set_msg_code(MsgCode::SuccessContent);
+///             msg_out.append_payload_string("device-1")
+///         })
+///     })
+///     .with_default(|context: &DatagramRespondableInboundContext<_>| {
+///         context.respond(|msg_out| {
+///             msg_out.set_msg_code(MsgCode::SuccessContent);
+///             msg_out.append_payload_string("gateway")
+///         })
+///     });
+///
+/// # let _ =
+/// local_endpoint.receive_loop(|context| virtual_hosts.handle(context));
+/// ```
+///
+/// [IETF-RFC7252 Section 5.10.1]: https://tools.ietf.org/html/rfc7252#section-5.10.1
+pub struct VirtualHosts<T> {
+    hosts: HashMap<String, VirtualHostHandler<T>>,
+    default: Option<VirtualHostHandler<T>>,
+}
+
+impl<T> std::fmt::Debug for VirtualHosts<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VirtualHosts")
+            .field("hosts", &self.hosts.keys().collect::<Vec<_>>())
+            .field("default", &self.default.is_some())
+            .finish()
+    }
+}
+
+impl<T> Default for VirtualHosts<T> {
+    fn default() -> Self {
+        VirtualHosts {
+            hosts: HashMap::new(),
+            default: None,
+        }
+    }
+}
+
+impl<T: RespondableInboundContext> VirtualHosts<T> {
+    /// Creates a new `VirtualHosts` instance without any hosts or default handler.
+    pub fn new() -> VirtualHosts<T> {
+        Default::default()
+    }
+
+    /// Adds a handler for requests whose `Uri-Host` option matches `host`, replacing any
+    /// handler previously added for the same host.
+    pub fn with_host<S, F>(mut self, host: S, handler: F) -> VirtualHosts<T>
+    where
+        S: AsRef<str>,
+        F: Fn(&T) -> Result<(), Error> + Send + Sync + 'static,
+    {
+        self.hosts
+            .insert(host.as_ref().to_ascii_lowercase(), Box::new(handler));
+        self
+    }
+
+    /// Sets the handler for requests without a `Uri-Host` option, or for hosts
+    /// which haven't been added using [`VirtualHosts::with_host`].
+    pub fn with_default<F>(mut self, handler: F) -> VirtualHosts<T>
+    where
+        F: Fn(&T) -> Result<(), Error> + Send + Sync + 'static,
+    {
+        self.default = Some(Box::new(handler));
+        self
+    }
+
+    /// Returns true if a handler was added for `host`.
+    pub fn contains_host(&self, host: &str) -> bool {
+        self.hosts.contains_key(&host.to_ascii_lowercase())
+    }
+
+    /// Passes the inbound request to the handler for the host it is directed at.
+    ///
+    /// This method is intended to be called from the handler passed to
+    /// [`LocalEndpoint::receive`].
+    pub fn handle(&self, context: &T) -> Result<(), Error> {
+        let host = context
+            .message()
+            .options()
+            .find_next_of(option::URI_HOST)
+            .transpose()?;
+
+        let handler = host
+            .and_then(|host| self.hosts.get(&host.to_ascii_lowercase()))
+            .or(self.default.as_ref());
+
+        match handler {
+            Some(handler) => handler(context),
+            None => context.respond_not_found(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datagram::{
+        DatagramLocalEndpoint, DatagramRespondableInboundContext, LoopbackSocket,
+        LoopbackSocketAddr,
+    };
+    use futures::executor::block_on;
+    use futures::future::{select, Either};
+
+    type Context = DatagramRespondableInboundContext<LoopbackSocketAddr>;
+
+    fn respond_with(name: &'static str) -> impl Fn(&Context) -> Result<(), Error> {
+        move |context| {
+            context.respond(|msg_out| {
+                msg_out.set_msg_code(MsgCode::SuccessContent);
+                msg_out.append_payload_string(name)
+            })
+        }
+    }
+
+    fn get(virtual_hosts: &VirtualHosts<Context>, host: Option<&str>) -> (MsgCode, String) {
+        let local_endpoint = DatagramLocalEndpoint::new(LoopbackSocket::new());
+        let request = CoapRequest::get();
+        let future = match host {
+            Some(host) => local_endpoint.send(
+                LoopbackSocketAddr::Unicast,
+                request.uri_host(host).emit_any_response(),
+            ),
+            None => local_endpoint.send(LoopbackSocketAddr::Unicast, request.emit_any_response()),
+        };
+        let future_receive = local_endpoint.receive_loop(|context| virtual_hosts.handle(context));
+
+        let ret = match block_on(select(future, future_receive)) {
+            Either::Right(_) => panic!("Receive future finished unexpectedly"),
+            Either::Left((ret, _)) => ret.unwrap(),
+        };
+
+        (ret.msg_code(), ret.payload_as_str().unwrap().to_string())
+    }
+
+    #[test]
+    fn virtual_hosts() {
+        let virtual_hosts = VirtualHosts::new()
+            .with_host("a.example.com", respond_with("a"))
+            .with_host("B.example.com", respond_with("b"));
+
+        assert!(virtual_hosts.contains_host("b.EXAMPLE.com"));
+        assert!(!virtual_hosts.contains_host("c.example.com"));
+
+        let ok = |name: &str| (MsgCode::SuccessContent, name.to_string());
+        let not_found = (MsgCode::ClientErrorNotFound, String::new());
+
+        assert_eq!(get(&virtual_hosts, Some("a.example.com")), ok("a"));
+        assert_eq!(get(&virtual_hosts, Some("b.example.com")), ok("b"));
+        assert_eq!(get(&virtual_hosts, Some("A.Example.Com")), ok("a"));
+        assert_eq!(get(&virtual_hosts, Some("c.example.com")), not_found);
+        assert_eq!(get(&virtual_hosts, None), not_found);
+
+        let virtual_hosts = virtual_hosts.with_default(respond_with("default"));
+
+        assert_eq!(get(&virtual_hosts, Some("a.example.com")), ok("a"));
+        assert_eq!(get(&virtual_hosts, Some("c.example.com")), ok("default"));
+        assert_eq!(get(&virtual_hosts, None), ok("default"));
+    }
+}